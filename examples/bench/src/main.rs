@@ -0,0 +1,196 @@
+//! A load generator for rpcx servers.
+//!
+//! Start a server with an echo service, `rpcx-bench -serve -addr 0.0.0.0:8972`, and drive it
+//! from another host with `rpcx-bench -addr <host>:8972 -c 100 -n 1000000 -s 581`. Any server
+//! which echoes the payload of `Echo.Echo` can be the target.
+
+use std::{
+    collections::HashMap,
+    env, process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use rpcx::*;
+
+#[derive(Debug)]
+struct Args {
+    addr: String,
+    serve: bool,
+    concurrency: u64,
+    total: u64,
+    payload_size: usize,
+    service_path: String,
+    service_method: String,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            addr: "127.0.0.1:8972".to_owned(),
+            serve: false,
+            concurrency: 100,
+            total: 100_000,
+            payload_size: 581,
+            service_path: "Echo".to_owned(),
+            service_method: "Echo".to_owned(),
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: rpcx-bench [-serve] [-addr host:port] [-c concurrency] [-n requests] \
+         [-s payload size] [-service path.method]"
+    );
+    process::exit(2)
+}
+
+fn parse_args() -> Args {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        if flag == "-serve" {
+            args.serve = true;
+            continue;
+        }
+        let value = iter.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "-addr" => args.addr = value,
+            "-c" => args.concurrency = value.parse().unwrap_or_else(|_| usage()),
+            "-n" => args.total = value.parse().unwrap_or_else(|_| usage()),
+            "-s" => args.payload_size = value.parse().unwrap_or_else(|_| usage()),
+            "-service" => {
+                let mut items = value.splitn(2, '.');
+                args.service_path = items.next().unwrap_or_default().to_owned();
+                args.service_method = items.next().unwrap_or_else(|| usage()).to_owned();
+            }
+            _ => usage(),
+        }
+    }
+    if args.concurrency == 0 {
+        usage();
+    }
+    args
+}
+
+fn serve(args: &Args) {
+    let mut rpc_server = Server::new(args.addr.clone(), 0);
+    let echo: RpcxFn = |x, _| Ok(x.to_vec());
+    rpc_server.register_fn(
+        args.service_path.clone(),
+        args.service_method.clone(),
+        "".to_owned(),
+        echo,
+    );
+    if let Err(err) = rpc_server.start() {
+        eprintln!("failed to start the server: {}", err);
+        process::exit(1);
+    }
+}
+
+// the latencies of the calls made by a worker, and the number of failed calls.
+struct Report {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+fn run(args: Args) {
+    let args = Arc::new(args);
+    // workers take requests from the shared counter until `total` are sent
+    let sent = Arc::new(AtomicU64::new(0));
+    let payload = Bytes::from(vec![b'a'; args.payload_size]);
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| {
+            let args = args.clone();
+            let sent = sent.clone();
+            let payload = payload.clone();
+            thread::spawn(move || {
+                let mut client = Client::new(&args.addr);
+                client.opt.serialize_type = SerializeType::SerializeNone;
+                if let Err(err) = client.start() {
+                    eprintln!("failed to connect to {}: {}", args.addr, err);
+                    process::exit(1);
+                }
+
+                let metadata = HashMap::new();
+                let mut report = Report {
+                    latencies: Vec::new(),
+                    errors: 0,
+                };
+                while sent.fetch_add(1, Ordering::Relaxed) < args.total {
+                    let begin = Instant::now();
+                    let reply: Option<Result<Bytes>> = client.call(
+                        &args.service_path,
+                        &args.service_method,
+                        false,
+                        &metadata,
+                        &payload,
+                    );
+                    match reply {
+                        Some(Ok(_)) => report.latencies.push(begin.elapsed()),
+                        _ => report.errors += 1,
+                    }
+                }
+                report
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(args.total as usize);
+    let mut errors = 0;
+    for worker in workers {
+        let report = worker.join().unwrap();
+        latencies.extend(report.latencies);
+        errors += report.errors;
+    }
+    let elapsed = start.elapsed();
+
+    latencies.sort();
+    // the latency below which the given per mille of the calls completed
+    let percentile = |per_mille: usize| {
+        let index = (latencies.len() * per_mille / 1000).min(latencies.len().saturating_sub(1));
+        latencies.get(index).cloned().unwrap_or_default()
+    };
+    let mean = if latencies.is_empty() {
+        Duration::default()
+    } else {
+        latencies.iter().sum::<Duration>() / latencies.len() as u32
+    };
+
+    println!(
+        "concurrency: {}, requests: {}, payload: {} bytes",
+        args.concurrency, args.total, args.payload_size
+    );
+    println!(
+        "succeeded: {}, failed: {}, took: {:?}, throughput: {:.0} calls/s",
+        latencies.len(),
+        errors,
+        elapsed,
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "latency mean: {:?}, p50: {:?}, p99: {:?}, p999: {:?}, max: {:?}",
+        mean,
+        percentile(500),
+        percentile(990),
+        percentile(999),
+        percentile(1000)
+    );
+}
+
+fn main() {
+    let args = parse_args();
+    if args.serve {
+        serve(&args);
+    } else {
+        run(args);
+    }
+}
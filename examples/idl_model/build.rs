@@ -0,0 +1,3 @@
+fn main() {
+    rpcx_build::compile("arith.idl").unwrap();
+}
@@ -0,0 +1,7 @@
+//! the model of `mul_model` generated from `arith.idl` by `rpcx_build`.
+
+mod arith {
+    include!(concat!(env!("OUT_DIR"), "/arith.rs"));
+}
+
+pub use arith::*;
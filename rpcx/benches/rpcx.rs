@@ -0,0 +1,135 @@
+use std::{collections::HashMap, net::TcpListener, thread};
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rpcx::*;
+
+const PAYLOAD_SIZE: usize = 1024;
+
+fn new_message() -> Message {
+    let mut msg = Message::new();
+    msg.set_version(0);
+    msg.set_message_type(MessageType::Request);
+    msg.set_serialize_type(SerializeType::SerializeNone);
+    msg.set_compress_type(CompressType::CompressNone);
+    msg.set_seq(1);
+    msg.service_path = "Echo".to_owned();
+    msg.service_method = "Echo".to_owned();
+    msg.metadata.borrow_mut().insert(
+        "__ID".to_owned(),
+        "6ba7b810-9dad-11d1-80b4-00c04fd430c9".to_owned(),
+    );
+    msg.payload = Bytes::from(vec![b'a'; PAYLOAD_SIZE]);
+    msg
+}
+
+fn bench_codec(c: &mut Criterion) {
+    let msg = new_message();
+    c.bench_function("encode", |b| {
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            msg.encode_to(&mut buf);
+        })
+    });
+
+    let data = msg.encode();
+    c.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut msg = Message::new();
+            msg.decode(&mut data.as_slice()).unwrap();
+        })
+    });
+
+    // the metadata of a traced and authenticated call
+    let mut msg = new_message();
+    msg.payload = Bytes::new();
+    for i in 0..8 {
+        msg.metadata.get_mut().insert(
+            format!("X-Metadata-{}", i),
+            "6ba7b810-9dad-11d1-80b4-00c04fd430c9".to_owned(),
+        );
+    }
+    c.bench_function("encode metadata", |b| {
+        let mut buf = Vec::new();
+        b.iter(|| {
+            buf.clear();
+            msg.encode_to(&mut buf);
+        })
+    });
+
+    let data = msg.encode();
+    c.bench_function("decode metadata", |b| {
+        b.iter(|| {
+            let mut msg = Message::new();
+            msg.decode(&mut data.as_slice()).unwrap();
+        })
+    });
+
+    // the metadata is copied into a map once it is borrowed, such as by the server for handlers
+    c.bench_function("decode and borrow metadata", |b| {
+        b.iter(|| {
+            let mut msg = Message::new();
+            msg.decode(&mut data.as_slice()).unwrap();
+            assert_eq!(9, msg.metadata.borrow().len());
+        })
+    });
+}
+
+fn bench_call(c: &mut Criterion) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let mut rpc_server = Server::new(addr.clone(), 0);
+    let echo: RpcxFn = |x, _| Ok(x.to_vec());
+    rpc_server.register_fn("Echo".to_owned(), "Echo".to_owned(), "".to_owned(), echo);
+    thread::spawn(move || rpc_server.start_with_listener(listener));
+
+    let mut client = Client::new(&addr);
+    client.opt.serialize_type = SerializeType::SerializeNone;
+    client.start().unwrap();
+
+    let metadata = HashMap::new();
+    let args = Bytes::from(vec![b'a'; PAYLOAD_SIZE]);
+    c.bench_function("loopback call", |b| {
+        b.iter(|| {
+            let reply: Option<Result<Bytes>> = client.call("Echo", "Echo", false, &metadata, &args);
+            reply.unwrap().unwrap()
+        })
+    });
+}
+
+fn bench_selector(c: &mut Criterion) {
+    let mut servers = HashMap::new();
+    for i in 0..10 {
+        servers.insert(format!("tcp@127.0.0.1:{}", 8972 + i), "weight=1".to_owned());
+    }
+    let args = Bytes::from(&b"hello"[..]);
+
+    let mut random = RandomSelector::new();
+    random.update_server(&servers);
+    c.bench_function("random selector", |b| {
+        b.iter(|| random.select("Echo", "Echo", &args))
+    });
+
+    let mut round_robin = RoundbinSelector::new();
+    round_robin.update_server(&servers);
+    c.bench_function("round robin selector", |b| {
+        b.iter(|| round_robin.select("Echo", "Echo", &args))
+    });
+
+    let mut weighted = WeightedSelector::new();
+    weighted.update_server(&servers);
+    c.bench_function("weighted selector", |b| {
+        b.iter(|| weighted.select("Echo", "Echo", &args))
+    });
+
+    let mut consistent_hash = ConsistentHashSelector::new();
+    consistent_hash.update_server(&servers);
+    c.bench_function("consistent hash selector", |b| {
+        b.iter(|| consistent_hash.select("Echo", "Echo", &args))
+    });
+}
+
+criterion_group!(benches, bench_codec, bench_call, bench_selector);
+criterion_main!(benches);
@@ -1,4 +1,24 @@
+//! the rpcx framework. The clients and the server are the `client` and `server` features,
+//! both enabled by default, so a crate which only calls services is built without the server:
+//!
+//! ```toml
+//! rpcx = { version = "0.2", default-features = false, features = ["client"] }
+//! ```
+//!
+//! The registries, the transports, the compressors and the ciphers are features as well, all
+//! enabled by default and forwarded to the clients and the server which are built:
+//! `etcd-registry`, `eureka-registry`, `http-gateway`, `grpc`, `tracing`, `gzip`,
+//! `stream-compression`, `crypto` and `tls`.
+//!
+//! The `testing` feature, which is not enabled by default, adds `testing::TestCluster` for
+//! the integration tests of services.
+
+#[cfg(feature = "client")]
 pub use rpcx_client::*;
 pub use rpcx_derive::*;
 pub use rpcx_protocol::*;
+#[cfg(feature = "server")]
 pub use rpcx_server::*;
+
+#[cfg(feature = "testing")]
+pub mod testing;
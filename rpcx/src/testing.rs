@@ -0,0 +1,103 @@
+//! helpers for the integration tests of rpcx services.
+
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use rpcx_client::{ClientSelector, FailMode, Opt, RoundbinSelector, XClient};
+use rpcx_protocol::Result;
+use rpcx_server::Server;
+
+// a server of the cluster and its accept loop.
+struct Node {
+    server: Arc<Server>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// starts servers on ephemeral ports of the loopback interface and builds `XClient`s to call
+/// them. The servers are stopped when the cluster is dropped. It is the `testing` feature.
+///
+/// ```no_run
+/// use rpcx::{testing::TestCluster, *};
+///
+/// let cluster = TestCluster::start(3, |server| {
+///     let echo: RpcxFn = |x, _| Ok(x.to_vec());
+///     server.register_fn("Echo".to_owned(), "Echo".to_owned(), "".to_owned(), echo);
+/// })
+/// .unwrap();
+/// let mut xc = cluster.xclient("Echo", FailMode::Failover);
+/// ```
+pub struct TestCluster {
+    nodes: Vec<Node>,
+}
+
+impl TestCluster {
+    /// starts `n` servers, `setup` registers the services of each of them.
+    pub fn start<F>(n: usize, setup: F) -> Result<Self>
+    where
+        F: Fn(&mut Server),
+    {
+        let mut nodes = Vec::with_capacity(n);
+        for _ in 0..n {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let mut server = Server::new(listener.local_addr()?.to_string(), 0);
+            setup(&mut server);
+
+            let server = Arc::new(server);
+            let server_cloned = server.clone();
+            let handle = thread::spawn(move || {
+                let _ = server_cloned.start_with_listener(listener);
+            });
+            nodes.push(Node {
+                server,
+                handle: Some(handle),
+            });
+        }
+        Ok(TestCluster { nodes })
+    }
+
+    pub fn servers(&self) -> Vec<Arc<Server>> {
+        self.nodes.iter().map(|node| node.server.clone()).collect()
+    }
+
+    /// returns the addresses of the servers in the `tcp@host:port` form of selectors.
+    pub fn addrs(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .map(|node| format!("tcp@{}", node.server.addr))
+            .collect()
+    }
+
+    /// builds a client of the service which selects the servers of the cluster by round robin.
+    pub fn xclient(&self, service_path: &str, fail_mode: FailMode) -> XClient<RoundbinSelector> {
+        let servers: HashMap<String, String> = self
+            .addrs()
+            .into_iter()
+            .map(|addr| (addr, String::new()))
+            .collect();
+        // what a static discovery would feed the selector
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+
+        XClient::new(
+            service_path.to_owned(),
+            fail_mode,
+            Box::new(selector),
+            Opt::default(),
+        )
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        for node in &mut self.nodes {
+            node.server.close();
+            if let Some(handle) = node.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use super::idl::{Idl, Service, Struct};
+use std::fmt::Write;
+
+// the imports the generated code and the `RpcxParam` derive depend on.
+const PRELUDE: &str = "#[allow(unused_imports)]
+use std::error::Error as StdError;
+
+use rmp_serde as rmps;
+use rpcx::*;
+use serde::{Deserialize, Serialize};
+";
+
+/// converts a method name such as `GetUser` or `HTTPGet` to `get_user` or `http_get`.
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = matches!(chars.get(i + 1), Some(c) if c.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn gen_struct(out: &mut String, s: &Struct) {
+    out.push_str(
+        "\n#[derive(RpcxParam, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]\n",
+    );
+    writeln!(out, "pub struct {} {{", s.name).unwrap();
+    for field in &s.fields {
+        if let Some(rename) = &field.rename {
+            writeln!(out, "    #[serde(rename = {:?})]", rename).unwrap();
+        }
+        writeln!(out, "    pub {}: {},", field.name, field.ty).unwrap();
+    }
+    out.push_str("}\n");
+}
+
+// the trait implemented by the servers and the function registering its methods.
+fn gen_server(out: &mut String, service: &Service) {
+    writeln!(out, "\n/// the methods of the `{}` service.", service.name).unwrap();
+    writeln!(out, "pub trait {} {{", service.name).unwrap();
+    for m in &service.methods {
+        writeln!(
+            out,
+            "    fn {}(args: {}) -> {};",
+            snake_case(&m.name),
+            m.args,
+            m.reply
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+
+    writeln!(
+        out,
+        "\n/// registers the methods of the `{}` service implemented by `S`.",
+        service.name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn register_{}<S: {}>(rpc_server: &mut Server, meta: String) {{",
+        snake_case(&service.name),
+        service.name
+    )
+    .unwrap();
+    for m in &service.methods {
+        writeln!(out, "    let f: RpcxFn = |x, st| {{").unwrap();
+        writeln!(
+            out,
+            "        let mut args: {} = Default::default();",
+            m.args
+        )
+        .unwrap();
+        writeln!(out, "        args.from_slice(st, x)?;").unwrap();
+        writeln!(
+            out,
+            "        let reply: {} = S::{}(args);",
+            m.reply,
+            snake_case(&m.name)
+        )
+        .unwrap();
+        writeln!(out, "        reply.into_bytes(st)").unwrap();
+        writeln!(out, "    }};").unwrap();
+        writeln!(
+            out,
+            "    rpc_server.register_fn({:?}.to_owned(), {:?}.to_owned(), meta.clone(), f);",
+            service.name, m.name
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+}
+
+// the typed client calling the service through a `RpcxClient`.
+fn gen_client(out: &mut String, service: &Service) {
+    let client = format!("{}Client", service.name);
+    writeln!(
+        out,
+        "\n/// a typed client of the `{}` service, `C` is a `XClient` or a `MockClient` of it.",
+        service.name
+    )
+    .unwrap();
+    writeln!(out, "pub struct {}<C> {{\n    pub client: C,\n}}", client).unwrap();
+    writeln!(out, "\nimpl<C: RpcxClient> {}<C> {{", client).unwrap();
+    writeln!(out, "    pub fn new(client: C) -> Self {{").unwrap();
+    writeln!(out, "        {} {{ client }}\n    }}", client).unwrap();
+    for m in &service.methods {
+        let method = snake_case(&m.name);
+        writeln!(
+            out,
+            "\n    pub fn {}(&mut self, metadata: &Metadata, args: &{}) -> Result<{}> {{",
+            method, m.args, m.reply
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        self.client\n            .call({:?}, false, metadata, args)",
+            m.name
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "            .unwrap_or_else(|| Err(Error::new(ErrorKind::Client, \"no reply\")))\n    }}"
+        )
+        .unwrap();
+
+        writeln!(
+            out,
+            "\n    pub fn {}_async(\n        &mut self,\n        metadata: &Metadata,\n        args: &{},",
+            method, m.args
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    ) -> Box<dyn futures::Future<Item = Result<{}>, Error = Error> + Send + Sync> {{",
+            m.reply
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        self.client.acall({:?}, metadata, args)\n    }}",
+            m.name
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+}
+
+/// generates the Rust source of the IDL, see `compile` for its dependencies.
+pub fn generate(idl: &Idl, source_name: &str) -> String {
+    let mut out = format!(
+        "// generated by rpcx-build from {}, do not edit.\n\n{}",
+        source_name, PRELUDE
+    );
+    for s in &idl.structs {
+        gen_struct(&mut out, s);
+    }
+    for service in &idl.services {
+        gen_server(&mut out, service);
+        gen_client(&mut out, service);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snake_case_names() {
+        assert_eq!("mul", snake_case("Mul"));
+        assert_eq!("get_user", snake_case("GetUser"));
+        assert_eq!("get_user2_by_id", snake_case("GetUser2ById"));
+        assert_eq!("http_get", snake_case("HTTPGet"));
+    }
+
+    #[test]
+    fn generate_service() {
+        let idl = crate::parse(
+            r#"
+            struct Args { a: u64 as "A"; }
+            struct Reply { c: u64; }
+            service Arith { Mul(Args) -> Reply; }
+            "#,
+        )
+        .unwrap();
+        let code = generate(&idl, "arith.idl");
+        assert!(code.starts_with("// generated by rpcx-build from arith.idl"));
+        assert!(code.contains("    #[serde(rename = \"A\")]\n    pub a: u64,\n"));
+        assert!(code.contains("    fn mul(args: Args) -> Reply;\n"));
+        assert!(code.contains("pub fn register_arith<S: Arith>(rpc_server: &mut Server"));
+        assert!(code.contains("rpc_server.register_fn(\"Arith\".to_owned(), \"Mul\".to_owned()"));
+        assert!(code.contains("    pub fn mul(&mut self, metadata: &Metadata, args: &Args)"));
+        assert!(code.contains("    pub fn mul_async("));
+    }
+}
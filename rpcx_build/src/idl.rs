@@ -0,0 +1,335 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// a struct of the IDL, used as the argument or the reply of methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+/// a field of a struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// the Rust type of the field, such as `Vec<u64>`.
+    pub ty: String,
+    /// the name of the field in the payloads, such as `A` to interoperate with Go services.
+    pub rename: Option<String>,
+}
+
+/// a service and its methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    pub methods: Vec<Method>,
+}
+
+/// a method of a service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub args: String,
+    pub reply: String,
+}
+
+/// the structs and services of an IDL file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Idl {
+    pub structs: Vec<Struct>,
+    pub services: Vec<Service>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Punct(&'static str),
+}
+
+// the punctuations of the IDL, the longest first.
+const PUNCTS: &[&str] = &["->", "{", "}", "(", ")", ";", ":", "<", ">", ",", "[", "]"];
+
+fn syntax_error(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, msg))
+}
+
+// splits the source into tokens and their lines, skipping the `//` comments.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line.trim_start();
+        while !rest.is_empty() && !rest.starts_with("//") {
+            if let Some(&p) = PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                tokens.push((Token::Punct(p), line_no));
+                rest = &rest[p.len()..];
+            } else if rest.starts_with('"') {
+                let end = rest[1..]
+                    .find('"')
+                    .ok_or_else(|| syntax_error(line_no, "unterminated string"))?;
+                tokens.push((Token::Str(rest[1..=end].to_owned()), line_no));
+                rest = &rest[end + 2..];
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    let c = rest.chars().next().unwrap();
+                    return Err(syntax_error(line_no, &format!("unexpected {:?}", c)));
+                }
+                tokens.push((Token::Ident(rest[..end].to_owned()), line_no));
+                rest = &rest[end..];
+            }
+            rest = rest.trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| syntax_error(self.line(), "unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            token => Err(syntax_error(
+                line,
+                &format!("expected a name, found {:?}", token),
+            )),
+        }
+    }
+
+    fn punct(&mut self, punct: &str) -> Result<()> {
+        let line = self.line();
+        match self.next()? {
+            Token::Punct(p) if p == punct => Ok(()),
+            token => Err(syntax_error(
+                line,
+                &format!("expected `{}`, found {:?}", punct, token),
+            )),
+        }
+    }
+
+    // skips the punctuation if it is the next token.
+    fn eat(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(p)) if *p == punct => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // a type is kept as Rust source, up to `as` or `;` outside of brackets.
+    fn ty(&mut self) -> Result<String> {
+        let mut ty = String::new();
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                Some(Token::Punct(";")) if depth == 0 => break,
+                Some(Token::Ident(ident)) if ident == "as" && depth == 0 => break,
+                None => break,
+                _ => {}
+            }
+            let line = self.line();
+            match self.next()? {
+                Token::Ident(ident) => ty.push_str(&ident),
+                Token::Punct(p) => {
+                    match p {
+                        "<" | "[" | "(" => depth += 1,
+                        ">" | "]" | ")" => depth -= 1,
+                        _ => {}
+                    }
+                    ty.push_str(p);
+                    if p == "," {
+                        ty.push(' ');
+                    }
+                }
+                token => {
+                    return Err(syntax_error(
+                        line,
+                        &format!("unexpected {:?} in a type", token),
+                    ))
+                }
+            }
+        }
+        if ty.is_empty() {
+            return Err(syntax_error(self.line(), "expected a type"));
+        }
+        Ok(ty)
+    }
+
+    fn parse_struct(&mut self) -> Result<Struct> {
+        let name = self.ident()?;
+        self.punct("{")?;
+        let mut fields = Vec::new();
+        while !self.eat("}") {
+            let name = self.ident()?;
+            self.punct(":")?;
+            let ty = self.ty()?;
+            let rename = match self.peek() {
+                Some(Token::Ident(ident)) if ident == "as" => {
+                    self.pos += 1;
+                    let line = self.line();
+                    match self.next()? {
+                        Token::Str(rename) => Some(rename),
+                        _ => return Err(syntax_error(line, "expected a quoted name")),
+                    }
+                }
+                _ => None,
+            };
+            self.punct(";")?;
+            fields.push(Field { name, ty, rename });
+        }
+        Ok(Struct { name, fields })
+    }
+
+    fn parse_service(&mut self) -> Result<Service> {
+        let name = self.ident()?;
+        self.punct("{")?;
+        let mut methods = Vec::new();
+        while !self.eat("}") {
+            let name = self.ident()?;
+            self.punct("(")?;
+            let args = self.ident()?;
+            self.punct(")")?;
+            self.punct("->")?;
+            let reply = self.ident()?;
+            self.punct(";")?;
+            methods.push(Method { name, args, reply });
+        }
+        Ok(Service { name, methods })
+    }
+}
+
+/// parses an IDL file:
+///
+/// ```text
+/// // the argument of the methods, `as` sets the name of a field in the payloads
+/// struct ArithAddArgs {
+///     a: u64 as "A";
+///     b: u64 as "B";
+/// }
+///
+/// struct ArithAddReply {
+///     c: u64 as "C";
+/// }
+///
+/// service Arith {
+///     Add(ArithAddArgs) -> ArithAddReply;
+///     Mul(ArithAddArgs) -> ArithAddReply;
+/// }
+/// ```
+pub fn parse(source: &str) -> Result<Idl> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    let mut idl = Idl::default();
+    while parser.peek().is_some() {
+        let line = parser.line();
+        match parser.ident()?.as_str() {
+            "struct" => idl.structs.push(parser.parse_struct()?),
+            "service" => idl.services.push(parser.parse_service()?),
+            other => {
+                return Err(syntax_error(
+                    line,
+                    &format!("expected `struct` or `service`, found `{}`", other),
+                ))
+            }
+        }
+    }
+
+    for service in &idl.services {
+        for method in &service.methods {
+            for ty in &[&method.args, &method.reply] {
+                if !idl.structs.iter().any(|s| &s.name == *ty) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{}.{}: unknown struct {}", service.name, method.name, ty),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(idl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_idl() {
+        let idl = parse(
+            r#"
+            // arith
+            struct Args {
+                a: u64 as "A";
+                tags: HashMap<String, Vec<u8>>;
+            }
+            struct Reply { c: Option<u64>; }
+            service Arith {
+                Mul(Args) -> Reply;
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(2, idl.structs.len());
+        assert_eq!(
+            Field {
+                name: "a".to_owned(),
+                ty: "u64".to_owned(),
+                rename: Some("A".to_owned()),
+            },
+            idl.structs[0].fields[0]
+        );
+        assert_eq!("HashMap<String, Vec<u8>>", idl.structs[0].fields[1].ty);
+        assert_eq!("Option<u64>", idl.structs[1].fields[0].ty);
+        assert_eq!(
+            Method {
+                name: "Mul".to_owned(),
+                args: "Args".to_owned(),
+                reply: "Reply".to_owned(),
+            },
+            idl.services[0].methods[0]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = parse("struct Args {\n a u64;\n}").unwrap_err();
+        assert_eq!(
+            "line 2: expected `:`, found Ident(\"u64\")",
+            err.to_string()
+        );
+        let err = parse("service Arith { Mul(Args) -> Reply; }").unwrap_err();
+        assert_eq!("Arith.Mul: unknown struct Args", err.to_string());
+        assert!(parse("struct Args { a: u64 as \"A; }").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! generates the arguments and replies, the server registration and the typed client stubs of
+//! rpcx services described by an IDL file, see `idl::parse` for its syntax.
+//!
+//! It is called by the `main` of the `build.rs` of the crate of the model:
+//!
+//! ```no_run
+//! rpcx_build::compile("arith.idl").unwrap();
+//! ```
+//!
+//! and the generated code is included in a module of it:
+//!
+//! ```ignore
+//! mod arith {
+//!     include!(concat!(env!("OUT_DIR"), "/arith.rs"));
+//! }
+//! pub use arith::*;
+//! ```
+//!
+//! The crate depends on `rpcx`, `serde` with the `derive` feature, `serde_json`, `rmp-serde`
+//! and `futures`, like the hand-written models. For a service `Arith`, the servers implement
+//! the trait `Arith` and register it by `register_arith::<S>(&mut server, meta)`, the clients
+//! call it by `ArithClient::new(xclient)`.
+
+mod gen;
+pub mod idl;
+
+pub use gen::{generate, snake_case};
+pub use idl::parse;
+
+use std::{
+    env, fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+/// generates the code of the IDL file to `$OUT_DIR/<name>.rs`, for build scripts. Cargo reruns
+/// the build script when the file changes.
+pub fn compile<P: AsRef<Path>>(idl: P) -> Result<PathBuf> {
+    let out_dir = env::var_os("OUT_DIR")
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "OUT_DIR is not set"))?;
+    println!("cargo:rerun-if-changed={}", idl.as_ref().display());
+    compile_to(idl, out_dir)
+}
+
+/// generates the code of the IDL file to `<out_dir>/<name>.rs` and returns its path.
+pub fn compile_to<P: AsRef<Path>, Q: AsRef<Path>>(idl: P, out_dir: Q) -> Result<PathBuf> {
+    let idl = idl.as_ref();
+    let source = fs::read_to_string(idl)?;
+    let parsed = parse(&source)
+        .map_err(|err| Error::new(err.kind(), format!("{}: {}", idl.display(), err)))?;
+    let name = idl
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = idl
+        .file_stem()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "the IDL path has no file name"))?;
+
+    let out = out_dir.as_ref().join(stem).with_extension("rs");
+    fs::write(&out, generate(&parsed, &name))?;
+    Ok(out)
+}
@@ -0,0 +1,38 @@
+use std::{env, process};
+
+// generates the code of IDL files out of build scripts, to check it in or to inspect it:
+//
+//     rpcx-build <idl>... [-o <out_dir>]
+fn main() {
+    let mut idls = Vec::new();
+    let mut out_dir = ".".to_owned();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => match args.next() {
+                Some(dir) => out_dir = dir,
+                None => usage(),
+            },
+            "-h" | "--help" => usage(),
+            _ => idls.push(arg),
+        }
+    }
+    if idls.is_empty() {
+        usage();
+    }
+
+    for idl in &idls {
+        match rpcx_build::compile_to(idl, &out_dir) {
+            Ok(out) => println!("generated {}", out.display()),
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: rpcx-build <idl>... [-o <out_dir>]");
+    process::exit(2);
+}
@@ -0,0 +1,120 @@
+//! lists the methods of rpcx servers and calls them with JSON arguments, the library of
+//! `rpcx-cli`.
+//!
+//! The servers are the static ones or the ones discovered from the registry of a
+//! `XClientConfig`, so the tool reaches a deployment the way its clients do.
+
+use bytes::Bytes;
+use rmp_serde as rmps;
+use rpcx_client::{Client, RpcxClient, XClient, XClientConfig};
+use rpcx_protocol::*;
+use serde_json::Value;
+
+/// returns the methods registered to the server at `addr`, such as `Arith.Mul`.
+pub fn list(addr: &str, config: &XClientConfig) -> Result<Vec<String>> {
+    let mut config = config.clone();
+    config.apply_env()?;
+
+    let mut client = Client::new(addr);
+    client.opt = config.opt();
+    client.start()?;
+    client.services()
+}
+
+/// calls `method`, `<service path>.<service method>`, with `args` in JSON and returns the reply
+/// in JSON. The payloads are converted to the serialize type of `config`.
+pub fn call(
+    config: &XClientConfig,
+    method: &str,
+    metadata: &Metadata,
+    args: &str,
+) -> Result<String> {
+    let (service_path, service_method) = split_method(method)?;
+    let mut config = config.clone();
+    config.service_path = service_path.to_owned();
+    config.apply_env()?;
+
+    let payload = encode_args(args, config.serialize_type)?;
+    let mut client = XClient::with_config(&config)?;
+    let reply: Bytes = client
+        .call(service_method, false, metadata, &Bytes::from(payload))
+        .unwrap_or_else(|| Err(Error::new(ErrorKind::Client, "no reply")))?;
+    decode_reply(&reply, config.serialize_type)
+}
+
+/// splits `Arith.Mul` into the service path and the service method.
+pub fn split_method(method: &str) -> Result<(&str, &str)> {
+    match method.rfind('.') {
+        Some(i) if i > 0 && i < method.len() - 1 => Ok((&method[..i], &method[i + 1..])),
+        _ => Err(Error::new(
+            ErrorKind::Client,
+            format!("{} is not <service path>.<service method>", method),
+        )),
+    }
+}
+
+/// converts JSON arguments to the payload of a request. The payload of `SerializeNone` is
+/// the arguments as they are.
+pub fn encode_args(args: &str, st: SerializeType) -> Result<Vec<u8>> {
+    let parse = || serde_json::from_str::<Value>(args);
+    match st {
+        SerializeType::SerializeNone => Ok(args.as_bytes().to_vec()),
+        SerializeType::JSON => Ok(serde_json::to_vec(&parse()?)?),
+        SerializeType::MsgPack => rmps::to_vec(&parse()?)
+            .map_err(|err| Error::new(ErrorKind::Serialization, err.to_string())),
+        st => Err(Error::new(
+            ErrorKind::Client,
+            format!("{} payloads can't be converted from JSON", st),
+        )),
+    }
+}
+
+/// converts the payload of a reply to pretty-printed JSON. The payload of other serialize
+/// types is returned as text.
+pub fn decode_reply(payload: &[u8], st: SerializeType) -> Result<String> {
+    let value: Value = match st {
+        SerializeType::JSON => serde_json::from_slice(payload)?,
+        SerializeType::MsgPack => rmps::from_slice(payload)
+            .map_err(|err| Error::new(ErrorKind::Serialization, err.to_string()))?,
+        _ => return Ok(String::from_utf8_lossy(payload).into_owned()),
+    };
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn methods() {
+        assert_eq!(("Arith", "Mul"), split_method("Arith.Mul").unwrap());
+        assert_eq!(("a.b", "Mul"), split_method("a.b.Mul").unwrap());
+        assert!(split_method("Arith").is_err());
+        assert!(split_method("Arith.").is_err());
+        assert!(split_method(".Mul").is_err());
+    }
+
+    #[test]
+    fn payloads() {
+        let args = r#"{ "A": 10, "B": 20 }"#;
+        let json = encode_args(args, SerializeType::JSON).unwrap();
+        assert_eq!(br#"{"A":10,"B":20}"#.to_vec(), json);
+        assert_eq!(
+            "{\n  \"A\": 10,\n  \"B\": 20\n}",
+            decode_reply(&json, SerializeType::JSON).unwrap()
+        );
+
+        let msgpack = encode_args(args, SerializeType::MsgPack).unwrap();
+        assert_eq!(
+            decode_reply(&json, SerializeType::JSON).unwrap(),
+            decode_reply(&msgpack, SerializeType::MsgPack).unwrap()
+        );
+
+        assert_eq!(
+            b"raw".to_vec(),
+            encode_args("raw", SerializeType::SerializeNone).unwrap()
+        );
+        assert!(encode_args("{", SerializeType::JSON).is_err());
+        assert!(encode_args("{}", SerializeType::Protobuf).is_err());
+    }
+}
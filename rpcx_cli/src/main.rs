@@ -0,0 +1,100 @@
+use std::{collections::HashMap, env, process, str::FromStr};
+
+use rpcx_client::XClientConfig;
+use rpcx_protocol::*;
+
+const USAGE: &str = "usage: rpcx-cli [options] list
+       rpcx-cli [options] call <service path>.<service method> [<json args>]
+
+options:
+    -a <host:port>     the server, 127.0.0.1:8972 by default
+    -r <endpoints>     discovers the servers from the comma separated etcd endpoints
+    -b <base path>     the base path of the registry, /rpcx by default
+    -s <type>          the serialize type, JSON by default, MsgPack or SerializeNone
+    -c <type>          the compress type, CompressNone by default or Gzip
+    -m <key=value>     adds metadata to the call, it can be repeated
+    -t <ms>            the timeout of the call";
+
+// lists the methods of a server or calls one of them:
+//
+//     rpcx-cli -a 127.0.0.1:8972 call Arith.Mul '{"A":10,"B":20}'
+fn main() {
+    let mut addr = "127.0.0.1:8972".to_owned();
+    let mut config = XClientConfig::default();
+    let mut metadata = HashMap::new();
+    let mut rest = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-a" => addr = value(),
+            "-r" => {
+                let registry = config.registry.get_or_insert_with(Default::default);
+                registry.addrs = value().split(',').map(str::to_owned).collect();
+            }
+            "-b" => {
+                let registry = config.registry.get_or_insert_with(Default::default);
+                registry.base_path = value();
+            }
+            "-s" => config.serialize_type = parse(&value()),
+            "-c" => config.compress_type = parse(&value()),
+            "-m" => {
+                let pair = value();
+                let mut kv = pair.splitn(2, '=');
+                let key = kv.next().unwrap_or_default().to_owned();
+                let v = kv.next().unwrap_or_else(|| usage()).to_owned();
+                metadata.insert(key, v);
+            }
+            "-t" => config.read_timeout_ms = parse(&value()),
+            "-h" | "--help" => usage(),
+            _ => rest.push(arg),
+        }
+    }
+    if config.registry.is_none() {
+        config
+            .servers
+            .insert(format!("tcp@{}", addr), String::new());
+    }
+
+    let rt = match rest
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["list"] => list(&addr, &config),
+        ["call", method] => rpcx_cli::call(&config, method, &metadata, "{}"),
+        ["call", method, args] => rpcx_cli::call(&config, method, &metadata, args),
+        _ => usage(),
+    };
+    match rt {
+        Ok(out) => println!("{}", out),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn list(addr: &str, config: &XClientConfig) -> Result<String> {
+    if config.registry.is_some() {
+        return Err(Error::new(
+            ErrorKind::Client,
+            "list asks a server by -a, not the registry",
+        ));
+    }
+    Ok(rpcx_cli::list(addr, config)?.join("\n"))
+}
+
+fn parse<T: FromStr>(value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("invalid value {}", value);
+        usage()
+    })
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,93 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Bucket {
+    second: u64,
+    requests: u64,
+    retries: u64,
+}
+
+/// limits retries to a percentage of the recent calls, so a client sheds retries instead of
+/// multiplying the load of servers which are browning out.
+///
+/// A budget can be shared by the clients of several services with `XClient::set_retry_budget`,
+/// the retries of all fail modes are counted.
+#[derive(Debug)]
+pub struct RetryBudget {
+    percent: u32,
+    min_retries: u64,
+    start: Instant,
+    // the counts of the seconds of the window, indexed by the second modulo its length
+    buckets: Mutex<Vec<Bucket>>,
+    shed: AtomicU64,
+}
+
+impl RetryBudget {
+    /// allows retries up to `percent` of the calls of the last `window`, and at least
+    /// `min_retries` in the window so clients with little traffic can still retry.
+    pub fn new(percent: u32, min_retries: u64, window: Duration) -> Self {
+        let secs = window.as_secs().max(1) as usize;
+        RetryBudget {
+            percent,
+            min_retries,
+            start: Instant::now(),
+            buckets: Mutex::new(vec![Default::default(); secs]),
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    // returns the bucket of the current second, it is reset if it is stale.
+    fn current(&self, buckets: &mut [Bucket]) -> usize {
+        let second = self.start.elapsed().as_secs();
+        let idx = second as usize % buckets.len();
+        if buckets[idx].second != second {
+            buckets[idx] = Bucket {
+                second,
+                requests: 0,
+                retries: 0,
+            };
+        }
+        idx
+    }
+
+    /// records a call, which is not a retry.
+    pub fn record_request(&self) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let idx = self.current(&mut buckets);
+        buckets[idx].requests += 1;
+    }
+
+    /// withdraws a retry from the budget, it returns false if the budget is exhausted and the
+    /// call should fail instead.
+    pub fn try_retry(&self) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        let idx = self.current(&mut buckets);
+        let second = buckets[idx].second;
+        let window = buckets.len() as u64;
+        let (requests, retries) = buckets
+            .iter()
+            .filter(|b| second - b.second < window)
+            .fold((0, 0), |(requests, retries), b| {
+                (requests + b.requests, retries + b.retries)
+            });
+
+        let allowed = (requests * u64::from(self.percent) / 100).max(self.min_retries);
+        if retries >= allowed {
+            self.shed.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        buckets[idx].retries += 1;
+        true
+    }
+
+    /// returns how many retries have been shed.
+    pub fn shed_retries(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use rpcx_protocol::{Metadata, DEADLINE, REQUEST_ID};
+
+use super::{ClientSelector, XClient};
+
+// the metadata which differs by call without changing the reply, left out of the keys.
+const UNKEYED_METADATA: [&str; 2] = [REQUEST_ID, DEADLINE];
+
+/// the hit statistics of the response cache of a method.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// the number of cached responses, including expired ones which are not evicted yet.
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// returns the ratio of calls served from the cache, 0 if there is no call.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+#[derive(Debug)]
+struct MethodCache {
+    ttl: Duration,
+    max_entries: usize,
+    // the keys of `cache_key` to the time the reply is cached and the serialized reply
+    entries: HashMap<Vec<u8>, (Instant, Vec<u8>)>,
+    // keys in insertion order, for evicting the oldest response. The keys cached again are
+    // queued again, the stale ones are compacted away.
+    order: VecDeque<(Vec<u8>, Instant)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl MethodCache {
+    fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let data = match self.entries.get(key) {
+            Some((cached_at, data)) if cached_at.elapsed() < self.ttl => Some(data.clone()),
+            _ => None,
+        };
+        match data {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        data
+    }
+
+    fn insert(&mut self, key: Vec<u8>, data: Vec<u8>) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        if !self.entries.contains_key(&key) {
+            while self.entries.len() >= self.max_entries {
+                self.evict_oldest();
+            }
+        }
+        self.order.push_back((key.clone(), now));
+        self.entries.insert(key, (now, data));
+        if self.order.len() > 2 * self.entries.len() {
+            let entries = &self.entries;
+            self.order
+                .retain(|(key, cached_at)| entries.get(key).map(|e| e.0) == Some(*cached_at));
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        while let Some((key, cached_at)) = self.order.pop_front() {
+            // skips the keys which are cached again after being queued
+            if self.entries.get(&key).map(|e| e.0) == Some(cached_at) {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+/// returns the key of the reply of a call, its metadata and its serialized args.
+pub(crate) fn cache_key(metadata: &Metadata, args: Vec<u8>) -> Vec<u8> {
+    let mut pairs: Vec<(&String, &String)> = metadata
+        .iter()
+        .filter(|(k, _)| !UNKEYED_METADATA.contains(&k.as_str()))
+        .collect();
+    pairs.sort();
+    let mut key = (pairs.len() as u32).to_be_bytes().to_vec();
+    for (k, v) in pairs {
+        for item in &[k, v] {
+            key.extend_from_slice(&(item.len() as u32).to_be_bytes());
+            key.extend_from_slice(item.as_bytes());
+        }
+    }
+    key.extend(args);
+    key
+}
+
+/// the cached responses of the idempotent methods of a service, keyed by their metadata and
+/// their serialized args.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseCache {
+    methods: RwLock<HashMap<String, Mutex<MethodCache>>>,
+}
+
+impl ResponseCache {
+    pub(crate) fn is_enabled(&self, service_method: &str) -> bool {
+        self.methods.read().unwrap().contains_key(service_method)
+    }
+
+    /// returns the serialized reply cached for the args, and records a hit or a miss.
+    pub(crate) fn get(&self, service_method: &str, key: &[u8]) -> Option<Vec<u8>> {
+        let methods = self.methods.read().unwrap();
+        let cache = methods.get(service_method)?;
+        let mut cache = cache.lock().unwrap();
+        cache.get(key)
+    }
+
+    pub(crate) fn insert(&self, service_method: &str, key: Vec<u8>, data: Vec<u8>) {
+        if let Some(cache) = self.methods.read().unwrap().get(service_method) {
+            cache.lock().unwrap().insert(key, data);
+        }
+    }
+}
+
+impl<S: ClientSelector> XClient<S> {
+    /// caches the replies of the method for `ttl`, keyed by the metadata and the serialized
+    /// args, so repeated calls with the same metadata and args are served without calling
+    /// servers. At most `max_entries` replies are cached, the oldest one is evicted first.
+    ///
+    /// Only idempotent methods should be cached. Oneway calls are never cached.
+    pub fn enable_cache(&mut self, service_method: &str, ttl: Duration, max_entries: usize) {
+        self.cache.methods.write().unwrap().insert(
+            service_method.to_owned(),
+            Mutex::new(MethodCache {
+                ttl,
+                max_entries,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+                hits: 0,
+                misses: 0,
+            }),
+        );
+    }
+
+    /// stops caching the replies of the method and drops its cached replies.
+    pub fn disable_cache(&mut self, service_method: &str) {
+        self.cache.methods.write().unwrap().remove(service_method);
+    }
+
+    /// returns the cache statistics of the method, `None` if its replies are not cached.
+    pub fn cache_stats(&self, service_method: &str) -> Option<CacheStats> {
+        let methods = self.cache.methods.read().unwrap();
+        let cache = methods.get(service_method)?.lock().unwrap();
+        Some(CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.entries.len(),
+        })
+    }
+}
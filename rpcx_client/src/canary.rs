@@ -0,0 +1,231 @@
+use qstring::QString;
+use rand::Rng;
+use rpcx_protocol::{Error, ErrorKind, Load, Metadata, Result, RpcxParam};
+use std::{
+    cmp::Ordering::{Equal, Greater, Less},
+    collections::HashMap,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use super::ClientSelector;
+
+/// the metadata key which tags a server as a canary when it is `true`.
+pub const CANARY: &str = "canary";
+
+/// returns whether the metadata of a server tags it as a canary.
+pub fn is_canary(meta: &str) -> bool {
+    QString::from(meta).get(CANARY) == Some("true")
+}
+
+/// routes calls to the canary servers, see `XClient::set_canary_rule`. A call is routed to
+/// them if its metadata matches any of the predicates, otherwise by `percent` of chance.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CanaryRule {
+    /// the percentage of the other calls routed to the canary servers, 0 to 100.
+    pub percent: u8,
+    pub predicates: Vec<MetadataPredicate>,
+}
+
+impl CanaryRule {
+    /// creates a rule from predicates such as `uid % 100 < 5` or `region == eu`.
+    pub fn new(percent: u8, predicates: &[&str]) -> Result<Self> {
+        let predicates = predicates
+            .iter()
+            .map(|p| p.parse())
+            .collect::<Result<_>>()?;
+        Ok(CanaryRule {
+            percent,
+            predicates,
+        })
+    }
+
+    /// returns whether a call with the metadata is routed to the canary servers.
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        if self.predicates.iter().any(|p| p.matches(metadata)) {
+            return true;
+        }
+        self.percent > 0 && rand::thread_rng().gen_range(0, 100) < u32::from(self.percent)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// a condition on a value of the metadata of calls, `<key> [% <modulus>] <op> <value>` where
+/// the op is one of `==`, `!=`, `<`, `<=`, `>` and `>=`.
+///
+/// Values are compared as integers if both of them are, otherwise as strings. With a modulus,
+/// a value which is not an integer is hashed, so `uid % 100 < 5` routes 5% of users whatever
+/// their ids. A call without the key doesn't match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataPredicate {
+    key: String,
+    modulus: Option<u64>,
+    op: Op,
+    value: String,
+}
+
+impl FromStr for MetadataPredicate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::Config,
+                format!("invalid metadata predicate {}", s),
+            )
+        };
+        // the longer ops first, `<=` contains `<`
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        let (pos, len, op) = ops
+            .iter()
+            .find_map(|(token, op)| s.find(token).map(|pos| (pos, token.len(), *op)))
+            .ok_or_else(invalid)?;
+        let (left, value) = (&s[..pos], s[pos + len..].trim());
+
+        let mut left = left.splitn(2, '%');
+        let key = left.next().unwrap_or_default().trim();
+        let modulus = match left.next() {
+            Some(m) => match m.trim().parse::<u64>() {
+                Ok(m) if m > 0 => Some(m),
+                _ => return Err(invalid()),
+            },
+            None => None,
+        };
+        if key.is_empty() || value.is_empty() {
+            return Err(invalid());
+        }
+        Ok(MetadataPredicate {
+            key: key.to_owned(),
+            modulus,
+            op,
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl MetadataPredicate {
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        let v = match metadata.get(&self.key) {
+            Some(v) => v,
+            None => return false,
+        };
+        let expected = self.value.parse::<i64>();
+        let actual = match self.modulus {
+            Some(m) => {
+                let n = v.parse::<u64>().unwrap_or_else(|_| fnv1a(v.as_bytes()));
+                Ok((n % m) as i64)
+            }
+            None => v.parse::<i64>(),
+        };
+        let ordering = match (actual, expected) {
+            (Ok(a), Ok(e)) => a.cmp(&e),
+            _ => v.as_str().cmp(self.value.as_str()),
+        };
+        match self.op {
+            Op::Eq => ordering == Equal,
+            Op::Ne => ordering != Equal,
+            Op::Lt => ordering == Less,
+            Op::Le => ordering != Greater,
+            Op::Gt => ordering == Greater,
+            Op::Ge => ordering != Less,
+        }
+    }
+}
+
+// a hash which is the same in every process, so the clients of a user agree on the route.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// selects the canary servers for the calls `XClient` routes to them and the other servers
+/// for the rest. The calls routed to the canaries go to the other servers while there is no
+/// canary.
+pub struct CanarySelector<S: ClientSelector> {
+    stable: S,
+    canary: S,
+    has_canary: AtomicBool,
+    routed: AtomicBool,
+}
+
+impl<S: ClientSelector> CanarySelector<S> {
+    /// creates a selector of the canary servers by `canary` and the others by `stable`.
+    pub fn new(stable: S, canary: S) -> Self {
+        CanarySelector {
+            stable,
+            canary,
+            has_canary: AtomicBool::new(false),
+            routed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<S: ClientSelector> ClientSelector for CanarySelector<S> {
+    fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
+        if self.routed.load(Ordering::Relaxed) && self.has_canary.load(Ordering::Relaxed) {
+            self.canary.select(service_path, service_method, args)
+        } else {
+            self.stable.select(service_path, service_method, args)
+        }
+    }
+    fn update_server(&self, servers: &HashMap<String, String>) {
+        let (canary, stable): (HashMap<String, String>, HashMap<String, String>) = servers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .partition(|(_, meta)| is_canary(meta));
+        self.has_canary.store(!canary.is_empty(), Ordering::Relaxed);
+        self.stable.update_server(&stable);
+        self.canary.update_server(&canary);
+    }
+    fn close(&self) {
+        self.stable.close();
+        self.canary.close();
+    }
+    fn on_route(&self, canary: bool) {
+        self.routed.store(canary, Ordering::Relaxed);
+    }
+    fn on_hash_key(&self, key: Option<&str>) {
+        self.stable.on_hash_key(key);
+        self.canary.on_hash_key(key);
+    }
+    // the servers of the selectors differ, so calls are reported to both
+    fn on_call_start(&self, server: &str) {
+        self.stable.on_call_start(server);
+        self.canary.on_call_start(server);
+    }
+    fn on_call_end(&self, server: &str) {
+        self.stable.on_call_end(server);
+        self.canary.on_call_end(server);
+    }
+    fn on_result(&self, server: &str, latency: Duration, success: bool) {
+        self.stable.on_result(server, latency, success);
+        self.canary.on_result(server, latency, success);
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        self.stable.on_load(server, load);
+        self.canary.on_load(server, load);
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        let mut servers = self.stable.candidates()?;
+        servers.extend(self.canary.candidates()?);
+        Some(servers)
+    }
+}
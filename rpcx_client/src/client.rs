@@ -2,32 +2,51 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     error::Error as StdError,
-    io::{BufReader, BufWriter, Write},
-    net::{Shutdown, SocketAddr, TcpStream},
+    io::{self, BufWriter, Read, Write},
+    net::{Shutdown, TcpStream},
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::{self, Receiver, SendError, Sender},
         Arc, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use futures::future::*;
+#[cfg(feature = "crypto")]
+use std::sync::RwLock;
 
+use bytes::{Bytes, BytesMut};
+use futures::future::*;
 
 use rpcx_protocol::{call::*, *};
 
-#[derive(Debug, Copy, Clone)]
+#[cfg(feature = "tls")]
+use crate::TlsConnector;
+use crate::{eyeballs, pending::PendingCalls, resolver::Resolver, timer::CallTimer};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Opt {
     pub retry: u8,
     pub compress_type: CompressType,
     pub serialize_type: SerializeType,
     pub connect_timeout: Duration,
+    /// the timeout of each call, a call fails with `ErrorKind::Timeout` if it is not replied
+    /// in time while the connection and the other calls on it go on.
     pub read_timeout: Duration,
     pub write_timeout: Duration,
     pub nodelay: Option<bool>,
     pub ttl: Option<u32>,
+    /// the bound of the bytes of the requests queued to be written to the connection, so a
+    /// server which stops reading doesn't make the client buffer without limit.
+    pub max_queued_bytes: usize,
+    /// what a call does when the queue is full.
+    pub backpressure: BackpressurePolicy,
+    /// logs the errors replied by servers with the ids of their requests to stderr.
+    pub log_errors: bool,
+    /// the most bytes of a reply, `MAX_MESSAGE_LEN` by default. A longer reply closes the
+    /// connection and fails the calls on it.
+    pub max_message_len: usize,
 }
 
 impl Default for Opt {
@@ -41,14 +60,31 @@ impl Default for Opt {
             write_timeout: Default::default(),
             nodelay: None,
             ttl: None,
+            max_queued_bytes: MAX_QUEUED_BYTES,
+            backpressure: BackpressurePolicy::Block,
+            log_errors: false,
+            max_message_len: MAX_MESSAGE_LEN,
         }
     }
 }
 
+/// the capacity of the write buffer of a connection, pipelined requests are flushed together
+/// up to this size.
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, Default)]
 struct RpcData {
     seq: u64,
     data: Vec<u8>,
+    // the bytes reserved from the outbound queue, stream frames are bounded by their credits
+    queued: usize,
+}
+
+// the receiving and sending state of a stream.
+#[derive(Debug)]
+struct ClientStream {
+    data: Sender<StreamFrame>,
+    credit: Arc<StreamCredit>,
 }
 
 /// a direct client to connect rpcx services.
@@ -60,7 +96,23 @@ pub struct Client {
     seq: Arc<AtomicU64>,
     chan_sender: Sender<RpcData>,
     chan_receiver: Arc<Mutex<Receiver<RpcData>>>,
-    calls: Arc<Mutex<HashMap<u64, ArcCall>>>,
+    outbound: Arc<OutboundQueue>,
+    calls: Arc<PendingCalls>,
+    timer: Arc<CallTimer>,
+    server_message_sender: Arc<Mutex<Option<Sender<Message>>>>,
+    streams: Arc<Mutex<HashMap<u64, ClientStream>>>,
+    #[cfg(feature = "crypto")]
+    ciphers: Arc<RwLock<HashMap<String, Arc<PayloadCipher>>>>,
+    stream_compression: Option<StreamCompression>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+    faults: Option<FaultInjector>,
+    closed: AtomicBool,
+    // whether the reader of the connection failed
+    disconnected: Arc<AtomicBool>,
+    resolver: Arc<Resolver>,
+    // the load attached to the latest reply
+    load: Arc<Mutex<Option<Load>>>,
 }
 
 impl Client {
@@ -74,25 +126,237 @@ impl Client {
             seq: Arc::new(AtomicU64::new(0)),
             chan_sender: sender,
             chan_receiver: Arc::new(Mutex::new(receiver)),
-            calls: Arc::new(Mutex::new(HashMap::new())),
+            outbound: Arc::new(OutboundQueue::new(
+                MAX_QUEUED_BYTES,
+                BackpressurePolicy::Block,
+            )),
+            calls: Arc::new(PendingCalls::new()),
+            timer: Arc::new(CallTimer::new(Duration::default())),
+            server_message_sender: Arc::new(Mutex::new(None)),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "crypto")]
+            ciphers: Arc::new(RwLock::new(HashMap::new())),
+            stream_compression: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            faults: None,
+            closed: AtomicBool::new(false),
+            disconnected: Arc::new(AtomicBool::new(false)),
+            resolver: Arc::new(Resolver::default()),
+            load: Arc::new(Mutex::new(None)),
         }
     }
-    pub fn start(&mut self) -> Result<()> {
-        let stream = if self.opt.connect_timeout.as_millis() == 0 {
-            TcpStream::connect(self.addr.as_str())?
-        } else {
-            let socket_addr: SocketAddr = self
-                .addr
-                .parse()
-                .map_err(|err| Error::new(ErrorKind::Network, err))?;
-            TcpStream::connect_timeout(&socket_addr, self.opt.connect_timeout)?
+
+    /// returns a receiver of the messages pushed by the server.
+    ///
+    /// Only the receiver returned by the latest invocation gets messages.
+    pub fn subscribe(&self) -> Receiver<Message> {
+        let (sender, receiver) = mpsc::channel();
+        *self.server_message_sender.lock().unwrap() = Some(sender);
+        receiver
+    }
+
+    /// subscribes to the topics matching the pattern, for example `orders.*.created`.
+    ///
+    /// Published messages are delivered to the receiver returned by `subscribe`, with
+    /// `PUBSUB_SERVICE` as the service path and the topic as the service method.
+    pub fn subscribe_topic(&mut self, pattern: &str) -> Result<()> {
+        self.call_pubsub(PUBSUB_SUBSCRIBE, pattern)
+    }
+
+    pub fn unsubscribe_topic(&mut self, pattern: &str) -> Result<()> {
+        self.call_pubsub(PUBSUB_UNSUBSCRIBE, pattern)
+    }
+
+    fn call_pubsub(&mut self, service_method: &str, pattern: &str) -> Result<()> {
+        let metadata = HashMap::new();
+        let args = BytesMut::from(pattern);
+        let reply: Option<Result<BytesMut>> =
+            self.call(PUBSUB_SERVICE, service_method, false, &metadata, &args);
+        reply.unwrap().map(|_| ())
+    }
+
+    /// encrypts the payloads of the calls to the service with a pre-shared AES-256 key, the
+    /// server must be configured with the same key by `EncryptionPlugin`.
+    #[cfg(feature = "crypto")]
+    pub fn set_encryption_key(&mut self, service_path: &str, key: &[u8]) -> Result<()> {
+        let cipher = PayloadCipher::new(key)?;
+        self.ciphers
+            .write()
+            .unwrap()
+            .insert(service_path.to_owned(), Arc::new(cipher));
+        Ok(())
+    }
+
+    /// compresses the whole stream of the connection, which is negotiated with the server by
+    /// `start`. The server must enable it with the same dictionary by
+    /// `Server::enable_stream_compression`, the connection is not compressed otherwise.
+    ///
+    /// It suits verbose payloads such as JSON whose messages repeat each other, and works
+    /// alongside the compression of payloads by `Opt::compress_type`.
+    pub fn set_stream_compression(&mut self, compression: StreamCompression) {
+        self.stream_compression = Some(compression);
+    }
+
+    /// connects the server over TLS from the next `start` on, see `TlsConnector`.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, tls: TlsConnector) {
+        self.tls = Some(tls);
+    }
+
+    /// injects the faults into the calls before they are sent, so fail modes and circuit
+    /// breakers can be exercised without touching the servers. A delay sleeps on the thread
+    /// which sends the call, an error fails the call without sending it, and a dropped
+    /// response sends the call as oneway so the call times out once the server serves it.
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.faults = Some(injector);
+    }
+
+    /// changes the timeouts of the calls and of the writes of the connection, a zero
+    /// duration means no timeout. The new call timeout applies to the calls sent later.
+    pub fn set_timeouts(&self, read_timeout: Duration, write_timeout: Duration) -> Result<()> {
+        self.timer.set_timeout(read_timeout);
+        if let Some(stream) = &self.stream {
+            let timeout = |d: Duration| if d.as_millis() > 0 { Some(d) } else { None };
+            stream.set_write_timeout(timeout(write_timeout))?;
+        }
+        Ok(())
+    }
+
+    /// closes the connection gracefully. New calls fail at once, while the responses of the
+    /// calls in flight are waited for up to `timeout`. Then the connection is shut down and
+    /// the calls which are still pending fail.
+    ///
+    /// An error of `ErrorKind::Timeout` is returned if some calls were still pending.
+    pub fn close(&self, timeout: Duration) -> Result<()> {
+        self.closed.store(true, Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        while !self.calls.is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let drained = self.calls.is_empty();
+
+        // the reader and the writer of the connection exit once it is shut down
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.timer.close();
+        if !drained {
+            return Err(Error::new(
+                ErrorKind::Timeout,
+                format!("pending calls to {} are not finished", self.addr),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// returns whether the connection is started and neither closed nor lost.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some() && !self.is_closed() && !self.disconnected.load(Ordering::SeqCst)
+    }
+
+    /// returns the number of calls waiting for their replies.
+    pub fn in_flight(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// returns the load the server attached to its latest reply, see
+    /// `Server::enable_load_report`.
+    pub fn load(&self) -> Option<Load> {
+        *self.load.lock().unwrap()
+    }
+
+    // connects the first reachable address of the server, its hostname is resolved again
+    // if none is.
+    fn connect(&self) -> Result<TcpStream> {
+        let addrs = self.resolver.resolve(&self.addr)?;
+        eyeballs::connect(&addrs, self.opt.connect_timeout).map_err(|err| {
+            self.resolver.invalidate(&self.addr);
+            Error::from(err)
+        })
+    }
+
+    // does the TLS handshake of a new connection if TLS is set, the server is waited for up
+    // to the read timeout.
+    fn secure(&self, stream: TcpStream) -> Result<Conn> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = &self.tls {
+                if self.opt.read_timeout.as_millis() > 0 {
+                    stream.set_read_timeout(Some(self.opt.read_timeout))?;
+                }
+                let conn = tls.connect(&self.addr, stream)?;
+                conn.get_ref().set_read_timeout(None)?;
+                return Ok(conn);
+            }
+        }
+        Ok(Conn::Tcp(stream))
+    }
+
+    // returns the read half and the write half of a new connection, compressed if the
+    // server accepts the stream compression. Nothing else is sent until it is replied.
+    fn compress_stream(
+        &self,
+        conn: &Conn,
+    ) -> Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        let compression = match &self.stream_compression {
+            Some(compression) => compression,
+            None => return Ok((Box::new(conn.try_clone()?), Box::new(conn.try_clone()?))),
         };
 
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        conn.try_clone()?
+            .write_all(&compression.request(seq).encode())?;
+        let stream = conn.get_ref();
         if self.opt.read_timeout.as_millis() > 0 {
             stream.set_read_timeout(Some(self.opt.read_timeout))?;
         }
+        let mut reader = MessageReader::new(conn.try_clone()?);
+        reader.set_max_message_len(self.opt.max_message_len);
+        let reply = loop {
+            let msg = reader.read_msg()?;
+            if msg.get_seq() == seq {
+                if let Some(MessageType::Response) = msg.get_message_type() {
+                    break msg;
+                }
+            }
+            // messages pushed by the server in the meantime
+            if !msg.is_heartbeat() {
+                if let Some(sender) = &*self.server_message_sender.lock().unwrap() {
+                    let _ = sender.send(msg);
+                }
+            }
+        };
+        // the calls time out by the timer
+        stream.set_read_timeout(None)?;
+
+        if let Some(err) = Error::from_reply(&reply) {
+            eprintln!("stream compression is rejected by {}: {}", self.addr, err);
+            return Ok((Box::new(reader.into_inner()), Box::new(conn.try_clone()?)));
+        }
+        let read = compression.decoder(reader.into_inner())?;
+        let write = compression.encoder(conn.try_clone()?)?;
+        Ok((read, write))
+    }
+
+    /// shares the resolved addresses of hostnames with other clients.
+    pub(crate) fn set_resolver(&mut self, resolver: Arc<Resolver>) {
+        self.resolver = resolver;
+    }
+
+    /// connects the server, `addr` is an address like `127.0.0.1:8972` or a hostname and a
+    /// port like `service.internal:8972`. The addresses of a hostname are tried in turn, in
+    /// parallel if it has both IPv6 and IPv4 ones, see `CONNECTION_ATTEMPT_DELAY`.
+    pub fn start(&mut self) -> Result<()> {
+        let stream = self.connect()?;
+
         if self.opt.write_timeout.as_millis() > 0 {
-            stream.set_write_timeout(Some(self.opt.read_timeout))?;
+            stream.set_write_timeout(Some(self.opt.write_timeout))?;
         }
 
         if self.opt.nodelay.is_some() {
@@ -101,28 +365,95 @@ impl Client {
         if self.opt.ttl.is_some() {
             stream.set_ttl(self.opt.ttl.unwrap())?;
         }
+        let conn = self.secure(stream)?;
+        let (conn_read, conn_write) = self.compress_stream(&conn)?;
+        self.outbound.close();
+        self.outbound = Arc::new(OutboundQueue::new(
+            self.opt.max_queued_bytes,
+            self.opt.backpressure,
+        ));
+        let stream = conn.get_ref();
         let read_stream = stream.try_clone()?;
         let write_stream = stream.try_clone()?;
-        self.stream = Some(stream);
+        self.stream = Some(stream.try_clone()?);
+
+        // the calls time out by the timer rather than by reads of the connection, which would
+        // fail all the calls on it
+        self.timer.close();
+        self.timer = Arc::new(CallTimer::new(self.opt.read_timeout));
+        let timer = self.timer.clone();
+        let timer_calls = self.calls.clone();
+        thread::spawn(move || timer.run(|seqs| Self::expire_calls(&timer_calls, seqs)));
 
         let calls = self.calls.clone();
+        let timer = self.timer.clone();
+        let server_message_sender = self.server_message_sender.clone();
+        let streams = self.streams.clone();
+        #[cfg(feature = "crypto")]
+        let ciphers = self.ciphers.clone();
+        let load = self.load.clone();
+        let disconnected = self.disconnected.clone();
+        let log_errors = self.opt.log_errors;
+        let max_message_len = self.opt.max_message_len;
+        disconnected.store(false, Ordering::SeqCst);
+        let read_outbound = self.outbound.clone();
         thread::spawn(move || {
-            let mut reader = BufReader::new(read_stream.try_clone().unwrap());
+            let mut reader = MessageReader::new(conn_read);
+            reader.set_max_message_len(max_message_len);
 
             loop {
-                let mut msg = Message::new();
-                match msg.decode(&mut reader) {
-                    Ok(()) => {
-                        if let Some(call) = calls.lock().unwrap().remove(&msg.get_seq()) {
+                match reader.read_msg() {
+                    // replies are only decrypted in place
+                    #[cfg_attr(not(feature = "crypto"), allow(unused_mut))]
+                    Ok(mut msg) => {
+                        // requests from the server are pushed messages
+                        if let Some(MessageType::Request) = msg.get_message_type() {
+                            if !msg.is_heartbeat() {
+                                if let Some(sender) = &*server_message_sender.lock().unwrap() {
+                                    let _ = sender.send(msg);
+                                }
+                            }
+                            continue;
+                        }
+
+                        if let Some(kind) = get_stream_frame(&msg) {
+                            Self::on_stream_frame(&streams, &kind, msg);
+                            continue;
+                        }
+
+                        if let Some(server_load) = get_load(&msg.metadata.borrow()) {
+                            *load.lock().unwrap() = Some(server_load);
+                        }
+
+                        if let Some(call) = calls.remove(msg.get_seq()) {
                             let internal_call_cloned = call.clone();
                             let mut internal_call_mutex = internal_call_cloned.lock().unwrap();
                             let internal_call = internal_call_mutex.get_mut();
                             internal_call.is_client_error = false;
-                            if let Some(MessageStatusType::Error) = msg.get_message_status_type() {
-                                internal_call.error =
-                                    msg.get_error().unwrap_or_else(|| "".to_owned());
+                            #[cfg(feature = "crypto")]
+                            let checked =
+                                check_reply(&msg).and_then(|_| open_reply(&ciphers, &mut msg));
+                            #[cfg(not(feature = "crypto"))]
+                            let checked = check_reply(&msg);
+
+                            if let Err(err) = checked {
+                                internal_call.is_client_error = true;
+                                internal_call.error_kind = err.kind();
+                                internal_call.error = err.to_string();
+                            } else if let Some(err) = Error::from_reply(&msg) {
+                                if log_errors {
+                                    eprintln!(
+                                        "request {} to {}.{} failed: {}",
+                                        msg.get_request_id().unwrap_or_default(),
+                                        msg.service_path,
+                                        msg.service_method,
+                                        err
+                                    );
+                                }
+                                internal_call.error = err.to_string();
+                                internal_call.reply_error = Some(err);
                             } else {
-                                internal_call.reply_data.extend_from_slice(&msg.payload);
+                                internal_call.reply_data = msg.payload;
                             }
 
                             let mut status = internal_call.state.lock().unwrap();
@@ -134,7 +465,11 @@ impl Client {
                     }
                     Err(err) => {
                         println!("failed to read: {}", err.to_string());
-                        Self::drain_calls(calls, err);
+                        disconnected.store(true, Ordering::SeqCst);
+                        read_outbound.close();
+                        Self::close_streams(&streams, &err);
+                        Self::drain_calls(&calls, err);
+                        timer.close();
                         match read_stream.shutdown(Shutdown::Both) {
                             Ok(_) => {}
                             Err(err) => eprintln!("failed to shutdown stream: {}", err),
@@ -147,40 +482,41 @@ impl Client {
 
         let chan_receiver = self.chan_receiver.clone();
         let send_calls = self.calls.clone();
+        let outbound = self.outbound.clone();
         thread::spawn(move || {
-            let mut writer = BufWriter::new(write_stream.try_clone().unwrap());
+            let mut writer = BufWriter::with_capacity(WRITE_BUFFER_SIZE, conn_write);
+            let chan_receiver = chan_receiver.lock().unwrap();
             loop {
-                match chan_receiver.lock().unwrap().recv() {
+                let mut rpcdata = match chan_receiver.recv() {
                     Err(_err) => {
                         //eprintln!("failed to fetch RpcData: {}", err.to_string());
+                        outbound.close();
                         write_stream.shutdown(Shutdown::Both).unwrap();
                         return;
                     }
-                    Ok(rpcdata) => {
-                        match writer.write_all(rpcdata.data.as_slice()) {
-                            Ok(()) => {
-                                //println!("wrote");
-                            }
-                            Err(err) => {
-                                //println!("failed to write: {}", err.to_string());
-                                Self::drain_calls(send_calls.clone(), err);
-                                write_stream.shutdown(Shutdown::Both).unwrap();
-                                return;
-                            }
-                        }
+                    Ok(rpcdata) => rpcdata,
+                };
 
-                        match writer.flush() {
-                            Ok(()) => {
-                                //println!("flushed");
-                            }
-                            Err(err) => {
-                                //println!("failed to flush: {}", err.to_string());
-                                Self::drain_calls(send_calls.clone(), err);
-                                write_stream.shutdown(Shutdown::Both).unwrap();
-                                return;
-                            }
-                        }
+                // requests queued by pipelined calls are coalesced into a single flush
+                let rt = loop {
+                    let rt = writer.write_all(rpcdata.data.as_slice());
+                    buffer_pool().put(rpcdata.data);
+                    outbound.release(rpcdata.queued);
+                    if rt.is_err() {
+                        break rt;
+                    }
+                    match chan_receiver.try_recv() {
+                        Ok(next) => rpcdata = next,
+                        Err(_) => break writer.flush(),
                     }
+                };
+
+                if let Err(err) = rt {
+                    //println!("failed to write: {}", err.to_string());
+                    outbound.close();
+                    Self::drain_calls(&send_calls, err);
+                    write_stream.shutdown(Shutdown::Both).unwrap();
+                    return;
                 }
             }
         });
@@ -197,42 +533,79 @@ impl Client {
         metadata: &Metadata,
         args: &dyn RpcxParam,
     ) -> CallFuture {
-        let seq = self.seq.clone().fetch_add(1, Ordering::SeqCst);
+        if self.is_closed() {
+            return closed_call(is_oneway || is_heartbeat);
+        }
+        let mut is_dropped = false;
+        if let (Some(faults), false) = (&self.faults, is_heartbeat) {
+            let mut err = None;
+            for fault in faults.roll(service_path, service_method) {
+                match fault {
+                    Fault::Delay(delay) => thread::sleep(delay),
+                    Fault::DropResponse => is_dropped = true,
+                    Fault::Error(kind) => err = Some(Error::new(kind, "injected fault")),
+                }
+            }
+            if let Some(err) = err {
+                return failed_call(is_oneway, err);
+            }
+        }
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
 
         let mut req = Message::new();
-        req.set_version(0);
+        req.set_version(PROTOCOL_VERSION);
         req.set_message_type(MessageType::Request);
+        req.set_heartbeat(is_heartbeat);
+        // the server doesn't reply a dropped response, while the call waits for it
+        req.set_oneway(is_oneway || is_dropped);
         req.set_serialize_type(self.opt.serialize_type);
         req.set_compress_type(self.opt.compress_type);
         req.set_seq(seq);
         req.service_path = service_path.to_string();
         req.service_method = service_method.to_string();
 
-        let mut new_metadata = HashMap::with_capacity(metadata.len());
+        let mut new_metadata = HashMap::with_capacity(metadata.len() + 1);
         for (k, v) in metadata {
             new_metadata.insert(k.clone(), v.clone());
         }
+        // callers can pass the id of the request they serve to propagate it
+        if !is_heartbeat && !new_metadata.contains_key(REQUEST_ID) {
+            new_metadata.insert(REQUEST_ID.to_owned(), new_request_id());
+        }
         req.metadata.replace(new_metadata);
         let payload = args.into_bytes(self.opt.serialize_type).unwrap();
-        req.payload = payload;
+        req.payload = Bytes::from(payload);
+        #[cfg(feature = "crypto")]
+        {
+            if let Some(cipher) = self.ciphers.read().unwrap().get(service_path) {
+                cipher.seal(&mut req).unwrap();
+            }
+        }
 
-        let data = req.encode();
+        let mut data = buffer_pool().get();
+        req.encode_to(&mut data);
+        // a server which doesn't read holds the calls back by the backpressure policy
+        let queued = data.len();
+        if let Err(err) = self.outbound.reserve(queued) {
+            buffer_pool().put(data);
+            return failed_call(is_oneway || is_heartbeat, err);
+        }
 
+        // no reply is written for oneway requests, so nothing waits for one
         let call_future = if !is_oneway && !is_heartbeat {
             let callback = Call::new(seq);
             let arc_call = Arc::new(Mutex::new(RefCell::from(callback)));
-            self.calls
-                .clone()
-                .lock()
-                .unwrap()
-                .insert(seq, arc_call.clone());
+            self.calls.insert(seq, arc_call.clone());
+            if let Some(timeout) = self.timer.timeout(get_deadline(metadata)) {
+                self.timer.schedule(seq, timeout);
+            }
 
             CallFuture::new(Some(arc_call))
         } else {
             CallFuture::new(None)
         };
 
-        let send_data = RpcData { seq, data };
+        let send_data = RpcData { seq, data, queued };
         match self.chan_sender.clone().send(send_data) {
             Ok(_) => {}
             Err(err) => self.remove_call_with_senderr(err),
@@ -241,11 +614,173 @@ impl Client {
         call_future
     }
 
+    fn on_stream_frame(streams: &Arc<Mutex<HashMap<u64, ClientStream>>>, kind: &str, msg: Message) {
+        let mut streams = streams.lock().unwrap();
+        let seq = msg.get_seq();
+        match kind {
+            STREAM_DATA => {
+                if let Some(s) = streams.get(&seq) {
+                    let _ = s.data.send(StreamFrame::Data(msg.payload));
+                }
+            }
+            STREAM_ACK => {
+                if let Some(s) = streams.get(&seq) {
+                    let credit = msg
+                        .metadata
+                        .borrow()
+                        .get(STREAM_CREDIT)
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    s.credit.release(credit);
+                }
+            }
+            STREAM_END => {
+                if let Some(s) = streams.remove(&seq) {
+                    let frame = match Error::from_reply(&msg) {
+                        Some(err) => StreamFrame::Error(err),
+                        None => StreamFrame::End,
+                    };
+                    let _ = s.data.send(frame);
+                    // the server doesn't read the request body any more
+                    s.credit.close();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn close_streams<T: StdError + 'static>(
+        streams: &Arc<Mutex<HashMap<u64, ClientStream>>>,
+        err: &T,
+    ) {
+        let kind = conn_error_kind(err);
+        let mut streams = streams.lock().unwrap();
+        for (_, s) in streams.drain() {
+            let _ = s
+                .data
+                .send(StreamFrame::Error(Error::new(kind, err.to_string())));
+            s.credit.close();
+        }
+    }
+
+    /// calls a stream function of the server.
+    ///
+    /// The request body is read from `body` and sent in chunks, while the response body is
+    /// written to `out`, so large payloads are never buffered entirely in memory.
+    pub fn call_stream(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+        mut body: Box<dyn Read + Send>,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+
+        let mut req = Message::new();
+        req.set_version(PROTOCOL_VERSION);
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(self.opt.serialize_type);
+        req.set_compress_type(self.opt.compress_type);
+        req.set_seq(seq);
+        req.service_path = service_path.to_string();
+        req.service_method = service_method.to_string();
+        req.metadata.replace(metadata.clone());
+        req.metadata
+            .borrow_mut()
+            .insert(STREAM_FRAME.to_owned(), STREAM_OPEN.to_owned());
+        req.payload = Bytes::from(args.into_bytes(self.opt.serialize_type)?);
+
+        let (sender, receiver) = mpsc::channel();
+        let credit = Arc::new(StreamCredit::new(STREAM_WINDOW));
+        self.streams.lock().unwrap().insert(
+            seq,
+            ClientStream {
+                data: sender,
+                credit: credit.clone(),
+            },
+        );
+        let data = req.encode();
+        if let Err(err) = self.chan_sender.send(RpcData {
+            seq,
+            data,
+            queued: 0,
+        }) {
+            self.streams.lock().unwrap().remove(&seq);
+            return Err(Error::new(ErrorKind::Client, err.to_string()));
+        }
+
+        // the request body is sent in background while the response body is received
+        let data_sender = self.chan_sender.clone();
+        let end_sender = self.chan_sender.clone();
+        let body_handler = thread::spawn(move || -> io::Result<()> {
+            let mut w = StreamWriter::new(
+                credit,
+                Box::new(move |chunk| {
+                    let frame = new_stream_frame(MessageType::Request, seq, STREAM_DATA, chunk);
+                    let data = frame.encode();
+                    data_sender
+                        .send(RpcData {
+                            seq,
+                            data,
+                            queued: 0,
+                        })
+                        .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err.to_string()))
+                }),
+            );
+            io::copy(&mut body, &mut w)?;
+            w.flush()?;
+
+            let frame = new_stream_frame(MessageType::Request, seq, STREAM_END, Vec::new());
+            let data = frame.encode();
+            end_sender
+                .send(RpcData {
+                    seq,
+                    data,
+                    queued: 0,
+                })
+                .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err.to_string()))
+        });
+
+        let ack_sender = self.chan_sender.clone();
+        let mut reader = StreamReader::new(
+            receiver,
+            Box::new(move |n| {
+                let frame = new_stream_frame(MessageType::Request, seq, STREAM_ACK, Vec::new());
+                frame
+                    .metadata
+                    .borrow_mut()
+                    .insert(STREAM_CREDIT.to_owned(), n.to_string());
+                let data = frame.encode();
+                let _ = ack_sender.send(RpcData {
+                    seq,
+                    data,
+                    queued: 0,
+                });
+            }),
+        );
+        let rt = io::copy(&mut reader, out);
+
+        self.streams.lock().unwrap().remove(&seq);
+        let _ = body_handler.join();
+
+        match rt {
+            Ok(_) => Ok(()),
+            // errors returned by the server or of the connection
+            Err(err) => {
+                if err.get_ref().map_or(false, |err| err.is::<Error>()) {
+                    let inner = err.into_inner().unwrap().downcast::<Error>().unwrap();
+                    return Err(*inner);
+                }
+                Err(Error::new(ErrorKind::Network, err))
+            }
+        }
+    }
+
     fn remove_call_with_senderr(&self, err: SendError<RpcData>) {
         let seq = err.0.seq;
-        let calls = self.calls.clone();
-        let mut m = calls.lock().unwrap();
-        if let Some(call) = m.remove(&seq) {
+        if let Some(call) = self.calls.remove(seq) {
             let internal_call_cloned = call.clone();
             let mut internal_call_mutex = internal_call_cloned.lock().unwrap();
             let internal_call = internal_call_mutex.get_mut();
@@ -258,13 +793,34 @@ impl Client {
         }
     }
 
-    fn drain_calls<T: StdError>(calls: Arc<Mutex<HashMap<u64, ArcCall>>>, err: T) {
-        let mut m = calls.lock().unwrap();
-        for (_, call) in m.drain().take(1) {
+    fn drain_calls<T: StdError + 'static>(calls: &PendingCalls, err: T) {
+        let kind = conn_error_kind(&err);
+        for call in calls.drain() {
             let internal_call_cloned = call.clone();
             let mut internal_call_mutex = internal_call_cloned.lock().unwrap();
             let internal_call = internal_call_mutex.get_mut();
             internal_call.error = String::from(err.description());
+            internal_call.error_kind = kind;
+            let mut status = internal_call.state.lock().unwrap();
+            status.ready = true;
+            if let Some(ref task) = status.task {
+                task.notify()
+            }
+        }
+    }
+
+    // fails the calls which are not replied by their deadlines.
+    fn expire_calls(calls: &PendingCalls, seqs: Vec<u64>) {
+        for seq in seqs {
+            // the call was replied or failed in time
+            let call = match calls.remove(seq) {
+                Some(call) => call,
+                None => continue,
+            };
+            let mut internal_call_mutex = call.lock().unwrap();
+            let internal_call = internal_call_mutex.get_mut();
+            internal_call.error = "call timed out".to_owned();
+            internal_call.error_kind = ErrorKind::Timeout;
             let mut status = internal_call.state.lock().unwrap();
             status.ready = true;
             if let Some(ref task) = status.task {
@@ -275,9 +831,7 @@ impl Client {
 
     #[allow(dead_code)]
     fn remove_call_with_err<T: StdError>(&mut self, seq: u64, err: T) {
-        let calls = self.calls.clone();
-        let m = calls.lock().unwrap();
-        if let Some(call) = m.get(&seq) {
+        if let Some(call) = self.calls.get(seq) {
             let internal_call_cloned = call.clone();
             let mut internal_call_mutex = internal_call_cloned.lock().unwrap();
             let internal_call = internal_call_mutex.get_mut();
@@ -290,8 +844,49 @@ impl Client {
         }
     }
 
+    /// returns the compressors supported by the server, from its `_reflection` service.
+    /// Compressors which are unknown to this client are left out.
+    pub fn compressors(&self) -> Result<Vec<CompressType>> {
+        let metadata = HashMap::new();
+        let reply: Bytes = self
+            .call(
+                REFLECTION_SERVICE,
+                REFLECTION_COMPRESSORS,
+                false,
+                &metadata,
+                &Bytes::new(),
+            )
+            .unwrap()?;
+        Ok(parse_compressors(&reply))
+    }
+
+    /// returns the methods registered to the server, such as `Arith.Mul`, from its
+    /// `_reflection` service.
+    pub fn services(&self) -> Result<Vec<String>> {
+        let metadata = HashMap::new();
+        let reply: Bytes = self
+            .call(
+                REFLECTION_SERVICE,
+                REFLECTION_SERVICES,
+                false,
+                &metadata,
+                &Bytes::new(),
+            )
+            .unwrap()?;
+        Ok(parse_services(&reply))
+    }
+
+    /// turns off the compression of requests if the server doesn't support the compressor of
+    /// `opt`, and returns the compress type in use.
+    pub fn negotiate_compress_type(&mut self) -> Result<CompressType> {
+        if !self.compressors()?.contains(&self.opt.compress_type) {
+            self.opt.compress_type = CompressType::CompressNone;
+        }
+        Ok(self.opt.compress_type)
+    }
+
     pub fn call<T>(
-        &mut self,
+        &self,
         service_path: &str,
         service_method: &str,
         is_oneway: bool,
@@ -315,29 +910,39 @@ impl Client {
         }
 
         let arc_call = f.wait().unwrap();
-        let arc_call_1 = arc_call.unwrap().clone();
-        let mut arc_call_2 = arc_call_1.lock().unwrap();
+        Some(self.reply(arc_call.unwrap()))
+    }
+
+    // returns the reply or the error of a finished call.
+    pub(crate) fn reply<T>(&self, arc_call: ArcCall) -> Result<T>
+    where
+        T: RpcxParam + Default,
+    {
+        let mut arc_call_2 = arc_call.lock().unwrap();
         let arc_call_3 = arc_call_2.get_mut();
-        let reply_data = &arc_call_3.reply_data;
+        if let Some(err) = arc_call_3.reply_error.take() {
+            return Err(err);
+        }
 
         if !arc_call_3.error.is_empty() {
             let err = &arc_call_3.error;
             if arc_call_3.is_client_error {
-                return Some(Err(Error::new(ErrorKind::Client, String::from(err))));
+                return Err(Error::new(arc_call_3.error_kind, String::from(err)));
             } else {
-                return Some(Err(Error::from(String::from(err))));
+                return Err(Error::from(String::from(err)));
             }
         }
 
+        let reply_data = std::mem::replace(&mut arc_call_3.reply_data, Bytes::new());
         let mut reply: T = Default::default();
-        match reply.from_slice(self.opt.serialize_type, &reply_data) {
-            Ok(()) => Some(Ok(reply)),
-            Err(err) => Some(Err(err)),
+        match reply.from_bytes(self.opt.serialize_type, reply_data) {
+            Ok(()) => Ok(reply),
+            Err(err) => Err(err),
         }
     }
 
     pub fn acall<T>(
-        &mut self,
+        &self,
         service_path: &str,
         service_method: &str,
         metadata: &Metadata,
@@ -354,14 +959,20 @@ impl Client {
                 let arc_call_1 = opt_arc_call.unwrap().clone();
                 let mut arc_call_2 = arc_call_1.lock().unwrap();
                 let arc_call_3 = arc_call_2.get_mut();
-                let reply_data = &arc_call_3.reply_data;
+                if let Some(err) = arc_call_3.reply_error.take() {
+                    return Err(err);
+                }
                 if !arc_call_3.error.is_empty() {
                     let err = &arc_call_3.error;
+                    if arc_call_3.is_client_error {
+                        return Err(Error::new(arc_call_3.error_kind, String::from(err)));
+                    }
                     return Err(Error::from(String::from(err)));
                 }
 
+                let reply_data = std::mem::replace(&mut arc_call_3.reply_data, Bytes::new());
                 let mut reply: T = Default::default();
-                match reply.from_slice(st, &reply_data) {
+                match reply.from_bytes(st, reply_data) {
                     Ok(()) => Ok(reply),
                     Err(err) => Err(err),
                 }
@@ -371,3 +982,75 @@ impl Client {
         Box::new(rt)
     }
 }
+
+// returns a call which fails since the client is closed.
+fn closed_call(is_oneway: bool) -> CallFuture {
+    failed_call(
+        is_oneway,
+        Error::new(ErrorKind::ConnectionClosed, "client is closed"),
+    )
+}
+
+// returns a call which failed before it is sent.
+fn failed_call(is_oneway: bool, err: Error) -> CallFuture {
+    if is_oneway {
+        return CallFuture::new(None);
+    }
+    let mut call = Call::new(0);
+    call.error_kind = err.kind();
+    call.error = err.to_string();
+    call.state.lock().unwrap().ready = true;
+    CallFuture::new(Some(Arc::new(Mutex::new(RefCell::from(call)))))
+}
+
+// fails the replies whose payloads can't be decompressed.
+fn check_reply(msg: &Message) -> Result<()> {
+    if msg.get_compress_type().is_none() {
+        return Err(Error::new(
+            ErrorKind::Protocol,
+            "unsupported compress type of the reply",
+        ));
+    }
+    Ok(())
+}
+
+// decrypts the payload of a reply by the key of its service, if it is encrypted.
+#[cfg(feature = "crypto")]
+fn open_reply(
+    ciphers: &RwLock<HashMap<String, Arc<PayloadCipher>>>,
+    msg: &mut Message,
+) -> Result<()> {
+    if !is_encrypted(msg) {
+        return Ok(());
+    }
+    let cipher = ciphers.read().unwrap().get(&msg.service_path).cloned();
+    match cipher {
+        Some(cipher) => cipher.open(msg),
+        None => Err(Error::new(
+            ErrorKind::Client,
+            "no encryption key for the reply",
+        )),
+    }
+}
+
+/// generates a random id of a request, 32 hex digits.
+pub fn new_request_id() -> String {
+    format!(
+        "{:016x}{:016x}",
+        rand::random::<u64>(),
+        rand::random::<u64>()
+    )
+}
+
+// tells timeouts from broken connections.
+fn conn_error_kind(err: &(dyn StdError + 'static)) -> ErrorKind {
+    let io_err = err.downcast_ref::<io::Error>().or_else(|| {
+        err.downcast_ref::<Error>()
+            .and_then(|err| err.get_ref())
+            .and_then(|err| err.downcast_ref::<io::Error>())
+    });
+    match io_err.map(|err| err.kind()) {
+        Some(io::ErrorKind::WouldBlock) | Some(io::ErrorKind::TimedOut) => ErrorKind::Timeout,
+        _ => ErrorKind::ConnectionClosed,
+    }
+}
@@ -0,0 +1,510 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+#[cfg(feature = "etcd-registry")]
+use etcd::Client as EtcdClient;
+use serde::Deserialize;
+
+use rpcx_protocol::*;
+
+#[cfg(feature = "tls")]
+use super::TlsConnector;
+use super::{
+    new_selector, xclient::close_clients, CallPolicy, CanaryRule, CanarySelector, ClientSelector,
+    FailMode, HedgePolicy, MethodSelector, Opt, SelectMode, SharedSelector, VersionSelector,
+    XClient, DNS_REFRESH_INTERVAL,
+};
+#[cfg(feature = "etcd-registry")]
+use super::{Discovery, EtcdDiscovery};
+
+/// the options of a `XClient` which can be read from a configuration file by
+/// `XClient::from_config`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct XClientConfig {
+    pub service_path: String,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub fail_mode: FailMode,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub select_mode: SelectMode,
+    /// selects only the servers whose version matches the requirement, such as `>=1.2, <2`.
+    pub version: String,
+    pub retry: u8,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub serialize_type: SerializeType,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub compress_type: CompressType,
+    pub connect_timeout_ms: u64,
+    pub read_timeout_ms: u64,
+    pub write_timeout_ms: u64,
+    pub nodelay: Option<bool>,
+    pub ttl: Option<u32>,
+    /// the bound of the bytes of the requests queued to be written to a connection.
+    pub max_queued_bytes: usize,
+    /// `Block` or `Fail`, what a call does when the queue of its connection is full.
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub backpressure: BackpressurePolicy,
+    /// logs the errors replied by servers with the ids of their requests.
+    pub log_errors: bool,
+    /// the most bytes of a reply.
+    pub max_message_len: usize,
+    /// how long the addresses of the hostnames of servers are cached, see
+    /// `XClient::set_dns_ttl`.
+    pub dns_ttl_ms: u64,
+    /// static servers, from `tcp@host:port` to their metadata such as `weight=10`. They are
+    /// ignored if a registry is set.
+    pub servers: HashMap<String, String>,
+    /// discovers the servers from etcd if it is set.
+    pub registry: Option<RegistryConfig>,
+    pub tls: Option<TlsConfig>,
+    /// overrides the fail mode, the retries, the select mode and the hedging of service methods.
+    pub methods: HashMap<String, MethodConfig>,
+    /// connects to every server in advance, see `XClient::enable_warm_up`.
+    pub warm_up: bool,
+    /// routes calls to the servers tagged as canaries if it is set.
+    pub canary: Option<CanaryConfig>,
+    /// overrides the options of the calls to other services, see `XClient::set_service_opt`.
+    pub services: HashMap<String, ServiceConfig>,
+}
+
+/// the options of the calls to a service, the options of the client are used if they are
+/// unset.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ServiceConfig {
+    pub retry: Option<u8>,
+    #[serde(deserialize_with = "deserialize_option_from_str")]
+    pub serialize_type: Option<SerializeType>,
+    #[serde(deserialize_with = "deserialize_option_from_str")]
+    pub compress_type: Option<CompressType>,
+    pub connect_timeout_ms: Option<u64>,
+    pub read_timeout_ms: Option<u64>,
+    pub write_timeout_ms: Option<u64>,
+}
+
+impl ServiceConfig {
+    /// returns `opt` overridden by the options which are set.
+    pub fn opt(&self, opt: Opt) -> Opt {
+        let ms = |v: Option<u64>, default: Duration| v.map_or(default, Duration::from_millis);
+        Opt {
+            retry: self.retry.unwrap_or(opt.retry),
+            serialize_type: self.serialize_type.unwrap_or(opt.serialize_type),
+            compress_type: self.compress_type.unwrap_or(opt.compress_type),
+            connect_timeout: ms(self.connect_timeout_ms, opt.connect_timeout),
+            read_timeout: ms(self.read_timeout_ms, opt.read_timeout),
+            write_timeout: ms(self.write_timeout_ms, opt.write_timeout),
+            ..opt
+        }
+    }
+}
+
+/// the options of a service method, the options of the client are used if they are unset.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MethodConfig {
+    #[serde(deserialize_with = "deserialize_option_from_str")]
+    pub fail_mode: Option<FailMode>,
+    #[serde(deserialize_with = "deserialize_option_from_str")]
+    pub select_mode: Option<SelectMode>,
+    pub retry: Option<u8>,
+    /// hedges the calls of the method, whatever its fail mode.
+    pub hedge: Option<HedgeConfig>,
+}
+
+/// the calls routed to the canary servers, see `CanaryRule`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CanaryConfig {
+    pub percent: u8,
+    /// predicates on the metadata of calls such as `uid % 100 < 5`.
+    pub rules: Vec<String>,
+}
+
+impl CanaryConfig {
+    pub fn rule(&self) -> Result<CanaryRule> {
+        let rules: Vec<&str> = self.rules.iter().map(String::as_str).collect();
+        CanaryRule::new(self.percent, &rules)
+    }
+}
+
+/// the hedging of a service method, see `HedgePolicy`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HedgeConfig {
+    pub percentile: f64,
+    pub delay_ms: u64,
+    pub max_hedges: u8,
+}
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        let policy = HedgePolicy::default();
+        HedgeConfig {
+            percentile: policy.percentile,
+            delay_ms: policy.delay.as_millis() as u64,
+            max_hedges: policy.max_hedges,
+        }
+    }
+}
+
+impl HedgeConfig {
+    pub fn policy(&self) -> HedgePolicy {
+        HedgePolicy {
+            percentile: self.percentile,
+            delay: Duration::from_millis(self.delay_ms),
+            max_hedges: self.max_hedges,
+        }
+    }
+}
+
+impl Default for XClientConfig {
+    fn default() -> Self {
+        let opt = Opt::default();
+        XClientConfig {
+            service_path: String::new(),
+            fail_mode: FailMode::Failfast,
+            select_mode: SelectMode::RandomSelect,
+            version: String::new(),
+            retry: opt.retry,
+            serialize_type: opt.serialize_type,
+            compress_type: opt.compress_type,
+            connect_timeout_ms: 0,
+            read_timeout_ms: 0,
+            write_timeout_ms: 0,
+            nodelay: opt.nodelay,
+            ttl: opt.ttl,
+            max_queued_bytes: opt.max_queued_bytes,
+            backpressure: opt.backpressure,
+            log_errors: opt.log_errors,
+            max_message_len: opt.max_message_len,
+            dns_ttl_ms: DNS_REFRESH_INTERVAL.as_millis() as u64,
+            servers: HashMap::new(),
+            registry: None,
+            tls: None,
+            methods: HashMap::new(),
+            warm_up: false,
+            canary: None,
+            services: HashMap::new(),
+        }
+    }
+}
+
+impl XClientConfig {
+    /// overrides the config by environment variables: `RPCX_FAIL_MODE`, `RPCX_SELECT_MODE`,
+    /// `RPCX_SERVICE_VERSION`, `RPCX_RETRY`, `RPCX_SERIALIZE_TYPE`, `RPCX_COMPRESS_TYPE`,
+    /// `RPCX_CONNECT_TIMEOUT_MS`, `RPCX_CALL_TIMEOUT_MS` for the read timeout,
+    /// `RPCX_WRITE_TIMEOUT_MS` and the registry variables of `RegistryConfig::apply_env`.
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Some(fail_mode) = env_var("RPCX_FAIL_MODE")? {
+            self.fail_mode = fail_mode;
+        }
+        if let Some(select_mode) = env_var("RPCX_SELECT_MODE")? {
+            self.select_mode = select_mode;
+        }
+        if let Some(version) = env_var("RPCX_SERVICE_VERSION")? {
+            self.version = version;
+        }
+        if let Some(retry) = env_var("RPCX_RETRY")? {
+            self.retry = retry;
+        }
+        if let Some(serialize_type) = env_var("RPCX_SERIALIZE_TYPE")? {
+            self.serialize_type = serialize_type;
+        }
+        if let Some(compress_type) = env_var("RPCX_COMPRESS_TYPE")? {
+            self.compress_type = compress_type;
+        }
+        if let Some(timeout) = env_var("RPCX_CONNECT_TIMEOUT_MS")? {
+            self.connect_timeout_ms = timeout;
+        }
+        if let Some(timeout) = env_var("RPCX_CALL_TIMEOUT_MS")? {
+            self.read_timeout_ms = timeout;
+        }
+        if let Some(timeout) = env_var("RPCX_WRITE_TIMEOUT_MS")? {
+            self.write_timeout_ms = timeout;
+        }
+        RegistryConfig::apply_env(&mut self.registry)
+    }
+
+    pub fn opt(&self) -> Opt {
+        Opt {
+            retry: self.retry,
+            compress_type: self.compress_type,
+            serialize_type: self.serialize_type,
+            connect_timeout: Duration::from_millis(self.connect_timeout_ms),
+            read_timeout: Duration::from_millis(self.read_timeout_ms),
+            write_timeout: Duration::from_millis(self.write_timeout_ms),
+            nodelay: self.nodelay,
+            ttl: self.ttl,
+            max_queued_bytes: self.max_queued_bytes,
+            backpressure: self.backpressure,
+            log_errors: self.log_errors,
+            max_message_len: self.max_message_len,
+        }
+    }
+}
+
+impl XClient<SharedSelector> {
+    /// creates a client from a TOML or YAML file of `XClientConfig`.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config: XClientConfig = load_config(path)?;
+        Self::with_config(&config)
+    }
+
+    /// creates a client from the config, overridden by environment variables.
+    pub fn with_config(config: &XClientConfig) -> Result<Self> {
+        let mut config = config.clone();
+        config.apply_env()?;
+        check(&config)?;
+
+        let selector = SharedSelector::new(config_selector(&config)?);
+        let registry_watch = watch_servers(&config, &selector)?;
+
+        let mut xc = XClient::new(
+            config.service_path.clone(),
+            config.fail_mode,
+            Box::new(selector),
+            config.opt(),
+        );
+        set_call_policies(&mut xc, &config);
+        set_service_opts(&mut xc, &config);
+        xc.set_dns_ttl(Duration::from_millis(config.dns_ttl_ms));
+        #[cfg(feature = "tls")]
+        set_tls(&mut xc, &config)?;
+        xc.canary = canary_rule(&config)?;
+        if config.warm_up {
+            xc.enable_warm_up();
+        }
+        xc.registry_watch = registry_watch;
+        xc.config = Some(config);
+        Ok(xc)
+    }
+
+    /// applies a new config to the client. Connections to the servers which are still
+    /// configured are kept, their timeouts are updated. They are all made again if TLS
+    /// changes.
+    ///
+    /// The service path can't be changed.
+    pub fn reload(&mut self, config: &XClientConfig) -> Result<()> {
+        let mut config = config.clone();
+        config.apply_env()?;
+        check(&config)?;
+        if config.service_path != self.service_path {
+            return Err(Error::new(
+                ErrorKind::Config,
+                "service_path can't be changed by reloading",
+            ));
+        }
+
+        let old = self.config.clone().unwrap_or_default();
+        if old.select_mode != config.select_mode
+            || old.version != config.version
+            || old.methods != config.methods
+            || old.canary.is_some() != config.canary.is_some()
+        {
+            self.selector.set_selector(config_selector(&config)?);
+        }
+        if old.registry != config.registry || old.servers != config.servers {
+            // the discovery of the old registry is closed
+            self.registry_watch = watch_servers(&config, &self.selector)?;
+            if config.registry.is_none() {
+                close_clients(self.remove_clients(|k| !config.servers.contains_key(k)));
+            }
+        }
+
+        self.fail_mode = config.fail_mode;
+        self.opt = config.opt();
+        set_call_policies(self, &config);
+        if old.services != config.services || old.opt() != config.opt() {
+            set_service_opts(self, &config);
+        }
+        self.set_dns_ttl(Duration::from_millis(config.dns_ttl_ms));
+        #[cfg(feature = "tls")]
+        {
+            if old.tls != config.tls {
+                // the servers are connected again with the new TLS
+                set_tls(self, &config)?;
+                close_clients(self.remove_clients(|_| true));
+            }
+        }
+        self.canary = canary_rule(&config)?;
+        if !config.warm_up {
+            self.warm_up = None;
+        } else if !old.warm_up {
+            self.enable_warm_up();
+        }
+        for client in self.clients.read().unwrap().values() {
+            client.set_timeouts(self.opt.read_timeout, self.opt.write_timeout)?;
+        }
+        self.config = Some(config);
+        Ok(())
+    }
+
+    /// reloads the config from a TOML or YAML file, see `reload`.
+    pub fn reload_from<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let config: XClientConfig = load_config(path)?;
+        self.reload(&config)
+    }
+}
+
+// creates the selector of the config, which tells the canary servers apart if it routes calls
+// to them.
+fn config_selector(config: &XClientConfig) -> Result<Box<dyn ClientSelector + Send>> {
+    if config.canary.is_none() {
+        return mode_selector(config);
+    }
+    Ok(Box::new(CanarySelector::new(
+        mode_selector(config)?,
+        mode_selector(config)?,
+    )))
+}
+
+fn canary_rule(config: &XClientConfig) -> Result<Option<CanaryRule>> {
+    config.canary.as_ref().map(CanaryConfig::rule).transpose()
+}
+
+// creates the selector of the select modes, restricted to the version of the config.
+fn mode_selector(config: &XClientConfig) -> Result<Box<dyn ClientSelector + Send>> {
+    let mut selector = new_selector(config.select_mode)?;
+    let methods: Vec<(&String, SelectMode)> = config
+        .methods
+        .iter()
+        .filter_map(|(method, mc)| mc.select_mode.map(|mode| (method, mode)))
+        .collect();
+    if !methods.is_empty() {
+        let mut method_selector = MethodSelector::new(selector);
+        for (method, select_mode) in methods {
+            method_selector.set_method_selector(method, new_selector(select_mode)?);
+        }
+        selector = Box::new(method_selector);
+    }
+    if config.version.is_empty() {
+        return Ok(selector);
+    }
+    Ok(Box::new(VersionSelector::new(selector, &config.version)?))
+}
+
+// replaces the call and hedge policies of the methods of the client by those of the config,
+// the policies of other services are kept.
+fn set_call_policies<S: ClientSelector>(xc: &mut XClient<S>, config: &XClientConfig) {
+    let service_path = xc.service_path.clone();
+    xc.policies.retain(|(path, _), _| *path != service_path);
+    xc.hedges.retain(|(path, _), _| *path != service_path);
+    for (method, mc) in &config.methods {
+        if let Some(hedge) = &mc.hedge {
+            xc.set_hedge_policy(method, hedge.policy());
+        }
+        if mc.fail_mode.is_none() && mc.retry.is_none() {
+            continue;
+        }
+        let policy = CallPolicy {
+            fail_mode: mc.fail_mode.unwrap_or(config.fail_mode),
+            retry: mc.retry.unwrap_or(config.retry),
+        };
+        xc.set_call_policy(method, policy);
+    }
+}
+
+// connects the servers over the TLS of the config, or without TLS if it has none.
+#[cfg(feature = "tls")]
+fn set_tls<S: ClientSelector>(xc: &mut XClient<S>, config: &XClientConfig) -> Result<()> {
+    xc.transport.tls = match &config.tls {
+        Some(tls) => Some(TlsConnector::with_config(tls)?),
+        None => None,
+    };
+    Ok(())
+}
+
+// replaces the options of the services of the client by those of the config.
+fn set_service_opts<S: ClientSelector>(xc: &mut XClient<S>, config: &XClientConfig) {
+    let removed: Vec<String> = xc
+        .service_opts
+        .keys()
+        .filter(|service_path| !config.services.contains_key(*service_path))
+        .cloned()
+        .collect();
+    for service_path in removed {
+        xc.remove_service_opt(&service_path);
+    }
+    let opt = config.opt();
+    for (service_path, sc) in &config.services {
+        xc.set_service_opt(service_path, sc.opt(opt));
+    }
+}
+
+fn check(config: &XClientConfig) -> Result<()> {
+    if config.service_path.is_empty() {
+        return Err(Error::new(ErrorKind::Config, "service_path is required"));
+    }
+    canary_rule(config)?;
+    #[cfg(not(feature = "tls"))]
+    {
+        if config.tls.is_some() {
+            return Err(Error::new(
+                ErrorKind::Config,
+                "TLS is not enabled, see the tls feature",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// the discovery of the registry of a config, which is closed once the config is replaced or
+/// the client is dropped.
+#[derive(Default)]
+pub(crate) struct RegistryWatch {
+    // the discovery and the handle of the selector it refers to, which is dropped after it
+    #[cfg(feature = "etcd-registry")]
+    etcd: Option<(Box<EtcdDiscovery<'static>>, Box<SharedSelector>)>,
+}
+
+#[cfg(feature = "etcd-registry")]
+impl Drop for RegistryWatch {
+    fn drop(&mut self) {
+        // the discovery releases the handle before it is freed
+        if let Some((disc, _)) = &self.etcd {
+            disc.close();
+        }
+    }
+}
+
+// feeds the selector with the servers of the registry or the static servers of the config.
+fn watch_servers(config: &XClientConfig, selector: &SharedSelector) -> Result<RegistryWatch> {
+    let source = selector.new_source();
+    let watch = RegistryWatch::default();
+    match &config.registry {
+        #[cfg(feature = "etcd-registry")]
+        Some(registry) => {
+            let addrs: Vec<&str> = registry.addrs.iter().map(String::as_str).collect();
+            let client = EtcdClient::new(&addrs, None)
+                .map_err(|err| Error::new(ErrorKind::Registry, format!("{:?}", err)))?;
+            // the discovery watches the registry until the watch is dropped. The handle is
+            // ignored once the source is replaced.
+            let base_path = registry.base_path.clone();
+            let service_path = config.service_path.clone();
+            let disc = if registry.cache_file.is_empty() {
+                EtcdDiscovery::new(client, base_path, service_path)
+            } else {
+                let cache_file = Path::new(&registry.cache_file);
+                EtcdDiscovery::with_cache_file(client, base_path, service_path, cache_file)
+            };
+            let disc = Box::new(disc);
+            let handle = Box::new(source);
+            // both are boxed, so the references stay valid while the watch owns them
+            let (disc_ref, handle_ref): (&'static EtcdDiscovery<'static>, &'static SharedSelector) =
+                unsafe { (&*(&*disc as *const _), &*(&*handle as *const _)) };
+            disc_ref.add_selector(handle_ref);
+            disc_ref.update_servers(&disc_ref.get_services());
+            return Ok(RegistryWatch {
+                etcd: Some((disc, handle)),
+            });
+        }
+        #[cfg(not(feature = "etcd-registry"))]
+        Some(_) => {
+            return Err(Error::new(
+                ErrorKind::Config,
+                "the etcd registry is not enabled, see the etcd-registry feature",
+            ));
+        }
+        None => source.update_server(&config.servers),
+    }
+    Ok(watch)
+}
@@ -1,17 +1,30 @@
 use super::selector::ClientSelector;
 
+#[cfg(feature = "etcd-registry")]
 use etcd::{
     kv::{self, KeyValueInfo},
     Client,
 };
+#[cfg(feature = "etcd-registry")]
 use hyper::client::HttpConnector;
+use rpcx_protocol::Result;
+#[cfg(any(feature = "etcd-registry", feature = "eureka-registry"))]
+use std::path::PathBuf;
 use std::{
     collections::HashMap,
-    mem::transmute,
+    fs,
     ops::Deref,
+    path::Path,
     sync::{Arc, RwLock},
+};
+#[cfg(feature = "etcd-registry")]
+use std::{
+    mem::transmute,
+    sync::atomic::{AtomicBool, Ordering},
     thread,
+    time::Duration,
 };
+#[cfg(feature = "etcd-registry")]
 use tokio::runtime::Runtime;
 
 pub trait Discovery<'a> {
@@ -59,69 +72,118 @@ impl<'a> Discovery<'a> for StaticDiscovery<'a> {
     fn close(&self) {}
 }
 
+#[cfg(feature = "etcd-registry")]
 #[derive(Default)]
 pub struct EtcdDiscovery<'a> {
     base_path: String,
     service_path: String,
     servers: Arc<RwLock<HashMap<String, String>>>,
     selectors: Arc<RwLock<Vec<&'a (dyn ClientSelector + Sync + Send + 'static)>>>,
+    closed: Arc<AtomicBool>,
 }
 
+#[cfg(feature = "etcd-registry")]
 impl<'a> EtcdDiscovery<'a> {
     pub fn new(
         client: Client<HttpConnector>,
         base_path: String,
         service_path: String,
+    ) -> EtcdDiscovery<'a> {
+        Self::start(client, base_path, service_path, None)
+    }
+
+    /// creates a discovery which snapshots the servers to `cache_file`, and starts with the
+    /// servers of the snapshot if etcd is unreachable.
+    pub fn with_cache_file(
+        client: Client<HttpConnector>,
+        base_path: String,
+        service_path: String,
+        cache_file: &Path,
+    ) -> EtcdDiscovery<'a> {
+        Self::start(client, base_path, service_path, Some(cache_file.to_owned()))
+    }
+
+    fn start(
+        client: Client<HttpConnector>,
+        base_path: String,
+        service_path: String,
+        cache_file: Option<PathBuf>,
     ) -> EtcdDiscovery<'a> {
         let d = EtcdDiscovery {
             base_path,
             service_path,
             servers: Arc::new(RwLock::new(HashMap::new())),
             selectors: Arc::new(RwLock::new(Vec::new())),
+            closed: Arc::new(AtomicBool::new(false)),
         };
 
         let mut prefix = d.base_path.clone();
         prefix.push('/');
         prefix.push_str(d.service_path.clone().as_str());
         prefix.push('/');
-        Self::list(&client, prefix.clone(), d.servers.clone());
+        let mut rt = Runtime::new().unwrap();
+        let listed = match Self::list(&mut rt, &client, &prefix) {
+            Some(servers) => {
+                cache_servers(&cache_file, &servers);
+                *d.servers.write().unwrap() = servers;
+                true
+            }
+            None => {
+                restore_servers(&cache_file, &d.servers);
+                false
+            }
+        };
 
         let selectors_cloned: Arc<RwLock<Vec<&(dyn ClientSelector + Sync + Send + 'static)>>> =
             unsafe { transmute(d.selectors.clone()) };
         let servers_cloned = d.servers.clone();
+        let closed = d.closed.clone();
 
         thread::spawn(move || {
-            Self::watch(client, prefix, selectors_cloned, servers_cloned);
+            Self::watch_and_cache(
+                rt,
+                client,
+                prefix,
+                selectors_cloned,
+                servers_cloned,
+                cache_file,
+                listed,
+                closed,
+            );
         });
         d
     }
+
+    // returns all the servers under the prefix, or None if etcd is unreachable.
     fn list(
+        rt: &mut Runtime,
         etc_client: &Client<HttpConnector>,
-        prefix: String,
-        servers: Arc<RwLock<HashMap<String, String>>>,
-    ) {
-        let key: String = prefix;
-
+        prefix: &str,
+    ) -> Option<HashMap<String, String>> {
         let mut get_opt: kv::GetOptions = Default::default();
         get_opt.recursive = true;
-        let op = kv::get(etc_client, key.as_str(), get_opt);
+        let op = kv::get(etc_client, prefix, get_opt);
 
-        match Runtime::new().unwrap().block_on(op) {
+        match rt.block_on(op) {
             Ok(resp) => {
                 let kvi: KeyValueInfo = resp.data;
+                let mut m = HashMap::new();
                 if let Some(nodes) = kvi.node.nodes {
-                    let mut m = servers.write().unwrap();
                     for node in &nodes {
                         if node.key.is_some() && node.value.is_some() {
                             let k = node.key.as_ref().unwrap().clone();
                             let v = node.value.as_ref().unwrap().clone();
-                            let k2 = k.trim_start_matches(&key).to_owned();
+                            let k2 = k.trim_start_matches(prefix).to_owned();
                             m.insert(k2, v);
                         }
                     }
                 }
+                Some(m)
+            }
+            Err(err) => {
+                eprintln!("{:?}", err);
+                None
             }
-            Err(err) => eprintln!("{:?}", err),
         }
     }
 
@@ -130,13 +192,60 @@ impl<'a> EtcdDiscovery<'a> {
         prefix: String,
         selectors: Arc<RwLock<Vec<&(dyn ClientSelector + Sync + Send + 'static)>>>,
         servers: Arc<RwLock<HashMap<String, String>>>,
+    ) {
+        let closed = Arc::new(AtomicBool::new(false));
+        Self::watch_and_cache(
+            Runtime::new().unwrap(),
+            etc_client,
+            prefix,
+            selectors,
+            servers,
+            None,
+            false,
+            closed,
+        )
+    }
+
+    // watches the servers until the discovery is closed, which is seen once the pending watch
+    // returns. The servers are listed again and replaced while they are not `listed`, which
+    // they are not after the watch fails, the changes made while etcd was unreachable are
+    // missed by the watch.
+    #[allow(clippy::too_many_arguments)]
+    fn watch_and_cache(
+        mut rt: Runtime,
+        etc_client: Client<HttpConnector>,
+        prefix: String,
+        selectors: Arc<RwLock<Vec<&(dyn ClientSelector + Sync + Send + 'static)>>>,
+        servers: Arc<RwLock<HashMap<String, String>>>,
+        cache_file: Option<PathBuf>,
+        mut listed: bool,
+        closed: Arc<AtomicBool>,
     ) {
         let key = prefix;
         let mut watch_opt: kv::WatchOptions = Default::default();
         watch_opt.recursive = true;
-        loop {
+        while !closed.load(Ordering::SeqCst) {
+            if !listed {
+                match Self::list(&mut rt, &etc_client, &key) {
+                    Some(listed_servers) => {
+                        let mut m = servers.write().unwrap();
+                        if *m != listed_servers {
+                            *m = listed_servers;
+                            notify_selectors(&selectors, &m);
+                            cache_servers(&cache_file, &m);
+                        }
+                        listed = true;
+                    }
+                    None => {
+                        // doesn't spin while etcd is unreachable
+                        thread::sleep(Duration::from_secs(1));
+                        continue;
+                    }
+                }
+            }
+
             let changed = kv::watch(&etc_client, key.as_str(), watch_opt);
-            match Runtime::new().unwrap().block_on(changed) {
+            match rt.block_on(changed) {
                 Ok(resp) => {
                     let kvi: KeyValueInfo = resp.data;
                     let node = kvi.node;
@@ -164,14 +273,16 @@ impl<'a> EtcdDiscovery<'a> {
                     }
 
                     if changed {
-                        let selectors = selectors.write().unwrap();
-                        let v = selectors.deref();
-                        for s in v {
-                            s.update_server(&*m);
-                        }
+                        notify_selectors(&selectors, &m);
+                        cache_servers(&cache_file, &m);
                     }
                 }
-                Err(err) => eprintln!("{}", err),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    listed = false;
+                    // doesn't spin while etcd is unreachable
+                    thread::sleep(Duration::from_secs(1));
+                }
             }
         }
     }
@@ -185,6 +296,7 @@ impl<'a> EtcdDiscovery<'a> {
     }
 }
 
+#[cfg(feature = "etcd-registry")]
 impl<'a> Discovery<'a> for EtcdDiscovery<'a> {
     fn get_services(&self) -> HashMap<String, String> {
         let mut servers = HashMap::new();
@@ -202,5 +314,79 @@ impl<'a> Discovery<'a> for EtcdDiscovery<'a> {
         let ss = self.servers.read().unwrap();
         s.update_server(&*ss);
     }
-    fn close(&self) {}
+
+    /// stops watching the servers. The selectors are released at once, the watch ends once
+    /// its pending request returns.
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.selectors.write().unwrap().clear();
+    }
+}
+
+#[cfg(feature = "etcd-registry")]
+fn notify_selectors(
+    selectors: &RwLock<Vec<&(dyn ClientSelector + Sync + Send + 'static)>>,
+    servers: &HashMap<String, String>,
+) {
+    let selectors = selectors.write().unwrap();
+    for s in selectors.deref() {
+        s.update_server(servers);
+    }
+}
+
+/// saves the servers and their metadata to a file, one server and its metadata separated by
+/// a tab per line.
+pub fn save_servers(path: &Path, servers: &HashMap<String, String>) -> Result<()> {
+    let mut data = String::new();
+    for (k, v) in servers {
+        data.push_str(&format!("{}\t{}\n", k, v));
+    }
+    // replaces the file at once, a crash can't leave a partial list
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// loads the servers saved by `save_servers`.
+pub fn load_servers(path: &Path) -> Result<HashMap<String, String>> {
+    let data = fs::read_to_string(path)?;
+    Ok(data
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut kv = line.splitn(2, '\t');
+            let k = kv.next().unwrap_or_default().to_owned();
+            let v = kv.next().unwrap_or_default().to_owned();
+            (k, v)
+        })
+        .collect())
+}
+
+// snapshots the servers fetched from a registry. Empty lists are not saved, they would
+// override the snapshot of a healthy registry.
+#[cfg(any(feature = "etcd-registry", feature = "eureka-registry"))]
+pub(crate) fn cache_servers(cache_file: &Option<PathBuf>, servers: &HashMap<String, String>) {
+    if let Some(path) = cache_file {
+        if servers.is_empty() {
+            return;
+        }
+        if let Err(err) = save_servers(path, servers) {
+            eprintln!("failed to save servers to {}: {}", path.display(), err);
+        }
+    }
+}
+
+// falls back to the snapshot if the registry is unreachable.
+#[cfg(any(feature = "etcd-registry", feature = "eureka-registry"))]
+pub(crate) fn restore_servers(
+    cache_file: &Option<PathBuf>,
+    servers: &RwLock<HashMap<String, String>>,
+) {
+    if let Some(path) = cache_file {
+        match load_servers(path) {
+            Ok(cached) => *servers.write().unwrap() = cached,
+            Err(err) => eprintln!("failed to load servers from {}: {}", path.display(), err),
+        }
+    }
 }
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    mem::transmute,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use futures::{sync::oneshot, Future, Stream};
+use hyper::{client::HttpConnector, header::ACCEPT, Body, Client, Request};
+use rpcx_protocol::*;
+use tokio::runtime::{Runtime, TaskExecutor};
+
+use super::{
+    discovery::{cache_servers, restore_servers},
+    selector::ClientSelector,
+    Discovery,
+};
+
+type Selectors<'a> = Arc<RwLock<Vec<&'a (dyn ClientSelector + Sync + Send + 'static)>>>;
+
+// the instances of all the applications by their application and id, which the deltas of
+// Eureka are applied to.
+type Registry = HashMap<(String, String), EurekaInstance>;
+
+/// discovers the servers of a service from Netflix Eureka, the instances of the application
+/// registered by `EurekaRegister`. Only instances which are `UP` are selected.
+///
+/// The registry is fetched at first, then the deltas of Eureka are polled every
+/// `poll_interval`. The registry is fetched again if it doesn't match the hash of a delta,
+/// such as after deltas are missed while Eureka is unreachable.
+pub struct EurekaDiscovery<'a> {
+    servers: Arc<RwLock<HashMap<String, String>>>,
+    selectors: Selectors<'a>,
+}
+
+impl<'a> EurekaDiscovery<'a> {
+    /// `eureka_url` is the url of the REST API, for example `http://127.0.0.1:8761/eureka`.
+    pub fn new(
+        eureka_url: String,
+        service_path: String,
+        poll_interval: Duration,
+    ) -> EurekaDiscovery<'a> {
+        Self::start(eureka_url, service_path, poll_interval, None)
+    }
+
+    /// creates a discovery which snapshots the servers to `cache_file`, and starts with the
+    /// servers of the snapshot if Eureka is unreachable.
+    pub fn with_cache_file(
+        eureka_url: String,
+        service_path: String,
+        poll_interval: Duration,
+        cache_file: &Path,
+    ) -> EurekaDiscovery<'a> {
+        Self::start(
+            eureka_url,
+            service_path,
+            poll_interval,
+            Some(cache_file.to_owned()),
+        )
+    }
+
+    fn start(
+        eureka_url: String,
+        service_path: String,
+        poll_interval: Duration,
+        cache_file: Option<PathBuf>,
+    ) -> EurekaDiscovery<'a> {
+        let d = EurekaDiscovery {
+            servers: Arc::new(RwLock::new(HashMap::new())),
+            selectors: Arc::new(RwLock::new(Vec::new())),
+        };
+
+        // runs the connections of the client and the requests, owned by the polling thread
+        let runtime = Runtime::new().unwrap();
+        let client = Client::builder().executor(runtime.executor()).build_http();
+        let eureka_url = eureka_url.trim_end_matches('/').to_owned();
+        let app = eureka_app(&service_path);
+        let mut registry = match fetch_registry(&runtime.executor(), &client, &eureka_url) {
+            Ok(registry) => {
+                let servers = app_servers(&registry, &app);
+                cache_servers(&cache_file, &servers);
+                *d.servers.write().unwrap() = servers;
+                registry
+            }
+            Err(err) => {
+                eprintln!("failed to fetch {}. err: {}", app, err);
+                restore_servers(&cache_file, &d.servers);
+                // mismatches the first delta, which fetches the registry again
+                Registry::new()
+            }
+        };
+
+        let selectors_cloned: Selectors<'static> = unsafe { transmute(d.selectors.clone()) };
+        let servers_cloned = d.servers.clone();
+        thread::spawn(move || loop {
+            thread::sleep(poll_interval);
+            if let Err(err) = Self::poll(
+                &runtime.executor(),
+                &client,
+                &eureka_url,
+                &app,
+                &mut registry,
+                &selectors_cloned,
+                &servers_cloned,
+                &cache_file,
+            ) {
+                eprintln!("failed to fetch the delta of {}. err: {}", app, err);
+            }
+        });
+        d
+    }
+
+    // applies the delta of the registry, and the changes of the instances of the application.
+    #[allow(clippy::too_many_arguments)]
+    fn poll(
+        executor: &TaskExecutor,
+        client: &Client<HttpConnector>,
+        eureka_url: &str,
+        app: &str,
+        registry: &mut Registry,
+        selectors: &Selectors<'static>,
+        servers: &RwLock<HashMap<String, String>>,
+        cache_file: &Option<PathBuf>,
+    ) -> Result<()> {
+        let data = get(executor, client, &format!("{}/apps/delta", eureka_url))?;
+        let apps = EurekaApplications::from_json(&data)?;
+        for application in &apps.application {
+            for instance in &application.instance {
+                let key = (application.name.clone(), instance.instance_id.clone());
+                if instance.action_type.as_ref().map(String::as_str) == Some("DELETED") {
+                    registry.remove(&key);
+                } else {
+                    registry.insert(key, instance.clone());
+                }
+            }
+        }
+        if eureka_hashcode(registry.values()) != apps.apps_hashcode {
+            *registry = fetch_registry(executor, client, eureka_url)?;
+        }
+
+        let app_servers = app_servers(registry, app);
+        let mut m = servers.write().unwrap();
+        if *m != app_servers {
+            *m = app_servers;
+            let selectors = selectors.read().unwrap();
+            for s in selectors.deref() {
+                s.update_server(&*m);
+            }
+            cache_servers(cache_file, &m);
+        }
+        Ok(())
+    }
+
+    pub fn update_servers(&self, servers: &HashMap<String, String>) {
+        let selectors = self.selectors.read().unwrap();
+        for s in selectors.deref() {
+            s.update_server(servers);
+        }
+    }
+}
+
+// fetches the instances of all the applications.
+fn fetch_registry(
+    executor: &TaskExecutor,
+    client: &Client<HttpConnector>,
+    eureka_url: &str,
+) -> Result<Registry> {
+    let data = get(executor, client, &format!("{}/apps", eureka_url))?;
+    let apps = EurekaApplications::from_json(&data)?;
+    let mut registry = Registry::new();
+    for application in apps.application {
+        for instance in application.instance {
+            registry.insert(
+                (application.name.clone(), instance.instance_id.clone()),
+                instance,
+            );
+        }
+    }
+    Ok(registry)
+}
+
+// returns the servers of the instances of the application which are up.
+fn app_servers(registry: &Registry, app: &str) -> HashMap<String, String> {
+    registry
+        .iter()
+        .filter(|((name, _), instance)| name == app && instance.is_up())
+        .map(|(_, instance)| instance.server())
+        .collect()
+}
+
+// gets a document of Eureka in JSON.
+fn get(executor: &TaskExecutor, client: &Client<HttpConnector>, uri: &str) -> Result<Vec<u8>> {
+    let req = Request::get(uri)
+        .header(ACCEPT, "application/json")
+        .body(Body::empty())
+        .map_err(|err| Error::new(ErrorKind::Registry, err))?;
+    let op = client.request(req).and_then(|res| {
+        let status = res.status();
+        res.into_body()
+            .concat2()
+            .map(move |body| (status, body.to_vec()))
+    });
+    let (status, body) = oneshot::spawn(op, executor)
+        .wait()
+        .map_err(|err| Error::new(ErrorKind::Registry, err))?;
+    if !status.is_success() {
+        return Err(Error::new(
+            ErrorKind::Registry,
+            format!("failed to get {}: {}", uri, status),
+        ));
+    }
+    Ok(body)
+}
+
+impl<'a> Discovery<'a> for EurekaDiscovery<'a> {
+    fn get_services(&self) -> HashMap<String, String> {
+        self.servers.read().unwrap().clone()
+    }
+
+    fn add_selector(&'a self, s: &'a (dyn ClientSelector + Sync + Send + 'static)) {
+        let mut selectors = self.selectors.write().unwrap();
+        selectors.push(s);
+
+        let ss = self.servers.read().unwrap();
+        s.update_server(&*ss);
+    }
+    fn close(&self) {}
+}
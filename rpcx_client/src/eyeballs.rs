@@ -0,0 +1,95 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::{channel, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// how long a connection attempt runs before the next address is tried in parallel, when a
+/// server resolves to both IPv6 and IPv4 addresses (RFC 8305).
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// connects the first reachable address, a zero `timeout` means no timeout for an attempt.
+///
+/// Addresses of a single family are tried in turn. With both families, the addresses are
+/// interleaved by family and the attempts are staggered by `CONNECTION_ATTEMPT_DELAY`, so a
+/// broken IPv6 path doesn't stall connections until it times out.
+pub(crate) fn connect(addrs: &[SocketAddr], timeout: Duration) -> io::Result<TcpStream> {
+    let dual_stack =
+        addrs.iter().any(|addr| addr.is_ipv6()) && addrs.iter().any(|addr| addr.is_ipv4());
+    if dual_stack {
+        return connect_staggered(interleave(addrs), timeout);
+    }
+
+    let mut last_err = None;
+    for addr in addrs {
+        match connect_addr(addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address")))
+}
+
+fn connect_addr(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    if timeout.as_millis() == 0 {
+        TcpStream::connect(addr)
+    } else {
+        TcpStream::connect_timeout(addr, timeout)
+    }
+}
+
+// alternates the families, starting with the family of the first address.
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_v6 = addrs[0].is_ipv6();
+    let (mut first, mut second): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        addrs.iter().partition(|addr| addr.is_ipv6() == first_v6);
+    let mut interleaved = Vec::with_capacity(addrs.len());
+    first.reverse();
+    second.reverse();
+    while !first.is_empty() || !second.is_empty() {
+        interleaved.extend(first.pop());
+        interleaved.extend(second.pop());
+    }
+    interleaved
+}
+
+// starts an attempt for each address, the next one when the previous failed or once the
+// delay passed, and returns the first connection. The connections of later attempts are
+// closed.
+fn connect_staggered(addrs: Vec<SocketAddr>, timeout: Duration) -> io::Result<TcpStream> {
+    let (sender, receiver) = channel();
+    let mut addrs = addrs.into_iter();
+    let mut running = 0;
+    let mut last_err = None;
+    loop {
+        let more = match addrs.next() {
+            Some(addr) => {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let _ = sender.send(connect_addr(&addr, timeout));
+                });
+                running += 1;
+                !addrs.as_slice().is_empty()
+            }
+            None if running == 0 => break,
+            None => false,
+        };
+
+        let rt = if more {
+            receiver.recv_timeout(CONNECTION_ATTEMPT_DELAY)
+        } else {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match rt {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(err)) => {
+                running -= 1;
+                last_err = Some(err);
+            }
+            Err(_) => {}
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address")))
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{Shutdown, TcpStream},
+    path::Path,
+};
+
+use super::client::Client;
+use rpcx_protocol::*;
+
+impl Client {
+    /// uploads a local file to the `_filetransfer` service of the server as `file_name`.
+    ///
+    /// If a previous upload of the same file was interrupted, only the missing bytes are sent.
+    pub fn upload_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        file_name: &str,
+        meta: &Metadata,
+    ) -> Result<()> {
+        let mut file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let args = FileTransferArgs {
+            file_name: file_name.to_owned(),
+            file_size,
+            meta: meta.clone(),
+        };
+        let reply = self.call_file_transfer(FILE_TRANSFER_UPLOAD, &args)?;
+
+        let mut conn = open_data_conn(&reply)?;
+        file.seek(SeekFrom::Start(reply.offset))?;
+        io::copy(
+            &mut file.take(file_size.saturating_sub(reply.offset)),
+            &mut conn,
+        )?;
+        conn.flush()?;
+
+        // the server acknowledges the complete file
+        let mut ack = [0u8; 1];
+        conn.read_exact(&mut ack)
+            .map_err(|err| Error::new(ErrorKind::Network, err))?;
+        let _ = conn.shutdown(Shutdown::Both);
+        Ok(())
+    }
+
+    /// downloads `file_name` from the `_filetransfer` service of the server to a local file.
+    ///
+    /// If the local file exists, it is taken as an interrupted download and only the rest of
+    /// the file is fetched.
+    pub fn download_file<P: AsRef<Path>>(
+        &mut self,
+        file_name: &str,
+        path: P,
+        meta: &Metadata,
+    ) -> Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let args = DownloadFileArgs {
+            file_name: file_name.to_owned(),
+            offset: file.metadata()?.len(),
+            meta: meta.clone(),
+        };
+        let reply = self.call_file_transfer(FILE_TRANSFER_DOWNLOAD, &args)?;
+
+        let mut conn = open_data_conn(&reply)?;
+        let n = io::copy(&mut conn, &mut file)?;
+        file.flush()?;
+        let _ = conn.shutdown(Shutdown::Both);
+
+        if reply.offset + n != reply.file_size {
+            return Err(Error::new(
+                ErrorKind::Network,
+                format!("download of {} is interrupted", file_name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn call_file_transfer(
+        &mut self,
+        service_method: &str,
+        args: &dyn RpcxParam,
+    ) -> Result<FileTransferReply> {
+        let metadata = HashMap::new();
+        self.call(
+            FILE_TRANSFER_SERVICE,
+            service_method,
+            false,
+            &metadata,
+            args,
+        )
+        .unwrap()
+    }
+}
+
+fn open_data_conn(reply: &FileTransferReply) -> Result<TcpStream> {
+    let mut conn = TcpStream::connect(reply.addr.as_str())?;
+    conn.write_all(&reply.token)?;
+    Ok(conn)
+}
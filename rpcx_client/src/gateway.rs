@@ -0,0 +1,222 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use futures::{
+    future::{self, Either},
+    sync::oneshot,
+    Future, Stream,
+};
+use hyper::{client::HttpConnector, Body, Client as HyperClient, Request, Response};
+use tokio::{runtime::Runtime, timer::Timeout};
+
+use super::client::Opt;
+use rpcx_protocol::{http::*, *};
+
+/// a client that invokes rpcx services through a rpcx-gateway over http.
+///
+/// It is used where raw tcp connections to the backends are not allowed. The requests are
+/// plain http POSTs carrying the X-RPCX-* headers.
+///
+/// The `connect_timeout` and the `read_timeout` of `opt` apply like they do to `Client`, a
+/// call which is not replied within the read timeout fails with `ErrorKind::Timeout`.
+pub struct GatewayClient {
+    pub opt: Opt,
+    url: String,
+    seq: AtomicU64,
+    // the http client and the connect timeout it is built with
+    client: HyperClient<HttpConnector>,
+    connect_timeout: Duration,
+    runtime: Runtime,
+}
+
+impl GatewayClient {
+    /// Creates a client for the gateway at `addr`, which is either a url or `host:port`.
+    pub fn new(addr: &str) -> Result<GatewayClient> {
+        let url = if addr.starts_with("http://") {
+            addr.to_owned()
+        } else {
+            format!("http://{}/", addr)
+        };
+        let runtime = Runtime::new()?;
+        let opt = Opt::default();
+        let client = build_client(&runtime, opt.connect_timeout);
+
+        Ok(GatewayClient {
+            connect_timeout: opt.connect_timeout,
+            opt,
+            url,
+            seq: AtomicU64::new(0),
+            client,
+            runtime,
+        })
+    }
+
+    // returns the http client, which is built again if the connect timeout is changed.
+    fn client(&mut self) -> HyperClient<HttpConnector> {
+        if self.connect_timeout != self.opt.connect_timeout {
+            self.connect_timeout = self.opt.connect_timeout;
+            self.client = build_client(&self.runtime, self.connect_timeout);
+        }
+        self.client.clone()
+    }
+
+    fn build_request(
+        &self,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Result<Request<Body>> {
+        let mut req = Message::new();
+        req.set_version(PROTOCOL_VERSION);
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(self.opt.serialize_type);
+        req.set_oneway(is_oneway);
+        req.set_seq(self.seq.fetch_add(1, Ordering::SeqCst));
+        req.service_path = service_path.to_string();
+        req.service_method = service_method.to_string();
+        req.metadata.replace(metadata.clone());
+
+        let mut builder = Request::builder();
+        builder.method("POST").uri(self.url.as_str());
+        for (k, v) in message_to_headers(&req) {
+            builder.header(k.as_str(), v.as_str());
+        }
+        let payload = args.into_bytes(self.opt.serialize_type)?;
+        builder
+            .body(Body::from(payload))
+            .map_err(|err| Error::new(ErrorKind::Client, err))
+    }
+
+    fn send<T>(
+        &mut self,
+        req: Request<Body>,
+    ) -> impl Future<Item = Result<T>, Error = Error> + Send + 'static
+    where
+        T: RpcxParam + Default + Send + 'static,
+    {
+        let st = self.opt.serialize_type;
+        let read_timeout = self.opt.read_timeout;
+        let f = self
+            .client()
+            .request(req)
+            .and_then(|resp: Response<Body>| {
+                let (parts, body) = resp.into_parts();
+                body.concat2().map(move |chunk| (parts, chunk))
+            })
+            .map_err(|err| Error::new(ErrorKind::Network, err))
+            .map(move |(parts, chunk)| {
+                let error = parts
+                    .headers
+                    .get(X_ERROR_MESSAGE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(percent_decode);
+                if let Some(err) = error {
+                    // the error metadata is carried in the X-RPCX-Meta header
+                    let headers: Vec<(String, String)> = parts
+                        .headers
+                        .iter()
+                        .filter_map(|(k, v)| {
+                            v.to_str()
+                                .ok()
+                                .map(|v| (k.as_str().to_owned(), v.to_owned()))
+                        })
+                        .collect();
+                    return Err(message_from_headers(&headers, Vec::new())
+                        .ok()
+                        .and_then(|msg| Error::from_reply(&msg))
+                        .unwrap_or_else(|| Error::new(ErrorKind::Server, err)));
+                }
+                if !parts.status.is_success() {
+                    return Err(Error::new(
+                        ErrorKind::Server,
+                        format!("gateway returned http status {}", parts.status),
+                    ));
+                }
+
+                let mut reply: T = Default::default();
+                reply.from_slice(st, &chunk).map(|_| reply)
+            });
+
+        if read_timeout.as_millis() == 0 {
+            return Either::A(f);
+        }
+        Either::B(Timeout::new(f, read_timeout).map_err(|err| {
+            if err.is_elapsed() {
+                return Error::new(ErrorKind::Timeout, "call timed out");
+            }
+            err.into_inner()
+                .unwrap_or_else(|| Error::new(ErrorKind::Client, "the timer of the call failed"))
+        }))
+    }
+
+    pub fn call<T>(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default + Send + 'static,
+    {
+        let req = match self.build_request(service_path, service_method, is_oneway, metadata, args)
+        {
+            Ok(req) => req,
+            Err(err) => return Some(Err(err)),
+        };
+        let f = self.send::<T>(req);
+
+        if is_oneway {
+            self.runtime.spawn(f.then(|_| Ok::<(), ()>(())));
+            return None;
+        }
+
+        match self.runtime.block_on(f) {
+            Ok(rt) => Some(rt),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    pub fn acall<T>(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync>
+    where
+        T: RpcxParam + Default + Sync + Send + 'static,
+    {
+        let req = match self.build_request(service_path, service_method, false, metadata, args) {
+            Ok(req) => req,
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        // the request is driven by the runtime of this client so the returned future can be
+        // waited anywhere.
+        let (tx, rx) = oneshot::channel();
+        let f = self.send::<T>(req).then(move |rt| {
+            let _ = tx.send(rt.and_then(|r| r));
+            Ok::<(), ()>(())
+        });
+        self.runtime.spawn(f);
+
+        Box::new(rx.map_err(|_| Error::new(ErrorKind::Client, "call is canceled")))
+    }
+}
+
+// builds the http client whose connections time out after `connect_timeout`, zero for none.
+fn build_client(runtime: &Runtime, connect_timeout: Duration) -> HyperClient<HttpConnector> {
+    let mut connector = HttpConnector::new(4);
+    if connect_timeout.as_millis() > 0 {
+        connector.set_connect_timeout(Some(connect_timeout));
+    }
+    HyperClient::builder()
+        .executor(runtime.executor())
+        .build(connector)
+}
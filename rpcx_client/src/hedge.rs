@@ -0,0 +1,125 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    executor::{self, Notify, NotifyHandle, Spawn},
+    Async,
+};
+use rpcx_protocol::{call::CallFuture, Error, Result, RpcxParam};
+
+use super::client::Client;
+
+// the number of recent latencies of a method the delay of hedges is computed from.
+const HEDGE_WINDOW: usize = 100;
+// the latencies needed before the percentile replaces the delay of the policy.
+const HEDGE_MIN_SAMPLES: usize = 10;
+
+/// hedges the calls of a method: if the selected server doesn't reply in time, the call is
+/// sent to another server too and the first successful reply is used. It is independent of
+/// the fail mode, only idempotent methods such as reads should be hedged.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HedgePolicy {
+    /// the percentile of the recent latencies of the method after which a hedge is sent.
+    pub percentile: f64,
+    /// the delay of hedges until enough latencies are recorded.
+    pub delay: Duration,
+    /// the maximum number of hedges of a call.
+    pub max_hedges: u8,
+}
+
+impl Default for HedgePolicy {
+    fn default() -> Self {
+        HedgePolicy {
+            percentile: 0.95,
+            delay: Duration::from_millis(100),
+            max_hedges: 1,
+        }
+    }
+}
+
+// the policy of a method and its recent latencies.
+pub(crate) struct Hedge {
+    pub(crate) policy: HedgePolicy,
+    latencies: Mutex<VecDeque<Duration>>,
+}
+
+impl Hedge {
+    pub(crate) fn new(policy: HedgePolicy) -> Self {
+        Hedge {
+            policy,
+            latencies: Mutex::new(VecDeque::with_capacity(HEDGE_WINDOW)),
+        }
+    }
+
+    // records the latency of a successful call.
+    pub(crate) fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == HEDGE_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    // returns how long a call waits for a reply before it is hedged.
+    pub(crate) fn delay(&self) -> Duration {
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.len() < HEDGE_MIN_SAMPLES {
+            return self.policy.delay;
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().cloned().collect();
+        sorted.sort();
+        let percentile = self.policy.percentile.max(0.0).min(1.0);
+        let idx = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+        sorted[idx]
+    }
+}
+
+// wakes the thread waiting for hedged calls.
+struct ThreadNotify(Thread);
+
+impl Notify for ThreadNotify {
+    fn notify(&self, _id: usize) {
+        self.0.unpark();
+    }
+}
+
+// returns a handle which wakes the current thread when a polled call is finished.
+pub(crate) fn thread_notify() -> NotifyHandle {
+    NotifyHandle::from(Arc::new(ThreadNotify(thread::current())))
+}
+
+// a call sent to a server, which is polled until it is finished.
+pub(crate) struct SentCall {
+    pub(crate) server: String,
+    pub(crate) start: Instant,
+    client: Arc<Client>,
+    future: Spawn<CallFuture>,
+}
+
+impl SentCall {
+    pub(crate) fn new(server: &str, client: Arc<Client>, future: CallFuture) -> Self {
+        SentCall {
+            server: server.to_owned(),
+            start: Instant::now(),
+            client,
+            future: executor::spawn(future),
+        }
+    }
+
+    // returns the result of the call if it is finished, the thread of the handle is woken
+    // once it is otherwise.
+    pub(crate) fn poll<T>(&mut self, notify: &NotifyHandle) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        match self.future.poll_future_notify(notify, 0) {
+            Ok(Async::NotReady) => None,
+            Ok(Async::Ready(arc_call)) => Some(self.client.reply(arc_call.unwrap())),
+            Err(err) => Some(Err(Error::from(err))),
+        }
+    }
+}
@@ -1,33 +1,85 @@
-pub mod client;
-pub mod discovery;
-pub mod selector;
-pub mod xclient;
+// the clients over the sockets and the threads of std, which don't build for wasm32.
+macro_rules! native {
+    ($($item:item)*) => {
+        $(
+            #[cfg(not(target_arch = "wasm32"))]
+            $item
+        )*
+    };
+}
+
+#[cfg(feature = "wasm")]
+mod websocket;
+#[cfg(feature = "wasm")]
+pub use websocket::{WsClient, WsError};
+
+native! {
+    mod budget;
+    mod cache;
+    mod canary;
+    pub mod client;
+    mod config;
+    pub mod discovery;
+    #[cfg(feature = "eureka-registry")]
+    mod eureka;
+    mod eyeballs;
+    mod filetransfer;
+    #[cfg(feature = "http-gateway")]
+    pub mod gateway;
+    mod hedge;
+    pub mod mock;
+    mod pending;
+    mod resolver;
+    pub mod selector;
+    mod stats;
+    mod timer;
+    #[cfg(feature = "tls")]
+    mod tls;
+    mod warmup;
+    pub mod xclient;
 
-pub use client::*;
-pub use discovery::*;
-pub use selector::*;
-pub use xclient::*;
+    pub use budget::RetryBudget;
+    pub use cache::CacheStats;
+    pub use canary::*;
+    pub use client::*;
+    pub use config::{CanaryConfig, HedgeConfig, MethodConfig, ServiceConfig, XClientConfig};
+    pub use discovery::*;
+    #[cfg(feature = "eureka-registry")]
+    pub use eureka::EurekaDiscovery;
+    pub use eyeballs::CONNECTION_ATTEMPT_DELAY;
+    #[cfg(feature = "http-gateway")]
+    pub use gateway::*;
+    pub use hedge::HedgePolicy;
+    pub use mock::*;
+    pub use resolver::DNS_REFRESH_INTERVAL;
+    pub use selector::*;
+    pub use stats::{ConnState, EndpointStats, LATENCY_WINDOW};
+    #[cfg(feature = "tls")]
+    pub use tls::TlsConnector;
+    pub use warmup::WARM_UP_INTERVAL;
+    pub use xclient::*;
 
-use futures::Future;
-use rpcx_protocol::{Error, Metadata, Result, RpcxParam};
+    use futures::Future;
+    use rpcx_protocol::{Error, Metadata, Result, RpcxParam};
 
-pub trait RpcxClient {
-    fn call<T>(
-        &mut self,
-        service_method: &str,
-        is_oneway: bool,
-        metadata: &Metadata,
-        args: &dyn RpcxParam,
-    ) -> Option<Result<T>>
-    where
-        T: RpcxParam + Default;
+    pub trait RpcxClient {
+        fn call<T>(
+            &mut self,
+            service_method: &str,
+            is_oneway: bool,
+            metadata: &Metadata,
+            args: &dyn RpcxParam,
+        ) -> Option<Result<T>>
+        where
+            T: RpcxParam + Default;
 
-    fn acall<T>(
-        &mut self,
-        service_method: &str,
-        metadata: &Metadata,
-        args: &dyn RpcxParam,
-    ) -> Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync>
-    where
-        T: RpcxParam + Default + Sync + Send + 'static;
+        fn acall<T>(
+            &mut self,
+            service_method: &str,
+            metadata: &Metadata,
+            args: &dyn RpcxParam,
+        ) -> Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync>
+        where
+            T: RpcxParam + Default + Sync + Send + 'static;
+    }
 }
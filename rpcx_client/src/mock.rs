@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use futures::{future, Future};
+use rpcx_protocol::{Error, ErrorKind, Metadata, Result, RpcxParam, SerializeType};
+
+use super::RpcxClient;
+
+type MockFn = Arc<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+
+#[derive(Default)]
+struct MockMethod {
+    handler: Option<MockFn>,
+    latency: Duration,
+    calls: usize,
+}
+
+/// a `RpcxClient` serving scripted responses instead of calling servers, for unit testing the
+/// consumers of a service.
+///
+/// Calls to methods without a scripted response fail with `ErrorKind::Server`, like calls to
+/// services which are not registered.
+pub struct MockClient {
+    pub serialize_type: SerializeType,
+    service_path: String,
+    methods: Mutex<HashMap<String, MockMethod>>,
+}
+
+impl std::fmt::Debug for MockClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockClient")
+            .field("serialize_type", &self.serialize_type)
+            .field("service_path", &self.service_path)
+            .finish()
+    }
+}
+
+impl MockClient {
+    pub fn new(service_path: &str) -> Self {
+        MockClient {
+            serialize_type: SerializeType::JSON,
+            service_path: service_path.to_owned(),
+            methods: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// replies to every call of the method with `reply`.
+    pub fn reply<T: RpcxParam>(&mut self, service_method: &str, reply: &T) -> Result<&mut Self> {
+        let data = reply.into_bytes(self.serialize_type)?;
+        Ok(self.handle(service_method, move |_| Ok(data.clone())))
+    }
+
+    /// fails every call of the method with an error of the kind.
+    pub fn error(&mut self, service_method: &str, kind: ErrorKind, message: &str) -> &mut Self {
+        let message = message.to_owned();
+        self.handle(service_method, move |_| {
+            Err(Error::new(kind, message.as_str()))
+        })
+    }
+
+    /// serves the calls of the method with `f`, which gets the serialized args and returns the
+    /// serialized reply.
+    pub fn handle<F>(&mut self, service_method: &str, f: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.methods
+            .lock()
+            .unwrap()
+            .entry(service_method.to_owned())
+            .or_default()
+            .handler = Some(Arc::new(f));
+        self
+    }
+
+    /// delays the responses of the method.
+    pub fn latency(&mut self, service_method: &str, latency: Duration) -> &mut Self {
+        self.methods
+            .lock()
+            .unwrap()
+            .entry(service_method.to_owned())
+            .or_default()
+            .latency = latency;
+        self
+    }
+
+    /// returns how many times the method has been called.
+    pub fn calls(&self, service_method: &str) -> usize {
+        self.methods
+            .lock()
+            .unwrap()
+            .get(service_method)
+            .map_or(0, |m| m.calls)
+    }
+
+    // records the call and returns the handler and the latency of the method.
+    fn invoke(&self, service_method: &str) -> (Option<MockFn>, Duration) {
+        let mut methods = self.methods.lock().unwrap();
+        let method = methods.entry(service_method.to_owned()).or_default();
+        method.calls += 1;
+        (method.handler.clone(), method.latency)
+    }
+}
+
+fn mock_reply<T>(
+    key: &str,
+    handler: Option<MockFn>,
+    st: SerializeType,
+    args: Result<Vec<u8>>,
+) -> Result<T>
+where
+    T: RpcxParam + Default,
+{
+    let handler = handler
+        .ok_or_else(|| Error::new(ErrorKind::Server, format!("service {} not found", key)))?;
+    let data = handler(&args?)?;
+    let mut reply: T = Default::default();
+    reply.from_slice(st, &data)?;
+    Ok(reply)
+}
+
+impl RpcxClient for MockClient {
+    fn call<T>(
+        &mut self,
+        service_method: &str,
+        is_oneway: bool,
+        _metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        let (handler, latency) = self.invoke(service_method);
+        thread::sleep(latency);
+
+        let key = format!("{}.{}", self.service_path, service_method);
+        let rt = mock_reply(
+            &key,
+            handler,
+            self.serialize_type,
+            args.into_bytes(self.serialize_type),
+        );
+        if is_oneway {
+            return None;
+        }
+        Some(rt)
+    }
+
+    fn acall<T>(
+        &mut self,
+        service_method: &str,
+        _metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync>
+    where
+        T: RpcxParam + Default + Sync + Send + 'static,
+    {
+        let (handler, latency) = self.invoke(service_method);
+
+        let key = format!("{}.{}", self.service_path, service_method);
+        let st = self.serialize_type;
+        let args = args.into_bytes(st);
+        Box::new(future::lazy(move || {
+            thread::sleep(latency);
+            future::ok(mock_reply(&key, handler, st, args))
+        }))
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use rpcx_protocol::call::ArcCall;
+
+/// the number of shards of a pending-call table, a power of two.
+const SHARDS: usize = 64;
+
+/// the calls waiting for their replies, keyed by sequence number.
+///
+/// Sequence numbers are handed out by a counter, so consecutive calls fall into different
+/// shards and callers on one connection rarely wait for each other or for the reader.
+#[derive(Debug)]
+pub(crate) struct PendingCalls {
+    shards: Vec<Mutex<HashMap<u64, ArcCall>>>,
+}
+
+impl PendingCalls {
+    pub(crate) fn new() -> Self {
+        PendingCalls {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, seq: u64) -> MutexGuard<HashMap<u64, ArcCall>> {
+        self.shards[seq as usize & (SHARDS - 1)].lock().unwrap()
+    }
+
+    pub(crate) fn insert(&self, seq: u64, call: ArcCall) {
+        self.shard(seq).insert(seq, call);
+    }
+
+    pub(crate) fn get(&self, seq: u64) -> Option<ArcCall> {
+        self.shard(seq).get(&seq).cloned()
+    }
+
+    pub(crate) fn remove(&self, seq: u64) -> Option<ArcCall> {
+        self.shard(seq).remove(&seq)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.lock().unwrap().is_empty())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    /// removes all pending calls.
+    pub(crate) fn drain(&self) -> Vec<ArcCall> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .lock()
+                    .unwrap()
+                    .drain()
+                    .map(|(_, call)| call)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
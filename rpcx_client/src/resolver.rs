@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rpcx_protocol::*;
+
+/// how long the addresses of a hostname are used before it is resolved again by default, see
+/// `XClient::set_dns_ttl`.
+pub const DNS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Resolved {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+    // the address tried first by the next connection
+    next: usize,
+}
+
+/// resolves the hostnames of servers, such as `service.internal:8972`, to all their addresses.
+/// Connections rotate through the addresses, and a hostname is resolved again once its
+/// addresses are older than the ttl and after a connection to it failed, so changed records
+/// are followed.
+#[derive(Debug)]
+pub(crate) struct Resolver {
+    hosts: Mutex<HashMap<String, Resolved>>,
+    ttl_ms: AtomicU64,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver {
+            hosts: Mutex::new(HashMap::new()),
+            ttl_ms: AtomicU64::new(DNS_REFRESH_INTERVAL.as_millis() as u64),
+        }
+    }
+}
+
+impl Resolver {
+    /// sets how long resolved addresses are used, 0 resolves hostnames at every connection.
+    pub(crate) fn set_ttl(&self, ttl: Duration) {
+        self.ttl_ms.store(ttl.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_ms.load(Ordering::Relaxed))
+    }
+
+    /// returns the cached addresses of the hostname if they are not expired.
+    pub(crate) fn cached(&self, addr: &str) -> Option<Vec<SocketAddr>> {
+        let ttl = self.ttl();
+        let hosts = self.hosts.lock().unwrap();
+        let resolved = hosts.get(addr)?;
+        if resolved.resolved_at.elapsed() >= ttl {
+            return None;
+        }
+        Some(resolved.addrs.clone())
+    }
+
+    /// returns the addresses to connect in order, starting from the next one in rotation.
+    pub(crate) fn resolve(&self, addr: &str) -> Result<Vec<SocketAddr>> {
+        if let Ok(addr) = addr.parse::<SocketAddr>() {
+            return Ok(vec![addr]);
+        }
+
+        let ttl = self.ttl();
+        let mut hosts = self.hosts.lock().unwrap();
+        let stale = match hosts.get(addr) {
+            Some(resolved) => resolved.resolved_at.elapsed() >= ttl,
+            None => true,
+        };
+        if stale {
+            let addrs: Vec<SocketAddr> = addr
+                .to_socket_addrs()
+                .map_err(|err| Error::new(ErrorKind::Network, err))?
+                .collect();
+            if addrs.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::Network,
+                    format!("no address is resolved for {}", addr),
+                ));
+            }
+            let next = hosts.get(addr).map(|r| r.next).unwrap_or(0);
+            hosts.insert(
+                addr.to_owned(),
+                Resolved {
+                    addrs,
+                    resolved_at: Instant::now(),
+                    next,
+                },
+            );
+        }
+
+        let resolved = hosts.get_mut(addr).unwrap();
+        let n = resolved.addrs.len();
+        let start = resolved.next % n;
+        resolved.next = (start + 1) % n;
+        Ok(resolved.addrs[start..]
+            .iter()
+            .chain(resolved.addrs[..start].iter())
+            .cloned()
+            .collect())
+    }
+
+    /// drops the addresses of the hostname, so it is resolved again by the next connection.
+    pub(crate) fn invalidate(&self, addr: &str) {
+        self.hosts.lock().unwrap().remove(addr);
+    }
+}
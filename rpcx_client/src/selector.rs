@@ -1,21 +1,123 @@
 use qstring::QString;
 use rand::{prelude::*, Rng};
-use rpcx_protocol::{RpcxParam, SerializeType};
+use rpcx_protocol::{Error, ErrorKind, Load, Result, RpcxParam, SerializeType};
+use semver::{Version, VersionReq};
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
+use super::SelectMode;
+
+pub use rpcx_protocol::{STATE, STATE_ACTIVE, STATE_INACTIVE, STATE_PAUSED};
+
 use weighted_rs::*;
 
 pub trait ClientSelector {
     fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String;
     fn update_server(&self, servers: &HashMap<String, String>);
+    /// stops following the discovery of servers, it is invoked when the client is closed.
+    fn close(&self) {}
+    /// is invoked by `XClient` when a call to the selected server starts.
+    fn on_call_start(&self, _server: &str) {}
+    /// is invoked by `XClient` when a call to the server ends. The ends of asynchronous calls
+    /// are reported before the next selection.
+    fn on_call_end(&self, _server: &str) {}
+    /// is invoked by `XClient` after `on_call_end` with the outcome of the call, so adaptive
+    /// selectors can weigh or blacklist servers. `success` is false if the call returned an
+    /// error, including the errors of services, or if an asynchronous call was dropped.
+    fn on_result(&self, _server: &str, _latency: Duration, _success: bool) {}
+    /// is invoked by `XClient` before a selection with the load the server attached to its
+    /// latest reply, see `LoadAwareSelector`.
+    fn on_load(&self, _server: &str, _load: &Load) {}
+    /// is invoked by `XClient` before a selection with whether its `CanaryRule` routes the
+    /// call to the canary servers, see `CanarySelector`.
+    fn on_route(&self, _canary: bool) {}
+    /// is invoked by `XClient` before a selection with the key extracted from the args of the
+    /// call by `XClient::set_hash_key`, `None` if the method has no extractor. See
+    /// `ConsistentHashSelector`.
+    fn on_hash_key(&self, _key: Option<&str>) {}
+    /// returns the servers which can be selected and the paused ones, `None` if the selector
+    /// doesn't list them. `XClient` warms up the connections to them and closes the
+    /// connections to the others.
+    fn candidates(&self) -> Option<Vec<String>> {
+        None
+    }
+}
+
+impl<S: ClientSelector + ?Sized> ClientSelector for Box<S> {
+    fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
+        (**self).select(service_path, service_method, args)
+    }
+    fn update_server(&self, servers: &HashMap<String, String>) {
+        (**self).update_server(servers)
+    }
+    fn close(&self) {
+        (**self).close()
+    }
+    fn on_call_start(&self, server: &str) {
+        (**self).on_call_start(server)
+    }
+    fn on_call_end(&self, server: &str) {
+        (**self).on_call_end(server)
+    }
+    fn on_result(&self, server: &str, latency: Duration, success: bool) {
+        (**self).on_result(server, latency, success)
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        (**self).on_load(server, load)
+    }
+    fn on_route(&self, canary: bool) {
+        (**self).on_route(canary)
+    }
+    fn on_hash_key(&self, key: Option<&str>) {
+        (**self).on_hash_key(key)
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        (**self).candidates()
+    }
+}
+
+/// returns whether the metadata of a server doesn't mark it inactive or paused. Inactive
+/// servers are kept by discoveries but excluded from selection, so they can be drained from
+/// the registry.
+pub fn is_active(meta: &str) -> bool {
+    match QString::from(meta).get(STATE) {
+        Some(STATE_INACTIVE) | Some(STATE_PAUSED) => false,
+        _ => true,
+    }
+}
+
+fn active_servers(map: &HashMap<String, String>) -> impl Iterator<Item = &String> {
+    map.iter()
+        .filter(|(_, meta)| is_active(meta))
+        .map(|(k, _)| k)
+}
+
+/// returns whether the metadata of a server marks it paused. Paused servers are not selected
+/// for new calls, but the connections to them are kept for the calls in flight.
+pub fn is_paused(meta: &str) -> bool {
+    QString::from(meta).get(STATE) == Some(STATE_PAUSED)
+}
+
+fn paused_servers(map: &HashMap<String, String>) -> Vec<String> {
+    map.iter()
+        .filter(|(_, meta)| is_paused(meta))
+        .map(|(k, _)| k.clone())
+        .collect()
+}
+
+// appends the paused servers to the servers which can be selected.
+fn with_paused(mut servers: Vec<String>, paused: &RwLock<Vec<String>>) -> Option<Vec<String>> {
+    servers.extend(paused.read().unwrap().iter().cloned());
+    Some(servers)
 }
 
 #[derive(Default)]
 pub struct RandomSelector {
     pub servers: Arc<RwLock<Vec<String>>>,
+    paused: RwLock<Vec<String>>,
     rnd: ThreadRng,
 }
 
@@ -23,6 +125,7 @@ impl RandomSelector {
     pub fn new() -> Self {
         RandomSelector {
             servers: Arc::new(RwLock::new(Vec::new())),
+            paused: RwLock::new(Vec::new()),
             rnd: thread_rng(),
         }
     }
@@ -48,17 +151,22 @@ impl ClientSelector for RandomSelector {
         String::from(s)
     }
     fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
         let mut servers = self.servers.write().unwrap();
         servers.clear();
-        for k in map.keys() {
+        for k in active_servers(map) {
             servers.push(String::from(k));
         }
     }
+    fn candidates(&self) -> Option<Vec<String>> {
+        with_paused(self.servers.read().unwrap().clone(), &self.paused)
+    }
 }
 
 #[derive(Default)]
 pub struct RoundbinSelector {
     pub servers: Arc<RwLock<Vec<String>>>,
+    paused: RwLock<Vec<String>>,
     index: usize,
 }
 
@@ -66,6 +174,7 @@ impl RoundbinSelector {
     pub fn new() -> Self {
         RoundbinSelector {
             servers: Arc::new(RwLock::new(Vec::new())),
+            paused: RwLock::new(Vec::new()),
             index: 0,
         }
     }
@@ -91,23 +200,32 @@ impl ClientSelector for RoundbinSelector {
         String::from(s)
     }
     fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
         let mut servers = self.servers.write().unwrap();
         servers.clear();
-        for k in map.keys() {
+        for k in active_servers(map) {
             servers.push(String::from(k));
         }
     }
+    fn candidates(&self) -> Option<Vec<String>> {
+        with_paused(self.servers.read().unwrap().clone(), &self.paused)
+    }
 }
 
 #[derive(Default)]
 pub struct WeightedSelector {
     pub servers: Arc<RwLock<SmoothWeight<String>>>,
+    // the names of the weighted servers
+    names: RwLock<Vec<String>>,
+    paused: RwLock<Vec<String>>,
 }
 
 impl WeightedSelector {
     pub fn new() -> Self {
         WeightedSelector {
             servers: Arc::new(RwLock::new(SmoothWeight::new())),
+            names: RwLock::new(Vec::new()),
+            paused: RwLock::new(Vec::new()),
         }
     }
 }
@@ -130,10 +248,14 @@ impl ClientSelector for WeightedSelector {
         }
     }
     fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
         let mut servers = self.servers.write().unwrap();
 
+        let mut names = self.names.write().unwrap();
         servers.reset();
-        for (k, v) in map.iter() {
+        names.clear();
+        for (k, v) in map.iter().filter(|(_, v)| is_active(v)) {
+            names.push(k.clone());
             let qs = QString::from(v.as_str());
             if let Some(val) = qs.get("weight") {
                 if let Ok(w) = val.parse::<isize>() {
@@ -146,18 +268,25 @@ impl ClientSelector for WeightedSelector {
             }
         }
     }
+    fn candidates(&self) -> Option<Vec<String>> {
+        with_paused(self.names.read().unwrap().clone(), &self.paused)
+    }
 }
 
+/// selects servers by the jump hash of the service, the method and the args of calls, so the
+/// same args go to the same server. The args are hashed as their JSON payload, or as the key
+/// extracted from them by `XClient::set_hash_key`.
 #[derive(Default)]
 pub struct ConsistentHashSelector {
     pub servers: Arc<RwLock<Vec<String>>>,
+    paused: RwLock<Vec<String>>,
+    // the key of the next selection, see `on_hash_key`
+    hash_key: Mutex<Option<String>>,
 }
 
 impl ConsistentHashSelector {
     pub fn new() -> Self {
-        ConsistentHashSelector {
-            servers: Arc::new(RwLock::new(Vec::new())),
-        }
+        Default::default()
     }
 }
 
@@ -165,11 +294,15 @@ fn hash_request(
     data: &mut Vec<u8>,
     service_path: &str,
     service_method: &str,
+    hash_key: Option<&str>,
     args: &dyn RpcxParam,
 ) {
     data.extend(service_path.to_string().into_bytes());
     data.extend(service_method.to_string().into_bytes());
-    data.extend(args.into_bytes(SerializeType::JSON).unwrap());
+    match hash_key {
+        Some(key) => data.extend(key.as_bytes()),
+        None => data.extend(args.into_bytes(SerializeType::JSON).unwrap()),
+    }
 }
 impl ClientSelector for ConsistentHashSelector {
     fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
@@ -181,15 +314,499 @@ impl ClientSelector for ConsistentHashSelector {
         // let data = Vec::new(service_path.len() + service_method.len());
         let jh = jumphash::JumpHasher::new();
         let mut data = Vec::new();
-        hash_request(&mut data, service_path, service_method, args);
+        let hash_key = self.hash_key.lock().unwrap();
+        hash_request(
+            &mut data,
+            service_path,
+            service_method,
+            hash_key.as_ref().map(String::as_str),
+            args,
+        );
         let index = jh.slot(&data, size as u32);
         let s = &servers[index as usize];
         String::from(s)
     }
     fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
         let mut servers = (*self).servers.write().unwrap();
-        for k in map.keys() {
+        servers.clear();
+        for k in active_servers(map) {
             servers.push(String::from(k));
         }
     }
+    fn on_hash_key(&self, key: Option<&str>) {
+        *self.hash_key.lock().unwrap() = key.map(str::to_owned);
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        with_paused(self.servers.read().unwrap().clone(), &self.paused)
+    }
+}
+
+/// selects the server with the fewest calls in flight, which are counted by the reports of
+/// `XClient`. It beats round robin if the costs of calls vary widely.
+#[derive(Default)]
+pub struct LeastConnSelector {
+    // the servers and their calls in flight
+    servers: Mutex<Vec<(String, usize)>>,
+    paused: RwLock<Vec<String>>,
+    index: usize,
+}
+
+impl LeastConnSelector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// returns the number of calls in flight to the server.
+    pub fn active_calls(&self, server: &str) -> usize {
+        self.servers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(k, _)| k == server)
+            .map_or(0, |(_, n)| *n)
+    }
+}
+
+impl ClientSelector for LeastConnSelector {
+    fn select(
+        &mut self,
+        _service_path: &str,
+        _service_method: &str,
+        _args: &dyn RpcxParam,
+    ) -> String {
+        let servers = self.servers.lock().unwrap();
+        let size = servers.len();
+        if size == 0 {
+            return String::new();
+        }
+        // ties are broken by round robin
+        self.index = (self.index + 1) % size;
+        let start = self.index;
+        let idx = (0..size)
+            .map(|i| (start + i) % size)
+            .min_by_key(|&i| servers[i].1)
+            .unwrap();
+        servers[idx].0.clone()
+    }
+    fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
+        let mut servers = self.servers.lock().unwrap();
+        // keeps the counts of calls in flight
+        let counts: HashMap<String, usize> = servers.drain(..).collect();
+        for k in active_servers(map) {
+            servers.push((k.clone(), counts.get(k).cloned().unwrap_or(0)));
+        }
+    }
+    fn on_call_start(&self, server: &str) {
+        let mut servers = self.servers.lock().unwrap();
+        if let Some((_, n)) = servers.iter_mut().find(|(k, _)| k == server) {
+            *n += 1;
+        }
+    }
+    fn on_call_end(&self, server: &str) {
+        let mut servers = self.servers.lock().unwrap();
+        if let Some((_, n)) = servers.iter_mut().find(|(k, _)| k == server) {
+            *n = n.saturating_sub(1);
+        }
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        let servers = self.servers.lock().unwrap();
+        with_paused(
+            servers.iter().map(|(k, _)| k.clone()).collect(),
+            &self.paused,
+        )
+    }
+}
+
+/// selects servers at random, weighted by the loads they attach to their replies (see
+/// `Server::enable_load_report`). The `weight` metadata of a server, 1 by default, is scaled
+/// down by its CPU usage and its queued requests, so loaded servers get less traffic than
+/// static weights would send them.
+#[derive(Default)]
+pub struct LoadAwareSelector {
+    // the servers, their weights and their latest loads
+    servers: Mutex<Vec<(String, f64, Option<Load>)>>,
+    paused: RwLock<Vec<String>>,
+}
+
+impl LoadAwareSelector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// returns the weight of the server scaled down by its latest load, 0 if it is unknown.
+    pub fn weight(&self, server: &str) -> f64 {
+        self.servers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(k, _, _)| k == server)
+            .map_or(0.0, |(_, weight, load)| load_weight(*weight, load))
+    }
+}
+
+// the queue beyond which servers are not weighted down any further, the queues are
+// reported by the servers and may be anything.
+const MAX_LOAD_QUEUE: usize = 10_000;
+
+// scales the weight down by the load. A fully loaded server keeps a small share, so the
+// replies keep reporting its load.
+fn load_weight(weight: f64, load: &Option<Load>) -> f64 {
+    match load {
+        Some(load) => {
+            let idle = f64::from(100 - load.cpu.min(99)) / 100.0;
+            let queue = load.queue.saturating_add(1).min(MAX_LOAD_QUEUE + 1);
+            weight * idle / queue as f64
+        }
+        None => weight,
+    }
+}
+
+impl ClientSelector for LoadAwareSelector {
+    fn select(
+        &mut self,
+        _service_path: &str,
+        _service_method: &str,
+        _args: &dyn RpcxParam,
+    ) -> String {
+        let servers = self.servers.lock().unwrap();
+        let weights: Vec<f64> = servers
+            .iter()
+            .map(|(_, weight, load)| load_weight(*weight, load))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        if servers.is_empty() || total <= 0.0 {
+            return String::new();
+        }
+        let mut point = rand::random::<f64>() * total;
+        for (i, weight) in weights.iter().enumerate() {
+            if point < *weight {
+                return servers[i].0.clone();
+            }
+            point -= weight;
+        }
+        servers[servers.len() - 1].0.clone()
+    }
+    fn update_server(&self, map: &HashMap<String, String>) {
+        *self.paused.write().unwrap() = paused_servers(map);
+        let mut servers = self.servers.lock().unwrap();
+        // keeps the loads of the servers
+        let loads: HashMap<String, Option<Load>> =
+            servers.drain(..).map(|(k, _, load)| (k, load)).collect();
+        for (k, v) in map.iter().filter(|(_, v)| is_active(v)) {
+            let weight = QString::from(v.as_str())
+                .get("weight")
+                .and_then(|w| w.parse::<f64>().ok())
+                .filter(|w| *w > 0.0)
+                .unwrap_or(1.0);
+            let load = loads.get(k).and_then(|load| *load);
+            servers.push((k.clone(), weight, load));
+        }
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        let mut servers = self.servers.lock().unwrap();
+        if let Some((_, _, l)) = servers.iter_mut().find(|(k, _, _)| k == server) {
+            *l = Some(*load);
+        }
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        let servers = self.servers.lock().unwrap();
+        with_paused(
+            servers.iter().map(|(k, _, _)| k.clone()).collect(),
+            &self.paused,
+        )
+    }
+}
+
+struct SharedState {
+    selector: Box<dyn ClientSelector + Send>,
+    servers: HashMap<String, String>,
+    source: u64,
+}
+
+/// a selector which can be shared by a client and a discovery updating its servers.
+///
+/// The selector and the source of servers can be replaced at runtime, see `XClient::reload`.
+#[derive(Clone)]
+pub struct SharedSelector {
+    inner: Arc<Mutex<SharedState>>,
+    // the source of servers of this handle, 0 for the client itself.
+    source: u64,
+}
+
+impl SharedSelector {
+    pub fn new(selector: Box<dyn ClientSelector + Send>) -> Self {
+        SharedSelector {
+            inner: Arc::new(Mutex::new(SharedState {
+                selector,
+                servers: HashMap::new(),
+                source: 0,
+            })),
+            source: 0,
+        }
+    }
+
+    /// returns a handle for a new source of servers. The handles of former sources are
+    /// ignored from now on, so a replaced discovery can't override the servers.
+    pub fn new_source(&self) -> SharedSelector {
+        let mut state = self.inner.lock().unwrap();
+        state.source += 1;
+        SharedSelector {
+            inner: self.inner.clone(),
+            source: state.source,
+        }
+    }
+
+    /// replaces the selector, it starts with the current servers.
+    pub fn set_selector(&self, selector: Box<dyn ClientSelector + Send>) {
+        let mut state = self.inner.lock().unwrap();
+        selector.update_server(&state.servers);
+        state.selector = selector;
+    }
+
+    pub fn servers(&self) -> HashMap<String, String> {
+        self.inner.lock().unwrap().servers.clone()
+    }
+}
+
+impl ClientSelector for SharedSelector {
+    fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
+        self.inner
+            .lock()
+            .unwrap()
+            .selector
+            .select(service_path, service_method, args)
+    }
+    fn update_server(&self, servers: &HashMap<String, String>) {
+        let mut state = self.inner.lock().unwrap();
+        if self.source != 0 && self.source != state.source {
+            return;
+        }
+        state.servers = servers.clone();
+        state.selector.update_server(servers);
+    }
+    fn close(&self) {
+        // the handles of discoveries are ignored from now on
+        self.new_source();
+    }
+    fn on_call_start(&self, server: &str) {
+        self.inner.lock().unwrap().selector.on_call_start(server)
+    }
+    fn on_call_end(&self, server: &str) {
+        self.inner.lock().unwrap().selector.on_call_end(server)
+    }
+    fn on_result(&self, server: &str, latency: Duration, success: bool) {
+        self.inner
+            .lock()
+            .unwrap()
+            .selector
+            .on_result(server, latency, success)
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        self.inner.lock().unwrap().selector.on_load(server, load)
+    }
+    fn on_route(&self, canary: bool) {
+        self.inner.lock().unwrap().selector.on_route(canary)
+    }
+    fn on_hash_key(&self, key: Option<&str>) {
+        self.inner.lock().unwrap().selector.on_hash_key(key)
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        self.inner.lock().unwrap().selector.candidates()
+    }
+}
+
+/// selects servers by the selectors of service methods, for example round robin for cheap
+/// reads and consistent hash for the methods of stateful sessions. The methods without a
+/// selector of their own use the default selector.
+pub struct MethodSelector {
+    default: Box<dyn ClientSelector + Send>,
+    methods: HashMap<String, Box<dyn ClientSelector + Send>>,
+    servers: Mutex<HashMap<String, String>>,
+}
+
+impl MethodSelector {
+    pub fn new(default: Box<dyn ClientSelector + Send>) -> Self {
+        MethodSelector {
+            default,
+            methods: HashMap::new(),
+            servers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// sets the selector of the method, it starts with the current servers.
+    pub fn set_method_selector(
+        &mut self,
+        service_method: &str,
+        selector: Box<dyn ClientSelector + Send>,
+    ) {
+        selector.update_server(&self.servers.lock().unwrap());
+        self.methods.insert(service_method.to_owned(), selector);
+    }
+
+    // the default selector followed by the selectors of methods.
+    fn selectors(&self) -> impl Iterator<Item = &(dyn ClientSelector + Send)> {
+        std::iter::once(&self.default)
+            .chain(self.methods.values())
+            .map(|selector| &**selector)
+    }
+}
+
+impl ClientSelector for MethodSelector {
+    fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
+        match self.methods.get_mut(service_method) {
+            Some(selector) => selector.select(service_path, service_method, args),
+            None => self.default.select(service_path, service_method, args),
+        }
+    }
+    fn update_server(&self, servers: &HashMap<String, String>) {
+        *self.servers.lock().unwrap() = servers.clone();
+        for selector in self.selectors() {
+            selector.update_server(servers);
+        }
+    }
+    fn close(&self) {
+        for selector in self.selectors() {
+            selector.close();
+        }
+    }
+    // calls are reported to every selector, they load the same servers.
+    fn on_call_start(&self, server: &str) {
+        for selector in self.selectors() {
+            selector.on_call_start(server);
+        }
+    }
+    fn on_call_end(&self, server: &str) {
+        for selector in self.selectors() {
+            selector.on_call_end(server);
+        }
+    }
+    fn on_result(&self, server: &str, latency: Duration, success: bool) {
+        for selector in self.selectors() {
+            selector.on_result(server, latency, success);
+        }
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        for selector in self.selectors() {
+            selector.on_load(server, load);
+        }
+    }
+    fn on_route(&self, canary: bool) {
+        for selector in self.selectors() {
+            selector.on_route(canary);
+        }
+    }
+    fn on_hash_key(&self, key: Option<&str>) {
+        for selector in self.selectors() {
+            selector.on_hash_key(key);
+        }
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        let lists: Option<Vec<Vec<String>>> = self
+            .selectors()
+            .map(|selector| selector.candidates())
+            .collect();
+        let mut servers: Vec<String> = lists?.into_iter().flatten().collect();
+        servers.sort();
+        servers.dedup();
+        Some(servers)
+    }
+}
+
+/// the metadata key of the version of a server.
+pub const VERSION: &str = "version";
+
+/// restricts a selector to the servers whose `version` metadata matches a requirement such as
+/// `>=1.2, <2`. Servers without a version are excluded.
+pub struct VersionSelector<S: ClientSelector> {
+    inner: S,
+    req: VersionReq,
+}
+
+impl<S: ClientSelector> VersionSelector<S> {
+    pub fn new(inner: S, req: &str) -> Result<Self> {
+        let req = VersionReq::parse(req).map_err(|err| {
+            Error::new(
+                ErrorKind::Config,
+                format!("invalid version requirement {}: {}", req, err),
+            )
+        })?;
+        Ok(VersionSelector { inner, req })
+    }
+
+    /// returns whether the metadata of a server has a matching version.
+    pub fn matches(&self, meta: &str) -> bool {
+        let qs = QString::from(meta);
+        match qs.get(VERSION).and_then(parse_version) {
+            Some(version) => self.req.matches(&version),
+            None => false,
+        }
+    }
+}
+
+// parses a version, missing minor and patch numbers are 0.
+fn parse_version(v: &str) -> Option<Version> {
+    let mut v = v.trim().to_owned();
+    for _ in v.matches('.').count()..2 {
+        v.push_str(".0");
+    }
+    Version::parse(&v).ok()
+}
+
+impl<S: ClientSelector> ClientSelector for VersionSelector<S> {
+    fn select(&mut self, service_path: &str, service_method: &str, args: &dyn RpcxParam) -> String {
+        self.inner.select(service_path, service_method, args)
+    }
+    fn update_server(&self, servers: &HashMap<String, String>) {
+        let servers: HashMap<String, String> = servers
+            .iter()
+            .filter(|(_, meta)| self.matches(meta))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        self.inner.update_server(&servers);
+    }
+    fn close(&self) {
+        self.inner.close()
+    }
+    fn on_call_start(&self, server: &str) {
+        self.inner.on_call_start(server)
+    }
+    fn on_call_end(&self, server: &str) {
+        self.inner.on_call_end(server)
+    }
+    fn on_result(&self, server: &str, latency: Duration, success: bool) {
+        self.inner.on_result(server, latency, success)
+    }
+    fn on_load(&self, server: &str, load: &Load) {
+        self.inner.on_load(server, load)
+    }
+    fn on_route(&self, canary: bool) {
+        self.inner.on_route(canary)
+    }
+    fn on_hash_key(&self, key: Option<&str>) {
+        self.inner.on_hash_key(key)
+    }
+    fn candidates(&self) -> Option<Vec<String>> {
+        self.inner.candidates()
+    }
+}
+
+/// creates a selector of the built-in select modes.
+pub fn new_selector(select_mode: SelectMode) -> Result<Box<dyn ClientSelector + Send>> {
+    let selector: Box<dyn ClientSelector + Send> = match select_mode {
+        SelectMode::RandomSelect => Box::new(RandomSelector::new()),
+        SelectMode::RoundRobin => Box::new(RoundbinSelector::new()),
+        SelectMode::WeightedRoundRobin => Box::new(WeightedSelector::new()),
+        SelectMode::ConsistentHash => Box::new(ConsistentHashSelector::new()),
+        SelectMode::LeastConnections => Box::new(LeastConnSelector::new()),
+        SelectMode::LoadAware => Box::new(LoadAwareSelector::new()),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Config,
+                format!("select mode {} has no built-in selector", select_mode),
+            ))
+        }
+    };
+    Ok(selector)
 }
@@ -0,0 +1,154 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rpcx_protocol::ErrorKind;
+
+use super::{ClientSelector, XClient};
+
+/// how many of the latest latencies of each server the percentiles of `EndpointStats` are
+/// computed from.
+pub const LATENCY_WINDOW: usize = 1024;
+
+/// the state of the connection of `XClient` to a server.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ConnState {
+    /// no connection is made yet, or it is closed since the server is removed.
+    NotConnected,
+    Connected,
+    /// the connection is lost or closed, it is not made again until the server is removed.
+    Disconnected,
+}
+
+/// the calls of `XClient` to a server, see `XClient::stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointStats {
+    /// the key of the server in the selector, such as `tcp@127.0.0.1:8972`.
+    pub server: String,
+    pub state: ConnState,
+    /// the calls waiting for their replies on the connection.
+    pub in_flight: usize,
+    pub calls: u64,
+    pub errors: u64,
+    /// the median and the 99th percentile of the latest `LATENCY_WINDOW` latencies, zero if
+    /// there is no call.
+    pub p50: Duration,
+    pub p99: Duration,
+    /// the kind of the last error and when it happened.
+    pub last_error: Option<(ErrorKind, Instant)>,
+}
+
+impl EndpointStats {
+    /// returns the ratio of the calls which succeed, 1 if there is none.
+    pub fn success_ratio(&self) -> f64 {
+        if self.calls == 0 {
+            return 1.0;
+        }
+        (self.calls - self.errors) as f64 / self.calls as f64
+    }
+}
+
+#[derive(Debug, Default)]
+struct EndpointRecord {
+    calls: u64,
+    errors: u64,
+    latencies: VecDeque<Duration>,
+    last_error: Option<(ErrorKind, Instant)>,
+}
+
+impl EndpointRecord {
+    fn record(&mut self, latency: Duration, error: Option<ErrorKind>) {
+        self.calls += 1;
+        if let Some(kind) = error {
+            self.errors += 1;
+            self.last_error = Some((kind, Instant::now()));
+        }
+        if self.latencies.len() == LATENCY_WINDOW {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+    }
+
+    // returns the median and the 99th percentile of the latencies.
+    fn percentiles(&self) -> (Duration, Duration) {
+        let mut latencies: Vec<Duration> = self.latencies.iter().cloned().collect();
+        latencies.sort();
+        (percentile(&latencies, 0.5), percentile(&latencies, 0.99))
+    }
+}
+
+// returns the nearest-rank percentile of sorted latencies.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1) - 1]
+}
+
+/// the outcomes of the calls of `XClient` by server.
+#[derive(Debug, Default)]
+pub(crate) struct EndpointTable {
+    endpoints: Mutex<HashMap<String, EndpointRecord>>,
+}
+
+impl EndpointTable {
+    pub(crate) fn record(&self, server: &str, latency: Duration, error: Option<ErrorKind>) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        match endpoints.get_mut(server) {
+            Some(endpoint) => endpoint.record(latency, error),
+            None => {
+                let mut endpoint = EndpointRecord::default();
+                endpoint.record(latency, error);
+                endpoints.insert(server.to_owned(), endpoint);
+            }
+        }
+    }
+}
+
+impl<S: ClientSelector> XClient<S> {
+    /// returns the stats of the servers of the selector and of the servers called before,
+    /// ordered by server, for health dashboards or to feed custom selectors.
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        let clients = self.clients.read().unwrap();
+        let endpoints = self.endpoint_stats.endpoints.lock().unwrap();
+        let mut servers: BTreeSet<&str> = endpoints.keys().map(String::as_str).collect();
+        servers.extend(clients.keys().map(String::as_str));
+        let candidates = self.selector.candidates().unwrap_or_default();
+        servers.extend(candidates.iter().map(String::as_str));
+
+        servers
+            .into_iter()
+            .map(|server| {
+                let (state, in_flight) = match clients.get(server) {
+                    Some(client) if client.is_connected() => {
+                        (ConnState::Connected, client.in_flight())
+                    }
+                    Some(client) => (ConnState::Disconnected, client.in_flight()),
+                    None => (ConnState::NotConnected, 0),
+                };
+                let mut stats = EndpointStats {
+                    server: server.to_owned(),
+                    state,
+                    in_flight,
+                    calls: 0,
+                    errors: 0,
+                    p50: Duration::default(),
+                    p99: Duration::default(),
+                    last_error: None,
+                };
+                if let Some(endpoint) = endpoints.get(server) {
+                    let (p50, p99) = endpoint.percentiles();
+                    stats.calls = endpoint.calls;
+                    stats.errors = endpoint.errors;
+                    stats.p50 = p50;
+                    stats.p99 = p99;
+                    stats.last_error = endpoint.last_error;
+                }
+                stats
+            })
+            .collect()
+    }
+}
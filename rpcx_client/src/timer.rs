@@ -0,0 +1,110 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rpcx_protocol::{TimerWheel, TIMER_SLOTS, TIMER_TICK};
+
+#[derive(Debug)]
+struct TimerState {
+    // the sequence numbers of the calls by their deadlines
+    wheel: TimerWheel<u64>,
+    // when the timer thread wakes up next, it sleeps until a call is scheduled if unset
+    wake_at: Option<Instant>,
+}
+
+/// the deadlines of the calls of a connection.
+///
+/// They share one timer wheel which a single thread expires, so a call costs an insertion
+/// rather than a timer of its own, and the thread only wakes up when a deadline may have
+/// passed. Calls which are replied in time are not removed from the wheel, their sequence
+/// numbers expire later and are ignored.
+#[derive(Debug)]
+pub(crate) struct CallTimer {
+    state: Mutex<TimerState>,
+    cond: Condvar,
+    // the timeout of the calls in milliseconds, 0 means none
+    timeout_ms: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl CallTimer {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        CallTimer {
+            state: Mutex::new(TimerState {
+                wheel: TimerWheel::new(TIMER_TICK, TIMER_SLOTS),
+                wake_at: None,
+            }),
+            cond: Condvar::new(),
+            timeout_ms: AtomicU64::new(timeout.as_millis() as u64),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn set_timeout(&self, timeout: Duration) {
+        self.timeout_ms
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// returns the timeout of a call, the shorter of the timeout of the connection and the
+    /// time left to the deadline the caller propagates.
+    pub(crate) fn timeout(&self, deadline: Option<Duration>) -> Option<Duration> {
+        let timeout = match self.timeout_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        match (timeout, deadline) {
+            (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+            (timeout, deadline) => timeout.or(deadline),
+        }
+    }
+
+    /// fails the call of `seq` if it is not replied in `timeout`.
+    pub(crate) fn schedule(&self, seq: u64, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        state.wheel.insert(deadline, seq);
+        if state.wake_at.map_or(true, |wake_at| deadline < wake_at) {
+            state.wake_at = Some(deadline);
+            self.cond.notify_one();
+        }
+    }
+
+    /// passes the sequence numbers of the calls whose deadlines passed to `expire`, until
+    /// the timer is closed.
+    pub(crate) fn run<F>(&self, mut expire: F)
+    where
+        F: FnMut(Vec<u64>),
+    {
+        let mut state = self.state.lock().unwrap();
+        while !self.closed.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            let expired = state.wheel.expire(now);
+            if !expired.is_empty() {
+                drop(state);
+                expire(expired);
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.wake_at = state.wheel.next_expiry();
+            state = match state.wake_at {
+                Some(wake_at) => {
+                    let timeout = wake_at.saturating_duration_since(now);
+                    self.cond.wait_timeout(state, timeout).unwrap().0
+                }
+                None => self.cond.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// stops the timer thread.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _state = self.state.lock().unwrap();
+        self.cond.notify_one();
+    }
+}
@@ -0,0 +1,102 @@
+use rpcx_protocol::*;
+use rustls::ClientConfig;
+use std::{
+    fmt,
+    net::{IpAddr, TcpStream},
+    sync::Arc,
+};
+
+/// the TLS of the connections of a client, see `Client::set_tls` and `XClient::set_tls`.
+///
+/// The certificates of servers are verified by the CA and by the name of the server, which
+/// is sent by SNI as well so proxies in front of the servers route by it. The name is the
+/// host of the address of the server unless it is set, servers addressed by IPs need it.
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    server_name: Option<String>,
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConnector")
+            .field("server_name", &self.server_name)
+            .field("alpn_protocols", &self.config.alpn_protocols)
+            .finish()
+    }
+}
+
+impl TlsConnector {
+    /// verifies the certificates of servers by the CAs of the PEM file.
+    pub fn new(ca_file: &str) -> Result<Self> {
+        let mut config = ClientConfig::new();
+        config.root_store = load_roots(ca_file)?;
+        Ok(TlsConnector {
+            config: Arc::new(config),
+            server_name: None,
+        })
+    }
+
+    /// creates the connector of the config, with the certificate of the client if the
+    /// config has one.
+    pub fn with_config(config: &TlsConfig) -> Result<Self> {
+        let mut connector = TlsConnector::new(&config.ca_file)?;
+        if !config.server_name.is_empty() {
+            connector.set_server_name(&config.server_name);
+        }
+        let protocols: Vec<&str> = config.alpn_protocols.iter().map(String::as_str).collect();
+        connector.set_alpn_protocols(&protocols);
+        if !config.cert_file.is_empty() || !config.key_file.is_empty() {
+            connector.set_client_cert(&config.cert_file, &config.key_file)?;
+        }
+        Ok(connector)
+    }
+
+    /// sets the name the certificates of servers are verified by and which is sent by SNI.
+    pub fn set_server_name(&mut self, server_name: &str) {
+        self.server_name = Some(server_name.to_owned());
+    }
+
+    /// offers the protocols to servers by ALPN in the order of preference, see
+    /// `TlsStream::alpn_protocol`.
+    pub fn set_alpn_protocols(&mut self, protocols: &[&str]) {
+        Arc::make_mut(&mut self.config).alpn_protocols = protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+    }
+
+    /// authenticates the client by the certificate and the key of the PEM files, for the
+    /// servers which verify clients.
+    pub fn set_client_cert(&mut self, cert_file: &str, key_file: &str) -> Result<()> {
+        let certs = load_certs(cert_file)?;
+        let key = load_private_key(key_file)?;
+        Arc::make_mut(&mut self.config)
+            .set_single_client_cert(certs, key)
+            .map_err(|err| Error::new(ErrorKind::Config, format!("invalid {}: {}", key_file, err)))
+    }
+
+    /// does the handshake of a connection to the server at `addr`, like `host:port`.
+    pub(crate) fn connect(&self, addr: &str, stream: TcpStream) -> Result<Conn> {
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name.as_str(),
+            None => host(addr),
+        };
+        if server_name.parse::<IpAddr>().is_ok() {
+            return Err(Error::new(
+                ErrorKind::Config,
+                format!(
+                    "the TLS server name of {} is not set, see `TlsConnector::set_server_name`",
+                    addr
+                ),
+            ));
+        }
+        TlsStream::connect(&self.config, server_name, stream).map(Conn::Tls)
+    }
+}
+
+// the host of an address like `host:port` or `[::1]:port`.
+fn host(addr: &str) -> &str {
+    let host = addr.rsplitn(2, ':').last().unwrap_or(addr);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
@@ -0,0 +1,54 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::client::Client;
+use rpcx_protocol::Result;
+
+/// how often a client with warm-up checks the selector for servers which are not connected.
+pub const WARM_UP_INTERVAL: Duration = Duration::from_secs(1);
+
+// connects to the servers of a selector in advance, so the first calls after a deploy don't
+// wait for connections.
+#[derive(Default)]
+pub(crate) struct WarmUp {
+    // the servers being connected
+    pending: Arc<Mutex<HashSet<String>>>,
+    checked_at: Mutex<Option<Instant>>,
+}
+
+impl WarmUp {
+    // returns whether the servers are due to be checked, at most once per interval.
+    pub(crate) fn is_due(&self) -> bool {
+        let mut checked_at = self.checked_at.lock().unwrap();
+        match *checked_at {
+            Some(at) if at.elapsed() < WARM_UP_INTERVAL => false,
+            _ => {
+                *checked_at = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    // connects to the servers in background threads, skipping those being connected. The
+    // servers which fail are tried again at the next check.
+    pub(crate) fn connect<F>(&self, servers: Vec<String>, connect: F)
+    where
+        F: Fn(&str) -> Result<Arc<Client>> + Clone + Send + 'static,
+    {
+        for server in servers {
+            if !self.pending.lock().unwrap().insert(server.clone()) {
+                continue;
+            }
+            let pending = self.pending.clone();
+            let connect = connect.clone();
+            thread::spawn(move || {
+                let _ = connect(&server);
+                pending.lock().unwrap().remove(&server);
+            });
+        }
+    }
+}
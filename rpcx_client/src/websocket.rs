@@ -0,0 +1,360 @@
+//! the client of browsers, which calls services over the WebSocket transport of servers. It
+//! builds for `wasm32-unknown-unknown` without threads or sockets of std:
+//!
+//! ```text
+//! cargo build -p rpcx_client --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+//!
+//! Only the frames of `rpcx_protocol` are used, so the errors are `WsError` rather than the
+//! `Error` of the native clients.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use rpcx_protocol::frame::*;
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
+
+// the values of `SerializeType` and `MessageStatusType` in the header
+const SERIALIZE_JSON: u8 = 1;
+const STATUS_ERROR: u8 = 1;
+// the subprotocol which is asked for in the handshake
+const SUBPROTOCOL: &str = "rpcx";
+
+/// why a call of a `WsClient` fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsError {
+    /// the WebSocket can't be opened or sent to, with the error of the browser.
+    Connect(String),
+    /// the connection is closed before the reply.
+    Closed,
+    /// a reply can't be decoded.
+    Frame(FrameError),
+    /// the error which the service replied.
+    Server(String),
+    /// the args or the reply can't be (de)serialized as JSON.
+    Serialization(String),
+}
+
+impl fmt::Display for WsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WsError::Connect(err) => write!(f, "failed to connect: {}", err),
+            WsError::Closed => write!(f, "connection closed"),
+            WsError::Frame(err) => write!(f, "{}", err),
+            WsError::Server(err) => write!(f, "{}", err),
+            WsError::Serialization(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for WsError {}
+
+impl From<FrameError> for WsError {
+    fn from(err: FrameError) -> Self {
+        WsError::Frame(err)
+    }
+}
+
+fn js_error(err: JsValue) -> WsError {
+    WsError::Connect(err.as_string().unwrap_or_else(|| format!("{:?}", err)))
+}
+
+/// a client which calls the services of a server over `ws://host:port/_rpcx_/ws`, see
+/// `WEBSOCKET_PATH`. Calls are futures which the event loop of the browser drives, e.g. by
+/// `wasm_bindgen_futures::spawn_local`, and many of them can be in flight on the connection.
+pub struct WsClient {
+    socket: WebSocket,
+    state: Rc<RefCell<State>>,
+    seq: Cell<u64>,
+    // the handlers of the socket, which live as long as the client
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+// the payload of a reply or why the call fails
+type Reply = Result<Vec<u8>, WsError>;
+
+#[derive(Default)]
+struct State {
+    // the bytes of the frames which are not complete yet
+    buf: Vec<u8>,
+    pending: HashMap<u64, Rc<Slot<Reply>>>,
+    closed: bool,
+}
+
+impl State {
+    // completes the calls of the complete frames in the buffer.
+    fn on_data(&mut self, data: &[u8]) -> Result<(), WsError> {
+        self.buf.extend_from_slice(data);
+        let mut pos = 0;
+        while pos < self.buf.len() {
+            let (frame, len) = match Frame::decode(&self.buf[pos..]) {
+                Ok(decoded) => decoded,
+                Err(FrameError::Truncated) => break,
+                Err(err) => return Err(err.into()),
+            };
+            pos += len;
+            // the messages pushed by the server have no pending call
+            if let Some(slot) = self.pending.remove(&frame.seq()) {
+                slot.set(reply_of(&frame));
+            }
+        }
+        self.buf.drain(..pos);
+        Ok(())
+    }
+
+    // fails the pending calls once the connection is closed.
+    fn close(&mut self, err: WsError) {
+        self.closed = true;
+        self.buf.clear();
+        for (_, slot) in self.pending.drain() {
+            slot.set(Err(err.clone()));
+        }
+    }
+}
+
+fn reply_of(frame: &Frame<'_>) -> Reply {
+    if frame.is_chunked() {
+        return Err(WsError::Server(
+            "chunked replies are not supported".to_owned(),
+        ));
+    }
+    if frame.header[2] & 0x03 == STATUS_ERROR {
+        let err = frame
+            .metadata()
+            .filter_map(|pair| pair.ok())
+            .find(|(key, _)| *key == SERVICE_ERROR)
+            .map_or("", |(_, value)| value);
+        return Err(WsError::Server(err.to_owned()));
+    }
+    Ok(frame.payload.to_vec())
+}
+
+impl WsClient {
+    /// opens a WebSocket to `url`, e.g. `ws://127.0.0.1:8972/_rpcx_/ws`, and returns the
+    /// client once it is open.
+    pub async fn connect(url: &str) -> Result<WsClient, WsError> {
+        let socket = WebSocket::new_with_str(url, SUBPROTOCOL).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let opened = Rc::new(Slot::default());
+        let on_open = {
+            let opened = opened.clone();
+            Closure::wrap(Box::new(move |_: Event| opened.set(Ok(()))) as Box<dyn FnMut(Event)>)
+        };
+        let on_error = {
+            let opened = opened.clone();
+            Closure::wrap(Box::new(move |_: Event| {
+                opened.set(Err(WsError::Connect(
+                    "failed to open the websocket".to_owned(),
+                )))
+            }) as Box<dyn FnMut(Event)>)
+        };
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        let rt = Wait(opened).await;
+        socket.set_onopen(None);
+        socket.set_onerror(None);
+        rt?;
+
+        let state = Rc::new(RefCell::new(State::default()));
+        let on_message = {
+            let state = state.clone();
+            Closure::wrap(Box::new(move |e: MessageEvent| {
+                let data = match e.data().dyn_into::<ArrayBuffer>() {
+                    Ok(data) => Uint8Array::new(&data).to_vec(),
+                    Err(_) => return,
+                };
+                let mut state = state.borrow_mut();
+                if let Err(err) = state.on_data(&data) {
+                    state.close(err);
+                }
+            }) as Box<dyn FnMut(MessageEvent)>)
+        };
+        let on_close = {
+            let state = state.clone();
+            Closure::wrap(
+                Box::new(move |_: CloseEvent| state.borrow_mut().close(WsError::Closed))
+                    as Box<dyn FnMut(CloseEvent)>,
+            )
+        };
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(WsClient {
+            socket,
+            state,
+            seq: Cell::new(0),
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    /// calls the method with the payload serialized as `serialize_type`, a value of
+    /// `SerializeType`, and returns the payload of the reply.
+    pub async fn call_raw(
+        &self,
+        service_path: &str,
+        service_method: &str,
+        metadata: &[(&str, &str)],
+        serialize_type: u8,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, WsError> {
+        let seq = self.seq.get();
+        self.seq.set(seq.wrapping_add(1));
+        let mut buf = Vec::new();
+        encode_frame(
+            &request_header(seq, serialize_type),
+            service_path,
+            service_method,
+            metadata.iter().copied(),
+            payload,
+            &mut buf,
+        );
+
+        let slot = Rc::new(Slot::default());
+        {
+            let mut state = self.state.borrow_mut();
+            if state.closed {
+                return Err(WsError::Closed);
+            }
+            state.pending.insert(seq, slot.clone());
+        }
+        if let Err(err) = self.socket.send_with_u8_array(&buf) {
+            self.state.borrow_mut().pending.remove(&seq);
+            return Err(js_error(err));
+        }
+        Wait(slot).await
+    }
+
+    /// calls the method with the args and the reply serialized as JSON.
+    pub async fn call<A, R>(
+        &self,
+        service_path: &str,
+        service_method: &str,
+        args: &A,
+    ) -> Result<R, WsError>
+    where
+        A: Serialize,
+        R: DeserializeOwned,
+    {
+        let payload =
+            serde_json::to_vec(args).map_err(|err| WsError::Serialization(err.to_string()))?;
+        let reply = self
+            .call_raw(service_path, service_method, &[], SERIALIZE_JSON, &payload)
+            .await?;
+        serde_json::from_slice(&reply).map_err(|err| WsError::Serialization(err.to_string()))
+    }
+}
+
+impl Drop for WsClient {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+        self.state.borrow_mut().close(WsError::Closed);
+    }
+}
+
+// the value of a callback of the browser which a future waits for.
+struct Slot<T> {
+    value: RefCell<Option<T>>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Slot {
+            value: RefCell::new(None),
+            waker: RefCell::new(None),
+        }
+    }
+}
+
+impl<T> Slot<T> {
+    // keeps the first value and wakes the future.
+    fn set(&self, value: T) {
+        let mut slot = self.value.borrow_mut();
+        if slot.is_none() {
+            *slot = Some(value);
+            if let Some(waker) = self.waker.borrow_mut().take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+struct Wait<T>(Rc<Slot<T>>);
+
+impl<T> Future for Wait<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match self.0.value.borrow_mut().take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                *self.0.waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies() {
+        let mut state = State::default();
+        let slots: Vec<_> = (0..3).map(|_| Rc::new(Slot::default())).collect();
+        for (seq, slot) in slots.iter().enumerate() {
+            state.pending.insert(seq as u64, slot.clone());
+        }
+
+        let mut data = Vec::new();
+        encode_frame(&request_header(0, 1), "", "", vec![], b"0", &mut data);
+        let mut header = request_header(1, 1);
+        header[2] |= STATUS_ERROR;
+        encode_frame(
+            &header,
+            "",
+            "",
+            vec![(SERVICE_ERROR, "denied")],
+            b"",
+            &mut data,
+        );
+        encode_frame(&request_header(2, 1), "", "", vec![], b"2", &mut data);
+
+        // the frames span the data of the socket
+        let (first, rest) = data.split_at(20);
+        state.on_data(first).unwrap();
+        assert!(slots[0].value.borrow().is_none());
+        state.on_data(rest).unwrap();
+        assert!(state.buf.is_empty());
+        assert!(state.pending.is_empty());
+
+        assert_eq!(Some(Ok(b"0".to_vec())), slots[0].value.borrow_mut().take());
+        assert_eq!(
+            Some(Err(WsError::Server("denied".to_owned()))),
+            slots[1].value.borrow_mut().take()
+        );
+        assert_eq!(Some(Ok(b"2".to_vec())), slots[2].value.borrow_mut().take());
+
+        let slot = Rc::new(Slot::default());
+        state.pending.insert(3, slot.clone());
+        assert!(state.on_data(&[0xff; 16]).is_err());
+        state.close(WsError::Closed);
+        assert_eq!(Some(Err(WsError::Closed)), slot.value.borrow_mut().take());
+    }
+}
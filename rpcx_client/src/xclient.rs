@@ -1,19 +1,42 @@
 #![allow(non_snake_case)]
 
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 use super::selector::ClientSelector;
 
+#[cfg(feature = "tls")]
+use super::TlsConnector;
+
 use super::{
-    client::{Client, Opt},
-    RpcxClient,
+    budget::RetryBudget,
+    cache::{cache_key, ResponseCache},
+    canary::CanaryRule,
+    client::{new_request_id, Client, Opt},
+    config::RegistryWatch,
+    hedge::{thread_notify, Hedge, HedgePolicy, SentCall},
+    resolver::Resolver,
+    stats::EndpointTable,
+    warmup::WarmUp,
+    RpcxClient, XClientConfig,
 };
+use bytes::Bytes;
 use futures::{future, Future};
-use rpcx_protocol::{Error, ErrorKind, Metadata, Result, RpcxParam};
+use rpcx_protocol::{
+    error_class, local_server, status_label, AdaptiveLimit, CompressType, Error, ErrorKind,
+    FaultInjector, LocalHandler, Message, MessageType, Metadata, MetricsSink, Result, RpcxMessage,
+    RpcxParam, ServiceMethod, ServicePath, StreamCompression, CLIENT_CALLS, CLIENT_CALL_DURATION,
+    CLIENT_ERRORS, PROTOCOL_VERSION, REQUEST_ID,
+};
 use std::{
+    any,
     boxed::Box,
-    cell::RefCell,
-    sync::{Arc, RwLock, RwLockWriteGuard},
+    net::SocketAddr,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 use strum_macros::{Display, EnumIter, EnumString};
 
@@ -44,67 +67,662 @@ pub enum SelectMode {
     ConsistentHash = 4,
     //Closest is selecting the closest server
     Closest = 5,
+    //LeastConnections is selecting the server with the fewest calls in flight
+    LeastConnections = 6,
+    //LoadAware is selecting randomly, weighted by the loads reported by the servers
+    LoadAware = 7,
     // SelectByUser is selecting by implementation of users
     SelectByUser = 1000,
 }
 
+/// how often `XClient` checks the selector for removed servers, the connections to them are
+/// closed.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// how long the connections to removed servers wait for their calls in flight to finish.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how the calls of a method handle failures, overriding the fail mode and the retries of
+/// the client.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CallPolicy {
+    pub fail_mode: FailMode,
+    pub retry: u8,
+}
+
 pub struct XClient<S: ClientSelector> {
     pub opt: Opt,
-    service_path: String,
-    fail_mode: FailMode,
-    clients: Arc<RwLock<HashMap<String, RefCell<Client>>>>,
-    selector: Box<S>,
+    pub(crate) service_path: ServicePath,
+    pub(crate) fail_mode: FailMode,
+    pub(crate) clients: Arc<RwLock<HashMap<String, Arc<Client>>>>,
+    pub(crate) service_opts: HashMap<String, ServiceOpt>,
+    pub(crate) selector: Box<S>,
+    // the config the client is created or reloaded from
+    pub(crate) config: Option<XClientConfig>,
+    // the discovery of the registry of the config
+    pub(crate) registry_watch: RegistryWatch,
+    pub(crate) cache: Arc<ResponseCache>,
+    pub(crate) policies: HashMap<MethodKey, CallPolicy>,
+    pub(crate) hedges: HashMap<MethodKey, Arc<Hedge>>,
+    pub(crate) canary: Option<CanaryRule>,
+    hash_keys: HashMap<MethodKey, HashKey>,
+    retry_budget: Option<Arc<RetryBudget>>,
+    adaptive_limit: Option<Arc<AdaptiveLimit>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    resolver: Arc<Resolver>,
+    // the outcomes of the calls by server, see `stats`
+    pub(crate) endpoint_stats: EndpointTable,
+    pub(crate) transport: Transport,
+    pub(crate) warm_up: Option<WarmUp>,
+    local_dispatch: bool,
+    refreshed_at: Mutex<Instant>,
+    closed: bool,
+    // the asynchronous calls which are finished, reported to the selector before the next
+    // selection
+    finished_sender: Mutex<Sender<CallOutcome>>,
+    finished_receiver: Mutex<Receiver<CallOutcome>>,
+}
+
+// the policies of methods are kept by their service and their name, so the methods of the
+// services of `call_service` don't take those of the methods of the client.
+pub(crate) type MethodKey = (ServicePath, ServiceMethod);
+
+fn method_key(service_path: &str, service_method: &str) -> MethodKey {
+    (
+        ServicePath::new(service_path),
+        ServiceMethod::new(service_method),
+    )
+}
+
+// extracts the hash key of a call from its args, fails if they are not of the type of the
+// extractor.
+type HashKey = Box<dyn Fn(&dyn RpcxParam) -> Result<String> + Send + Sync>;
+
+// the options of a service overridden by `XClient::set_service_opt` and the connections made
+// with them.
+pub(crate) struct ServiceOpt {
+    opt: Opt,
+    clients: Arc<RwLock<HashMap<String, Arc<Client>>>>,
+}
+
+// the end of a call to a server.
+struct CallOutcome {
+    server: String,
+    service_path: ServicePath,
+    service_method: ServiceMethod,
+    latency: Duration,
+    // the kind of the error the call failed with, `None` if it succeeded
+    error: Option<ErrorKind>,
+}
+
+// reports the end of an asynchronous call when it is finished or dropped.
+struct CallGuard {
+    server: String,
+    service_path: ServicePath,
+    service_method: ServiceMethod,
+    start: Instant,
+    error: Option<ErrorKind>,
+    // the adaptive limit the call holds a slot of
+    limit: Option<Arc<AdaptiveLimit>>,
+    sender: Mutex<Sender<CallOutcome>>,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        if let Some(limit) = &self.limit {
+            limit.release(self.start.elapsed(), is_dropped(self.error));
+        }
+        let outcome = CallOutcome {
+            server: std::mem::replace(&mut self.server, String::new()),
+            service_path: self.service_path.clone(),
+            service_method: self.service_method.clone(),
+            latency: self.start.elapsed(),
+            error: self.error,
+        };
+        let _ = self.sender.lock().unwrap().send(outcome);
+    }
 }
 
 impl<S: ClientSelector> XClient<S> {
-    pub fn new(service_path: String, fm: FailMode, s: Box<S>, opt: Opt) -> Self {
+    pub fn new(service_path: impl Into<ServicePath>, fm: FailMode, s: Box<S>, opt: Opt) -> Self {
+        let (finished_sender, finished_receiver) = mpsc::channel();
         XClient {
-            service_path,
+            service_path: service_path.into(),
             fail_mode: fm,
             selector: s,
             clients: Arc::new(RwLock::new(HashMap::new())),
+            service_opts: HashMap::new(),
+            config: None,
+            registry_watch: RegistryWatch::default(),
+            cache: Arc::new(ResponseCache::default()),
+            policies: HashMap::new(),
+            hedges: HashMap::new(),
+            canary: None,
+            hash_keys: HashMap::new(),
+            retry_budget: None,
+            adaptive_limit: None,
+            metrics: None,
+            resolver: Arc::new(Resolver::default()),
+            endpoint_stats: EndpointTable::default(),
+            transport: Transport::default(),
+            warm_up: None,
+            local_dispatch: false,
+            refreshed_at: Mutex::new(Instant::now()),
+            closed: false,
+            finished_sender: Mutex::new(finished_sender),
+            finished_receiver: Mutex::new(finished_receiver),
             opt,
         }
     }
 
-    fn get_cached_client<'a>(
-        &'a self,
-        clients_guard: &'a mut RwLockWriteGuard<HashMap<String, RefCell<Client>>>,
-        k: String,
-    ) -> Result<&'a mut RefCell<Client>> {
-        let client = clients_guard.get_mut(&k);
-        if client.is_none() {
-            drop(client);
-            match clients_guard.get(&k) {
-                Some(_) => {}
-                None => {
-                    let mut items: Vec<&str> = k.split('@').collect();
-                    if items.len() == 1 {
-                        items.insert(0, "tcp");
-                    }
-                    let mut created_client = Client::new(&items[1]);
-                    created_client.opt = self.opt;
-                    match created_client.start() {
-                        Ok(_) => {
-                            clients_guard.insert(k.clone(), RefCell::new(created_client));
-                        }
-                        Err(err) => return Err(err),
-                    }
-                }
+    /// closes the client gracefully. New calls fail at once, and the responses of the calls in
+    /// flight are waited for up to `timeout`. Then the connections are shut down and the
+    /// selector stops following the discovery of servers.
+    ///
+    /// An error of `ErrorKind::Timeout` is returned if some calls were still pending.
+    pub fn close(&mut self, timeout: Duration) -> Result<()> {
+        self.closed = true;
+        self.selector.close();
+
+        let deadline = Instant::now() + timeout;
+        let mut clients: Vec<Arc<Client>> = self
+            .clients
+            .write()
+            .unwrap()
+            .drain()
+            .map(|(_, client)| client)
+            .collect();
+        for (_, service_opt) in self.service_opts.drain() {
+            clients.extend(service_opt.clients.write().unwrap().drain().map(|(_, c)| c));
+        }
+        let mut rt = Ok(());
+        for client in clients {
+            let now = Instant::now();
+            let timeout = if now < deadline {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            if let Err(err) = client.close(timeout) {
+                rt = Err(err);
             }
         }
+        rt
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
 
-        let client = clients_guard.get_mut(&k);
-        match client {
-            Some(_) => Ok(client.unwrap()),
-            None => Err(Error::from("client still not found".to_owned())),
+    /// sets the fail mode and the retries of the calls of the method, for example `Failfast`
+    /// for writes which are not idempotent while reads fail over.
+    pub fn set_call_policy(&mut self, service_method: &str, policy: CallPolicy) {
+        let key = (
+            self.service_path.clone(),
+            ServiceMethod::new(service_method),
+        );
+        self.policies.insert(key, policy);
+    }
+
+    /// is like `set_call_policy` for a method of another service, which is called by
+    /// `call_service`.
+    pub fn set_service_call_policy(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        policy: CallPolicy,
+    ) {
+        self.policies
+            .insert(method_key(service_path, service_method), policy);
+    }
+
+    /// returns how the calls of the method handle failures.
+    pub fn call_policy(&self, service_method: &str) -> CallPolicy {
+        self.service_call_policy(&self.service_path, service_method)
+    }
+
+    // the retries of a service without a policy of the method are those of its options.
+    fn service_call_policy(&self, service_path: &str, service_method: &str) -> CallPolicy {
+        let policy = if self.policies.is_empty() {
+            None
+        } else {
+            self.policies.get(&method_key(service_path, service_method))
+        };
+        match policy {
+            Some(policy) => *policy,
+            None => CallPolicy {
+                fail_mode: self.fail_mode,
+                retry: self.service_opt(service_path).retry,
+            },
         }
     }
-}
 
-impl<S: ClientSelector> RpcxClient for XClient<S> {
-    fn call<T>(
+    /// overrides the options of the calls to `service_path` by `call_service`, such as their
+    /// timeouts, serialize type, compress type and retries, so one client calls services
+    /// which need different options. The calls to the service are made on connections of
+    /// their own, the connections of its previous options are closed.
+    pub fn set_service_opt(&mut self, service_path: &str, opt: Opt) {
+        let service_opt = ServiceOpt {
+            opt,
+            clients: Arc::new(RwLock::new(HashMap::new())),
+        };
+        if let Some(old) = self
+            .service_opts
+            .insert(service_path.to_owned(), service_opt)
+        {
+            close_clients(
+                old.clients
+                    .write()
+                    .unwrap()
+                    .drain()
+                    .map(|(_, c)| c)
+                    .collect(),
+            );
+        }
+    }
+
+    /// removes the options of `set_service_opt`, the calls to the service use the options
+    /// of the client again.
+    pub fn remove_service_opt(&mut self, service_path: &str) {
+        if let Some(old) = self.service_opts.remove(service_path) {
+            close_clients(
+                old.clients
+                    .write()
+                    .unwrap()
+                    .drain()
+                    .map(|(_, c)| c)
+                    .collect(),
+            );
+        }
+    }
+
+    /// returns the options of the calls to the service.
+    pub fn service_opt(&self, service_path: &str) -> Opt {
+        self.service_opts
+            .get(service_path)
+            .map_or(self.opt, |service_opt| service_opt.opt)
+    }
+
+    /// hedges the synchronous calls of the method by the policy, whatever its fail mode.
+    /// Hedges are withdrawn from the retry budget if one is set.
+    pub fn set_hedge_policy(&mut self, service_method: &str, policy: HedgePolicy) {
+        let key = (
+            self.service_path.clone(),
+            ServiceMethod::new(service_method),
+        );
+        self.hedges.insert(key, Arc::new(Hedge::new(policy)));
+    }
+
+    /// returns how the calls of the method are hedged, `None` if they are not.
+    pub fn hedge_policy(&self, service_method: &str) -> Option<HedgePolicy> {
+        self.hedges
+            .get(&method_key(&self.service_path, service_method))
+            .map(|hedge| hedge.policy)
+    }
+
+    /// routes the calls matching the rule to the servers tagged as canaries, the selector must
+    /// be a `CanarySelector` to tell them apart.
+    pub fn set_canary_rule(&mut self, rule: CanaryRule) {
+        self.canary = Some(rule);
+    }
+
+    /// hashes the calls of the method by the key `f` extracts from their args, such as
+    /// `|args: &OrderArgs| args.customer_id.to_string()`, instead of their whole payload, so
+    /// the `ConsistentHash` select mode keeps a customer on its server whatever the other
+    /// fields. The args are read as they are, without encoding, see `RpcxParam::as_any`. The
+    /// calls of the method with args of another type fail with `ErrorKind::Client`.
+    pub fn set_hash_key<T, F>(&mut self, service_method: &str, f: F)
+    where
+        T: RpcxParam + 'static,
+        F: Fn(&T) -> String + Send + Sync + 'static,
+    {
+        let method = service_method.to_owned();
+        let extract = move |args: &dyn RpcxParam| match args
+            .as_any()
+            .and_then(|args| args.downcast_ref::<T>())
+        {
+            Some(args) => Ok(f(args)),
+            None => Err(Error::new(
+                ErrorKind::Client,
+                format!(
+                    "the args of {} are not {}, which its hash key is extracted from",
+                    method,
+                    any::type_name::<T>()
+                ),
+            )),
+        };
+        let key = (
+            self.service_path.clone(),
+            ServiceMethod::new(service_method),
+        );
+        self.hash_keys.insert(key, Box::new(extract));
+    }
+
+    // tells the selector where the call with the metadata and the args is routed, fails if
+    // the hash key can't be extracted from the args.
+    fn route(
+        &self,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Result<()> {
+        if let Some(rule) = &self.canary {
+            self.selector.on_route(rule.matches(metadata));
+        }
+        if !self.hash_keys.is_empty() {
+            let key = match self
+                .hash_keys
+                .get(&method_key(service_path, service_method))
+            {
+                Some(extract) => Some(extract(args)?),
+                None => None,
+            };
+            self.selector.on_hash_key(key.as_ref().map(String::as_str));
+        }
+        Ok(())
+    }
+
+    // returns the cached client of the server for the service, or connects to it.
+    fn get_cached_client(&self, service_path: &str, k: &str) -> Result<Arc<Client>> {
+        let transport = &self.transport;
+        match self.service_opts.get(service_path) {
+            Some(service_opt) => connect_client(
+                &service_opt.clients,
+                k,
+                service_opt.opt,
+                &self.resolver,
+                transport,
+            ),
+            None => connect_client(&self.clients, k, self.opt, &self.resolver, transport),
+        }
+    }
+
+    /// compresses the whole streams of the connections made from now on, see
+    /// `Client::set_stream_compression`.
+    pub fn set_stream_compression(&mut self, compression: StreamCompression) {
+        self.transport.stream_compression = Some(compression);
+    }
+
+    /// injects the faults into the calls to the servers connected from now on, see
+    /// `Client::set_fault_injector`.
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.transport.faults = Some(injector);
+    }
+
+    /// connects the servers over TLS from now on, see `TlsConnector`. The connections made
+    /// before are kept.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, tls: TlsConnector) {
+        self.transport.tls = Some(tls);
+    }
+
+    /// sets how long the resolved addresses of the hostnames of servers are cached, 30s by
+    /// default, see `DNS_REFRESH_INTERVAL`. 0 resolves them at every connection.
+    pub fn set_dns_ttl(&self, ttl: Duration) {
+        self.resolver.set_ttl(ttl);
+    }
+
+    /// returns the cached addresses of a server, `tcp@host:port` or `host:port`, `None` if
+    /// they are not resolved or expired.
+    pub fn resolved_addrs(&self, endpoint: &str) -> Option<Vec<SocketAddr>> {
+        self.resolver.cached(endpoint_addr(endpoint))
+    }
+
+    /// drops the cached addresses of a server, `tcp@host:port` or `host:port`, and closes its
+    /// connections once their calls in flight are finished, so the next call resolves it
+    /// again. It suits failovers behind DNS, which keep the name but move the address.
+    pub fn invalidate(&self, endpoint: &str) {
+        let addr = endpoint_addr(endpoint);
+        self.resolver.invalidate(addr);
+        close_clients(self.remove_clients(|k| endpoint_addr(k) == addr));
+    }
+
+    // removes the clients of the servers matching `f` from the connections of all services.
+    pub(crate) fn remove_clients<F: Fn(&str) -> bool>(&self, f: F) -> Vec<Arc<Client>> {
+        let pools = self
+            .service_opts
+            .values()
+            .map(|service_opt| &service_opt.clients);
+        let mut removed = Vec::new();
+        for clients in std::iter::once(&self.clients).chain(pools) {
+            let mut clients = clients.write().unwrap();
+            let keys: Vec<String> = clients.keys().filter(|k| f(k)).cloned().collect();
+            removed.extend(keys.iter().filter_map(|k| clients.remove(k)));
+        }
+        removed
+    }
+
+    /// calls the servers of this process directly, without the network or the framing and
+    /// compression of messages, which suits services deployed together in one binary. A server
+    /// is local once it serves on a listener, and is found by the address of the selector, see
+    /// `local_server`.
+    ///
+    /// The args and the replies are still serialized by the serialize type of the service,
+    /// since the functions of servers take and return payloads. The message plugins of the
+    /// server run as usual, but the ciphers of the client and the queue of the server are
+    /// skipped, so it doesn't suit encrypted services.
+    pub fn enable_local_dispatch(&mut self) {
+        self.local_dispatch = true;
+    }
+
+    // calls a server of this process.
+    fn call_local<T>(
+        &self,
+        handler: &LocalHandler,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        let st = self.service_opt(service_path).serialize_type;
+        let mut req = Message::new();
+        req.set_version(PROTOCOL_VERSION);
+        req.set_message_type(MessageType::Request);
+        req.set_oneway(is_oneway);
+        req.set_serialize_type(st);
+        req.set_compress_type(CompressType::CompressNone);
+        req.service_path = service_path.to_owned();
+        req.service_method = service_method.to_owned();
+        let mut metadata = metadata.clone();
+        if !metadata.contains_key(REQUEST_ID) {
+            metadata.insert(REQUEST_ID.to_owned(), new_request_id());
+        }
+        req.metadata.replace(metadata);
+        req.payload = match args.into_bytes(st) {
+            Ok(payload) => Bytes::from(payload),
+            Err(err) => return Some(Err(err)),
+        };
+
+        let reply_msg = handler(req)?;
+        if let Some(err) = Error::from_reply(&reply_msg) {
+            return Some(Err(err));
+        }
+        let mut reply: T = Default::default();
+        Some(reply.from_bytes(st, reply_msg.payload).map(|()| reply))
+    }
+
+    /// connects to every server of the selector in the background, so the first calls don't
+    /// wait for connections. The servers which appear later are connected at the next call,
+    /// the selector is checked at most once per `WARM_UP_INTERVAL`.
+    pub fn enable_warm_up(&mut self) {
+        self.warm_up = Some(WarmUp::default());
+        self.warm_up();
+    }
+
+    // connects to the servers of the selector which are not connected yet.
+    fn warm_up(&self) {
+        let warm_up = match &self.warm_up {
+            Some(warm_up) if warm_up.is_due() => warm_up,
+            _ => return,
+        };
+        let servers: Vec<String> = {
+            let clients = self.clients.read().unwrap();
+            self.selector
+                .candidates()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|k| !clients.contains_key(k))
+                .collect()
+        };
+        let clients = self.clients.clone();
+        let opt = self.opt;
+        let resolver = self.resolver.clone();
+        let transport = self.transport.clone();
+        warm_up.connect(servers, move |k| {
+            connect_client(&clients, k, opt, &resolver, &transport)
+        });
+    }
+
+    // closes the connections to the servers which are removed from the selector, once their
+    // calls in flight are finished. Selectors which don't list their servers are skipped.
+    fn refresh_clients(&self) {
+        {
+            let mut refreshed_at = self.refreshed_at.lock().unwrap();
+            if refreshed_at.elapsed() < REFRESH_INTERVAL {
+                return;
+            }
+            *refreshed_at = Instant::now();
+        }
+        let servers: HashSet<String> = match self.selector.candidates() {
+            Some(servers) => servers.into_iter().collect(),
+            None => return,
+        };
+        close_clients(self.remove_clients(|k| !servers.contains(k)));
+    }
+
+    /// reports the calls of the client to the sink, see `CLIENT_CALLS`.
+    pub fn set_metrics_sink(&mut self, metrics: Arc<dyn MetricsSink>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// limits the retries of all fail modes by the budget, which can be shared by clients.
+    pub fn set_retry_budget(&mut self, budget: Arc<RetryBudget>) {
+        self.retry_budget = Some(budget);
+    }
+
+    /// limits the calls in flight by an adaptive limit, which can be shared by clients of
+    /// the same servers. The calls beyond it fail with `ErrorKind::ServerBusy` without being
+    /// sent, so the client sheds load before the servers collapse. Timeouts and rejections
+    /// by the servers shrink the limit.
+    pub fn set_adaptive_limit(&mut self, limit: Arc<AdaptiveLimit>) {
+        self.adaptive_limit = Some(limit);
+    }
+
+    // takes a slot of the adaptive limit, if there is one.
+    fn acquire_limit(&self) -> Result<Option<Arc<AdaptiveLimit>>> {
+        match &self.adaptive_limit {
+            Some(limit) if !limit.try_acquire() => Err(Error::new(
+                ErrorKind::ServerBusy,
+                format!(
+                    "client reached its adaptive limit of {} concurrent calls",
+                    limit.limit()
+                ),
+            )),
+            limit => Ok(limit.clone()),
+        }
+    }
+
+    // withdraws a retry from the budget if there is one.
+    fn may_retry(&self) -> bool {
+        match &self.retry_budget {
+            Some(budget) => budget.try_retry(),
+            None => true,
+        }
+    }
+
+    // reports the asynchronous calls which are finished and the loads of the servers to the
+    // selector, and refreshes the connections to the servers before the next selection.
+    fn report_finished(&self) {
+        self.refresh_clients();
+        self.warm_up();
+        let receiver = self.finished_receiver.lock().unwrap();
+        while let Ok(outcome) = receiver.try_recv() {
+            self.selector.on_call_end(&outcome.server);
+            self.report_result(
+                &outcome.server,
+                &outcome.service_path,
+                &outcome.service_method,
+                outcome.latency,
+                outcome.error,
+            );
+        }
+        drop(receiver);
+        for (server, client) in self.clients.read().unwrap().iter() {
+            if let Some(load) = client.load() {
+                self.selector.on_load(server, &load);
+            }
+        }
+    }
+
+    // reports the outcome of a call to the selector and to the metrics sink. `error` is the
+    // kind of the error the call failed with, failures are counted by its class as well.
+    fn report_result(
+        &self,
+        server: &str,
+        service_path: &str,
+        service_method: &str,
+        latency: Duration,
+        error: Option<ErrorKind>,
+    ) {
+        self.selector.on_result(server, latency, error.is_none());
+        self.endpoint_stats.record(server, latency, error);
+        if let Some(metrics) = &self.metrics {
+            let labels = [
+                ("service", service_path),
+                ("method", service_method),
+                ("server", server),
+                ("status", status_label(error.is_none())),
+            ];
+            metrics.counter(CLIENT_CALLS, 1, &labels);
+            metrics.histogram(CLIENT_CALL_DURATION, latency.as_secs_f64(), &labels);
+            if let Some(kind) = error {
+                let labels = [
+                    ("service", service_path),
+                    ("method", service_method),
+                    ("server", server),
+                    ("class", error_class(kind)),
+                ];
+                metrics.counter(CLIENT_ERRORS, 1, &labels);
+            }
+        }
+    }
+
+    // calls the servers selected by the selector and handles failures by the fail mode.
+    fn call_servers<T>(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        let limit = match self.acquire_limit() {
+            Ok(limit) => limit,
+            Err(err) => return Some(Err(err)),
+        };
+        let start = Instant::now();
+        let rt = self.select_and_call(service_path, service_method, is_oneway, metadata, args);
+        if let Some(limit) = limit {
+            let error = match &rt {
+                Some(Err(err)) => Some(err.kind()),
+                _ => None,
+            };
+            limit.release(start.elapsed(), is_dropped(error));
+        }
+        rt
+    }
+
+    // selects a server and calls it.
+    fn select_and_call<T>(
         &mut self,
+        service_path: &str,
         service_method: &str,
         is_oneway: bool,
         metadata: &Metadata,
@@ -113,10 +731,13 @@ impl<S: ClientSelector> RpcxClient for XClient<S> {
     where
         T: RpcxParam + Default,
     {
-        let service_path = self.service_path.as_str();
+        self.report_finished();
+        if let Err(err) = self.route(service_path, service_method, metadata, args) {
+            return Some(Err(err));
+        }
         // get a key from selector
         let selector = &mut (self.selector);
-        let k = selector.select(service_path, service_method, args);
+        let mut k = selector.select(service_path, service_method, args);
         if k.is_empty() {
             return Some(Err(Error::new(
                 ErrorKind::Client,
@@ -124,68 +745,214 @@ impl<S: ClientSelector> RpcxClient for XClient<S> {
             )));
         }
 
-        let mut clients_guard = self.clients.write().unwrap();
-        let client = self.get_cached_client(&mut clients_guard, k.clone());
-        if let Err(err) = client {
-            return Some(Err(Error::new(ErrorKind::Client, err)));
+        if !is_oneway && !self.hedges.is_empty() {
+            let hedge = self
+                .hedges
+                .get(&method_key(service_path, service_method))
+                .cloned();
+            if let Some(hedge) = hedge {
+                return Some(self.call_hedged(
+                    &k,
+                    &hedge,
+                    service_path,
+                    service_method,
+                    metadata,
+                    args,
+                ));
+            }
         }
-        // invoke this client
-        let mut selected_client = client.unwrap().borrow_mut();
-        let opt_rt =
-            (*selected_client).call::<T>(service_path, service_method, is_oneway, metadata, args);
 
+        let policy = self.service_call_policy(service_path, service_method);
+        if let Some(budget) = &self.retry_budget {
+            budget.record_request();
+        }
+        let mut retry = policy.retry;
+        loop {
+            self.selector.on_call_start(&k);
+            let start = Instant::now();
+            let rt = self.call_server(&k, service_path, service_method, is_oneway, metadata, args);
+            self.selector.on_call_end(&k);
+            let error = match &rt {
+                Some(Err(err)) => Some(err.kind()),
+                _ => None,
+            };
+            self.report_result(&k, service_path, service_method, start.elapsed(), error);
+
+            // the client errors of the calls waiting for their replies fail over
+            if policy.fail_mode != FailMode::Failover
+                || is_oneway
+                || error != Some(ErrorKind::Client)
+                || retry == 0
+                || !self.may_retry()
+            {
+                return rt;
+            }
+            retry -= 1;
+            // the retries go to the servers the selector selects again
+            k = self.selector.select(service_path, service_method, args);
+            if k.is_empty() {
+                return rt;
+            }
+        }
+    }
+
+    // calls the selected server, and other servers while no reply arrives in the delay of the
+    // hedge. The first successful reply is returned, or the last error if all the calls fail.
+    // Failures are not retried.
+    fn call_hedged<T>(
+        &mut self,
+        k: &str,
+        hedge: &Hedge,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Result<T>
+    where
+        T: RpcxParam + Default,
+    {
+        if let Some(budget) = &self.retry_budget {
+            budget.record_request();
+        }
+        let notify = thread_notify();
+        let start = Instant::now();
+        let delay = hedge.delay();
+        let mut calls = vec![self.send_call(k, service_path, service_method, metadata, args)?];
+        let mut hedges = 0;
+        let mut hedge_at = start + delay;
+        let mut rt = None;
+        loop {
+            let mut i = 0;
+            while i < calls.len() {
+                let call_rt = match calls[i].poll::<T>(&notify) {
+                    Some(call_rt) => call_rt,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                };
+                let call = calls.remove(i);
+                let latency = call.start.elapsed();
+                self.selector.on_call_end(&call.server);
+                let error = call_rt.as_ref().err().map(Error::kind);
+                self.report_result(&call.server, service_path, service_method, latency, error);
+                if call_rt.is_ok() {
+                    // the latency of the method includes the delays of hedges
+                    hedge.record(start.elapsed());
+                    // the calls which lost the race end now
+                    for call in calls {
+                        self.selector.on_call_end(&call.server);
+                    }
+                    return call_rt;
+                }
+                rt = Some(call_rt);
+            }
+            if calls.is_empty() {
+                return rt.unwrap();
+            }
+
+            let now = Instant::now();
+            if hedges >= hedge.policy.max_hedges {
+                thread::park();
+            } else if now < hedge_at {
+                thread::park_timeout(hedge_at - now);
+            } else {
+                hedges += 1;
+                hedge_at = now + delay;
+                if !self.may_retry() {
+                    continue;
+                }
+                // hedges go to other servers
+                let server = self.selector.select(service_path, service_method, args);
+                if server.is_empty() || calls.iter().any(|call| call.server == server) {
+                    continue;
+                }
+                if let Ok(call) =
+                    self.send_call(&server, service_path, service_method, metadata, args)
+                {
+                    calls.push(call);
+                }
+            }
+        }
+    }
+
+    // sends the call to the server without waiting for the reply.
+    fn send_call(
+        &self,
+        k: &str,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Result<SentCall> {
+        self.selector.on_call_start(k);
+        let start = Instant::now();
+        let client = match self.get_cached_client(service_path, k) {
+            Ok(client) => client,
+            Err(err) => {
+                self.selector.on_call_end(k);
+                let latency = start.elapsed();
+                self.report_result(k, service_path, service_method, latency, Some(err.kind()));
+                return Err(err);
+            }
+        };
+        let future = client.send(service_path, service_method, false, false, metadata, args);
+        Ok(SentCall::new(k, client, future))
+    }
+
+    // calls the selected server.
+    fn call_server<T>(
+        &self,
+        k: &str,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        if self.local_dispatch {
+            if let Some(handler) = local_server(k) {
+                return self.call_local(
+                    &handler,
+                    service_path,
+                    service_method,
+                    is_oneway,
+                    metadata,
+                    args,
+                );
+            }
+        }
+        let selected_client = match self.get_cached_client(service_path, k) {
+            Ok(client) => client,
+            Err(err) => return Some(Err(Error::new(ErrorKind::Client, err))),
+        };
+        // nothing waits for the reply of a oneway call, so it is neither retried nor failed over
         if is_oneway {
-            return opt_rt;
+            return selected_client.call::<T>(service_path, service_method, true, metadata, args);
         }
 
-        let rt = opt_rt.unwrap();
+        // invoke this client
+        let rt = selected_client
+            .call::<T>(service_path, service_method, false, metadata, args)
+            .unwrap_or_else(|| Err(no_reply()));
 
         match rt {
             Err(rt_err) => {
+                // `select_and_call` fails over to other servers
                 if rt_err.kind() == ErrorKind::Client {
-                    match self.fail_mode {
-                        FailMode::Failover => {
-                            let mut retry = self.opt.retry;
-                            while retry > 0 {
-                                retry -= 1;
-
-                                // re-select
-                                let mut clients_guard = self.clients.write().unwrap();
-                                let client = self.get_cached_client(&mut clients_guard, k.clone());
-                                if let Err(err) = client {
-                                    return Some(Err(err));
-                                }
-                                let mut selected_client = client.unwrap().borrow_mut();
-
-                                let opt_rt = (*selected_client).call::<T>(
-                                    service_path,
-                                    service_method,
-                                    is_oneway,
-                                    metadata,
-                                    args,
-                                );
-                                let rt = opt_rt.unwrap();
-                                if rt.is_ok() {
-                                    return Some(rt);
-                                }
-                                if rt.unwrap_err().kind() == ErrorKind::Client {
-                                    continue;
-                                }
-                            }
-                        }
-                        FailMode::Failfast => return Some(Err(rt_err)),
+                    let policy = self.service_call_policy(service_path, service_method);
+                    match policy.fail_mode {
+                        FailMode::Failover | FailMode::Failfast => return Some(Err(rt_err)),
                         FailMode::Failtry => {
-                            let mut retry = self.opt.retry;
-                            while retry > 0 {
+                            let mut retry = policy.retry;
+                            while retry > 0 && self.may_retry() {
                                 retry -= 1;
-                                let opt_rt = (*selected_client).call::<T>(
-                                    service_path,
-                                    service_method,
-                                    is_oneway,
-                                    metadata,
-                                    args,
-                                );
-                                let rt = opt_rt.unwrap();
+                                let rt = selected_client
+                                    .call::<T>(service_path, service_method, false, metadata, args)
+                                    .unwrap_or_else(|| Err(no_reply()));
                                 if rt.is_ok() {
                                     return Some(rt);
                                 }
@@ -203,8 +970,55 @@ impl<S: ClientSelector> RpcxClient for XClient<S> {
             Ok(r) => Some(Ok(r)),
         }
     }
-    fn acall<T>(
+}
+
+impl<S: ClientSelector> XClient<S> {
+    /// calls a method of another service than the one of the client, on the same servers
+    /// and with the options of `set_service_opt`. The responses of other services are not
+    /// cached.
+    pub fn call_service<T>(
         &mut self,
+        service_path: &str,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        if self.closed {
+            return Some(Err(closed_error()));
+        }
+        if is_oneway || service_path != self.service_path || !self.cache.is_enabled(service_method)
+        {
+            return self.call_servers(service_path, service_method, is_oneway, metadata, args);
+        }
+
+        let st = self.service_opt(service_path).serialize_type;
+        let key = match args.into_bytes(st) {
+            Ok(data) => cache_key(metadata, data),
+            Err(err) => return Some(Err(err)),
+        };
+        if let Some(data) = self.cache.get(service_method, &key) {
+            let mut reply: T = Default::default();
+            return Some(reply.from_slice(st, &data).map(|_| reply));
+        }
+
+        let rt: Option<Result<T>> =
+            self.call_servers(service_path, service_method, is_oneway, metadata, args);
+        if let Some(Ok(reply)) = &rt {
+            if let Ok(data) = reply.into_bytes(st) {
+                self.cache.insert(service_method, key, data);
+            }
+        }
+        rt
+    }
+
+    /// calls a method of another service asynchronously, see `call_service`.
+    pub fn acall_service<T>(
+        &mut self,
+        service_path: &str,
         service_method: &str,
         metadata: &Metadata,
         args: &dyn RpcxParam,
@@ -212,22 +1026,212 @@ impl<S: ClientSelector> RpcxClient for XClient<S> {
     where
         T: RpcxParam + Default + Sync + Send + 'static,
     {
-        let service_path = self.service_path.as_str();
+        if self.closed {
+            return Box::new(future::err(closed_error()));
+        }
+        let st = self.service_opt(service_path).serialize_type;
+        let mut cached_key = None;
+        if service_path == self.service_path && self.cache.is_enabled(service_method) {
+            let key = match args.into_bytes(st) {
+                Ok(data) => cache_key(metadata, data),
+                Err(err) => return Box::new(future::err(err)),
+            };
+            if let Some(data) = self.cache.get(service_method, &key) {
+                let mut reply: T = Default::default();
+                return Box::new(future::ok(reply.from_slice(st, &data).map(|_| reply)));
+            }
+            cached_key = Some(key);
+        }
+
+        self.report_finished();
+        if let Err(err) = self.route(service_path, service_method, metadata, args) {
+            return Box::new(future::err(err));
+        }
         // get a key from selector
         let k = self.selector.select(service_path, service_method, args);
         if k.is_empty() {
-            return Box::new(future::err(Error::from("server not found".to_owned())));
+            return Box::new(future::err(Error::new(
+                ErrorKind::Client,
+                "server not found",
+            )));
         }
 
-        let mut clients_guard = self.clients.write().unwrap();
-        let client = self.get_cached_client(&mut clients_guard, k.clone());
+        let limit = match self.acquire_limit() {
+            Ok(limit) => limit,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        self.selector.on_call_start(&k);
+        let mut guard = CallGuard {
+            server: k.clone(),
+            service_path: if service_path == self.service_path {
+                self.service_path.clone()
+            } else {
+                ServicePath::new(service_path)
+            },
+            service_method: ServiceMethod::new(service_method),
+            start: Instant::now(),
+            // the calls which are dropped before they finish fail
+            error: Some(ErrorKind::Other),
+            limit,
+            sender: Mutex::new(self.finished_sender.lock().unwrap().clone()),
+        };
+        let selected_client = match self.get_cached_client(service_path, &k) {
+            Ok(client) => client,
+            Err(err) => return Box::new(future::err(err)),
+        };
 
-        if let Err(err) = client {
-            return Box::new(future::err(err));
+        // invoke this client
+        let fut = selected_client
+            .acall::<T>(service_path, service_method, metadata, args)
+            .then(move |rt| {
+                guard.error = match &rt {
+                    Ok(Ok(_)) => None,
+                    Ok(Err(err)) | Err(err) => Some(err.kind()),
+                };
+                drop(guard);
+                rt
+            });
+        let fut: Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync> = Box::new(fut);
+        match cached_key {
+            Some(key) => {
+                let cache = self.cache.clone();
+                let service_method = service_method.to_owned();
+                Box::new(fut.map(move |rt| {
+                    if let Ok(reply) = &rt {
+                        if let Ok(data) = reply.into_bytes(st) {
+                            cache.insert(&service_method, key, data);
+                        }
+                    }
+                    rt
+                }))
+            }
+            None => fut,
         }
+    }
+}
 
-        // invoke this client
-        let mut selected_client = client.unwrap().borrow_mut();
-        selected_client.acall::<T>(service_path, service_method, metadata, args)
+impl<S: ClientSelector> RpcxClient for XClient<S> {
+    fn call<T>(
+        &mut self,
+        service_method: &str,
+        is_oneway: bool,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Option<Result<T>>
+    where
+        T: RpcxParam + Default,
+    {
+        let service_path = self.service_path.clone();
+        self.call_service(&service_path, service_method, is_oneway, metadata, args)
+    }
+    fn acall<T>(
+        &mut self,
+        service_method: &str,
+        metadata: &Metadata,
+        args: &dyn RpcxParam,
+    ) -> Box<dyn Future<Item = Result<T>, Error = Error> + Send + Sync>
+    where
+        T: RpcxParam + Default + Sync + Send + 'static,
+    {
+        let service_path = self.service_path.clone();
+        self.acall_service(&service_path, service_method, metadata, args)
+    }
+}
+
+// returns whether a call failed with an error which shows the servers are overloaded, which
+// shrinks the adaptive limit.
+fn is_dropped(error: Option<ErrorKind>) -> bool {
+    match error {
+        Some(ErrorKind::Timeout) | Some(ErrorKind::ServerBusy) | Some(ErrorKind::RateLimited) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+// how the connections to the servers are set up besides their `Opt`.
+#[derive(Clone, Default)]
+pub(crate) struct Transport {
+    stream_compression: Option<StreamCompression>,
+    faults: Option<FaultInjector>,
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<TlsConnector>,
+}
+
+impl Transport {
+    fn apply(&self, client: &mut Client) {
+        if let Some(compression) = &self.stream_compression {
+            client.set_stream_compression(compression.clone());
+        }
+        if let Some(faults) = &self.faults {
+            client.set_fault_injector(faults.clone());
+        }
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = &self.tls {
+                client.set_tls(tls.clone());
+            }
+        }
     }
 }
+
+// returns the cached client of the server, or connects to it. Cache hits only take the read
+// lock, and calls are made after the lock is released, so calls to different servers don't
+// contend.
+fn connect_client(
+    clients: &RwLock<HashMap<String, Arc<Client>>>,
+    k: &str,
+    opt: Opt,
+    resolver: &Arc<Resolver>,
+    transport: &Transport,
+) -> Result<Arc<Client>> {
+    if let Some(client) = clients.read().unwrap().get(k) {
+        return Ok(client.clone());
+    }
+
+    let mut items: Vec<&str> = k.split('@').collect();
+    if items.len() == 1 {
+        items.insert(0, "tcp");
+    }
+    let mut created_client = Client::new(&items[1]);
+    created_client.opt = opt;
+    created_client.set_resolver(resolver.clone());
+    transport.apply(&mut created_client);
+    created_client.start()?;
+
+    // keeps the client of a concurrent caller which connected first, and closes this one
+    let mut clients = clients.write().unwrap();
+    match clients.entry(k.to_owned()) {
+        Entry::Occupied(entry) => {
+            // no call is made by it, it is closed at once
+            let _ = created_client.close(Duration::from_secs(0));
+            Ok(entry.get().clone())
+        }
+        Entry::Vacant(entry) => Ok(entry.insert(Arc::new(created_client)).clone()),
+    }
+}
+
+// returns the address of a server key, `tcp@host:port` or `host:port`.
+fn endpoint_addr(endpoint: &str) -> &str {
+    endpoint.splitn(2, '@').last().unwrap_or(endpoint)
+}
+
+// closes the clients once their calls in flight are finished.
+pub(crate) fn close_clients(clients: Vec<Arc<Client>>) {
+    if clients.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        for client in clients {
+            let _ = client.close(DRAIN_TIMEOUT);
+        }
+    });
+}
+
+fn no_reply() -> Error {
+    Error::new(ErrorKind::Client, "no reply")
+}
+
+fn closed_error() -> Error {
+    Error::new(ErrorKind::Client, "xclient is closed")
+}
@@ -4,19 +4,116 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Lit, Meta, NestedMeta,
+};
 
-#[proc_macro_derive(RpcxParam)]
+/// implements `RpcxParam` by the serde implementations of structs and enums, including generic
+/// ones whose type parameters are serializable.
+///
+/// `#[rpcx(rename = "...")]` sets the name of a field in the payloads, such as the capitalized
+/// names of Go services. MsgPack payloads are positional, the names are only used to decode
+/// the ones encoded as maps.
+#[proc_macro_derive(RpcxParam, attributes(rpcx))]
 pub fn rpcx_param(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
-    let name = input.ident;
+    let name = &input.ident;
+    let (fields, renames) = match renamed_fields(&input) {
+        Ok(renamed) => renamed.into_iter().unzip::<_, _, Vec<_>, Vec<_>>(),
+        Err(err) => return TokenStream::from(err.to_compile_error()),
+    };
+
+    // the params with lifetimes can't be `Any`
+    let is_static = input.generics.lifetimes().next().is_none();
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        // `RpcxParam` requires `Debug`
+        param.bounds.push(parse_quote!(std::fmt::Debug));
+        param.bounds.push(parse_quote!(serde::Serialize));
+        param.bounds.push(parse_quote!(serde::de::DeserializeOwned));
+        if is_static {
+            param.bounds.push(parse_quote!('static));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // the renamed fields are mapped in a json value between the codec and serde
+    let (to_json, from_value) = if fields.is_empty() {
+        (
+            quote! { serde_json::to_vec(self).map_err(|err| Error::from(err)) },
+            quote! {
+                let param: Self = serde_json::from_slice(data)?;
+                *self = param;
+                Ok(())
+            },
+        )
+    } else {
+        let (field_names, payload_names) = (&fields, &renames);
+        (
+            quote! {
+                let mut value = serde_json::to_value(self)?;
+                if let Some(map) = value.as_object_mut() {
+                    #(
+                        if let Some(v) = map.remove(#field_names) {
+                            map.insert(#payload_names.to_owned(), v);
+                        }
+                    )*
+                }
+                serde_json::to_vec(&value).map_err(|err| Error::from(err))
+            },
+            quote! {
+                if let Some(map) = value.as_object_mut() {
+                    #(
+                        if let Some(v) = map.remove(#payload_names) {
+                            map.insert(#field_names.to_owned(), v);
+                        }
+                    )*
+                }
+                let param: Self = serde_json::from_value(value)?;
+                *self = param;
+                Ok(())
+            },
+        )
+    };
+    let from_msgpack = if fields.is_empty() {
+        quote! {
+            let param: Self = rmps::from_slice(data)
+                .map_err(|err| Error::new(ErrorKind::Other, err.description()))?;
+            *self = param;
+            Ok(())
+        }
+    } else {
+        quote! {
+            let mut value: serde_json::Value = rmps::from_slice(data)
+                .map_err(|err| Error::new(ErrorKind::Other, err.description()))?;
+            #from_value
+        }
+    };
+    let from_json = if fields.is_empty() {
+        from_value
+    } else {
+        quote! {
+            let mut value: serde_json::Value = serde_json::from_slice(data)?;
+            #from_value
+        }
+    };
+
+    let as_any = if is_static {
+        quote! {
+            fn as_any(&self) -> Option<&dyn std::any::Any> {
+                Some(self)
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     let expanded = quote! {
-        impl RpcxParam for #name {
+        impl #impl_generics RpcxParam for #name #ty_generics #where_clause {
             fn into_bytes(&self, st: SerializeType) -> Result<Vec<u8>> {
                 match st {
-                    SerializeType::JSON => serde_json::to_vec(self).map_err(|err| Error::from(err)),
+                    SerializeType::JSON => { #to_json }
                     SerializeType::MsgPack => {
                         rmps::to_vec(self).map_err(|err| Error::new(ErrorKind::Other, err.description()))
                     }
@@ -25,23 +122,67 @@ pub fn rpcx_param(input: TokenStream) -> TokenStream {
             }
             fn from_slice(&mut self, st: SerializeType, data: &[u8]) -> Result<()> {
                 match st {
-                    SerializeType::JSON => {
-                        let param: Self = serde_json::from_slice(data)?;
-                        *self = param;
-                        Ok(())
-                    }
-                    SerializeType::MsgPack => {
-                        let param: Self = rmps::from_slice(data)
-                            .map_err(|err| Error::new(ErrorKind::Other, err.description()))?;
-                        *self = param;
-                        Ok(())
-                    }
+                    SerializeType::JSON => { #from_json }
+                    SerializeType::MsgPack => { #from_msgpack }
                     _ => Err(Error::new(ErrorKind::Other, "unknown format")),
                 }
             }
+            #as_any
         }
     };
 
     // Hand the output tokens back to the compiler
     TokenStream::from(expanded)
 }
+
+// returns the names of the fields with `#[rpcx(rename = "...")]` and their names in payloads.
+fn renamed_fields(input: &DeriveInput) -> syn::Result<Vec<(String, String)>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    let mut renamed = Vec::new();
+    for field in fields {
+        if let Some(rename) = rename_attr(&field.attrs)? {
+            let ident = field.ident.as_ref().unwrap().to_string();
+            let ident = ident.trim_start_matches("r#").to_owned();
+            renamed.push((ident, rename));
+        }
+    }
+    Ok(renamed)
+}
+
+fn rename_attr(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    for attr in attrs {
+        if attr.path.segments.len() != 1 || attr.path.segments[0].ident != "rpcx" {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected `rpcx(...)`")),
+        };
+        // the first of the arguments is the rename
+        let first = list.nested.iter().next();
+        if let Some(nested) = first {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.ident == "rename" => {
+                    if let Lit::Str(s) = &nv.lit {
+                        return Ok(Some(s.value()));
+                    }
+                    return Err(syn::Error::new_spanned(&nv.lit, "expected a string"));
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "expected `rename = \"...\"`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,46 @@
+/* The C API of the rpcx client, implemented by the rpcx_ffi crate. */
+#ifndef RPCX_H
+#define RPCX_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* the serialize types of the payloads */
+#define RPCX_SERIALIZE_NONE 0
+#define RPCX_JSON 1
+#define RPCX_PROTOBUF 2
+#define RPCX_MSGPACK 3
+
+/* a client of a service. It must be used by one thread at a time. */
+typedef struct CClient rpcx_client;
+
+/* returns why the last call of the thread failed, or NULL. */
+const char *rpcx_last_error(void);
+
+/* creates a client of service_path on the comma separated host:port servers, or returns NULL. */
+rpcx_client *rpcx_client_connect(const char *service_path, const char *servers,
+                                 int serialize_type);
+
+/* creates a client from a TOML or YAML configuration file, or returns NULL. */
+rpcx_client *rpcx_client_from_config(const char *path);
+
+/* calls service_method with the metadata, "k1=v1&k2=v2" or NULL, and the encoded arguments.
+ * Returns 0 and stores the reply, which must be freed by rpcx_bytes_free, or returns -1. */
+int rpcx_client_call(rpcx_client *client, const char *service_method, const char *metadata,
+                     const uint8_t *args, size_t args_len, uint8_t **reply, size_t *reply_len);
+
+/* frees a reply of rpcx_client_call. */
+void rpcx_bytes_free(uint8_t *data, size_t len);
+
+/* closes the connections of the client and frees it. */
+void rpcx_client_free(rpcx_client *client);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,242 @@
+//! the C API of the rpcx client, declared in `include/rpcx.h`.
+//!
+//! A client is created by `rpcx_client_connect` or `rpcx_client_from_config` and calls the
+//! methods of one service with raw payloads, encoded by the caller in the serialize type of
+//! the client. The functions return `NULL` or `-1` on failure, and `rpcx_last_error` returns
+//! why. No panic unwinds into C.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use num_traits::FromPrimitive;
+use rpcx_client::{RpcxClient, SharedSelector, XClient, XClientConfig};
+use rpcx_protocol::*;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// a client of a service, `rpcx_client` in C. It must be used by one thread at a time.
+pub struct CClient(XClient<SharedSelector>);
+
+fn set_last_error(err: String) {
+    // the message is cut at an interior nul
+    let err = CString::new(err).unwrap_or_else(|e| {
+        let pos = e.nul_position();
+        CString::new(&e.into_vec()[..pos]).unwrap()
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(err));
+}
+
+// runs `f`, recording its error or its panic and returning `failed` instead.
+fn guard<T, F: FnOnce() -> Result<T>>(failed: T, f: F) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => {
+            set_last_error(err.to_string());
+            failed
+        }
+        Err(_) => {
+            set_last_error("rpcx client panicked".to_owned());
+            failed
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(Error::new(ErrorKind::Client, format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Error::new(ErrorKind::Client, format!("{} is not utf-8", name)))
+}
+
+/// parses `k1=v1&k2=v2`.
+fn parse_metadata(s: &str) -> Result<Metadata> {
+    let mut metadata = HashMap::new();
+    for pair in s.split('&').filter(|pair| !pair.is_empty()) {
+        let mut kv = pair.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(k), Some(v)) => {
+                metadata.insert(k.to_owned(), v.to_owned());
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Client,
+                    format!("invalid metadata {}", pair),
+                ))
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+fn into_raw(client: XClient<SharedSelector>) -> *mut CClient {
+    Box::into_raw(Box::new(CClient(client)))
+}
+
+/// returns why the last call of the thread failed, or `NULL`. The string is valid until the
+/// next failure of the thread.
+#[no_mangle]
+pub extern "C" fn rpcx_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// creates a client of `service_path` on the comma separated `host:port` servers, with the
+/// serialize type as its value in `SerializeType`.
+///
+/// # Safety
+///
+/// `service_path` and `servers` must be nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rpcx_client_connect(
+    service_path: *const c_char,
+    servers: *const c_char,
+    serialize_type: c_int,
+) -> *mut CClient {
+    guard(ptr::null_mut(), || {
+        let mut config = XClientConfig::default();
+        config.service_path = str_arg(service_path, "service path")?.to_owned();
+        for addr in str_arg(servers, "servers")?.split(',') {
+            config
+                .servers
+                .insert(format!("tcp@{}", addr.trim()), String::new());
+        }
+        config.serialize_type = u8::from_i32(serialize_type)
+            .and_then(SerializeType::from_u8)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Client,
+                    format!("invalid serialize type {}", serialize_type),
+                )
+            })?;
+        Ok(into_raw(XClient::with_config(&config)?))
+    })
+}
+
+/// creates a client from a TOML or YAML file of `XClientConfig`.
+///
+/// # Safety
+///
+/// `path` must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rpcx_client_from_config(path: *const c_char) -> *mut CClient {
+    guard(ptr::null_mut(), || {
+        let path = str_arg(path, "path")?;
+        Ok(into_raw(XClient::from_config(path)?))
+    })
+}
+
+/// calls `service_method` with the metadata, `k1=v1&k2=v2` or `NULL`, and the encoded
+/// arguments. On success the reply is stored in `reply` and `reply_len`, and must be freed by
+/// `rpcx_bytes_free`.
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `client` must be created by this library and not freed, the strings must be nul-terminated,
+/// `args` must point to `args_len` bytes, and `reply` and `reply_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rpcx_client_call(
+    client: *mut CClient,
+    service_method: *const c_char,
+    metadata: *const c_char,
+    args: *const u8,
+    args_len: usize,
+    reply: *mut *mut u8,
+    reply_len: *mut usize,
+) -> c_int {
+    guard(-1, || {
+        if client.is_null() || reply.is_null() || reply_len.is_null() {
+            return Err(Error::new(ErrorKind::Client, "null argument"));
+        }
+        let service_method = str_arg(service_method, "service method")?;
+        let metadata = if metadata.is_null() {
+            HashMap::new()
+        } else {
+            parse_metadata(str_arg(metadata, "metadata")?)?
+        };
+        let args = if args_len == 0 {
+            Bytes::new()
+        } else {
+            Bytes::from(slice::from_raw_parts(args, args_len))
+        };
+
+        let client = &mut (*client).0;
+        let rt: Bytes = client
+            .call(service_method, false, &metadata, &args)
+            .unwrap_or_else(|| Err(Error::new(ErrorKind::Client, "no reply")))?;
+
+        let rt: Box<[u8]> = rt.to_vec().into_boxed_slice();
+        *reply_len = rt.len();
+        *reply = Box::into_raw(rt) as *mut u8;
+        Ok(0)
+    })
+}
+
+/// frees a reply of `rpcx_client_call`.
+///
+/// # Safety
+///
+/// `data` and `len` must be a reply which is not freed yet, or `data` is `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn rpcx_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(slice::from_raw_parts_mut(data, len)));
+    }
+}
+
+/// closes the connections of the client and frees it.
+///
+/// # Safety
+///
+/// `client` must be created by this library and not freed yet, or `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn rpcx_client_free(client: *mut CClient) {
+    if client.is_null() {
+        return;
+    }
+    let mut client = Box::from_raw(client);
+    guard((), || client.0.close(Duration::from_secs(0)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata() {
+        let metadata = parse_metadata("a=1&b=x=y&&c=").unwrap();
+        assert_eq!(3, metadata.len());
+        assert_eq!("x=y", metadata["b"]);
+        assert_eq!("", metadata["c"]);
+        assert!(parse_metadata("a").is_err());
+    }
+
+    #[test]
+    fn last_error() {
+        unsafe {
+            let client = rpcx_client_connect(ptr::null(), ptr::null(), 1);
+            assert!(client.is_null());
+            let err = CStr::from_ptr(rpcx_last_error()).to_str().unwrap();
+            assert_eq!("service path is null", err);
+        }
+        assert_eq!(-1, guard(-1, || -> Result<c_int> { panic!("boom") }));
+        let err = unsafe { CStr::from_ptr(rpcx_last_error()) };
+        assert_eq!("rpcx client panicked", err.to_str().unwrap());
+    }
+}
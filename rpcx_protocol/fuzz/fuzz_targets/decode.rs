@@ -0,0 +1,10 @@
+// decodes a message from arbitrary bytes: the header, the lengths of the fields and the
+// compressed payloads.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rpcx_protocol::{Message, RpcxMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let mut msg = Message::new();
+    let _ = msg.decode(&mut &data[..]);
+});
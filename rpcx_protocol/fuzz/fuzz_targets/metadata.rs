@@ -0,0 +1,23 @@
+// decodes a valid message whose metadata is arbitrary, so the pairs are fuzzed instead of
+// the header.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rpcx_protocol::{Message, RpcxMessage, SerializeType};
+
+fuzz_target!(|data: &[u8]| {
+    let mut msg = Message::new();
+    msg.set_serialize_type(SerializeType::JSON);
+    msg.service_path = "Arith".to_owned();
+    msg.service_method = "Mul".to_owned();
+    let mut frame = msg.encode();
+
+    // replaces the empty metadata and the empty payload after the service method
+    frame.truncate(frame.len() - 8);
+    frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&[0, 0, 0, 0]);
+    let len = (frame.len() - 16) as u32;
+    frame[12..16].copy_from_slice(&len.to_be_bytes());
+
+    let _ = Message::new().decode(&mut &frame[..]);
+});
@@ -0,0 +1,10 @@
+// reads the messages of a connection whose bytes are arbitrary, like the read path of
+// servers and clients.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rpcx_protocol::MessageReader;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = MessageReader::new(data);
+    while reader.read_msg().is_ok() {}
+});
@@ -0,0 +1,232 @@
+use std::{sync::Mutex, time::Duration};
+
+// the weight of a sample in the long-term latency of `LimitAlgorithm::Gradient`.
+const LONG_RTT_WEIGHT: f64 = 1.0 / 600.0;
+// the weight of a new limit of `LimitAlgorithm::Gradient`, which smooths the changes.
+const SMOOTHING: f64 = 0.2;
+// how much the latest latency can exceed the long-term one before the limit shrinks.
+const TOLERANCE: f64 = 1.5;
+// what the limit is multiplied by when a call is dropped.
+const DROP_BACKOFF: f64 = 0.9;
+
+/// how an `AdaptiveLimit` adjusts the limit to the latencies of the calls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitAlgorithm {
+    /// the gradient of Netflix's concurrency-limits: the limit is scaled by the ratio of the
+    /// long-term latency to the latest one, plus a queue of the square root of the limit. It
+    /// shrinks as soon as latencies rise above the baseline and grows while they don't.
+    Gradient,
+    /// additive increase, multiplicative decrease: the limit grows by one while the calls
+    /// take `threshold` at most, and is multiplied by `backoff` when a call takes longer.
+    Aimd { threshold: Duration, backoff: f64 },
+}
+
+#[derive(Debug)]
+struct LimitState {
+    limit: f64,
+    in_flight: usize,
+    // the long-term average latency in seconds
+    long_rtt: Option<f64>,
+}
+
+/// limits the calls in flight to a limit which adapts to their latencies, so a congested
+/// server sheds the calls beyond what it can serve instead of queueing them until they time
+/// out. Dropped calls, such as timeouts and rejections, shrink the limit as well.
+///
+/// ```
+/// use rpcx_protocol::*;
+/// use std::time::Duration;
+///
+/// let limit = AdaptiveLimit::gradient(10);
+/// assert!(limit.try_acquire());
+/// limit.release(Duration::from_millis(5), false);
+/// ```
+#[derive(Debug)]
+pub struct AdaptiveLimit {
+    algorithm: LimitAlgorithm,
+    min_limit: usize,
+    max_limit: usize,
+    state: Mutex<LimitState>,
+}
+
+impl AdaptiveLimit {
+    /// starts with `initial` calls in flight at most, the limit stays in
+    /// `min_limit..=max_limit`.
+    pub fn new(
+        algorithm: LimitAlgorithm,
+        initial: usize,
+        min_limit: usize,
+        max_limit: usize,
+    ) -> Self {
+        let min_limit = min_limit.max(1);
+        let max_limit = max_limit.max(min_limit);
+        AdaptiveLimit {
+            algorithm,
+            min_limit,
+            max_limit,
+            state: Mutex::new(LimitState {
+                limit: initial.max(min_limit).min(max_limit) as f64,
+                in_flight: 0,
+                long_rtt: None,
+            }),
+        }
+    }
+
+    /// a `LimitAlgorithm::Gradient` limit between 1 and 1000.
+    pub fn gradient(initial: usize) -> Self {
+        Self::new(LimitAlgorithm::Gradient, initial, 1, 1000)
+    }
+
+    /// a `LimitAlgorithm::Aimd` limit between 1 and 1000 which backs off by 0.9.
+    pub fn aimd(initial: usize, threshold: Duration) -> Self {
+        let algorithm = LimitAlgorithm::Aimd {
+            threshold,
+            backoff: DROP_BACKOFF,
+        };
+        Self::new(algorithm, initial, 1, 1000)
+    }
+
+    /// takes a slot for a call, it returns false if the limit is reached. A call which gets a
+    /// slot must `release` it.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight as f64 >= state.limit.floor() {
+            return false;
+        }
+        state.in_flight += 1;
+        true
+    }
+
+    /// releases the slot of a call which took `rtt`, and adjusts the limit by it.
+    pub fn release(&self, rtt: Duration, dropped: bool) {
+        let mut state = self.state.lock().unwrap();
+        let in_flight = state.in_flight;
+        state.in_flight = in_flight.saturating_sub(1);
+
+        let limit = if dropped {
+            state.limit * DROP_BACKOFF
+        } else {
+            match self.algorithm {
+                LimitAlgorithm::Gradient => gradient(&mut state, in_flight, rtt.as_secs_f64()),
+                LimitAlgorithm::Aimd { threshold, backoff } => {
+                    if rtt > threshold {
+                        state.limit * backoff
+                    } else if in_flight * 2 >= state.limit as usize {
+                        state.limit + 1.0
+                    } else {
+                        state.limit
+                    }
+                }
+            }
+        };
+        state.limit = limit.clamp(self.min_limit as f64, self.max_limit as f64);
+    }
+
+    /// returns the current limit.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// returns the calls holding a slot.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+}
+
+// returns the next limit of `LimitAlgorithm::Gradient` after a call which took `rtt` seconds
+// while `in_flight` calls were running.
+fn gradient(state: &mut LimitState, in_flight: usize, rtt: f64) -> f64 {
+    let mut long_rtt = match state.long_rtt {
+        Some(long_rtt) => long_rtt * (1.0 - LONG_RTT_WEIGHT) + rtt * LONG_RTT_WEIGHT,
+        None => rtt,
+    };
+    // the latencies dropped well below the baseline, which catches up faster
+    if long_rtt > rtt * 2.0 {
+        long_rtt *= 0.95;
+    }
+    state.long_rtt = Some(long_rtt);
+
+    // the callers don't use the limit, so the latencies say nothing about it
+    if (in_flight as f64) < state.limit / 2.0 {
+        return state.limit;
+    }
+    let ratio = if rtt > 0.0 {
+        TOLERANCE * long_rtt / rtt
+    } else {
+        1.0
+    };
+    let gradient = ratio.clamp(0.5, 1.0);
+    let limit = state.limit * gradient + state.limit.sqrt();
+    state.limit * (1.0 - SMOOTHING) + limit * SMOOTHING
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire() {
+        let limit = AdaptiveLimit::new(LimitAlgorithm::Gradient, 2, 1, 10);
+        assert!(limit.try_acquire());
+        assert!(limit.try_acquire());
+        assert!(!limit.try_acquire());
+        assert_eq!(2, limit.in_flight());
+        limit.release(Duration::from_millis(10), false);
+        assert!(limit.try_acquire());
+    }
+
+    // runs `n` rounds which saturate the limit with calls taking `rtt`.
+    fn saturate(limit: &AdaptiveLimit, n: usize, rtt: Duration) {
+        for _ in 0..n {
+            let mut acquired = 0;
+            while limit.try_acquire() {
+                acquired += 1;
+            }
+            for _ in 0..acquired {
+                limit.release(rtt, false);
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_limit() {
+        let limit = AdaptiveLimit::gradient(10);
+        saturate(&limit, 3, Duration::from_millis(10));
+        let grown = limit.limit();
+        assert!(grown > 10, "{}", grown);
+
+        // latencies rise far above the baseline
+        saturate(&limit, 1, Duration::from_millis(100));
+        assert!(limit.limit() < grown, "{} {}", limit.limit(), grown);
+
+        // an idle client doesn't grow the limit
+        let limit = AdaptiveLimit::gradient(10);
+        for _ in 0..100 {
+            assert!(limit.try_acquire());
+            limit.release(Duration::from_millis(10), false);
+        }
+        assert_eq!(10, limit.limit());
+    }
+
+    #[test]
+    fn aimd_limit() {
+        let limit = AdaptiveLimit::aimd(10, Duration::from_millis(50));
+        saturate(&limit, 1, Duration::from_millis(10));
+        assert!(limit.limit() > 10);
+
+        let limit = AdaptiveLimit::aimd(10, Duration::from_millis(50));
+        assert!(limit.try_acquire());
+        limit.release(Duration::from_millis(60), false);
+        assert_eq!(9, limit.limit());
+        assert!(limit.try_acquire());
+        limit.release(Duration::from_millis(10), true);
+        assert_eq!(8, limit.limit());
+
+        // the limit doesn't drop below the minimum
+        for _ in 0..100 {
+            assert!(limit.try_acquire());
+            limit.release(Duration::from_millis(60), false);
+        }
+        assert_eq!(1, limit.limit());
+    }
+}
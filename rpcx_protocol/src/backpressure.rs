@@ -0,0 +1,156 @@
+use std::sync::{Condvar, Mutex};
+
+use strum_macros::{Display, EnumString};
+
+use crate::{Error, ErrorKind, Result};
+
+/// the default bound of the bytes queued to be written to a connection.
+pub const MAX_QUEUED_BYTES: usize = 16 * 1024 * 1024;
+
+/// what sending a message does when the outbound queue of its connection is full, since the
+/// peer reads slower than it is written to or stopped reading.
+#[derive(Debug, Copy, Clone, Display, PartialEq, EnumString)]
+pub enum BackpressurePolicy {
+    /// waits until the queue is drained below its bound or the connection is closed. A write
+    /// timeout of the connection bounds the wait for a peer which stopped reading.
+    Block,
+    /// fails the send at once with `ErrorKind::ServerBusy`.
+    Fail,
+}
+
+impl Default for BackpressurePolicy {
+    fn default() -> Self {
+        BackpressurePolicy::Block
+    }
+}
+
+/// the bytes queued to be written to a connection, bounded by a limit. Senders reserve the
+/// bytes of their messages before queueing them and the writer of the connection releases
+/// them once they are written.
+#[derive(Debug)]
+pub struct OutboundQueue {
+    limit: usize,
+    policy: BackpressurePolicy,
+    // the queued bytes and whether the connection is closed
+    state: Mutex<(usize, bool)>,
+    drained: Condvar,
+}
+
+impl OutboundQueue {
+    pub fn new(limit: usize, policy: BackpressurePolicy) -> Self {
+        OutboundQueue {
+            limit,
+            policy,
+            state: Mutex::new((0, false)),
+            drained: Condvar::new(),
+        }
+    }
+
+    /// reserves `n` bytes of the queue, waiting or failing by the policy if it is full. A
+    /// message larger than the limit is only queued into an empty queue.
+    pub fn reserve(&self, n: usize) -> Result<()> {
+        self.reserve_by(n, self.policy)
+    }
+
+    /// reserves `n` bytes of the queue, failing if it is full whatever the policy, for the
+    /// messages which must not hold their sender back.
+    pub fn try_reserve(&self, n: usize) -> Result<()> {
+        self.reserve_by(n, BackpressurePolicy::Fail)
+    }
+
+    fn reserve_by(&self, n: usize, policy: BackpressurePolicy) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let (queued, closed) = *state;
+            if closed {
+                return Err(Error::new(
+                    ErrorKind::ConnectionClosed,
+                    "connection is closed",
+                ));
+            }
+            if queued == 0 || queued + n <= self.limit {
+                state.0 += n;
+                return Ok(());
+            }
+            if policy == BackpressurePolicy::Fail {
+                return Err(Error::new(
+                    ErrorKind::ServerBusy,
+                    format!(
+                        "outbound queue of the connection is full, {} bytes queued",
+                        queued
+                    ),
+                ));
+            }
+            state = self.drained.wait(state).unwrap();
+        }
+    }
+
+    /// releases the bytes which are written.
+    pub fn release(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.0 = state.0.saturating_sub(n);
+        self.drained.notify_all();
+    }
+
+    /// fails the senders waiting for the queue and the later ones, once the connection is
+    /// closed.
+    pub fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.drained.notify_all();
+    }
+
+    /// returns the bytes which are queued and not written yet.
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn fail() {
+        let queue = OutboundQueue::new(10, BackpressurePolicy::Fail);
+        // a large message is queued into an empty queue
+        queue.reserve(20).unwrap();
+        assert_eq!(ErrorKind::ServerBusy, queue.reserve(1).unwrap_err().kind());
+        queue.release(15);
+        queue.reserve(5).unwrap();
+        assert_eq!(10, queue.queued());
+
+        queue.close();
+        assert_eq!(
+            ErrorKind::ConnectionClosed,
+            queue.reserve(1).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn block() {
+        let queue = Arc::new(OutboundQueue::new(10, BackpressurePolicy::Block));
+        queue.reserve(10).unwrap();
+
+        let queue_cloned = queue.clone();
+        let sender = thread::spawn(move || queue_cloned.reserve(5));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(10, queue.queued());
+        queue.release(5);
+        sender.join().unwrap().unwrap();
+        assert_eq!(10, queue.queued());
+        // the messages which must not wait fail whatever the policy
+        assert_eq!(
+            ErrorKind::ServerBusy,
+            queue.try_reserve(1).unwrap_err().kind()
+        );
+
+        // the blocked senders fail once the connection is closed
+        let queue_cloned = queue.clone();
+        let sender = thread::spawn(move || queue_cloned.reserve(5));
+        thread::sleep(Duration::from_millis(50));
+        queue.close();
+        assert!(sender.join().unwrap().is_err());
+    }
+}
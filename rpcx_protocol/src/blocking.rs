@@ -0,0 +1,288 @@
+//! a minimal blocking client of the frames, which is `no_std` with `alloc` like `frame`, so
+//! embedded devices can call services over their own sockets:
+//!
+//! ```ignore
+//! let mut client = BlockingClient::new(socket);
+//! let reply = client.call("Arith", "Mul", &[], 1, br#"{"A":10,"B":20}"#)?;
+//! ```
+//!
+//! Calls are sequential, the messages pushed by the server between them are skipped. Replies
+//! must be neither compressed nor chunked, which servers send to requests that are neither.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::fmt;
+
+use crate::frame::*;
+
+// the value of `MessageStatusType::Error` in the header
+const STATUS_ERROR: u8 = 1;
+// the bytes read from the transport at once
+const READ_LEN: usize = 1024;
+
+/// the connection a `BlockingClient` sends its frames over, such as a TCP socket of the
+/// network stack of a device.
+pub trait BlockingTransport {
+    type Error;
+
+    /// writes the whole of `buf`.
+    fn send(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// reads into `buf` and returns the number of bytes read, 0 once the connection is closed.
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl BlockingTransport for std::net::TcpStream {
+    type Error = std::io::Error;
+
+    fn send(&mut self, buf: &[u8]) -> Result<(), Self::Error> {
+        std::io::Write::write_all(self, buf)
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        std::io::Read::read(self, buf)
+    }
+}
+
+/// why a call of a `BlockingClient` fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockingError<E> {
+    /// the error of the transport.
+    Transport(E),
+    /// the connection is closed before the reply.
+    Closed,
+    /// a reply can't be decoded or is longer than the limit.
+    Frame(FrameError),
+    /// the error which the service replied.
+    Server(String),
+}
+
+impl<E: fmt::Display> fmt::Display for BlockingError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockingError::Transport(err) => write!(f, "{}", err),
+            BlockingError::Closed => write!(f, "connection closed"),
+            BlockingError::Frame(err) => write!(f, "{}", err),
+            BlockingError::Server(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for BlockingError<E> {}
+
+impl<E> From<FrameError> for BlockingError<E> {
+    fn from(err: FrameError) -> Self {
+        BlockingError::Frame(err)
+    }
+}
+
+/// a client which calls the services of a server one at a time over a `BlockingTransport`.
+pub struct BlockingClient<T> {
+    transport: T,
+    seq: u64,
+    // the bytes read after the last reply
+    buf: Vec<u8>,
+    max_frame_len: usize,
+}
+
+impl<T: BlockingTransport> BlockingClient<T> {
+    pub fn new(transport: T) -> Self {
+        BlockingClient {
+            transport,
+            seq: 0,
+            buf: Vec::new(),
+            max_frame_len: 1 << 20,
+        }
+    }
+
+    /// limits the bytes of the frames read, 1 MiB by default, so a peer can't make the device
+    /// allocate more. Longer frames fail the call with `FrameError::TooLong`.
+    pub fn set_max_frame_len(&mut self, max_len: usize) {
+        self.max_frame_len = max_len;
+    }
+
+    /// returns the transport, the bytes read after the last reply are dropped.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// calls the method with the payload serialized as `serialize_type`, a value of
+    /// `SerializeType`, and returns the payload of the reply.
+    pub fn call(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        metadata: &[(&str, &str)],
+        serialize_type: u8,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, BlockingError<T::Error>> {
+        let seq = self.seq;
+        self.seq = seq.wrapping_add(1);
+        let mut req = Vec::new();
+        encode_frame(
+            &request_header(seq, serialize_type),
+            service_path,
+            service_method,
+            metadata.iter().copied(),
+            payload,
+            &mut req,
+        );
+        self.transport
+            .send(&req)
+            .map_err(BlockingError::Transport)?;
+
+        loop {
+            match Frame::frame_len(&self.buf) {
+                Ok(len) if len > self.max_frame_len => {
+                    self.buf.clear();
+                    return Err(FrameError::TooLong.into());
+                }
+                Ok(len) if self.buf.len() >= len => {
+                    let reply = {
+                        let (frame, _) = Frame::decode(&self.buf[..len])?;
+                        if frame.seq() == seq {
+                            Some(reply_of(&frame))
+                        } else {
+                            None
+                        }
+                    };
+                    self.buf.drain(..len);
+                    if let Some(reply) = reply {
+                        return reply;
+                    }
+                }
+                Ok(_) | Err(FrameError::Truncated) => self.fill()?,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn fill(&mut self) -> Result<(), BlockingError<T::Error>> {
+        let mut chunk = [0u8; READ_LEN];
+        match self
+            .transport
+            .recv(&mut chunk)
+            .map_err(BlockingError::Transport)?
+        {
+            0 => Err(BlockingError::Closed),
+            n => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn reply_of<E>(frame: &Frame<'_>) -> Result<Vec<u8>, BlockingError<E>> {
+    if frame.is_chunked() {
+        return Err(BlockingError::Server(
+            "chunked replies are not supported".to_owned(),
+        ));
+    }
+    if frame.header[2] & 0x03 == STATUS_ERROR {
+        let err = frame
+            .metadata()
+            .filter_map(|pair| pair.ok())
+            .find(|(key, _)| *key == SERVICE_ERROR)
+            .map_or("", |(_, value)| value);
+        return Err(BlockingError::Server(err.to_owned()));
+    }
+    Ok(frame.payload.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // replies the frames of `replies` a few bytes at a time.
+    struct Replay {
+        written: Vec<u8>,
+        replies: Vec<u8>,
+    }
+
+    impl BlockingTransport for Replay {
+        type Error = ();
+
+        fn send(&mut self, buf: &[u8]) -> Result<(), ()> {
+            self.written.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+            let n = buf.len().min(self.replies.len()).min(7);
+            buf[..n].copy_from_slice(&self.replies[..n]);
+            self.replies.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let mut replies = Vec::new();
+        // a message pushed by the server before the reply
+        encode_frame(&request_header(9, 1), "", "", vec![], b"push", &mut replies);
+        encode_frame(&request_header(0, 1), "", "", vec![], b"21", &mut replies);
+        let mut header = request_header(1, 1);
+        header[2] |= STATUS_ERROR;
+        encode_frame(
+            &header,
+            "",
+            "",
+            vec![(SERVICE_ERROR, "denied")],
+            b"",
+            &mut replies,
+        );
+
+        let mut client = BlockingClient::new(Replay {
+            written: Vec::new(),
+            replies,
+        });
+        assert_eq!(
+            Ok(b"21".to_vec()),
+            client.call("Arith", "Mul", &[], 1, br#"{"A":3,"B":7}"#)
+        );
+        assert_eq!(
+            Err(BlockingError::Server("denied".to_owned())),
+            client.call("Arith", "Mul", &[("key", "value")], 1, b"{}")
+        );
+        assert_eq!(
+            Err(BlockingError::Closed),
+            client.call("Arith", "Mul", &[], 1, b"{}")
+        );
+
+        let written = client.into_inner().written;
+        let (frame, len) = Frame::decode(&written).unwrap();
+        assert_eq!(0, frame.seq());
+        assert_eq!("Mul", frame.service_method);
+        let (frame, _) = Frame::decode(&written[len..]).unwrap();
+        assert_eq!(1, frame.seq());
+        assert_eq!(
+            vec![Ok(("key", "value"))],
+            frame.metadata().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        let mut replies = Vec::new();
+        encode_frame(
+            &request_header(0, 1),
+            "",
+            "",
+            vec![],
+            &[0; 64],
+            &mut replies,
+        );
+        let mut client = BlockingClient::new(Replay {
+            written: Vec::new(),
+            replies,
+        });
+        client.set_max_frame_len(32);
+        assert_eq!(
+            Err(BlockingError::Frame(FrameError::TooLong)),
+            client.call("Arith", "Mul", &[], 1, b"{}")
+        );
+    }
+}
@@ -4,18 +4,32 @@ use futures::{
     Async, Future, Poll,
 };
 use std::{
+    any::Any,
     cell::RefCell,
     fmt::Debug,
     sync::{Arc, Mutex},
 };
 
-use crate::SerializeType;
+use crate::{Error, ErrorKind, SerializeType};
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 pub trait RpcxParam: Debug {
     fn into_bytes(&self, st: SerializeType) -> Result<Vec<u8>>;
     fn from_slice(&mut self, st: SerializeType, data: &[u8]) -> Result<()>;
+
+    /// is like `from_slice` but takes the payload itself, so raw params can keep it without
+    /// copying.
+    fn from_bytes(&mut self, st: SerializeType, data: Bytes) -> Result<()> {
+        self.from_slice(st, &data)
+    }
+
+    /// returns the param as `Any`, so hooks such as `XClient::set_hash_key` read the typed args
+    /// without decoding them. `None` by default, the derive returns the params without
+    /// lifetimes.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
 }
 
 impl RpcxParam for BytesMut {
@@ -27,6 +41,40 @@ impl RpcxParam for BytesMut {
         (*self).extend_from_slice(data);
         Ok(())
     }
+    fn from_bytes(&mut self, st: SerializeType, data: Bytes) -> Result<()> {
+        if !self.is_empty() {
+            return self.from_slice(st, &data);
+        }
+        // takes over the buffer if nothing else refers to it
+        match data.try_mut() {
+            Ok(data) => *self = data,
+            Err(data) => self.extend_from_slice(&data),
+        }
+        Ok(())
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl RpcxParam for Bytes {
+    fn into_bytes(&self, _: SerializeType) -> Result<Vec<u8>> {
+        Ok(self.to_vec())
+    }
+    fn from_slice(&mut self, _: SerializeType, data: &[u8]) -> Result<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+    fn from_bytes(&mut self, st: SerializeType, data: Bytes) -> Result<()> {
+        if !self.is_empty() {
+            return self.from_slice(st, &data);
+        }
+        *self = data;
+        Ok(())
+    }
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 #[derive(Debug)]
@@ -39,9 +87,13 @@ pub struct Status {
 pub struct Call {
     pub seq: u64,
     pub is_client_error: bool,
+    /// the kind of client errors.
+    pub error_kind: ErrorKind,
     pub state: Arc<Mutex<Status>>,
     pub error: String,
-    pub reply_data: Vec<u8>,
+    /// the error replied by the server.
+    pub reply_error: Option<Error>,
+    pub reply_data: Bytes,
 }
 
 impl Call {
@@ -49,12 +101,14 @@ impl Call {
         Call {
             seq,
             is_client_error: true,
+            error_kind: ErrorKind::Client,
             state: Arc::new(Mutex::new(Status {
                 ready: false,
                 task: None,
             })),
             error: String::new(),
-            reply_data: Vec::new(),
+            reply_error: None,
+            reply_data: Bytes::new(),
         }
     }
 }
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    Error, ErrorKind, Message, MessageType, Result, RpcxMessage, SerializeType, REFLECTION_SERVICE,
+    REFLECTION_STREAM_COMPRESSION,
+};
+
+// The stream compression is negotiated by the first request of a connection, a call of
+// `_reflection.StreamCompression` with the name of the compressor as its payload. If the
+// server accepts it, both ends compress the bytes they write after the request and its
+// reply as a single zstd stream, flushed at the end of each write so the peer never waits
+// for more bytes to decode a message.
+
+/// the name of the zstd compressor of streams.
+pub const STREAM_COMPRESSION_ZSTD: &str = "zstd";
+/// metadata key of the name of the dictionary a stream is compressed with.
+pub const STREAM_DICTIONARY: &str = "__rpcx_stream_dictionary__";
+/// the default zstd level of streams, which favours speed.
+pub const DEFAULT_STREAM_COMPRESSION_LEVEL: i32 = 3;
+
+const NOT_ENABLED: &str =
+    "the stream compression is not enabled, see the stream-compression feature";
+
+/// the compression of the whole stream of a connection, for verbose payloads such as JSON
+/// which are repeated across messages. A dictionary trained on typical messages, shared by
+/// the clients and the server under the same name, compresses the first messages as well.
+#[derive(Debug, Clone)]
+pub struct StreamCompression {
+    pub level: i32,
+    dictionary: Option<(String, Arc<Vec<u8>>)>,
+}
+
+impl Default for StreamCompression {
+    fn default() -> Self {
+        StreamCompression::new(DEFAULT_STREAM_COMPRESSION_LEVEL)
+    }
+}
+
+impl StreamCompression {
+    pub fn new(level: i32) -> Self {
+        StreamCompression {
+            level,
+            dictionary: None,
+        }
+    }
+
+    /// compresses the streams with the dictionary, the peer must have the same dictionary
+    /// under the same name, for example `orders-v2`.
+    pub fn with_dictionary(mut self, name: &str, dictionary: Vec<u8>) -> Self {
+        self.dictionary = Some((name.to_owned(), Arc::new(dictionary)));
+        self
+    }
+
+    /// returns the name of the dictionary, `None` if there is none.
+    pub fn dictionary_name(&self) -> Option<&str> {
+        self.dictionary.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    #[cfg(feature = "stream-compression")]
+    fn dictionary(&self) -> &[u8] {
+        self.dictionary
+            .as_ref()
+            .map_or(&[][..], |(_, dictionary)| dictionary.as_slice())
+    }
+
+    /// wraps the write half of a connection. Each flush ends a zstd block, so the bytes
+    /// written before it can be decoded by the peer.
+    #[cfg(feature = "stream-compression")]
+    pub fn encoder<W: Write + Send + 'static>(&self, w: W) -> Result<Box<dyn Write + Send>> {
+        let encoder = zstd::stream::Encoder::with_dictionary(w, self.level, self.dictionary())?;
+        Ok(Box::new(encoder))
+    }
+
+    /// wraps the write half of a connection, which fails without the stream-compression
+    /// feature.
+    #[cfg(not(feature = "stream-compression"))]
+    pub fn encoder<W: Write + Send + 'static>(&self, _: W) -> Result<Box<dyn Write + Send>> {
+        Err(Error::new(ErrorKind::Protocol, NOT_ENABLED))
+    }
+
+    /// wraps the read half of a connection.
+    #[cfg(feature = "stream-compression")]
+    pub fn decoder<R: Read + Send + 'static>(&self, r: R) -> Result<Box<dyn Read + Send>> {
+        let decoder =
+            zstd::stream::Decoder::with_dictionary(std::io::BufReader::new(r), self.dictionary())?;
+        Ok(Box::new(decoder))
+    }
+
+    /// wraps the read half of a connection, which fails without the stream-compression
+    /// feature.
+    #[cfg(not(feature = "stream-compression"))]
+    pub fn decoder<R: Read + Send + 'static>(&self, _: R) -> Result<Box<dyn Read + Send>> {
+        Err(Error::new(ErrorKind::Protocol, NOT_ENABLED))
+    }
+
+    /// builds the request which negotiates the compression, it must be the only message in
+    /// flight on the connection until it is replied.
+    pub fn request(&self, seq: u64) -> Message {
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(SerializeType::SerializeNone);
+        req.set_seq(seq);
+        req.service_path = REFLECTION_SERVICE.to_owned();
+        req.service_method = REFLECTION_STREAM_COMPRESSION.to_owned();
+        if let Some(name) = self.dictionary_name() {
+            let mut metadata = HashMap::new();
+            metadata.insert(STREAM_DICTIONARY.to_owned(), name.to_owned());
+            req.metadata.replace(metadata);
+        }
+        req.payload = Bytes::from(STREAM_COMPRESSION_ZSTD);
+        req
+    }
+
+    /// checks that the peer asks for the compressor and the dictionary of this side.
+    pub fn accept(&self, req: &Message) -> Result<()> {
+        if cfg!(not(feature = "stream-compression")) {
+            return Err(Error::new(ErrorKind::Protocol, NOT_ENABLED));
+        }
+        if &req.payload[..] != STREAM_COMPRESSION_ZSTD.as_bytes() {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!(
+                    "unsupported stream compressor {}",
+                    String::from_utf8_lossy(&req.payload)
+                ),
+            ));
+        }
+        let metadata = req.metadata.borrow();
+        let requested = metadata.get(STREAM_DICTIONARY).map(String::as_str);
+        if requested != self.dictionary_name() {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!(
+                    "unknown stream dictionary {}",
+                    requested.unwrap_or_default()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "stream-compression"))]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use super::*;
+
+    // a writer whose bytes can be read while it is still owned by an encoder.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn compress_stream() {
+        let dictionary = br#"{"service":"orders","status":"created"}"#.to_vec();
+        let compression = StreamCompression::default().with_dictionary("orders", dictionary);
+        let msg = br#"{"service":"orders","status":"created","id":1}"#;
+
+        let buf = SharedBuf::default();
+        let mut encoder = compression.encoder(buf.clone()).unwrap();
+        for _ in 0..100 {
+            encoder.write_all(msg).unwrap();
+        }
+        // the flushed bytes are decoded without the end of the stream
+        encoder.flush().unwrap();
+        let compressed = buf.0.lock().unwrap().clone();
+        assert!(compressed.len() < msg.len() * 10);
+
+        let mut decoder = compression.decoder(Cursor::new(compressed)).unwrap();
+        let mut data = vec![0u8; msg.len() * 100];
+        decoder.read_exact(&mut data).unwrap();
+        assert_eq!(msg.repeat(100), data);
+    }
+
+    #[test]
+    fn accept() {
+        let compression = StreamCompression::default().with_dictionary("orders", vec![1, 2, 3]);
+        assert!(compression.accept(&compression.request(1)).is_ok());
+
+        let other = StreamCompression::default().with_dictionary("users", vec![1, 2, 3]);
+        let err = compression.accept(&other.request(1)).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert!(compression
+            .accept(&StreamCompression::default().request(1))
+            .is_err());
+
+        let mut req = compression.request(1);
+        req.payload = Bytes::from("brotli");
+        assert!(compression.accept(&req).is_err());
+    }
+}
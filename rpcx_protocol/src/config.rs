@@ -0,0 +1,224 @@
+use std::{env, fmt::Display, fs, path::Path, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::{Error, ErrorKind, Result};
+
+/// the settings of the etcd registry services are registered to and discovered from.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RegistryConfig {
+    /// the endpoints of etcd, for example `http://127.0.0.1:2379`.
+    pub addrs: Vec<String>,
+    pub base_path: String,
+    /// the address registered by servers, it is `tcp@<addr>` of the server if it is empty.
+    pub service_addr: String,
+    /// how often servers refresh their registration.
+    pub update_interval_secs: u64,
+    /// the file clients snapshot the discovered servers to, and start with if the registry is
+    /// unreachable. Nothing is saved if it is empty.
+    pub cache_file: String,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        RegistryConfig {
+            addrs: vec!["http://127.0.0.1:2379".to_owned()],
+            base_path: "/rpcx".to_owned(),
+            service_addr: String::new(),
+            update_interval_secs: 10,
+            cache_file: String::new(),
+        }
+    }
+}
+
+impl RegistryConfig {
+    /// overrides the registry by `RPCX_REGISTRY_ADDR`, comma separated etcd endpoints which
+    /// enable the registry if it is not configured, `RPCX_REGISTRY_BASE_PATH` and
+    /// `RPCX_SERVICE_ADDR`.
+    pub fn apply_env(registry: &mut Option<RegistryConfig>) -> Result<()> {
+        if let Some(addrs) = env_var::<String>("RPCX_REGISTRY_ADDR")? {
+            registry.get_or_insert_with(Default::default).addrs = addrs
+                .split(',')
+                .map(|addr| addr.trim().to_owned())
+                .collect();
+        }
+        if let Some(registry) = registry {
+            if let Some(base_path) = env_var("RPCX_REGISTRY_BASE_PATH")? {
+                registry.base_path = base_path;
+            }
+            if let Some(service_addr) = env_var("RPCX_SERVICE_ADDR")? {
+                registry.service_addr = service_addr;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the certificates of TLS connections, see `Server::enable_tls` and `TlsConnector`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// the PEM files of the certificate and the key of this side. Clients only need them
+    /// for the servers which verify clients.
+    pub cert_file: String,
+    pub key_file: String,
+    /// the CA which signs the certificates of peers.
+    pub ca_file: String,
+    /// the name the certificate of the server is verified by, which is sent by SNI as well.
+    /// Clients use the host of the address of the server if it is empty.
+    pub server_name: String,
+    /// the protocols of ALPN in the order of preference, such as `rpcx`.
+    pub alpn_protocols: Vec<String>,
+}
+
+/// reads a configuration file. The format is told by the extension, `.toml` or `.yaml`/`.yml`.
+pub fn load_config<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path)?;
+    let config_error = |err: &dyn Display| {
+        Error::new(
+            ErrorKind::Config,
+            format!("invalid config {}: {}", path.display(), err),
+        )
+    };
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&data).map_err(|err| config_error(&err)),
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&data).map_err(|err| config_error(&err)),
+        _ => Err(config_error(&"unknown format")),
+    }
+}
+
+/// reads and parses the environment variable. It is `None` if the variable is unset or empty.
+pub fn env_var<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(ref value) if !value.is_empty() => value.parse().map(Some).map_err(|err| {
+            Error::new(
+                ErrorKind::Config,
+                format!("invalid {}={}: {}", name, value, err),
+            )
+        }),
+        _ => Ok(None),
+    }
+}
+
+/// deserializes a field by its `FromStr` implementation, such as the names of
+/// `SerializeType` variants.
+pub fn deserialize_from_str<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+/// deserializes an optional field by its `FromStr` implementation.
+pub fn deserialize_option_from_str<'de, D, T>(
+    deserializer: D,
+) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializeType;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(default)]
+    struct Config {
+        #[serde(deserialize_with = "deserialize_from_str")]
+        serialize_type: SerializeType,
+        registry: Option<RegistryConfig>,
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Config {
+                serialize_type: SerializeType::JSON,
+                registry: None,
+            }
+        }
+    }
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("rpcx_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            "serialize_type = \"MsgPack\"\n[registry]\nbase_path = \"/rpcx_test\"\n",
+        )
+        .unwrap();
+        let config: Config = load_config(&path).unwrap();
+        assert_eq!(SerializeType::MsgPack, config.serialize_type);
+        let registry = config.registry.unwrap();
+        assert_eq!("/rpcx_test", registry.base_path);
+        assert_eq!(10, registry.update_interval_secs);
+
+        let path = dir.join("config.yaml");
+        fs::write(&path, "serialize_type: Thrift\n").unwrap();
+        let config: Config = load_config(&path).unwrap();
+        assert_eq!(SerializeType::Thrift, config.serialize_type);
+        assert!(config.registry.is_none());
+
+        let path = dir.join("config.yaml");
+        fs::write(&path, "serialize_type: Bincode\n").unwrap();
+        let err = load_config::<Config, _>(&path).unwrap_err();
+        assert_eq!(ErrorKind::Config, err.kind());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn env_overrides() {
+        // the variables are restored before asserting, for the other tests of the process
+        let saved: Vec<_> = ["RPCX_REGISTRY_ADDR", "RPCX_REGISTRY_BASE_PATH"]
+            .iter()
+            .map(|name| (*name, env::var_os(name)))
+            .collect();
+        let mut registry = None;
+        env::set_var(
+            "RPCX_REGISTRY_ADDR",
+            "http://10.0.0.1:2379, http://10.0.0.2:2379",
+        );
+        env::set_var("RPCX_REGISTRY_BASE_PATH", "/rpcx_env");
+        let rt = RegistryConfig::apply_env(&mut registry);
+        for (name, value) in saved {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+        rt.unwrap();
+        let registry = registry.unwrap();
+        assert_eq!(
+            vec!["http://10.0.0.1:2379", "http://10.0.0.2:2379"],
+            registry.addrs
+        );
+        assert_eq!("/rpcx_env", registry.base_path);
+
+        env::set_var("RPCX_TEST_TIMEOUT_MS", "abc");
+        let err = env_var::<u64>("RPCX_TEST_TIMEOUT_MS").unwrap_err();
+        env::remove_var("RPCX_TEST_TIMEOUT_MS");
+        assert_eq!(ErrorKind::Config, err.kind());
+        assert_eq!(None, env_var::<u64>("RPCX_TEST_UNSET").unwrap());
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+};
+
+#[cfg(feature = "tls")]
+use crate::TlsStream;
+use crate::WsStream;
+
+/// a connection between a client and a server, plain TCP, TLS over it or WebSocket over
+/// plain TCP. Its clones share the connection, so one thread reads it while others write it.
+#[derive(Debug)]
+pub enum Conn {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(TlsStream),
+    Ws(WsStream),
+}
+
+impl Conn {
+    /// returns the TCP connection, to set its timeouts or to shut it down.
+    pub fn get_ref(&self) -> &TcpStream {
+        match self {
+            Conn::Tcp(stream) => stream,
+            #[cfg(feature = "tls")]
+            Conn::Tls(stream) => stream.get_ref(),
+            Conn::Ws(stream) => stream.get_ref().get_ref(),
+        }
+    }
+
+    pub fn is_tls(&self) -> bool {
+        match self {
+            Conn::Tcp(_) => false,
+            #[cfg(feature = "tls")]
+            Conn::Tls(_) => true,
+            Conn::Ws(stream) => stream.get_ref().is_tls(),
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Conn> {
+        match self {
+            Conn::Tcp(stream) => stream.try_clone().map(Conn::Tcp),
+            #[cfg(feature = "tls")]
+            Conn::Tls(stream) => stream.try_clone().map(Conn::Tls),
+            Conn::Ws(stream) => stream.try_clone().map(Conn::Ws),
+        }
+    }
+}
+
+impl Read for Conn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Conn::Tcp(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Conn::Tls(stream) => stream.read(buf),
+            Conn::Ws(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Conn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Conn::Tcp(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Conn::Tls(stream) => stream.write(buf),
+            Conn::Ws(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Conn::Tcp(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Conn::Tls(stream) => stream.flush(),
+            Conn::Ws(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use bytes::Bytes;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{CompressType, Error, ErrorKind, Message, MessageType, Result, RpcxMessage};
+
+// An encrypted payload is `nonce || ciphertext || tag`. The message type, the seq, the service
+// path and method and the metadata are authenticated as well, so a payload can't be replayed
+// against another method or call, or as a response, and its metadata can't be changed.
+
+/// metadata key of the cipher of an encrypted payload.
+pub const ENCRYPTION: &str = "__rpcx_encryption__";
+/// metadata key of the keys of the metadata authenticated with an encrypted payload, a JSON
+/// array. The metadata added once the payload is sealed, such as by plugins, is not.
+pub const ENCRYPTION_METADATA: &str = "__rpcx_encryption_metadata__";
+pub const ENCRYPTION_AES_256_GCM: &str = "aes-256-gcm";
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+/// encrypts payloads with a pre-shared AES-256-GCM key.
+pub struct PayloadCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for PayloadCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PayloadCipher")
+    }
+}
+
+impl PayloadCipher {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() != ENCRYPTION_KEY_LEN {
+            return Err(Error::new(
+                ErrorKind::Client,
+                format!("encryption key must be {} bytes", ENCRYPTION_KEY_LEN),
+            ));
+        }
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| Error::new(ErrorKind::Client, "invalid encryption key"))?;
+        Ok(PayloadCipher {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// encrypts the payload of the message and flags it in the metadata.
+    ///
+    /// Ciphertext doesn't compress, so the compression of the message is turned off.
+    pub fn seal(&self, msg: &mut Message) -> Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| Error::new(ErrorKind::Other, "failed to generate nonce"))?;
+
+        let mut keys: Vec<String> = msg
+            .metadata
+            .borrow()
+            .keys()
+            .filter(|key| *key != ENCRYPTION && *key != ENCRYPTION_METADATA)
+            .cloned()
+            .collect();
+        keys.sort();
+        let encoded_keys = serde_json::to_string(&keys)
+            .map_err(|err| Error::new(ErrorKind::Serialization, err))?;
+        msg.metadata
+            .borrow_mut()
+            .insert(ENCRYPTION_METADATA.to_owned(), encoded_keys);
+
+        let aad = aad(msg, &keys)?;
+        let mut data = msg.payload.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&aad),
+                &mut data,
+            )
+            .map_err(|_| Error::new(ErrorKind::Other, "failed to encrypt payload"))?;
+
+        let mut payload = Vec::with_capacity(NONCE_LEN + data.len());
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&data);
+        msg.payload = Bytes::from(payload);
+        msg.set_compress_type(CompressType::CompressNone);
+        msg.metadata
+            .borrow_mut()
+            .insert(ENCRYPTION.to_owned(), ENCRYPTION_AES_256_GCM.to_owned());
+        Ok(())
+    }
+
+    /// decrypts the payload of a message flagged by `seal`.
+    pub fn open(&self, msg: &mut Message) -> Result<()> {
+        match msg.metadata.borrow().get(ENCRYPTION).map(|v| v.as_str()) {
+            Some(ENCRYPTION_AES_256_GCM) => {}
+            Some(cipher) => {
+                return Err(Error::new(
+                    ErrorKind::Protocol,
+                    format!("unsupported cipher {}", cipher),
+                ))
+            }
+            None => return Err(Error::new(ErrorKind::Protocol, "payload is not encrypted")),
+        }
+        if msg.payload.len() < NONCE_LEN {
+            return Err(Error::new(ErrorKind::Protocol, "invalid encrypted payload"));
+        }
+
+        // the list is authenticated by the keys in the data, removing it fails the decryption
+        let keys: Vec<String> = match msg.metadata.borrow().get(ENCRYPTION_METADATA) {
+            Some(keys) => serde_json::from_str(keys)
+                .map_err(|_| Error::new(ErrorKind::Protocol, "invalid encrypted metadata"))?,
+            None => Vec::new(),
+        };
+        let aad = aad(msg, &keys)?;
+        let mut data = msg.payload[NONCE_LEN..].to_vec();
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&msg.payload[..NONCE_LEN]);
+        let len = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&aad),
+                &mut data,
+            )
+            .map_err(|_| Error::new(ErrorKind::Protocol, "failed to decrypt payload"))?
+            .len();
+        data.truncate(len);
+        msg.payload = Bytes::from(data);
+        Ok(())
+    }
+}
+
+// returns the data authenticated with the payload: the message type, the seq, the service path
+// and method and the metadata of `keys`, each field prefixed by its length.
+fn aad(msg: &Message, keys: &[String]) -> Result<Vec<u8>> {
+    let mut aad = Vec::new();
+    aad.push((msg.get_message_type() == Some(MessageType::Response)) as u8);
+    aad.extend_from_slice(&msg.get_seq().to_be_bytes());
+    push_field(&mut aad, msg.service_path.as_bytes());
+    push_field(&mut aad, msg.service_method.as_bytes());
+
+    let metadata = msg.metadata.borrow();
+    for key in keys {
+        let value = metadata.get(key).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Protocol,
+                format!("authenticated metadata {} is missing", key),
+            )
+        })?;
+        push_field(&mut aad, key.as_bytes());
+        push_field(&mut aad, value.as_bytes());
+    }
+    Ok(aad)
+}
+
+fn push_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+/// returns whether the payload of the message is encrypted.
+pub fn is_encrypted(msg: &Message) -> bool {
+    msg.metadata.borrow().contains_key(ENCRYPTION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tampered<F: FnOnce(&mut Message)>(msg: &Message, tamper: F) -> Message {
+        let mut tampered = Message::new();
+        tampered.header = msg.header;
+        tampered.service_path = msg.service_path.clone();
+        tampered.service_method = msg.service_method.clone();
+        tampered.metadata.replace(msg.metadata.borrow().clone());
+        tampered.payload = msg.payload.clone();
+        tamper(&mut tampered);
+        tampered
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let cipher = PayloadCipher::new(&[7u8; ENCRYPTION_KEY_LEN]).unwrap();
+        let mut msg = Message::new();
+        msg.set_message_type(MessageType::Request);
+        msg.set_seq(7);
+        msg.service_path = "Arith".to_owned();
+        msg.service_method = "Mul".to_owned();
+        msg.metadata
+            .borrow_mut()
+            .insert("user".to_owned(), "alice".to_owned());
+        msg.payload = Bytes::from(&b"{\"A\":1,\"B\":2}"[..]);
+
+        cipher.seal(&mut msg).unwrap();
+        assert!(is_encrypted(&msg));
+        assert_ne!(b"{\"A\":1,\"B\":2}".to_vec(), msg.payload);
+
+        let tampers: Vec<Box<dyn Fn(&mut Message)>> = vec![
+            Box::new(|m| m.service_method = "Add".to_owned()),
+            Box::new(|m| m.set_seq(8)),
+            Box::new(|m| m.set_message_type(MessageType::Response)),
+            Box::new(|m| {
+                m.metadata
+                    .borrow_mut()
+                    .insert("user".to_owned(), "bob".to_owned());
+            }),
+            Box::new(|m| {
+                m.metadata.borrow_mut().remove("user");
+            }),
+            Box::new(|m| {
+                m.metadata.borrow_mut().remove(ENCRYPTION_METADATA);
+            }),
+        ];
+        for tamper in tampers {
+            assert!(cipher.open(&mut tampered(&msg, tamper)).is_err());
+        }
+
+        // the metadata added later is not authenticated
+        msg.metadata
+            .borrow_mut()
+            .insert("load".to_owned(), "1".to_owned());
+        cipher.open(&mut msg).unwrap();
+        assert_eq!(b"{\"A\":1,\"B\":2}".to_vec(), msg.payload);
+    }
+}
@@ -1,5 +1,10 @@
 use std::{convert::From, error, fmt, result, str};
 
+use crate::{
+    Message, MessageStatusType, Metadata, RpcxMessage, SERVICE_ERROR, SERVICE_ERROR_CODE,
+    SERVICE_ERROR_KIND,
+};
+
 pub type Result<T> = result::Result<T, Error>;
 
 pub struct Error {
@@ -33,6 +38,13 @@ pub enum ErrorKind {
     Server,
     Serialization,
     Other,
+    Timeout,
+    ConnectionClosed,
+    Service,
+    Registry,
+    RateLimited,
+    Config,
+    ServerBusy,
 }
 
 impl ErrorKind {
@@ -45,10 +57,74 @@ impl ErrorKind {
             ErrorKind::Server => "server error",
             ErrorKind::Serialization => "serialization failure",
             ErrorKind::Other => "other",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::ConnectionClosed => "connection closed",
+            ErrorKind::Service => "service error",
+            ErrorKind::Registry => "registry failure",
+            ErrorKind::RateLimited => "rate limited",
+            ErrorKind::Config => "invalid configuration",
+            ErrorKind::ServerBusy => "server busy",
+        }
+    }
+
+    // the name of the kind in reply metadata.
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Protocol => "protocol",
+            ErrorKind::IO => "io",
+            ErrorKind::Client => "client",
+            ErrorKind::Network => "network",
+            ErrorKind::Server => "server",
+            ErrorKind::Serialization => "serialization",
+            ErrorKind::Other => "other",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::ConnectionClosed => "connection_closed",
+            ErrorKind::Service => "service",
+            ErrorKind::Registry => "registry",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::Config => "config",
+            ErrorKind::ServerBusy => "server_busy",
         }
     }
+
+    fn from_name(name: &str) -> Option<ErrorKind> {
+        let kind = match name {
+            "protocol" => ErrorKind::Protocol,
+            "io" => ErrorKind::IO,
+            "client" => ErrorKind::Client,
+            "network" => ErrorKind::Network,
+            "server" => ErrorKind::Server,
+            "serialization" => ErrorKind::Serialization,
+            "other" => ErrorKind::Other,
+            "timeout" => ErrorKind::Timeout,
+            "connection_closed" => ErrorKind::ConnectionClosed,
+            "service" => ErrorKind::Service,
+            "registry" => ErrorKind::Registry,
+            "rate_limited" => ErrorKind::RateLimited,
+            "config" => ErrorKind::Config,
+            "server_busy" => ErrorKind::ServerBusy,
+            _ => return None,
+        };
+        Some(kind)
+    }
 }
 
+/// an error returned by a service handler, it is sent to the client as is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServiceError {
+    pub code: i32,
+    pub message: String,
+    pub metadata: Metadata,
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl error::Error for ServiceError {}
+
 impl From<&'static str> for Error {
     #[inline]
     fn from(s: &'static str) -> Error {
@@ -74,6 +150,13 @@ impl From<serde_json::error::Error> for Error {
     }
 }
 
+impl From<crate::FrameError> for Error {
+    #[inline]
+    fn from(err: crate::FrameError) -> Error {
+        Error::new(ErrorKind::Protocol, err)
+    }
+}
+
 impl From<std::io::Error> for Error {
     #[inline]
     fn from(err: std::io::Error) -> Error {
@@ -141,6 +224,75 @@ impl Error {
             Repr::Simple(kind) => kind,
         }
     }
+
+    /// creates an error of a service handler with an application defined code and metadata.
+    pub fn service<S: Into<String>>(code: i32, message: S, metadata: Metadata) -> Error {
+        Error::new(
+            ErrorKind::Service,
+            ServiceError {
+                code,
+                message: message.into(),
+                metadata,
+            },
+        )
+    }
+
+    pub fn service_error(&self) -> Option<&ServiceError> {
+        match self.repr {
+            Repr::Custom(ref c) if c.kind == ErrorKind::Service => c.error.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// returns the error carried by a reply with the error status.
+    ///
+    /// Errors without a kind in the metadata are service errors, the remaining metadata of the
+    /// reply becomes their metadata.
+    pub fn from_reply(msg: &Message) -> Option<Error> {
+        match msg.get_message_status_type() {
+            Some(MessageStatusType::Error) => {}
+            _ => return None,
+        }
+
+        let mut metadata = msg.metadata.borrow().clone();
+        let message = metadata.remove(SERVICE_ERROR).unwrap_or_default();
+        let kind = metadata
+            .remove(SERVICE_ERROR_KIND)
+            .and_then(|name| ErrorKind::from_name(&name))
+            .unwrap_or(ErrorKind::Service);
+        let code = metadata
+            .remove(SERVICE_ERROR_CODE)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0);
+
+        if kind == ErrorKind::Service {
+            Some(Error::service(code, message, metadata))
+        } else {
+            Some(Error::new(kind, message))
+        }
+    }
+
+    /// marks the reply as failed and stores this error in its metadata.
+    pub fn set_reply(&self, reply: &mut Message) {
+        reply.set_message_status_type(MessageStatusType::Error);
+        let mut metadata = reply.metadata.borrow_mut();
+        match self.service_error() {
+            Some(err) => {
+                for (k, v) in &err.metadata {
+                    metadata.insert(k.clone(), v.clone());
+                }
+                if err.code != 0 {
+                    metadata.insert(SERVICE_ERROR_CODE.to_owned(), err.code.to_string());
+                }
+            }
+            // the kind tells other errors from service errors on the client
+            None if self.kind() != ErrorKind::Other => {
+                metadata.insert(SERVICE_ERROR_KIND.to_owned(), self.kind().name().to_owned());
+            }
+            None => {}
+        }
+        metadata.insert(SERVICE_ERROR.to_owned(), self.to_string());
+    }
 }
 
 impl fmt::Debug for Repr {
@@ -187,3 +339,33 @@ impl error::Error for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn error_in_reply() {
+        let mut metadata = HashMap::new();
+        metadata.insert("retry_after".to_owned(), "10".to_owned());
+        let mut reply = Message::new();
+        Error::service(429, "too busy", metadata.clone()).set_reply(&mut reply);
+
+        let err = Error::from_reply(&reply).unwrap();
+        assert_eq!(ErrorKind::Service, err.kind());
+        assert_eq!("too busy", err.to_string());
+        let service_error = err.service_error().unwrap();
+        assert_eq!(429, service_error.code);
+        assert_eq!(metadata, service_error.metadata);
+
+        let mut reply = Message::new();
+        Error::new(ErrorKind::RateLimited, "limit exceeded").set_reply(&mut reply);
+        let err = Error::from_reply(&reply).unwrap();
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+        assert_eq!("limit exceeded", err.to_string());
+        assert!(err.service_error().is_none());
+
+        assert!(Error::from_reply(&Message::new()).is_none());
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{Error, ErrorKind, Result};
+
+/// the status of instances which can serve requests.
+pub const EUREKA_STATUS_UP: &str = "UP";
+
+/// an instance of the REST API of Netflix Eureka, an rpcx server of a service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EurekaInstance {
+    pub instance_id: String,
+    pub host_name: String,
+    pub app: String,
+    pub ip_addr: String,
+    pub status: String,
+    pub port: EurekaPort,
+    #[serde(default)]
+    pub vip_address: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    pub data_center_info: EurekaDataCenterInfo,
+    /// how the instance is changed, only set in deltas.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EurekaPort {
+    #[serde(rename = "$")]
+    pub port: u16,
+    #[serde(rename = "@enabled")]
+    pub enabled: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EurekaDataCenterInfo {
+    #[serde(rename = "@class")]
+    pub class: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EurekaApplication {
+    pub name: String,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub instance: Vec<EurekaInstance>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EurekaApplications {
+    /// the hash of the whole registry after the delta is applied, see `eureka_hashcode`.
+    #[serde(default, rename = "apps__hashcode")]
+    pub apps_hashcode: String,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub application: Vec<EurekaApplication>,
+}
+
+#[derive(Deserialize)]
+struct ApplicationDoc {
+    application: EurekaApplication,
+}
+
+#[derive(Deserialize)]
+struct ApplicationsDoc {
+    applications: EurekaApplications,
+}
+
+#[derive(Serialize)]
+struct InstanceDoc<'a> {
+    instance: &'a EurekaInstance,
+}
+
+// Eureka serializes lists of one element as the element itself.
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(T),
+    }
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(t) => Ok(vec![t]),
+        OneOrMany::Many(v) => Ok(v),
+    }
+}
+
+/// returns the Eureka application of a service, Eureka names applications in upper case.
+pub fn eureka_app(service_path: &str) -> String {
+    service_path.to_uppercase()
+}
+
+/// returns the hash Eureka reconciles registries by, the number of instances of each status
+/// in the order of the statuses, such as `DOWN_1_UP_2_`.
+pub fn eureka_hashcode<'a, I>(instances: I) -> String
+where
+    I: IntoIterator<Item = &'a EurekaInstance>,
+{
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for instance in instances {
+        *counts.entry(instance.status.as_str()).or_default() += 1;
+    }
+    counts
+        .iter()
+        .map(|(status, count)| format!("{}_{}_", status, count))
+        .collect()
+}
+
+impl EurekaInstance {
+    /// creates the instance of a server of the service, `service_addr` is like
+    /// `tcp@127.0.0.1:8972` and the url-encoded `meta` is published as the metadata of the
+    /// instance.
+    pub fn new(service_path: &str, service_addr: &str, meta: &str) -> Result<Self> {
+        let addr = service_addr.rsplit('@').next().unwrap_or_default();
+        let invalid = || {
+            Error::new(
+                ErrorKind::Registry,
+                format!("invalid service address {}", service_addr),
+            )
+        };
+        let pos = addr.rfind(':').ok_or_else(invalid)?;
+        let host = &addr[..pos];
+        let port = addr[pos + 1..].parse::<u16>().map_err(|_| invalid())?;
+
+        let app = eureka_app(service_path);
+        Ok(EurekaInstance {
+            instance_id: format!("{}:{}:{}", host, app.to_lowercase(), port),
+            host_name: host.to_owned(),
+            app: app.clone(),
+            ip_addr: host.to_owned(),
+            status: EUREKA_STATUS_UP.to_owned(),
+            port: EurekaPort {
+                port,
+                enabled: "true".to_owned(),
+            },
+            vip_address: service_path.to_owned(),
+            metadata: parse_meta(meta),
+            data_center_info: EurekaDataCenterInfo {
+                class: "com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo".to_owned(),
+                name: "MyOwn".to_owned(),
+            },
+            action_type: None,
+        })
+    }
+
+    /// returns the server of the instance as a key and metadata of selectors, such as
+    /// `tcp@127.0.0.1:8972` and `weight=10`.
+    pub fn server(&self) -> (String, String) {
+        let key = format!("tcp@{}:{}", self.ip_addr, self.port.port);
+        // sorted for stable metadata
+        let metadata: BTreeMap<&String, &String> = self.metadata.iter().collect();
+        let meta: Vec<String> = metadata
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        (key, meta.join("&"))
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.status == EUREKA_STATUS_UP
+    }
+
+    /// returns the body of a registration.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(&InstanceDoc { instance: self })
+            .map_err(|err| Error::new(ErrorKind::Registry, err))
+    }
+}
+
+fn parse_meta(meta: &str) -> HashMap<String, String> {
+    meta.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let mut kv = p.splitn(2, '=');
+            let k = kv.next().unwrap_or_default().to_owned();
+            let v = kv.next().unwrap_or_default().to_owned();
+            (k, v)
+        })
+        .collect()
+}
+
+impl EurekaApplication {
+    /// parses the body of `GET /apps/{app}`.
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        let doc: ApplicationDoc =
+            serde_json::from_slice(data).map_err(|err| Error::new(ErrorKind::Registry, err))?;
+        Ok(doc.application)
+    }
+}
+
+impl EurekaApplications {
+    /// parses the body of `GET /apps` or `GET /apps/delta`.
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        let doc: ApplicationsDoc =
+            serde_json::from_slice(data).map_err(|err| Error::new(ErrorKind::Registry, err))?;
+        Ok(doc.applications)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance() {
+        let instance =
+            EurekaInstance::new("Arith", "tcp@127.0.0.1:8972", "weight=10&group=a").unwrap();
+        assert_eq!("ARITH", instance.app);
+        assert_eq!("127.0.0.1:arith:8972", instance.instance_id);
+        assert_eq!(
+            (
+                "tcp@127.0.0.1:8972".to_owned(),
+                "group=a&weight=10".to_owned()
+            ),
+            instance.server()
+        );
+
+        let json = String::from_utf8(instance.to_json().unwrap()).unwrap();
+        assert!(json.starts_with(r#"{"instance":{"instanceId":"127.0.0.1:arith:8972""#));
+        assert!(json.contains(r#""port":{"$":8972,"@enabled":"true"}"#));
+
+        assert!(EurekaInstance::new("Arith", "tcp@127.0.0.1", "").is_err());
+    }
+
+    #[test]
+    fn applications() {
+        let data = br#"{"applications":{"versions__delta":"1","apps__hashcode":"UP_1_",
+            "application":{"name":"ARITH","instance":{"instanceId":"127.0.0.1:arith:8972",
+            "hostName":"127.0.0.1","app":"ARITH","ipAddr":"127.0.0.1","status":"DOWN",
+            "port":{"$":8972,"@enabled":"true"},"metadata":{"weight":"10"},
+            "dataCenterInfo":{"@class":"com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo",
+            "name":"MyOwn"},"actionType":"MODIFIED"}}}}"#;
+        let apps = EurekaApplications::from_json(data).unwrap();
+        assert_eq!(1, apps.application.len());
+        let instance = &apps.application[0].instance[0];
+        assert!(!instance.is_up());
+        assert_eq!(Some("MODIFIED".to_owned()), instance.action_type);
+        assert_eq!("UP_1_", apps.apps_hashcode);
+        assert_eq!("DOWN_1_", eureka_hashcode(&apps.application[0].instance));
+
+        let up = EurekaInstance::new("Arith", "tcp@127.0.0.1:8973", "").unwrap();
+        let instances = vec![up.clone(), instance.clone(), up];
+        assert_eq!("DOWN_1_UP_2_", eureka_hashcode(&instances));
+        assert_eq!("", eureka_hashcode(&[]));
+
+        let data = br#"{"application":{"name":"ARITH","instance":[]}}"#;
+        let app = EurekaApplication::from_json(data).unwrap();
+        assert!(app.instance.is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::ErrorKind;
+use rand::{thread_rng, Rng};
+use std::{collections::HashMap, time::Duration};
+
+/// a fault injected into the calls of a method, see `FaultInjector`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// delays the call.
+    Delay(Duration),
+    /// serves the call but doesn't write its response.
+    DropResponse,
+    /// fails the call with an error of the kind instead of serving it.
+    Error(ErrorKind),
+}
+
+/// the faults injected into the calls of methods at random, by `FaultInjectionPlugin` on
+/// servers and by `Client::set_fault_injector` on clients.
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: HashMap<String, Vec<(Fault, f64)>>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// injects the fault into the calls of the method with the probability in `[0, 1]`. The
+    /// method `*` matches all methods of the service.
+    pub fn add_fault(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        fault: Fault,
+        probability: f64,
+    ) {
+        self.faults
+            .entry(format!("{}.{}", service_path, service_method))
+            .or_insert_with(Vec::new)
+            .push((fault, probability.max(0.0).min(1.0)));
+    }
+
+    /// rolls each fault of the method independently and returns the ones which hit the call.
+    pub fn roll(&self, service_path: &str, service_method: &str) -> Vec<Fault> {
+        let keys = [
+            format!("{}.{}", service_path, service_method),
+            format!("{}.*", service_path),
+        ];
+        let mut rng = thread_rng();
+        keys.iter()
+            .filter_map(|k| self.faults.get(k))
+            .flatten()
+            .filter(|(_, probability)| rng.gen_bool(*probability))
+            .map(|(fault, _)| *fault)
+            .collect()
+    }
+}
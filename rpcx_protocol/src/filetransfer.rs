@@ -0,0 +1,89 @@
+use std::{any::Any, collections::HashMap};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Error, ErrorKind, Result, RpcxParam, SerializeType};
+
+// A file is transferred in two steps like rpcx-go does: the client asks the `_filetransfer`
+// service for a transfer, gets a token and the address of the data listener, then connects to
+// this address, writes the token and streams the file. Interrupted transfers are resumed from
+// the offset in the reply (uploads) or in the args (downloads).
+
+pub const FILE_TRANSFER_SERVICE: &str = "_filetransfer";
+pub const FILE_TRANSFER_UPLOAD: &str = "TransferFile";
+pub const FILE_TRANSFER_DOWNLOAD: &str = "DownloadFile";
+pub const FILE_TRANSFER_TOKEN_SIZE: usize = 32;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct FileTransferArgs {
+    #[serde(rename = "FileName")]
+    pub file_name: String,
+    #[serde(rename = "FileSize")]
+    pub file_size: u64,
+    #[serde(rename = "Meta", default)]
+    pub meta: HashMap<String, String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadFileArgs {
+    #[serde(rename = "FileName")]
+    pub file_name: String,
+    /// the number of bytes the client already has.
+    #[serde(rename = "Offset", default)]
+    pub offset: u64,
+    #[serde(rename = "Meta", default)]
+    pub meta: HashMap<String, String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct FileTransferReply {
+    #[serde(rename = "Token")]
+    pub token: Vec<u8>,
+    #[serde(rename = "Addr")]
+    pub addr: String,
+    /// the number of bytes the server already has, the client uploads the rest.
+    #[serde(rename = "Offset", default)]
+    pub offset: u64,
+    /// the size of the downloaded file.
+    #[serde(rename = "FileSize", default)]
+    pub file_size: u64,
+}
+
+fn to_bytes<T: Serialize>(v: &T, st: SerializeType) -> Result<Vec<u8>> {
+    match st {
+        SerializeType::JSON => serde_json::to_vec(v).map_err(Error::from),
+        SerializeType::MsgPack => {
+            rmp_serde::to_vec_named(v).map_err(|err| Error::new(ErrorKind::Serialization, err))
+        }
+        _ => Err(Error::new(ErrorKind::Other, "unknown format")),
+    }
+}
+
+fn from_slice<T: DeserializeOwned>(st: SerializeType, data: &[u8]) -> Result<T> {
+    match st {
+        SerializeType::JSON => serde_json::from_slice(data).map_err(Error::from),
+        SerializeType::MsgPack => {
+            rmp_serde::from_slice(data).map_err(|err| Error::new(ErrorKind::Serialization, err))
+        }
+        _ => Err(Error::new(ErrorKind::Other, "unknown format")),
+    }
+}
+
+macro_rules! impl_rpcx_param {
+    ($($t:ty),*) => {$(
+        impl RpcxParam for $t {
+            fn into_bytes(&self, st: SerializeType) -> Result<Vec<u8>> {
+                to_bytes(self, st)
+            }
+            fn from_slice(&mut self, st: SerializeType, data: &[u8]) -> Result<()> {
+                *self = from_slice(st, data)?;
+                Ok(())
+            }
+            fn as_any(&self) -> Option<&dyn Any> {
+                Some(self)
+            }
+        }
+    )*};
+}
+
+impl_rpcx_param!(FileTransferArgs, DownloadFileArgs, FileTransferReply);
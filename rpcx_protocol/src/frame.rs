@@ -0,0 +1,459 @@
+//! the encoding of the frames of messages, which is `no_std` with `alloc` so embedded devices
+//! can speak the wire format without the rest of the crate:
+//!
+//! ```text
+//! header (12 bytes) | length of the rest (4) | service path | service method | metadata | payload
+//! ```
+//!
+//! Each field is its length (4 bytes, big endian) and its bytes. The metadata is the pairs of
+//! keys and values, each of them a field too.
+//!
+//! A payload which is longer than `MAX_CHUNK_LEN` is split into chunks. The first frame is
+//! the message with the first chunk, followed by continuation frames of the same seq with an
+//! empty service path, service method and metadata, and the next chunks. All the frames of
+//! the message are flagged by `CHUNKED` in the header.
+
+use alloc::vec::Vec;
+use core::{convert::TryFrom, fmt, str};
+
+pub const MAGIC_NUMBER: u8 = 0x08;
+/// the version of the rpcx protocol in the header of the messages sent by this
+/// implementation, the same as the Go implementation.
+pub const PROTOCOL_VERSION: u8 = 0;
+
+/// the path of the http gateway of servers which upgrades to the WebSocket transport, which
+/// browsers connect as `ws://host:port/_rpcx_/ws`.
+pub const WEBSOCKET_PATH: &str = "/_rpcx_/ws";
+/// metadata key of the message of the error of a reply.
+pub const SERVICE_ERROR: &str = "__rpcx_error__";
+
+/// the length of the header and the length of the rest of a frame.
+pub const FRAME_PREFIX_LEN: usize = 16;
+
+/// the flag in the 4th byte of the header of the frames of a message whose payload is split
+/// into chunks.
+pub const CHUNKED: u8 = 0x01;
+/// the most bytes of the payload of a frame, larger payloads are split into chunks so the
+/// frames fit their 32-bit lengths.
+pub const MAX_CHUNK_LEN: usize = 1 << 30;
+
+/// why a frame can't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// the buffer ends before the frame or one of its fields.
+    Truncated,
+    InvalidMagicNumber(u8),
+    UnsupportedVersion(u8),
+    /// the service path, the service method or the metadata is not UTF-8.
+    InvalidUtf8,
+    /// the payload doesn't end the frame.
+    InvalidPayloadLength,
+    /// the length of the frame exceeds the limit, or the memory of the target.
+    TooLong,
+    /// the metadata has more entries than the limit.
+    TooManyMetadata(usize),
+    /// a key of the metadata is longer than the limit.
+    MetadataKeyTooLong(usize),
+    /// the encoded metadata is larger than the limit.
+    MetadataTooLarge(usize),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Truncated => write!(f, "truncated message"),
+            FrameError::InvalidMagicNumber(b) => write!(f, "invalid magic number {:#04x}", b),
+            FrameError::UnsupportedVersion(v) => write!(f, "unsupported protocol version {}", v),
+            FrameError::InvalidUtf8 => write!(f, "invalid utf-8"),
+            FrameError::InvalidPayloadLength => write!(f, "invalid payload length"),
+            FrameError::TooLong => write!(f, "frame is too long"),
+            FrameError::TooManyMetadata(max) => {
+                write!(f, "metadata has more than {} entries", max)
+            }
+            FrameError::MetadataKeyTooLong(max) => {
+                write!(f, "metadata key is longer than {} bytes", max)
+            }
+            FrameError::MetadataTooLarge(max) => write!(f, "metadata exceeds {} bytes", max),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FrameError {}
+
+/// a frame borrowed from the buffer it is decoded from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame<'a> {
+    /// the header, see `RpcxMessage` for its flags.
+    pub header: [u8; 12],
+    pub service_path: &'a str,
+    pub service_method: &'a str,
+    /// the encoded pairs of the metadata, see `metadata`.
+    pub metadata: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// returns the length of the frame at the start of `buf` once its prefix is read, it
+    /// fails with `FrameError::Truncated` before. A length which overflows `usize`, as it can
+    /// on 16 and 32-bit targets, fails with `FrameError::TooLong`.
+    pub fn frame_len(buf: &[u8]) -> Result<usize, FrameError> {
+        if buf.len() < FRAME_PREFIX_LEN {
+            return Err(FrameError::Truncated);
+        }
+        usize::try_from(read_u32(&buf[12..FRAME_PREFIX_LEN]))
+            .ok()
+            .and_then(|len| len.checked_add(FRAME_PREFIX_LEN))
+            .ok_or(FrameError::TooLong)
+    }
+
+    /// decodes the frame at the start of `buf` and returns it with its length, the bytes
+    /// after it are left for the next frame.
+    pub fn decode(buf: &'a [u8]) -> Result<(Frame<'a>, usize), FrameError> {
+        let len = Self::frame_len(buf)?;
+        let mut header = [0u8; 12];
+        header.copy_from_slice(&buf[..12]);
+        check_header(&header)?;
+        if buf.len() < len {
+            return Err(FrameError::Truncated);
+        }
+        let frame = Self::decode_body(header, &buf[FRAME_PREFIX_LEN..len])?;
+        Ok((frame, len))
+    }
+
+    /// decodes the rest of a frame after its header and its length. The payload ends `body`.
+    pub fn decode_body(header: [u8; 12], body: &'a [u8]) -> Result<Frame<'a>, FrameError> {
+        let (service_path, pos) = read_field(body, 0)?;
+        let (service_method, pos) = read_field(body, pos)?;
+        let (metadata, pos) = read_field(body, pos)?;
+        let (payload, pos) = read_field(body, pos)?;
+        if pos != body.len() {
+            return Err(FrameError::InvalidPayloadLength);
+        }
+        Ok(Frame {
+            header,
+            service_path: read_str(service_path)?,
+            service_method: read_str(service_method)?,
+            metadata,
+            payload,
+        })
+    }
+
+    pub fn seq(&self) -> u64 {
+        let mut seq = [0u8; 8];
+        seq.copy_from_slice(&self.header[4..]);
+        u64::from_be_bytes(seq)
+    }
+
+    /// returns whether the frame is a chunk of a message, see `CHUNKED`.
+    pub fn is_chunked(&self) -> bool {
+        self.header[3] & CHUNKED == CHUNKED
+    }
+
+    /// returns the pairs of the metadata. A trailing key without a value is paired with an
+    /// empty value.
+    pub fn metadata(&self) -> MetadataPairs<'a> {
+        MetadataPairs {
+            buf: self.metadata,
+            pos: 0,
+        }
+    }
+
+    /// appends the frame to `buf`, with the metadata up to its first malformed pair.
+    pub fn encode_to(&self, buf: &mut Vec<u8>) {
+        let pairs = self.metadata().valid_pairs();
+        encode_frame(
+            &self.header,
+            self.service_path,
+            self.service_method,
+            pairs,
+            self.payload,
+            buf,
+        );
+    }
+}
+
+/// the limits of the metadata of a frame, so a peer can't make the receiver allocate a map of
+/// many small entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataLimits {
+    pub max_entries: usize,
+    /// the most bytes of a key.
+    pub max_key_len: usize,
+    /// the most bytes of the encoded metadata.
+    pub max_bytes: usize,
+}
+
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        MetadataLimits {
+            max_entries: 1024,
+            max_key_len: 1024,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl MetadataLimits {
+    /// checks the metadata of the frame and returns the number of its entries.
+    pub fn check(&self, frame: &Frame<'_>) -> Result<usize, FrameError> {
+        let mut entries = 0;
+        self.check_pairs(frame, |_| entries += 1)?;
+        Ok(entries)
+    }
+
+    /// checks the metadata of the frame and passes its pairs to `f` as they are checked, so
+    /// they are decoded once.
+    pub fn check_pairs<'a, F>(&self, frame: &Frame<'a>, mut f: F) -> Result<(), FrameError>
+    where
+        F: FnMut((&'a str, &'a str)),
+    {
+        if frame.metadata.len() > self.max_bytes {
+            return Err(FrameError::MetadataTooLarge(self.max_bytes));
+        }
+        let mut entries = 0;
+        for pair in frame.metadata() {
+            let (key, value) = pair?;
+            if key.len() > self.max_key_len {
+                return Err(FrameError::MetadataKeyTooLong(self.max_key_len));
+            }
+            entries += 1;
+            if entries > self.max_entries {
+                return Err(FrameError::TooManyMetadata(self.max_entries));
+            }
+            f((key, value));
+        }
+        Ok(())
+    }
+}
+
+/// the pairs of the metadata of a frame.
+#[derive(Debug, Clone)]
+pub struct MetadataPairs<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> MetadataPairs<'a> {
+    // the pairs up to the first malformed one.
+    fn valid_pairs(self) -> impl Iterator<Item = (&'a str, &'a str)> + Clone {
+        self.scan((), |_, pair| pair.ok())
+    }
+}
+
+impl<'a> Iterator for MetadataPairs<'a> {
+    type Item = Result<(&'a str, &'a str), FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+        let rt = read_field(self.buf, self.pos).and_then(|(key, pos)| {
+            let key = read_str(key)?;
+            if pos >= self.buf.len() {
+                return Ok(((key, ""), pos));
+            }
+            let (value, pos) = read_field(self.buf, pos)?;
+            Ok(((key, read_str(value)?), pos))
+        });
+        match rt {
+            Ok((pair, pos)) => {
+                self.pos = pos;
+                Some(Ok(pair))
+            }
+            Err(err) => {
+                // a malformed pair ends the metadata
+                self.pos = self.buf.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// returns the header of a request which is not compressed, with the serialize type as its
+/// value in `SerializeType`.
+pub fn request_header(seq: u64, serialize_type: u8) -> [u8; 12] {
+    let mut header = [0u8; 12];
+    header[0] = MAGIC_NUMBER;
+    header[1] = PROTOCOL_VERSION;
+    header[3] = serialize_type << 4;
+    header[4..].copy_from_slice(&seq.to_be_bytes());
+    header
+}
+
+/// appends a frame to `buf`, the payload as it is.
+pub fn encode_frame<'b, M>(
+    header: &[u8; 12],
+    service_path: &str,
+    service_method: &str,
+    metadata: M,
+    payload: &[u8],
+    buf: &mut Vec<u8>,
+) where
+    M: IntoIterator<Item = (&'b str, &'b str)> + Clone,
+{
+    let metadata_len: usize = metadata
+        .clone()
+        .into_iter()
+        .map(|(k, v)| 8 + k.len() + v.len())
+        .sum();
+    let len = 4 * 4 + service_path.len() + service_method.len() + metadata_len + payload.len();
+    // the buffer grows once
+    buf.reserve(FRAME_PREFIX_LEN + len);
+
+    buf.extend_from_slice(header);
+    buf.extend_from_slice(&(len as u32).to_be_bytes());
+    write_field(buf, service_path.as_bytes());
+    write_field(buf, service_method.as_bytes());
+    buf.extend_from_slice(&(metadata_len as u32).to_be_bytes());
+    for (key, value) in metadata {
+        write_field(buf, key.as_bytes());
+        write_field(buf, value.as_bytes());
+    }
+    write_field(buf, payload);
+}
+
+/// checks the magic number and the version of a header.
+pub fn check_header(header: &[u8; 12]) -> Result<(), FrameError> {
+    if header[0] != MAGIC_NUMBER {
+        return Err(FrameError::InvalidMagicNumber(header[0]));
+    }
+    if header[1] != PROTOCOL_VERSION {
+        return Err(FrameError::UnsupportedVersion(header[1]));
+    }
+    Ok(())
+}
+
+fn read_u32(buf: &[u8]) -> u32 {
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&buf[..4]);
+    u32::from_be_bytes(len)
+}
+
+// returns the bytes of the field at `pos` and the position after it. The lengths of malformed
+// frames can exceed the buffer.
+fn read_field(buf: &[u8], pos: usize) -> Result<(&[u8], usize), FrameError> {
+    if buf.len() < pos + 4 {
+        return Err(FrameError::Truncated);
+    }
+    let len = read_u32(&buf[pos..]) as usize;
+    let start = pos + 4;
+    if buf.len() - start < len {
+        return Err(FrameError::Truncated);
+    }
+    Ok((&buf[start..start + len], start + len))
+}
+
+fn read_str(buf: &[u8]) -> Result<&str, FrameError> {
+    str::from_utf8(buf).map_err(|_| FrameError::InvalidUtf8)
+}
+
+fn write_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn encode() -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_frame(
+            &request_header(7, 1),
+            "Arith",
+            "Mul",
+            vec![("key", "value")],
+            b"{}",
+            &mut buf,
+        );
+        buf
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = encode();
+        let len = buf.len();
+        buf.extend_from_slice(b"next");
+
+        let (frame, frame_len) = Frame::decode(&buf).unwrap();
+        assert_eq!(len, frame_len);
+        assert_eq!(7, frame.seq());
+        assert_eq!(0x10, frame.header[3]);
+        assert_eq!("Arith", frame.service_path);
+        assert_eq!("Mul", frame.service_method);
+        assert_eq!(
+            vec![Ok(("key", "value"))],
+            frame.metadata().collect::<Vec<_>>()
+        );
+        assert_eq!(b"{}", frame.payload);
+
+        let mut encoded = Vec::new();
+        frame.encode_to(&mut encoded);
+        assert_eq!(&buf[..len], &encoded[..]);
+    }
+
+    #[test]
+    fn malformed_frames() {
+        let buf = encode();
+        for len in 0..buf.len() {
+            assert_eq!(Err(FrameError::Truncated), Frame::decode(&buf[..len]));
+        }
+
+        let mut invalid = buf.clone();
+        invalid[0] = b'G';
+        assert_eq!(
+            Err(FrameError::InvalidMagicNumber(b'G')),
+            Frame::decode(&invalid)
+        );
+
+        let body = &buf[FRAME_PREFIX_LEN..];
+        let mut header = [0u8; 12];
+        header.copy_from_slice(&buf[..12]);
+        let mut longer = body.to_vec();
+        longer.push(0);
+        assert_eq!(
+            Err(FrameError::InvalidPayloadLength),
+            Frame::decode_body(header, &longer)
+        );
+
+        let frame = Frame {
+            metadata: &[0, 0, 0, 3, b'k', b'e', b'y', 0, 0, 0, 9],
+            ..Frame::decode(&buf).unwrap().0
+        };
+        assert_eq!(
+            vec![Err(FrameError::Truncated)],
+            frame.metadata().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn metadata_limits() {
+        let mut buf = Vec::new();
+        let metadata = vec![("a", "1"), ("bb", "2"), ("ccc", "3")];
+        encode_frame(&request_header(1, 1), "", "", metadata, b"", &mut buf);
+        let (frame, _) = Frame::decode(&buf).unwrap();
+
+        let limits = MetadataLimits::default();
+        assert_eq!(Ok(3), limits.check(&frame));
+        let limits = MetadataLimits {
+            max_entries: 2,
+            ..MetadataLimits::default()
+        };
+        assert_eq!(Err(FrameError::TooManyMetadata(2)), limits.check(&frame));
+        let limits = MetadataLimits {
+            max_key_len: 2,
+            ..MetadataLimits::default()
+        };
+        assert_eq!(Err(FrameError::MetadataKeyTooLong(2)), limits.check(&frame));
+        let limits = MetadataLimits {
+            max_bytes: frame.metadata.len() - 1,
+            ..MetadataLimits::default()
+        };
+        assert_eq!(
+            Err(FrameError::MetadataTooLarge(frame.metadata.len() - 1)),
+            limits.check(&frame)
+        );
+    }
+}
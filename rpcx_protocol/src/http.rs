@@ -0,0 +1,246 @@
+use bytes::Bytes;
+use num_traits::{FromPrimitive, ToPrimitive};
+use qstring::QString;
+use std::fmt::Write;
+
+use crate::{
+    Error, ErrorKind, Message, MessageStatusType, MessageType, Metadata, Result, RpcxMessage,
+    SerializeType,
+};
+
+// http headers used by the rpcx http invoke protocol (and rpcx-gateway).
+pub const X_VERSION: &str = "X-RPCX-Version";
+// the typo is kept to be compatible with rpcx-go.
+pub const X_MESSAGE_TYPE: &str = "X-RPCX-MesssageType";
+pub const X_HEARTBEAT: &str = "X-RPCX-Heartbeat";
+pub const X_ONEWAY: &str = "X-RPCX-Oneway";
+pub const X_MESSAGE_STATUS_TYPE: &str = "X-RPCX-MessageStatusType";
+pub const X_SERIALIZE_TYPE: &str = "X-RPCX-SerializeType";
+pub const X_MESSAGE_ID: &str = "X-RPCX-MessageID";
+pub const X_SERVICE_PATH: &str = "X-RPCX-ServicePath";
+pub const X_SERVICE_METHOD: &str = "X-RPCX-ServiceMethod";
+pub const X_META: &str = "X-RPCX-Meta";
+pub const X_ERROR_MESSAGE: &str = "X-RPCX-ErrorMessage";
+
+fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.trim())
+}
+
+fn parse_header<T: std::str::FromStr>(
+    headers: &[(String, String)],
+    name: &str,
+) -> Result<Option<T>> {
+    match get_header(headers, name) {
+        Some(v) if !v.is_empty() => v
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| Error::new(ErrorKind::Protocol, format!("invalid {}: {}", name, v))),
+        _ => Ok(None),
+    }
+}
+
+/// percent-encodes the bytes of a header value out of the printable ASCII range and '%' itself,
+/// like the status message of gRPC, so an error message can't break the headers.
+pub fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        if b >= 0x20 && b <= 0x7e && b != b'%' {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+/// decodes a header value encoded by `percent_encode`, the malformed escapes are kept as they
+/// are.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// decodes metadata from the url-encoded form used in `X-RPCX-Meta`.
+pub fn decode_metadata(s: &str) -> Metadata {
+    let mut metadata = Metadata::new();
+    for (k, v) in QString::from(s).into_pairs() {
+        metadata.insert(k, v);
+    }
+    metadata
+}
+
+/// encodes metadata into the url-encoded form used in `X-RPCX-Meta`.
+pub fn encode_metadata(metadata: &Metadata) -> String {
+    let pairs: Vec<(&str, &str)> = metadata
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    QString::new(pairs).to_string()
+}
+
+/// builds a message from the X-RPCX-* headers and the body of a http request or response.
+pub fn message_from_headers(headers: &[(String, String)], payload: Vec<u8>) -> Result<Message> {
+    let mut msg = Message::new();
+
+    if let Some(v) = parse_header::<u8>(headers, X_VERSION)? {
+        msg.set_version(v);
+    }
+    if let Some(v) = parse_header::<u8>(headers, X_MESSAGE_TYPE)? {
+        let mt = MessageType::from_u8(v)
+            .ok_or_else(|| Error::new(ErrorKind::Protocol, "invalid message type"))?;
+        msg.set_message_type(mt);
+    }
+    if let Some(v) = get_header(headers, X_HEARTBEAT) {
+        msg.set_heartbeat(v == "true");
+    }
+    if let Some(v) = get_header(headers, X_ONEWAY) {
+        msg.set_oneway(v == "true");
+    }
+    if let Some(v) = parse_header::<u8>(headers, X_MESSAGE_STATUS_TYPE)? {
+        let mst = MessageStatusType::from_u8(v)
+            .ok_or_else(|| Error::new(ErrorKind::Protocol, "invalid message status type"))?;
+        msg.set_message_status_type(mst);
+    }
+    match parse_header::<u8>(headers, X_SERIALIZE_TYPE)? {
+        Some(v) => {
+            let st = SerializeType::from_u8(v)
+                .ok_or_else(|| Error::new(ErrorKind::Protocol, "invalid serialize type"))?;
+            msg.set_serialize_type(st);
+        }
+        None => return Err(Error::new(ErrorKind::Protocol, "empty serialize type")),
+    }
+    if let Some(v) = parse_header::<u64>(headers, X_MESSAGE_ID)? {
+        msg.set_seq(v);
+    }
+
+    msg.service_path = get_header(headers, X_SERVICE_PATH)
+        .unwrap_or_default()
+        .to_owned();
+    msg.service_method = get_header(headers, X_SERVICE_METHOD)
+        .unwrap_or_default()
+        .to_owned();
+
+    if let Some(v) = get_header(headers, X_META) {
+        msg.metadata.replace(decode_metadata(v));
+    }
+    if let Some(v) = get_header(headers, X_ERROR_MESSAGE) {
+        msg.metadata
+            .borrow_mut()
+            .insert(crate::SERVICE_ERROR.to_owned(), percent_decode(v));
+    }
+
+    msg.payload = Bytes::from(payload);
+    Ok(msg)
+}
+
+/// converts the header and metadata of a message into X-RPCX-* http headers.
+pub fn message_to_headers(msg: &Message) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    headers.push((X_VERSION.to_owned(), msg.get_version().to_string()));
+    if let Some(mt) = msg.get_message_type() {
+        headers.push((X_MESSAGE_TYPE.to_owned(), mt.to_u8().unwrap().to_string()));
+    }
+    headers.push((X_HEARTBEAT.to_owned(), msg.is_heartbeat().to_string()));
+    headers.push((X_ONEWAY.to_owned(), msg.is_oneway().to_string()));
+    if let Some(mst) = msg.get_message_status_type() {
+        headers.push((
+            X_MESSAGE_STATUS_TYPE.to_owned(),
+            mst.to_u8().unwrap().to_string(),
+        ));
+    }
+    if let Some(st) = msg.get_serialize_type() {
+        headers.push((X_SERIALIZE_TYPE.to_owned(), st.to_u8().unwrap().to_string()));
+    }
+    headers.push((X_MESSAGE_ID.to_owned(), msg.get_seq().to_string()));
+    headers.push((X_SERVICE_PATH.to_owned(), msg.service_path.clone()));
+    headers.push((X_SERVICE_METHOD.to_owned(), msg.service_method.clone()));
+
+    let mut metadata = msg.metadata.borrow().clone();
+    if let Some(err) = metadata.remove(crate::SERVICE_ERROR) {
+        headers.push((X_ERROR_MESSAGE.to_owned(), percent_encode(&err)));
+    }
+    if !metadata.is_empty() {
+        headers.push((X_META.to_owned(), encode_metadata(&metadata)));
+    }
+
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_round_trip() {
+        let mut msg = Message::new();
+        msg.set_message_type(MessageType::Request);
+        msg.set_serialize_type(SerializeType::JSON);
+        msg.set_seq(42);
+        msg.service_path = "Arith".to_owned();
+        msg.service_method = "Mul".to_owned();
+        msg.metadata
+            .borrow_mut()
+            .insert("__ID".to_owned(), "a b&c".to_owned());
+
+        let headers = message_to_headers(&msg);
+        let decoded = message_from_headers(&headers, b"{}".to_vec()).unwrap();
+
+        assert_eq!(MessageType::Request, decoded.get_message_type().unwrap());
+        assert_eq!(SerializeType::JSON, decoded.get_serialize_type().unwrap());
+        assert_eq!(42, decoded.get_seq());
+        assert_eq!("Arith", decoded.service_path);
+        assert_eq!("Mul", decoded.service_method);
+        assert_eq!("a b&c", decoded.metadata.borrow().get("__ID").unwrap());
+        assert_eq!(b"{}".to_vec(), decoded.payload);
+    }
+
+    #[test]
+    fn error_message_header() {
+        let mut msg = Message::new();
+        msg.set_message_status_type(MessageStatusType::Error);
+        msg.metadata.borrow_mut().insert(
+            crate::SERVICE_ERROR.to_owned(),
+            "100% failed\r\nX-Injected: 1 – é".to_owned(),
+        );
+
+        // the header can't be broken by the message
+        let headers = message_to_headers(&msg);
+        let (_, v) = headers.iter().find(|(k, _)| k == X_ERROR_MESSAGE).unwrap();
+        assert_eq!("100%25 failed%0D%0AX-Injected: 1 %E2%80%93 %C3%A9", v);
+
+        let decoded = message_from_headers(&headers, Vec::new()).unwrap();
+        assert_eq!(
+            Some("100% failed\r\nX-Injected: 1 – é".to_owned()),
+            decoded.get_error()
+        );
+        assert_eq!("50%", percent_decode("50%"));
+        assert_eq!("%zz", percent_decode("%zz"));
+    }
+
+    #[test]
+    fn missing_serialize_type() {
+        let headers = vec![(X_SERVICE_PATH.to_owned(), "Arith".to_owned())];
+        assert!(message_from_headers(&headers, Vec::new()).is_err());
+    }
+}
@@ -1,7 +1,123 @@
+//! the rpcx protocol. Without the default `std` feature only `frame` and `blocking` are built,
+//! which encode and decode the frames of messages and call services with them with `no_std`
+//! and `alloc`.
+//!
+//! The compressors and the ciphers are the default `gzip`, `stream-compression` and `crypto`
+//! features. Without them `CompressType::Gzip` payloads are sent uncompressed and rejected,
+//! `StreamCompression` fails and `PayloadCipher` is not built. `TlsStream` is the default
+//! `tls` feature.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod adaptive;
+#[cfg(feature = "std")]
+pub mod backpressure;
+pub mod blocking;
+#[cfg(feature = "std")]
 pub mod call;
+#[cfg(feature = "std")]
+pub mod compression;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod conn;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
+pub mod eureka;
+#[cfg(feature = "std")]
+pub mod fault;
+#[cfg(feature = "std")]
+pub mod filetransfer;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod http;
+#[cfg(feature = "std")]
+pub mod load;
+#[cfg(feature = "std")]
+pub mod local;
+#[cfg(feature = "std")]
 pub mod message;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod names;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "std")]
+pub mod pubsub;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub mod reflection;
+#[cfg(feature = "std")]
+pub mod share;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod websocket;
+#[cfg(feature = "std")]
+pub mod wheel;
 
+#[cfg(feature = "std")]
+pub use adaptive::*;
+#[cfg(feature = "std")]
+pub use backpressure::*;
+pub use blocking::*;
+#[cfg(feature = "std")]
 pub use call::*;
+#[cfg(feature = "std")]
+pub use compression::*;
+#[cfg(feature = "std")]
+pub use config::*;
+#[cfg(feature = "std")]
+pub use conn::*;
+#[cfg(feature = "crypto")]
+pub use crypto::*;
+#[cfg(feature = "std")]
 pub use error::*;
+#[cfg(feature = "std")]
+pub use eureka::*;
+#[cfg(feature = "std")]
+pub use fault::*;
+#[cfg(feature = "std")]
+pub use filetransfer::*;
+pub use frame::*;
+#[cfg(feature = "std")]
+pub use load::*;
+#[cfg(feature = "std")]
+pub use local::*;
+#[cfg(feature = "std")]
 pub use message::*;
+#[cfg(feature = "std")]
+pub use metrics::*;
+#[cfg(feature = "std")]
+pub use names::*;
+#[cfg(feature = "std")]
+pub use pool::*;
+#[cfg(feature = "std")]
+pub use pubsub::*;
+#[cfg(feature = "std")]
+pub use reader::*;
+#[cfg(feature = "std")]
+pub use reflection::*;
+#[cfg(feature = "std")]
+pub use share::*;
+#[cfg(feature = "std")]
+pub use stream::*;
+#[cfg(feature = "tls")]
+pub use tls::*;
+#[cfg(feature = "std")]
+pub use trace::*;
+#[cfg(feature = "std")]
+pub use websocket::*;
+#[cfg(feature = "std")]
+pub use wheel::*;
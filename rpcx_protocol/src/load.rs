@@ -0,0 +1,55 @@
+use crate::Metadata;
+
+/// metadata key of the load a server attaches to its replies, such as `cpu=37&queue=4`.
+pub const LOAD: &str = "__rpcx_load__";
+
+/// the load of a server when it replied, clients weigh servers by it.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Load {
+    /// the CPU usage of the server process in percent of all the cores.
+    pub cpu: u32,
+    /// the requests waiting for a worker or being handled.
+    pub queue: usize,
+}
+
+/// attaches the load to the metadata of a reply.
+pub fn set_load(metadata: &mut Metadata, load: &Load) {
+    metadata.insert(
+        LOAD.to_owned(),
+        format!("cpu={}&queue={}", load.cpu, load.queue),
+    );
+}
+
+/// returns the load attached to the metadata, the unknown parameters are ignored.
+pub fn get_load(metadata: &Metadata) -> Option<Load> {
+    let mut load = Load::default();
+    for param in metadata.get(LOAD)?.split('&') {
+        let mut kv = param.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("cpu"), Some(v)) => load.cpu = v.parse().ok()?,
+            (Some("queue"), Some(v)) => load.queue = v.parse().ok()?,
+            _ => {}
+        }
+    }
+    Some(load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load() {
+        let mut metadata = Metadata::new();
+        assert_eq!(None, get_load(&metadata));
+        let load = Load { cpu: 37, queue: 4 };
+        set_load(&mut metadata, &load);
+        assert_eq!("cpu=37&queue=4", metadata[LOAD]);
+        assert_eq!(Some(load), get_load(&metadata));
+
+        metadata.insert(LOAD.to_owned(), "queue=2&mem=80".to_owned());
+        assert_eq!(Some(Load { cpu: 0, queue: 2 }), get_load(&metadata));
+        metadata.insert(LOAD.to_owned(), "cpu=high".to_owned());
+        assert_eq!(None, get_load(&metadata));
+    }
+}
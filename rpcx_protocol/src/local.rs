@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use super::Message;
+
+/// handles a request of a server of this process and returns its reply, `None` for a oneway
+/// request.
+pub type LocalHandler = Arc<dyn Fn(Message) -> Option<Message> + Send + Sync>;
+
+lazy_static! {
+    static ref LOCAL_SERVERS: RwLock<HashMap<SocketAddr, LocalHandler>> =
+        RwLock::new(HashMap::new());
+}
+
+/// registers a server of this process which listens on `addr`, so the clients of the process
+/// can call it without the network.
+pub fn register_local_server(addr: SocketAddr, handler: LocalHandler) {
+    LOCAL_SERVERS.write().unwrap().insert(addr, handler);
+}
+
+pub fn unregister_local_server(addr: &SocketAddr) {
+    LOCAL_SERVERS.write().unwrap().remove(addr);
+}
+
+/// returns the server of this process at the `tcp@host:port` address of a selector. A
+/// loopback address also finds a server listening on all the interfaces.
+pub fn local_server(server: &str) -> Option<LocalHandler> {
+    if !server.starts_with("tcp@") {
+        return None;
+    }
+    let addr = &server["tcp@".len()..];
+    let addr = match addr.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) if addr.starts_with("localhost:") => {
+            let port = addr["localhost:".len()..].parse().ok()?;
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)
+        }
+        Err(_) => return None,
+    };
+
+    let servers = LOCAL_SERVERS.read().unwrap();
+    if let Some(handler) = servers.get(&addr) {
+        return Some(handler.clone());
+    }
+    if !addr.ip().is_loopback() {
+        return None;
+    }
+    let any: IpAddr = match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    servers.get(&SocketAddr::new(any, addr.port())).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let echo: LocalHandler = Arc::new(Some);
+        register_local_server("127.0.0.1:18001".parse().unwrap(), echo.clone());
+        register_local_server("0.0.0.0:18002".parse().unwrap(), echo);
+
+        assert!(local_server("tcp@127.0.0.1:18001").is_some());
+        assert!(local_server("tcp@localhost:18001").is_some());
+        assert!(local_server("tcp@127.0.0.1:18002").is_some());
+        assert!(local_server("tcp@10.0.0.1:18002").is_none());
+        assert!(local_server("tcp@127.0.0.1:18003").is_none());
+        assert!(local_server("http@127.0.0.1:18001").is_none());
+
+        unregister_local_server(&"127.0.0.1:18001".parse().unwrap());
+        assert!(local_server("tcp@127.0.0.1:18001").is_none());
+    }
+}
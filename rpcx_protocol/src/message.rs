@@ -1,19 +1,60 @@
 use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
 use enum_primitive_derive::Primitive;
+#[cfg(feature = "gzip")]
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use num_traits::{FromPrimitive, ToPrimitive};
+use smallvec::SmallVec;
 use strum_macros::{Display, EnumIter, EnumString};
 
 use std::{
-    cell::RefCell,
+    cell::{Ref, RefCell, RefMut},
     collections::hash_map::HashMap,
-    io::{Read, Write},
+    io::{self, Read},
+    iter, str,
+    time::Duration,
 };
 
-use crate::{Error, Result};
+#[cfg(feature = "gzip")]
+use crate::buffer_pool;
+#[cfg(feature = "gzip")]
+use std::io::Write;
 
-const MAGIC_NUMBER: u8 = 0x08;
-pub const SERVICE_ERROR: &str = "__rpcx_error__";
+use crate::{
+    encode_frame, negotiate_compress_type, Error, ErrorKind, Frame, MetadataExt, MetadataLimits,
+    Result, CHUNKED, FRAME_PREFIX_LEN, MAGIC_NUMBER, MAX_CHUNK_LEN, PROTOCOL_VERSION,
+    SERVICE_ERROR,
+};
+
+/// metadata key of the code of a `ServiceError`.
+pub const SERVICE_ERROR_CODE: &str = "__rpcx_error_code__";
+/// metadata key of the `ErrorKind` of an error which is not a `ServiceError`.
+pub const SERVICE_ERROR_KIND: &str = "__rpcx_error_kind__";
+/// metadata key of the id of a request, which is echoed back in its response.
+pub const REQUEST_ID: &str = "__rpcx_request_id__";
+/// metadata key of the time left to the deadline of a call in milliseconds when it is sent. It
+/// is relative so the clocks of clients and servers don't need to be in sync.
+pub const DEADLINE: &str = "__rpcx_deadline__";
+/// metadata key of the idempotency key of a request. The requests of a method with the same
+/// key are handled once by servers with an `IdempotencyPlugin`, so a request can be retried
+/// or sent to another server without being applied twice.
+pub const IDEMPOTENCY_KEY: &str = "__rpcx_idempotency_key__";
+/// metadata key of the priority of a request, `high`, `normal` or `low`, see `Priority`.
+pub const PRIORITY: &str = "__rpcx_priority__";
+/// metadata key of the length of the payload of a message which is split into chunks, in the
+/// first of its frames. It is removed once the chunks are read.
+pub const CHUNKED_PAYLOAD_LEN: &str = "__rpcx_chunked_len__";
+/// the most bytes of a message which is read, of each of its frames and of its payload once it
+/// is decompressed, unless another limit is set, such as by `Server::set_max_message_len` or
+/// `Opt::max_message_len` of clients.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// the key of the registry metadata of the state of a server. Clients don't select servers
+/// which are `inactive` or `paused`, and keep their connections to the paused ones.
+pub const STATE: &str = "state";
+pub const STATE_ACTIVE: &str = "active";
+pub const STATE_INACTIVE: &str = "inactive";
+pub const STATE_PAUSED: &str = "paused";
 
 #[derive(Debug, Copy, Clone, Display, PartialEq, EnumIter, EnumString, Primitive)]
 pub enum MessageType {
@@ -65,20 +106,143 @@ pub trait RpcxMessage {
     where
         R: Read;
     fn encode(&self) -> Vec<u8>;
+    /// appends the encoded message to the buffer.
+    fn encode_to(&self, buf: &mut Vec<u8>);
 
     fn get_error(&self) -> Option<String>;
 }
 
 pub type Metadata = HashMap<String, String>;
 
+/// propagates the deadline of a call, `timeout` from now, to the server in the metadata.
+pub fn set_deadline(metadata: &mut Metadata, timeout: Duration) {
+    metadata.set_u64(DEADLINE, timeout.as_millis() as u64);
+}
+
+/// returns the time left to the deadline propagated in the metadata when it was sent.
+pub fn get_deadline(metadata: &Metadata) -> Option<Duration> {
+    metadata.get_u64(DEADLINE).map(Duration::from_millis)
+}
+
+/// the priority of a request. The workers of a connection take its waiting requests by
+/// priority, so health checks and control-plane calls are not stuck behind bulk traffic.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+}
+
+/// sets the priority of a call in the metadata.
+pub fn set_priority(metadata: &mut Metadata, priority: Priority) {
+    metadata.insert(PRIORITY.to_owned(), priority.as_str().to_owned());
+}
+
+/// returns the priority of a request, `Priority::Normal` if it is missing or unknown.
+pub fn get_priority(metadata: &Metadata) -> Priority {
+    match metadata.get(PRIORITY).map(String::as_str) {
+        Some("high") => Priority::High,
+        Some("low") => Priority::Low,
+        _ => Priority::Normal,
+    }
+}
+
+/// the metadata of a message, which is borrowed like a `RefCell<Metadata>`. The metadata of a
+/// decoded message is kept as the offsets of its pairs in the read buffer, and is copied into a
+/// map the first time it is borrowed, so a message which is only relayed or encoded again
+/// doesn't allocate a string per key and value.
+#[derive(Debug, Default)]
+pub struct MessageMetadata {
+    raw: RefCell<Option<RawMetadata>>,
+    map: RefCell<Metadata>,
+}
+
+impl MessageMetadata {
+    pub fn new(metadata: Metadata) -> Self {
+        MessageMetadata {
+            raw: RefCell::new(None),
+            map: RefCell::new(metadata),
+        }
+    }
+
+    /// borrows the metadata, see `RefCell::borrow`.
+    pub fn borrow(&self) -> Ref<'_, Metadata> {
+        self.materialize();
+        self.map.borrow()
+    }
+
+    /// mutably borrows the metadata, see `RefCell::borrow_mut`.
+    pub fn borrow_mut(&self) -> RefMut<'_, Metadata> {
+        self.materialize();
+        self.map.borrow_mut()
+    }
+
+    pub fn get_mut(&mut self) -> &mut Metadata {
+        self.materialize();
+        self.map.get_mut()
+    }
+
+    /// replaces the metadata and returns the old one.
+    pub fn replace(&self, metadata: Metadata) -> Metadata {
+        self.materialize();
+        self.map.replace(metadata)
+    }
+
+    /// returns the value of `key`, without copying the other pairs of a decoded message.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match &*self.raw.borrow() {
+            // the last pair of a key wins, as in the map
+            Some(raw) => raw
+                .pairs()
+                .filter(|(k, _)| *k == key)
+                .last()
+                .map(|(_, v)| v.to_owned()),
+            None => self.map.borrow().get(key).cloned(),
+        }
+    }
+
+    // keeps the pairs of a decoded message instead of the metadata.
+    fn set_raw(&mut self, raw: RawMetadata) {
+        self.map.get_mut().clear();
+        *self.raw.get_mut() = Some(raw);
+    }
+
+    // copies the pairs of a decoded message into the map.
+    fn materialize(&self) {
+        if let Some(raw) = self.raw.borrow_mut().take() {
+            *self.map.borrow_mut() = raw
+                .pairs()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect();
+        }
+    }
+}
+
 /// a commmon struct for request and response.
 #[derive(Debug, Default)]
 pub struct Message {
     pub header: [u8; 12],
     pub service_path: String,
     pub service_method: String,
-    pub metadata: RefCell<Metadata>,
-    pub payload: Vec<u8>,
+    pub metadata: MessageMetadata,
+    /// a slice of the read buffer for decoded messages.
+    pub payload: Bytes,
 }
 impl Message {
     /// Creates a new `Message`
@@ -86,23 +250,291 @@ impl Message {
         let mut msg: Message = Default::default();
         msg.header = [0u8; 12];
         msg.header[0] = MAGIC_NUMBER;
-        msg.metadata = RefCell::new(HashMap::new());
         msg
     }
 
     pub fn get_reply(&self) -> Result<Self> {
         let mut reply = Message::new();
         reply.set_version(self.get_version());
-        reply.set_compress_type(self.get_compress_type().unwrap());
+        reply.set_compress_type(negotiate_compress_type(self.get_compress_type()));
         reply.set_message_status_type(MessageStatusType::Normal);
         reply.set_message_type(MessageType::Response);
-        reply.set_serialize_type(self.get_serialize_type().unwrap());
+        // the serialize type is copied as it is, receivers reject the unknown ones
+        reply.header[3] = (reply.header[3] & !0xF0) | (self.header[3] & 0xF0);
         reply.set_seq(self.get_seq());
         reply.service_path = self.service_path.clone();
         reply.service_method = self.service_method.clone();
+        if let Some(request_id) = self.get_request_id() {
+            reply
+                .metadata
+                .get_mut()
+                .insert(REQUEST_ID.to_owned(), request_id);
+        }
 
         Ok(reply)
     }
+
+    /// returns the id of the request, or of the request the response replies to.
+    pub fn get_request_id(&self) -> Option<String> {
+        self.metadata.get(REQUEST_ID)
+    }
+
+    /// checks the magic number of the header, so a stream which is not rpcx is rejected
+    /// instead of being misparsed.
+    pub fn check_header(&self) -> Result<()> {
+        if !self.check_magic_number() {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!("invalid magic number {:#04x}", self.header[0]),
+            ));
+        }
+        Ok(())
+    }
+
+    /// decodes a message like `decode`, with at most `max_len` bytes instead of
+    /// `MAX_MESSAGE_LEN`.
+    pub fn decode_with_max_len<R: ?Sized>(&mut self, r: &mut R, max_len: usize) -> Result<()>
+    where
+        R: Read,
+    {
+        r.read_exact(&mut self.header)?;
+        self.check_header()?;
+
+        // the layout of the rest of the message depends on the version
+        match self.get_version() {
+            PROTOCOL_VERSION => self.decode_v0(r, max_len),
+            version => Err(Error::new(
+                ErrorKind::Protocol,
+                format!("unsupported protocol version {}", version),
+            )),
+        }
+    }
+
+    // decodes the rest of a message of version 0 after the header.
+    fn decode_v0<R: ?Sized>(&mut self, r: &mut R, max_len: usize) -> Result<()>
+    where
+        R: Read,
+    {
+        let mut buf = [0u8; 4];
+        r.read_exact(&mut buf[..])?;
+        let len = BigEndian::read_u32(&buf); //length of all expect header
+        check_frame_len(len as usize, max_len)?;
+
+        // the buffer grows with the bytes read instead of the length, which peers can fake
+        let mut buf = Vec::new();
+        r.take(u64::from(len)).read_to_end(&mut buf)?;
+        if buf.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        self.decode_body(Bytes::from(buf), &MetadataLimits::default(), max_len)?;
+        self.read_chunks(|| read_frame(r, max_len), max_len)
+    }
+
+    /// reads the continuation frames of a message whose payload is split into chunks, which
+    /// `next_frame` returns, then decompresses the payload. The payload is at most `max_len`
+    /// bytes, before and after it is decompressed. It does nothing for the other messages.
+    pub(crate) fn read_chunks<F>(&mut self, mut next_frame: F, max_len: usize) -> Result<()>
+    where
+        F: FnMut() -> Result<Bytes>,
+    {
+        if self.header[3] & CHUNKED == 0 {
+            return Ok(());
+        }
+        let total = self
+            .metadata
+            .get_mut()
+            .remove(CHUNKED_PAYLOAD_LEN)
+            .and_then(|len| len.parse::<u64>().ok())
+            .ok_or_else(|| Error::new(ErrorKind::Protocol, "chunked message without length"))?;
+        if total > max_len as u64 {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!("message exceeds {} bytes", max_len),
+            ));
+        }
+
+        // the payload grows with the chunks read instead of the length, which peers can fake
+        let mut payload = self.payload.to_vec();
+        while (payload.len() as u64) < total {
+            let frame = next_frame()?;
+            let (chunk, _) = Frame::decode(&frame)?;
+            // the chunks of a message are written together
+            if !chunk.is_chunked() || chunk.seq() != self.get_seq() {
+                return Err(Error::new(
+                    ErrorKind::Protocol,
+                    format!("chunk of message {} expected", self.get_seq()),
+                ));
+            }
+            payload.extend_from_slice(chunk.payload);
+        }
+        if payload.len() as u64 != total {
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                format!("chunks exceed the payload length {}", total),
+            ));
+        }
+
+        self.header[3] &= !CHUNKED;
+        self.payload = Bytes::from(payload);
+        self.decompress(max_len)
+    }
+
+    /// appends the message to `buf` like `encode_to`, splitting the payload into chunks of
+    /// `chunk_len` bytes if it is longer, see `MAX_CHUNK_LEN`.
+    pub fn encode_chunks_to(&self, buf: &mut Vec<u8>, chunk_len: usize) {
+        match self.get_compress_type() {
+            #[cfg(feature = "gzip")]
+            Some(CompressType::Gzip) => {
+                let mut e = GzEncoder::new(buffer_pool().get(), Compression::fast());
+                let _ = e.write_all(&self.payload[..]);
+                let compressed_payload = e.finish().unwrap();
+                self.encode_payload(self.header, &compressed_payload, buf, chunk_len);
+                buffer_pool().put(compressed_payload);
+            }
+            // the payload is sent uncompressed without the gzip feature
+            #[cfg(not(feature = "gzip"))]
+            Some(CompressType::Gzip) => {
+                let mut header = self.header;
+                header[2] &= !0x1C;
+                self.encode_payload(header, &self.payload, buf, chunk_len)
+            }
+            _ => self.encode_payload(self.header, &self.payload, buf, chunk_len),
+        }
+    }
+
+    // encodes the message with the header and the payload as they are. The pairs of a decoded
+    // message are encoded from the read buffer, without copying them into the map.
+    fn encode_payload(
+        &self,
+        header: [u8; 12],
+        payload: &[u8],
+        buf: &mut Vec<u8>,
+        chunk_len: usize,
+    ) {
+        // the length of a payload which is split into chunks, which the first chunk carries
+        let total = if payload.len() > chunk_len {
+            payload.len().to_string()
+        } else {
+            String::new()
+        };
+        if let Some(raw) = &*self.metadata.raw.borrow() {
+            return self.encode_pairs(header, raw.pairs(), &total, payload, buf, chunk_len);
+        }
+        let metadata = self.metadata.map.borrow();
+        let pairs = metadata.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+        self.encode_pairs(header, pairs, &total, payload, buf, chunk_len)
+    }
+
+    // encodes the message with the pairs of its metadata.
+    fn encode_pairs<'b, M>(
+        &self,
+        mut header: [u8; 12],
+        pairs: M,
+        total: &'b str,
+        payload: &[u8],
+        buf: &mut Vec<u8>,
+        chunk_len: usize,
+    ) where
+        M: Iterator<Item = (&'b str, &'b str)> + Clone,
+    {
+        if payload.len() <= chunk_len {
+            encode_frame(
+                &header,
+                &self.service_path,
+                &self.service_method,
+                pairs,
+                payload,
+                buf,
+            );
+            return;
+        }
+
+        header[3] |= CHUNKED;
+        let pairs = pairs.chain(iter::once((CHUNKED_PAYLOAD_LEN, total)));
+        let mut chunks = payload.chunks(chunk_len.max(1));
+        let first = chunks.next().unwrap_or_default();
+        encode_frame(
+            &header,
+            &self.service_path,
+            &self.service_method,
+            pairs,
+            first,
+            buf,
+        );
+        for chunk in chunks {
+            encode_frame(&header, "", "", iter::empty(), chunk, buf);
+        }
+    }
+
+    /// decodes a whole message, the header and the rest of it. The payload shares `frame`, and
+    /// is decompressed to at most `max_len` bytes.
+    pub(crate) fn decode_frame(
+        &mut self,
+        frame: Bytes,
+        limits: &MetadataLimits,
+        max_len: usize,
+    ) -> Result<()> {
+        self.header.copy_from_slice(&frame[..12]);
+        self.check_header()?;
+        match self.get_version() {
+            PROTOCOL_VERSION => {
+                self.decode_body(frame.slice_from(FRAME_PREFIX_LEN), limits, max_len)
+            }
+            version => Err(Error::new(
+                ErrorKind::Protocol,
+                format!("unsupported protocol version {}", version),
+            )),
+        }
+    }
+
+    // decodes the rest of a message of version 0 after the header and the length.
+    fn decode_body(&mut self, buf: Bytes, limits: &MetadataLimits, max_len: usize) -> Result<()> {
+        let frame = Frame::decode_body(self.header, &buf)?;
+        self.service_path = frame.service_path.to_owned();
+        self.service_method = frame.service_method.to_owned();
+        let raw = decode_metadata(&buf, &frame, limits)?;
+        self.metadata.set_raw(raw);
+
+        // the payload shares the read buffer instead of being copied, it ends the frame
+        self.payload = buf.slice_from(buf.len() - frame.payload.len());
+        // a chunked payload is decompressed once its chunks are read, see `read_chunks`
+        if self.header[3] & CHUNKED == CHUNKED {
+            return Ok(());
+        }
+        self.decompress(max_len)
+    }
+
+    // decompresses the payload to at most `max_len` bytes. The payload of an unknown compress
+    // type is kept as is, receivers reject it by the compress type.
+    fn decompress(&mut self, max_len: usize) -> Result<()> {
+        if let Some(CompressType::Gzip) = self.get_compress_type() {
+            self.payload = Bytes::from(gunzip(&self.payload, max_len)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(payload: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut vp = Vec::new();
+    GzDecoder::new(payload)
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut vp)?;
+    if vp.len() > max_len {
+        return Err(Error::new(
+            ErrorKind::Protocol,
+            format!("decompressed payload exceeds {} bytes", max_len),
+        ));
+    }
+    Ok(vp)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_: &[u8], _: usize) -> Result<Vec<u8>> {
+    Err(Error::new(
+        ErrorKind::Protocol,
+        "gzip is not enabled, see the gzip feature",
+    ))
 }
 
 impl RpcxMessage for Message {
@@ -172,167 +604,100 @@ impl RpcxMessage for Message {
     where
         R: Read,
     {
-        r.read_exact(&mut self.header)?;
-
-        let mut buf = [0u8; 4];
-        r.read(&mut buf[..]).map(|_| {})?;
-        let len = BigEndian::read_u32(&buf); //length of all expect header
-        let mut buf = vec![0u8; len as usize];
-        r.read(&mut buf[..]).map(|_| ())?;
-
-        let mut start = 0;
-        // read service_path
-        let len = read_len(&buf[start..(start + 4)]) as usize;
-        let service_path = read_str(&buf[(start + 4)..(start + 4 + len)])?;
-        self.service_path = service_path;
-        start = start + 4 + len;
-        // read service_method
-        let len = read_len(&buf[start..(start + 4)]) as usize;
-        let service_method = read_str(&buf[(start + 4)..(start + 4 + len)])?;
-        self.service_method = service_method;
-
-        start = start + 4 + len;
-        //metadata
-        let len = read_len(&buf[start..(start + 4)]) as usize;
-        let metadata_bytes = &buf[(start + 4)..(start + 4 + len)];
-        let mut meta_start = 0;
-        while meta_start < len {
-            let sl = read_len(&metadata_bytes[meta_start..(meta_start + 4)]) as usize;
-            let key = read_str(&metadata_bytes[(meta_start + 4)..(meta_start + 4 + sl)])?;
-            meta_start = meta_start + 4 + sl;
-            if meta_start < len {
-                let value_len = read_len(&metadata_bytes[meta_start..(meta_start + 4)]) as usize;
-                let value =
-                    read_str(&metadata_bytes[(meta_start + 4)..(meta_start + 4 + value_len)])?;
-                self.metadata.borrow_mut().insert(key, value);
-                meta_start = meta_start + 4 + value_len;
-            } else {
-                self.metadata.borrow_mut().insert(key, String::new());
-                break;
-            }
-        }
-        start = start + 4 + len;
-        // payload
-        let len = read_len(&buf[start..start + 4]) as usize;
-        let payload = &buf[start + 4..];
-        if len != payload.len() {
-            return Err(Error::from("invalid payload length"));
-        }
-
-        let mut vp = Vec::with_capacity(payload.len());
-        match self.get_compress_type().unwrap() {
-            CompressType::Gzip => {
-                let mut deflater = GzDecoder::new(payload);
-                deflater.read_to_end(&mut vp)?;
-            }
-            CompressType::CompressNone => {
-                vp.extend_from_slice(&payload);
-            }
-        }
-        self.payload = vp;
-
-        Ok(())
+        self.decode_with_max_len(r, MAX_MESSAGE_LEN)
     }
 
     fn encode(&self) -> Vec<u8> {
-        // encode all except header
-        let mut buf = Vec::<u8>::with_capacity(20);
-        buf.extend_from_slice(&self.header);
-
-        // push fake length
-        let len_bytes = write_len(0);
-        buf.extend_from_slice(&len_bytes);
-
-        // service_path
-        let len = self.service_path.len();
-        let len_bytes = write_len(len as u32);
-        buf.extend_from_slice(&len_bytes);
-        buf.extend_from_slice(self.service_path.as_bytes());
-
-        // service_method
-        let len = self.service_method.len();
-        let len_bytes = write_len(len as u32);
-        buf.extend_from_slice(&len_bytes);
-        buf.extend_from_slice(self.service_method.as_bytes());
-
-        // metadata
-        let mut metadata_bytes = Vec::<u8>::new();
-        let metadata = self.metadata.borrow_mut();
-        for meta in metadata.iter() {
-            let key = meta.0;
-            let len_bytes = write_len(key.len() as u32);
-            metadata_bytes.extend_from_slice(&len_bytes);
-            metadata_bytes.extend_from_slice(key.as_bytes());
-
-            let value = meta.1;
-            let len_bytes = write_len(value.len() as u32);
-            metadata_bytes.extend_from_slice(&len_bytes);
-            metadata_bytes.extend_from_slice(value.as_bytes());
-        }
-        let len = metadata_bytes.len();
-        let len_bytes = write_len(len as u32);
-        buf.extend_from_slice(&len_bytes);
-        buf.append(&mut metadata_bytes);
-
-        // data
-        // check compress
-
-        match self.get_compress_type().unwrap() {
-            CompressType::Gzip => {
-                let mut e = GzEncoder::new(Vec::new(), Compression::fast());
-                let _ = e.write_all(&self.payload[..]);
-                let compressed_payload = e.finish().unwrap();
-                let len = compressed_payload.len();
-                let len_bytes = write_len(len as u32);
-                buf.extend_from_slice(&len_bytes);
-                buf.extend_from_slice(&compressed_payload);
-            }
-            _ => {
-                let len = self.payload.len();
-                let len_bytes = write_len(len as u32);
-                buf.extend_from_slice(&len_bytes);
-                buf.extend_from_slice(&self.payload);
-            }
-        }
-
-        // set the real length
-        let len = buf.len() - 12 - 4;
-        let len_bytes = write_len(len as u32);
-        buf[12] = len_bytes[0];
-        buf[13] = len_bytes[1];
-        buf[14] = len_bytes[2];
-        buf[15] = len_bytes[3];
-
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf);
         buf
     }
 
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        self.encode_chunks_to(buf, MAX_CHUNK_LEN)
+    }
+
     fn get_error(&self) -> Option<String> {
         match self.get_message_status_type() {
-            Some(MessageStatusType::Error) => {
-                let metadata = &self.metadata;
-                let metadata2 = metadata.borrow();
-                let err_msg = metadata2.get(&SERVICE_ERROR.to_owned())?;
-                Some(String::from(err_msg))
-            }
+            Some(MessageStatusType::Error) => self.metadata.get(SERVICE_ERROR),
             _ => None,
         }
     }
 }
 
-fn read_len(buf: &[u8]) -> u32 {
-    BigEndian::read_u32(&buf[..4])
+// the pairs of metadata of most messages, such as of traced and authenticated calls, which are
+// indexed without allocating.
+const INLINE_METADATA: usize = 16;
+
+// the pairs of the metadata of a decoded message, as the offsets of their keys and values in
+// the read buffer, which the payload shares.
+#[derive(Debug)]
+struct RawMetadata {
+    buf: Bytes,
+    pairs: SmallVec<[[u32; 4]; INLINE_METADATA]>,
+}
+
+impl RawMetadata {
+    fn pairs(&self) -> impl Iterator<Item = (&str, &str)> + Clone + '_ {
+        self.pairs
+            .iter()
+            .map(move |&[key, key_end, value, value_end]| {
+                (self.str(key, key_end), self.str(value, value_end))
+            })
+    }
+
+    // the pairs are checked to be UTF-8 when they are decoded.
+    fn str(&self, start: u32, end: u32) -> &str {
+        str::from_utf8(&self.buf[start as usize..end as usize]).unwrap_or_default()
+    }
+}
+
+// checks the pairs of metadata of `frame` against the limits and indexes them in `buf`, which
+// the frame is decoded from, so they are parsed once and copied only when they are borrowed.
+fn decode_metadata(buf: &Bytes, frame: &Frame<'_>, limits: &MetadataLimits) -> Result<RawMetadata> {
+    let base = buf.as_ptr() as usize;
+    // the offsets of a slice of `buf`, an empty slice may be elsewhere
+    let span = |s: &str| {
+        if s.is_empty() {
+            return (0, 0);
+        }
+        let start = (s.as_ptr() as usize - base) as u32;
+        (start, start + s.len() as u32)
+    };
+    let mut pairs = SmallVec::new();
+    limits.check_pairs(frame, |(key, value)| {
+        let (key, key_end) = span(key);
+        let (value, value_end) = span(value);
+        pairs.push([key, key_end, value, value_end]);
+    })?;
+    Ok(RawMetadata {
+        buf: buf.clone(),
+        pairs,
+    })
 }
 
-fn write_len(len: u32) -> [u8; 4] {
-    let mut buf = [0u8; 4];
-    BigEndian::write_u32(&mut buf, len);
-    buf
+// reads a whole frame, its prefix and the rest of it.
+fn read_frame<R: ?Sized + Read>(r: &mut R, max_len: usize) -> Result<Bytes> {
+    let mut frame = vec![0u8; FRAME_PREFIX_LEN];
+    r.read_exact(&mut frame)?;
+    let len = Frame::frame_len(&frame)? - FRAME_PREFIX_LEN;
+    check_frame_len(len, max_len)?;
+    r.take(len as u64).read_to_end(&mut frame)?;
+    if frame.len() != FRAME_PREFIX_LEN + len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(Bytes::from(frame))
 }
 
-fn read_str(buf: &[u8]) -> Result<String> {
-    let s = std::str::from_utf8(&buf).unwrap();
-    let str: String = std::string::String::from(s);
-    Ok(str)
+/// fails if the rest of a frame after its prefix, of `len` bytes, is longer than `max_len`.
+pub(crate) fn check_frame_len(len: usize, max_len: usize) -> Result<()> {
+    if len > max_len {
+        return Err(Error::new(
+            ErrorKind::Protocol,
+            format!("message exceeds {} bytes", max_len),
+        ));
+    }
+    Ok(())
 }
 
 fn u64_from_slice(b: &[u8]) -> u64 {
@@ -464,4 +829,268 @@ mod tests {
 
         assert_eq!(&msg_data[..], &encoded_bytes[..]);
     }
+
+    #[test]
+    fn chunked_round_trip() {
+        for ct in &[CompressType::CompressNone, CompressType::Gzip] {
+            let mut msg = Message::new();
+            msg.set_seq(9);
+            msg.set_compress_type(*ct);
+            msg.service_path = "Arith".to_owned();
+            msg.service_method = "Mul".to_owned();
+            msg.metadata
+                .get_mut()
+                .insert("key".to_owned(), "value".to_owned());
+            msg.payload = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+            let mut data = Vec::new();
+            msg.encode_chunks_to(&mut data, 1000);
+            let (first, len) = Frame::decode(&data).unwrap();
+            assert!(first.is_chunked());
+            assert!(len < data.len());
+
+            let mut decoded = Message::new();
+            decoded.decode(&mut &data[..]).unwrap();
+            assert_eq!(9, decoded.get_seq());
+            assert_eq!("Mul", decoded.service_method);
+            assert_eq!(*msg.metadata.borrow(), *decoded.metadata.borrow());
+            assert_eq!(msg.payload, decoded.payload);
+            // re-encoded as one frame
+            assert_eq!(msg.encode(), decoded.encode());
+        }
+
+        // a payload of the chunk length is not split
+        let mut msg = Message::new();
+        msg.payload = vec![1u8; 1000].into();
+        let mut data = Vec::new();
+        msg.encode_chunks_to(&mut data, 1000);
+        assert_eq!(msg.encode(), data);
+    }
+
+    #[test]
+    fn malformed_chunks() {
+        let mut msg = Message::new();
+        msg.set_seq(1);
+        msg.payload = vec![1u8; 30].into();
+        let mut data = Vec::new();
+        msg.encode_chunks_to(&mut data, 10);
+
+        // the last chunk is truncated
+        let err = Message::new()
+            .decode(&mut &data[..data.len() - 1])
+            .unwrap_err();
+        assert_eq!(ErrorKind::IO, err.kind());
+
+        // a message is interleaved with the chunks
+        let (_, len) = Frame::decode(&data).unwrap();
+        let mut other = Message::new();
+        other.set_seq(2);
+        let mut interleaved = data[..len].to_vec();
+        other.encode_to(&mut interleaved);
+        interleaved.extend_from_slice(&data[len..]);
+        let err = Message::new().decode(&mut &interleaved[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let mut msg = Message::new();
+        msg.service_path = "Arith".to_owned();
+        msg.service_method = "Mul".to_owned();
+        for i in 0..3 {
+            msg.metadata
+                .get_mut()
+                .insert(format!("key{}", i), "value".repeat(i));
+        }
+        let data = msg.encode();
+
+        let mut decoded = Message::new();
+        decoded.decode(&mut &data[..]).unwrap();
+        assert_eq!(*msg.metadata.borrow(), *decoded.metadata.borrow());
+
+        // the metadata of a message is not valid UTF-8
+        let mut data = data;
+        let pos = data.windows(5).position(|w| w == b"value").unwrap();
+        data[pos] = 0xff;
+        let err = Message::new().decode(&mut &data[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+
+        // the metadata has too many entries
+        let mut msg = Message::new();
+        for i in 0..=MetadataLimits::default().max_entries {
+            msg.metadata.get_mut().insert(i.to_string(), String::new());
+        }
+        let data = msg.encode();
+        let err = Message::new().decode(&mut &data[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert!(err.to_string().contains("more than 1024 entries"));
+    }
+
+    #[test]
+    fn decoded_metadata() {
+        let mut msg = Message::new();
+        msg.metadata
+            .get_mut()
+            .insert("a".to_owned(), "1".to_owned());
+        msg.metadata.get_mut().insert("b".to_owned(), String::new());
+        let data = msg.encode();
+
+        // the pairs are read and encoded again from the read buffer
+        let mut decoded = Message::new();
+        decoded.decode(&mut &data[..]).unwrap();
+        assert_eq!(Some("1".to_owned()), decoded.metadata.get("a"));
+        assert_eq!(None, decoded.metadata.get("c"));
+        let mut relayed = Message::new();
+        relayed.decode(&mut &decoded.encode()[..]).unwrap();
+        assert_eq!(*msg.metadata.borrow(), *relayed.metadata.borrow());
+
+        // the pairs are copied into the map once they are borrowed
+        decoded.metadata.borrow_mut().remove("a");
+        assert_eq!(None, decoded.metadata.get("a"));
+        assert_eq!(Some(String::new()), decoded.metadata.get("b"));
+    }
+
+    #[test]
+    fn reply_request_id() {
+        let msg = Message::new();
+        assert_eq!(None, msg.get_reply().unwrap().get_request_id());
+
+        msg.metadata
+            .borrow_mut()
+            .insert(REQUEST_ID.to_owned(), "6ba7b810".to_owned());
+        msg.metadata
+            .borrow_mut()
+            .insert("__ID".to_owned(), "1".to_owned());
+        let reply = msg.get_reply().unwrap();
+        assert_eq!(Some("6ba7b810".to_owned()), reply.get_request_id());
+        assert_eq!(1, reply.metadata.borrow().len());
+    }
+
+    #[test]
+    fn unknown_compress_type() {
+        let mut msg = Message::new();
+        msg.set_serialize_type(SerializeType::JSON);
+        msg.payload = Bytes::from(&b"zstd"[..]);
+        // the compress type 3 is unknown here
+        msg.header[2] |= 3 << 2;
+        let data = msg.encode();
+
+        let mut decoded = Message::new();
+        decoded.decode(&mut &data[..]).unwrap();
+        assert_eq!(None, decoded.get_compress_type());
+        assert_eq!(&b"zstd"[..], &decoded.payload[..]);
+
+        let reply = decoded.get_reply().unwrap();
+        assert_eq!(Some(CompressType::CompressNone), reply.get_compress_type());
+    }
+
+    #[test]
+    fn unknown_serialize_type() {
+        let mut msg = Message::new();
+        // the serialize type 15 is unknown here
+        msg.header[3] |= 15 << 4;
+        let reply = msg.get_reply().unwrap();
+        assert_eq!(None, reply.get_serialize_type());
+        assert_eq!(15 << 4, reply.header[3] & 0xF0);
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn decompressed_payload_limit() {
+        let mut msg = Message::new();
+        msg.set_serialize_type(SerializeType::JSON);
+        msg.set_compress_type(CompressType::Gzip);
+        msg.payload = vec![0u8; 1024 * 1024].into();
+        let frame = Bytes::from(msg.encode());
+
+        let limits = MetadataLimits::default();
+        let err = Message::new()
+            .decode_frame(frame.clone(), &limits, 1024)
+            .unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert_eq!("decompressed payload exceeds 1024 bytes", err.to_string());
+
+        let mut decoded = Message::new();
+        decoded.decode_frame(frame, &limits, 1024 * 1024).unwrap();
+        assert_eq!(msg.payload, decoded.payload);
+    }
+
+    #[test]
+    fn check_header() {
+        let mut msg = Message::new();
+        msg.set_serialize_type(SerializeType::JSON);
+        let data = msg.encode();
+
+        let mut invalid = data.clone();
+        invalid[0] = b'G';
+        let err = Message::new().decode(&mut &invalid[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert!(err.to_string().contains("invalid magic number"));
+
+        let mut newer = data.clone();
+        newer[1] = PROTOCOL_VERSION + 1;
+        let err = Message::new().decode(&mut &newer[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert!(err.to_string().contains("unsupported protocol version"));
+
+        assert!(Message::new().decode(&mut &data[..]).is_ok());
+    }
+
+    #[test]
+    fn malformed_messages() {
+        let mut msg = Message::new();
+        msg.set_serialize_type(SerializeType::JSON);
+        msg.service_path = "Arith".to_owned();
+        msg.metadata
+            .get_mut()
+            .insert("key".to_owned(), "value".to_owned());
+        msg.payload = b"payload".to_vec().into();
+        let data = msg.encode();
+
+        // every field is cut off
+        for len in 16..data.len() {
+            let mut truncated = data[..len].to_vec();
+            BigEndian::write_u32(&mut truncated[12..16], len as u32 - 16);
+            let err = Message::new().decode(&mut &truncated[..]).unwrap_err();
+            assert_eq!(ErrorKind::Protocol, err.kind(), "{}", len);
+        }
+
+        // the length of the metadata key exceeds the metadata
+        let mut invalid = data.clone();
+        BigEndian::write_u32(&mut invalid[33..37], u32::max_value());
+        let err = Message::new().decode(&mut &invalid[..]).unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+
+        // the frame is shorter than its length
+        let mut invalid = data.clone();
+        BigEndian::write_u32(&mut invalid[12..16], u32::max_value());
+        assert!(Message::new().decode(&mut &invalid[..]).is_err());
+    }
+
+    #[test]
+    fn deadline() {
+        let mut metadata = Metadata::new();
+        assert_eq!(None, get_deadline(&metadata));
+        set_deadline(&mut metadata, Duration::from_millis(1500));
+        assert_eq!("1500", metadata[DEADLINE]);
+        assert_eq!(Some(Duration::from_millis(1500)), get_deadline(&metadata));
+
+        metadata.insert(DEADLINE.to_owned(), "soon".to_owned());
+        assert_eq!(None, get_deadline(&metadata));
+    }
+
+    #[test]
+    fn priority() {
+        let mut metadata = Metadata::new();
+        assert_eq!(Priority::Normal, get_priority(&metadata));
+        set_priority(&mut metadata, Priority::High);
+        assert_eq!("high", metadata[PRIORITY]);
+        assert_eq!(Priority::High, get_priority(&metadata));
+        set_priority(&mut metadata, Priority::Low);
+        assert_eq!(Priority::Low, get_priority(&metadata));
+
+        metadata.insert(PRIORITY.to_owned(), "urgent".to_owned());
+        assert_eq!(Priority::Normal, get_priority(&metadata));
+        assert!(Priority::High < Priority::Normal);
+    }
 }
@@ -0,0 +1,62 @@
+use crate::ErrorKind;
+
+/// counter of the calls made by clients, labeled by `service`, `method`, `server` and
+/// `status`, which is `ok` or `error`.
+pub const CLIENT_CALLS: &str = "rpcx_client_calls_total";
+/// histogram of the latencies of the calls made by clients in seconds, labeled like
+/// `CLIENT_CALLS`.
+pub const CLIENT_CALL_DURATION: &str = "rpcx_client_call_duration_seconds";
+/// counter of the failed calls made by clients, labeled by `service`, `method`, `server` and
+/// `class`, see `error_class`.
+pub const CLIENT_ERRORS: &str = "rpcx_client_errors_total";
+/// counter of the requests handled by servers, labeled by `service`, `method` and `status`.
+pub const SERVER_REQUESTS: &str = "rpcx_server_requests_total";
+/// histogram of the durations of the requests handled by servers in seconds, labeled like
+/// `SERVER_REQUESTS`.
+pub const SERVER_REQUEST_DURATION: &str = "rpcx_server_request_duration_seconds";
+/// gauge of the connections of a server.
+pub const SERVER_CONNECTIONS: &str = "rpcx_server_connections";
+/// gauge of the requests of a server waiting for a worker.
+pub const SERVER_QUEUED_REQUESTS: &str = "rpcx_server_queued_requests";
+/// gauge of the Apdex score of the requests of a method, labeled by `service` and `method`.
+pub const SERVER_METHOD_APDEX: &str = "rpcx_server_method_apdex";
+/// gauge of the ratio of the requests of a method which succeed, labeled like
+/// `SERVER_METHOD_APDEX`.
+pub const SERVER_METHOD_SUCCESS_RATIO: &str = "rpcx_server_method_success_ratio";
+
+/// receives the metrics of clients and servers, so they can be reported to statsd,
+/// Prometheus or any other system without the crates depending on one of them.
+///
+/// It is invoked on the paths of calls, implementations should not block.
+pub trait MetricsSink: Send + Sync {
+    /// adds `value` to a counter.
+    fn counter(&self, name: &str, value: u64, labels: &[(&str, &str)]);
+    /// sets a gauge.
+    fn gauge(&self, name: &str, value: f64, labels: &[(&str, &str)]);
+    /// records a sample of a histogram, such as a latency in seconds.
+    fn histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]);
+}
+
+/// returns the `status` label of a result.
+pub fn status_label(success: bool) -> &'static str {
+    if success {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+/// returns the `class` label of an error: `connect`, `timeout`, `serialization`, `service`,
+/// `server`, `rate_limited` or `other`. Busy servers shed load like rate limiters, so their errors are
+/// `rate_limited` as well.
+pub fn error_class(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Network | ErrorKind::IO | ErrorKind::ConnectionClosed => "connect",
+        ErrorKind::Timeout => "timeout",
+        ErrorKind::Serialization => "serialization",
+        ErrorKind::Service => "service",
+        ErrorKind::Server => "server",
+        ErrorKind::RateLimited | ErrorKind::ServerBusy => "rate_limited",
+        _ => "other",
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // the interned names. Services and methods are few, so they are kept for the process.
+    static ref NAMES: Mutex<HashSet<Arc<str>>> = Mutex::new(HashSet::new());
+}
+
+// returns the interned copy of the name, the copies of a name share one allocation.
+fn intern(name: &str) -> Arc<str> {
+    let mut names = NAMES.lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned.clone();
+    }
+    let interned: Arc<str> = Arc::from(name);
+    names.insert(interned.clone());
+    interned
+}
+
+macro_rules! interned_name {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(Arc<str>);
+
+        impl $name {
+            pub fn new(name: &str) -> Self {
+                $name(intern(name))
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new("")
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&*self.0, f)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(name: &str) -> Self {
+                $name::new(name)
+            }
+        }
+
+        impl From<&String> for $name {
+            fn from(name: &String) -> Self {
+                $name::new(name)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(name: String) -> Self {
+                $name::new(&name)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                &*self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                &*self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &*self.0 == other.as_str()
+            }
+        }
+
+        impl PartialEq<$name> for str {
+            fn eq(&self, other: &$name) -> bool {
+                self == &*other.0
+            }
+        }
+
+        impl PartialEq<$name> for &str {
+            fn eq(&self, other: &$name) -> bool {
+                *self == &*other.0
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                self.as_str() == &*other.0
+            }
+        }
+    };
+}
+
+interned_name!(
+    /// the path of a service, such as `Arith`. Paths are interned, so they are cheap to clone
+    /// and to keep per call, and a path can't be passed where a method is expected.
+    ServicePath
+);
+
+interned_name!(
+    /// the name of a method of a service, such as `Mul`, interned like `ServicePath`.
+    ServiceMethod
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned() {
+        let a = ServicePath::new("Arith");
+        let b = ServicePath::from("Arith".to_owned());
+        assert_eq!(a, b);
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!("Arith", a);
+        assert_eq!(a, "Arith");
+        assert_eq!("Arith".to_owned(), a);
+        assert_eq!("Arith", a.to_string());
+        assert_eq!("\"Arith\"", format!("{:?}", a));
+        assert_ne!(ServicePath::new("Echo"), a);
+
+        let method = ServiceMethod::from("Mul");
+        assert_eq!(3, method.len());
+        assert_eq!("", ServiceMethod::default().as_str());
+
+        let mut methods = HashSet::new();
+        methods.insert(method);
+        assert!(methods.contains("Mul"));
+    }
+}
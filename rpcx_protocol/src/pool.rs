@@ -0,0 +1,68 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+/// the number of idle buffers kept by a pool.
+pub const POOL_SIZE: usize = 1024;
+/// buffers which have grown beyond this capacity are released instead of being kept, so a few
+/// large messages don't pin memory.
+pub const POOL_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// reuses the buffers of encoded and decoded messages.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// takes an empty buffer from the pool, or allocates one if the pool is empty.
+    pub fn get(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    /// returns a buffer to the pool.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > POOL_BUFFER_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < POOL_SIZE {
+            buffers.push(buf);
+        }
+    }
+}
+
+lazy_static! {
+    static ref BUFFER_POOL: BufferPool = BufferPool::new();
+}
+
+/// returns the pool shared by the codecs of clients and servers.
+pub fn buffer_pool() -> &'static BufferPool {
+    &BUFFER_POOL
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_buffers() {
+        let pool = BufferPool::new();
+        let mut buf = pool.get();
+        buf.extend_from_slice(b"hello");
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert_eq!(ptr, buf.as_ptr());
+
+        pool.put(Vec::with_capacity(POOL_BUFFER_CAPACITY + 1));
+        assert_eq!(0, pool.get().capacity());
+    }
+}
@@ -0,0 +1,55 @@
+// Topics are dot-separated names such as `orders.eu.created`. A subscription pattern matches
+// topics segment by segment: `*` matches exactly one segment and `>`, as the last segment,
+// matches one or more remaining segments.
+
+/// the service path of subscription requests and published messages.
+pub const PUBSUB_SERVICE: &str = "__rpcx_pubsub__";
+pub const PUBSUB_SUBSCRIBE: &str = "Subscribe";
+pub const PUBSUB_UNSUBSCRIBE: &str = "Unsubscribe";
+
+/// checks whether the topic matches the subscription pattern.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut topic_segments = topic.split('.');
+    let mut pattern_segments = pattern.split('.').peekable();
+
+    while let Some(p) = pattern_segments.next() {
+        if p == ">" && pattern_segments.peek().is_none() {
+            return topic_segments.next().is_some();
+        }
+        match topic_segments.next() {
+            Some(t) if p == "*" || p == t => {}
+            _ => return false,
+        }
+    }
+
+    topic_segments.next().is_none()
+}
+
+/// checks whether the pattern is a valid subscription pattern.
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    let segments: Vec<&str> = pattern.split('.').collect();
+    segments
+        .iter()
+        .enumerate()
+        .all(|(i, s)| !s.is_empty() && (*s != ">" || i == segments.len() - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_topics() {
+        assert!(topic_matches("orders.created", "orders.created"));
+        assert!(!topic_matches("orders.created", "orders.deleted"));
+        assert!(topic_matches("orders.*.created", "orders.eu.created"));
+        assert!(!topic_matches("orders.*", "orders.eu.created"));
+        assert!(topic_matches("orders.>", "orders.eu.created"));
+        assert!(!topic_matches("orders.>", "orders"));
+        assert!(!topic_matches("orders.eu", "orders"));
+
+        assert!(is_valid_pattern("orders.>"));
+        assert!(!is_valid_pattern("orders.>.created"));
+        assert!(!is_valid_pattern("orders..created"));
+    }
+}
@@ -0,0 +1,291 @@
+use bytes::{Bytes, BytesMut};
+use std::io::{self, Read};
+
+use crate::{
+    message::check_frame_len, Error, ErrorKind, Frame, Message, MetadataLimits, Result,
+    FRAME_PREFIX_LEN, MAGIC_NUMBER, MAX_MESSAGE_LEN,
+};
+
+/// the most bytes read from a connection by a syscall.
+pub const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// reads the messages of a connection. All the messages in the bytes read by a syscall are
+/// decoded before it reads again, so requests pipelined by clients cost one syscall, and the
+/// payloads share the read buffer instead of being copied.
+#[derive(Debug)]
+pub struct MessageReader<R> {
+    inner: R,
+    buf: BytesMut,
+    limits: MetadataLimits,
+    max_message_len: usize,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(inner: R) -> Self {
+        MessageReader {
+            inner,
+            buf: BytesMut::with_capacity(READ_BUFFER_SIZE),
+            limits: MetadataLimits::default(),
+            max_message_len: MAX_MESSAGE_LEN,
+        }
+    }
+
+    /// sets the limits of the metadata of the messages, the default ones otherwise.
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) {
+        self.limits = limits;
+    }
+
+    /// sets the most bytes of a message, `MAX_MESSAGE_LEN` otherwise. A longer frame or
+    /// decompressed payload fails the read with `ErrorKind::Protocol`.
+    pub fn set_max_message_len(&mut self, max_len: usize) {
+        self.max_message_len = max_len;
+    }
+
+    /// returns the connection prefixed by the bytes which are read but not decoded yet, so the
+    /// rest of it can be read through another reader such as a decompressor.
+    pub fn into_inner(self) -> io::Chain<io::Cursor<BytesMut>, R> {
+        io::Cursor::new(self.buf).chain(self.inner)
+    }
+
+    /// returns the next message, reading the connection only if the buffer holds no whole
+    /// message. The chunks of a message which is split are read before it is returned.
+    pub fn read_msg(&mut self) -> Result<Message> {
+        let frame = self.read_frame()?;
+        let mut msg = Message::new();
+        let max_len = self.max_message_len;
+        msg.decode_frame(frame, &self.limits, max_len)?;
+        msg.read_chunks(|| self.read_frame(), max_len)?;
+        Ok(msg)
+    }
+
+    // returns the next frame, reading the connection only if the buffer holds no whole frame.
+    fn read_frame(&mut self) -> Result<Bytes> {
+        loop {
+            if self.buf.len() >= FRAME_PREFIX_LEN {
+                // a stream which is not rpcx is rejected before its length is trusted
+                if self.buf[0] != MAGIC_NUMBER {
+                    return Err(Error::new(
+                        ErrorKind::Protocol,
+                        format!("invalid magic number {:#04x}", self.buf[0]),
+                    ));
+                }
+                let len = Frame::frame_len(&self.buf)?;
+                check_frame_len(len - FRAME_PREFIX_LEN, self.max_message_len)?;
+                if self.buf.len() >= len {
+                    return Ok(self.buf.split_to(len).freeze());
+                }
+            }
+            self.fill()?;
+        }
+    }
+
+    // reads the connection once. The buffer grows with the bytes read instead of the length
+    // of the message, which peers can fake.
+    fn fill(&mut self) -> Result<()> {
+        let start = self.buf.len();
+        self.buf.resize(start + READ_BUFFER_SIZE, 0);
+        loop {
+            match self.inner.read(&mut self.buf[start..]) {
+                Ok(0) => {
+                    self.buf.truncate(start);
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                Ok(n) => {
+                    self.buf.truncate(start + n);
+                    return Ok(());
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.buf.truncate(start);
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RpcxMessage;
+    use byteorder::{BigEndian, ByteOrder};
+
+    // counts the reads, returning at most `chunk` bytes per read.
+    struct ChunkReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        reads: usize,
+    }
+
+    impl Read for ChunkReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            let n = buf.len().min(self.chunk).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn pipelined() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..3 {
+            let mut msg = Message::new();
+            msg.set_seq(i);
+            msg.service_path = "Arith".to_owned();
+            msg.service_method = "Mul".to_owned();
+            msg.metadata
+                .get_mut()
+                .insert("key".to_owned(), i.to_string());
+            msg.payload = vec![i as u8; 10].into();
+            msg.encode_to(&mut data);
+        }
+        data
+    }
+
+    #[test]
+    fn read_pipelined_messages() {
+        let data = pipelined();
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: usize::max_value(),
+            reads: 0,
+        });
+        for i in 0..3 {
+            let msg = reader.read_msg().unwrap();
+            assert_eq!(i, msg.get_seq());
+            assert_eq!("Mul", msg.service_method);
+            assert_eq!(i.to_string(), msg.metadata.borrow()["key"]);
+            assert_eq!(&vec![i as u8; 10][..], &msg.payload[..]);
+        }
+        assert_eq!(1, reader.inner.reads);
+        assert!(reader.read_msg().is_err());
+    }
+
+    #[test]
+    fn read_chunked_messages() {
+        let mut data = Vec::new();
+        let mut msg = Message::new();
+        msg.set_seq(1);
+        msg.payload = vec![7u8; 3 * READ_BUFFER_SIZE].into();
+        msg.encode_chunks_to(&mut data, READ_BUFFER_SIZE / 3);
+        data.extend_from_slice(&pipelined());
+
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: 1000,
+            reads: 0,
+        });
+        let chunked = reader.read_msg().unwrap();
+        assert_eq!(1, chunked.get_seq());
+        assert_eq!(msg.payload, chunked.payload);
+        assert!(chunked.metadata.borrow().is_empty());
+        for i in 0..3 {
+            assert_eq!(i, reader.read_msg().unwrap().get_seq());
+        }
+    }
+
+    #[test]
+    fn read_partial_messages() {
+        let data = pipelined();
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: 7,
+            reads: 0,
+        });
+        for i in 0..3 {
+            assert_eq!(i, reader.read_msg().unwrap().get_seq());
+        }
+        assert!(reader.read_msg().is_err());
+    }
+
+    #[test]
+    fn into_inner() {
+        let data = pipelined();
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: data.len() - 5,
+            reads: 0,
+        });
+        assert_eq!(0, reader.read_msg().unwrap().get_seq());
+
+        // the bytes buffered by the first reader are read before the rest of the connection
+        let mut reader = MessageReader::new(reader.into_inner());
+        for i in 1..3 {
+            assert_eq!(i, reader.read_msg().unwrap().get_seq());
+        }
+        assert!(reader.read_msg().is_err());
+    }
+
+    #[test]
+    fn read_malformed_messages() {
+        // a faked length is not allocated up front
+        let mut data = pipelined();
+        BigEndian::write_u32(&mut data[12..16], u32::max_value());
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: usize::max_value(),
+            reads: 0,
+        });
+        assert!(reader.read_msg().is_err());
+        assert!(reader.buf.capacity() < 4 * READ_BUFFER_SIZE);
+
+        // a stream which is not rpcx is rejected before it is read to the end
+        let mut data = vec![b'G'; 4 * READ_BUFFER_SIZE];
+        data[12..16].copy_from_slice(&[0xff; 4]);
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: READ_BUFFER_SIZE,
+            reads: 0,
+        });
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert_eq!(1, reader.inner.reads);
+    }
+
+    #[test]
+    fn read_max_message_len() {
+        let data = pipelined();
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: usize::max_value(),
+            reads: 0,
+        });
+        reader.set_max_message_len(8);
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert_eq!("message exceeds 8 bytes", err.to_string());
+
+        // the chunks of a longer message are not read
+        let mut data = Vec::new();
+        let mut msg = Message::new();
+        msg.payload = vec![7u8; 3 * READ_BUFFER_SIZE].into();
+        msg.encode_chunks_to(&mut data, READ_BUFFER_SIZE);
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: usize::max_value(),
+            reads: 0,
+        });
+        reader.set_max_message_len(2 * READ_BUFFER_SIZE);
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert!(!reader.inner.data.is_empty());
+    }
+
+    #[test]
+    fn read_metadata_limits() {
+        let data = pipelined();
+        let mut reader = MessageReader::new(ChunkReader {
+            data: &data,
+            chunk: usize::max_value(),
+            reads: 0,
+        });
+        reader.set_metadata_limits(MetadataLimits {
+            max_key_len: 2,
+            ..MetadataLimits::default()
+        });
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+        assert_eq!("metadata key is longer than 2 bytes", err.to_string());
+    }
+}
@@ -0,0 +1,96 @@
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+use crate::CompressType;
+
+/// the service path of the built-in service which describes the capabilities of a server.
+pub const REFLECTION_SERVICE: &str = "_reflection";
+/// the method which returns the compressors supported by the server, as comma-separated names
+/// such as `CompressNone,Gzip`.
+pub const REFLECTION_COMPRESSORS: &str = "Compressors";
+/// the method which returns the methods registered to the server, as sorted comma-separated
+/// names such as `Arith.Add,Arith.Mul`.
+pub const REFLECTION_SERVICES: &str = "Services";
+/// the method which switches the connection to the compression of its whole stream, see
+/// `StreamCompression`.
+pub const REFLECTION_STREAM_COMPRESSION: &str = "StreamCompression";
+
+/// returns the compress types this implementation can encode and decode, `Gzip` is left
+/// out without the gzip feature.
+pub fn supported_compressors() -> Vec<CompressType> {
+    CompressType::iter()
+        .filter(|ct| cfg!(feature = "gzip") || *ct != CompressType::Gzip)
+        .collect()
+}
+
+/// encodes compress types as the reply of `Compressors`.
+pub fn encode_compressors(compressors: &[CompressType]) -> Vec<u8> {
+    let names: Vec<String> = compressors.iter().map(|c| c.to_string()).collect();
+    names.join(",").into_bytes()
+}
+
+/// parses the reply of `Compressors`, skipping the compressors which are unknown here so
+/// peers of different versions agree on the common subset.
+pub fn parse_compressors(data: &[u8]) -> Vec<CompressType> {
+    String::from_utf8_lossy(data)
+        .split(',')
+        .filter_map(|name| CompressType::from_str(name.trim()).ok())
+        .collect()
+}
+
+/// encodes the names of methods, `<service path>.<service method>`, as the reply of
+/// `Services`.
+pub fn encode_services<S: AsRef<str>>(methods: &[S]) -> Vec<u8> {
+    let mut names: Vec<&str> = methods.iter().map(AsRef::as_ref).collect();
+    names.sort();
+    names.join(",").into_bytes()
+}
+
+/// parses the reply of `Services`.
+pub fn parse_services(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// returns the compress type to reply with, the one of the request if it is supported and no
+/// compression otherwise.
+pub fn negotiate_compress_type(requested: Option<CompressType>) -> CompressType {
+    requested
+        .filter(|ct| supported_compressors().contains(ct))
+        .unwrap_or(CompressType::CompressNone)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::FromPrimitive;
+
+    use super::*;
+
+    #[test]
+    fn compressors() {
+        let data = encode_compressors(&supported_compressors());
+        #[cfg(feature = "gzip")]
+        assert_eq!(b"CompressNone,Gzip".to_vec(), data);
+        assert_eq!(supported_compressors(), parse_compressors(&data));
+        assert_eq!(
+            vec![CompressType::Gzip],
+            parse_compressors(b"Snappy,Gzip,Zstd")
+        );
+        assert_eq!(
+            CompressType::CompressNone,
+            negotiate_compress_type(CompressType::from_u8(7))
+        );
+    }
+
+    #[test]
+    fn services() {
+        let data = encode_services(&["Arith.Mul", "Arith.Add"]);
+        assert_eq!(b"Arith.Add,Arith.Mul".to_vec(), data);
+        assert_eq!(vec!["Arith.Add", "Arith.Mul"], parse_services(&data));
+        assert!(parse_services(&encode_services::<&str>(&[])).is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::Metadata;
+use std::{net::SocketAddr, str::FromStr};
+
+/// metadata key of the token of a request, checked by the auth plugins of rpcx-go servers.
+pub const AUTH_KEY: &str = "__AUTH";
+/// metadata key of the address of the server handling a request, set by the server.
+pub const SERVER_ADDRESS: &str = "__ServerAddress";
+/// metadata key of the address of the client connection a request was read from, set by the
+/// server like the remote connection rpcx-go puts in the context of handlers.
+pub const REMOTE_CONN_ADDR: &str = "__RemoteConnAddr";
+/// metadata key of the group of a server, the `group` parameter of the metadata of services.
+pub const GROUP: &str = "group";
+
+/// typed accessors of the metadata keys rpcx-go uses and of values of any key, so handlers
+/// and plugins don't stringify and parse them by hand.
+///
+/// ```
+/// use rpcx_protocol::*;
+///
+/// let mut metadata = Metadata::new();
+/// metadata.set_auth_token("bearer abc");
+/// metadata.set_u64("retries", 3);
+/// assert_eq!(Some("bearer abc"), metadata.auth_token());
+/// assert_eq!(Some(3), metadata.get_u64("retries"));
+/// ```
+pub trait MetadataExt {
+    /// returns the value of the key parsed as `T`, `None` if it is missing or invalid.
+    fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T>;
+    fn get_u64(&self, key: &str) -> Option<u64>;
+    fn set_u64(&mut self, key: &str, value: u64);
+    fn get_i64(&self, key: &str) -> Option<i64>;
+    fn set_i64(&mut self, key: &str, value: i64);
+    /// returns the value of the key as a bool, which is `true` or `1`, `false` or `0`.
+    fn get_bool(&self, key: &str) -> Option<bool>;
+    fn set_bool(&mut self, key: &str, value: bool);
+    /// returns the token of the request, see `AUTH_KEY`.
+    fn auth_token(&self) -> Option<&str>;
+    fn set_auth_token(&mut self, token: &str);
+    /// returns the address of the server handling the request.
+    fn server_address(&self) -> Option<SocketAddr>;
+    /// returns the address of the client connection the request was read from.
+    fn remote_conn_addr(&self) -> Option<SocketAddr>;
+    fn group(&self) -> Option<&str>;
+    fn set_group(&mut self, group: &str);
+}
+
+impl MetadataExt for Metadata {
+    fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get_parsed(key)
+    }
+
+    fn set_u64(&mut self, key: &str, value: u64) {
+        self.insert(key.to_owned(), value.to_string());
+    }
+
+    fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_parsed(key)
+    }
+
+    fn set_i64(&mut self, key: &str, value: i64) {
+        self.insert(key.to_owned(), value.to_string());
+    }
+
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)?.as_str() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.insert(key.to_owned(), value.to_string());
+    }
+
+    fn auth_token(&self) -> Option<&str> {
+        self.get(AUTH_KEY).map(String::as_str)
+    }
+
+    fn set_auth_token(&mut self, token: &str) {
+        self.insert(AUTH_KEY.to_owned(), token.to_owned());
+    }
+
+    fn server_address(&self) -> Option<SocketAddr> {
+        self.get_parsed(SERVER_ADDRESS)
+    }
+
+    fn remote_conn_addr(&self) -> Option<SocketAddr> {
+        self.get_parsed(REMOTE_CONN_ADDR)
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.get(GROUP).map(String::as_str)
+    }
+
+    fn set_group(&mut self, group: &str) {
+        self.insert(GROUP.to_owned(), group.to_owned());
+    }
+}
+
+/// builds the metadata of a call.
+///
+/// ```
+/// use rpcx_protocol::*;
+///
+/// let metadata = MetadataBuilder::new()
+///     .auth_token("abc")
+///     .bool("dry_run", true)
+///     .build();
+/// assert_eq!(Some(true), metadata.get_bool("dry_run"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetadataBuilder {
+    metadata: Metadata,
+}
+
+impl MetadataBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn u64(mut self, key: &str, value: u64) -> Self {
+        self.metadata.set_u64(key, value);
+        self
+    }
+
+    pub fn i64(mut self, key: &str, value: i64) -> Self {
+        self.metadata.set_i64(key, value);
+        self
+    }
+
+    pub fn bool(mut self, key: &str, value: bool) -> Self {
+        self.metadata.set_bool(key, value);
+        self
+    }
+
+    pub fn auth_token(mut self, token: &str) -> Self {
+        self.metadata.set_auth_token(token);
+        self
+    }
+
+    pub fn group(mut self, group: &str) -> Self {
+        self.metadata.set_group(group);
+        self
+    }
+
+    pub fn build(self) -> Metadata {
+        self.metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessors() {
+        let mut metadata = Metadata::new();
+        assert_eq!(None, metadata.auth_token());
+        assert_eq!(None, metadata.remote_conn_addr());
+
+        metadata.set_auth_token("abc");
+        metadata.set_group("blue");
+        metadata.insert(REMOTE_CONN_ADDR.to_owned(), "127.0.0.1:5000".to_owned());
+        metadata.insert(SERVER_ADDRESS.to_owned(), "[::1]:8972".to_owned());
+        assert_eq!("abc", metadata["__AUTH"]);
+        assert_eq!(Some("abc"), metadata.auth_token());
+        assert_eq!(Some("blue"), metadata.group());
+        assert_eq!(
+            Some("127.0.0.1:5000".parse().unwrap()),
+            metadata.remote_conn_addr()
+        );
+        assert_eq!(
+            Some("[::1]:8972".parse().unwrap()),
+            metadata.server_address()
+        );
+
+        metadata.insert(REMOTE_CONN_ADDR.to_owned(), "unknown".to_owned());
+        assert_eq!(None, metadata.remote_conn_addr());
+    }
+
+    #[test]
+    fn typed_values() {
+        let mut metadata = MetadataBuilder::new()
+            .insert("name", "rpcx")
+            .u64("size", 42)
+            .i64("offset", -7)
+            .bool("compressed", false)
+            .group("blue")
+            .build();
+        assert_eq!(Some(42), metadata.get_u64("size"));
+        assert_eq!(Some(-7), metadata.get_i64("offset"));
+        assert_eq!(None, metadata.get_u64("offset"));
+        assert_eq!(Some(false), metadata.get_bool("compressed"));
+        assert_eq!("false", metadata["compressed"]);
+        assert_eq!(Some("blue"), metadata.group());
+        assert_eq!(None, metadata.get_bool("name"));
+        assert_eq!(None, metadata.get_u64("missing"));
+
+        metadata.insert("compressed".to_owned(), "1".to_owned());
+        assert_eq!(Some(true), metadata.get_bool("compressed"));
+        assert_eq!(Some(42.0), metadata.get_parsed::<f64>("size"));
+    }
+}
@@ -0,0 +1,266 @@
+use bytes::Bytes;
+use std::{
+    cmp,
+    io::{self, Read, Write},
+    sync::{mpsc::Receiver, Arc, Condvar, Mutex},
+};
+
+use crate::{
+    CompressType, Error, Message, MessageType, RpcxMessage, SerializeType, PROTOCOL_VERSION,
+};
+
+// A stream is opened by a request carrying `STREAM_FRAME: STREAM_OPEN` in its metadata, and its
+// frames share the seq of this request. Both sides send `STREAM_DATA` frames with chunks of the
+// body and finish with a `STREAM_END` frame. A sender can have at most `STREAM_WINDOW` chunks
+// in flight, the receiver grants more by `STREAM_ACK` frames.
+
+/// metadata key of the frame kind.
+pub const STREAM_FRAME: &str = "__rpcx_stream__";
+pub const STREAM_OPEN: &str = "open";
+pub const STREAM_DATA: &str = "data";
+pub const STREAM_END: &str = "end";
+pub const STREAM_ACK: &str = "ack";
+/// metadata key of the number of chunks acknowledged by a `STREAM_ACK` frame.
+pub const STREAM_CREDIT: &str = "__rpcx_stream_credit__";
+
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+pub const STREAM_WINDOW: usize = 16;
+
+/// returns the stream frame kind of the message if it belongs to a stream.
+pub fn get_stream_frame(msg: &Message) -> Option<String> {
+    msg.metadata.borrow().get(STREAM_FRAME).cloned()
+}
+
+/// builds a frame of the stream `seq`.
+pub fn new_stream_frame(mt: MessageType, seq: u64, kind: &str, payload: Vec<u8>) -> Message {
+    let mut msg = Message::new();
+    msg.set_version(PROTOCOL_VERSION);
+    msg.set_message_type(mt);
+    msg.set_serialize_type(SerializeType::SerializeNone);
+    msg.set_compress_type(CompressType::CompressNone);
+    msg.set_seq(seq);
+    msg.metadata
+        .borrow_mut()
+        .insert(STREAM_FRAME.to_owned(), kind.to_owned());
+    msg.payload = Bytes::from(payload);
+    msg
+}
+
+/// a received frame of a stream.
+#[derive(Debug)]
+pub enum StreamFrame {
+    Data(Bytes),
+    End,
+    Error(Error),
+}
+
+/// credits granted to the sender of a stream.
+#[derive(Debug)]
+pub struct StreamCredit {
+    credits: Mutex<Option<usize>>,
+    cond: Condvar,
+    window: usize,
+}
+
+impl StreamCredit {
+    pub fn new(window: usize) -> Self {
+        StreamCredit {
+            credits: Mutex::new(Some(window)),
+            cond: Condvar::new(),
+            window,
+        }
+    }
+
+    /// takes a credit, blocks until the receiver grants one.
+    pub fn acquire(&self) -> io::Result<()> {
+        let mut credits = self.credits.lock().unwrap();
+        loop {
+            match *credits {
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "stream is closed",
+                    ))
+                }
+                Some(0) => {}
+                Some(n) => {
+                    *credits = Some(n - 1);
+                    return Ok(());
+                }
+            }
+            credits = self.cond.wait(credits).unwrap();
+        }
+    }
+
+    /// grants `n` credits, the credits never exceed the window however many the receiver
+    /// grants.
+    pub fn release(&self, n: usize) {
+        if let Some(ref mut credits) = *self.credits.lock().unwrap() {
+            *credits = credits.saturating_add(n).min(self.window);
+        }
+        self.cond.notify_all();
+    }
+
+    /// wakes up the blocked sender with an error.
+    pub fn close(&self) {
+        *self.credits.lock().unwrap() = None;
+        self.cond.notify_all();
+    }
+}
+
+/// reads the body of a stream and acknowledges consumed chunks.
+pub struct StreamReader {
+    receiver: Receiver<StreamFrame>,
+    chunk: Bytes,
+    pos: usize,
+    done: bool,
+    unacked: usize,
+    ack: Box<dyn FnMut(usize) + Send>,
+}
+
+impl StreamReader {
+    pub fn new(receiver: Receiver<StreamFrame>, ack: Box<dyn FnMut(usize) + Send>) -> Self {
+        StreamReader {
+            receiver,
+            chunk: Bytes::new(),
+            pos: 0,
+            done: false,
+            unacked: 0,
+            ack,
+        }
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(StreamFrame::Data(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                    // acknowledge in batches to save frames
+                    self.unacked += 1;
+                    if self.unacked >= STREAM_WINDOW / 2 {
+                        (self.ack)(self.unacked);
+                        self.unacked = 0;
+                    }
+                }
+                Ok(StreamFrame::End) => self.done = true,
+                Ok(StreamFrame::Error(err)) => {
+                    return Err(io::Error::new(io::ErrorKind::Other, err));
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "stream is closed",
+                    ));
+                }
+            }
+        }
+
+        let n = cmp::min(buf.len(), self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// writes the body of a stream in chunks, waiting for credits when the window is full.
+///
+/// `flush` sends the buffered bytes, the end frame is sent by the owner.
+pub struct StreamWriter {
+    credit: Arc<StreamCredit>,
+    buf: Vec<u8>,
+    send: Box<dyn FnMut(Vec<u8>) -> io::Result<()> + Send>,
+}
+
+impl StreamWriter {
+    pub fn new(
+        credit: Arc<StreamCredit>,
+        send: Box<dyn FnMut(Vec<u8>) -> io::Result<()> + Send>,
+    ) -> Self {
+        StreamWriter {
+            credit,
+            buf: Vec::with_capacity(STREAM_CHUNK_SIZE),
+            send,
+        }
+    }
+
+    fn send_chunk(&mut self, n: usize) -> io::Result<()> {
+        self.credit.acquire()?;
+        let rest = self.buf.split_off(n);
+        let chunk = std::mem::replace(&mut self.buf, rest);
+        (self.send)(chunk)
+    }
+}
+
+impl Write for StreamWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        while self.buf.len() >= STREAM_CHUNK_SIZE {
+            self.send_chunk(STREAM_CHUNK_SIZE)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            let n = self.buf.len();
+            self.send_chunk(n)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread};
+
+    #[test]
+    fn credits_within_window() {
+        let credit = StreamCredit::new(2);
+        credit.release(usize::max_value());
+        for _ in 0..2 {
+            credit.acquire().unwrap();
+        }
+        assert_eq!(Some(0), *credit.credits.lock().unwrap());
+    }
+
+    #[test]
+    fn stream_with_flow_control() {
+        let (frame_sender, frame_receiver) = mpsc::channel();
+        let credit = Arc::new(StreamCredit::new(STREAM_WINDOW));
+
+        let credit_cloned = credit.clone();
+        let mut reader =
+            StreamReader::new(frame_receiver, Box::new(move |n| credit_cloned.release(n)));
+
+        let data: Vec<u8> = (0..STREAM_CHUNK_SIZE * 40 + 7)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let data_cloned = data.clone();
+        let end_sender = frame_sender.clone();
+        let writer = thread::spawn(move || {
+            let mut w = StreamWriter::new(
+                credit,
+                Box::new(move |chunk| {
+                    frame_sender
+                        .send(StreamFrame::Data(Bytes::from(chunk)))
+                        .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err.to_string()))
+                }),
+            );
+            w.write_all(&data_cloned).unwrap();
+            w.flush().unwrap();
+            end_sender.send(StreamFrame::End).unwrap();
+        });
+
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        writer.join().unwrap();
+        assert_eq!(data, received);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+use rustls::{
+    internal::pemfile, Certificate, ClientConfig, ClientSession, PrivateKey, RootCertStore,
+    ServerConfig, ServerSession, Session,
+};
+
+use crate::{Error, ErrorKind, Result};
+
+// the bytes read from the connection at once, a TLS record at most.
+const READ_CHUNK: usize = 16 * 1024 + 256;
+
+/// a TLS connection, see `Conn`.
+///
+/// The handshake is done by `accept` or `connect` before the connection is shared, so the
+/// plaintext written by one thread is encrypted at once while another thread reads. The
+/// reader processes the records which come after the handshake, such as session tickets
+/// and key updates, and writes the records they are answered with.
+pub struct TlsStream {
+    session: Arc<Mutex<dyn Session>>,
+    conn: TcpStream,
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("conn", &self.conn)
+            .finish()
+    }
+}
+
+impl TlsStream {
+    /// does the handshake of a connection accepted by a server. It waits for the client up
+    /// to the read timeout of the connection.
+    pub fn accept(config: &Arc<ServerConfig>, conn: TcpStream) -> Result<TlsStream> {
+        let mut session = ServerSession::new(config);
+        handshake(&mut session, &conn)?;
+        Ok(TlsStream {
+            session: Arc::new(Mutex::new(session)),
+            conn,
+        })
+    }
+
+    /// does the handshake of a connection to a server. The certificate of the server is
+    /// verified by `server_name`, which is sent by SNI as well.
+    pub fn connect(
+        config: &Arc<ClientConfig>,
+        server_name: &str,
+        conn: TcpStream,
+    ) -> Result<TlsStream> {
+        let name = webpki::DNSNameRef::try_from_ascii_str(server_name).map_err(|_| {
+            Error::new(
+                ErrorKind::Config,
+                format!("invalid TLS server name {}", server_name),
+            )
+        })?;
+        let mut session = ClientSession::new(config, name);
+        handshake(&mut session, &conn)?;
+        Ok(TlsStream {
+            session: Arc::new(Mutex::new(session)),
+            conn,
+        })
+    }
+
+    /// returns the TCP connection under TLS.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.conn
+    }
+
+    pub fn try_clone(&self) -> io::Result<TlsStream> {
+        Ok(TlsStream {
+            session: self.session.clone(),
+            conn: self.conn.try_clone()?,
+        })
+    }
+
+    /// returns the protocol negotiated by ALPN, `None` if the peers agreed on none.
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        let session = self.session.lock().unwrap();
+        session.get_alpn_protocol().map(<[u8]>::to_vec)
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut chunk = [0u8; READ_CHUNK];
+        loop {
+            match self.session.lock().unwrap().read(buf) {
+                Ok(0) => {}
+                // the peer sent close_notify
+                Err(ref err) if err.kind() == io::ErrorKind::ConnectionAborted => return Ok(0),
+                rt => return rt,
+            }
+
+            // the connection is read without the session, writers go on meanwhile
+            let n = (&self.conn).read(&mut chunk)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut session = self.session.lock().unwrap();
+            let mut records = &chunk[..n];
+            while !records.is_empty() {
+                session.read_tls(&mut records)?;
+                process_records(&mut *session, &self.conn)?;
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut session = self.session.lock().unwrap();
+        let n = session.write(buf)?;
+        write_records(&mut *session, &self.conn)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut session = self.session.lock().unwrap();
+        session.flush()?;
+        write_records(&mut *session, &self.conn)
+    }
+}
+
+fn handshake(session: &mut dyn Session, conn: &TcpStream) -> Result<()> {
+    exchange_handshake(session, conn)
+        .map_err(|err| Error::new(ErrorKind::Network, format!("TLS handshake failed: {}", err)))
+}
+
+// exchanges the records of the handshake until it is done.
+fn exchange_handshake(session: &mut dyn Session, conn: &TcpStream) -> io::Result<()> {
+    while session.is_handshaking() {
+        write_records(session, conn)?;
+        if !session.is_handshaking() {
+            break;
+        }
+        if session.read_tls(&mut &*conn)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        process_records(session, conn)?;
+    }
+    write_records(session, conn)
+}
+
+// processes the records read and writes the ones they are answered with, the alert of an
+// error as well.
+fn process_records(session: &mut dyn Session, conn: &TcpStream) -> io::Result<()> {
+    let processed = session.process_new_packets();
+    let written = write_records(session, conn);
+    processed.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    written
+}
+
+fn write_records(session: &mut dyn Session, mut conn: &TcpStream) -> io::Result<()> {
+    while session.wants_write() {
+        session.write_tls(&mut conn)?;
+    }
+    Ok(())
+}
+
+/// reads the certificates of a PEM file, the chain of a certificate or the certificates of
+/// CAs.
+pub fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let file = File::open(path).map_err(|err| tls_file_error(path, &err))?;
+    match pemfile::certs(&mut BufReader::new(file)) {
+        Ok(certs) if !certs.is_empty() => Ok(certs),
+        _ => Err(tls_file_error(path, &"no certificate")),
+    }
+}
+
+/// reads the private key of a PEM file, PKCS #8 or RSA.
+pub fn load_private_key(path: &str) -> Result<PrivateKey> {
+    let data = fs::read(path).map_err(|err| tls_file_error(path, &err))?;
+    let pkcs8 = pemfile::pkcs8_private_keys(&mut &data[..]).unwrap_or_default();
+    let rsa = pemfile::rsa_private_keys(&mut &data[..]).unwrap_or_default();
+    pkcs8
+        .into_iter()
+        .chain(rsa)
+        .next()
+        .ok_or_else(|| tls_file_error(path, &"no private key"))
+}
+
+/// reads the certificates of the CAs of a PEM file, which verify the certificates of peers.
+pub fn load_roots(path: &str) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(&cert)
+            .map_err(|err| tls_file_error(path, &format!("{:?}", err)))?;
+    }
+    Ok(roots)
+}
+
+fn tls_file_error(path: &str, err: &dyn fmt::Display) -> Error {
+    Error::new(ErrorKind::Config, format!("invalid {}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::NoClientAuth;
+    use std::{net::TcpListener, thread};
+
+    fn testdata(name: &str) -> String {
+        format!(
+            "{}/../test_suite/testdata/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    #[test]
+    fn negotiate_and_exchange() {
+        let mut server_config = ServerConfig::new(NoClientAuth::new());
+        server_config
+            .set_single_cert(
+                load_certs(&testdata("tls_a.pem")).unwrap(),
+                load_private_key(&testdata("tls_a.key")).unwrap(),
+            )
+            .unwrap();
+        server_config.set_protocols(&[b"h2".to_vec(), b"rpcx".to_vec()]);
+        let server_config = Arc::new(server_config);
+        let mut client_config = ClientConfig::new();
+        client_config.root_store = load_roots(&testdata("tls_ca.pem")).unwrap();
+        client_config.alpn_protocols = vec![b"rpcx".to_vec()];
+        let client_config = Arc::new(client_config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            let mut stream = TlsStream::accept(&server_config, conn).unwrap();
+            assert_eq!(Some(b"rpcx".to_vec()), stream.alpn_protocol());
+            // echoes until the client closes the connection
+            let mut reader = stream.try_clone().unwrap();
+            io::copy(&mut reader, &mut stream).unwrap();
+
+            let (conn, _) = listener.accept().unwrap();
+            assert!(TlsStream::accept(&server_config, conn).is_err());
+        });
+
+        let conn = TcpStream::connect(addr).unwrap();
+        let mut stream = TlsStream::connect(&client_config, "a.rpcx.test", conn).unwrap();
+        assert_eq!(Some(b"rpcx".to_vec()), stream.alpn_protocol());
+        let data = vec![7u8; 3 * READ_CHUNK];
+        stream.write_all(&data).unwrap();
+        let mut echoed = vec![0u8; data.len()];
+        stream.read_exact(&mut echoed).unwrap();
+        assert_eq!(data, echoed);
+        stream.session.lock().unwrap().send_close_notify();
+        stream.flush().unwrap();
+
+        // the certificate doesn't match another name
+        let conn = TcpStream::connect(addr).unwrap();
+        let err = TlsStream::connect(&client_config, "b.rpcx.test", conn).unwrap_err();
+        assert_eq!(ErrorKind::Network, err.kind());
+        server.join().unwrap();
+    }
+}
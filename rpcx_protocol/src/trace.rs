@@ -0,0 +1,73 @@
+use crate::Metadata;
+
+/// metadata key of the id of the trace of a request, 32 hex digits in the B3 format of Zipkin.
+pub const TRACE_ID: &str = "X-B3-TraceId";
+/// metadata key of the id of the span of a request, 16 hex digits.
+pub const SPAN_ID: &str = "X-B3-SpanId";
+/// metadata key of the id of the span which sent the request.
+pub const PARENT_SPAN_ID: &str = "X-B3-ParentSpanId";
+
+/// the ids of a span in a trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_id: Option<String>,
+}
+
+impl SpanContext {
+    /// starts a span which continues the trace of the metadata, or a new trace.
+    pub fn child_of(metadata: &Metadata) -> Self {
+        match metadata.get(TRACE_ID) {
+            Some(trace_id) if !trace_id.is_empty() => SpanContext {
+                trace_id: trace_id.clone(),
+                span_id: new_span_id(),
+                parent_id: metadata.get(SPAN_ID).cloned(),
+            },
+            _ => SpanContext {
+                trace_id: format!("{}{}", new_span_id(), new_span_id()),
+                span_id: new_span_id(),
+                parent_id: None,
+            },
+        }
+    }
+
+    /// sets the ids of the span in the metadata, so the calls made for it continue the trace.
+    pub fn inject(&self, metadata: &mut Metadata) {
+        metadata.insert(TRACE_ID.to_owned(), self.trace_id.clone());
+        metadata.insert(SPAN_ID.to_owned(), self.span_id.clone());
+        match &self.parent_id {
+            Some(parent_id) => metadata.insert(PARENT_SPAN_ID.to_owned(), parent_id.clone()),
+            None => metadata.remove(PARENT_SPAN_ID),
+        };
+    }
+}
+
+// generates a random id of a span, 16 hex digits.
+fn new_span_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_context() {
+        let root = SpanContext::child_of(&Metadata::new());
+        assert_eq!(32, root.trace_id.len());
+        assert_eq!(16, root.span_id.len());
+        assert_eq!(None, root.parent_id);
+
+        let mut metadata = Metadata::new();
+        root.inject(&mut metadata);
+        assert!(!metadata.contains_key(PARENT_SPAN_ID));
+        let child = SpanContext::child_of(&metadata);
+        assert_eq!(root.trace_id, child.trace_id);
+        assert_ne!(root.span_id, child.span_id);
+        assert_eq!(Some(root.span_id.clone()), child.parent_id);
+
+        child.inject(&mut metadata);
+        assert_eq!(root.span_id, metadata[PARENT_SPAN_ID]);
+    }
+}
@@ -0,0 +1,317 @@
+//! the WebSocket transport of connections, which browsers connect by their WebSocket API. The
+//! rpcx messages are carried by binary frames as a stream of bytes, a message can span frames
+//! and a frame can carry several messages.
+
+use std::{
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::Conn;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+// the longest payload of a control frame
+const MAX_CONTROL_LEN: u64 = 125;
+
+/// the server side of a WebSocket connection whose handshake is done. Reading returns the
+/// payloads of the data frames of the client and answers its pings, writing sends each write
+/// as a binary frame.
+///
+/// Its clones share the connection and copy the state of reading, so one of them reads while
+/// the others write.
+#[derive(Debug)]
+pub struct WsStream {
+    reader: Box<Conn>,
+    writer: Arc<Mutex<Conn>>,
+    // the bytes read from the connection with the handshake, read before it
+    buffered: Vec<u8>,
+    // the payload of the current frame which is not read yet and its mask
+    remaining: u64,
+    mask: [u8; 4],
+    mask_pos: usize,
+    closed: bool,
+}
+
+impl WsStream {
+    /// wraps the connection once the handshake is replied, `buffered` is what was read after
+    /// the handshake.
+    pub fn new(conn: Conn, buffered: Vec<u8>) -> io::Result<Self> {
+        Ok(WsStream {
+            reader: Box::new(conn.try_clone()?),
+            writer: Arc::new(Mutex::new(conn)),
+            buffered,
+            remaining: 0,
+            mask: [0; 4],
+            mask_pos: 0,
+            closed: false,
+        })
+    }
+
+    pub fn get_ref(&self) -> &Conn {
+        &self.reader
+    }
+
+    pub fn try_clone(&self) -> io::Result<WsStream> {
+        Ok(WsStream {
+            reader: Box::new(self.reader.try_clone()?),
+            writer: self.writer.clone(),
+            buffered: self.buffered.clone(),
+            remaining: self.remaining,
+            mask: self.mask,
+            mask_pos: self.mask_pos,
+            closed: self.closed,
+        })
+    }
+
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffered.is_empty() {
+            return self.reader.read(buf);
+        }
+        let n = buf.len().min(self.buffered.len());
+        buf[..n].copy_from_slice(&self.buffered[..n]);
+        self.buffered.drain(..n);
+        Ok(n)
+    }
+
+    fn read_source_exact(&mut self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_source(buf)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    // reads the header of the next frame. The control frames are handled here, false is
+    // returned once the client closes the connection.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        let mut head = [0u8; 2];
+        self.read_source_exact(&mut head)?;
+        let opcode = head[0] & 0x0f;
+        if head[1] & 0x80 == 0 {
+            return Err(invalid_data("the frames of clients must be masked"));
+        }
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                self.read_source_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                self.read_source_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        let mut mask = [0u8; 4];
+        self.read_source_exact(&mut mask)?;
+        self.mask = mask;
+        self.mask_pos = 0;
+        self.remaining = len;
+
+        match opcode {
+            OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => Ok(true),
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => {
+                if len > MAX_CONTROL_LEN {
+                    return Err(invalid_data("a control frame is longer than 125 bytes"));
+                }
+                let mut payload = vec![0u8; len as usize];
+                self.read_payload_exact(&mut payload)?;
+                match opcode {
+                    OPCODE_CLOSE => {
+                        // the close is echoed, the connection is closed by the server then
+                        let _ = self.write_frame(OPCODE_CLOSE, &payload);
+                        Ok(false)
+                    }
+                    OPCODE_PING => {
+                        self.write_frame(OPCODE_PONG, &payload)?;
+                        Ok(true)
+                    }
+                    _ => Ok(true),
+                }
+            }
+            _ => Err(invalid_data(format!("unknown websocket opcode {}", opcode))),
+        }
+    }
+
+    fn read_payload(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        let n = self.read_source(&mut buf[..len])?;
+        if n == 0 && len > 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        for b in &mut buf[..n] {
+            *b ^= self.mask[self.mask_pos];
+            self.mask_pos = (self.mask_pos + 1) % 4;
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+
+    fn read_payload_exact(&mut self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = self.read_payload(buf)?;
+            buf = &mut buf[n..];
+        }
+        Ok(())
+    }
+
+    fn write_frame(&self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(10 + payload.len());
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => frame.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        self.writer.lock().unwrap().write_all(&frame)
+    }
+}
+
+impl Read for WsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.remaining == 0 {
+            if self.closed {
+                return Ok(0);
+            }
+            if !self.next_frame()? {
+                self.closed = true;
+            }
+        }
+        self.read_payload(buf)
+    }
+}
+
+impl Write for WsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_frame(OPCODE_BINARY, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+/// returns the `Sec-WebSocket-Accept` of the `Sec-WebSocket-Key` of a handshake.
+pub fn websocket_accept(key: &str) -> String {
+    const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+// the digest of the handshake, which is all SHA-1 is used for.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *h = h.wrapping_add(*v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, v) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_accept() {
+        // the example of RFC 6455
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            websocket_accept("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        assert_eq!("", base64(b""));
+        assert_eq!("Zm9vYg==", base64(b"foob"));
+        assert_eq!("Zm9vYmE=", base64(b"fooba"));
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+
+/// the resolution of the timers of the calls of a connection.
+pub const TIMER_TICK: Duration = Duration::from_millis(10);
+/// the slots of the timer wheel of a connection, it covers 5.12s per round with `TIMER_TICK`.
+pub const TIMER_SLOTS: usize = 512;
+
+/// a hashed timer wheel, which tracks many deadlines with one thread.
+///
+/// A deadline goes into the slot of its tick modulo the slots, so inserting takes O(1)
+/// whatever the timers pending. Deadlines further than a round share slots with nearer ones
+/// and are skipped until their tick comes. Timers are not cancelled: the owner ignores the
+/// values which expire after they are done with, so finished timers are dropped lazily.
+///
+/// ```
+/// use rpcx_protocol::*;
+/// use std::time::{Duration, Instant};
+///
+/// let mut wheel = TimerWheel::new(Duration::from_millis(10), 64);
+/// let now = Instant::now();
+/// wheel.insert(now + Duration::from_millis(30), 1);
+/// assert!(wheel.expire(now).is_empty());
+/// assert_eq!(vec![1], wheel.expire(now + Duration::from_millis(40)));
+/// ```
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    tick: Duration,
+    start: Instant,
+    // the values and the ticks of their deadlines
+    slots: Vec<Vec<(u64, T)>>,
+    // the first tick which has not expired
+    current: u64,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new(tick: Duration, slots: usize) -> Self {
+        TimerWheel {
+            tick: tick.max(Duration::from_millis(1)),
+            start: Instant::now(),
+            slots: (0..slots.max(1)).map(|_| Vec::new()).collect(),
+            current: 0,
+            len: 0,
+        }
+    }
+
+    // returns the tick which the instant falls in.
+    fn tick_of(&self, instant: Instant) -> u64 {
+        let elapsed = instant.saturating_duration_since(self.start);
+        (elapsed.as_nanos() / self.tick.as_nanos()) as u64
+    }
+
+    // returns when the tick starts.
+    fn instant_of(&self, tick: u64) -> Instant {
+        self.start + Duration::from_nanos((self.tick.as_nanos() as u64).saturating_mul(tick))
+    }
+
+    /// adds a timer which expires at `deadline`, rounded up to the tick. Deadlines which
+    /// have passed expire at the next tick.
+    pub fn insert(&mut self, deadline: Instant, value: T) {
+        let mut tick = self.tick_of(deadline);
+        if self.instant_of(tick) < deadline {
+            tick += 1;
+        }
+        let tick = tick.max(self.current);
+        let n = self.slots.len() as u64;
+        self.slots[(tick % n) as usize].push((tick, value));
+        self.len += 1;
+    }
+
+    /// removes and returns the values whose deadlines have passed by `now`.
+    pub fn expire(&mut self, now: Instant) -> Vec<T> {
+        let now_tick = self.tick_of(now);
+        let mut expired = Vec::new();
+        if now_tick < self.current {
+            return expired;
+        }
+        // the slots are visited once at most, however long the wheel was not advanced
+        let n = self.slots.len() as u64;
+        let last = now_tick.min(self.current + n - 1);
+        for tick in self.current..=last {
+            let slot = &mut self.slots[(tick % n) as usize];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].0 <= now_tick {
+                    expired.push(slot.swap_remove(i).1);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.current = now_tick + 1;
+        self.len -= expired.len();
+        expired
+    }
+
+    /// returns when the owner should call `expire` next: the earliest deadline in the
+    /// current round, or the end of the round if all timers are in later ones.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        if self.len == 0 {
+            return None;
+        }
+        let n = self.slots.len() as u64;
+        let tick = (self.current..self.current + n)
+            .find(|&tick| {
+                self.slots[(tick % n) as usize]
+                    .iter()
+                    .any(|&(deadline, _)| deadline == tick)
+            })
+            .unwrap_or(self.current + n);
+        Some(self.instant_of(tick))
+    }
+
+    /// returns the timers pending.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire() {
+        let mut wheel = TimerWheel::new(Duration::from_millis(10), 8);
+        let start = wheel.start;
+        let ms = |n| start + Duration::from_millis(n);
+        wheel.insert(ms(25), 1);
+        wheel.insert(ms(30), 2);
+        // the same slot a round later
+        wheel.insert(ms(110), 3);
+        wheel.insert(ms(500), 4);
+        assert_eq!(4, wheel.len());
+        assert_eq!(Some(ms(30)), wheel.next_expiry());
+
+        assert!(wheel.expire(ms(29)).is_empty());
+        let mut expired = wheel.expire(ms(35));
+        expired.sort();
+        assert_eq!(vec![1, 2], expired);
+        assert!(wheel.expire(ms(109)).is_empty());
+        assert_eq!(vec![3], wheel.expire(ms(115)));
+        // only timers of later rounds are left
+        assert_eq!(Some(ms(200)), wheel.next_expiry());
+
+        // a passed deadline expires at the next tick
+        wheel.insert(ms(50), 5);
+        assert_eq!(vec![5], wheel.expire(ms(120)));
+
+        // the wheel catches up after many rounds
+        assert_eq!(vec![4], wheel.expire(ms(1000)));
+        assert!(wheel.is_empty());
+        assert_eq!(None, wheel.next_expiry());
+    }
+}
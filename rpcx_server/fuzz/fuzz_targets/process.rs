@@ -0,0 +1,36 @@
+// writes arbitrary bytes to a connection of a server like a peer, so they drive the read path
+// of connections, the builtin services and the dispatch of requests to a registered function.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rpcx_server::{RpcxFn, Server};
+use std::{
+    io::{self, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::Once,
+    thread,
+    time::Duration,
+};
+
+const ADDR: &str = "127.0.0.1:18972";
+
+static START: Once = Once::new();
+
+fn start_server() {
+    START.call_once(|| {
+        let mut server = Server::new(ADDR.to_owned(), 0);
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        server.register_fn("Echo", "Echo", "".to_owned(), echo);
+        let listener = TcpListener::bind(ADDR).unwrap();
+        thread::spawn(move || server.start_with_listener(listener));
+    });
+}
+
+fuzz_target!(|data: &[u8]| {
+    start_server();
+    let mut conn = TcpStream::connect(ADDR).unwrap();
+    conn.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let _ = conn.write_all(data);
+    let _ = conn.shutdown(Shutdown::Write);
+    // the replies are read until the server closes the connection
+    let _ = io::copy(&mut conn, &mut io::sink());
+});
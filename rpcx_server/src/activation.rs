@@ -0,0 +1,66 @@
+use super::Server;
+use rpcx_protocol::*;
+use std::{
+    env,
+    net::TcpListener,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+};
+
+// the first file descriptor passed by systemd, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+impl Server {
+    /// creates a server which serves on a listener bound by the caller, such as a socket
+    /// inherited from a supervisor, instead of binding `addr` itself. It uses 2 threads per
+    /// core to handle requests, like `Server::new` with 0 threads.
+    pub fn from_listener(listener: TcpListener) -> Result<Self> {
+        let mut server = Server::new(listener.local_addr()?.to_string(), 0);
+        server.listener = Some(listener);
+        Ok(server)
+    }
+
+    /// creates a server which serves on the first socket passed by systemd socket activation.
+    pub fn from_systemd() -> Result<Self> {
+        match listen_fds()?.into_iter().next() {
+            Some(listener) => Self::from_listener(listener),
+            None => Err(Error::new(
+                ErrorKind::Config,
+                "no socket is passed by systemd in LISTEN_FDS",
+            )),
+        }
+    }
+}
+
+/// returns the listening sockets passed by systemd socket activation, empty if the process
+/// is not socket activated. The `LISTEN_*` variables are removed so children don't inherit
+/// them.
+pub fn listen_fds() -> Result<Vec<TcpListener>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    // the sockets are passed to another process
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(std::process::id()) {
+        return Ok(Vec::new());
+    }
+    let n = match fds {
+        Some(fds) => fds
+            .parse::<RawFd>()
+            .map_err(|err| Error::new(ErrorKind::Config, format!("invalid LISTEN_FDS: {}", err)))?,
+        None => return Ok(Vec::new()),
+    };
+
+    let listeners: Vec<TcpListener> = (LISTEN_FDS_START..LISTEN_FDS_START + n)
+        .map(|fd| unsafe { TcpListener::from_raw_fd(fd) })
+        .collect();
+    for listener in &listeners {
+        unsafe {
+            libc::fcntl(listener.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        // fails if the descriptor is not a socket
+        listener.local_addr()?;
+    }
+    Ok(listeners)
+}
@@ -0,0 +1,342 @@
+use super::{
+    http::{read_body, read_head, write_response, HttpRequest},
+    metrics::MethodStatsTable,
+    ConnWriter, RequestQueue, RpcxFn, RpcxStreamFn, Server,
+};
+use rpcx_protocol::*;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufReader, BufWriter, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+// the most admin connections served at once, the others are replied with 503.
+const MAX_ADMIN_CONNS: usize = 16;
+// how long an admin connection may be idle, so idle ones don't hold the connections.
+const ADMIN_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// a request being handled by the server, see `Server::in_flight_calls`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InFlightCall {
+    /// the address of the client.
+    pub peer: SocketAddr,
+    pub seq: u64,
+    pub service_path: String,
+    pub service_method: String,
+    /// the time since the request was read, including the time it waited for a worker.
+    pub elapsed: Duration,
+}
+
+// compares the bytes in a time which only depends on their length, so a token can't be
+// guessed byte by byte from the times of the replies.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// returns the requests being handled by the connections, the longest running first.
+fn in_flight_calls(conns: &RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>) -> Vec<InFlightCall> {
+    let conns = conns.read().unwrap();
+    let mut calls: Vec<InFlightCall> =
+        conns
+            .iter()
+            .flat_map(|(peer, writer)| {
+                writer.calls().into_iter().map(
+                    move |(seq, service_path, service_method, received)| InFlightCall {
+                        peer: *peer,
+                        seq,
+                        service_path,
+                        service_method,
+                        elapsed: received.elapsed(),
+                    },
+                )
+            })
+            .collect();
+    calls.sort_by(|a, b| b.elapsed.cmp(&a.elapsed));
+    calls
+}
+
+// the state of the server shown by the admin endpoints.
+#[derive(Clone)]
+struct AdminState {
+    token: String,
+    services: Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    stream_services: Arc<RwLock<HashMap<String, RpcxStreamFn>>>,
+    metas: Arc<RwLock<HashMap<String, String>>>,
+    conns: Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+    queue: Arc<RequestQueue>,
+    method_stats: Arc<MethodStatsTable>,
+    plugins: Value,
+    weight: Arc<RwLock<Option<u32>>>,
+    config: Value,
+    conn_count: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// starts an http listener on `addr` for operators, which serves the state of the server
+    /// in JSON:
+    ///
+    /// - `GET /services`: the registered services, their methods and metadata.
+    /// - `GET /connections`: the connected clients, their requests in flight and the requests
+    ///   waiting for a worker.
+    /// - `GET /calls`: the requests being handled, see `in_flight_calls`.
+    /// - `GET /methods`: the requests of each method, see `method_stats`.
+    /// - `GET /plugins`: the number of plugins of each kind.
+    /// - `GET /config`: the runtime configuration.
+    ///
+    /// Requests must carry `Authorization: Bearer <token>`, the others are replied with 401
+    /// before their body is read. At most 16 connections are served at once. It returns the address the
+    /// listener is bound to. Plugins added after this call are not counted.
+    pub fn start_admin(&self, addr: &str, token: &str) -> Result<SocketAddr> {
+        if token.is_empty() {
+            return Err(Error::new(ErrorKind::Config, "the admin token is empty"));
+        }
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+
+        let state = AdminState {
+            token: token.to_owned(),
+            services: self.services.clone(),
+            stream_services: self.stream_services.clone(),
+            metas: self.metas.clone(),
+            conns: self.conns.clone(),
+            queue: self.queue.clone(),
+            method_stats: self.method_stats.clone(),
+            plugins: json!({
+                "register": self.register_plugins.read().unwrap().len(),
+                "connect": self.connect_plugins.read().unwrap().len(),
+                "message": self.message_plugins.read().unwrap().len(),
+            }),
+            weight: self.weight.clone(),
+            config: json!({
+                "addr": self.addr,
+                "thread_number": self.thread_number,
+                "version": self.version,
+                "file_transfer": self.file_transfer.is_some(),
+            }),
+            conn_count: Arc::new(AtomicUsize::new(0)),
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if state.conn_count.fetch_add(1, Ordering::SeqCst) >= MAX_ADMIN_CONNS {
+                            state.conn_count.fetch_sub(1, Ordering::SeqCst);
+                            reject(stream);
+                            continue;
+                        }
+                        let state = state.clone();
+                        thread::spawn(move || {
+                            state.serve(stream);
+                            state.conn_count.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                    Err(err) => {
+                        eprintln!("failed to accept admin connection: {}", err);
+                        return;
+                    }
+                }
+            }
+        });
+        println!("Admin listening on: {}", local_addr);
+        Ok(local_addr)
+    }
+
+    /// returns the requests being handled, the longest running first, to diagnose stuck
+    /// requests.
+    pub fn in_flight_calls(&self) -> Vec<InFlightCall> {
+        in_flight_calls(&self.conns)
+    }
+}
+
+impl AdminState {
+    fn serve(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(ADMIN_READ_TIMEOUT));
+        let (reader, writer) = match (stream.try_clone(), stream.try_clone()) {
+            (Ok(reader), Ok(writer)) => (reader, writer),
+            (Err(err), _) | (_, Err(err)) => {
+                eprintln!("failed to clone admin connection: {}", err);
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        };
+        let mut reader = BufReader::new(reader);
+        let mut writer = BufWriter::new(writer);
+
+        loop {
+            let result = read_head(&mut reader).and_then(|req| match req {
+                // the body of unauthorized requests is not read
+                Some(mut req) if self.authorized(&req) => {
+                    read_body(&mut reader, &mut req)?;
+                    Ok(Some(req))
+                }
+                req => Ok(req),
+            });
+            let req = match result {
+                Ok(Some(req)) => req,
+                Ok(None) => break,
+                Err(err) => {
+                    let body = json!({"error": err.error.to_string()});
+                    let _ = write_json(&mut writer, err.status, &body, false);
+                    break;
+                }
+            };
+            if !self.authorized(&req) {
+                let _ = write_json(&mut writer, 401, &json!({"error": "unauthorized"}), false);
+                break;
+            }
+            let keep_alive = req.keep_alive;
+            let (status, body) = self.handle(&req);
+            if write_json(&mut writer, status, &body, keep_alive).is_err() || !keep_alive {
+                break;
+            }
+        }
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+
+    // checks `Authorization: Bearer <token>` in constant time.
+    fn authorized(&self, req: &HttpRequest) -> bool {
+        let token = match req.get_header("Authorization") {
+            Some(v) if v.starts_with("Bearer ") => v["Bearer ".len()..].trim(),
+            _ => return false,
+        };
+        constant_time_eq(token.as_bytes(), self.token.as_bytes())
+    }
+
+    fn handle(&self, req: &HttpRequest) -> (u16, Value) {
+        if req.method != "GET" {
+            return (405, json!({"error": "method not allowed"}));
+        }
+
+        match req.path.as_str() {
+            "/services" => (200, self.services()),
+            "/connections" => (200, self.connections()),
+            "/calls" => (200, self.calls()),
+            "/methods" => (200, self.methods()),
+            "/plugins" => (200, self.plugins.clone()),
+            "/config" => {
+                let mut config = self.config.clone();
+                config["weight"] = json!(*self.weight.read().unwrap());
+                (200, config)
+            }
+            _ => (404, json!({"error": "not found"})),
+        }
+    }
+
+    fn services(&self) -> Value {
+        let mut services: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+        for key in self.services.read().unwrap().keys() {
+            if let Some((path, method)) = split_key(key) {
+                services.entry(path).or_default().0.push(method);
+            }
+        }
+        for key in self.stream_services.read().unwrap().keys() {
+            if let Some((path, method)) = split_key(key) {
+                services.entry(path).or_default().1.push(method);
+            }
+        }
+
+        let metas = self.metas.read().unwrap();
+        let services: BTreeMap<String, Value> = services
+            .into_iter()
+            .map(|(path, (mut methods, mut streams))| {
+                methods.sort();
+                streams.sort();
+                let meta = metas.get(&path).cloned().unwrap_or_default();
+                let service = json!({"methods": methods, "streams": streams, "meta": meta});
+                (path, service)
+            })
+            .collect();
+        json!(services)
+    }
+
+    fn connections(&self) -> Value {
+        let conns = self.conns.read().unwrap();
+        let mut in_flight = 0;
+        let mut clients: Vec<Value> = conns
+            .iter()
+            .map(|(addr, writer)| {
+                let n = writer.in_flight();
+                in_flight += n;
+                json!({
+                    "addr": addr.to_string(),
+                    "in_flight": n,
+                    "last_heartbeat_ms": writer
+                        .last_heartbeat()
+                        .map(|t| t.elapsed().as_millis() as u64),
+                    "last_activity_ms": writer.last_activity().elapsed().as_millis() as u64,
+                })
+            })
+            .collect();
+        clients.sort_by(|a, b| a["addr"].as_str().cmp(&b["addr"].as_str()));
+        json!({
+            "count": clients.len(),
+            "in_flight": in_flight,
+            "queued": self.queue.depth(),
+            "clients": clients,
+        })
+    }
+
+    fn calls(&self) -> Value {
+        let calls: Vec<Value> = in_flight_calls(&self.conns)
+            .into_iter()
+            .map(|call| {
+                json!({
+                    "peer": call.peer.to_string(),
+                    "seq": call.seq,
+                    "service_path": call.service_path,
+                    "service_method": call.service_method,
+                    "elapsed_ms": call.elapsed.as_millis() as u64,
+                })
+            })
+            .collect();
+        json!(calls)
+    }
+
+    fn methods(&self) -> Value {
+        let methods: Vec<Value> = self
+            .method_stats
+            .snapshot()
+            .into_iter()
+            .map(|stats| {
+                json!({
+                    "service_path": stats.service_path,
+                    "service_method": stats.service_method,
+                    "requests": stats.requests,
+                    "errors": stats.errors,
+                    "success_ratio": stats.success_ratio(),
+                    "apdex": stats.apdex(),
+                    "buckets": stats.buckets,
+                })
+            })
+            .collect();
+        json!(methods)
+    }
+}
+
+fn write_json<W: Write>(w: &mut W, status: u16, body: &Value, keep_alive: bool) -> Result<()> {
+    let headers = [("Content-Type".to_owned(), "application/json".to_owned())];
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    write_response(w, status, &headers, &body, keep_alive)
+}
+
+// replies 503 to a connection beyond `MAX_ADMIN_CONNS`.
+fn reject(stream: TcpStream) {
+    let mut writer = BufWriter::new(&stream);
+    let body = json!({"error": "too many admin connections"});
+    let _ = write_json(&mut writer, 503, &body, false);
+    drop(writer);
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// splits the key of a registered function into the service path and the method.
+fn split_key(key: &str) -> Option<(String, String)> {
+    let idx = key.rfind('.')?;
+    Some((key[..idx].to_owned(), key[idx + 1..].to_owned()))
+}
@@ -0,0 +1,188 @@
+use super::MessagePlugin;
+use rpcx_protocol::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// the metadata which differs by call or by connection without changing the reply, left out
+// of the keys. The caller is keyed by the IP of its connection instead.
+const UNKEYED_METADATA: [&str; 9] = [
+    REQUEST_ID,
+    DEADLINE,
+    IDEMPOTENCY_KEY,
+    PRIORITY,
+    REMOTE_CONN_ADDR,
+    SERVER_ADDRESS,
+    TRACE_ID,
+    SPAN_ID,
+    PARENT_SPAN_ID,
+];
+
+// a request is identified by its method, its serialize type, and its caller, its metadata
+// and its serialized args.
+type CacheKey = (String, u8, Vec<u8>);
+
+#[derive(Debug, Default)]
+struct Entries {
+    replies: HashMap<CacheKey, (Instant, Vec<u8>)>,
+    // keys in the order they are cached, for evicting the oldest reply. The keys cached again
+    // are queued again, the stale ones are compacted away.
+    order: VecDeque<(CacheKey, Instant)>,
+}
+
+impl Entries {
+    fn insert(&mut self, key: CacheKey, data: Vec<u8>, max_entries: usize) {
+        if max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        if !self.replies.contains_key(&key) {
+            while self.replies.len() >= max_entries {
+                self.evict_oldest();
+            }
+        }
+        self.order.push_back((key.clone(), now));
+        self.replies.insert(key, (now, data));
+        if self.order.len() > 2 * self.replies.len() {
+            let replies = &self.replies;
+            self.order
+                .retain(|(key, cached_at)| replies.get(key).map(|e| e.0) == Some(*cached_at));
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        while let Some((key, cached_at)) = self.order.pop_front() {
+            // skips the keys which are cached again after being queued
+            if self.replies.get(&key).map(|e| e.0) == Some(cached_at) {
+                self.replies.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+/// caches the replies of idempotent methods, so repeated requests are served without
+/// invoking the handlers. Error replies are not cached.
+///
+/// The replies are keyed by the method, the caller, which is the IP of its connection, the
+/// metadata, such as the auth token, and the serialized args, so callers don't get the
+/// replies of others. The metadata which differs by call, such as the request id and the
+/// deadline, is left out.
+///
+/// It should be added before plugins which transform payloads, such as `EncryptionPlugin`,
+/// so the cached replies are the plain ones.
+#[derive(Debug)]
+pub struct ResponseCachePlugin {
+    max_entries: usize,
+    ttls: HashMap<String, Duration>,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCachePlugin {
+    /// caches at most `max_entries` replies, the oldest one is evicted first.
+    pub fn new(max_entries: usize) -> Self {
+        ResponseCachePlugin {
+            max_entries,
+            ttls: HashMap::new(),
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// caches the replies of the method for `ttl`. Only idempotent methods should be cached.
+    pub fn cache_method(&mut self, service_path: &str, service_method: &str, ttl: Duration) {
+        self.ttls
+            .insert(format!("{}.{}", service_path, service_method), ttl);
+    }
+
+    /// returns the numbers of requests served from the cache and of requests of cached methods
+    /// which are not.
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    // returns the key and the ttl of a request of a cached method.
+    fn key(&self, req: &Message) -> Option<(CacheKey, Duration)> {
+        if req.is_oneway() {
+            return None;
+        }
+        let method = format!("{}.{}", req.service_path, req.service_method);
+        let ttl = *self.ttls.get(&method)?;
+        let st = req.get_serialize_type()? as u8;
+
+        let metadata = req.metadata.borrow();
+        let caller = metadata
+            .remote_conn_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+        let mut pairs: Vec<(&String, &String)> = metadata
+            .iter()
+            .filter(|(k, _)| !UNKEYED_METADATA.contains(&k.as_str()))
+            .collect();
+        pairs.sort();
+        let mut key = Vec::with_capacity(req.payload.len());
+        push_item(&mut key, caller.as_bytes());
+        key.extend_from_slice(&(pairs.len() as u32).to_be_bytes());
+        for (k, v) in pairs {
+            push_item(&mut key, k.as_bytes());
+            push_item(&mut key, v.as_bytes());
+        }
+        key.extend_from_slice(&req.payload);
+        Some(((method, st, key), ttl))
+    }
+}
+
+impl MessagePlugin for ResponseCachePlugin {
+    fn intercept_request(&self, req: &Message) -> Option<Vec<u8>> {
+        let (key, ttl) = self.key(req)?;
+        let entries = self.entries.lock().unwrap();
+        match entries.replies.get(&key) {
+            Some((cached_at, data)) if cached_at.elapsed() < ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(data.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        if let Some(MessageStatusType::Error) = res.get_message_status_type() {
+            return Ok(());
+        }
+        let (key, ttl) = match self.key(req) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        // the reply is served from the cache
+        if let Some((cached_at, _)) = entries.replies.get(&key) {
+            if cached_at.elapsed() < ttl {
+                return Ok(());
+            }
+        }
+        entries.insert(key, res.payload.to_vec(), self.max_entries);
+        Ok(())
+    }
+}
+
+// appends an item of a key prefixed by its length.
+fn push_item(key: &mut Vec<u8>, item: &[u8]) {
+    key.extend_from_slice(&(item.len() as u32).to_be_bytes());
+    key.extend_from_slice(item);
+}
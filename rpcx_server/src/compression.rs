@@ -0,0 +1,56 @@
+use std::{io::Read, sync::Arc};
+
+use bytes::Bytes;
+use rpcx_protocol::*;
+
+use super::{write_msg, ConnWriter, Server};
+
+/// the read half of a connection, wrapped by a decoder once the stream compression is
+/// negotiated.
+pub(crate) type ConnReader = Box<dyn Read + Send>;
+
+impl Server {
+    /// accepts the compression of the whole stream of connections whose clients ask for it
+    /// with the same dictionary, see `Client::set_stream_compression`. The clients which
+    /// don't ask for it are served as before.
+    ///
+    /// It applies to the connections accepted from now on.
+    pub fn enable_stream_compression(&mut self, compression: StreamCompression) {
+        self.stream_compression = Some(Arc::new(compression));
+    }
+}
+
+/// replies the request of `_reflection.StreamCompression`, which is only accepted as the
+/// first request of a connection. If it is accepted, the messages written from now on are
+/// compressed and the compression the connection must be read with is returned.
+pub(crate) fn negotiate(
+    compression: &Option<Arc<StreamCompression>>,
+    first: bool,
+    writer: &Arc<ConnWriter>,
+    msg: &Message,
+) -> Result<Option<Arc<StreamCompression>>> {
+    let mut reply = msg.get_reply().unwrap();
+    let rt = match compression {
+        Some(_) if !first => Err(Error::new(
+            ErrorKind::Protocol,
+            "stream compression must be negotiated by the first request",
+        )),
+        Some(compression) => compression.accept(msg).map(|_| compression),
+        None => Err(Error::new(
+            ErrorKind::Server,
+            "stream compression is not enabled",
+        )),
+    };
+    match rt {
+        Ok(compression) => {
+            reply.payload = Bytes::from(STREAM_COMPRESSION_ZSTD);
+            writer.start_compression(&reply, compression)?;
+            Ok(Some(compression.clone()))
+        }
+        Err(err) => {
+            err.set_reply(&mut reply);
+            write_msg(writer, &reply)?;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,193 @@
+#[cfg(feature = "etcd-registry")]
+use super::EtcdRegister;
+use super::{Quota, RateLimitPlugin, Server, DRAIN_DELAY};
+use rpcx_protocol::*;
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path, time::Duration};
+
+/// the options of a server which can be read from a configuration file by
+/// `Server::from_config`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub addr: String,
+    /// the number of worker threads of a connection, twice the cores if it is 0.
+    pub thread_number: u32,
+    /// the requests waiting for a worker beyond it are rejected as busy, 0 is unbounded.
+    pub max_queued_requests: usize,
+    /// closes the connections which read nothing for it, 0 keeps them.
+    pub idle_timeout_ms: u64,
+    /// the version published with the metadata of the services.
+    pub version: String,
+    /// registers the services to etcd if it is set.
+    pub registry: Option<RegistryConfig>,
+    /// more etcd registries the services are registered to as well, such as the new cluster
+    /// of a migration.
+    pub registries: Vec<RegistryConfig>,
+    pub tls: Option<TlsConfig>,
+    /// exports the spans of requests to a Zipkin or Jaeger collector if it is set.
+    pub tracing: Option<TracingConfig>,
+    /// limits the rate of requests by a `RateLimitPlugin` if it is set.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// forwards the requests for services which are not registered to these rpcx servers,
+    /// see `Server::forward_to`.
+    pub upstreams: Vec<String>,
+    /// notifies systemd of the readiness and the liveness of the server, see
+    /// `Server::enable_sd_notify`.
+    pub sd_notify: bool,
+    /// how long `Server::drain` waits for the registries to propagate the removal of the
+    /// server.
+    pub drain_delay_ms: u64,
+}
+
+/// the quotas of a `RateLimitPlugin`, the default one and the overrides of peer IPs.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub qps: f64,
+    pub burst: u32,
+    pub quotas: HashMap<String, Quota>,
+}
+
+impl RateLimitConfig {
+    /// returns the default quota.
+    pub fn quota(&self) -> Quota {
+        Quota {
+            qps: self.qps,
+            burst: self.burst,
+        }
+    }
+}
+
+/// the collector the spans of requests are exported to, see `TracingPlugin`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// the url of the Zipkin v2 API, such as `http://127.0.0.1:9411/api/v2/spans`.
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addr: "0.0.0.0:8972".to_owned(),
+            thread_number: 0,
+            max_queued_requests: 0,
+            idle_timeout_ms: 0,
+            version: String::new(),
+            registry: None,
+            registries: Vec::new(),
+            tls: None,
+            tracing: None,
+            rate_limit: None,
+            upstreams: Vec::new(),
+            sd_notify: false,
+            drain_delay_ms: DRAIN_DELAY.as_millis() as u64,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// overrides the config by environment variables: `RPCX_ADDR`, `RPCX_THREAD_NUMBER`,
+    /// `RPCX_SERVICE_VERSION` and the registry variables of `RegistryConfig::apply_env`.
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Some(addr) = env_var("RPCX_ADDR")? {
+            self.addr = addr;
+        }
+        if let Some(thread_number) = env_var("RPCX_THREAD_NUMBER")? {
+            self.thread_number = thread_number;
+        }
+        if let Some(version) = env_var("RPCX_SERVICE_VERSION")? {
+            self.version = version;
+        }
+        RegistryConfig::apply_env(&mut self.registry)
+    }
+}
+
+impl Server {
+    /// creates a server from a TOML or YAML file of `ServerConfig`.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config: ServerConfig = load_config(path)?;
+        Self::with_config(&config)
+    }
+
+    /// creates a server from the config, overridden by environment variables.
+    pub fn with_config(config: &ServerConfig) -> Result<Self> {
+        let mut config = config.clone();
+        config.apply_env()?;
+
+        let mut server = Server::new(config.addr.clone(), config.thread_number);
+        server.set_max_queued_requests(config.max_queued_requests);
+        if config.idle_timeout_ms > 0 {
+            server.set_idle_timeout(Duration::from_millis(config.idle_timeout_ms));
+        }
+        if !config.version.is_empty() {
+            server.set_version(&config.version);
+        }
+        if let Some(rate_limit) = &config.rate_limit {
+            let mut limiter = RateLimitPlugin::new(rate_limit.quota());
+            for (identity, quota) in &rate_limit.quotas {
+                limiter.set_quota(identity, *quota);
+            }
+            server.add_message_plugin(Box::new(limiter));
+        }
+        if let Some(tls) = &config.tls {
+            #[cfg(feature = "tls")]
+            server.enable_tls(tls)?;
+            #[cfg(not(feature = "tls"))]
+            return Err(not_enabled("tls", &tls.cert_file));
+        }
+        if let Some(tracing) = &config.tracing {
+            #[cfg(feature = "tracing")]
+            server.enable_tracing(&tracing.endpoint, &tracing.service_name)?;
+            #[cfg(not(feature = "tracing"))]
+            return Err(not_enabled("tracing", &tracing.endpoint));
+        }
+        if config.sd_notify {
+            server.enable_sd_notify();
+        }
+        server.set_drain_delay(Duration::from_millis(config.drain_delay_ms));
+        if !config.upstreams.is_empty() {
+            let upstreams: Vec<&str> = config.upstreams.iter().map(String::as_str).collect();
+            server.forward_to(&upstreams)?;
+        }
+        #[cfg(feature = "etcd-registry")]
+        for registry in config.registry.iter().chain(&config.registries) {
+            server.add_register_plugin(Box::new(etcd_register(registry, &config.addr)?));
+        }
+        #[cfg(not(feature = "etcd-registry"))]
+        if let Some(registry) = config.registry.iter().chain(&config.registries).next() {
+            return Err(not_enabled("etcd-registry", &registry.base_path));
+        }
+        Ok(server)
+    }
+}
+
+// the error of a config which needs a feature the server is built without.
+#[cfg(not(all(feature = "etcd-registry", feature = "tracing", feature = "tls")))]
+fn not_enabled(feature: &str, setting: &str) -> Error {
+    Error::new(
+        ErrorKind::Config,
+        format!("{} needs the {} feature of rpcx_server", setting, feature),
+    )
+}
+
+// creates the plugin which registers the services of the server at `addr` to etcd.
+#[cfg(feature = "etcd-registry")]
+fn etcd_register(registry: &RegistryConfig, addr: &str) -> Result<EtcdRegister> {
+    let addrs: Vec<&str> = registry.addrs.iter().map(String::as_str).collect();
+    let client = etcd::Client::new(&addrs, None)
+        .map_err(|err| Error::new(ErrorKind::Registry, format!("{:?}", err)))?;
+    let service_addr = if registry.service_addr.is_empty() {
+        format!("tcp@{}", addr)
+    } else {
+        registry.service_addr.clone()
+    };
+    Ok(EtcdRegister::new(
+        client,
+        registry.base_path.clone(),
+        service_addr,
+        Duration::from_secs(registry.update_interval_secs),
+    ))
+}
@@ -0,0 +1,90 @@
+use rpcx_protocol::*;
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Context>> = RefCell::new(None);
+}
+
+/// the context of the request a handler is serving, returned by `Context::current`.
+///
+/// ```no_run
+/// use rpcx_protocol::*;
+/// use rpcx_server::Context;
+/// use std::time::Duration;
+///
+/// fn report(args: &[u8], _: SerializeType) -> Result<Vec<u8>> {
+///     // skips the work the caller won't wait for
+///     if Context::current().remaining() == Some(Duration::from_secs(0)) {
+///         return Err(Error::new(ErrorKind::Timeout, "deadline exceeded"));
+///     }
+///     Ok(args.to_vec())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    service_path: String,
+    service_method: String,
+    deadline: Option<Instant>,
+    metadata: Metadata,
+}
+
+impl Context {
+    /// returns the context of the request served by this thread, an empty context outside
+    /// handlers.
+    pub fn current() -> Context {
+        CURRENT.with(|c| c.borrow().clone().unwrap_or_default())
+    }
+
+    /// returns the service path of the request.
+    pub fn service_path(&self) -> &str {
+        &self.service_path
+    }
+
+    /// returns the method of the request, as it is registered, such as `Mul@2`.
+    pub fn service_method(&self) -> &str {
+        &self.service_method
+    }
+
+    /// returns the deadline propagated by the client, counted from when the request was
+    /// received. `None` if the client set no deadline.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// returns the time left to the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        let deadline = self.deadline?;
+        let now = Instant::now();
+        Some(if deadline > now {
+            deadline - now
+        } else {
+            Duration::from_secs(0)
+        })
+    }
+
+    /// returns the metadata of the request.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub(crate) fn from_request(msg: &Message, received: Instant) -> Self {
+        let metadata = msg.metadata.borrow().clone();
+        Context {
+            service_path: msg.service_path.clone(),
+            service_method: msg.service_method.clone(),
+            deadline: get_deadline(&metadata).map(|timeout| received + timeout),
+            metadata,
+        }
+    }
+}
+
+/// runs `f` with `ctx` as the current context of this thread.
+pub(crate) fn scope<T, F: FnOnce() -> T>(ctx: Context, f: F) -> T {
+    let prev = CURRENT.with(|c| c.replace(Some(ctx)));
+    let rt = f();
+    CURRENT.with(|c| c.replace(prev));
+    rt
+}
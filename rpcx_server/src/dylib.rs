@@ -0,0 +1,245 @@
+use super::{Context, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr, CString},
+    os::raw::{c_char, c_int},
+    path::Path,
+    ptr, slice,
+    sync::RwLock,
+};
+
+/// the symbol a service library exports to register its services, of type
+/// `DylibRegisterFn`.
+pub const DYLIB_REGISTER_SYMBOL: &str = "rpcx_register_services";
+/// the symbol a service library exports to free the replies of its handlers, of type
+/// `DylibFreeFn`.
+pub const DYLIB_FREE_SYMBOL: &str = "rpcx_free_reply";
+
+/// a handler of a service library. It is passed the payload and the serialize type of the
+/// request and sets `reply` to a buffer allocated by the library, which is freed by its
+/// `DylibFreeFn`. It returns 0 on success, otherwise the reply is the message of the error.
+pub type DylibHandlerFn = unsafe extern "C" fn(
+    args: *const u8,
+    args_len: usize,
+    serialize_type: c_int,
+    reply: *mut *mut u8,
+    reply_len: *mut usize,
+) -> c_int;
+
+/// frees a reply of the handlers of a service library.
+pub type DylibFreeFn = unsafe extern "C" fn(reply: *mut u8, reply_len: usize);
+
+/// adds a handler of the service method, it is passed to `DylibRegisterFn`.
+pub type DylibAddServiceFn = unsafe extern "C" fn(
+    registrar: *mut c_void,
+    service_path: *const c_char,
+    service_method: *const c_char,
+    handler: DylibHandlerFn,
+);
+
+/// registers the services of a library by calling `add_service` with `registrar` for each
+/// method. It returns 0 on success.
+pub type DylibRegisterFn =
+    unsafe extern "C" fn(registrar: *mut c_void, add_service: DylibAddServiceFn) -> c_int;
+
+#[derive(Clone, Copy)]
+struct DylibHandler {
+    handler: DylibHandlerFn,
+    free: DylibFreeFn,
+}
+
+impl DylibHandler {
+    fn call(&self, args: &[u8], st: SerializeType) -> Result<Vec<u8>> {
+        let mut reply: *mut u8 = ptr::null_mut();
+        let mut reply_len = 0;
+        let rt = unsafe {
+            (self.handler)(
+                args.as_ptr(),
+                args.len(),
+                st as c_int,
+                &mut reply,
+                &mut reply_len,
+            )
+        };
+        let data = if reply.is_null() {
+            Vec::new()
+        } else {
+            let data = unsafe { slice::from_raw_parts(reply, reply_len) }.to_vec();
+            unsafe { (self.free)(reply, reply_len) };
+            data
+        };
+        if rt != 0 {
+            return Err(Error::new(
+                ErrorKind::Service,
+                String::from_utf8_lossy(&data).into_owned(),
+            ));
+        }
+        Ok(data)
+    }
+}
+
+// the handlers of the methods loaded from libraries, keyed by `service_path.service_method`.
+// They are kept for the process like the libraries, since the functions of `services` can't
+// carry them.
+static HANDLERS: RwLock<Option<HashMap<String, DylibHandler>>> = RwLock::new(None);
+
+// the function methods loaded from libraries are registered by, which calls the handler of the
+// method of the request.
+fn call_dylib(args: &[u8], st: SerializeType) -> Result<Vec<u8>> {
+    let ctx = Context::current();
+    let key = format!("{}.{}", ctx.service_path(), ctx.service_method());
+    let handler = HANDLERS
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|handlers| handlers.get(&key).copied());
+    match handler {
+        Some(handler) => handler.call(args, st),
+        None => Err(Error::new(
+            ErrorKind::Server,
+            format!("service {} not found", key),
+        )),
+    }
+}
+
+// the state `add_service` is called with.
+struct Registrar {
+    free: DylibFreeFn,
+    added: Vec<(String, String, DylibHandler)>,
+}
+
+unsafe extern "C" fn add_service(
+    registrar: *mut c_void,
+    service_path: *const c_char,
+    service_method: *const c_char,
+    handler: DylibHandlerFn,
+) {
+    let registrar = &mut *(registrar as *mut Registrar);
+    if service_path.is_null() || service_method.is_null() {
+        return;
+    }
+    let free = registrar.free;
+    registrar.added.push((
+        CStr::from_ptr(service_path).to_string_lossy().into_owned(),
+        CStr::from_ptr(service_method)
+            .to_string_lossy()
+            .into_owned(),
+        DylibHandler { handler, free },
+    ));
+}
+
+// returns the message of the latest error of the dynamic linker.
+fn dl_error() -> String {
+    let err = unsafe { libc::dlerror() };
+    if err.is_null() {
+        return "unknown error".to_owned();
+    }
+    unsafe { CStr::from_ptr(err) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl Server {
+    /// loads the services of the dynamic library at `path`, which exports
+    /// `DYLIB_REGISTER_SYMBOL` and `DYLIB_FREE_SYMBOL`, and returns the methods it
+    /// registered, such as `Arith.Mul`. So handlers can be added to a running server, or
+    /// upgraded by loading a new version of a library, without rebuilding it.
+    ///
+    /// The dynamic linker loads a library once per process, so each version must be built to
+    /// a distinct path, such as `libservices.2.so`. Loading a library which is loaded already
+    /// fails with `ErrorKind::Config`, even after its file is replaced.
+    ///
+    /// The loaded methods are registered like the functions of `try_register_fn`, by the
+    /// duplicate policy of the server and to its registries, so with the default policy they
+    /// replace the methods of the same names. The handlers are kept for the process, a method
+    /// loaded by several servers of it runs the handler loaded last. Libraries are not
+    /// unloaded, since calls may still be running in them.
+    ///
+    /// # Safety
+    ///
+    /// The library runs its initializers when it is loaded, and its exported functions must
+    /// have the types of `DylibRegisterFn` and `DylibFreeFn`, with the contract of
+    /// `register_dylib_services`. Nothing of it can be checked.
+    pub unsafe fn load_services<P: AsRef<Path>>(&self, path: P) -> Result<Vec<String>> {
+        let path = path.as_ref();
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|err| Error::new(ErrorKind::Config, err))?;
+        let loaded = libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_NOLOAD);
+        if !loaded.is_null() {
+            libc::dlclose(loaded);
+            return Err(Error::new(
+                ErrorKind::Config,
+                format!("{} is loaded already", path.display()),
+            ));
+        }
+        let lib = libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if lib.is_null() {
+            return Err(Error::new(
+                ErrorKind::Config,
+                format!("failed to load {}: {}", path.display(), dl_error()),
+            ));
+        }
+
+        let symbol = |name: &str| {
+            let c_name = CString::new(name).unwrap();
+            let sym = libc::dlsym(lib, c_name.as_ptr());
+            if sym.is_null() {
+                return Err(Error::new(
+                    ErrorKind::Config,
+                    format!("{} doesn't export {}", path.display(), name),
+                ));
+            }
+            Ok(sym)
+        };
+        let register = symbol(DYLIB_REGISTER_SYMBOL)?;
+        let free = symbol(DYLIB_FREE_SYMBOL)?;
+        self.register_dylib_services(
+            std::mem::transmute::<*mut c_void, DylibRegisterFn>(register),
+            std::mem::transmute::<*mut c_void, DylibFreeFn>(free),
+        )
+    }
+
+    /// registers the services of a library by its exported functions, see `load_services`.
+    ///
+    /// # Safety
+    ///
+    /// `register` must call `add_service` with valid C strings and the handlers and `free`
+    /// must stay valid as long as the process.
+    pub unsafe fn register_dylib_services(
+        &self,
+        register: DylibRegisterFn,
+        free: DylibFreeFn,
+    ) -> Result<Vec<String>> {
+        let mut registrar = Registrar {
+            free,
+            added: Vec::new(),
+        };
+        let rt = register(&mut registrar as *mut Registrar as *mut c_void, add_service);
+        if rt != 0 {
+            return Err(Error::new(
+                ErrorKind::Config,
+                format!("failed to register the services of the library: {}", rt),
+            ));
+        }
+
+        let mut keys = Vec::with_capacity(registrar.added.len());
+        for (service_path, service_method, handler) in registrar.added {
+            let service_path = ServicePath::from(service_path);
+            let service_method = self.register_service(
+                service_path.clone(),
+                ServiceMethod::from(service_method),
+                String::new(),
+                call_dylib,
+            )?;
+            let key = format!("{}.{}", service_path, service_method);
+            HANDLERS
+                .write()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(key.clone(), handler);
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
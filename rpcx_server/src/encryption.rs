@@ -0,0 +1,54 @@
+use super::MessagePlugin;
+use rpcx_protocol::*;
+use std::collections::HashMap;
+
+/// decrypts the requests and encrypts the responses of services with a pre-shared key.
+///
+/// Requests to these services must be encrypted by the client, see
+/// `Client::set_encryption_key`. Other services are not affected.
+#[derive(Debug, Default)]
+pub struct EncryptionPlugin {
+    ciphers: HashMap<String, PayloadCipher>,
+}
+
+impl EncryptionPlugin {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// sets the AES-256 key of the service.
+    pub fn add_key(&mut self, service_path: &str, key: &[u8]) -> Result<()> {
+        let cipher = PayloadCipher::new(key)?;
+        self.ciphers.insert(service_path.to_owned(), cipher);
+        Ok(())
+    }
+}
+
+impl MessagePlugin for EncryptionPlugin {
+    fn post_read_request(&self, req: &mut Message) -> Result<()> {
+        match self.ciphers.get(&req.service_path) {
+            Some(cipher) => cipher.open(req).map_err(|err| {
+                Error::new(
+                    ErrorKind::Client,
+                    format!("service {} requires encryption: {}", req.service_path, err),
+                )
+            }),
+            None if is_encrypted(req) => Err(Error::new(
+                ErrorKind::Server,
+                format!("no encryption key for service {}", req.service_path),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        // error replies carry no payload
+        if let Some(MessageStatusType::Error) = res.get_message_status_type() {
+            return Ok(());
+        }
+        match self.ciphers.get(&req.service_path) {
+            Some(cipher) => cipher.seal(res),
+            None => Ok(()),
+        }
+    }
+}
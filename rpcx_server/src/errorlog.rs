@@ -0,0 +1,30 @@
+use super::{MessagePlugin, Server};
+use rpcx_protocol::*;
+
+// logs the errors replied to requests with the ids of the requests.
+struct ErrorLogPlugin;
+
+impl MessagePlugin for ErrorLogPlugin {
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        if let Some(err) = Error::from_reply(res) {
+            eprintln!(
+                "request {} to {}.{} failed: {}",
+                req.get_request_id().unwrap_or_default(),
+                req.service_path,
+                req.service_method,
+                err
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Server {
+    /// logs the errors replied to requests with the ids of the requests to stderr, so they
+    /// can be matched with the logs of the clients.
+    ///
+    /// The errors set by the message plugins added after it are not logged.
+    pub fn enable_error_log(&mut self) {
+        self.add_message_plugin(Box::new(ErrorLogPlugin));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use futures::{sync::oneshot, Future, Stream};
+use hyper::{
+    client::HttpConnector,
+    header::{ACCEPT, CONTENT_TYPE},
+    Body, Client, Method, Request, StatusCode,
+};
+use rpcx_protocol::*;
+use tokio::runtime::{Runtime, TaskExecutor};
+
+use super::{RegisterPlugin, RpcxFn};
+
+/// registers services to Netflix Eureka, each service is an application and the server is an
+/// instance of it. The instances are renewed every `renew_interval`, Eureka expires them after
+/// 90 seconds without renewal by default.
+pub struct EurekaRegister {
+    // runs the connections of the client and the requests, shared with the renewals
+    runtime: Arc<Runtime>,
+    client: Client<HttpConnector>,
+    eureka_url: String,
+    service_addr: String,
+    instances: Arc<RwLock<HashMap<String, EurekaInstance>>>,
+}
+
+impl EurekaRegister {
+    /// `eureka_url` is the url of the REST API, for example `http://127.0.0.1:8761/eureka`.
+    pub fn new(eureka_url: String, service_addr: String, renew_interval: Duration) -> Self {
+        let runtime = Arc::new(Runtime::new().unwrap());
+        let client = Client::builder().executor(runtime.executor()).build_http();
+        let instances = Arc::new(RwLock::new(HashMap::new()));
+
+        let runtime_cloned = runtime.clone();
+        let client_cloned = client.clone();
+        let eureka_url_cloned = eureka_url.trim_end_matches('/').to_owned();
+        let instances_cloned = instances.clone();
+        thread::spawn(move || loop {
+            thread::sleep(renew_interval);
+            Self::renew(
+                &runtime_cloned.executor(),
+                &client_cloned,
+                &eureka_url_cloned,
+                &instances_cloned,
+            );
+        });
+
+        EurekaRegister {
+            runtime,
+            client,
+            eureka_url: eureka_url.trim_end_matches('/').to_owned(),
+            service_addr,
+            instances,
+        }
+    }
+
+    fn renew(
+        executor: &TaskExecutor,
+        client: &Client<HttpConnector>,
+        eureka_url: &str,
+        instances: &RwLock<HashMap<String, EurekaInstance>>,
+    ) {
+        let instances = instances.read().unwrap();
+        for instance in instances.values() {
+            let uri = format!(
+                "{}/apps/{}/{}",
+                eureka_url, instance.app, instance.instance_id
+            );
+            let rt = match send(executor, client, Method::PUT, &uri, Vec::new()) {
+                // registers again the instances Eureka has expired
+                Ok(StatusCode::NOT_FOUND) => Self::register(executor, client, eureka_url, instance),
+                Ok(status) => check_status(&uri, status),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = rt {
+                eprintln!("failed to renew {}. err: {}", instance.app, err);
+            }
+        }
+    }
+
+    fn register(
+        executor: &TaskExecutor,
+        client: &Client<HttpConnector>,
+        eureka_url: &str,
+        instance: &EurekaInstance,
+    ) -> Result<()> {
+        let uri = format!("{}/apps/{}", eureka_url, instance.app);
+        let status = send(executor, client, Method::POST, &uri, instance.to_json()?)?;
+        check_status(&uri, status)
+    }
+}
+
+// sends a request to Eureka on the runtime and returns the status of the response.
+fn send(
+    executor: &TaskExecutor,
+    client: &Client<HttpConnector>,
+    method: Method,
+    uri: &str,
+    body: Vec<u8>,
+) -> Result<StatusCode> {
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .header(ACCEPT, "application/json")
+        .body(Body::from(body))
+        .map_err(|err| Error::new(ErrorKind::Registry, err))?;
+    let op = client.request(req).and_then(|res| {
+        let status = res.status();
+        res.into_body().concat2().map(move |_| status)
+    });
+    oneshot::spawn(op, executor)
+        .wait()
+        .map_err(|err| Error::new(ErrorKind::Registry, err))
+}
+
+fn check_status(uri: &str, status: StatusCode) -> Result<()> {
+    if !status.is_success() {
+        return Err(Error::new(
+            ErrorKind::Registry,
+            format!("failed to request {}: {}", uri, status),
+        ));
+    }
+    Ok(())
+}
+
+impl RegisterPlugin for EurekaRegister {
+    fn register_fn(&mut self, service_path: &str, _: &str, meta: String, _: RpcxFn) -> Result<()> {
+        if self.instances.read().unwrap().contains_key(service_path) {
+            return Ok(());
+        }
+        let instance = EurekaInstance::new(service_path, &self.service_addr, &meta)?;
+        Self::register(
+            &self.runtime.executor(),
+            &self.client,
+            &self.eureka_url,
+            &instance,
+        )?;
+        println!("succeed to register: {}", instance.instance_id);
+
+        self.instances
+            .write()
+            .unwrap()
+            .insert(service_path.to_owned(), instance);
+        Ok(())
+    }
+
+    fn update_meta(&mut self, service_path: &str, meta: String) -> Result<()> {
+        if !self.instances.read().unwrap().contains_key(service_path) {
+            return Ok(());
+        }
+        // registering an instance again replaces it
+        let instance = EurekaInstance::new(service_path, &self.service_addr, &meta)?;
+        Self::register(
+            &self.runtime.executor(),
+            &self.client,
+            &self.eureka_url,
+            &instance,
+        )?;
+        self.instances
+            .write()
+            .unwrap()
+            .insert(service_path.to_owned(), instance);
+        Ok(())
+    }
+
+    fn unregister(&mut self, service_path: &str) -> Result<()> {
+        // stops renewing it first
+        let instance = match self.instances.write().unwrap().remove(service_path) {
+            Some(instance) => instance,
+            None => return Ok(()),
+        };
+        let uri = format!(
+            "{}/apps/{}/{}",
+            self.eureka_url, instance.app, instance.instance_id
+        );
+        let executor = self.runtime.executor();
+        match send(&executor, &self.client, Method::DELETE, &uri, Vec::new())? {
+            // Eureka has expired it
+            StatusCode::NOT_FOUND => Ok(()),
+            status => check_status(&uri, status),
+        }
+    }
+}
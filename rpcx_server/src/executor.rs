@@ -0,0 +1,95 @@
+use super::queue::Job;
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// the handler workers of the reactors of `Server::start_reuseport`. Each reactor has a
+/// queue which the connections it accepts push their requests to. A worker runs the jobs of
+/// the queue of its reactor first and steals the oldest jobs of the other queues once it is
+/// empty, so the idle reactors help a busy one instead of waiting.
+pub(crate) struct StealingExecutor {
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    // the jobs in the queues which no worker took yet and whether the executor is closed
+    state: Mutex<(usize, bool)>,
+    ready: Condvar,
+}
+
+impl StealingExecutor {
+    pub(crate) fn new(reactors: usize) -> Self {
+        StealingExecutor {
+            queues: (0..reactors).map(|_| Mutex::new(VecDeque::new())).collect(),
+            state: Mutex::new((0, false)),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// pushes a job to the queue of a reactor. It is run at once by the calling thread if
+    /// the executor is closed.
+    pub(crate) fn execute(&self, reactor: usize, job: Job) {
+        let mut state = self.state.lock().unwrap();
+        if state.1 {
+            drop(state);
+            run(job);
+            return;
+        }
+        self.queues[reactor].lock().unwrap().push_back(job);
+        state.0 += 1;
+        self.ready.notify_one();
+    }
+
+    /// runs the jobs of the reactor and steals the others' until the executor is closed.
+    pub(crate) fn work(&self, reactor: usize) {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                while state.0 == 0 && !state.1 {
+                    state = self.ready.wait(state).unwrap();
+                }
+                if state.0 == 0 {
+                    return;
+                }
+                // one job in the queues is this worker's
+                state.0 -= 1;
+            }
+            let job = loop {
+                if let Some(job) = self.take(reactor) {
+                    break job;
+                }
+            };
+            run(job);
+        }
+    }
+
+    /// stops the workers once they run the jobs which are queued.
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.ready.notify_all();
+    }
+
+    fn take(&self, reactor: usize) -> Option<Job> {
+        let n = self.queues.len();
+        (0..n).find_map(|i| self.queues[(reactor + i) % n].lock().unwrap().pop_front())
+    }
+}
+
+/// the queue of a reactor in its executor, which the connections it accepts push to.
+#[derive(Clone)]
+pub(crate) struct ReactorQueue {
+    pub(crate) executor: Arc<StealingExecutor>,
+    pub(crate) reactor: usize,
+}
+
+impl ReactorQueue {
+    pub(crate) fn execute(&self, job: Job) {
+        self.executor.execute(self.reactor, job);
+    }
+}
+
+// a handler which panics fails its own request only, the worker goes on.
+fn run(job: Job) {
+    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+        eprintln!("a handler panicked");
+    }
+}
@@ -0,0 +1,51 @@
+use super::MessagePlugin;
+pub use rpcx_protocol::Fault;
+use rpcx_protocol::*;
+use std::thread;
+
+/// injects faults into calls at random, so fail modes and timeouts of clients can be exercised.
+///
+/// Each fault of a method is rolled independently, a delay is applied before the call fails or
+/// its response is dropped. A delay sleeps on the worker which handles the request, so it
+/// holds the worker like a slow handler would and the other requests queue up behind it.
+#[derive(Debug, Default)]
+pub struct FaultInjectionPlugin {
+    injector: FaultInjector,
+}
+
+impl FaultInjectionPlugin {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// injects the fault into the calls of the method with the probability in `[0, 1]`. The
+    /// method `*` matches all methods of the service.
+    pub fn add_fault(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        fault: Fault,
+        probability: f64,
+    ) {
+        self.injector
+            .add_fault(service_path, service_method, fault, probability);
+    }
+}
+
+impl MessagePlugin for FaultInjectionPlugin {
+    fn post_read_request(&self, req: &mut Message) -> Result<()> {
+        let mut err = None;
+        for fault in self.injector.roll(&req.service_path, &req.service_method) {
+            match fault {
+                Fault::Delay(delay) => thread::sleep(delay),
+                Fault::DropResponse => req.set_oneway(true),
+                Fault::Error(kind) => err = Some(Error::new(kind, "injected fault")),
+            }
+        }
+
+        match err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
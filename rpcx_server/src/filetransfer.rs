@@ -0,0 +1,282 @@
+use super::{handle_builtin, serialize_type, write_msg, ConnWriter, MessagePlugins, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long a transfer token can be used after it is issued.
+pub const FILE_TRANSFER_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+// the suffix of files which are being uploaded.
+const PARTIAL_SUFFIX: &str = ".part";
+
+enum Transfer {
+    Upload {
+        path: PathBuf,
+        offset: u64,
+        file_size: u64,
+    },
+    Download {
+        path: PathBuf,
+        offset: u64,
+    },
+}
+
+/// stores uploaded files in a directory and serves downloads from it.
+pub(crate) struct FileTransfer {
+    addr: String,
+    dir: PathBuf,
+    tokens: Mutex<HashMap<Vec<u8>, (Transfer, Instant)>>,
+}
+
+impl Server {
+    /// enables the `_filetransfer` service. Files are uploaded to and downloaded from `dir`, the
+    /// data connections are accepted on `addr`, which must be reachable by clients.
+    pub fn enable_file_transfer<P: Into<PathBuf>>(&mut self, addr: &str, dir: P) -> Result<()> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let listener = TcpListener::bind(addr)?;
+        let ft = Arc::new(FileTransfer {
+            addr: listener.local_addr()?.to_string(),
+            dir,
+            tokens: Mutex::new(HashMap::new()),
+        });
+
+        let ft_cloned = ft.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let ft = ft_cloned.clone();
+                        thread::spawn(move || {
+                            if let Err(err) = ft.serve(&stream) {
+                                eprintln!("failed to transfer file: {}", err);
+                            }
+                            let _ = stream.shutdown(Shutdown::Both);
+                        });
+                    }
+                    Err(err) => {
+                        eprintln!("failed to accept file transfer: {}", err);
+                        return;
+                    }
+                }
+            }
+        });
+
+        self.file_transfer = Some(ft);
+        Ok(())
+    }
+}
+
+impl FileTransfer {
+    fn transfer_file(&self, args: &FileTransferArgs) -> Result<FileTransferReply> {
+        let path = self.file_path(&args.file_name)?;
+        // resume from the bytes received by an interrupted upload.
+        let partial = partial_path(&path);
+        let mut offset = fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+        if offset > args.file_size {
+            fs::remove_file(&partial)?;
+            offset = 0;
+        }
+
+        let token = self.issue(Transfer::Upload {
+            path,
+            offset,
+            file_size: args.file_size,
+        })?;
+        Ok(FileTransferReply {
+            token,
+            addr: self.addr.clone(),
+            offset,
+            file_size: args.file_size,
+        })
+    }
+
+    fn download_file(&self, args: &DownloadFileArgs) -> Result<FileTransferReply> {
+        let path = self.file_path(&args.file_name)?;
+        let file_size = fs::metadata(&path)
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::Client,
+                    format!("file {} not found", args.file_name),
+                )
+            })?
+            .len();
+        if args.offset > file_size {
+            return Err(Error::new(
+                ErrorKind::Client,
+                format!(
+                    "offset {} exceeds the size of file {}",
+                    args.offset, args.file_name
+                ),
+            ));
+        }
+
+        let token = self.issue(Transfer::Download {
+            path,
+            offset: args.offset,
+        })?;
+        Ok(FileTransferReply {
+            token,
+            addr: self.addr.clone(),
+            offset: args.offset,
+            file_size,
+        })
+    }
+
+    // file names are plain names in the directory, paths are rejected.
+    fn file_path(&self, file_name: &str) -> Result<PathBuf> {
+        if file_name.is_empty()
+            || file_name == "."
+            || file_name == ".."
+            || file_name.contains('/')
+            || file_name.contains('\\')
+        {
+            return Err(Error::new(
+                ErrorKind::Client,
+                format!("invalid file name {}", file_name),
+            ));
+        }
+        Ok(self.dir.join(file_name))
+    }
+
+    fn issue(&self, transfer: Transfer) -> Result<Vec<u8>> {
+        let mut token = vec![0u8; FILE_TRANSFER_TOKEN_SIZE];
+        File::open("/dev/urandom")?.read_exact(&mut token)?;
+
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, (_, issued)| issued.elapsed() < FILE_TRANSFER_TOKEN_TTL);
+        tokens.insert(token.clone(), (transfer, Instant::now()));
+        Ok(token)
+    }
+
+    // serves a data connection, which starts with the token.
+    fn serve(&self, stream: &TcpStream) -> Result<()> {
+        let mut conn = stream;
+        let mut token = vec![0u8; FILE_TRANSFER_TOKEN_SIZE];
+        conn.read_exact(&mut token)?;
+        let transfer = match self.tokens.lock().unwrap().remove(&token) {
+            Some((transfer, issued)) if issued.elapsed() < FILE_TRANSFER_TOKEN_TTL => transfer,
+            _ => return Err(Error::new(ErrorKind::Client, "invalid file transfer token")),
+        };
+
+        match transfer {
+            Transfer::Upload {
+                path,
+                offset,
+                file_size,
+            } => {
+                // every transfer writes a file of its own, so concurrent uploads of a name don't
+                // mix their bytes. A resumed upload takes over the bytes received before.
+                let partial = partial_path(&path);
+                let temp = temp_path(&partial, &token);
+                if offset > 0 {
+                    fs::rename(&partial, &temp).map_err(|_| {
+                        Error::new(
+                            ErrorKind::Client,
+                            format!("upload of {} is resumed already", path.display()),
+                        )
+                    })?;
+                }
+                let mut file = OpenOptions::new().create(true).append(true).open(&temp)?;
+                let written = file.metadata()?.len();
+                let copied = if written == offset {
+                    io::copy(&mut conn.take(file_size - offset), &mut file)
+                        .and_then(|_| file.sync_all())
+                        .and_then(|_| file.metadata())
+                        .map(|m| m.len() == file_size)
+                } else {
+                    Ok(false)
+                };
+                if let Ok(true) = copied {
+                    fs::rename(&temp, &path)?;
+                    // acknowledge the complete file
+                    conn.write_all(&[0])?;
+                    return Ok(());
+                }
+
+                // keeps the bytes received for the upload resuming it
+                if written == offset {
+                    fs::rename(&temp, &partial)?;
+                } else {
+                    fs::remove_file(&temp)?;
+                }
+                copied?;
+                return Err(Error::new(
+                    ErrorKind::Network,
+                    format!("upload of {} is interrupted", path.display()),
+                ));
+            }
+            Transfer::Download { path, offset } => {
+                let mut file = File::open(&path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                io::copy(&mut file, &mut conn)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    path.with_file_name(name)
+}
+
+// the file of a transfer, named after its token.
+fn temp_path(partial: &Path, token: &[u8]) -> PathBuf {
+    let mut name = partial.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    for b in &token[..8] {
+        name.push(format!("{:02x}", b));
+    }
+    partial.with_file_name(name)
+}
+
+/// handles a request of the `_filetransfer` service read from the connection of `peer_addr` on
+/// `local_addr`. The request must pass the `post_read_request` hook of the plugins first, and
+/// its reply their `pre_write_response` hook.
+pub(crate) fn handle_msg(
+    file_transfer: &Option<Arc<FileTransfer>>,
+    message_plugins: &MessagePlugins,
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    writer: &Arc<ConnWriter>,
+    mut msg: Message,
+) {
+    let reply_msg = handle_builtin(message_plugins, &mut msg, peer_addr, local_addr, |msg| {
+        let st = serialize_type(msg)?;
+        let ft = file_transfer
+            .as_ref()
+            .ok_or_else(|| Error::new(ErrorKind::Server, "file transfer is not enabled"))?;
+        match msg.service_method.as_str() {
+            FILE_TRANSFER_UPLOAD => {
+                let mut args = FileTransferArgs::default();
+                args.from_slice(st, &msg.payload)
+                    .and_then(|_| ft.transfer_file(&args))
+                    .and_then(|reply| reply.into_bytes(st))
+            }
+            FILE_TRANSFER_DOWNLOAD => {
+                let mut args = DownloadFileArgs::default();
+                args.from_slice(st, &msg.payload)
+                    .and_then(|_| ft.download_file(&args))
+                    .and_then(|reply| reply.into_bytes(st))
+            }
+            method => Err(Error::new(
+                ErrorKind::Server,
+                format!("service {}.{} not found", FILE_TRANSFER_SERVICE, method),
+            )),
+        }
+    });
+
+    if !msg.is_oneway() {
+        let _ = write_msg(writer, &reply_msg);
+    }
+}
@@ -0,0 +1,210 @@
+use super::{MessagePlugin, RpcxFn, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+/// how long a forwarded request waits for the reply of the upstream if it has no deadline.
+pub const FORWARD_TIMEOUT: Duration = Duration::from_secs(30);
+
+const FORWARD_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// an upstream server and its idle connections.
+#[derive(Debug)]
+struct Upstream {
+    addr: SocketAddr,
+    idle: Mutex<Vec<TcpStream>>,
+}
+
+impl Upstream {
+    // sends the request and waits for its reply on an idle connection, or on a new one if
+    // there is none or the idle one was closed by the upstream. It fails with
+    // `ErrorKind::Network` if the upstream can't be reached, when the request is not sent.
+    // A request which is written is not sent again, the upstream may have handled it.
+    fn call(&self, req: &Message, data: &[u8]) -> Result<Message> {
+        let idle = self.idle.lock().unwrap().pop();
+        if let Some(mut conn) = idle.filter(|conn| !is_closed(conn)) {
+            match exchange(&mut conn, req, data) {
+                Ok(reply) => {
+                    self.idle.lock().unwrap().push(conn);
+                    return Ok(reply);
+                }
+                Err((true, err)) => return Err(sent_error(err)),
+                // the upstream closed the connection before the request was written
+                Err((false, _)) => {}
+            }
+        }
+        let mut conn = TcpStream::connect_timeout(&self.addr, FORWARD_CONNECT_TIMEOUT)
+            .map_err(|err| Error::new(ErrorKind::Network, err))?;
+        conn.set_nodelay(true)?;
+        match exchange(&mut conn, req, data) {
+            Ok(reply) => {
+                self.idle.lock().unwrap().push(conn);
+                Ok(reply)
+            }
+            Err((true, err)) => Err(sent_error(err)),
+            Err((false, err)) => Err(Error::new(ErrorKind::Network, err.to_string())),
+        }
+    }
+}
+
+// returns whether the upstream closed the idle connection, which is at EOF or failed.
+fn is_closed(conn: &TcpStream) -> bool {
+    if conn.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let mut buf = [0u8; 1];
+    let closed = match conn.peek(&mut buf) {
+        Ok(n) => n == 0,
+        Err(err) => err.kind() != io::ErrorKind::WouldBlock,
+    };
+    conn.set_nonblocking(false).is_err() || closed
+}
+
+// writes the request to the connection and reads its reply. The error tells whether the
+// request was written.
+fn exchange(
+    conn: &mut TcpStream,
+    req: &Message,
+    data: &[u8],
+) -> std::result::Result<Message, (bool, Error)> {
+    conn.write_all(data)
+        .map_err(|err| (false, Error::from(err)))?;
+    read_reply(conn, req).map_err(|err| (true, err))
+}
+
+fn read_reply(conn: &mut TcpStream, req: &Message) -> Result<Message> {
+    if req.is_oneway() {
+        return Ok(req.get_reply()?);
+    }
+    let timeout = get_deadline(&req.metadata.borrow()).unwrap_or(FORWARD_TIMEOUT);
+    conn.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+    loop {
+        let mut reply = Message::new();
+        reply.decode(conn)?;
+        // heartbeats and pushed messages are skipped
+        if reply.get_message_type() == Some(MessageType::Response)
+            && reply.get_seq() == req.get_seq()
+        {
+            return Ok(reply);
+        }
+    }
+}
+
+// the error of a request which was written but not replied, which is not retried.
+fn sent_error(err: Error) -> Error {
+    if timed_out(&err) {
+        return timeout_error(err);
+    }
+    Error::new(ErrorKind::ConnectionClosed, err.to_string())
+}
+
+// returns whether the reply was not read in time.
+fn timed_out(err: &Error) -> bool {
+    let io_err = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<io::Error>());
+    match io_err.map(|err| err.kind()) {
+        Some(io::ErrorKind::WouldBlock) | Some(io::ErrorKind::TimedOut) => true,
+        _ => false,
+    }
+}
+
+fn timeout_error(err: Error) -> Error {
+    Error::new(ErrorKind::Timeout, err.to_string())
+}
+
+/// forwards the requests for services which are not registered to upstream rpcx servers.
+/// Requests are sent as they are, with their metadata, serialize type and payload, and the
+/// replies of the upstreams, including their errors and metadata, are replied to the
+/// callers.
+struct ForwardPlugin {
+    services: Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    upstreams: Vec<Upstream>,
+    next: AtomicUsize,
+}
+
+impl ForwardPlugin {
+    // forwards the request to the upstreams in turn. The next one is tried only if an
+    // upstream can't be reached, so requests are not sent twice.
+    fn forward(&self, req: &Message) -> Message {
+        let data = req.encode();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut last_err = None;
+        for i in 0..self.upstreams.len() {
+            let upstream = &self.upstreams[(start + i) % self.upstreams.len()];
+            match upstream.call(req, &data) {
+                Ok(reply) => return reply,
+                Err(err) => {
+                    eprintln!("failed to forward to {}: {}", upstream.addr, err);
+                    let retriable = err.kind() == ErrorKind::Network;
+                    last_err = Some(Error::new(
+                        err.kind(),
+                        format!("failed to forward to {}: {}", upstream.addr, err),
+                    ));
+                    if !retriable {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut reply = req.get_reply().unwrap();
+        let err = last_err.unwrap_or_else(|| Error::new(ErrorKind::Server, "no upstream"));
+        err.set_reply(&mut reply);
+        reply
+    }
+}
+
+impl MessagePlugin for ForwardPlugin {
+    fn intercept_reply(&self, req: &Message) -> Option<Message> {
+        let key = format!("{}.{}", req.service_path, req.service_method);
+        if self.services.read().unwrap().contains_key(&key) {
+            return None;
+        }
+        Some(self.forward(req))
+    }
+}
+
+impl Server {
+    /// makes the server a proxy of the rpcx servers at `upstreams`, such as
+    /// `127.0.0.1:8973`. The requests for services which are not registered here are
+    /// forwarded to the upstreams in turn, so the server can be an edge proxy or a sidecar.
+    ///
+    /// The message plugins see the forwarded requests and their replies as well, so the
+    /// proxy can authenticate and limit the requests before they are forwarded.
+    pub fn forward_to(&mut self, upstreams: &[&str]) -> Result<()> {
+        let mut resolved = Vec::with_capacity(upstreams.len());
+        for upstream in upstreams {
+            let addr = upstream
+                .to_socket_addrs()
+                .map_err(|err| Error::new(ErrorKind::Config, err))?
+                .next()
+                .ok_or_else(|| {
+                    Error::new(ErrorKind::Config, format!("no address of {}", upstream))
+                })?;
+            resolved.push(Upstream {
+                addr,
+                idle: Mutex::new(Vec::new()),
+            });
+        }
+        if resolved.is_empty() {
+            return Err(Error::new(ErrorKind::Config, "no upstream to forward to"));
+        }
+
+        let plugin = ForwardPlugin {
+            services: self.services.clone(),
+            upstreams: resolved,
+            next: AtomicUsize::new(0),
+        };
+        self.add_message_plugin(Box::new(plugin));
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use super::{
+    http::{read_request, write_response},
+    websocket, Dispatcher,
+};
+use rpcx_protocol::{http::*, *};
+use std::{
+    io::{BufReader, BufWriter},
+    net::{Shutdown, TcpStream},
+};
+
+/// serves rpcx http invoke requests (from curl, browsers or rpcx-gateway) on this connection.
+///
+/// The request carries the service and the serialize type in X-RPCX-* headers and the serialized
+/// args in the body. The reply is returned the same way. The requests pass the message plugins
+/// like the ones of rpcx connections.
+///
+/// A request to upgrade to the WebSocket transport on `WEBSOCKET_PATH` is accepted and the
+/// bytes read after it are returned, the connection carries rpcx messages from then on.
+pub(crate) fn serve(dispatcher: Dispatcher, stream: TcpStream) -> Option<Vec<u8>> {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = BufWriter::new(stream.try_clone().unwrap());
+
+    loop {
+        let req = match read_request(&mut reader) {
+            Ok(Some(req)) => req,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("failed to read http request: {}", err);
+                let body = err.error.to_string();
+                let _ = write_response(&mut writer, err.status, &[], body.as_bytes(), false);
+                break;
+            }
+        };
+        let keep_alive = req.keep_alive;
+        if websocket::is_upgrade(&req) {
+            match websocket::accept(&mut writer, &req) {
+                Ok(()) => return Some(reader.buffer().to_vec()),
+                Err(err) => {
+                    eprintln!("failed to upgrade to websocket: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let rt = if req.method != "POST" {
+            write_response(&mut writer, 405, &[], &[], keep_alive)
+        } else {
+            match message_from_headers(&req.headers, req.body) {
+                Ok(mut msg) => {
+                    let reply = dispatcher.call(&mut msg);
+                    // http always responds, but without the reply of a oneway request
+                    if msg.is_oneway() {
+                        write_response(&mut writer, 200, &[], &[], keep_alive)
+                    } else {
+                        let headers = message_to_headers(&reply);
+                        write_response(&mut writer, 200, &headers, &reply.payload, keep_alive)
+                    }
+                }
+                Err(err) => {
+                    let headers = vec![
+                        (
+                            X_MESSAGE_STATUS_TYPE.to_owned(),
+                            (MessageStatusType::Error as u8).to_string(),
+                        ),
+                        (X_ERROR_MESSAGE.to_owned(), percent_encode(&err.to_string())),
+                    ];
+                    write_response(&mut writer, 400, &headers, &[], keep_alive)
+                }
+            }
+        };
+
+        if let Err(err) = rt {
+            eprintln!("failed to write http response: {}", err);
+            break;
+        }
+        if !keep_alive {
+            break;
+        }
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+    None
+}
@@ -0,0 +1,194 @@
+use super::{http::MAX_BODY_LEN, Dispatcher, Server};
+use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
+use futures::{
+    future::{self, Either, Loop},
+    Async, Future, Poll, Stream,
+};
+use hyper::{
+    body::Payload,
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    server::conn::{AddrIncoming, AddrStream},
+    service::{make_service_fn, service_fn},
+    Body, Chunk, Request, Response, Server as HyperServer,
+};
+use rpcx_protocol::{http::percent_encode, *};
+use std::{net::SocketAddr, thread};
+
+// gRPC status codes used by the bridge.
+const GRPC_OK: u32 = 0;
+const GRPC_UNKNOWN: u32 = 2;
+const GRPC_INVALID_ARGUMENT: u32 = 3;
+const GRPC_RESOURCE_EXHAUSTED: u32 = 8;
+const GRPC_UNIMPLEMENTED: u32 = 12;
+
+/// the body of a unary gRPC response: one length-prefixed message followed by the trailers
+/// carrying the gRPC status.
+struct GrpcBody {
+    data: Option<Chunk>,
+    trailers: Option<HeaderMap>,
+}
+
+impl Payload for GrpcBody {
+    type Data = Chunk;
+    type Error = hyper::Error;
+
+    fn poll_data(&mut self) -> Poll<Option<Chunk>, hyper::Error> {
+        Ok(Async::Ready(self.data.take()))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, hyper::Error> {
+        Ok(Async::Ready(self.trailers.take()))
+    }
+}
+
+impl Server {
+    /// exposes the registered services as unary gRPC services on `addr` (h2c) in background.
+    ///
+    /// A gRPC call to `/<package>.<service_path>/<service_method>` invokes the function registered
+    /// as `<service_path>.<service_method>` with the protobuf serialize type, so the arg and reply
+    /// types must support `SerializeType::Protobuf`. The calls pass the message plugins like the
+    /// ones of rpcx connections.
+    pub fn start_grpc(&self, addr: &str) -> Result<()> {
+        let addr = addr
+            .parse::<SocketAddr>()
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        let incoming =
+            AddrIncoming::bind(&addr).map_err(|err| Error::new(ErrorKind::Network, err))?;
+        let local_addr = incoming.local_addr();
+        println!("gRPC bridge listening on: {}", local_addr);
+
+        let dispatcher = self.dispatcher();
+        let server = HyperServer::builder(incoming)
+            .http2_only(true)
+            .serve(make_service_fn(move |conn: &AddrStream| {
+                let dispatcher = dispatcher.conn(Some(conn.remote_addr()), Some(local_addr));
+                service_fn(move |req: Request<Body>| handle(dispatcher.clone(), req))
+            }))
+            .map_err(|err| eprintln!("gRPC bridge error: {}", err));
+
+        thread::spawn(move || tokio::run(server));
+        Ok(())
+    }
+}
+
+fn handle(
+    dispatcher: Dispatcher,
+    req: Request<Body>,
+) -> impl Future<Item = Response<GrpcBody>, Error = hyper::Error> + Send {
+    let path = req.uri().path().to_owned();
+    // a body of a known length is refused before it is read
+    let too_large = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok())
+        .map_or(false, |len| len > MAX_BODY_LEN);
+    let body = if too_large {
+        Either::A(future::ok(None))
+    } else {
+        Either::B(read_body(req.into_body()))
+    };
+    body.map(move |body| {
+        let rt = match body {
+            Some(body) => invoke(&dispatcher, &path, &body),
+            None => Err((
+                GRPC_RESOURCE_EXHAUSTED,
+                format!("gRPC message is larger than {} bytes", MAX_BODY_LEN),
+            )),
+        };
+        match rt {
+            Ok(data) => grpc_response(GRPC_OK, "", Some(data)),
+            Err((code, message)) => grpc_response(code, &message, None),
+        }
+    })
+}
+
+// reads the body up to `MAX_BODY_LEN` like the http gateway, `None` is returned for a larger
+// one without reading the rest of it.
+fn read_body(body: Body) -> impl Future<Item = Option<Vec<u8>>, Error = hyper::Error> + Send {
+    future::loop_fn((body, Vec::new()), |(body, mut buf)| {
+        body.into_future()
+            .map_err(|(err, _)| err)
+            .map(move |(chunk, body)| match chunk {
+                None => Loop::Break(Some(buf)),
+                Some(chunk) if buf.len() + chunk.len() > MAX_BODY_LEN => Loop::Break(None),
+                Some(chunk) => {
+                    buf.extend_from_slice(&chunk);
+                    Loop::Continue((body, buf))
+                }
+            })
+    })
+}
+
+fn invoke(
+    dispatcher: &Dispatcher,
+    path: &str,
+    body: &[u8],
+) -> std::result::Result<Vec<u8>, (u32, String)> {
+    // "/<package>.<service>/<method>"
+    let mut items = path.trim_start_matches('/').splitn(2, '/');
+    let service = items.next().unwrap_or_default();
+    let service_method = items.next().unwrap_or_default();
+    let service_path = service.rsplit('.').next().unwrap_or_default();
+    // a gRPC message is prefixed by a compressed flag and a 4-byte length.
+    if body.len() < 5 {
+        return Err((GRPC_INVALID_ARGUMENT, "malformed gRPC message".to_owned()));
+    }
+    if body[0] != 0 {
+        return Err((
+            GRPC_UNIMPLEMENTED,
+            "compressed gRPC messages are not supported".to_owned(),
+        ));
+    }
+    let len = BigEndian::read_u32(&body[1..5]) as usize;
+    if body.len() - 5 != len {
+        return Err((
+            GRPC_INVALID_ARGUMENT,
+            "invalid gRPC message length".to_owned(),
+        ));
+    }
+
+    let mut msg = Message::new();
+    msg.set_message_type(MessageType::Request);
+    msg.set_serialize_type(SerializeType::Protobuf);
+    msg.set_compress_type(CompressType::CompressNone);
+    msg.service_path = service_path.to_owned();
+    msg.service_method = service_method.to_owned();
+    msg.payload = Bytes::from(&body[5..]);
+
+    // the plugins may reply the methods which are not registered, such as proxies
+    let reply = dispatcher.call(&mut msg);
+    if let Some(err) = reply.get_error() {
+        let key = format!("{}.{}", service_path, service_method);
+        if !dispatcher.contains(&key) {
+            return Err((GRPC_UNIMPLEMENTED, format!("method {} not found", path)));
+        }
+        return Err((GRPC_UNKNOWN, err));
+    }
+
+    let mut data = Vec::with_capacity(5 + reply.payload.len());
+    data.push(0);
+    let mut len_bytes = [0u8; 4];
+    BigEndian::write_u32(&mut len_bytes, reply.payload.len() as u32);
+    data.extend_from_slice(&len_bytes);
+    data.extend_from_slice(&reply.payload);
+    Ok(data)
+}
+
+fn grpc_response(code: u32, message: &str, data: Option<Vec<u8>>) -> Response<GrpcBody> {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from(code));
+    if !message.is_empty() {
+        if let Ok(v) = HeaderValue::from_str(&percent_encode(message)) {
+            trailers.insert("grpc-message", v);
+        }
+    }
+
+    let mut resp = Response::new(GrpcBody {
+        data: data.map(Chunk::from),
+        trailers: Some(trailers),
+    });
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    resp
+}
@@ -0,0 +1,224 @@
+use rpcx_protocol::{Error, ErrorKind, Result};
+use std::{
+    fmt, io,
+    io::{BufRead, Read, Write},
+};
+
+// a minimal http/1.1 implementation, enough for the http invoke protocol.
+
+const MAX_HEADERS: usize = 128;
+// the longest request line or header line, longer ones are replied with 431.
+const MAX_LINE_LEN: usize = 8 * 1024;
+// the largest body, larger ones are replied with 413 before they are read.
+pub(crate) const MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+
+/// the failure to read a request, which is replied with the status before the connection is
+/// closed.
+#[derive(Debug)]
+pub(crate) struct HttpError {
+    pub status: u16,
+    pub error: Error,
+}
+
+impl HttpError {
+    fn new(status: u16, msg: impl Into<String>) -> Self {
+        HttpError {
+            status,
+            error: Error::new(ErrorKind::Protocol, msg.into()),
+        }
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(err: io::Error) -> Self {
+        HttpError {
+            status: 400,
+            error: Error::from(err),
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.status, self.error)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub keep_alive: bool,
+}
+
+impl HttpRequest {
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn read_line<R: BufRead>(r: &mut R) -> std::result::Result<Option<String>, HttpError> {
+    let mut line = String::new();
+    if r.by_ref()
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_line(&mut line)?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LEN {
+        return Err(HttpError::new(
+            431,
+            format!("http line is longer than {} bytes", MAX_LINE_LEN),
+        ));
+    }
+    let len = line.trim_end_matches(|c| c == '\r' || c == '\n').len();
+    line.truncate(len);
+    Ok(Some(line))
+}
+
+/// reads a http request. Returns `None` if the connection has been closed.
+///
+/// The lines and the body are bounded, so a client can't make the server buffer without
+/// limit.
+pub(crate) fn read_request<R: BufRead>(
+    r: &mut R,
+) -> std::result::Result<Option<HttpRequest>, HttpError> {
+    let mut req = match read_head(r)? {
+        Some(req) => req,
+        None => return Ok(None),
+    };
+    read_body(r, &mut req)?;
+    Ok(Some(req))
+}
+
+/// reads the request line and the headers of a http request, the body is left to `read_body`
+/// so the headers can be checked before it is read.
+pub(crate) fn read_head<R: BufRead>(
+    r: &mut R,
+) -> std::result::Result<Option<HttpRequest>, HttpError> {
+    let line = match read_line(r)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    let items: Vec<&str> = line.split_whitespace().collect();
+    if items.len() != 3 {
+        return Err(HttpError::new(
+            400,
+            format!("malformed http request line: {}", line),
+        ));
+    }
+    let mut req = HttpRequest {
+        method: items[0].to_owned(),
+        path: items[1].to_owned(),
+        keep_alive: items[2] == "HTTP/1.1",
+        ..Default::default()
+    };
+
+    loop {
+        let line =
+            read_line(r)?.ok_or_else(|| HttpError::new(400, "unexpected eof in http headers"))?;
+        if line.is_empty() {
+            break;
+        }
+        if req.headers.len() >= MAX_HEADERS {
+            return Err(HttpError::new(431, "too many http headers"));
+        }
+        let idx = line
+            .find(':')
+            .ok_or_else(|| HttpError::new(400, "malformed http header"))?;
+        let (k, v) = line.split_at(idx);
+        req.headers
+            .push((k.trim().to_owned(), v[1..].trim().to_owned()));
+    }
+
+    if let Some(conn) = req.get_header("Connection") {
+        if conn.eq_ignore_ascii_case("close") {
+            req.keep_alive = false;
+        } else if conn.eq_ignore_ascii_case("keep-alive") {
+            req.keep_alive = true;
+        }
+    }
+
+    Ok(Some(req))
+}
+
+/// reads the body of the request whose head is read by `read_head`.
+pub(crate) fn read_body<R: BufRead>(
+    r: &mut R,
+    req: &mut HttpRequest,
+) -> std::result::Result<(), HttpError> {
+    // the bodies are only read by their length
+    if req.get_header("Transfer-Encoding").is_some() {
+        return Err(HttpError::new(
+            501,
+            "Transfer-Encoding is not supported, the body must be sent with Content-Length",
+        ));
+    }
+    let len = match req.get_header("Content-Length") {
+        Some(v) => v
+            .parse::<usize>()
+            .map_err(|err| HttpError::new(400, format!("malformed Content-Length: {}", err)))?,
+        None => 0,
+    };
+    if len > MAX_BODY_LEN {
+        return Err(HttpError::new(
+            413,
+            format!("http body is larger than {} bytes", MAX_BODY_LEN),
+        ));
+    }
+    let mut body = vec![0u8; len];
+    r.read_exact(&mut body)?;
+    req.body = body;
+    Ok(())
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// writes a http response with the given headers and body.
+pub(crate) fn write_response<W: Write>(
+    w: &mut W,
+    status: u16,
+    headers: &[(String, String)],
+    body: &[u8],
+    keep_alive: bool,
+) -> Result<()> {
+    let mut buf = Vec::with_capacity(256 + body.len());
+    write!(buf, "HTTP/1.1 {} {}\r\n", status, reason_phrase(status))?;
+    for (k, v) in headers {
+        // the control characters are dropped, so a value can't end the header and add others
+        let v: String = v.chars().filter(|c| !c.is_control()).collect();
+        write!(buf, "{}: {}\r\n", k, v)?;
+    }
+    write!(buf, "Content-Length: {}\r\n", body.len())?;
+    if !keep_alive {
+        write!(buf, "Connection: close\r\n")?;
+    }
+    write!(buf, "\r\n")?;
+    buf.extend_from_slice(body);
+
+    w.write_all(&buf)?;
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use super::MessagePlugin;
+use bytes::Bytes;
+use rpcx_protocol::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// a request is identified by the token of its caller, its method and its idempotency key.
+type IdempotencyKey = (String, String, String);
+
+#[derive(Debug)]
+enum Entry {
+    // the request is being handled since the instant, by the message at the address
+    InProgress(usize, Instant),
+    // the reply of the request, its metadata and its payload
+    Done(Instant, Metadata, Bytes),
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: HashMap<IdempotencyKey, Entry>,
+    // the keys of the replies in the order they were cached, the oldest first
+    done: VecDeque<(Instant, IdempotencyKey)>,
+}
+
+/// handles the requests with the same `IDEMPOTENCY_KEY` in their metadata once, so the
+/// retries of clients and the duplicates of `FailMode::Failover` don't apply a
+/// non-idempotent handler twice. The reply of a request is cached for the TTL and replied to
+/// its duplicates. A duplicate of a request which is being handled fails with
+/// `ErrorKind::ServerBusy`, and is retried by the client.
+///
+/// The replies are cached by the token of the caller, see `MetadataExt::auth_token`, so a
+/// caller can't get the reply of another one by its idempotency key. Error replies are not
+/// cached, so a failed request can be sent again. Like
+/// `ResponseCachePlugin`, it should be added before plugins which transform payloads.
+#[derive(Debug)]
+pub struct IdempotencyPlugin {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+    duplicates: AtomicU64,
+}
+
+impl IdempotencyPlugin {
+    /// caches the replies for `ttl`, at most `max_entries` of them, the oldest one is evicted
+    /// first.
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        IdempotencyPlugin {
+            ttl,
+            max_entries,
+            entries: Mutex::new(Entries::default()),
+            duplicates: AtomicU64::new(0),
+        }
+    }
+
+    /// returns the number of duplicate requests which were not handled.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    // returns the key of a request with an idempotency key.
+    fn key(req: &Message) -> Option<IdempotencyKey> {
+        if req.is_oneway() {
+            return None;
+        }
+        let metadata = req.metadata.borrow();
+        let key = metadata.get(IDEMPOTENCY_KEY)?.clone();
+        let token = metadata.auth_token().unwrap_or_default().to_owned();
+        let method = format!("{}.{}", req.service_path, req.service_method);
+        Some((token, method, key))
+    }
+
+    // removes the expired replies, and the oldest ones while the cache is full.
+    fn evict(&self, entries: &mut Entries) {
+        while let Some((done_at, _)) = entries.done.front() {
+            if done_at.elapsed() < self.ttl && entries.entries.len() < self.max_entries {
+                return;
+            }
+            let (done_at, key) = entries.done.pop_front().unwrap();
+            // the key may have been handled again since
+            if let Some(Entry::Done(at, _, _)) = entries.entries.get(&key) {
+                if *at == done_at {
+                    entries.entries.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+// the requests are told apart by their addresses, which are unique while they are handled.
+fn request_addr(req: &Message) -> usize {
+    req as *const Message as usize
+}
+
+impl MessagePlugin for IdempotencyPlugin {
+    fn intercept_reply(&self, req: &Message) -> Option<Message> {
+        let key = Self::key(req)?;
+        let mut entries = self.entries.lock().unwrap();
+        let mut reply = req.get_reply().unwrap();
+        match entries.entries.get(&key) {
+            Some(Entry::Done(done_at, metadata, payload)) if done_at.elapsed() < self.ttl => {
+                let mut cached = metadata.clone();
+                if let Some(request_id) = req.get_request_id() {
+                    cached.insert(REQUEST_ID.to_owned(), request_id);
+                }
+                *reply.metadata.get_mut() = cached;
+                reply.payload = payload.clone();
+            }
+            // a request whose handler panicked is handled again after the TTL
+            Some(Entry::InProgress(_, started)) if started.elapsed() < self.ttl => {
+                let err = Error::new(
+                    ErrorKind::ServerBusy,
+                    format!("the request of idempotency key {} is in progress", key.2),
+                );
+                err.set_reply(&mut reply);
+            }
+            _ => {
+                self.evict(&mut entries);
+                let in_progress = Entry::InProgress(request_addr(req), Instant::now());
+                entries.entries.insert(key, in_progress);
+                return None;
+            }
+        }
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+        Some(reply)
+    }
+
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        let key = match Self::key(req) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        // only the request which is handled records its reply
+        match entries.entries.get(&key) {
+            Some(Entry::InProgress(addr, _)) if *addr == request_addr(req) => {}
+            _ => return Ok(()),
+        }
+        if let Some(MessageStatusType::Error) = res.get_message_status_type() {
+            entries.entries.remove(&key);
+            return Ok(());
+        }
+        let now = Instant::now();
+        let done = Entry::Done(now, res.metadata.borrow().clone(), res.payload.clone());
+        entries.done.push_back((now, key.clone()));
+        entries.entries.insert(key, done);
+        Ok(())
+    }
+}
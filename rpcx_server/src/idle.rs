@@ -0,0 +1,26 @@
+use std::{io, time::Duration};
+
+use rpcx_protocol::*;
+
+use super::Server;
+
+impl Server {
+    /// closes the connections which read nothing for `timeout`, heartbeats included, so the
+    /// connections of clients vanished behind NATs are freed. Connections waiting for the
+    /// replies of their requests or with open streams are kept.
+    ///
+    /// It is disabled by default and applies to the connections accepted from now on. Clients
+    /// which only subscribe to topics must send heartbeats to stay connected.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        *self.idle_timeout.write().unwrap() = Some(timeout);
+    }
+}
+
+/// whether a read failed since the read timeout of the connection expired.
+pub(crate) fn is_timeout(err: &Error) -> bool {
+    err.get_ref()
+        .and_then(|err| err.downcast_ref::<io::Error>())
+        .map_or(false, |err| {
+            err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut
+        })
+}
@@ -0,0 +1,232 @@
+use super::{
+    http::{read_request, write_response},
+    Dispatcher, Server,
+};
+use bytes::Bytes;
+use rpcx_protocol::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::{BufReader, BufWriter},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    thread,
+};
+
+// error codes defined by the JSON-RPC 2.0 specification.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    // requests without id are notifications and get no response.
+    #[serde(default)]
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcError>,
+    id: Value,
+}
+
+impl JsonRpcResponse {
+    fn result(id: Value, result: Value) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0",
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0",
+            result: None,
+            error: Some(JsonRpcError { code, message }),
+            id,
+        }
+    }
+}
+
+impl Server {
+    /// starts a JSON-RPC 2.0 listener on `addr` in background.
+    ///
+    /// Methods are named as `<service_path>.<service_method>`, e.g. `Arith.Add`, and params are
+    /// passed to the registered function as JSON. The requests pass the message plugins like
+    /// the ones of rpcx connections.
+    pub fn start_jsonrpc(&self, addr: &str) -> Result<()> {
+        let addr = addr
+            .parse::<SocketAddr>()
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        let listener = TcpListener::bind(&addr)?;
+        println!("JSON-RPC listening on: {}", addr);
+
+        let dispatcher = self.dispatcher();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let dispatcher =
+                            dispatcher.conn(stream.peer_addr().ok(), stream.local_addr().ok());
+                        thread::spawn(move || serve(dispatcher, stream));
+                    }
+                    Err(err) => {
+                        eprintln!("failed to accept JSON-RPC connection: {}", err);
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+fn serve(dispatcher: Dispatcher, stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = BufWriter::new(stream.try_clone().unwrap());
+    let headers = vec![("Content-Type".to_owned(), "application/json".to_owned())];
+
+    loop {
+        let req = match read_request(&mut reader) {
+            Ok(Some(req)) => req,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("failed to read http request: {}", err);
+                let body = err.error.to_string();
+                let _ = write_response(&mut writer, err.status, &[], body.as_bytes(), false);
+                break;
+            }
+        };
+        let keep_alive = req.keep_alive;
+
+        let rt = if req.method != "POST" {
+            write_response(&mut writer, 405, &[], &[], keep_alive)
+        } else {
+            match handle_body(&dispatcher, &req.body) {
+                Some(body) => write_response(&mut writer, 200, &headers, &body, keep_alive),
+                None => write_response(&mut writer, 204, &[], &[], keep_alive),
+            }
+        };
+
+        if let Err(err) = rt {
+            eprintln!("failed to write http response: {}", err);
+            break;
+        }
+        if !keep_alive {
+            break;
+        }
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// handles a single request or a batch. Returns `None` if there is nothing to respond.
+fn handle_body(dispatcher: &Dispatcher, body: &[u8]) -> Option<Vec<u8>> {
+    let value: Value = match serde_json::from_slice(body) {
+        Ok(v) => v,
+        Err(err) => {
+            let resp = JsonRpcResponse::error(Value::Null, PARSE_ERROR, err.to_string());
+            return serde_json::to_vec(&resp).ok();
+        }
+    };
+
+    match value {
+        Value::Array(reqs) => {
+            if reqs.is_empty() {
+                let resp =
+                    JsonRpcResponse::error(Value::Null, INVALID_REQUEST, "empty batch".to_owned());
+                return serde_json::to_vec(&resp).ok();
+            }
+            let resps: Vec<JsonRpcResponse> = reqs
+                .into_iter()
+                .filter_map(|req| handle_request(dispatcher, req))
+                .collect();
+            if resps.is_empty() {
+                return None;
+            }
+            serde_json::to_vec(&resps).ok()
+        }
+        req => handle_request(dispatcher, req).and_then(|resp| serde_json::to_vec(&resp).ok()),
+    }
+}
+
+fn handle_request(dispatcher: &Dispatcher, value: Value) -> Option<JsonRpcResponse> {
+    let req: JsonRpcRequest = match serde_json::from_value(value) {
+        Ok(req) => req,
+        Err(err) => {
+            return Some(JsonRpcResponse::error(
+                Value::Null,
+                INVALID_REQUEST,
+                err.to_string(),
+            ))
+        }
+    };
+    let id = req.id.clone();
+    let resp = invoke(dispatcher, req);
+
+    // notifications get no response
+    id.map(|id| match resp {
+        Ok(result) => JsonRpcResponse::result(id, result),
+        Err((code, message)) => JsonRpcResponse::error(id, code, message),
+    })
+}
+
+fn invoke(
+    dispatcher: &Dispatcher,
+    req: JsonRpcRequest,
+) -> std::result::Result<Value, (i64, String)> {
+    if req.jsonrpc != "2.0" {
+        return Err((INVALID_REQUEST, "jsonrpc must be 2.0".to_owned()));
+    }
+
+    let mut items = req.method.rsplitn(2, '.');
+    let service_method = items.next().unwrap_or_default();
+    let service_path = match items.next() {
+        Some(service_path) => service_path,
+        None => return Err((METHOD_NOT_FOUND, format!("method {} not found", req.method))),
+    };
+    let key = format!("{}.{}", service_path, service_method);
+    if !dispatcher.contains(&key) {
+        return Err((METHOD_NOT_FOUND, format!("method {} not found", req.method)));
+    }
+
+    // positional params with a single element are passed as the args
+    let params = match req.params {
+        Value::Array(mut params) if params.len() == 1 => params.remove(0),
+        params => params,
+    };
+
+    let mut msg = Message::new();
+    msg.set_message_type(MessageType::Request);
+    msg.set_serialize_type(SerializeType::JSON);
+    msg.set_compress_type(CompressType::CompressNone);
+    msg.service_path = service_path.to_owned();
+    msg.service_method = service_method.to_owned();
+    msg.payload = serde_json::to_vec(&params)
+        .map(Bytes::from)
+        .map_err(|err| (SERVER_ERROR, err.to_string()))?;
+
+    let reply = dispatcher.call(&mut msg);
+    if let Some(err) = reply.get_error() {
+        return Err((SERVER_ERROR, err));
+    }
+    serde_json::from_slice(&reply.payload).map_err(|err| (SERVER_ERROR, err.to_string()))
+}
@@ -1,35 +1,161 @@
 use std::{
     boxed::Box,
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 use std::net::SocketAddr;
 
 use rpcx_protocol::*;
-use std::{
-    io::{BufReader, BufWriter, Write},
-    net::{Shutdown, TcpListener, TcpStream},
-};
+use std::net::{Shutdown, TcpListener, TcpStream};
 
 use std::{
     os::unix::io::{AsRawFd, RawFd},
     thread,
 };
 
+use bytes::Bytes;
 use scoped_threadpool::Pool;
 
+mod activation;
+mod admin;
+mod cache;
+mod compression;
+mod config;
+mod context;
+mod dylib;
+#[cfg(feature = "crypto")]
+mod encryption;
+mod errorlog;
+#[cfg(feature = "eureka-registry")]
+mod eureka;
+mod executor;
+mod fault;
+mod filetransfer;
+mod forward;
+mod gateway;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod http;
+mod idempotency;
+mod idle;
+mod jsonrpc;
+mod limit;
+mod load;
+mod metrics;
+mod notify;
 pub mod plugin;
+mod pubsub;
+mod queue;
+mod ratelimit;
+mod reflection;
+mod registration;
+mod reload;
+mod restart;
+mod reuseport;
+mod shadow;
+mod stream;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tracing")]
+mod trace;
+mod websocket;
+mod writer;
+pub use activation::listen_fds;
+pub use admin::InFlightCall;
+pub use cache::ResponseCachePlugin;
+use compression::ConnReader;
+pub use config::{RateLimitConfig, ServerConfig, TracingConfig};
+pub use context::Context;
+pub use dylib::{
+    DylibAddServiceFn, DylibFreeFn, DylibHandlerFn, DylibRegisterFn, DYLIB_FREE_SYMBOL,
+    DYLIB_REGISTER_SYMBOL,
+};
+#[cfg(feature = "crypto")]
+pub use encryption::EncryptionPlugin;
+#[cfg(feature = "eureka-registry")]
+pub use eureka::EurekaRegister;
+use executor::ReactorQueue;
+pub use fault::{Fault, FaultInjectionPlugin};
+use filetransfer::FileTransfer;
+pub use filetransfer::FILE_TRANSFER_TOKEN_TTL;
+pub use forward::FORWARD_TIMEOUT;
+pub use idempotency::IdempotencyPlugin;
+use limit::MethodLimits;
+pub use limit::{AdaptiveLimitPlugin, LimitPolicy};
+use metrics::MethodStatsTable;
+pub use metrics::{MethodStats, APDEX_THRESHOLD, LATENCY_BUCKETS};
+use notify::AcceptWatch;
+pub use notify::{sd_notify, watchdog_interval};
 pub use plugin::*;
+use pubsub::{Subscriptions, Topics};
+pub use pubsub::{TOPIC_IDLE_TIMEOUT, TOPIC_QUEUE_SIZE};
+use queue::{Job, PriorityJobs, RequestQueue};
+pub use ratelimit::{Quota, RateLimitPlugin};
+pub use registration::DuplicatePolicy;
+use restart::accept_polled;
+pub use restart::{inherited_listeners, DRAIN_DELAY, INHERITED_FDS};
+pub use shadow::{ShadowPlugin, SHADOW_QUEUE_SIZE};
+pub use stream::RpcxStreamFn;
+use stream::Streams;
+#[cfg(feature = "tls")]
+use tls::TlsAcceptor;
+#[cfg(feature = "tls")]
+pub use tls::{CERT_RELOAD_INTERVAL, TLS_HANDSHAKE_TIMEOUT};
+#[cfg(feature = "tracing")]
+pub use trace::{TracingPlugin, TRACE_QUEUE_SIZE};
+use writer::ConnWriter;
+pub use writer::WRITE_TIMEOUT;
 
 pub type RpcxFn = fn(&[u8], SerializeType) -> Result<Vec<u8>>;
+pub(crate) type MessagePlugins = Arc<RwLock<Vec<Box<dyn MessagePlugin + Send + Sync>>>>;
 pub struct Server {
     pub addr: String,
-    raw_fd: Option<RawFd>,
+    // the listeners being served, see `close` and `restart`
+    raw_fds: Mutex<Vec<RawFd>>,
     pub services: Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
     thread_number: u32,
     register_plugins: Arc<RwLock<Vec<Box<dyn RegisterPlugin + Send + Sync>>>>,
     connect_plugins: Arc<RwLock<Vec<Box<dyn ConnectPlugin + Send + Sync>>>>,
+    message_plugins: MessagePlugins,
+    conns: Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+    seq: Arc<AtomicU64>,
+    stream_services: Arc<RwLock<HashMap<String, RpcxStreamFn>>>,
+    subscriptions: Subscriptions,
+    topics: Topics,
+    file_transfer: Option<Arc<FileTransfer>>,
+    version: Option<String>,
+    weight: Arc<RwLock<Option<u32>>>,
+    // whether the server is paused, see `pause`
+    paused: AtomicBool,
+    metas: Arc<RwLock<HashMap<String, String>>>,
+    listener: Option<TcpListener>,
+    queue: Arc<RequestQueue>,
+    limits: MethodLimits,
+    idle_timeout: RwLock<Option<Duration>>,
+    metadata_limits: MetadataLimits,
+    max_message_len: usize,
+    // the compression of the streams of connections, see `enable_stream_compression`
+    stream_compression: Option<Arc<StreamCompression>>,
+    // the TLS of the connections, see `enable_tls`
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+    // the bound of the outbound queues of connections, see `set_outbound_limit`
+    outbound_limit: (usize, BackpressurePolicy, Option<Duration>),
+    // what `register_fn` does with the methods registered already
+    duplicate_policy: DuplicatePolicy,
+    method_stats: Arc<MethodStatsTable>,
+    // whether systemd is notified, see `enable_sd_notify`
+    sd_notify: bool,
+    // how long `drain` waits for the registries, see `set_drain_delay`
+    drain_delay: Duration,
+    // whether the accept loops stop, see `shutdown` and `close`
+    stopped: AtomicBool,
+    restarted: AtomicBool,
 }
 
 impl Server {
@@ -45,17 +171,192 @@ impl Server {
             thread_number,
             register_plugins: Arc::new(RwLock::new(Vec::new())),
             connect_plugins: Arc::new(RwLock::new(Vec::new())),
-            raw_fd: None,
+            message_plugins: Arc::new(RwLock::new(Vec::new())),
+            conns: Arc::new(RwLock::new(HashMap::new())),
+            seq: Arc::new(AtomicU64::new(0)),
+            stream_services: Arc::new(RwLock::new(HashMap::new())),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            topics: Arc::new(Mutex::new(HashMap::new())),
+            file_transfer: None,
+            version: None,
+            weight: Arc::new(RwLock::new(None)),
+            paused: AtomicBool::new(false),
+            metas: Arc::new(RwLock::new(HashMap::new())),
+            listener: None,
+            queue: Arc::new(RequestQueue::default()),
+            limits: Arc::new(RwLock::new(HashMap::new())),
+            idle_timeout: RwLock::new(None),
+            metadata_limits: MetadataLimits::default(),
+            max_message_len: MAX_MESSAGE_LEN,
+            stream_compression: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            outbound_limit: (
+                MAX_QUEUED_BYTES,
+                BackpressurePolicy::Block,
+                Some(WRITE_TIMEOUT),
+            ),
+            duplicate_policy: DuplicatePolicy::default(),
+            method_stats: Arc::new(MethodStatsTable::default()),
+            sd_notify: false,
+            drain_delay: DRAIN_DELAY,
+            raw_fds: Mutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+            restarted: AtomicBool::new(false),
+        }
+    }
+
+    /// sets the version published with the metadata of the services registered from now on,
+    /// for example `1.2.0`. Clients can select servers by version with `VersionSelector`.
+    pub fn set_version(&mut self, version: &str) {
+        self.version = Some(version.to_owned());
+    }
+
+    /// limits the metadata of requests, the connections of clients which exceed the limits
+    /// are closed. It applies to the connections accepted from now on.
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) {
+        self.metadata_limits = limits;
+    }
+
+    /// limits the bytes of requests, `MAX_MESSAGE_LEN` by default. The connections of clients
+    /// which send longer requests, or whose payloads decompress to more, are closed. It applies
+    /// to the connections accepted from now on.
+    pub fn set_max_message_len(&mut self, max_len: usize) {
+        self.max_message_len = max_len;
+    }
+
+    /// bounds the bytes of the responses and pushed messages queued to be written to each
+    /// connection, `MAX_QUEUED_BYTES` by default. When a client stops reading, the writes to
+    /// its connection wait or fail by the policy instead of buffering without limit. The
+    /// messages pushed to clients, such as the ones of subscriptions, fail instead of waiting
+    /// whatever the policy. It applies to the connections accepted from now on.
+    pub fn set_outbound_limit(&mut self, max_queued_bytes: usize, policy: BackpressurePolicy) {
+        self.outbound_limit.0 = max_queued_bytes;
+        self.outbound_limit.1 = policy;
+    }
+
+    /// sets the timeout of the writes to connections, `WRITE_TIMEOUT` by default. A connection
+    /// whose write times out is closed, so a client which stops reading holds the writers
+    /// waiting for its queue by `BackpressurePolicy::Block` at most this long. `None` waits
+    /// without limit. It applies to the connections accepted from now on.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.outbound_limit.2 = timeout;
+    }
+
+    /// sets the weight published with the metadata of the services and pushes the updated
+    /// metadata to the registries at once, so the weighted selectors of clients follow. It can
+    /// be called while the server is running to drain or warm up the instance gradually.
+    ///
+    /// The error of the last registry which failed to update is returned.
+    pub fn set_weight(&self, weight: u32) -> Result<()> {
+        *self.weight.write().unwrap() = Some(weight);
+        self.update_metas("weight", &weight.to_string())
+    }
+
+    /// marks the services paused in the registries by the `state=paused` metadata. Clients
+    /// stop selecting the server for new calls but keep their connections to it, so the calls
+    /// in flight finish and the server is reachable again at once after `resume`. It is meant
+    /// for staged maintenance, the server keeps serving the requests it receives.
+    ///
+    /// The error of the last registry which failed to update is returned.
+    pub fn pause(&self) -> Result<()> {
+        self.paused.store(true, Ordering::SeqCst);
+        self.update_metas(STATE, STATE_PAUSED)
+    }
+
+    /// marks the paused services active again by the `state=active` metadata.
+    pub fn resume(&self) -> Result<()> {
+        self.paused.store(false, Ordering::SeqCst);
+        self.update_metas(STATE, STATE_ACTIVE)
+    }
+
+    /// returns whether the server is paused by `pause`.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    // sets the param of the metadata of all services and pushes the metadata to the
+    // registries, returning the error of the last registry which failed to update.
+    fn update_metas(&self, key: &str, value: &str) -> Result<()> {
+        let mut rt = Ok(());
+        let mut metas = self.metas.write().unwrap();
+        let mut plugins = self.register_plugins.write().unwrap();
+        for (service_path, meta) in metas.iter_mut() {
+            *meta = set_meta_param(meta, key, value);
+            for p in plugins.iter_mut() {
+                if let Err(err) = p.update_meta(service_path, meta.clone()) {
+                    eprintln!("failed to update {}. err: {}", service_path, err);
+                    rt = Err(err);
+                }
+            }
         }
+        rt
     }
 
+    /// registers the function of a method, `meta` is the metadata of the service published
+    /// to the registries. The path and the method can be given as strings or as
+    /// `ServicePath` and `ServiceMethod`.
+    ///
+    /// A method which is registered already is handled by the policy of
+    /// `set_duplicate_policy`, a rejected registration is logged. See `try_register_fn`.
     pub fn register_fn(
         &mut self,
-        service_path: String,
-        service_method: String,
+        service_path: impl Into<ServicePath>,
+        service_method: impl Into<ServiceMethod>,
         meta: String,
         f: RpcxFn,
     ) {
+        if let Err(err) = self.try_register_fn(service_path, service_method, meta, f) {
+            eprintln!("{}", err);
+        }
+    }
+
+    /// like `register_fn` but returns the method the function is registered by, which is a
+    /// version of the method by `DuplicatePolicy::Version`, or the error of
+    /// `DuplicatePolicy::Reject`.
+    pub fn try_register_fn(
+        &mut self,
+        service_path: impl Into<ServicePath>,
+        service_method: impl Into<ServiceMethod>,
+        meta: String,
+        f: RpcxFn,
+    ) -> Result<ServiceMethod> {
+        self.register_service(service_path.into(), service_method.into(), meta, f)
+    }
+
+    // registers the function like `try_register_fn`, also to running servers.
+    pub(crate) fn register_service(
+        &self,
+        service_path: ServicePath,
+        service_method: ServiceMethod,
+        meta: String,
+        f: RpcxFn,
+    ) -> Result<ServiceMethod> {
+        let service_method = registration::resolve(
+            self.duplicate_policy,
+            &self.services.read().unwrap(),
+            &service_path,
+            service_method,
+        )?;
+        let mut meta = meta;
+        if let Some(version) = &self.version {
+            meta = set_meta_param(&meta, "version", version);
+        }
+        if let Some(weight) = *self.weight.read().unwrap() {
+            meta = set_meta_param(&meta, "weight", &weight.to_string());
+        }
+        if self.is_paused() {
+            meta = set_meta_param(&meta, STATE, STATE_PAUSED);
+        }
+        // the methods of a service share the metadata it is registered with first
+        let meta = self
+            .metas
+            .write()
+            .unwrap()
+            .entry(service_path.to_string())
+            .or_insert(meta)
+            .clone();
+
         // invoke register plugins
         let mut plugins = self.register_plugins.write().unwrap();
         for p in plugins.iter_mut() {
@@ -76,9 +377,15 @@ impl Server {
         let services = self.services.clone();
         let mut map = services.write().unwrap();
         map.insert(key, Box::new(f));
+        Ok(service_method)
     }
 
-    pub fn get_fn(&self, service_path: String, service_method: String) -> Option<RpcxFn> {
+    pub fn get_fn(
+        &self,
+        service_path: impl Into<ServicePath>,
+        service_method: impl Into<ServiceMethod>,
+    ) -> Option<RpcxFn> {
+        let (service_path, service_method) = (service_path.into(), service_method.into());
         let key = format!("{}.{}", service_path, service_method);
         let map = self.services.read().unwrap();
         let box_fn = map.get(&key)?;
@@ -86,14 +393,102 @@ impl Server {
     }
 
     pub fn start_with_listener(&self, listener: TcpListener) -> Result<()> {
+        self.start_reactor(listener, None)
+    }
+
+    // serves on the listener, the requests of its connections are handled by the pool of
+    // each connection or by the executor of the reactor, see `start_reuseport`.
+    fn start_reactor(&self, listener: TcpListener, reactor: Option<ReactorQueue>) -> Result<()> {
+        let raw_fd = listener.as_raw_fd();
+        let local_addr = listener.local_addr()?;
+        self.raw_fds.lock().unwrap().push(raw_fd);
+        register_local_server(local_addr, local_handler(self, local_addr));
+        let watch = Arc::new(AcceptWatch::new());
+        self.notify_ready(&watch);
+        let rt = self.accept(&listener, &watch, reactor);
+        self.notify_stopping(&watch);
+        unregister_local_server(&local_addr);
+        self.raw_fds.lock().unwrap().retain(|&fd| fd != raw_fd);
+        rt
+    }
+
+    // accepts connections until the listener fails or the server is shut down.
+    fn accept(
+        &self,
+        listener: &TcpListener,
+        watch: &AcceptWatch,
+        reactor: Option<ReactorQueue>,
+    ) -> Result<()> {
         let thread_number = self.thread_number;
+        let read_limits = (self.metadata_limits, self.max_message_len);
+        let outbound_limit = self.outbound_limit;
 
-        'accept_loop: for stream in listener.incoming() {
+        listener.set_nonblocking(true)?;
+        'accept_loop: loop {
+            let stream = match accept_polled(listener) {
+                Ok(None) if !self.stopped.load(Ordering::SeqCst) => continue,
+                Ok(None) => break,
+                Ok(Some(stream)) => stream.set_nonblocking(false).map(|_| stream),
+                Err(err) => Err(err),
+            };
+            let _busy = watch.busy();
+            if self.stopped.load(Ordering::SeqCst) {
+                // the connection is retried by the client, with the next process if it is
+                // restarted
+                if let Ok(stream) = stream {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+                break;
+            }
             match stream {
                 Ok(stream) => {
+                    if let Err(err) = self.connected(&stream) {
+                        eprintln!("connection is rejected: {}", err);
+                        let _ = stream.shutdown(Shutdown::Both);
+                        continue 'accept_loop;
+                    }
+
                     let services_cloned = self.services.clone();
+                    let message_plugins_cloned = self.message_plugins.clone();
+                    let conns_cloned = self.conns.clone();
+                    let stream_services_cloned = self.stream_services.clone();
+                    let subscriptions_cloned = self.subscriptions.clone();
+                    let file_transfer_cloned = self.file_transfer.clone();
+                    let queue_cloned = self.queue.clone();
+                    let limits_cloned = self.limits.clone();
+                    let idle_timeout = *self.idle_timeout.read().unwrap();
+                    let stream_compression = self.stream_compression.clone();
+                    let reactor = reactor.clone();
+                    #[cfg(feature = "tls")]
+                    let tls = self.tls.clone();
                     thread::spawn(move || {
-                        Server::process(thread_number, services_cloned, stream);
+                        #[cfg(feature = "tls")]
+                        let conn = match tls::accept(&tls, stream) {
+                            Ok(conn) => conn,
+                            Err(err) => {
+                                eprintln!("connection is rejected: {}", err);
+                                return;
+                            }
+                        };
+                        #[cfg(not(feature = "tls"))]
+                        let conn = Conn::Tcp(stream);
+                        Server::process(
+                            thread_number,
+                            services_cloned,
+                            message_plugins_cloned,
+                            conns_cloned,
+                            stream_services_cloned,
+                            subscriptions_cloned,
+                            file_transfer_cloned,
+                            queue_cloned,
+                            limits_cloned,
+                            idle_timeout,
+                            read_limits,
+                            stream_compression,
+                            outbound_limit,
+                            reactor,
+                            conn,
+                        );
                     });
                 }
                 Err(e) => {
@@ -105,7 +500,13 @@ impl Server {
 
         Ok(())
     }
+    /// serves on the listener of `from_listener` if there is one, otherwise binds `addr`.
     pub fn start(&mut self) -> Result<()> {
+        if let Some(listener) = self.listener.take() {
+            println!("Listening on: {}", self.addr);
+            return self.start_with_listener(listener);
+        }
+
         let addr = self
             .addr
             .parse::<SocketAddr>()
@@ -114,58 +515,302 @@ impl Server {
         let listener = TcpListener::bind(&addr)?;
         println!("Listening on: {}", addr);
 
-        self.raw_fd = Some(listener.as_raw_fd());
-
         self.start_with_listener(listener)
     }
 
+    // invokes connect plugins. The connection is closed if any plugin returns an error.
+    fn connected(&self, stream: &TcpStream) -> Result<()> {
+        let mut plugins = self.connect_plugins.write().unwrap();
+        for p in plugins.iter_mut() {
+            p.connected(stream)?;
+        }
+        Ok(())
+    }
+
+    /// returns the remote addresses of connected rpcx clients.
+    pub fn active_conns(&self) -> Vec<SocketAddr> {
+        self.conns.read().unwrap().keys().cloned().collect()
+    }
+
+    /// returns when the client of the connection sent its last heartbeat, `None` if it is not
+    /// connected or has not sent any heartbeat.
+    pub fn last_heartbeat(&self, conn: &SocketAddr) -> Option<Instant> {
+        self.conns.read().unwrap().get(conn)?.last_heartbeat()
+    }
+
+    /// returns when the connection read its last message, `None` if it is not connected.
+    pub fn last_activity(&self, conn: &SocketAddr) -> Option<Instant> {
+        Some(self.conns.read().unwrap().get(conn)?.last_activity())
+    }
+
+    /// sends a message to a connected client without a request from it.
+    ///
+    /// The message is sent as a oneway request and is delivered to the receiver returned by
+    /// `Client::subscribe` on the client side.
+    /// It fails with `ErrorKind::ServerBusy` instead of waiting if the outbound queue of the
+    /// connection is full.
+    pub fn send_message(
+        &self,
+        conn: &SocketAddr,
+        service_path: &str,
+        service_method: &str,
+        metadata: &Metadata,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let stream = self
+            .conns
+            .read()
+            .unwrap()
+            .get(conn)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::Network, format!("client {} not found", conn)))?;
+
+        let mut msg = Message::new();
+        msg.set_message_type(MessageType::Request);
+        msg.set_seq(self.seq.fetch_add(1, Ordering::SeqCst));
+        msg.set_oneway(true);
+        msg.set_serialize_type(SerializeType::SerializeNone);
+        msg.set_compress_type(CompressType::CompressNone);
+        msg.service_path = service_path.to_owned();
+        msg.service_method = service_method.to_owned();
+        msg.metadata.replace(metadata.clone());
+        msg.payload = Bytes::from(data);
+
+        push_msg(&stream, &msg)
+    }
+
+    /// stops accepting connections, on the listeners which are served later as well. The
+    /// listeners are shut down and closed once their accept loops return, the connections go
+    /// on.
     pub fn close(&self) {
-        if let Some(raw_fd) = self.raw_fd {
+        self.stopped.store(true, Ordering::SeqCst);
+        for &raw_fd in self.raw_fds.lock().unwrap().iter() {
             unsafe {
-                libc::close(raw_fd);
+                libc::shutdown(raw_fd, libc::SHUT_RDWR);
             }
         }
     }
     fn process(
         thread_number: u32,
         service: Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
-        stream: TcpStream,
+        message_plugins: MessagePlugins,
+        conns: Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+        stream_services: Arc<RwLock<HashMap<String, RpcxStreamFn>>>,
+        subscriptions: Subscriptions,
+        file_transfer: Option<Arc<FileTransfer>>,
+        queue: Arc<RequestQueue>,
+        limits: MethodLimits,
+        idle_timeout: Option<Duration>,
+        read_limits: (MetadataLimits, usize),
+        stream_compression: Option<Arc<StreamCompression>>,
+        outbound_limit: (usize, BackpressurePolicy, Option<Duration>),
+        reactor: Option<ReactorQueue>,
+        conn: Conn,
     ) {
         let services_cloned = service;
+        let mut conn = conn;
+        let (max_queued_bytes, backpressure, write_timeout) = outbound_limit;
+        let peer_addr = conn.get_ref().peer_addr().ok();
+
+        // rpcx messages always start with the magic number, anything else is served as http,
+        // which upgrades to the WebSocket transport. The gateway isn't served over TLS.
+        let mut first = [0u8; 1];
+        if !conn.is_tls() {
+            // the client is connected while the first bytes are awaited, such as by warm-up
+            if let (Some(addr), Ok(conn)) = (peer_addr, conn.try_clone()) {
+                let writer = ConnWriter::new(conn, max_queued_bytes, backpressure);
+                conns.write().unwrap().insert(addr, Arc::new(writer));
+            }
+            let stream = conn.get_ref();
+            if let Ok(1) = stream.peek(&mut first) {
+                if first[0] != MAGIC_NUMBER {
+                    let dispatcher = Dispatcher {
+                        services: services_cloned.clone(),
+                        message_plugins: message_plugins.clone(),
+                        limits: limits.clone(),
+                        queue: queue.clone(),
+                        peer_addr: stream.peer_addr().ok(),
+                        local_addr: stream.local_addr().ok(),
+                    };
+                    let upgraded = gateway::serve(dispatcher, stream.try_clone().unwrap())
+                        .and_then(|buffered| WsStream::new(conn.try_clone().ok()?, buffered).ok());
+                    match upgraded {
+                        Some(ws) => conn = Conn::Ws(ws),
+                        None => {
+                            if let Some(addr) = peer_addr {
+                                conns.write().unwrap().remove(&addr);
+                            }
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        let stream = conn.get_ref();
         let local_stream = stream.try_clone().unwrap();
 
-        let mut pool = Pool::new(thread_number);
+        // responses and messages pushed by the server share this writer.
+        if let Err(err) = stream.set_write_timeout(write_timeout) {
+            eprintln!("failed to set the write timeout: {}", err);
+        }
+        let writer = Arc::new(ConnWriter::new(
+            conn.try_clone().unwrap(),
+            max_queued_bytes,
+            backpressure,
+        ));
+        let local_addr = stream.local_addr().ok();
+        if let Some(addr) = peer_addr {
+            conns.write().unwrap().insert(addr, writer.clone());
+        }
+        let streams: Streams = Arc::new(Mutex::new(HashMap::new()));
+        if let Some(timeout) = idle_timeout {
+            if let Err(err) = stream.set_read_timeout(Some(timeout)) {
+                eprintln!("failed to set the idle timeout: {}", err);
+            }
+        }
+
+        // the requests waiting for a worker, taken by priority
+        let jobs = Arc::new(PriorityJobs::default());
+        // the executor of the reactor runs the jobs instead of the pool if there is one
+        let mut pool = Pool::new(if reactor.is_some() { 1 } else { thread_number });
         pool.scoped(|scoped| {
-            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let execute = |job: Job| match &reactor {
+                Some(reactor) => reactor.execute(job),
+                None => scoped.execute(job),
+            };
+            let conn: ConnReader = Box::new(conn.try_clone().unwrap());
+            let (metadata_limits, max_message_len) = read_limits;
+            let mut reader = MessageReader::new(conn);
+            reader.set_metadata_limits(metadata_limits);
+            reader.set_max_message_len(max_message_len);
+            let mut first = true;
             loop {
-                let mut msg = Message::new();
-                match msg.decode(&mut reader) {
-                    Ok(()) => {
-                        let service_path = &msg.service_path;
-                        let service_method = &msg.service_method;
-                        let key = format!("{}.{}", service_path, service_method);
-                        let map = &services_cloned.read().unwrap();
-                        match map.get(&key) {
-                            Some(box_fn) => {
-                                let f = **box_fn;
-                                let local_stream_in_child = local_stream.try_clone().unwrap();
-
-                                scoped.execute(move || {
-                                    invoke_fn(local_stream_in_child.try_clone().unwrap(), msg, f)
-                                });
+                match reader.read_msg() {
+                    Ok(msg) => {
+                        writer.touch();
+                        let is_first = std::mem::replace(&mut first, false);
+                        // heartbeats are answered at once like the Go server, so the
+                        // keepalive of clients is not delayed by busy workers
+                        if msg.is_heartbeat() {
+                            writer.heartbeat();
+                            let _ = write_msg(&writer, &heartbeat_reply(&msg));
+                            continue;
+                        }
+                        if msg.get_compress_type().is_none() {
+                            reflection::reject_compress_type(&writer, msg);
+                            continue;
+                        }
+                        if let Some(kind) = get_stream_frame(&msg) {
+                            if kind == STREAM_OPEN {
+                                execute(stream::open(&stream_services, &streams, &writer, msg));
+                            } else {
+                                stream::on_frame(&streams, &kind, msg);
+                            }
+                            continue;
+                        }
+                        if msg.service_path == PUBSUB_SERVICE {
+                            if let Some(addr) = peer_addr {
+                                pubsub::handle_subscription(
+                                    &subscriptions,
+                                    &message_plugins,
+                                    addr,
+                                    local_addr,
+                                    &writer,
+                                    msg,
+                                );
                             }
-                            None => {
-                                let err = format!("service {} not found", key);
-                                let reply_msg = msg.get_reply().unwrap();
-                                let mut metadata = reply_msg.metadata.borrow_mut();
-                                (*metadata).insert(SERVICE_ERROR.to_string(), err);
-                                drop(metadata);
-                                let data = reply_msg.encode();
-                                let mut writer = BufWriter::new(local_stream.try_clone().unwrap());
-                                writer.write_all(&data).unwrap();
-                                writer.flush().unwrap();
+                            continue;
+                        }
+                        if msg.service_path == REFLECTION_SERVICE
+                            && msg.service_method == REFLECTION_STREAM_COMPRESSION
+                        {
+                            let negotiated = compression::negotiate(
+                                &stream_compression,
+                                is_first,
+                                &writer,
+                                &msg,
+                            );
+                            // the bytes after the request, buffered or not, are compressed
+                            let decoded = match negotiated {
+                                Ok(Some(compression)) => compression.decoder(reader.into_inner()),
+                                Ok(None) => continue,
+                                Err(err) => Err(err),
+                            };
+                            match decoded {
+                                Ok(conn) => {
+                                    reader = MessageReader::new(conn);
+                                    reader.set_metadata_limits(metadata_limits);
+                                    reader.set_max_message_len(max_message_len);
+                                }
+                                Err(err) => {
+                                    eprintln!("failed to compress the stream: {}", err);
+                                    let _ = local_stream.shutdown(Shutdown::Both);
+                                    return;
+                                }
+                            }
+                            continue;
+                        }
+                        if msg.service_path == REFLECTION_SERVICE {
+                            reflection::handle_msg(&services_cloned, &writer, msg);
+                            continue;
+                        }
+                        if msg.service_path == FILE_TRANSFER_SERVICE {
+                            filetransfer::handle_msg(
+                                &file_transfer,
+                                &message_plugins,
+                                peer_addr,
+                                local_addr,
+                                &writer,
+                                msg,
+                            );
+                            continue;
+                        }
+
+                        let priority = get_priority(&msg.metadata.borrow());
+                        if !queue.push(priority) {
+                            queue::reject_busy(&writer, &msg);
+                            continue;
+                        }
+                        set_conn_addrs(&msg, peer_addr, local_addr);
+                        let services_in_child = services_cloned.clone();
+                        let plugins_in_child = message_plugins.clone();
+                        let writer_in_child = writer.clone();
+                        let queue_in_child = queue.clone();
+                        let limits_in_child = limits.clone();
+                        let received = Instant::now();
+
+                        writer.start_request(&msg, received);
+                        jobs.push(
+                            priority,
+                            Box::new(move || {
+                                queue_in_child.pop();
+                                invoke_fn(
+                                    writer_in_child,
+                                    &services_in_child,
+                                    &plugins_in_child,
+                                    &limits_in_child,
+                                    msg,
+                                    received,
+                                )
+                            }),
+                        );
+                        let jobs_in_child = jobs.clone();
+                        execute(Box::new(move || {
+                            if let Some(job) = jobs_in_child.pop() {
+                                job();
                             }
+                        }));
+                    }
+                    Err(ref err) if idle::is_timeout(err) => {
+                        // the client is waiting for the server, not gone
+                        if writer.in_flight() > 0 || !streams.lock().unwrap().is_empty() {
+                            continue;
+                        }
+                        if let Some(addr) = peer_addr {
+                            println!("client {} is idle, closing it", addr);
                         }
+                        let _ = local_stream.shutdown(Shutdown::Both);
+                        return;
                     }
                     Err(err) => {
                         eprintln!("failed to read: {}", err.to_string());
@@ -186,26 +831,261 @@ impl Server {
                 }
             }
         });
+
+        stream::close_all(&streams);
+        if let Some(addr) = peer_addr {
+            conns.write().unwrap().remove(&addr);
+            subscriptions.write().unwrap().remove(&addr);
+        }
     }
 }
 
-fn invoke_fn(stream: TcpStream, msg: Message, f: RpcxFn) {
+/// handles the requests of the protocols served besides rpcx, such as http, JSON-RPC and
+/// gRPC, like the requests of rpcx connections: they are shed when the request queue is full
+/// and pass the message plugins and the limits of their methods.
+#[derive(Clone)]
+pub(crate) struct Dispatcher {
+    services: Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    message_plugins: MessagePlugins,
+    limits: MethodLimits,
+    queue: Arc<RequestQueue>,
+    // the addresses of the connection the requests are read from
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl Server {
+    pub(crate) fn dispatcher(&self) -> Dispatcher {
+        Dispatcher {
+            services: self.services.clone(),
+            message_plugins: self.message_plugins.clone(),
+            limits: self.limits.clone(),
+            queue: self.queue.clone(),
+            peer_addr: None,
+            local_addr: None,
+        }
+    }
+}
+
+impl Dispatcher {
+    /// returns the dispatcher of the requests read from a connection, whose addresses the
+    /// handlers and the plugins see.
+    pub(crate) fn conn(
+        &self,
+        peer_addr: Option<SocketAddr>,
+        local_addr: Option<SocketAddr>,
+    ) -> Self {
+        Dispatcher {
+            peer_addr,
+            local_addr,
+            ..self.clone()
+        }
+    }
+
+    /// returns whether a function is registered as `key`, such as `Arith.Mul`.
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.services.read().unwrap().contains_key(key)
+    }
+
+    /// handles the request on this thread and returns its reply. It takes a slot of the
+    /// request queue while it is handled, since it doesn't wait for a worker of a pool.
+    pub(crate) fn call(&self, msg: &mut Message) -> Message {
+        if !self.queue.push(get_priority(&msg.metadata.borrow())) {
+            return queue::busy_reply(msg);
+        }
+        set_conn_addrs(msg, self.peer_addr, self.local_addr);
+        let reply_msg = dispatch(
+            &self.services,
+            &self.message_plugins,
+            &self.limits,
+            msg,
+            Instant::now(),
+        );
+        self.queue.pop();
+        reply_msg
+    }
+}
+
+/// returns the serialize type of a request, which fails for the types this server doesn't know.
+pub(crate) fn serialize_type(msg: &Message) -> Result<SerializeType> {
+    msg.get_serialize_type()
+        .ok_or_else(|| Error::new(ErrorKind::Protocol, "unknown serialize type"))
+}
+
+/// finds the registered function for `msg`, invokes it and builds the reply. `received` is
+/// when the request was read, from which the deadline of the request is counted.
+pub(crate) fn handle_msg(
+    services: &Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    msg: &Message,
+    received: Instant,
+) -> Message {
     let mut reply_msg = msg.get_reply().unwrap();
-    let reply = f(&msg.payload, msg.get_serialize_type().unwrap()).unwrap();
-    reply_msg.payload = reply;
-    let data = reply_msg.encode();
 
-    let mut writer = BufWriter::new(stream.try_clone().unwrap());
-    match writer.write_all(&data) {
-        Ok(()) => {}
-        Err(_err) => {}
+    let key = format!("{}.{}", msg.service_path, msg.service_method);
+    let f = services.read().unwrap().get(&key).map(|box_fn| **box_fn);
+    let rt = match f {
+        Some(f) => serialize_type(msg).and_then(|st| {
+            context::scope(Context::from_request(msg, received), || f(&msg.payload, st))
+        }),
+        None => Err(Error::new(
+            ErrorKind::Server,
+            format!("service {} not found", key),
+        )),
+    };
+
+    match rt {
+        Ok(payload) => reply_msg.payload = Bytes::from(payload),
+        Err(err) => err.set_reply(&mut reply_msg),
     }
-    match writer.flush() {
-        Ok(()) => {}
-        Err(_err) => {}
+
+    reply_msg
+}
+
+// echoes a heartbeat back as its response.
+fn heartbeat_reply(msg: &Message) -> Message {
+    let mut reply = msg.get_reply().unwrap();
+    reply.set_heartbeat(true);
+    reply.metadata.replace(msg.metadata.borrow().clone());
+    reply.payload = msg.payload.clone();
+    reply
+}
+
+// sets the addresses of the connection in the metadata handlers see, like rpcx-go.
+fn set_conn_addrs(msg: &Message, peer_addr: Option<SocketAddr>, local_addr: Option<SocketAddr>) {
+    let mut metadata = msg.metadata.borrow_mut();
+    if let Some(addr) = peer_addr {
+        metadata.insert(REMOTE_CONN_ADDR.to_owned(), addr.to_string());
+    }
+    if let Some(addr) = local_addr {
+        metadata.insert(SERVER_ADDRESS.to_owned(), addr.to_string());
     }
 }
 
+/// handles a request of the builtin services, which are not dispatched to registered
+/// functions, by `f` and returns its reply. Like `dispatch`, `f` runs once the request passes
+/// the `post_read_request` hook of the plugins, such as the authentication, and the reply
+/// passes their `pre_write_response` hook, which releases what they took for the request.
+pub(crate) fn handle_builtin<F>(
+    message_plugins: &MessagePlugins,
+    msg: &mut Message,
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    f: F,
+) -> Message
+where
+    F: FnOnce(&Message) -> Result<Vec<u8>>,
+{
+    set_conn_addrs(msg, peer_addr, local_addr);
+    let plugins = message_plugins.read().unwrap();
+    let rt = plugins
+        .iter()
+        .try_for_each(|p| p.post_read_request(msg))
+        .and_then(|_| f(msg));
+    let mut reply_msg = msg.get_reply().unwrap();
+    match rt {
+        Ok(payload) => reply_msg.payload = Bytes::from(payload),
+        Err(err) => err.set_reply(&mut reply_msg),
+    }
+    if let Err(err) = plugins
+        .iter()
+        .try_for_each(|p| p.pre_write_response(msg, &mut reply_msg))
+    {
+        reply_msg = msg.get_reply().unwrap();
+        err.set_reply(&mut reply_msg);
+    }
+    reply_msg
+}
+
+/// writes a message to the connection shared by responses and pushed messages.
+pub(crate) fn write_msg(writer: &Arc<ConnWriter>, msg: &Message) -> Result<()> {
+    writer.write_msg(msg)
+}
+
+/// pushes a message to the connection, failing at once if its outbound queue is full.
+pub(crate) fn push_msg(writer: &Arc<ConnWriter>, msg: &Message) -> Result<()> {
+    writer.push_msg(msg)
+}
+
+fn invoke_fn(
+    writer: Arc<ConnWriter>,
+    services: &Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    message_plugins: &MessagePlugins,
+    limits: &MethodLimits,
+    mut msg: Message,
+    received: Instant,
+) {
+    let reply_msg = dispatch(services, message_plugins, limits, &mut msg, received);
+    if !msg.is_oneway() {
+        if let Err(err) = write_msg(&writer, &reply_msg) {
+            eprintln!(
+                "failed to reply {}.{}: {}",
+                msg.service_path, msg.service_method, err
+            );
+        }
+    }
+    writer.finish_request(msg.get_seq());
+}
+
+// runs the message plugins and the handler of a request and returns its reply.
+fn dispatch(
+    services: &Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    message_plugins: &MessagePlugins,
+    limits: &MethodLimits,
+    msg: &mut Message,
+    received: Instant,
+) -> Message {
+    let plugins = message_plugins.read().unwrap();
+    let mut reply_msg = match plugins.iter().try_for_each(|p| p.post_read_request(msg)) {
+        Ok(()) => match plugins.iter().find_map(|p| p.intercept_request(msg)) {
+            Some(payload) => {
+                let mut reply_msg = msg.get_reply().unwrap();
+                reply_msg.payload = Bytes::from(payload);
+                reply_msg
+            }
+            None => match plugins.iter().find_map(|p| p.intercept_reply(msg)) {
+                Some(reply_msg) => reply_msg,
+                None => limit::handle_msg_limited(limits, services, msg, received),
+            },
+        },
+        Err(err) => {
+            let mut reply_msg = msg.get_reply().unwrap();
+            err.set_reply(&mut reply_msg);
+            reply_msg
+        }
+    };
+    if let Err(err) = plugins
+        .iter()
+        .try_for_each(|p| p.pre_write_response(msg, &mut reply_msg))
+    {
+        reply_msg = msg.get_reply().unwrap();
+        err.set_reply(&mut reply_msg);
+    }
+    drop(plugins);
+    reply_msg
+}
+
+// handles the requests of the clients of this process, see `register_local_server`. They run
+// on the threads of the callers and skip the queue of the server.
+fn local_handler(server: &Server, addr: SocketAddr) -> LocalHandler {
+    let services = server.services.clone();
+    let message_plugins = server.message_plugins.clone();
+    let limits = server.limits.clone();
+    Arc::new(move |mut msg: Message| {
+        set_conn_addrs(&msg, None, Some(addr));
+        let reply_msg = dispatch(
+            &services,
+            &message_plugins,
+            &limits,
+            &mut msg,
+            Instant::now(),
+        );
+        if msg.is_oneway() {
+            return None;
+        }
+        Some(reply_msg)
+    })
+}
+
 #[macro_export]
 macro_rules! register_func {
     ($rpc_server:expr, $service_path:expr, $service_method:expr, $service_fn:expr, $meta:expr, $arg_type:ty, $reply_type:ty) => {{
@@ -224,3 +1104,15 @@ macro_rules! register_func {
         );
     }};
 }
+
+// sets a parameter of the url-encoded metadata of a service, replacing its previous value.
+fn set_meta_param(meta: &str, key: &str, value: &str) -> String {
+    let prefix = format!("{}=", key);
+    let mut params: Vec<String> = meta
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with(&prefix) && *p != key)
+        .map(|p| p.to_owned())
+        .collect();
+    params.push(format!("{}{}", prefix, value));
+    params.join("&")
+}
@@ -0,0 +1,176 @@
+use super::{handle_msg, MessagePlugin, RpcxFn, Server};
+use rpcx_protocol::*;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    // when the request the worker thread is handling took a slot of the adaptive limit
+    static ADMITTED_AT: Cell<Option<Instant>> = Cell::new(None);
+}
+
+/// what happens to the calls of a method beyond its concurrency limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitPolicy {
+    /// fails the call with `ErrorKind::ServerBusy` at once.
+    Reject,
+    /// waits for a running call to finish, at most for the duration, then fails the call with
+    /// `ErrorKind::ServerBusy`. The waiting call holds a worker of its connection.
+    Queue(Duration),
+}
+
+#[derive(Debug)]
+pub(crate) struct MethodLimit {
+    max: usize,
+    policy: LimitPolicy,
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+pub(crate) type MethodLimits = Arc<RwLock<HashMap<String, Arc<MethodLimit>>>>;
+
+// releases the slot of a running call when it is dropped.
+struct Permit<'a>(&'a MethodLimit);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() -= 1;
+        self.0.finished.notify_one();
+    }
+}
+
+impl MethodLimit {
+    fn acquire(&self, key: &str) -> Result<Permit<'_>> {
+        let mut running = self.running.lock().unwrap();
+        if let LimitPolicy::Queue(timeout) = self.policy {
+            let deadline = Instant::now() + timeout;
+            while *running >= self.max {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                running = self
+                    .finished
+                    .wait_timeout(running, deadline - now)
+                    .unwrap()
+                    .0;
+            }
+        }
+        if *running >= self.max {
+            return Err(Error::new(
+                ErrorKind::ServerBusy,
+                format!("{} reached its limit of {} concurrent calls", key, self.max),
+            ));
+        }
+        *running += 1;
+        Ok(Permit(self))
+    }
+}
+
+impl Server {
+    /// limits the calls of the method which run at the same time to `max`, for methods which
+    /// are too expensive to run many at once. The calls beyond it are handled by `policy`.
+    ///
+    /// It is usually called right after the method is registered.
+    pub fn set_concurrency_limit(
+        &mut self,
+        service_path: &str,
+        service_method: &str,
+        max: usize,
+        policy: LimitPolicy,
+    ) {
+        let limit = MethodLimit {
+            max,
+            policy,
+            running: Mutex::new(0),
+            finished: Condvar::new(),
+        };
+        self.limits.write().unwrap().insert(
+            format!("{}.{}", service_path, service_method),
+            Arc::new(limit),
+        );
+    }
+
+    /// registers the function and limits its concurrent calls, see `set_concurrency_limit`.
+    pub fn register_fn_with_limit(
+        &mut self,
+        service_path: String,
+        service_method: String,
+        meta: String,
+        f: RpcxFn,
+        max: usize,
+        policy: LimitPolicy,
+    ) {
+        self.set_concurrency_limit(&service_path, &service_method, max, policy);
+        self.register_fn(service_path, service_method, meta, f);
+    }
+}
+
+/// is `handle_msg` within the concurrency limit of the method.
+pub(crate) fn handle_msg_limited(
+    limits: &MethodLimits,
+    services: &Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    msg: &Message,
+    received: Instant,
+) -> Message {
+    let key = format!("{}.{}", msg.service_path, msg.service_method);
+    let limit = limits.read().unwrap().get(&key).cloned();
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return handle_msg(services, msg, received),
+    };
+
+    // the permit must be released before the limit it borrows
+    let permit = limit.acquire(&key);
+    match permit {
+        Ok(_permit) => handle_msg(services, msg, received),
+        Err(err) => {
+            let mut reply_msg = msg.get_reply().unwrap();
+            err.set_reply(&mut reply_msg);
+            reply_msg
+        }
+    }
+}
+
+/// limits the requests the server handles at once by an `AdaptiveLimit`, which follows the
+/// latencies of the requests. The requests beyond the limit fail with
+/// `ErrorKind::ServerBusy` at once, so clients back off or fail over before the server
+/// collapses. Requests which exceed their deadline shrink the limit like dropped ones.
+///
+/// It limits the requests which pass the plugins added before it.
+pub struct AdaptiveLimitPlugin {
+    limit: Arc<AdaptiveLimit>,
+}
+
+impl AdaptiveLimitPlugin {
+    pub fn new(limit: Arc<AdaptiveLimit>) -> Self {
+        AdaptiveLimitPlugin { limit }
+    }
+}
+
+impl MessagePlugin for AdaptiveLimitPlugin {
+    fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+        if !self.limit.try_acquire() {
+            return Err(Error::new(
+                ErrorKind::ServerBusy,
+                format!(
+                    "server reached its adaptive limit of {} concurrent requests",
+                    self.limit.limit()
+                ),
+            ));
+        }
+        ADMITTED_AT.with(|admitted_at| admitted_at.set(Some(Instant::now())));
+        Ok(())
+    }
+
+    fn pre_write_response(&self, _req: &Message, res: &mut Message) -> Result<()> {
+        if let Some(admitted_at) = ADMITTED_AT.with(Cell::take) {
+            let dropped = Error::from_reply(res).map(|err| err.kind()) == Some(ErrorKind::Timeout);
+            self.limit.release(admitted_at.elapsed(), dropped);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use super::{ConnWriter, MessagePlugin, RequestQueue, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+// how often the CPU usage of the process is sampled.
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// attaches the load of the server to the metadata of replies.
+struct LoadReportPlugin {
+    queue: Arc<RequestQueue>,
+    conns: Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+    cpu: Mutex<CpuSample>,
+}
+
+// the CPU time of the process at the last sample and the usage since the one before.
+struct CpuSample {
+    at: Instant,
+    cpu_time: Duration,
+    usage: u32,
+}
+
+impl CpuSample {
+    // returns the CPU usage in percent of all the cores, sampled at most once per interval.
+    fn usage(&mut self) -> u32 {
+        let elapsed = self.at.elapsed();
+        if elapsed < CPU_SAMPLE_INTERVAL {
+            return self.usage;
+        }
+        let cpu_time = process_cpu_time();
+        let used = cpu_time.checked_sub(self.cpu_time).unwrap_or_default();
+        let usage = used.as_secs_f64() / elapsed.as_secs_f64() / num_cpus::get() as f64;
+        self.at = Instant::now();
+        self.cpu_time = cpu_time;
+        self.usage = (usage * 100.0).round().min(100.0) as u32;
+        self.usage
+    }
+}
+
+// returns the user and system CPU time of the process.
+fn process_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::default();
+    }
+    let micros = |tv: libc::timeval| tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+impl MessagePlugin for LoadReportPlugin {
+    fn pre_write_response(&self, _req: &Message, res: &mut Message) -> Result<()> {
+        let in_flight: usize = self
+            .conns
+            .read()
+            .unwrap()
+            .values()
+            .map(|conn| conn.in_flight())
+            .sum();
+        let load = Load {
+            cpu: self.cpu.lock().unwrap().usage(),
+            // the request being replied is not counted
+            queue: self.queue.depth() + in_flight.saturating_sub(1),
+        };
+        set_load(&mut res.metadata.borrow_mut(), &load);
+        Ok(())
+    }
+}
+
+impl Server {
+    /// attaches the load of the server, its CPU usage and the requests waiting or being
+    /// handled, to the metadata of replies. Clients with `SelectMode::LoadAware` send less
+    /// traffic to loaded servers.
+    pub fn enable_load_report(&mut self) {
+        let plugin = LoadReportPlugin {
+            queue: self.queue.clone(),
+            conns: self.conns.clone(),
+            cpu: Mutex::new(CpuSample {
+                at: Instant::now(),
+                cpu_time: process_cpu_time(),
+                usage: 0,
+            }),
+        };
+        self.add_message_plugin(Box::new(plugin));
+    }
+}
@@ -0,0 +1,195 @@
+use super::{ConnWriter, MessagePlugin, RequestQueue, Server};
+use rpcx_protocol::*;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    // the start of the request the worker thread is handling, the hooks of a request are
+    // invoked by the same worker.
+    static REQUEST_START: Cell<Option<Instant>> = Cell::new(None);
+}
+
+/// the default threshold of the Apdex of methods, see `Server::set_apdex_threshold`.
+pub const APDEX_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// the upper bounds of the latency buckets of `MethodStats` in seconds.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// the requests of a method handled by the server, see `Server::method_stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodStats {
+    pub service_path: String,
+    pub service_method: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// the successful requests which take the Apdex threshold at most.
+    pub satisfied: u64,
+    /// the successful requests which take more than the threshold and 4 times it at most.
+    pub tolerating: u64,
+    /// the timed requests by latency. `buckets[i]` counts the latencies above the bound of
+    /// the previous bucket and `LATENCY_BUCKETS[i]` at most, the last one the latencies above
+    /// all the bounds.
+    pub buckets: Vec<u64>,
+}
+
+impl MethodStats {
+    /// returns the ratio of the requests which succeed, 1 if there is none.
+    pub fn success_ratio(&self) -> f64 {
+        if self.requests == 0 {
+            return 1.0;
+        }
+        (self.requests - self.errors) as f64 / self.requests as f64
+    }
+
+    /// returns the Apdex score of the method, 1 if there is no request. Failed requests
+    /// count as frustrated.
+    pub fn apdex(&self) -> f64 {
+        if self.requests == 0 {
+            return 1.0;
+        }
+        (self.satisfied as f64 + self.tolerating as f64 / 2.0) / self.requests as f64
+    }
+
+    fn record(&mut self, latency: Option<Duration>, success: bool, threshold: Duration) {
+        self.requests += 1;
+        if !success {
+            self.errors += 1;
+        }
+        let latency = match latency {
+            Some(latency) => latency,
+            None => return,
+        };
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        let secs = latency.as_secs_f64();
+        let i = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[i] += 1;
+        if success && latency <= threshold {
+            self.satisfied += 1;
+        } else if success && latency <= threshold * 4 {
+            self.tolerating += 1;
+        }
+    }
+}
+
+// the stats of the methods keyed by `<service_path>.<service_method>`, and the threshold of
+// their Apdex.
+pub(crate) struct MethodStatsTable {
+    threshold: RwLock<Duration>,
+    methods: RwLock<HashMap<String, MethodStats>>,
+}
+
+impl Default for MethodStatsTable {
+    fn default() -> Self {
+        MethodStatsTable {
+            threshold: RwLock::new(APDEX_THRESHOLD),
+            methods: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl MethodStatsTable {
+    // records the request and returns the updated stats of its method.
+    fn record(&self, req: &Message, latency: Option<Duration>, success: bool) -> MethodStats {
+        let threshold = *self.threshold.read().unwrap();
+        let key = format!("{}.{}", req.service_path, req.service_method);
+        let mut methods = self.methods.write().unwrap();
+        let stats = methods.entry(key).or_insert_with(|| MethodStats {
+            service_path: req.service_path.clone(),
+            service_method: req.service_method.clone(),
+            ..Default::default()
+        });
+        stats.record(latency, success, threshold);
+        stats.clone()
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<MethodStats> {
+        let mut stats: Vec<MethodStats> = self.methods.read().unwrap().values().cloned().collect();
+        stats.sort_by(|a, b| {
+            (&a.service_path, &a.service_method).cmp(&(&b.service_path, &b.service_method))
+        });
+        stats
+    }
+}
+
+// reports the requests of the server to a metrics sink.
+struct MetricsPlugin {
+    sink: Arc<dyn MetricsSink>,
+    method_stats: Arc<MethodStatsTable>,
+    queue: Arc<RequestQueue>,
+    conns: Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+}
+
+impl MessagePlugin for MetricsPlugin {
+    fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+        REQUEST_START.with(|start| start.set(Some(Instant::now())));
+        Ok(())
+    }
+
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        let success = res.get_message_status_type() != Some(MessageStatusType::Error);
+        let labels = [
+            ("service", req.service_path.as_str()),
+            ("method", req.service_method.as_str()),
+            ("status", status_label(success)),
+        ];
+        self.sink.counter(SERVER_REQUESTS, 1, &labels);
+        let latency = REQUEST_START.with(Cell::take).map(|start| start.elapsed());
+        if let Some(latency) = latency {
+            self.sink
+                .histogram(SERVER_REQUEST_DURATION, latency.as_secs_f64(), &labels);
+        }
+        let stats = self.method_stats.record(req, latency, success);
+        let labels = [
+            ("service", req.service_path.as_str()),
+            ("method", req.service_method.as_str()),
+        ];
+        self.sink.gauge(SERVER_METHOD_APDEX, stats.apdex(), &labels);
+        self.sink
+            .gauge(SERVER_METHOD_SUCCESS_RATIO, stats.success_ratio(), &labels);
+        let conns = self.conns.read().unwrap().len();
+        self.sink.gauge(SERVER_CONNECTIONS, conns as f64, &[]);
+        let queued = self.queue.depth();
+        self.sink.gauge(SERVER_QUEUED_REQUESTS, queued as f64, &[]);
+        Ok(())
+    }
+}
+
+impl Server {
+    /// reports the requests, the connections and the queue of the server to the sink, see
+    /// `SERVER_REQUESTS`. The requests which fail in the plugins added before are not timed.
+    ///
+    /// The requests of each method are counted by `method_stats` as well.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        let plugin = MetricsPlugin {
+            sink,
+            method_stats: self.method_stats.clone(),
+            queue: self.queue.clone(),
+            conns: self.conns.clone(),
+        };
+        self.add_message_plugin(Box::new(plugin));
+    }
+
+    /// sets the latency the Apdex of methods counts as satisfying, `APDEX_THRESHOLD` by
+    /// default. Requests which take 4 times it at most are tolerated.
+    pub fn set_apdex_threshold(&self, threshold: Duration) {
+        *self.method_stats.threshold.write().unwrap() = threshold;
+    }
+
+    /// returns the stats of the methods since the metrics sink is set, ordered by service and
+    /// method, to find which method regressed after a deploy.
+    pub fn method_stats(&self) -> Vec<MethodStats> {
+        self.method_stats.snapshot()
+    }
+}
@@ -0,0 +1,171 @@
+use super::Server;
+use rpcx_protocol::*;
+use std::{
+    env, mem,
+    os::unix::{ffi::OsStrExt, io::AsRawFd, net::UnixDatagram},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// sends a state such as `READY=1` to systemd by `NOTIFY_SOCKET`, see sd_notify(3). It does
+/// nothing if the process is not run by a service of `Type=notify`.
+pub fn sd_notify(state: &str) -> Result<()> {
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let path = path.as_bytes();
+
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    if path.is_empty() || path.len() >= addr.sun_path.len() {
+        return Err(Error::new(
+            ErrorKind::Config,
+            format!("invalid NOTIFY_SOCKET: {}", String::from_utf8_lossy(path)),
+        ));
+    }
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (i, b) in path.iter().enumerate() {
+        addr.sun_path[i] = *b as libc::c_char;
+    }
+    // a socket in the abstract namespace
+    if path[0] == b'@' {
+        addr.sun_path[0] = 0;
+    }
+    let addr_len = mem::size_of::<libc::sa_family_t>() + path.len();
+
+    let socket = UnixDatagram::unbound()?;
+    let sent = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            state.as_ptr() as *const libc::c_void,
+            state.len(),
+            0,
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            addr_len as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// returns how often systemd expects `WATCHDOG=1`, `None` if the watchdog of the service
+/// is off or is meant for another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+/// tells whether an accept loop is stuck with a connection, such as in a connect plugin,
+/// rather than waiting for the next one.
+#[derive(Debug)]
+pub(crate) struct AcceptWatch {
+    start: Instant,
+    // the milliseconds since `start` when the loop took a connection plus one, 0 while it
+    // waits for connections
+    busy_since: AtomicU64,
+    done: AtomicBool,
+}
+
+impl AcceptWatch {
+    pub(crate) fn new() -> Self {
+        AcceptWatch {
+            start: Instant::now(),
+            busy_since: AtomicU64::new(0),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    /// marks the loop busy until the guard is dropped.
+    pub(crate) fn busy(&self) -> AcceptBusy<'_> {
+        let now = self.start.elapsed().as_millis() as u64 + 1;
+        self.busy_since.store(now, Ordering::SeqCst);
+        AcceptBusy(self)
+    }
+
+    // returns whether the loop has been busy with a connection for longer than `timeout`.
+    fn is_stuck(&self, timeout: Duration) -> bool {
+        match self.busy_since.load(Ordering::SeqCst) {
+            0 => false,
+            since => {
+                let now = self.start.elapsed().as_millis() as u64 + 1;
+                now.saturating_sub(since) > timeout.as_millis() as u64
+            }
+        }
+    }
+
+    /// stops the watchdog of the loop.
+    pub(crate) fn finish(&self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
+
+pub(crate) struct AcceptBusy<'a>(&'a AcceptWatch);
+
+impl Drop for AcceptBusy<'_> {
+    fn drop(&mut self) {
+        self.0.busy_since.store(0, Ordering::SeqCst);
+    }
+}
+
+// pets the watchdog of systemd as long as the accept loop is not stuck, so systemd restarts
+// the server once it is.
+fn pet_watchdog(watch: Arc<AcceptWatch>, interval: Duration) {
+    while !watch.done.load(Ordering::SeqCst) {
+        thread::sleep(interval / 2);
+        if watch.is_stuck(interval) {
+            eprintln!("the accept loop is stuck, the watchdog of systemd is not notified");
+            continue;
+        }
+        if let Err(err) = sd_notify("WATCHDOG=1") {
+            eprintln!("failed to notify the watchdog of systemd: {}", err);
+        }
+    }
+}
+
+impl Server {
+    /// notifies systemd with `READY=1` once the server accepts connections, when the
+    /// services registered before `start` are registered to the registries, and with
+    /// `STOPPING=1` when it stops accepting them. If the watchdog of the service is on, it is
+    /// notified as long as the accept loop is not stuck, so systemd restarts a wedged server.
+    ///
+    /// It takes effect for the service units of `Type=notify`.
+    pub fn enable_sd_notify(&mut self) {
+        self.sd_notify = true;
+    }
+
+    // notifies systemd that the accept loop of `watch` is ready, and starts its watchdog.
+    pub(crate) fn notify_ready(&self, watch: &Arc<AcceptWatch>) {
+        if !self.sd_notify {
+            return;
+        }
+        if let Err(err) = sd_notify("READY=1") {
+            eprintln!("failed to notify systemd: {}", err);
+        }
+        if let Some(interval) = watchdog_interval() {
+            let watch = watch.clone();
+            thread::spawn(move || pet_watchdog(watch, interval));
+        }
+    }
+
+    // notifies systemd that the accept loop of `watch` stopped.
+    pub(crate) fn notify_stopping(&self, watch: &AcceptWatch) {
+        watch.finish();
+        if self.sd_notify {
+            let _ = sd_notify("STOPPING=1");
+        }
+    }
+}
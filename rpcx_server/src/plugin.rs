@@ -1,20 +1,40 @@
-use super::{RpcxFn, Server};
+use super::{RpcxFn, Server, ServerConfig};
+#[cfg(feature = "etcd-registry")]
 use etcd::{kv, Client};
+#[cfg(feature = "etcd-registry")]
 #[allow(unused_imports)]
 use futures::future::Future;
+#[cfg(feature = "etcd-registry")]
 use hyper::client::HttpConnector;
 use rpcx_protocol::*;
+use std::net::TcpStream;
+#[cfg(feature = "etcd-registry")]
 use std::{
     collections::HashMap,
-    net::TcpStream,
     sync::{Arc, RwLock},
     thread,
     time::Duration,
 };
+#[cfg(feature = "etcd-registry")]
 use tokio::runtime::Runtime;
 
 impl Server {
-    pub fn add_register_plugin(&mut self, p: Box<dyn RegisterPlugin + Send + Sync>) {
+    /// adds a registry the services are registered to. Plugins can be added alongside each
+    /// other, for example to register to the old and the new registry during a migration,
+    /// and each keeps its own heartbeat. The services registered before are registered to
+    /// the plugin at once, with the same metadata as to the other registries.
+    pub fn add_register_plugin(&mut self, mut p: Box<dyn RegisterPlugin + Send + Sync>) {
+        let metas = self.metas.read().unwrap();
+        for (key, f) in self.services.read().unwrap().iter() {
+            let (service_path, service_method) = match key.rfind('.') {
+                Some(i) => (&key[..i], &key[i + 1..]),
+                None => continue,
+            };
+            let meta = metas.get(service_path).cloned().unwrap_or_default();
+            if let Err(err) = p.register_fn(service_path, service_method, meta, **f) {
+                eprintln!("{}", err);
+            }
+        }
         let mut plugins = self.register_plugins.write().unwrap();
         plugins.push(p);
     }
@@ -22,6 +42,10 @@ impl Server {
         let mut plugins = self.connect_plugins.write().unwrap();
         plugins.push(p);
     }
+    pub fn add_message_plugin(&mut self, p: Box<dyn MessagePlugin + Send + Sync>) {
+        let mut plugins = self.message_plugins.write().unwrap();
+        plugins.push(p);
+    }
 }
 
 pub trait RegisterPlugin {
@@ -32,12 +56,61 @@ pub trait RegisterPlugin {
         meta: String,
         f: RpcxFn,
     ) -> Result<()>;
+
+    /// is invoked when the metadata of a registered service changes at runtime, for example by
+    /// `Server::set_weight`.
+    fn update_meta(&mut self, _service_path: &str, _meta: String) -> Result<()> {
+        Ok(())
+    }
+
+    /// is invoked when the server leaves the registries, for example by `Server::drain`.
+    /// The service is not registered again by the plugin afterwards.
+    fn unregister(&mut self, _service_path: &str) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait ConnectPlugin {
     fn connected(&mut self, conn: &TcpStream) -> Result<()>;
 }
 
+/// intercepts the requests and responses of rpcx connections.
+///
+/// Plugins are invoked concurrently by the worker threads.
+pub trait MessagePlugin {
+    /// is invoked before the request is dispatched. The call fails with the returned error.
+    ///
+    /// No response is written for requests which are oneway after this hook.
+    fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+        Ok(())
+    }
+
+    /// is invoked after `post_read_request`, the payload returned by a plugin is replied
+    /// instead of invoking the handler.
+    fn intercept_request(&self, _req: &Message) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// is like `intercept_request` but the returned message is replied as it is, so plugins
+    /// such as proxies can reply the metadata and the errors of other servers.
+    fn intercept_reply(&self, _req: &Message) -> Option<Message> {
+        None
+    }
+
+    /// is invoked before the response is written. The call fails with the returned error.
+    fn pre_write_response(&self, _req: &Message, _res: &mut Message) -> Result<()> {
+        Ok(())
+    }
+
+    /// is invoked by `Server::reload` with the new config, so plugins such as authenticators
+    /// and rate limiters pick up their settings without dropping connections. The reload
+    /// fails with the returned error.
+    fn reload(&self, _config: &ServerConfig) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "etcd-registry")]
 #[allow(dead_code)]
 pub struct EtcdRegister {
     client: Client<HttpConnector>,
@@ -47,6 +120,7 @@ pub struct EtcdRegister {
     update_interval: Duration,
 }
 
+#[cfg(feature = "etcd-registry")]
 impl EtcdRegister {
     pub fn new(
         client: Client<HttpConnector>,
@@ -131,17 +205,18 @@ impl EtcdRegister {
             Err(err) => match &err[0] {
                 etcd::Error::Api(api_err) => {
                     if api_err.error_code != 105 {
-                        return Err(Error::from(format!("{:?}", err)));
+                        return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                     }
                 }
                 _ => {
-                    return Err(Error::from(format!("{:?}", err)));
+                    return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                 }
             },
         }
         Ok(())
     }
 }
+#[cfg(feature = "etcd-registry")]
 impl RegisterPlugin for EtcdRegister {
     fn register_fn(&mut self, service_path: &str, _: &str, meta: String, _: RpcxFn) -> Result<()> {
         if self.services.read().unwrap().get(service_path).is_some() {
@@ -162,11 +237,11 @@ impl RegisterPlugin for EtcdRegister {
             Err(err) => match &err[0] {
                 etcd::Error::Api(api_err) => {
                     if api_err.error_code != 105 {
-                        return Err(Error::from(format!("{:?}", err)));
+                        return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                     }
                 }
                 _ => {
-                    return Err(Error::from(format!("{:?}", err)));
+                    return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                 }
             },
         }
@@ -181,11 +256,11 @@ impl RegisterPlugin for EtcdRegister {
             Err(err) => match &err[0] {
                 etcd::Error::Api(api_err) => {
                     if api_err.error_code != 105 {
-                        return Err(Error::from(format!("{:?}", err)));
+                        return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                     }
                 }
                 _ => {
-                    return Err(Error::from(format!("{:?}", err)));
+                    return Err(Error::new(ErrorKind::Registry, format!("{:?}", err)));
                 }
             },
         }
@@ -204,19 +279,17 @@ impl RegisterPlugin for EtcdRegister {
             Err(err) => match &err[0] {
                 etcd::Error::Api(api_err) => {
                     if api_err.error_code != 105 {
-                        return Err(Error::from(format!(
-                            "failed to register:{}, err:{:?}",
-                            key.as_str(),
-                            err
-                        )));
+                        return Err(Error::new(
+                            ErrorKind::Registry,
+                            format!("failed to register:{}, err:{:?}", key.as_str(), err),
+                        ));
                     }
                 }
                 _ => {
-                    return Err(Error::from(format!(
-                        "failed to set:{}, err:{:?}",
-                        key.as_str(),
-                        err
-                    )));
+                    return Err(Error::new(
+                        ErrorKind::Registry,
+                        format!("failed to set:{}, err:{:?}", key.as_str(), err),
+                    ));
                 }
             },
         }
@@ -228,4 +301,49 @@ impl RegisterPlugin for EtcdRegister {
             .insert(service_path.to_owned(), meta);
         Ok(())
     }
+
+    fn update_meta(&mut self, service_path: &str, meta: String) -> Result<()> {
+        match self.services.write().unwrap().get_mut(service_path) {
+            Some(m) => *m = meta.clone(),
+            None => return Ok(()),
+        }
+        Self::refresh_fn(
+            &self.client,
+            self.base_path.clone(),
+            self.service_addr.clone(),
+            self.update_interval,
+            service_path,
+            meta.as_str(),
+        )
+    }
+
+    fn unregister(&mut self, service_path: &str) -> Result<()> {
+        // stops refreshing it first
+        if self
+            .services
+            .write()
+            .unwrap()
+            .remove(service_path)
+            .is_none()
+        {
+            return Ok(());
+        }
+        // "<base_path>/<service_path>/<service_addr>"
+        let key = format!("{}/{}/{}", self.base_path, service_path, self.service_addr);
+        let op = kv::delete(&self.client, key.as_str(), false);
+        match Runtime::new().unwrap().block_on(op) {
+            Ok(_) => println!("succeed to unregister: {}", key.as_str()),
+            Err(err) => match &err[0] {
+                // the key has expired
+                etcd::Error::Api(api_err) if api_err.error_code == 100 => {}
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::Registry,
+                        format!("failed to unregister:{}, err:{:?}", key.as_str(), err),
+                    ));
+                }
+            },
+        }
+        Ok(())
+    }
 }
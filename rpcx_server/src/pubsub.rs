@@ -0,0 +1,221 @@
+use super::{handle_builtin, push_msg, write_msg, ConnWriter, MessagePlugins, Server};
+use bytes::Bytes;
+use rpcx_protocol::*;
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// the number of messages a topic can queue before publishers are held back.
+pub const TOPIC_QUEUE_SIZE: usize = 1024;
+/// how long the delivery thread of a topic waits for messages before it stops. It is started
+/// again by the next message of the topic.
+pub const TOPIC_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// subscription patterns of the connected clients.
+pub(crate) type Subscriptions = Arc<RwLock<HashMap<SocketAddr, HashSet<String>>>>;
+// delivery queues of the published topics.
+pub(crate) type Topics = Arc<Mutex<HashMap<String, SyncSender<Published>>>>;
+
+pub(crate) struct Published {
+    metadata: Metadata,
+    data: Vec<u8>,
+}
+
+impl Server {
+    /// publishes a message to the clients subscribed to a pattern matching the topic.
+    ///
+    /// Messages of a topic are delivered in order by a background thread, at most once: a client
+    /// which fails to receive a message doesn't get it again. This call blocks while the queue
+    /// of the topic is full.
+    pub fn publish(&self, topic: &str, metadata: &Metadata, data: Vec<u8>) -> Result<()> {
+        let published = Published {
+            metadata: metadata.clone(),
+            data,
+        };
+        match self.queue_published(topic, published)? {
+            None => Ok(()),
+            Some((sender, published)) => sender
+                .send(published)
+                .map_err(|_| Error::new(ErrorKind::Server, format!("topic {} is closed", topic))),
+        }
+    }
+
+    /// like `publish` but fails instead of blocking when the queue of the topic is full.
+    pub fn try_publish(&self, topic: &str, metadata: &Metadata, data: Vec<u8>) -> Result<()> {
+        let published = Published {
+            metadata: metadata.clone(),
+            data,
+        };
+        match self.queue_published(topic, published)? {
+            None => Ok(()),
+            Some(_) => Err(Error::new(
+                ErrorKind::Server,
+                format!("topic {} is full", topic),
+            )),
+        }
+    }
+
+    /// returns the subscription patterns of a connected client.
+    pub fn subscriptions(&self, conn: &SocketAddr) -> Vec<String> {
+        self.subscriptions
+            .read()
+            .unwrap()
+            .get(conn)
+            .map(|patterns| patterns.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // queues the message of the topic without blocking, starting the delivery thread of the topic
+    // if it is new or idle. A message the queue is full for is returned with the queue.
+    fn queue_published(
+        &self,
+        topic: &str,
+        published: Published,
+    ) -> Result<Option<(SyncSender<Published>, Published)>> {
+        // the delivery thread stops under this lock once its queue is empty, so it never drops a
+        // message queued here.
+        let mut topics = self.topics.lock().unwrap();
+        let sender = match topics.get(topic) {
+            Some(sender) => sender.clone(),
+            None => {
+                let (sender, receiver) = mpsc::sync_channel::<Published>(TOPIC_QUEUE_SIZE);
+                let conns = self.conns.clone();
+                let subscriptions = self.subscriptions.clone();
+                let seq = self.seq.clone();
+                let all_topics = self.topics.clone();
+                let topic_name = topic.to_owned();
+                thread::spawn(move || {
+                    deliver(
+                        &topic_name,
+                        receiver,
+                        &all_topics,
+                        &conns,
+                        &subscriptions,
+                        &seq,
+                    )
+                });
+                topics.insert(topic.to_owned(), sender.clone());
+                sender
+            }
+        };
+
+        match sender.try_send(published) {
+            Ok(()) => Ok(None),
+            Err(TrySendError::Full(published)) => Ok(Some((sender, published))),
+            Err(TrySendError::Disconnected(_)) => Err(Error::new(
+                ErrorKind::Server,
+                format!("topic {} is closed", topic),
+            )),
+        }
+    }
+}
+
+// delivers the messages of a topic in order until it is idle for `TOPIC_IDLE_TIMEOUT`.
+fn deliver(
+    topic: &str,
+    receiver: Receiver<Published>,
+    topics: &Topics,
+    conns: &Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+    subscriptions: &Subscriptions,
+    seq: &AtomicU64,
+) {
+    loop {
+        let published = match receiver.recv_timeout(TOPIC_IDLE_TIMEOUT) {
+            Ok(published) => published,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut topics = topics.lock().unwrap();
+                match receiver.try_recv() {
+                    Ok(published) => published,
+                    Err(_) => {
+                        topics.remove(topic);
+                        return;
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let msg = new_topic_message(topic, seq, published);
+        for writer in subscribers(conns, subscriptions, topic) {
+            let _ = push_msg(&writer, &msg);
+        }
+    }
+}
+
+fn new_topic_message(topic: &str, seq: &AtomicU64, published: Published) -> Message {
+    let mut msg = Message::new();
+    msg.set_message_type(MessageType::Request);
+    msg.set_seq(seq.fetch_add(1, Ordering::SeqCst));
+    msg.set_oneway(true);
+    msg.set_serialize_type(SerializeType::SerializeNone);
+    msg.set_compress_type(CompressType::CompressNone);
+    msg.service_path = PUBSUB_SERVICE.to_owned();
+    msg.service_method = topic.to_owned();
+    msg.metadata.replace(published.metadata);
+    msg.payload = Bytes::from(published.data);
+    msg
+}
+
+fn subscribers(
+    conns: &Arc<RwLock<HashMap<SocketAddr, Arc<ConnWriter>>>>,
+    subscriptions: &Subscriptions,
+    topic: &str,
+) -> Vec<Arc<ConnWriter>> {
+    let subscriptions = subscriptions.read().unwrap();
+    let conns = conns.read().unwrap();
+    subscriptions
+        .iter()
+        .filter(|(_, patterns)| patterns.iter().any(|p| topic_matches(p, topic)))
+        .filter_map(|(addr, _)| conns.get(addr).cloned())
+        .collect()
+}
+
+/// handles a subscribe or unsubscribe request of the client `addr` on `local_addr`. The request
+/// must pass the `post_read_request` hook of the plugins first, such as the authentication, and
+/// its reply their `pre_write_response` hook.
+pub(crate) fn handle_subscription(
+    subscriptions: &Subscriptions,
+    message_plugins: &MessagePlugins,
+    addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
+    writer: &Arc<ConnWriter>,
+    mut msg: Message,
+) {
+    let reply_msg = handle_builtin(message_plugins, &mut msg, Some(addr), local_addr, |msg| {
+        let pattern = String::from_utf8_lossy(&msg.payload).into_owned();
+        if !is_valid_pattern(&pattern) {
+            return Err(Error::new(
+                ErrorKind::Client,
+                format!("invalid topic pattern {}", pattern),
+            ));
+        }
+        let mut subscriptions = subscriptions.write().unwrap();
+        match msg.service_method.as_str() {
+            PUBSUB_SUBSCRIBE => {
+                subscriptions.entry(addr).or_default().insert(pattern);
+                Ok(Vec::new())
+            }
+            PUBSUB_UNSUBSCRIBE => {
+                if let Some(patterns) = subscriptions.get_mut(&addr) {
+                    patterns.remove(&pattern);
+                }
+                Ok(Vec::new())
+            }
+            method => Err(Error::new(
+                ErrorKind::Server,
+                format!("service {}.{} not found", PUBSUB_SERVICE, method),
+            )),
+        }
+    });
+
+    if !msg.is_oneway() {
+        let _ = write_msg(writer, &reply_msg);
+    }
+}
@@ -0,0 +1,119 @@
+use super::{write_msg, ConnWriter, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// counts the decoded requests waiting for a worker of the handler pools, so a server which
+/// can't keep up sheds requests instead of queueing them without bound.
+#[derive(Debug, Default)]
+pub(crate) struct RequestQueue {
+    // 0 means unbounded
+    capacity: AtomicUsize,
+    // the slots beyond the capacity which only high priority requests take
+    high_priority_slots: AtomicUsize,
+    depth: AtomicUsize,
+}
+
+impl RequestQueue {
+    /// takes a slot of the queue, it returns false if the queue is full. High priority
+    /// requests take the slots reserved for them once the queue is full, and are shed beyond
+    /// them too, since any client can set the priority.
+    pub(crate) fn push(&self, priority: Priority) -> bool {
+        let mut capacity = self.capacity.load(Ordering::Relaxed);
+        if capacity > 0 && priority == Priority::High {
+            capacity += self.high_priority_slots.load(Ordering::Relaxed);
+        }
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst);
+        if capacity > 0 && depth >= capacity {
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+
+    /// releases the slot once a worker picks the request up.
+    pub(crate) fn pop(&self) {
+        self.depth.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_high_priority_slots(&self, slots: usize) {
+        self.high_priority_slots.store(slots, Ordering::Relaxed);
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+}
+
+/// a request waiting for a worker.
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+/// the requests of a connection waiting for a worker by priority, see `Priority`. Every job
+/// pushed is paired with a task of the pool which pops the job of the highest priority when
+/// a worker runs it, so the pool serves the jobs by priority and in order within a priority.
+#[derive(Default)]
+pub(crate) struct PriorityJobs {
+    // the jobs of high, normal and low priority
+    jobs: Mutex<[VecDeque<Job>; 3]>,
+}
+
+impl PriorityJobs {
+    pub(crate) fn push(&self, priority: Priority, job: Job) {
+        self.jobs.lock().unwrap()[priority as usize].push_back(job);
+    }
+
+    pub(crate) fn pop(&self) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.iter_mut().find_map(VecDeque::pop_front)
+    }
+}
+
+impl Server {
+    /// limits the requests waiting for a worker to `capacity`, the requests beyond it are
+    /// replied with an `ErrorKind::ServerBusy` error at once. High priority requests take up
+    /// to `capacity` more slots, which `set_high_priority_slots` changes. 0, the default, is
+    /// unbounded.
+    pub fn set_max_queued_requests(&mut self, capacity: usize) {
+        self.queue.set_capacity(capacity);
+        self.queue.set_high_priority_slots(capacity);
+    }
+
+    /// sets the slots beyond the capacity of `set_max_queued_requests` which only high priority
+    /// requests take, the high priority requests beyond them are shed too.
+    pub fn set_high_priority_slots(&mut self, slots: usize) {
+        self.queue.set_high_priority_slots(slots);
+    }
+
+    /// returns the number of requests waiting for a worker.
+    pub fn queued_requests(&self) -> usize {
+        self.queue.depth()
+    }
+}
+
+/// replies a request which is shed since the queue is full.
+pub(crate) fn reject_busy(writer: &Arc<ConnWriter>, msg: &Message) {
+    if msg.is_oneway() {
+        return;
+    }
+    let _ = write_msg(writer, &busy_reply(msg));
+}
+
+/// returns the reply of a request which is shed since the queue is full.
+pub(crate) fn busy_reply(msg: &Message) -> Message {
+    let mut reply_msg = msg.get_reply().unwrap();
+    Error::new(
+        ErrorKind::ServerBusy,
+        "server is busy, too many queued requests",
+    )
+    .set_reply(&mut reply_msg);
+    reply_msg
+}
@@ -0,0 +1,186 @@
+use super::{MessagePlugin, ServerConfig};
+use rpcx_protocol::*;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+// how often the buckets of idle identities are dropped.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+type Identify = Box<dyn Fn(&Metadata) -> Option<String> + Send + Sync>;
+
+/// the rate of the requests of an identity.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Quota {
+    /// the requests per second.
+    pub qps: f64,
+    /// the requests which can be made at once after being idle.
+    pub burst: u32,
+}
+
+#[derive(Debug)]
+struct Quotas {
+    default: Quota,
+    overrides: HashMap<String, Quota>,
+}
+
+impl Quotas {
+    fn get(&self, identity: &str) -> Quota {
+        self.overrides
+            .get(identity)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    cleaned_at: Instant,
+}
+
+/// limits the rate of the requests of each identity by a token bucket, so a tenant of a
+/// multi-tenant service can't exhaust it. Requests beyond the quota fail with
+/// `ErrorKind::RateLimited`.
+///
+/// The identity is the IP of the peer by default. The tokens of requests are not trusted
+/// unless a callback verifies them, see `with_identity`. Requests without a valid identity
+/// are limited by the IP of their peer.
+pub struct RateLimitPlugin {
+    quotas: RwLock<Quotas>,
+    identify: Option<Identify>,
+    buckets: Mutex<Buckets>,
+}
+
+impl fmt::Debug for RateLimitPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitPlugin")
+            .field("quotas", &*self.quotas.read().unwrap())
+            .finish()
+    }
+}
+
+impl RateLimitPlugin {
+    /// limits each peer IP to `quota`, the overrides of `set_quota` are keyed by IP.
+    pub fn new(quota: Quota) -> Self {
+        Self::build(quota, None)
+    }
+
+    /// limits each identity returned by `identify` to `quota`. It verifies the credentials
+    /// of the request and returns the subject they authenticate, such as the tenant of a
+    /// signed token, or `None` if they are missing or invalid.
+    pub fn with_identity<F>(quota: Quota, identify: F) -> Self
+    where
+        F: Fn(&Metadata) -> Option<String> + Send + Sync + 'static,
+    {
+        Self::build(quota, Some(Box::new(identify)))
+    }
+
+    fn build(quota: Quota, identify: Option<Identify>) -> Self {
+        RateLimitPlugin {
+            quotas: RwLock::new(Quotas {
+                default: quota,
+                overrides: HashMap::new(),
+            }),
+            identify,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                cleaned_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// overrides the quota of an identity.
+    pub fn set_quota(&mut self, identity: &str, quota: Quota) {
+        let quotas = self.quotas.get_mut().unwrap();
+        quotas.overrides.insert(identity.to_owned(), quota);
+    }
+
+    /// replaces the quotas at runtime, the default one and the overrides of identities. The
+    /// buckets are kept, so the new quotas apply from their current tokens.
+    pub fn set_quotas(&self, quota: Quota, overrides: HashMap<String, Quota>) {
+        *self.quotas.write().unwrap() = Quotas {
+            default: quota,
+            overrides,
+        };
+    }
+
+    // takes a token from the bucket of the identity.
+    fn acquire(&self, key: String, quota: Quota) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if now - buckets.cleaned_at >= CLEANUP_INTERVAL {
+            // an idle bucket is full again, like a new one
+            let quotas = self.quotas.read().unwrap();
+            buckets.buckets.retain(|key, bucket| {
+                let quota = quotas.get(key);
+                let refill = (now - bucket.updated_at).as_secs_f64() * quota.qps;
+                bucket.tokens + refill < f64::from(quota.burst)
+            });
+            buckets.cleaned_at = now;
+        }
+
+        let bucket = buckets.buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: f64::from(quota.burst),
+            updated_at: now,
+        });
+        let refill = (now - bucket.updated_at).as_secs_f64() * quota.qps;
+        bucket.tokens = (bucket.tokens + refill).min(f64::from(quota.burst));
+        bucket.updated_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+impl MessagePlugin for RateLimitPlugin {
+    fn post_read_request(&self, req: &mut Message) -> Result<()> {
+        let metadata = req.metadata.borrow();
+        let identity = match &self.identify {
+            Some(identify) => identify(&metadata),
+            None => metadata
+                .remote_conn_addr()
+                .map(|addr| addr.ip().to_string()),
+        };
+        let (key, quota) = match identity {
+            Some(identity) => {
+                let quota = self.quotas.read().unwrap().get(&identity);
+                (identity, quota)
+            }
+            // the peer IPs are kept apart from the identities
+            None => match metadata.remote_conn_addr() {
+                Some(addr) => (
+                    format!("\0{}", addr.ip()),
+                    self.quotas.read().unwrap().default,
+                ),
+                None => return Ok(()),
+            },
+        };
+        if self.acquire(key, quota) {
+            return Ok(());
+        }
+        Err(Error::new(
+            ErrorKind::RateLimited,
+            format!("exceeded the quota of {} requests per second", quota.qps),
+        ))
+    }
+
+    fn reload(&self, config: &ServerConfig) -> Result<()> {
+        if let Some(rate_limit) = &config.rate_limit {
+            self.set_quotas(rate_limit.quota(), rate_limit.quotas.clone());
+        }
+        Ok(())
+    }
+}
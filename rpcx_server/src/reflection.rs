@@ -0,0 +1,54 @@
+use super::{write_msg, ConnWriter, RpcxFn};
+use bytes::Bytes;
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// serves the built-in `_reflection` service, which tells clients what the server supports.
+pub(crate) fn handle_msg(
+    services: &Arc<RwLock<HashMap<String, Box<RpcxFn>>>>,
+    writer: &Arc<ConnWriter>,
+    msg: Message,
+) {
+    let rt = match msg.service_method.as_str() {
+        REFLECTION_COMPRESSORS => Ok(encode_compressors(&supported_compressors())),
+        REFLECTION_SERVICES => {
+            let services = services.read().unwrap();
+            let methods: Vec<&String> = services.keys().collect();
+            Ok(encode_services(&methods))
+        }
+        method => Err(Error::new(
+            ErrorKind::Server,
+            format!("service {}.{} not found", REFLECTION_SERVICE, method),
+        )),
+    };
+    reply(writer, &msg, rt);
+}
+
+/// rejects a request compressed by a compressor the server doesn't support. The reply is not
+/// compressed so the client can read the error.
+pub(crate) fn reject_compress_type(writer: &Arc<ConnWriter>, msg: Message) {
+    let err = Error::new(
+        ErrorKind::Protocol,
+        format!(
+            "unsupported compress type {}, supported: {}",
+            (msg.header[2] & 0x1C) >> 2,
+            String::from_utf8_lossy(&encode_compressors(&supported_compressors()))
+        ),
+    );
+    reply(writer, &msg, Err(err));
+}
+
+fn reply(writer: &Arc<ConnWriter>, msg: &Message, rt: Result<Vec<u8>>) {
+    if msg.is_oneway() {
+        return;
+    }
+    let mut reply_msg = msg.get_reply().unwrap();
+    match rt {
+        Ok(payload) => reply_msg.payload = Bytes::from(payload),
+        Err(err) => err.set_reply(&mut reply_msg),
+    }
+    let _ = write_msg(writer, &reply_msg);
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use rpcx_protocol::*;
+
+use super::{RpcxFn, Server};
+
+/// what `register_fn` does when the method of a service is registered already.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    /// replaces the handler of the method and logs it. It is the default.
+    Replace,
+    /// fails the registration with `ErrorKind::Server`, the handler registered first is
+    /// kept. `register_fn` only logs the error, callers receive it from `try_register_fn`.
+    Reject,
+    /// keeps the handler of the method and registers the new one as a version of it, such as
+    /// `Mul@2` for the second registration of `Mul`, which clients call by that name.
+    Version,
+}
+
+impl Default for DuplicatePolicy {
+    fn default() -> Self {
+        DuplicatePolicy::Replace
+    }
+}
+
+impl Server {
+    /// sets what `register_fn` does when the method of a service is registered already.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+}
+
+/// returns the method the function of `service_path.service_method` is registered by, by the
+/// policy if the method is registered already.
+pub(crate) fn resolve(
+    policy: DuplicatePolicy,
+    services: &HashMap<String, Box<RpcxFn>>,
+    service_path: &ServicePath,
+    service_method: ServiceMethod,
+) -> Result<ServiceMethod> {
+    let key = format!("{}.{}", service_path, service_method);
+    if !services.contains_key(&key) {
+        return Ok(service_method);
+    }
+    match policy {
+        DuplicatePolicy::Replace => {
+            eprintln!("the handler of {} is replaced", key);
+            Ok(service_method)
+        }
+        DuplicatePolicy::Reject => Err(Error::new(
+            ErrorKind::Server,
+            format!("{} is registered already", key),
+        )),
+        DuplicatePolicy::Version => {
+            let version = (2..)
+                .map(|n| format!("{}@{}", service_method, n))
+                .find(|method| !services.contains_key(&format!("{}.{}", service_path, method)))
+                .unwrap();
+            Ok(ServiceMethod::from(version))
+        }
+    }
+}
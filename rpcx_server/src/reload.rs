@@ -0,0 +1,116 @@
+use super::{Server, ServerConfig};
+use rpcx_protocol::*;
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::{raw::c_int, unix::io::FromRawFd},
+    path::Path,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// the write end of the pipe the SIGHUP handler wakes the reloading thread by, -1 if there is
+// none. Writing to a pipe is async-signal-safe, unlike most of the reload.
+static SIGHUP_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_sighup(_: c_int) {
+    let fd = SIGHUP_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // the pipe is non-blocking, a full pipe has a pending reload already
+        unsafe {
+            libc::write(fd, b"1".as_ptr() as *const libc::c_void, 1);
+        }
+    }
+}
+
+impl Server {
+    /// applies the settings of the config which can change at runtime, without dropping
+    /// connections: `max_queued_requests`, `idle_timeout_ms` for the connections accepted
+    /// from now on, and whatever the message plugins pick up by `MessagePlugin::reload`, such
+    /// as the quotas of a `RateLimitPlugin`. The config is overridden by environment variables
+    /// like `with_config`.
+    ///
+    /// The address, threads, version, registry and tracing take effect after a restart. TLS
+    /// can't be changed by reloading, its certificate is reloaded once the files change, see
+    /// `enable_tls`.
+    pub fn reload(&self, config: &ServerConfig) -> Result<()> {
+        let mut config = config.clone();
+        config.apply_env()?;
+        self.check_tls(&config)?;
+
+        self.queue.set_capacity(config.max_queued_requests);
+        let idle_timeout = if config.idle_timeout_ms > 0 {
+            Some(Duration::from_millis(config.idle_timeout_ms))
+        } else {
+            None
+        };
+        *self.idle_timeout.write().unwrap() = idle_timeout;
+        for plugin in self.message_plugins.read().unwrap().iter() {
+            plugin.reload(&config)?;
+        }
+        Ok(())
+    }
+
+    // fails the configs whose TLS is not the one the server is serving.
+    fn check_tls(&self, config: &ServerConfig) -> Result<()> {
+        #[cfg(feature = "tls")]
+        let tls = self.tls.as_ref().map(|tls| &tls.config);
+        #[cfg(not(feature = "tls"))]
+        let tls: Option<&TlsConfig> = None;
+        if config.tls.as_ref() != tls {
+            return Err(Error::new(
+                ErrorKind::Config,
+                "tls can't be changed by reloading",
+            ));
+        }
+        Ok(())
+    }
+
+    /// reloads the server from the TOML or YAML file of `ServerConfig` every time the process
+    /// receives SIGHUP. A config which fails to load is reported and the running one is kept.
+    ///
+    /// Only one server of a process can be reloaded on SIGHUP.
+    pub fn reload_on_sighup<P: AsRef<Path>>(server: Arc<Server>, path: P) -> Result<()> {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        unsafe {
+            libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK);
+        }
+        let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+        if SIGHUP_PIPE
+            .compare_exchange(-1, fds[1], Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            unsafe {
+                libc::close(fds[1]);
+            }
+            return Err(Error::new(
+                ErrorKind::Config,
+                "a server is reloaded on SIGHUP already",
+            ));
+        }
+        unsafe {
+            libc::signal(libc::SIGHUP, on_sighup as libc::sighandler_t);
+        }
+
+        let path = path.as_ref().to_owned();
+        thread::spawn(move || {
+            let mut buf = [0u8; 1];
+            while reader.read_exact(&mut buf).is_ok() {
+                let rt = load_config(&path).and_then(|config: ServerConfig| server.reload(&config));
+                if let Err(err) = rt {
+                    eprintln!("failed to reload {}: {}", path.display(), err);
+                }
+            }
+        });
+        Ok(())
+    }
+}
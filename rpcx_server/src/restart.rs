@@ -0,0 +1,240 @@
+use super::Server;
+use rpcx_protocol::*;
+use std::{
+    env, io,
+    net::{TcpListener, TcpStream},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, RawFd},
+        process::CommandExt,
+    },
+    process::{Child, Command},
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// the environment variable of the listening sockets a server passes to the process which
+/// replaces it, their comma separated file descriptors.
+pub const INHERITED_FDS: &str = "RPCX_INHERITED_FDS";
+
+/// how long `drain` waits by default for the registries to propagate the removal of the
+/// server to the clients.
+pub const DRAIN_DELAY: Duration = Duration::from_secs(5);
+
+// how often `shutdown` checks whether the requests in flight are finished.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+// how long the accept loop waits for a connection before it checks whether the server is
+// stopped.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+impl Server {
+    /// creates a server which serves on the first socket passed by the process it replaces,
+    /// see `restart`.
+    pub fn from_parent() -> Result<Self> {
+        match inherited_listeners()?.into_iter().next() {
+            Some(listener) => Self::from_listener(listener),
+            None => Err(Error::new(
+                ErrorKind::Config,
+                format!("no socket is passed in {}", INHERITED_FDS),
+            )),
+        }
+    }
+
+    /// starts the executable of this process again with its arguments and passes it the
+    /// listening sockets, which it serves by `from_parent`. Both processes accept connections
+    /// until this one is shut down, so a binary can be upgraded without refusing any:
+    ///
+    /// ```no_run
+    /// # use rpcx_server::Server;
+    /// # use std::time::Duration;
+    /// # fn upgrade(server: &Server) -> rpcx_protocol::Result<()> {
+    /// server.restart()?;
+    /// server.shutdown(Duration::from_secs(30))?;
+    /// std::process::exit(0);
+    /// # }
+    /// ```
+    pub fn restart(&self) -> Result<Child> {
+        let fds = self.raw_fds.lock().unwrap().clone();
+        if fds.is_empty() {
+            return Err(Error::new(ErrorKind::Server, "the server is not listening"));
+        }
+        let value: Vec<String> = fds.iter().map(RawFd::to_string).collect();
+
+        let mut cmd = Command::new(env::current_exe()?);
+        cmd.args(env::args_os().skip(1))
+            .env(INHERITED_FDS, value.join(","));
+        unsafe {
+            // the sockets are close-on-exec, they are inherited once it is cleared in the child
+            cmd.pre_exec(move || {
+                for &fd in &fds {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let child = cmd.spawn()?;
+        self.restarted.store(true, Ordering::SeqCst);
+        Ok(child)
+    }
+
+    /// stops accepting connections and waits up to `timeout` for the requests in flight and
+    /// queued, then closes the connections so their clients reconnect, to the new process
+    /// after `restart`. It returns an `ErrorKind::Timeout` error if the requests are not
+    /// finished in time.
+    pub fn shutdown(&self, timeout: Duration) -> Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        // the accept loops see the flag within `ACCEPT_INTERVAL`. The sockets are shared with
+        // the new process after `restart`, so they are only shut down if there is none.
+        if !self.restarted.load(Ordering::SeqCst) {
+            for &raw_fd in self.raw_fds.lock().unwrap().iter() {
+                unsafe {
+                    libc::shutdown(raw_fd, libc::SHUT_RDWR);
+                }
+            }
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut rt = Ok(());
+        while self.queue.depth() > 0 || self.conns_in_flight() > 0 {
+            if Instant::now() >= deadline {
+                rt = Err(Error::new(
+                    ErrorKind::Timeout,
+                    "requests are in flight after the shutdown timeout",
+                ));
+                break;
+            }
+            thread::sleep(DRAIN_INTERVAL);
+        }
+
+        for writer in self.conns.read().unwrap().values() {
+            writer.shutdown();
+        }
+        rt
+    }
+
+    /// sets how long `drain` waits after unregistering the services, for the registries
+    /// and the clients watching them to stop sending requests to the server.
+    pub fn set_drain_delay(&mut self, delay: Duration) {
+        self.drain_delay = delay;
+    }
+
+    /// prepares the server to be stopped without failing any call, for rolling deploys. It
+    /// unregisters the services from the registries, waits for the drain delay, then waits
+    /// up to `timeout` for the requests in flight and queued and the open streams to finish.
+    /// The server keeps serving meanwhile, the clients which have not seen the removal yet
+    /// are served as before.
+    ///
+    /// ```no_run
+    /// # use rpcx_server::Server;
+    /// # use std::time::Duration;
+    /// # fn deploy(server: &Server) -> rpcx_protocol::Result<()> {
+    /// server.drain(Duration::from_secs(30))?;
+    /// server.shutdown(Duration::from_secs(0))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// It returns the last error of the registries, which are all unregistered from
+    /// regardless, or an `ErrorKind::Timeout` error if the requests and the streams are not
+    /// finished in time.
+    pub fn drain(&self, timeout: Duration) -> Result<()> {
+        let mut rt = Ok(());
+        {
+            let metas = self.metas.read().unwrap();
+            let mut plugins = self.register_plugins.write().unwrap();
+            for service_path in metas.keys() {
+                for p in plugins.iter_mut() {
+                    if let Err(err) = p.unregister(service_path) {
+                        eprintln!("failed to unregister {}: {}", service_path, err);
+                        rt = Err(err);
+                    }
+                }
+            }
+        }
+        thread::sleep(self.drain_delay);
+
+        let deadline = Instant::now() + timeout;
+        while self.queue.depth() > 0 || self.conns_in_flight() > 0 || self.open_streams() > 0 {
+            if Instant::now() >= deadline {
+                return Err(Error::new(
+                    ErrorKind::Timeout,
+                    "requests or streams are open after the drain timeout",
+                ));
+            }
+            thread::sleep(DRAIN_INTERVAL);
+        }
+        rt
+    }
+
+    fn conns_in_flight(&self) -> usize {
+        let conns = self.conns.read().unwrap();
+        conns.values().map(|writer| writer.in_flight()).sum()
+    }
+
+    fn open_streams(&self) -> usize {
+        let conns = self.conns.read().unwrap();
+        conns.values().map(|writer| writer.open_streams()).sum()
+    }
+}
+
+/// accepts a connection of the non-blocking listener, or returns `None` if there is none
+/// within `ACCEPT_INTERVAL`. The listener may be shared with the process which replaces this
+/// one, which can take the connection first, so it is not blocked on.
+pub(crate) fn accept_polled(listener: &TcpListener) -> io::Result<Option<TcpStream>> {
+    let mut fds = libc::pollfd {
+        fd: listener.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let n = unsafe { libc::poll(&mut fds, 1, ACCEPT_INTERVAL.as_millis() as libc::c_int) };
+    if n < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::Interrupted {
+            return Ok(None);
+        }
+        return Err(err);
+    }
+    if n == 0 {
+        return Ok(None);
+    }
+    match listener.accept() {
+        Ok((stream, _)) => Ok(Some(stream)),
+        Err(ref err)
+            if err.kind() == io::ErrorKind::WouldBlock
+                || err.kind() == io::ErrorKind::Interrupted =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// returns the listening sockets passed by the process this one replaces, empty if there is
+/// none. The variable is removed so children don't inherit it.
+pub fn inherited_listeners() -> Result<Vec<TcpListener>> {
+    let fds = match env::var(INHERITED_FDS) {
+        Ok(fds) => fds,
+        Err(_) => return Ok(Vec::new()),
+    };
+    env::remove_var(INHERITED_FDS);
+
+    let mut listeners = Vec::new();
+    for fd in fds.split(',') {
+        let fd = fd.parse::<RawFd>().map_err(|err| {
+            Error::new(
+                ErrorKind::Config,
+                format!("invalid {}: {}", INHERITED_FDS, err),
+            )
+        })?;
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        // fails if the descriptor is not a socket
+        listener.local_addr()?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
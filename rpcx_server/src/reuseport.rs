@@ -0,0 +1,213 @@
+use super::{
+    executor::{ReactorQueue, StealingExecutor},
+    Server,
+};
+use rpcx_protocol::*;
+use std::{
+    io, mem,
+    net::{SocketAddr, TcpListener},
+    os::unix::io::FromRawFd,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use scoped_threadpool::Pool;
+
+const LISTEN_BACKLOG: libc::c_int = 1024;
+
+impl Server {
+    /// starts `acceptors` reactors, each accepting on its own listener bound to the same
+    /// address with `SO_REUSEPORT`, so the kernel spreads incoming connections over their
+    /// accept queues. It uses one reactor per core if `acceptors` is 0.
+    ///
+    /// The requests of the connections of a reactor are queued to it and handled by the
+    /// workers of `Server::new`, which are shared by the reactors. A worker handles the
+    /// requests of its own reactor first and steals the others' once it has none, so a busy
+    /// reactor is helped by the idle ones. Each connection is still read by a thread, and the
+    /// handlers of streams take a worker while their streams are open.
+    ///
+    /// It returns when all acceptors have stopped, with the error of the last one that failed.
+    pub fn start_reuseport(&mut self, acceptors: usize) -> Result<()> {
+        self.start_acceptors(acceptors, false)
+    }
+
+    /// starts the reactors like `start_reuseport` and pins the i-th one, its workers and the
+    /// threads of its connections to the i-th core the process may run on, as reported by
+    /// `sched_getaffinity`, so the cores of its cpuset or of `taskset` are respected. A
+    /// request is read, handled and written on the core its connection was accepted by
+    /// unless it is stolen, which keeps the caches warm and avoids migrations on machines
+    /// with many cores. The cores are reused in turn if there are more reactors than cores.
+    ///
+    /// Pinning is only supported on Linux, the reactors are not pinned on other systems.
+    pub fn start_reuseport_pinned(&mut self, acceptors: usize) -> Result<()> {
+        self.start_acceptors(acceptors, true)
+    }
+
+    fn start_acceptors(&mut self, acceptors: usize, pinned: bool) -> Result<()> {
+        let addr = self
+            .addr
+            .parse::<SocketAddr>()
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        let cores = allowed_cores()?;
+        let acceptors = if acceptors == 0 {
+            cores.len()
+        } else {
+            acceptors
+        };
+
+        let mut listeners = Vec::with_capacity(acceptors);
+        for _ in 0..acceptors {
+            listeners.push(bind_reuseport(&addr)?);
+        }
+        println!("Listening on: {} with {} acceptors", addr, acceptors);
+
+        // the workers are spread over the reactors, on their cores
+        let executor = Arc::new(StealingExecutor::new(acceptors));
+        let workers = (self.thread_number as usize).max(acceptors);
+        for i in 0..workers {
+            let reactor = i % acceptors;
+            let core = cores[reactor % cores.len()];
+            let executor = executor.clone();
+            thread::spawn(move || {
+                if pinned {
+                    pin_or_warn(&format!("worker {}", i), core);
+                }
+                executor.work(reactor);
+            });
+        }
+
+        let rt = Mutex::new(Ok(()));
+        let server = &*self;
+        Pool::new(acceptors as u32).scoped(|scoped| {
+            for (i, listener) in listeners.into_iter().enumerate() {
+                let rt = &rt;
+                let core = cores[i % cores.len()];
+                let reactor = ReactorQueue {
+                    executor: executor.clone(),
+                    reactor: i,
+                };
+                scoped.execute(move || {
+                    if pinned {
+                        pin_or_warn(&format!("acceptor {}", i), core);
+                    }
+                    if let Err(err) = server.start_reactor(listener, Some(reactor)) {
+                        *rt.lock().unwrap() = Err(err);
+                    }
+                });
+            }
+        });
+        // the workers exit once the queued requests are handled
+        executor.close();
+        rt.into_inner().unwrap()
+    }
+}
+
+fn pin_or_warn(thread: &str, core: usize) {
+    if let Err(err) = pin_to_core(core) {
+        eprintln!("failed to pin {} to core {}: {}", thread, core, err);
+    }
+}
+
+/// returns the cores the calling thread may run on.
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> io::Result<Vec<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of_val(&set), &mut set) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&core| libc::CPU_ISSET(core, &set))
+            .collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cores() -> io::Result<Vec<usize>> {
+    Ok((0..num_cpus::get()).collect())
+}
+
+/// pins the calling thread, and the threads it spawns from now on, to a core.
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of_val(&set), &set) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> io::Result<()> {
+    Ok(())
+}
+
+/// binds a listener which shares the address with other listeners of this process.
+pub(crate) fn bind_reuseport(addr: &SocketAddr) -> io::Result<TcpListener> {
+    let family = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+
+    unsafe {
+        let fd = libc::socket(family, libc::SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // owns the socket from here, so it is closed on errors
+        let listener = TcpListener::from_raw_fd(fd);
+
+        let on: libc::c_int = 1;
+        for opt in &[libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+            let rt = libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                *opt,
+                &on as *const libc::c_int as *const libc::c_void,
+                mem::size_of_val(&on) as libc::socklen_t,
+            );
+            if rt < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let rt = match addr {
+            SocketAddr::V4(addr) => {
+                let mut sin: libc::sockaddr_in = mem::zeroed();
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr = libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                };
+                libc::bind(
+                    fd,
+                    &sin as *const libc::sockaddr_in as *const libc::sockaddr,
+                    mem::size_of_val(&sin) as libc::socklen_t,
+                )
+            }
+            SocketAddr::V6(addr) => {
+                let mut sin6: libc::sockaddr_in6 = mem::zeroed();
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr = libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                };
+                sin6.sin6_scope_id = addr.scope_id();
+                libc::bind(
+                    fd,
+                    &sin6 as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    mem::size_of_val(&sin6) as libc::socklen_t,
+                )
+            }
+        };
+        if rt < 0 || libc::listen(fd, LISTEN_BACKLOG) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(listener)
+    }
+}
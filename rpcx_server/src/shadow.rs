@@ -0,0 +1,109 @@
+use super::MessagePlugin;
+use rand::{thread_rng, Rng};
+use rpcx_protocol::*;
+use std::{
+    io::{BufReader, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// the number of mirrored requests waiting to be sent, requests beyond it are not mirrored.
+pub const SHADOW_QUEUE_SIZE: usize = 1024;
+
+const SHADOW_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// mirrors a percentage of the requests to a shadow server, such as a new version of the
+/// services, so it can be tested with production traffic. The requests are sent in background
+/// and the replies of the shadow server are discarded, the callers are not affected by it.
+///
+/// Requests are mirrored as they are when the plugin is invoked, so it is usually added before
+/// plugins which change requests.
+#[derive(Debug)]
+pub struct ShadowPlugin {
+    probability: f64,
+    sender: SyncSender<Vec<u8>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ShadowPlugin {
+    /// mirrors `percentage` of the requests, from 0 to 100, to the rpcx server at `shadow_addr`
+    /// such as `127.0.0.1:8973`.
+    pub fn new(shadow_addr: &str, percentage: f64) -> Result<Self> {
+        let addr = shadow_addr
+            .parse::<SocketAddr>()
+            .map_err(|err| Error::new(ErrorKind::Config, err))?;
+        let (sender, receiver) = sync_channel(SHADOW_QUEUE_SIZE);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let dropped_cloned = dropped.clone();
+        thread::spawn(move || mirror(addr, receiver, &dropped_cloned));
+
+        Ok(ShadowPlugin {
+            probability: (percentage / 100.0).max(0.0).min(1.0),
+            sender,
+            dropped,
+        })
+    }
+
+    /// returns the number of requests which were selected but could not be mirrored.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl MessagePlugin for ShadowPlugin {
+    fn post_read_request(&self, req: &mut Message) -> Result<()> {
+        if !thread_rng().gen_bool(self.probability) {
+            return Ok(());
+        }
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(req.encode()) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+// sends the mirrored requests to the shadow server, connecting again after failures.
+fn mirror(addr: SocketAddr, receiver: Receiver<Vec<u8>>, dropped: &AtomicU64) {
+    let mut conn: Option<TcpStream> = None;
+    for data in receiver {
+        if conn.is_none() {
+            conn = match connect(&addr) {
+                Ok(stream) => Some(stream),
+                Err(err) => {
+                    eprintln!("failed to connect shadow server {}: {}", addr, err);
+                    None
+                }
+            };
+        }
+        let sent = match &mut conn {
+            Some(stream) => stream.write_all(&data).is_ok(),
+            None => false,
+        };
+        if !sent {
+            dropped.fetch_add(1, Ordering::Relaxed);
+            if let Some(stream) = conn.take() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+// connects the shadow server and discards its replies.
+fn connect(addr: &SocketAddr) -> Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(addr, SHADOW_CONNECT_TIMEOUT)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    thread::spawn(move || loop {
+        let mut msg = Message::new();
+        if msg.decode(&mut reader).is_err() {
+            return;
+        }
+    });
+    Ok(stream)
+}
@@ -0,0 +1,139 @@
+use super::{serialize_type, write_msg, ConnWriter, Server};
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    sync::{mpsc, Arc, Mutex, RwLock},
+};
+
+/// a function serving a stream. It reads the request body from the reader and writes the
+/// response body to the writer, so neither of them has to be buffered entirely in memory.
+pub type RpcxStreamFn = fn(&[u8], SerializeType, &mut dyn Read, &mut dyn Write) -> Result<()>;
+
+// the open streams of a connection, keyed by seq.
+pub(crate) type Streams = Arc<Mutex<HashMap<u64, ServerStream>>>;
+
+pub(crate) struct ServerStream {
+    body: mpsc::Sender<StreamFrame>,
+    credit: Arc<StreamCredit>,
+    // the connection of the stream, which counts it while it is open
+    writer: Arc<ConnWriter>,
+}
+
+impl Server {
+    pub fn register_stream_fn(
+        &mut self,
+        service_path: String,
+        service_method: String,
+        f: RpcxStreamFn,
+    ) {
+        let key = format!("{}.{}", service_path, service_method);
+        let mut map = self.stream_services.write().unwrap();
+        map.insert(key, f);
+    }
+}
+
+/// opens the stream requested by `msg` and returns the job running its function.
+pub(crate) fn open(
+    stream_services: &Arc<RwLock<HashMap<String, RpcxStreamFn>>>,
+    streams: &Streams,
+    writer: &Arc<ConnWriter>,
+    msg: Message,
+) -> Box<dyn FnOnce() + Send> {
+    let key = format!("{}.{}", msg.service_path, msg.service_method);
+    let f = stream_services.read().unwrap().get(&key).cloned();
+    let seq = msg.get_seq();
+
+    let (sender, receiver) = mpsc::channel();
+    let credit = Arc::new(StreamCredit::new(STREAM_WINDOW));
+    let replaced = streams.lock().unwrap().insert(
+        seq,
+        ServerStream {
+            body: sender,
+            credit: credit.clone(),
+            writer: writer.clone(),
+        },
+    );
+    if replaced.is_none() {
+        writer.open_stream();
+    }
+
+    let streams = streams.clone();
+    let writer = writer.clone();
+    Box::new(move || {
+        let ack_writer = writer.clone();
+        let mut body = StreamReader::new(
+            receiver,
+            Box::new(move |n| {
+                let frame = new_stream_frame(MessageType::Response, seq, STREAM_ACK, Vec::new());
+                frame
+                    .metadata
+                    .borrow_mut()
+                    .insert(STREAM_CREDIT.to_owned(), n.to_string());
+                let _ = write_msg(&ack_writer, &frame);
+            }),
+        );
+        let data_writer = writer.clone();
+        let mut out = StreamWriter::new(
+            credit,
+            Box::new(move |chunk| {
+                let frame = new_stream_frame(MessageType::Response, seq, STREAM_DATA, chunk);
+                write_msg(&data_writer, &frame)
+                    .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err.to_string()))
+            }),
+        );
+
+        let rt = match f {
+            Some(f) => serialize_type(&msg)
+                .and_then(|st| f(&msg.payload, st, &mut body, &mut out))
+                .and_then(|_| out.flush().map_err(Error::from)),
+            None => Err(Error::new(
+                ErrorKind::Server,
+                format!("service {} not found", key),
+            )),
+        };
+        if let Some(s) = streams.lock().unwrap().remove(&seq) {
+            s.writer.close_stream();
+        }
+
+        let mut end = new_stream_frame(MessageType::Response, seq, STREAM_END, Vec::new());
+        if let Err(err) = rt {
+            err.set_reply(&mut end);
+        }
+        let _ = write_msg(&writer, &end);
+    })
+}
+
+/// dispatches a data, end or ack frame of an open stream.
+pub(crate) fn on_frame(streams: &Streams, kind: &str, msg: Message) {
+    let streams = streams.lock().unwrap();
+    if let Some(s) = streams.get(&msg.get_seq()) {
+        match kind {
+            STREAM_DATA => {
+                let _ = s.body.send(StreamFrame::Data(msg.payload));
+            }
+            STREAM_END => {
+                let _ = s.body.send(StreamFrame::End);
+            }
+            STREAM_ACK => {
+                let credit = msg
+                    .metadata
+                    .borrow()
+                    .get(STREAM_CREDIT)
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or(0);
+                s.credit.release(credit);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// aborts all open streams of a closed connection.
+pub(crate) fn close_all(streams: &Streams) {
+    let mut streams = streams.lock().unwrap();
+    for (_, s) in streams.drain() {
+        s.credit.close();
+        s.writer.close_stream();
+    }
+}
@@ -0,0 +1,150 @@
+use super::Server;
+use rpcx_protocol::*;
+use rustls::{
+    sign::{self, CertifiedKey},
+    AllowAnyAuthenticatedClient, ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig,
+};
+use std::{
+    fs,
+    net::TcpStream,
+    sync::{Arc, RwLock, Weak},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// how often the certificate and the key of `Server::enable_tls` are checked for changes.
+pub const CERT_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+/// how long a server waits for the TLS handshake of a connection.
+pub const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// when the certificate and the key were modified and their sizes.
+type Stamp = [(SystemTime, u64); 2];
+
+/// the TLS of the connections of a server and the config it is built from.
+#[derive(Clone)]
+pub(crate) struct TlsAcceptor {
+    pub(crate) config: TlsConfig,
+    server_config: Arc<ServerConfig>,
+}
+
+// serves the certificate and the key of the files, which are reloaded once they change.
+struct ReloadingCert {
+    cert_file: String,
+    key_file: String,
+    loaded: RwLock<(CertifiedKey, Option<Stamp>)>,
+}
+
+impl ReloadingCert {
+    fn new(cert_file: &str, key_file: &str) -> Result<Self> {
+        let stamp = stamp(cert_file, key_file);
+        let key = load_cert(cert_file, key_file)?;
+        Ok(ReloadingCert {
+            cert_file: cert_file.to_owned(),
+            key_file: key_file.to_owned(),
+            loaded: RwLock::new((key, stamp)),
+        })
+    }
+
+    // reloads the files if they changed since they were loaded. Files which are missing,
+    // such as while they are replaced, are checked again later.
+    fn reload(&self) {
+        let stamp = stamp(&self.cert_file, &self.key_file);
+        if stamp.is_none() || stamp == self.loaded.read().unwrap().1 {
+            return;
+        }
+        match load_cert(&self.cert_file, &self.key_file) {
+            Ok(key) => *self.loaded.write().unwrap() = (key, stamp),
+            Err(err) => {
+                // retried once the files change again
+                eprintln!("failed to reload the certificate: {}", err);
+                self.loaded.write().unwrap().1 = stamp;
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        Some(self.loaded.read().unwrap().0.clone())
+    }
+}
+
+fn load_cert(cert_file: &str, key_file: &str) -> Result<CertifiedKey> {
+    let certs = load_certs(cert_file)?;
+    let key = sign::any_supported_type(&load_private_key(key_file)?).map_err(|_| {
+        Error::new(
+            ErrorKind::Config,
+            format!("invalid {}: unsupported private key", key_file),
+        )
+    })?;
+    Ok(CertifiedKey::new(certs, Arc::new(key)))
+}
+
+fn stamp(cert_file: &str, key_file: &str) -> Option<Stamp> {
+    let stamp = |path| {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    };
+    Some([stamp(cert_file)?, stamp(key_file)?])
+}
+
+// reloads the certificate every `CERT_RELOAD_INTERVAL` until it is dropped with the server.
+fn watch(cert: Weak<ReloadingCert>) {
+    thread::spawn(move || loop {
+        thread::sleep(CERT_RELOAD_INTERVAL);
+        match cert.upgrade() {
+            Some(cert) => cert.reload(),
+            None => return,
+        }
+    });
+}
+
+impl Server {
+    /// serves the rpcx connections over TLS with the certificate and the key of the config.
+    /// The certificates of clients are verified by its CA if there is one, and its ALPN
+    /// protocols are offered to clients.
+    ///
+    /// The files are checked every `CERT_RELOAD_INTERVAL` and reloaded once they change, so
+    /// short-lived certificates are rotated without a restart. The connections accepted from
+    /// then on use the new certificate while the others go on. A certificate and a key which
+    /// fail to load are reported and the loaded ones are kept.
+    ///
+    /// Connections which don't start with a TLS handshake are closed, the http gateway is
+    /// not served over TLS.
+    pub fn enable_tls(&mut self, config: &TlsConfig) -> Result<()> {
+        let cert = Arc::new(ReloadingCert::new(&config.cert_file, &config.key_file)?);
+        let mut server_config = if config.ca_file.is_empty() {
+            ServerConfig::new(NoClientAuth::new())
+        } else {
+            ServerConfig::new(AllowAnyAuthenticatedClient::new(load_roots(
+                &config.ca_file,
+            )?))
+        };
+        server_config.cert_resolver = cert.clone();
+        let protocols: Vec<Vec<u8>> = config
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        server_config.set_protocols(&protocols);
+        watch(Arc::downgrade(&cert));
+
+        self.tls = Some(TlsAcceptor {
+            config: config.clone(),
+            server_config: Arc::new(server_config),
+        });
+        Ok(())
+    }
+}
+
+/// does the TLS handshake of an accepted connection if TLS is enabled.
+pub(crate) fn accept(tls: &Option<TlsAcceptor>, stream: TcpStream) -> Result<Conn> {
+    let tls = match tls {
+        Some(tls) => tls,
+        None => return Ok(Conn::Tcp(stream)),
+    };
+    stream.set_read_timeout(Some(TLS_HANDSHAKE_TIMEOUT))?;
+    let stream = TlsStream::accept(&tls.server_config, stream)?;
+    stream.get_ref().set_read_timeout(None)?;
+    Ok(Conn::Tls(stream))
+}
@@ -0,0 +1,167 @@
+use super::{MessagePlugin, Server};
+use futures::{Future, Stream};
+use hyper::{client::HttpConnector, header::CONTENT_TYPE, Body, Client, Method, Request, Uri};
+use rpcx_protocol::*;
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::runtime::Runtime;
+
+/// the number of finished spans waiting to be exported, spans beyond it are dropped.
+pub const TRACE_QUEUE_SIZE: usize = 4096;
+
+// the most spans exported by a request to the collector.
+const TRACE_BATCH_SIZE: usize = 100;
+// how long a span waits to be exported with the following ones.
+const TRACE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// the metadata key of the start of the span of a request, in microseconds since the epoch.
+const SPAN_START: &str = "__rpcx_span_start__";
+
+/// records a span of each request handled by the server and exports them in batches to the
+/// Zipkin v2 API of a collector, which Jaeger collectors also serve when their Zipkin port is
+/// enabled.
+///
+/// The trace is continued from the B3 ids in the metadata of requests, or a new one is
+/// started. The ids of the span are set in the metadata the handlers see, so the calls they
+/// make with it continue the trace, and in the metadata of replies.
+#[derive(Debug)]
+pub struct TracingPlugin {
+    service_name: String,
+    sender: SyncSender<Value>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl TracingPlugin {
+    /// exports the spans to `endpoint`, such as `http://127.0.0.1:9411/api/v2/spans`.
+    /// `service_name` names the server in the traces.
+    pub fn new(endpoint: &str, service_name: &str) -> Result<Self> {
+        let uri = endpoint
+            .parse::<Uri>()
+            .map_err(|err| Error::new(ErrorKind::Config, err))?;
+        let (sender, receiver) = sync_channel(TRACE_QUEUE_SIZE);
+        thread::spawn(move || export(&uri, receiver));
+
+        Ok(TracingPlugin {
+            service_name: service_name.to_owned(),
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// returns the number of spans which were dropped since the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl MessagePlugin for TracingPlugin {
+    fn post_read_request(&self, req: &mut Message) -> Result<()> {
+        let mut metadata = req.metadata.borrow_mut();
+        SpanContext::child_of(&metadata).inject(&mut metadata);
+        metadata.insert(SPAN_START.to_owned(), now_micros().to_string());
+        Ok(())
+    }
+
+    fn pre_write_response(&self, req: &Message, res: &mut Message) -> Result<()> {
+        let metadata = req.metadata.borrow();
+        let start = match metadata.get_u64(SPAN_START) {
+            Some(start) => start,
+            None => return Ok(()),
+        };
+        let mut span = json!({
+            "traceId": metadata[TRACE_ID],
+            "id": metadata[SPAN_ID],
+            "name": format!("{}.{}", req.service_path, req.service_method),
+            "kind": "SERVER",
+            "timestamp": start,
+            "duration": now_micros().saturating_sub(start).max(1),
+            "localEndpoint": {"serviceName": self.service_name},
+        });
+        if let Some(parent_id) = metadata.get(PARENT_SPAN_ID) {
+            span["parentId"] = json!(parent_id);
+        }
+        if let Some(err) = Error::from_reply(res) {
+            span["tags"] = json!({"error": err.to_string()});
+        }
+
+        let mut res_metadata = res.metadata.borrow_mut();
+        res_metadata.insert(TRACE_ID.to_owned(), metadata[TRACE_ID].clone());
+        res_metadata.insert(SPAN_ID.to_owned(), metadata[SPAN_ID].clone());
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(span) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+// exports the spans in batches until the plugin is dropped.
+fn export(uri: &Uri, receiver: Receiver<Value>) {
+    // a runtime is created for each request, connections can't be reused across them
+    let client = Client::builder().keep_alive(false).build_http();
+    while let Ok(span) = receiver.recv() {
+        let mut batch = vec![span];
+        let deadline = Instant::now() + TRACE_FLUSH_INTERVAL;
+        while batch.len() < TRACE_BATCH_SIZE {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match receiver.recv_timeout(deadline - now) {
+                Ok(span) => batch.push(span),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        if let Err(err) = post(&client, uri, &batch) {
+            eprintln!("failed to export {} spans to {}: {}", batch.len(), uri, err);
+        }
+    }
+}
+
+fn post(client: &Client<HttpConnector>, uri: &Uri, spans: &[Value]) -> Result<()> {
+    let body = serde_json::to_vec(spans).map_err(|err| Error::new(ErrorKind::Other, err))?;
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(uri.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .map_err(|err| Error::new(ErrorKind::Other, err))?;
+    let op = client.request(req).and_then(|res| {
+        let status = res.status();
+        res.into_body().concat2().map(move |_| status)
+    });
+    let status = Runtime::new()
+        .unwrap()
+        .block_on(op)
+        .map_err(|err| Error::new(ErrorKind::Network, err))?;
+    if !status.is_success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("the collector replied {}", status),
+        ));
+    }
+    Ok(())
+}
+
+impl Server {
+    /// records the spans of requests and exports them to a Zipkin or Jaeger collector, see
+    /// `TracingPlugin`.
+    pub fn enable_tracing(&mut self, endpoint: &str, service_name: &str) -> Result<()> {
+        let plugin = TracingPlugin::new(endpoint, service_name)?;
+        self.add_message_plugin(Box::new(plugin));
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use super::http::{write_response, HttpRequest};
+use rpcx_protocol::*;
+use std::io::Write;
+
+// the subprotocol which clients may ask for, it is echoed to them.
+const SUBPROTOCOL: &str = "rpcx";
+
+/// returns whether the request asks to upgrade the connection to the WebSocket transport.
+pub(crate) fn is_upgrade(req: &HttpRequest) -> bool {
+    req.method == "GET"
+        && req.path == WEBSOCKET_PATH
+        && req
+            .get_header("Upgrade")
+            .map_or(false, |v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// replies the handshake of the WebSocket transport, the connection carries rpcx messages
+/// from then on. A malformed handshake is replied with 400 and fails.
+pub(crate) fn accept<W: Write>(w: &mut W, req: &HttpRequest) -> Result<()> {
+    let key = match req.get_header("Sec-WebSocket-Key") {
+        Some(key) if req.get_header("Sec-WebSocket-Version") == Some("13") => key,
+        _ => {
+            let _ = write_response(w, 400, &[], &[], false);
+            return Err(Error::new(
+                ErrorKind::Protocol,
+                "malformed websocket handshake",
+            ));
+        }
+    };
+
+    let mut resp = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n",
+        websocket_accept(key)
+    );
+    let protocols = req.get_header("Sec-WebSocket-Protocol").unwrap_or("");
+    if protocols.split(',').any(|p| p.trim() == SUBPROTOCOL) {
+        resp.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", SUBPROTOCOL));
+    }
+    resp.push_str("\r\n");
+    w.write_all(resp.as_bytes())?;
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use rpcx_protocol::*;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Write,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, TryLockError,
+    },
+    time::{Duration, Instant},
+};
+
+/// the default timeout of the writes to connections, see `Server::set_write_timeout`.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// the write half of a connection shared by responses, stream frames and pushed messages.
+///
+/// Messages are encoded into a pending buffer. The thread which gets the stream writes out
+/// everything pending, including the messages encoded by other threads in the meantime, so
+/// pipelined responses are coalesced into a single write. The pending bytes are bounded by
+/// an `OutboundQueue`, so a client which stops reading holds the writers back by the policy
+/// of the server instead of growing the buffer without limit.
+///
+/// It also tracks the last heartbeat of the client, which is the liveness of the connection,
+/// when the connection read its last message, and the requests and streams of the connection which
+/// are being handled.
+pub(crate) struct ConnWriter {
+    conn: Conn,
+    // the connection, wrapped by an encoder once the stream compression is negotiated
+    stream: Mutex<Box<dyn Write + Send>>,
+    pending: Mutex<Vec<u8>>,
+    queue: OutboundQueue,
+    last_heartbeat: Mutex<Option<Instant>>,
+    last_activity: Mutex<Instant>,
+    in_flight: AtomicUsize,
+    open_streams: AtomicUsize,
+    // the methods of the requests being handled and when they were read, by their seqs
+    calls: Mutex<HashMap<u64, (String, String, Instant)>>,
+}
+
+impl fmt::Debug for ConnWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnWriter")
+            .field("conn", &self.conn)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+impl ConnWriter {
+    pub(crate) fn new(
+        conn: Conn,
+        max_queued_bytes: usize,
+        backpressure: BackpressurePolicy,
+    ) -> Self {
+        let writer = conn.try_clone().unwrap();
+        ConnWriter {
+            conn,
+            stream: Mutex::new(Box::new(writer)),
+            pending: Mutex::new(buffer_pool().get()),
+            queue: OutboundQueue::new(max_queued_bytes, backpressure),
+            last_heartbeat: Mutex::new(None),
+            last_activity: Mutex::new(Instant::now()),
+            in_flight: AtomicUsize::new(0),
+            open_streams: AtomicUsize::new(0),
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn heartbeat(&self) {
+        *self.last_heartbeat.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub(crate) fn last_heartbeat(&self) -> Option<Instant> {
+        *self.last_heartbeat.lock().unwrap()
+    }
+
+    pub(crate) fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    pub(crate) fn last_activity(&self) -> Instant {
+        *self.last_activity.lock().unwrap()
+    }
+
+    pub(crate) fn start_request(&self, msg: &Message, received: Instant) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let method = (
+            msg.service_path.clone(),
+            msg.service_method.clone(),
+            received,
+        );
+        self.calls.lock().unwrap().insert(msg.get_seq(), method);
+    }
+
+    pub(crate) fn finish_request(&self, seq: u64) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.calls.lock().unwrap().remove(&seq);
+    }
+
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn open_stream(&self) {
+        self.open_streams.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn close_stream(&self) {
+        self.open_streams.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn open_streams(&self) -> usize {
+        self.open_streams.load(Ordering::SeqCst)
+    }
+
+    /// returns the seqs, the methods and the start of the requests being handled.
+    pub(crate) fn calls(&self) -> Vec<(u64, String, String, Instant)> {
+        let calls = self.calls.lock().unwrap();
+        calls
+            .iter()
+            .map(|(seq, (path, method, received))| (*seq, path.clone(), method.clone(), *received))
+            .collect()
+    }
+
+    /// shuts the connection down, its reader fails and cleans it up.
+    pub(crate) fn shutdown(&self) {
+        self.queue.close();
+        let _ = self.conn.get_ref().shutdown(Shutdown::Both);
+    }
+
+    /// queues the message and flushes it unless another thread is flushing, which then
+    /// writes it out as well.
+    ///
+    /// If the queue is full, it waits for the queue to be drained or fails with
+    /// `ErrorKind::ServerBusy` by the policy. The error of a failed write is returned to the
+    /// thread which flushes.
+    pub(crate) fn write_msg(&self, msg: &Message) -> Result<()> {
+        let data = self.reserve(msg)?;
+        self.queue_data(data)
+    }
+
+    /// like `write_msg` but fails with `ErrorKind::ServerBusy` instead of waiting when the
+    /// queue is full, for the messages pushed to clients, so a client which stops reading
+    /// doesn't hold back the deliveries to the others.
+    pub(crate) fn push_msg(&self, msg: &Message) -> Result<()> {
+        let mut data = buffer_pool().get();
+        msg.encode_to(&mut data);
+        if let Err(err) = self.queue.try_reserve(data.len()) {
+            buffer_pool().put(data);
+            return Err(err);
+        }
+        self.queue_data(data)
+    }
+
+    // appends the reserved data to the pending buffer and flushes it.
+    fn queue_data(&self, data: Vec<u8>) -> Result<()> {
+        self.pending.lock().unwrap().extend_from_slice(&data);
+        buffer_pool().put(data);
+        self.flush_pending()
+    }
+
+    /// writes the reply which accepts the stream compression and everything queued before
+    /// it, then compresses the messages written from now on.
+    pub(crate) fn start_compression(
+        &self,
+        reply: &Message,
+        compression: &StreamCompression,
+    ) -> Result<()> {
+        // reserved before the stream is held, the holder of the stream drains the queue
+        let reply = self.reserve(reply)?;
+        let mut stream = match self.stream.lock() {
+            Ok(stream) => stream,
+            Err(err) => err.into_inner(),
+        };
+        let data = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.extend_from_slice(&reply);
+            std::mem::replace(&mut *pending, buffer_pool().get())
+        };
+        buffer_pool().put(reply);
+        self.write_out(&mut **stream, data)?;
+        *stream = compression.encoder(self.conn.try_clone()?)?;
+        drop(stream);
+
+        // the messages queued meanwhile are left to this thread
+        self.flush_pending()
+    }
+
+    // encodes the message and reserves its bytes from the queue.
+    fn reserve(&self, msg: &Message) -> Result<Vec<u8>> {
+        let mut data = buffer_pool().get();
+        msg.encode_to(&mut data);
+        if let Err(err) = self.queue.reserve(data.len()) {
+            buffer_pool().put(data);
+            return Err(err);
+        }
+        Ok(data)
+    }
+
+    // writes out everything pending unless another thread holds the stream.
+    fn flush_pending(&self) -> Result<()> {
+        loop {
+            let mut stream = match self.stream.try_lock() {
+                Ok(stream) => stream,
+                Err(TryLockError::WouldBlock) => return Ok(()),
+                Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            };
+
+            let data = {
+                let mut pending = self.pending.lock().unwrap();
+                if pending.is_empty() {
+                    // release the stream first, a writer which queues after the pending lock
+                    // is released must be able to take the stream.
+                    drop(stream);
+                    return Ok(());
+                }
+                std::mem::replace(&mut *pending, buffer_pool().get())
+            };
+
+            self.write_out(&mut **stream, data)?;
+        }
+    }
+
+    // writes the data and releases its bytes from the queue. The connection is shut down once
+    // a write fails or times out, the writers waiting for the queue fail as well.
+    fn write_out(&self, stream: &mut dyn Write, data: Vec<u8>) -> Result<()> {
+        let rt = stream.write_all(&data).and_then(|_| stream.flush());
+        self.queue.release(data.len());
+        buffer_pool().put(data);
+        if rt.is_err() {
+            self.shutdown();
+        }
+        rt.map_err(Error::from)
+    }
+}
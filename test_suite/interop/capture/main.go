@@ -0,0 +1,47 @@
+// capture writes a request encoded by rpcx-go to stdout, so rpcx-rs can compare its own
+// encoding byte-for-byte.
+package main
+
+import (
+	"flag"
+	"os"
+	"strings"
+
+	"github.com/smallnest/rpcx/protocol"
+)
+
+var (
+	seq      = flag.Uint64("seq", 0, "message seq")
+	st       = flag.Int("st", int(protocol.JSON), "serialize type")
+	ct       = flag.Int("ct", int(protocol.None), "compress type")
+	oneway   = flag.Bool("oneway", false, "oneway flag")
+	status   = flag.Int("status", int(protocol.Normal), "message status type")
+	response = flag.Bool("response", false, "encode a response")
+	meta     = flag.String("meta", "", "metadata as key=value (one entry at most)")
+	payload  = flag.String("payload", "", "payload")
+)
+
+func main() {
+	flag.Parse()
+
+	msg := protocol.NewMessage()
+	if *response {
+		msg.SetMessageType(protocol.Response)
+	} else {
+		msg.SetMessageType(protocol.Request)
+	}
+	msg.SetSeq(*seq)
+	msg.SetSerializeType(protocol.SerializeType(*st))
+	msg.SetCompressType(protocol.CompressType(*ct))
+	msg.SetOneway(*oneway)
+	msg.SetMessageStatusType(protocol.MessageStatusType(*status))
+	msg.ServicePath = "Arith"
+	msg.ServiceMethod = "Mul"
+	if *meta != "" {
+		kv := strings.SplitN(*meta, "=", 2)
+		msg.Metadata = map[string]string{kv[0]: kv[1]}
+	}
+	msg.Payload = []byte(*payload)
+
+	os.Stdout.Write(msg.Encode())
+}
@@ -0,0 +1,45 @@
+// client is the rpcx-go client used by the interop tests of rpcx-rs.
+// It calls Arith.Mul with every compress type and exits with a non-zero status on any mismatch.
+package main
+
+import (
+	"context"
+	"flag"
+	"log"
+
+	"github.com/smallnest/rpcx-rs/test_suite/interop"
+	"github.com/smallnest/rpcx/client"
+	"github.com/smallnest/rpcx/protocol"
+)
+
+var addr = flag.String("addr", "127.0.0.1:8991", "server address")
+
+func main() {
+	flag.Parse()
+
+	for _, st := range []protocol.SerializeType{protocol.JSON} {
+		for _, ct := range []protocol.CompressType{protocol.None, protocol.Gzip} {
+			opt := client.DefaultOption
+			opt.SerializeType = st
+			opt.CompressType = ct
+
+			d := client.NewPeer2PeerDiscovery("tcp@"+*addr, "")
+			xclient := client.NewXClient("Arith", client.Failfast, client.RandomSelect, d, opt)
+
+			args := &interop.Args{A: 10, B: 20}
+			reply := &interop.Reply{}
+			if err := xclient.Call(context.Background(), "Mul", args, reply); err != nil {
+				log.Fatalf("serialize type %d, compress type %d: %v", st, ct, err)
+			}
+			if reply.C != 200 {
+				log.Fatalf("serialize type %d, compress type %d: expect 200, got %d", st, ct, reply.C)
+			}
+
+			err := xclient.Call(context.Background(), "Div", args, reply)
+			if err == nil || err.Error() != "service Arith.Div not found" {
+				log.Fatalf("serialize type %d, compress type %d: unexpected error %v", st, ct, err)
+			}
+			xclient.Close()
+		}
+	}
+}
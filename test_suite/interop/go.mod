@@ -0,0 +1,5 @@
+module github.com/smallnest/rpcx-rs/test_suite/interop
+
+go 1.13
+
+require github.com/smallnest/rpcx v1.6.11
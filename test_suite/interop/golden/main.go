@@ -0,0 +1,76 @@
+// golden writes the messages encoded by rpcx-go to ../testdata, the fixtures of test_golden.rs.
+// Run it by `go run ./golden` in this directory after upgrading rpcx-go.
+package main
+
+import (
+	"io/ioutil"
+	"log"
+	"path/filepath"
+
+	"github.com/smallnest/rpcx/protocol"
+)
+
+const requestID = "6ba7b810-9dad-11d1-80b4-00c04fd430c9"
+
+type fixture struct {
+	name      string
+	seq       uint64
+	st        protocol.SerializeType
+	ct        protocol.CompressType
+	response  bool
+	heartbeat bool
+	oneway    bool
+	status    protocol.MessageStatusType
+	path      string
+	method    string
+	meta      map[string]string
+	payload   []byte
+}
+
+var (
+	jsonArgs     = []byte(`{"A":1,"B":2}`)
+	msgpackArgs  = []byte{0x82, 0xa1, 'A', 0x01, 0xa1, 'B', 0x02}
+	protobufArgs = []byte{0x08, 0x01, 0x10, 0x02}
+	id           = map[string]string{"__ID": requestID}
+)
+
+var fixtures = []fixture{
+	{name: "request_none", seq: 1234567890, st: protocol.SerializeNone, path: "Arith", method: "Mul", meta: id, payload: []byte("raw payload")},
+	{name: "request_json", seq: 1234567890, st: protocol.JSON, path: "Arith", method: "Mul", meta: id, payload: jsonArgs},
+	{name: "request_protobuf", seq: 1234567890, st: protocol.ProtoBuffer, path: "Arith", method: "Mul", meta: id, payload: protobufArgs},
+	{name: "request_msgpack", seq: 1234567890, st: protocol.MsgPack, path: "Arith", method: "Mul", meta: id, payload: msgpackArgs},
+	{name: "request_json_gzip", seq: 1234567890, st: protocol.JSON, ct: protocol.Gzip, path: "Arith", method: "Mul", meta: id, payload: jsonArgs},
+	{name: "request_msgpack_gzip", seq: 1234567890, st: protocol.MsgPack, ct: protocol.Gzip, path: "Arith", method: "Mul", meta: id, payload: msgpackArgs},
+	{name: "request_oneway", seq: 7, st: protocol.JSON, oneway: true, path: "Arith", method: "Mul", payload: jsonArgs},
+	{name: "heartbeat", seq: 8, st: protocol.JSON, heartbeat: true},
+	{name: "response_json", seq: 1234567890, st: protocol.JSON, response: true, path: "Arith", method: "Mul", payload: []byte(`{"C":2}`)},
+	{name: "response_msgpack", seq: 1234567890, st: protocol.MsgPack, response: true, path: "Arith", method: "Mul", payload: []byte{0x81, 0xa1, 'C', 0x02}},
+	{name: "response_error", seq: 18446744073709551615, st: protocol.JSON, response: true, status: protocol.Error, path: "Arith", method: "Div",
+		meta: map[string]string{protocol.ServiceError: "rpcx: can't find method Div"}},
+}
+
+func main() {
+	for _, f := range fixtures {
+		msg := protocol.NewMessage()
+		if f.response {
+			msg.SetMessageType(protocol.Response)
+		} else {
+			msg.SetMessageType(protocol.Request)
+		}
+		msg.SetSeq(f.seq)
+		msg.SetSerializeType(f.st)
+		msg.SetCompressType(f.ct)
+		msg.SetHeartbeat(f.heartbeat)
+		msg.SetOneway(f.oneway)
+		msg.SetMessageStatusType(f.status)
+		msg.ServicePath = f.path
+		msg.ServiceMethod = f.method
+		msg.Metadata = f.meta
+		msg.Payload = f.payload
+
+		path := filepath.Join("..", "testdata", f.name+".bin")
+		if err := ioutil.WriteFile(path, msg.Encode(), 0644); err != nil {
+			log.Fatal(err)
+		}
+	}
+}
@@ -0,0 +1,21 @@
+// server is the rpcx-go server used by the interop tests of rpcx-rs.
+package main
+
+import (
+	"flag"
+
+	"github.com/smallnest/rpcx-rs/test_suite/interop"
+	"github.com/smallnest/rpcx/server"
+)
+
+var addr = flag.String("addr", "127.0.0.1:8990", "server address")
+
+func main() {
+	flag.Parse()
+
+	s := server.NewServer()
+	s.RegisterName("Arith", new(interop.Arith), "")
+	if err := s.Serve("tcp", *addr); err != nil {
+		panic(err)
+	}
+}
@@ -0,0 +1,28 @@
+// the service and the cluster which the integration tests share. Each test crate uses a part
+// of them.
+#![allow(dead_code)]
+
+use mul_model::{ArithAddArgs, ArithAddReply};
+use rpcx::{testing::TestCluster, *};
+
+pub fn mul(args: ArithAddArgs) -> ArithAddReply {
+    ArithAddReply { c: args.a * args.b }
+}
+
+/// registers `mul` as `Arith.Mul`.
+pub fn register_mul(rpc_server: &mut Server) {
+    register_func!(
+        rpc_server,
+        "Arith",
+        "Mul",
+        mul,
+        "".to_owned(),
+        ArithAddArgs,
+        ArithAddReply
+    );
+}
+
+/// starts `n` servers of `Arith.Mul`.
+pub fn start_cluster(n: usize) -> TestCluster {
+    TestCluster::start(n, register_mul).unwrap()
+}
@@ -0,0 +1,43 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, env, net::TcpListener, thread};
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_from_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut rpc_server = Server::from_listener(listener).unwrap();
+        assert_eq!(addr, rpc_server.addr);
+        register_mul(&mut rpc_server);
+        thread::spawn(move || {
+            let _ = rpc_server.start();
+        });
+
+        // the listener is bound already, so the client can connect at once
+        let mut c = Client::new(&addr);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_listen_fds() {
+        // the sockets are passed to another process
+        env::set_var("LISTEN_PID", (std::process::id() + 1).to_string());
+        env::set_var("LISTEN_FDS", "1");
+        assert!(listen_fds().unwrap().is_empty());
+        assert!(env::var("LISTEN_FDS").is_err());
+
+        let err = Server::from_systemd().err().unwrap();
+        assert_eq!(ErrorKind::Config, err.kind());
+    }
+}
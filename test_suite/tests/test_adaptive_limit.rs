@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, sync::Arc, thread, time::Duration};
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(200));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn start_cluster(server_limit: Option<Arc<AdaptiveLimit>>) -> TestCluster {
+        TestCluster::start(1, move |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            if let Some(limit) = &server_limit {
+                rpc_server.add_message_plugin(Box::new(AdaptiveLimitPlugin::new(limit.clone())));
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_server_adaptive_limit() {
+        let limit = Arc::new(AdaptiveLimit::new(LimitAlgorithm::Gradient, 1, 1, 1));
+        let cluster = start_cluster(Some(limit.clone()));
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        thread::sleep(Duration::from_millis(50));
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+        assert_eq!(0, limit.in_flight());
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_adaptive_limit_builtin_services() {
+        let limit = Arc::new(AdaptiveLimit::new(LimitAlgorithm::Gradient, 1, 1, 1));
+        let cluster = start_cluster(Some(limit.clone()));
+
+        // the requests of the builtin services release their slots like the calls
+        let mut c = Client::new(&cluster.servers()[0].addr);
+        c.start().unwrap();
+        for _ in 0..5 {
+            c.subscribe_topic("orders").unwrap();
+        }
+        assert_eq!(0, limit.in_flight());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        assert_eq!(0, limit.in_flight());
+    }
+
+    #[test]
+    fn test_client_adaptive_limit() {
+        let cluster = start_cluster(None);
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let limit = Arc::new(AdaptiveLimit::new(LimitAlgorithm::Gradient, 1, 1, 1));
+        xc.set_adaptive_limit(limit.clone());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        assert_eq!(1, limit.in_flight());
+        // the call beyond the limit isn't sent
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+        assert_eq!(0, limit.in_flight());
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        assert_eq!(0, limit.in_flight());
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use super::common::mul;
+
+    fn send(addr: &SocketAddr, req: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        resp
+    }
+
+    fn get(addr: &SocketAddr, path: &str, token: &str) -> String {
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer {}\r\n\
+             Connection: close\r\n\r\n",
+            path, token
+        );
+        send(addr, &req)
+    }
+
+    #[test]
+    fn test_admin() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                mul,
+                "group=a".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+        assert!(server.start_admin("127.0.0.1:0", "").is_err());
+        let addr = server.start_admin("127.0.0.1:0", "secret").unwrap();
+
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        assert!(get(&addr, "/services", "wrong").starts_with("HTTP/1.1 401"));
+        assert!(get(&addr, "/services", "secre").starts_with("HTTP/1.1 401"));
+        // the token must follow the Bearer scheme
+        let req = "GET /services HTTP/1.1\r\nAuthorization: secret\r\nConnection: close\r\n\r\n";
+        assert!(send(&addr, req).starts_with("HTTP/1.1 401"));
+        // unauthorized requests are replied before their body is sent
+        let req =
+            "POST /services HTTP/1.1\r\nAuthorization: Bearer wrong\r\nContent-Length: 100\r\n\r\n";
+        assert!(send(&addr, req).starts_with("HTTP/1.1 401"));
+
+        let resp = get(&addr, "/services", "secret");
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with(r#"{"Arith":{"meta":"group=a","methods":["Mul"],"streams":[]}}"#));
+
+        let resp = get(&addr, "/connections", "secret");
+        assert!(resp.contains(r#""count":1"#));
+        assert!(resp.contains(r#""in_flight":0"#));
+
+        let resp = get(&addr, "/config", "secret");
+        assert!(resp.contains(&format!(r#""addr":"{}""#, server.addr)));
+
+        assert!(get(&addr, "/plugins", "secret").contains(r#""message":0"#));
+        assert!(get(&addr, "/unknown", "secret").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_admin_connection_limit() {
+        let cluster = TestCluster::start(1, |_| {}).unwrap();
+        let server = cluster.servers()[0].clone();
+        let addr = server.start_admin("127.0.0.1:0", "secret").unwrap();
+
+        let idle: Vec<TcpStream> = (0..16).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        thread::sleep(Duration::from_millis(200));
+        assert!(get(&addr, "/config", "secret").starts_with("HTTP/1.1 503"));
+
+        drop(idle);
+        thread::sleep(Duration::from_millis(200));
+        assert!(get(&addr, "/config", "secret").starts_with("HTTP/1.1 200"));
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+
+    const CHUNK: usize = 256 * 1024;
+
+    // accepts a connection which is not read until the stream is taken from the receiver.
+    fn stalled_server() -> (String, mpsc::Receiver<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            sender.send(conn).unwrap();
+        });
+        (addr, receiver)
+    }
+
+    fn start_client(addr: &str, policy: BackpressurePolicy) -> Client {
+        let mut c = Client::new(addr);
+        c.opt.max_queued_bytes = 1024 * 1024;
+        c.opt.backpressure = policy;
+        c.opt.read_timeout = Duration::from_millis(100);
+        c.start().unwrap();
+        c
+    }
+
+    #[test]
+    fn test_backpressure_fail() {
+        let (addr, _conns) = stalled_server();
+        let c = start_client(&addr, BackpressurePolicy::Fail);
+
+        let metadata = HashMap::new();
+        let args = Bytes::from(vec![0u8; CHUNK]);
+        // far beyond the socket buffers, the ones which don't fit the queue are dropped
+        for _ in 0..256 {
+            c.call::<Bytes>("Echo", "Echo", true, &metadata, &args);
+        }
+        let reply: Option<Result<Bytes>> = c.call("Echo", "Echo", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_backpressure_block() {
+        let (addr, conns) = stalled_server();
+        let c = start_client(&addr, BackpressurePolicy::Block);
+
+        let (sent, sent_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let metadata = HashMap::new();
+            let args = Bytes::from(vec![0u8; CHUNK]);
+            for _ in 0..256 {
+                c.call::<Bytes>("Echo", "Echo", true, &metadata, &args);
+            }
+            sent.send(()).unwrap();
+        });
+
+        // the sender waits while the server doesn't read
+        assert!(sent_receiver
+            .recv_timeout(Duration::from_millis(500))
+            .is_err());
+
+        let mut conn = conns.recv().unwrap();
+        thread::spawn(move || {
+            let mut buf = vec![0u8; CHUNK];
+            while let Ok(n) = conn.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+        });
+        sent_receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    #[test]
+    fn test_stalled_subscriber() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            rpc_server.set_outbound_limit(1024 * 1024, BackpressurePolicy::Block);
+            rpc_server.set_write_timeout(Some(Duration::from_secs(1)));
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+
+        // subscribes without ever reading
+        let mut stalled = TcpStream::connect(&server.addr).unwrap();
+        let mut msg = Message::new();
+        msg.set_message_type(MessageType::Request);
+        msg.set_oneway(true);
+        msg.set_serialize_type(SerializeType::SerializeNone);
+        msg.service_path = PUBSUB_SERVICE.to_owned();
+        msg.service_method = PUBSUB_SUBSCRIBE.to_owned();
+        msg.payload = Bytes::from(&b"orders"[..]);
+        stalled.write_all(&msg.encode()).unwrap();
+
+        let mut c = Client::new(&server.addr);
+        c.start().unwrap();
+        let messages = c.subscribe();
+        c.subscribe_topic("orders").unwrap();
+
+        // the deliveries to the reading subscriber go on
+        let metadata = HashMap::new();
+        for _ in 0..64 {
+            server
+                .publish("orders", &metadata, vec![0u8; CHUNK])
+                .unwrap();
+        }
+        for _ in 0..64 {
+            messages.recv_timeout(Duration::from_secs(10)).unwrap();
+        }
+    }
+}
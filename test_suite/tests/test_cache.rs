@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn mul(args: ArithAddArgs) -> ArithAddReply {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn call_mul(xc: &mut XClient<RoundbinSelector>, a: u64) -> u64 {
+        let args = ArithAddArgs { a, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        reply.unwrap().unwrap().c
+    }
+
+    #[test]
+    fn test_cache() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        assert_eq!(None, xc.cache_stats("Mul"));
+        xc.enable_cache("Mul", Duration::from_secs(60), 2);
+
+        for _ in 0..3 {
+            assert_eq!(10, call_mul(&mut xc, 1));
+        }
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        let args = ArithAddArgs { a: 1, b: 10 };
+        let reply = xc
+            .acall::<ArithAddReply>("Mul", &HashMap::new(), &args)
+            .wait()
+            .unwrap();
+        assert_eq!(10, reply.unwrap().c);
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        let stats = xc.cache_stats("Mul").unwrap();
+        assert_eq!((3, 1, 1), (stats.hits, stats.misses, stats.entries));
+        assert_eq!(0.75, stats.hit_rate());
+
+        // the oldest reply is evicted
+        assert_eq!(20, call_mul(&mut xc, 2));
+        assert_eq!(30, call_mul(&mut xc, 3));
+        assert_eq!(2, xc.cache_stats("Mul").unwrap().entries);
+        assert_eq!(10, call_mul(&mut xc, 1));
+        assert_eq!(4, CALLS.load(Ordering::SeqCst));
+
+        // the calls with other metadata are not served by the cached replies
+        let mut metadata = HashMap::new();
+        metadata.insert("tenant".to_owned(), "b".to_owned());
+        let reply: ArithAddReply = xc.call("Mul", false, &metadata, &args).unwrap().unwrap();
+        assert_eq!(10, reply.c);
+        assert_eq!(5, CALLS.load(Ordering::SeqCst));
+
+        // cached replies expire
+        xc.disable_cache("Mul");
+        assert_eq!(None, xc.cache_stats("Mul"));
+        xc.enable_cache("Mul", Duration::from_millis(50), 10);
+        assert_eq!(10, call_mul(&mut xc, 1));
+        assert_eq!(10, call_mul(&mut xc, 1));
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(10, call_mul(&mut xc, 1));
+        assert_eq!(7, CALLS.load(Ordering::SeqCst));
+    }
+}
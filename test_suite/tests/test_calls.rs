@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn get(addr: &SocketAddr, path: &str, token: &str) -> String {
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer {}\r\n\
+             Connection: close\r\n\r\n",
+            path, token
+        );
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        resp
+    }
+
+    #[test]
+    fn test_in_flight_calls() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+        let addr = server.start_admin("127.0.0.1:0", "secret").unwrap();
+        assert!(server.in_flight_calls().is_empty());
+
+        let mut c = Client::new(&cluster.servers()[0].addr);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let f = c.acall::<ArithAddReply>("Arith", "Mul", &metadata, &args);
+        thread::sleep(Duration::from_millis(100));
+
+        let calls = server.in_flight_calls();
+        assert_eq!(1, calls.len());
+        assert_eq!("Arith", calls[0].service_path);
+        assert_eq!("Mul", calls[0].service_method);
+        assert!(calls[0].elapsed >= Duration::from_millis(50));
+        let resp = get(&addr, "/calls", "secret");
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.contains(r#""service_method":"Mul""#));
+        assert!(resp.contains(&format!(r#""seq":{}"#, calls[0].seq)));
+
+        assert_eq!(20, f.wait().unwrap().unwrap().c);
+        // the call is removed after its reply is written
+        thread::sleep(Duration::from_millis(50));
+        assert!(server.in_flight_calls().is_empty());
+        assert!(get(&addr, "/calls", "secret").ends_with("[]"));
+    }
+}
@@ -0,0 +1,124 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::common::register_mul;
+
+    static SERVERS: AtomicUsize = AtomicUsize::new(0);
+
+    // the canary replies differently so the tests can tell where calls are routed
+    fn mul_canary(args: ArithAddArgs) -> ArithAddReply {
+        ArithAddReply {
+            c: args.a * args.b + 1,
+        }
+    }
+
+    fn metadata(uid: &str) -> Metadata {
+        let mut metadata = HashMap::new();
+        metadata.insert("uid".to_owned(), uid.to_owned());
+        metadata
+    }
+
+    #[test]
+    fn test_canary_routing() {
+        let cluster = TestCluster::start(2, |server| {
+            if SERVERS.fetch_add(1, Ordering::SeqCst) == 0 {
+                register_mul(server);
+            } else {
+                register_func!(
+                    server,
+                    "Arith",
+                    "Mul",
+                    mul_canary,
+                    "".to_owned(),
+                    ArithAddArgs,
+                    ArithAddReply
+                );
+            }
+        })
+        .unwrap();
+        let addrs = cluster.addrs();
+
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config.servers.insert(addrs[0].clone(), String::new());
+        config
+            .servers
+            .insert(addrs[1].clone(), "canary=true".to_owned());
+        config.canary = Some(CanaryConfig {
+            percent: 0,
+            rules: vec!["uid % 100 < 5".to_owned()],
+        });
+        let mut xc = XClient::with_config(&config).unwrap();
+
+        let args = ArithAddArgs { a: 10, b: 20 };
+        let mut call = |metadata: &Metadata| -> u64 {
+            let reply: ArithAddReply = xc.call("Mul", false, metadata, &args).unwrap().unwrap();
+            reply.c
+        };
+        for _ in 0..10 {
+            assert_eq!(201, call(&metadata("103")));
+            assert_eq!(200, call(&metadata("150")));
+            assert_eq!(200, call(&HashMap::new()));
+        }
+
+        // every call goes to the canary
+        config.canary = Some(CanaryConfig {
+            percent: 100,
+            rules: Vec::new(),
+        });
+        xc.reload(&config).unwrap();
+        let reply: ArithAddReply = xc
+            .call("Mul", false, &HashMap::new(), &args)
+            .unwrap()
+            .unwrap();
+        assert_eq!(201, reply.c);
+
+        // without canaries the calls go to the other servers
+        config.servers.remove(&addrs[1]);
+        xc.reload(&config).unwrap();
+        let reply: ArithAddReply = xc
+            .call("Mul", false, &HashMap::new(), &args)
+            .unwrap()
+            .unwrap();
+        assert_eq!(200, reply.c);
+
+        config.canary = Some(CanaryConfig {
+            percent: 0,
+            rules: vec!["uid ~ 1".to_owned()],
+        });
+        assert!(xc.reload(&config).is_err());
+    }
+
+    #[test]
+    fn test_canary_predicates() {
+        let rule = CanaryRule::new(0, &["region == eu", "build >= 42"]).unwrap();
+        let mut meta = HashMap::new();
+        assert!(!rule.matches(&meta));
+        meta.insert("region".to_owned(), "eu".to_owned());
+        assert!(rule.matches(&meta));
+        meta.insert("region".to_owned(), "us".to_owned());
+        assert!(!rule.matches(&meta));
+        meta.insert("build".to_owned(), "100".to_owned());
+        assert!(rule.matches(&meta));
+
+        // users whose ids are not numbers are hashed to the same route every time
+        let rule = CanaryRule::new(0, &["uid % 2 == 0"]).unwrap();
+        let routed = rule.matches(&metadata("alice"));
+        for _ in 0..10 {
+            assert_eq!(routed, rule.matches(&metadata("alice")));
+        }
+
+        assert!(CanaryRule::new(0, &["uid"]).is_err());
+        assert!(CanaryRule::new(0, &["uid % 0 < 5"]).is_err());
+        assert!(CanaryRule::new(0, &["== 5"]).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_cli() {
+        let mut rpc_server = Server::new("127.0.0.1:8987".to_owned(), 0);
+        register_mul(&mut rpc_server);
+        thread::spawn(move || {
+            if let Err(err) = rpc_server.start() {
+                println!("{}", err);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let mut config = XClientConfig::default();
+        config
+            .servers
+            .insert("tcp@127.0.0.1:8987".to_owned(), String::new());
+
+        let services = rpcx_cli::list("127.0.0.1:8987", &config).unwrap();
+        assert_eq!(vec!["Arith.Mul"], services);
+
+        let metadata = HashMap::new();
+        let args = r#"{"A":10,"B":20}"#;
+        let reply = rpcx_cli::call(&config, "Arith.Mul", &metadata, args).unwrap();
+        assert_eq!("{\n  \"C\": 200\n}", reply);
+
+        // the MsgPack replies of rpcx-rs are positional
+        config.serialize_type = SerializeType::MsgPack;
+        let reply = rpcx_cli::call(&config, "Arith.Mul", &metadata, args).unwrap();
+        assert_eq!("[\n  200\n]", reply);
+
+        let err = rpcx_cli::call(&config, "Arith.Div", &metadata, "{}").unwrap_err();
+        assert!(err.to_string().contains("Div"));
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn start_cluster() -> TestCluster {
+        TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_close() {
+        let cluster = start_cluster();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let f = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+
+        // waits for the call in flight
+        thread::sleep(Duration::from_millis(50));
+        xc.close(Duration::from_secs(5)).unwrap();
+        assert!(xc.is_closed());
+        assert_eq!(20, f.wait().unwrap().unwrap().c);
+
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::Client, reply.unwrap().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_close_timeout() {
+        let cluster = start_cluster();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let f = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+
+        thread::sleep(Duration::from_millis(50));
+        let err = xc.close(Duration::from_millis(50)).unwrap_err();
+        assert_eq!(ErrorKind::Timeout, err.kind());
+        assert!(f.wait().unwrap().is_err());
+    }
+}
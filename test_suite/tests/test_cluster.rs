@@ -0,0 +1,30 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::collections::HashMap;
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_cluster() {
+        let cluster = start_cluster(3);
+        assert_eq!(3, cluster.addrs().len());
+
+        let mut xc = cluster.xclient("Arith", FailMode::Failover);
+        let metadata = HashMap::new();
+        for a in 0..9 {
+            let args = ArithAddArgs { a, b: 10 };
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(a * 10, reply.unwrap().unwrap().c);
+        }
+
+        // round robin has connected to every server
+        for server in cluster.servers() {
+            assert_eq!(1, server.active_conns().len());
+        }
+    }
+}
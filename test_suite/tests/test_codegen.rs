@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use idl_model::{register_arith, Arith, ArithAddArgs, ArithAddReply, ArithClient};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::HashMap;
+
+    struct Calculator;
+
+    impl Arith for Calculator {
+        fn add(args: ArithAddArgs) -> ArithAddReply {
+            ArithAddReply { c: args.a + args.b }
+        }
+
+        fn mul(args: ArithAddArgs) -> ArithAddReply {
+            ArithAddReply { c: args.a * args.b }
+        }
+    }
+
+    #[test]
+    fn test_generated_service() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_arith::<Calculator>(rpc_server, "".to_owned());
+        })
+        .unwrap();
+        let mut client = ArithClient::new(cluster.xclient("Arith", FailMode::Failfast));
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        assert_eq!(12, client.add(&metadata, &args).unwrap().c);
+        assert_eq!(20, client.mul(&metadata, &args).unwrap().c);
+        let reply = client.mul_async(&metadata, &args).wait().unwrap();
+        assert_eq!(20, reply.unwrap().c);
+
+        // the payloads are the ones of the hand-written model
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let args = mul_model::ArithAddArgs { a: 3, b: 4 };
+        let reply: Option<Result<mul_model::ArithAddReply>> =
+            xc.call("Mul", false, &metadata, &args);
+        assert_eq!(12, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_generated_client_with_mock() {
+        let mut mock = MockClient::new("Arith");
+        mock.reply("Mul", &ArithAddReply { c: 42 }).unwrap();
+        let mut client = ArithClient::new(mock);
+        let args = ArithAddArgs { a: 6, b: 7 };
+        assert_eq!(42, client.mul(&HashMap::new(), &args).unwrap().c);
+        assert_eq!(1, client.client.calls("Mul"));
+    }
+}
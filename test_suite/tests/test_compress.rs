@@ -0,0 +1,62 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, io::Write, net::TcpStream};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_negotiate_compress_type() {
+        let cluster = start_cluster(1);
+        let server = cluster.servers()[0].clone();
+
+        let mut c = Client::new(&server.addr);
+        c.opt.compress_type = CompressType::Gzip;
+        c.start().unwrap();
+        assert_eq!(supported_compressors(), c.compressors().unwrap());
+        assert_eq!(CompressType::Gzip, c.negotiate_compress_type().unwrap());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_unsupported_compress_type() {
+        let cluster = start_cluster(1);
+        let server = cluster.servers()[0].clone();
+
+        let mut stream = TcpStream::connect(&server.addr).unwrap();
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(SerializeType::JSON);
+        req.set_seq(7);
+        req.service_path = "Arith".to_owned();
+        req.service_method = "Mul".to_owned();
+        req.payload = Bytes::from(&b"compressed by a newer client"[..]);
+        // a compress type this server doesn't know
+        req.header[2] |= 3 << 2;
+        stream.write_all(&req.encode()).unwrap();
+
+        // the request is rejected without closing the connection
+        let mut reply = Message::new();
+        reply.decode(&mut stream).unwrap();
+        assert_eq!(7, reply.get_seq());
+        assert_eq!(Some(CompressType::CompressNone), reply.get_compress_type());
+        let err = Error::from_reply(&reply).unwrap();
+        assert_eq!(ErrorKind::Protocol, err.kind());
+
+        let mut c = Client::new(&server.addr);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        env, fs,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        process,
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_config() {
+        let dir = env::temp_dir().join(format!("rpcx_test_config_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let server_config = dir.join("server.toml");
+        fs::write(
+            &server_config,
+            "addr = \"127.0.0.1:8985\"\nthread_number = 4\n",
+        )
+        .unwrap();
+        let client_config = dir.join("client.yaml");
+        fs::write(
+            &client_config,
+            r#"
+service_path: Arith
+fail_mode: Failover
+select_mode: WeightedRoundRobin
+serialize_type: MsgPack
+compress_type: Gzip
+read_timeout_ms: 1000
+servers:
+  tcp@127.0.0.1:8985: weight=10
+"#,
+        )
+        .unwrap();
+
+        // setup server
+        let mut rpc_server = Server::from_config(&server_config).unwrap();
+        register_mul(&mut rpc_server);
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let rpc_server = Arc::new(rpc_server);
+        let server_cloned = rpc_server.clone();
+        let handler = thread::spawn(move || match server_cloned.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut xc = XClient::from_config(&client_config).unwrap();
+        assert_eq!(SerializeType::MsgPack, xc.opt.serialize_type);
+        assert_eq!(CompressType::Gzip, xc.opt.compress_type);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 6, b: 7 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(42, reply.unwrap().unwrap().c);
+
+        // reloading keeps the connection and applies the new options
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config.select_mode = SelectMode::RoundRobin;
+        config.read_timeout_ms = 2000;
+        config
+            .servers
+            .insert("tcp@127.0.0.1:8985".to_owned(), String::new());
+        xc.reload(&config).unwrap();
+        assert_eq!(Duration::from_millis(2000), xc.opt.read_timeout);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(42, reply.unwrap().unwrap().c);
+
+        config.servers.clear();
+        xc.reload(&config).unwrap();
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+        // the connection to the removed server is closed
+        thread::sleep(Duration::from_millis(200));
+        assert!(rpc_server.active_conns().is_empty());
+
+        // environment variables override the file
+        env::set_var("RPCX_CALL_TIMEOUT_MS", "250");
+        env::set_var("RPCX_SERIALIZE_TYPE", "JSON");
+        let env_xc = XClient::from_config(&client_config).unwrap();
+        assert_eq!(SerializeType::JSON, env_xc.opt.serialize_type);
+        assert_eq!(Duration::from_millis(250), env_xc.opt.read_timeout);
+        env::remove_var("RPCX_CALL_TIMEOUT_MS");
+        env::remove_var("RPCX_SERIALIZE_TYPE");
+
+        // unknown modes are rejected
+        fs::write(
+            &client_config,
+            "service_path: Arith\nselect_mode: Fastest\n",
+        )
+        .unwrap();
+        let err = XClient::from_config(&client_config).err().unwrap();
+        assert_eq!(ErrorKind::Config, err.kind());
+
+        // clean
+        drop(xc);
+        unsafe {
+            libc::close(raw_fd);
+        }
+        let _ = handler.join();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, time::Duration};
+
+    // replies the milliseconds left to the deadline of the request.
+    fn remaining(_: ArithAddArgs) -> ArithAddReply {
+        let ctx = Context::current();
+        assert_eq!(ctx.deadline().is_some(), ctx.remaining().is_some());
+        ArithAddReply {
+            c: ctx
+                .remaining()
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(u64::max_value()),
+        }
+    }
+
+    #[test]
+    fn test_deadline() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Remaining",
+                remaining,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let args = ArithAddArgs { a: 2, b: 10 };
+
+        let mut metadata = HashMap::new();
+        let reply: Option<Result<ArithAddReply>> = xc.call("Remaining", false, &metadata, &args);
+        assert_eq!(u64::max_value(), reply.unwrap().unwrap().c);
+
+        set_deadline(&mut metadata, Duration::from_secs(2));
+        let reply: Option<Result<ArithAddReply>> = xc.call("Remaining", false, &metadata, &args);
+        let left = reply.unwrap().unwrap().c;
+        assert!(left > 1000 && left <= 2000);
+
+        // outside handlers there is no deadline
+        assert_eq!(None, Context::current().deadline());
+    }
+}
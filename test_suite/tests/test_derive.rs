@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+
+    use rmp_serde as rmps;
+    use rpcx::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(RpcxParam, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle { radius: f64 },
+        Square(u32),
+        Empty,
+    }
+
+    impl Default for Shape {
+        fn default() -> Self {
+            Shape::Empty
+        }
+    }
+
+    #[derive(RpcxParam, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Page<T> {
+        items: Vec<T>,
+        next: Option<String>,
+    }
+
+    #[derive(RpcxParam, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct User {
+        #[rpcx(rename = "Name")]
+        name: String,
+        #[rpcx(rename = "Age")]
+        age: Option<u32>,
+        tags: Vec<String>,
+    }
+
+    fn round_trip<T: RpcxParam + Default>(param: &T, st: SerializeType) -> T {
+        let data = param.into_bytes(st).unwrap();
+        let mut decoded = T::default();
+        decoded.from_slice(st, &data).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_derive_enum_and_generic() {
+        for st in &[SerializeType::JSON, SerializeType::MsgPack] {
+            for shape in &[
+                Shape::Circle { radius: 1.5 },
+                Shape::Square(3),
+                Shape::Empty,
+            ] {
+                assert_eq!(*shape, round_trip(shape, *st));
+            }
+
+            let page = Page {
+                // rmp-serde 0.13 can't decode an enum in an `Option`, so the items aren't
+                items: vec![Shape::Square(2), Shape::Empty],
+                next: Some("2".to_owned()),
+            };
+            assert_eq!(page, round_trip(&page, *st));
+            let page: Page<Page<u64>> = Page {
+                items: vec![Page {
+                    items: vec![1, 2],
+                    next: None,
+                }],
+                next: None,
+            };
+            assert_eq!(page, round_trip(&page, *st));
+        }
+    }
+
+    #[test]
+    fn test_derive_rename() {
+        let user = User {
+            name: "rpcx".to_owned(),
+            age: Some(5),
+            tags: vec!["go".to_owned()],
+        };
+        let data = user.into_bytes(SerializeType::JSON).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&data).unwrap();
+        assert_eq!(
+            serde_json::json!({"Name": "rpcx", "Age": 5, "tags": ["go"]}),
+            value
+        );
+        assert_eq!(user, round_trip(&user, SerializeType::JSON));
+        assert_eq!(user, round_trip(&user, SerializeType::MsgPack));
+
+        // a payload of a Go service, encoded as a map
+        let mut decoded = User::default();
+        let data = rmps::to_vec_named(&value).unwrap();
+        decoded.from_slice(SerializeType::MsgPack, &data).unwrap();
+        assert_eq!(user, decoded);
+
+        let mut decoded = User::default();
+        decoded
+            .from_slice(
+                SerializeType::JSON,
+                br#"{"Name":"rs","Age":null,"tags":[]}"#,
+            )
+            .unwrap();
+        assert_eq!("rs", decoded.name);
+        assert_eq!(None, decoded.age);
+    }
+}
@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{collections::HashMap, env, fs, time::Duration};
+
+    #[test]
+    fn test_discovery_cache() {
+        let cache_file = env::temp_dir().join(format!("rpcx_servers_{}", std::process::id()));
+
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), "weight=10".to_owned());
+        servers.insert("tcp@127.0.0.1:8973".to_owned(), "".to_owned());
+        save_servers(&cache_file, &servers).unwrap();
+        assert_eq!(servers, load_servers(&cache_file).unwrap());
+
+        // the registry is unreachable
+        let disc = EurekaDiscovery::with_cache_file(
+            "http://127.0.0.1:1/eureka".to_owned(),
+            "Arith".to_owned(),
+            Duration::from_secs(3600),
+            &cache_file,
+        );
+        assert_eq!(servers, disc.get_services());
+
+        fs::remove_file(&cache_file).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    struct UnregisterRecorder {
+        unregistered: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RegisterPlugin for UnregisterRecorder {
+        fn register_fn(&mut self, _: &str, _: &str, _: String, _: RpcxFn) -> Result<()> {
+            Ok(())
+        }
+
+        fn unregister(&mut self, service_path: &str) -> Result<()> {
+            self.unregistered
+                .lock()
+                .unwrap()
+                .push(service_path.to_owned());
+            Ok(())
+        }
+    }
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn start_slow(unregistered: &Arc<Mutex<Vec<String>>>, delay: Duration) -> TestCluster {
+        let unregistered = unregistered.clone();
+        TestCluster::start(1, move |rpc_server| {
+            rpc_server.add_register_plugin(Box::new(UnregisterRecorder {
+                unregistered: unregistered.clone(),
+            }));
+            rpc_server.set_drain_delay(delay);
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_drain() {
+        let unregistered = Arc::new(Mutex::new(Vec::new()));
+        let cluster = start_slow(&unregistered, Duration::from_millis(100));
+        let server = cluster.servers()[0].clone();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let call = thread::spawn(move || {
+            let args = ArithAddArgs { a: 2, b: 10 };
+            let reply: Option<Result<ArithAddReply>> =
+                xc.call("Mul", false, &HashMap::new(), &args);
+            reply.unwrap()
+        });
+        thread::sleep(Duration::from_millis(50));
+
+        let start = Instant::now();
+        server.drain(Duration::from_secs(5)).unwrap();
+        // the propagation delay and the call in flight are waited for
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(vec!["Arith".to_owned()], *unregistered.lock().unwrap());
+        assert_eq!(20, call.join().unwrap().unwrap().c);
+        assert!(server.in_flight_calls().is_empty());
+
+        // the server still serves the clients which have not seen the removal
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let args = ArithAddArgs { a: 3, b: 4 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        assert_eq!(12, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_drain_timeout() {
+        let unregistered = Arc::new(Mutex::new(Vec::new()));
+        let cluster = start_slow(&unregistered, Duration::from_millis(0));
+        let server = cluster.servers()[0].clone();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let call = thread::spawn(move || {
+            let args = ArithAddArgs { a: 2, b: 10 };
+            let _: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let err = server.drain(Duration::from_millis(50)).unwrap_err();
+        assert_eq!(ErrorKind::Timeout, err.kind());
+        call.join().unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::HashMap;
+
+    use super::common::mul;
+
+    fn add(args: ArithAddArgs) -> ArithAddReply {
+        ArithAddReply { c: args.a + args.b }
+    }
+
+    fn call(c: &Client, method: &str) -> Result<u64> {
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 3 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", method, false, &metadata, &args);
+        reply.unwrap().map(|reply| reply.c)
+    }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Calc",
+                add,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            let add_fn = rpc_server.get_fn("Arith", "Calc").unwrap();
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Replaced",
+                add,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Replaced",
+                mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            let mul_fn = rpc_server.get_fn("Arith", "Replaced").unwrap();
+
+            rpc_server.set_duplicate_policy(DuplicatePolicy::Reject);
+            let err = rpc_server
+                .try_register_fn("Arith", "Calc", "".to_owned(), mul_fn)
+                .unwrap_err();
+            assert_eq!(ErrorKind::Server, err.kind());
+
+            rpc_server.set_duplicate_policy(DuplicatePolicy::Version);
+            let method = rpc_server
+                .try_register_fn("Arith", "Calc", "".to_owned(), mul_fn)
+                .unwrap();
+            assert_eq!(method, "Calc@2");
+            let method = rpc_server
+                .try_register_fn("Arith", "Calc", "".to_owned(), add_fn)
+                .unwrap();
+            assert_eq!(method, "Calc@3");
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+
+        let mut c = Client::new(&server.addr);
+        c.start().unwrap();
+        assert_eq!(6, call(&c, "Replaced").unwrap());
+        // the handler registered first is kept by both policies
+        assert_eq!(5, call(&c, "Calc").unwrap());
+        assert_eq!(6, call(&c, "Calc@2").unwrap());
+        assert_eq!(5, call(&c, "Calc@3").unwrap());
+        assert!(call(&c, "Calc@4").is_err());
+    }
+}
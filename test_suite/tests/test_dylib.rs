@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        ffi::c_void,
+        os::raw::{c_char, c_int},
+        slice,
+    };
+
+    // hands the reply to the server, which frees it by `free_reply`
+    unsafe fn set_reply(data: Vec<u8>, reply: *mut *mut u8, reply_len: *mut usize) {
+        let data = data.into_boxed_slice();
+        *reply_len = data.len();
+        *reply = Box::into_raw(data) as *mut u8;
+    }
+
+    unsafe extern "C" fn echo(
+        args: *const u8,
+        args_len: usize,
+        _: c_int,
+        reply: *mut *mut u8,
+        reply_len: *mut usize,
+    ) -> c_int {
+        set_reply(
+            slice::from_raw_parts(args, args_len).to_vec(),
+            reply,
+            reply_len,
+        );
+        0
+    }
+
+    unsafe extern "C" fn fail(
+        _: *const u8,
+        _: usize,
+        _: c_int,
+        reply: *mut *mut u8,
+        reply_len: *mut usize,
+    ) -> c_int {
+        set_reply(b"out of order".to_vec(), reply, reply_len);
+        1
+    }
+
+    unsafe extern "C" fn free_reply(reply: *mut u8, reply_len: usize) {
+        drop(Vec::from_raw_parts(reply, reply_len, reply_len));
+    }
+
+    unsafe extern "C" fn register_services(
+        registrar: *mut c_void,
+        add_service: DylibAddServiceFn,
+    ) -> c_int {
+        let path = b"Echo\0".as_ptr() as *const c_char;
+        add_service(registrar, path, b"Echo\0".as_ptr() as *const c_char, echo);
+        add_service(registrar, path, b"Fail\0".as_ptr() as *const c_char, fail);
+        0
+    }
+
+    #[test]
+    fn test_dylib_services() {
+        let cluster = TestCluster::start(1, |_| {}).unwrap();
+        let server = cluster.servers()[0].clone();
+        // the services are added to the running server
+        let mut registered =
+            unsafe { server.register_dylib_services(register_services, free_reply) }.unwrap();
+        registered.sort();
+        assert_eq!(vec!["Echo.Echo", "Echo.Fail"], registered);
+        // they are registered like the functions of `register_fn`
+        assert!(server.get_fn("Echo", "Echo").is_some());
+
+        let mut xc = cluster.xclient("Echo", FailMode::Failfast);
+        let metadata = HashMap::new();
+        let args = BytesMut::from(&b"hello"[..]);
+        let reply: Option<Result<BytesMut>> = xc.call("Echo", false, &metadata, &args);
+        assert_eq!(&b"hello"[..], &reply.unwrap().unwrap()[..]);
+
+        let reply: Option<Result<BytesMut>> = xc.call("Fail", false, &metadata, &args);
+        let err = reply.unwrap().unwrap_err();
+        assert_eq!(ErrorKind::Service, err.kind());
+        assert!(err.to_string().contains("out of order"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_services_error() {
+        let server = Server::new("127.0.0.1:0".to_owned(), 0);
+        let err = unsafe { server.load_services("/nonexistent/libservices.so") }.unwrap_err();
+        assert_eq!(ErrorKind::Config, err.kind());
+
+        // a library is loaded once, a new version needs a path of its own
+        let err = unsafe { server.load_services("libc.so.6") }.unwrap_err();
+        assert_eq!(ErrorKind::Config, err.kind());
+        assert!(err.to_string().contains("loaded already"), "{}", err);
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        thread,
+    };
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_encryption() {
+        let key = [3u8; ENCRYPTION_KEY_LEN];
+
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8982".to_owned(), 0);
+        let mut encryption = EncryptionPlugin::new();
+        encryption.add_key("Arith", &key).unwrap();
+        rpc_server.add_message_plugin(Box::new(encryption));
+        register_mul(&mut rpc_server);
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 3, b: 4 };
+
+        // plaintext calls are rejected
+        let mut c = Client::new("127.0.0.1:8982");
+        c.start().unwrap();
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::Client, reply.unwrap().unwrap_err().kind());
+
+        c.set_encryption_key("Arith", &key).unwrap();
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(12, reply.unwrap().unwrap().c);
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
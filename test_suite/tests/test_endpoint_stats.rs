@@ -0,0 +1,60 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, time::Duration};
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_endpoint_stats() {
+        let cluster = TestCluster::start(2, |rpc_server| {
+            register_mul(rpc_server);
+            let limited: RpcxFn = |_, _| Err(Error::new(ErrorKind::RateLimited, "limit exceeded"));
+            rpc_server.register_fn("Arith", "Limited", "".to_owned(), limited);
+        })
+        .unwrap();
+        let mut addrs = cluster.addrs();
+        addrs.sort();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        // the servers of the selector are listed before they are called
+        let stats = xc.stats();
+        assert_eq!(
+            addrs,
+            stats.iter().map(|s| s.server.clone()).collect::<Vec<_>>()
+        );
+        for s in &stats {
+            assert_eq!(ConnState::NotConnected, s.state);
+            assert_eq!(0, s.calls);
+            assert_eq!(1.0, s.success_ratio());
+        }
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 3 };
+        for _ in 0..10 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(6, reply.unwrap().unwrap().c);
+        }
+        let reply: Option<Result<ArithAddReply>> = xc.call("Limited", false, &metadata, &args);
+        assert_eq!(ErrorKind::RateLimited, reply.unwrap().unwrap_err().kind());
+
+        let stats = xc.stats();
+        assert_eq!(11, stats.iter().map(|s| s.calls).sum::<u64>());
+        for s in &stats {
+            assert_eq!(ConnState::Connected, s.state);
+            assert_eq!(0, s.in_flight);
+            assert!(s.p50 > Duration::default() && s.p50 <= s.p99);
+        }
+        let failed: Vec<&EndpointStats> = stats.iter().filter(|s| s.errors > 0).collect();
+        assert_eq!(1, failed.len());
+        assert_eq!(5.0 / 6.0, failed[0].success_ratio());
+        assert_eq!(
+            Some(ErrorKind::RateLimited),
+            failed[0].last_error.map(|(kind, _)| kind)
+        );
+    }
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        thread,
+    };
+
+    #[test]
+    fn test_service_error() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8981".to_owned(), 0);
+        let busy: RpcxFn = |_, _| {
+            let mut metadata = HashMap::new();
+            metadata.insert("retry_after".to_owned(), "10".to_owned());
+            Err(Error::service(429, "too busy", metadata))
+        };
+        rpc_server.register_fn("Arith".to_owned(), "Busy".to_owned(), "".to_owned(), busy);
+        let limited: RpcxFn = |_, _| Err(Error::new(ErrorKind::RateLimited, "limit exceeded"));
+        rpc_server.register_fn(
+            "Arith".to_owned(),
+            "Limited".to_owned(),
+            "".to_owned(),
+            limited,
+        );
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut c = Client::new("127.0.0.1:8981");
+        c.start().unwrap();
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 3 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Busy", false, &metadata, &args);
+        let err = reply.unwrap().unwrap_err();
+        assert_eq!(ErrorKind::Service, err.kind());
+        assert_eq!("too busy", err.to_string());
+        let service_error = err.service_error().unwrap();
+        assert_eq!(429, service_error.code);
+        assert_eq!("10", service_error.metadata.get("retry_after").unwrap());
+
+        let reply: Option<Result<ArithAddReply>> =
+            c.call("Arith", "Limited", false, &metadata, &args);
+        assert_eq!(ErrorKind::RateLimited, reply.unwrap().unwrap_err().kind());
+
+        let reply: Option<Result<ArithAddReply>> =
+            c.call("Arith", "Missing", false, &metadata, &args);
+        assert_eq!(ErrorKind::Server, reply.unwrap().unwrap_err().kind());
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
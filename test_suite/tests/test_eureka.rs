@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+    use serde_json::{json, Value};
+
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    type Instances = Arc<Mutex<Vec<EurekaInstance>>>;
+
+    // starts a Eureka of the registrations, deltas are always empty so the registries of the
+    // clients are only reconciled by the hash of the deltas.
+    fn start_eureka() -> (String, Instances) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/eureka", listener.local_addr().unwrap());
+        let instances: Instances = Arc::new(Mutex::new(Vec::new()));
+        let instances_cloned = instances.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let instances = instances_cloned.clone();
+                thread::spawn(move || serve(stream.unwrap(), &instances));
+            }
+        });
+        (url, instances)
+    }
+
+    // serves the requests of a connection until it is closed.
+    fn serve(mut stream: TcpStream, instances: &Mutex<Vec<EurekaInstance>>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                return;
+            }
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                if line.starts_with("content-length:") {
+                    len = line["content-length:".len()..].trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap().to_owned();
+            let path = parts.next().unwrap().trim_start_matches("/eureka/apps");
+            let mut instances = instances.lock().unwrap();
+            let (status, body) = match (method.as_str(), path) {
+                ("GET", "") => (200, applications(&instances, &instances)),
+                ("GET", "/delta") => (200, applications(&instances, &[])),
+                ("POST", _) => {
+                    let doc: Value = serde_json::from_slice(&body).unwrap();
+                    let instance: EurekaInstance =
+                        serde_json::from_value(doc["instance"].clone()).unwrap();
+                    instances.retain(|i| i.instance_id != instance.instance_id);
+                    instances.push(instance);
+                    (204, String::new())
+                }
+                (_, path) => {
+                    let id = path.rsplit('/').next().unwrap();
+                    let found = instances.iter().any(|i| i.instance_id == id);
+                    if method == "DELETE" {
+                        instances.retain(|i| i.instance_id != id);
+                    }
+                    (if found { 200 } else { 404 }, String::new())
+                }
+            };
+            let head = format!(
+                "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                status,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(body.as_bytes()).unwrap();
+        }
+    }
+
+    // returns the document of `delta` with the hash of the whole registry.
+    fn applications(registry: &[EurekaInstance], delta: &[EurekaInstance]) -> String {
+        let application: Vec<Value> = delta
+            .iter()
+            .map(|instance| json!({"name": instance.app, "instance": [instance]}))
+            .collect();
+        json!({"applications": {
+            "apps__hashcode": eureka_hashcode(registry),
+            "application": application,
+        }})
+        .to_string()
+    }
+
+    fn wait_for<F: Fn() -> bool>(f: F) {
+        for _ in 0..50 {
+            if f() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn test_eureka_register() {
+        let (url, instances) = start_eureka();
+        let mut rpc_server = Server::new("127.0.0.1:0".to_owned(), 0);
+        rpc_server.add_register_plugin(Box::new(EurekaRegister::new(
+            url,
+            "tcp@127.0.0.1:8972".to_owned(),
+            Duration::from_millis(100),
+        )));
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        rpc_server.register_fn(
+            "Arith".to_owned(),
+            "Mul".to_owned(),
+            "weight=10".to_owned(),
+            echo,
+        );
+
+        let registered = instances.lock().unwrap().clone();
+        assert_eq!(1, registered.len());
+        assert_eq!("127.0.0.1:arith:8972", registered[0].instance_id);
+        assert!(registered[0].is_up());
+
+        // the instances Eureka has expired are registered again by the renewals
+        instances.lock().unwrap().clear();
+        wait_for(|| instances.lock().unwrap().len() == 1);
+    }
+
+    #[test]
+    fn test_eureka_discovery() {
+        let (url, instances) = start_eureka();
+        let d = EurekaDiscovery::new(url, "Arith".to_owned(), Duration::from_millis(100));
+        assert!(d.get_services().is_empty());
+
+        // the registrations missed by the deltas are fetched by the hash mismatch
+        let instance = EurekaInstance::new("Arith", "tcp@127.0.0.1:8972", "weight=10").unwrap();
+        instances.lock().unwrap().push(instance);
+        wait_for(|| d.get_services().get("tcp@127.0.0.1:8972") == Some(&"weight=10".to_owned()));
+
+        // the instances of other applications are not selected
+        let other = EurekaInstance::new("Echo", "tcp@127.0.0.1:8973", "").unwrap();
+        instances.lock().unwrap().push(other);
+        instances.lock().unwrap()[0].status = "DOWN".to_owned();
+        wait_for(|| d.get_services().is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::common::{mul, register_mul};
+
+    fn add(args: ArithAddArgs) -> ArithAddReply {
+        ArithAddReply { c: args.a + args.b }
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8984".to_owned(), 0);
+        let mut faults = FaultInjectionPlugin::new();
+        faults.add_fault("Arith", "Add", Fault::Delay(Duration::from_millis(50)), 1.0);
+        faults.add_fault("Arith", "Mul", Fault::Error(ErrorKind::RateLimited), 1.0);
+        faults.add_fault("Arith", "Mul", Fault::Error(ErrorKind::Server), 0.0);
+        faults.add_fault("Slow", "*", Fault::DropResponse, 1.0);
+        rpc_server.add_message_plugin(Box::new(faults));
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Add",
+            add,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        register_mul(&mut rpc_server);
+        register_func!(
+            rpc_server,
+            "Slow",
+            "Mul",
+            mul,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        let mut c = Client::new("127.0.0.1:8984");
+        c.opt.read_timeout = Duration::from_millis(500);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 3 };
+
+        let start = Instant::now();
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Add", false, &metadata, &args);
+        assert_eq!(5, reply.unwrap().unwrap().c);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::RateLimited, reply.unwrap().unwrap_err().kind());
+
+        // the response is dropped, so the call times out
+        let reply: Option<Result<ArithAddReply>> = c.call("Slow", "Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::Timeout, reply.unwrap().unwrap_err().kind());
+
+        // the faults of clients are injected before the calls are sent
+        let mut injector = FaultInjector::new();
+        injector.add_fault(
+            "Arith",
+            "Add",
+            Fault::Delay(Duration::from_millis(100)),
+            1.0,
+        );
+        injector.add_fault("Arith", "Mul", Fault::Error(ErrorKind::ServerBusy), 1.0);
+        let mut injected = Client::new("127.0.0.1:8984");
+        injected.opt.read_timeout = Duration::from_millis(500);
+        injected.set_fault_injector(injector);
+        injected.start().unwrap();
+
+        let start = Instant::now();
+        let reply: Option<Result<ArithAddReply>> =
+            injected.call("Arith", "Add", false, &metadata, &args);
+        assert_eq!(5, reply.unwrap().unwrap().c);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        let reply: Option<Result<ArithAddReply>> =
+            injected.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+
+        let mut injector = FaultInjector::new();
+        injector.add_fault("Arith", "*", Fault::DropResponse, 1.0);
+        injected.set_fault_injector(injector);
+        let reply: Option<Result<ArithAddReply>> =
+            injected.call("Arith", "Add", false, &metadata, &args);
+        assert_eq!(ErrorKind::Timeout, reply.unwrap().unwrap_err().kind());
+
+        // clean
+        drop(injected);
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
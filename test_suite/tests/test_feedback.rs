@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(100));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    // records the outcomes of calls.
+    struct Recorder {
+        inner: RoundbinSelector,
+        results: Arc<Mutex<Vec<(String, Duration, bool)>>>,
+    }
+
+    impl ClientSelector for Recorder {
+        fn select(
+            &mut self,
+            service_path: &str,
+            service_method: &str,
+            args: &dyn RpcxParam,
+        ) -> String {
+            self.inner.select(service_path, service_method, args)
+        }
+        fn update_server(&self, servers: &HashMap<String, String>) {
+            self.inner.update_server(servers)
+        }
+        fn on_result(&self, server: &str, latency: Duration, success: bool) {
+            self.results
+                .lock()
+                .unwrap()
+                .push((server.to_owned(), latency, success));
+        }
+    }
+
+    #[test]
+    fn test_feedback() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            let fail: RpcxFn = |_, _| Err(Error::new(ErrorKind::Other, "failed"));
+            rpc_server.register_fn("Arith".to_owned(), "Fail".to_owned(), "".to_owned(), fail);
+        })
+        .unwrap();
+        let server = cluster.addrs()[0].clone();
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let selector = Recorder {
+            inner: RoundbinSelector::new(),
+            results: results.clone(),
+        };
+        let mut servers = HashMap::new();
+        servers.insert(server.clone(), String::new());
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert!(reply.unwrap().is_ok());
+        let reply: Option<Result<ArithAddReply>> = xc.call("Fail", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+
+        let f = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        assert!(f.wait().unwrap().is_ok());
+        assert_eq!(2, results.lock().unwrap().len());
+        // the asynchronous call is reported before the next selection
+        let reply: Option<Result<ArithAddReply>> = xc.call("Fail", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+
+        let results = results.lock().unwrap();
+        let outcomes: Vec<(&str, bool)> = results.iter().map(|r| (r.0.as_str(), r.2)).collect();
+        assert_eq!(
+            vec![
+                (server.as_str(), true),
+                (server.as_str(), false),
+                (server.as_str(), true),
+                (server.as_str(), false)
+            ],
+            outcomes
+        );
+        assert!(results[0].1 >= Duration::from_millis(100));
+        assert!(results[2].1 >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+    use rpcx_ffi::*;
+
+    use std::{
+        ffi::{CStr, CString},
+        ptr, slice, thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_ffi() {
+        let mut rpc_server = Server::new("127.0.0.1:8988".to_owned(), 0);
+        register_mul(&mut rpc_server);
+        thread::spawn(move || {
+            if let Err(err) = rpc_server.start() {
+                println!("{}", err);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let service_path = CString::new("Arith").unwrap();
+        let servers = CString::new("127.0.0.1:8988").unwrap();
+        let client = unsafe {
+            rpcx_client_connect(
+                service_path.as_ptr(),
+                servers.as_ptr(),
+                SerializeType::JSON as i32,
+            )
+        };
+        assert!(!client.is_null());
+
+        let method = CString::new("Mul").unwrap();
+        let metadata = CString::new("key=value").unwrap();
+        let args = br#"{"A":10,"B":20}"#;
+        let mut reply = ptr::null_mut();
+        let mut reply_len = 0;
+        let rt = unsafe {
+            rpcx_client_call(
+                client,
+                method.as_ptr(),
+                metadata.as_ptr(),
+                args.as_ptr(),
+                args.len(),
+                &mut reply,
+                &mut reply_len,
+            )
+        };
+        assert_eq!(0, rt);
+        let payload = unsafe { slice::from_raw_parts(reply, reply_len) };
+        assert_eq!(br#"{"C":200}"#, payload);
+        unsafe { rpcx_bytes_free(reply, reply_len) };
+
+        let method = CString::new("Div").unwrap();
+        let rt = unsafe {
+            rpcx_client_call(
+                client,
+                method.as_ptr(),
+                ptr::null(),
+                args.as_ptr(),
+                args.len(),
+                &mut reply,
+                &mut reply_len,
+            )
+        };
+        assert_eq!(-1, rt);
+        let err = unsafe { CStr::from_ptr(rpcx_last_error()) };
+        assert!(err.to_str().unwrap().contains("Div"));
+
+        unsafe { rpcx_client_free(client) };
+    }
+}
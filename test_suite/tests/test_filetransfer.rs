@@ -0,0 +1,150 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        env, fs,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        thread,
+    };
+
+    #[test]
+    fn test_file_transfer() {
+        let dir = env::temp_dir().join("rpcx_test_file_transfer");
+        let _ = fs::remove_dir_all(&dir);
+        let server_dir = dir.join("server");
+        let client_dir = dir.join("client");
+        fs::create_dir_all(&client_dir).unwrap();
+
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8979".to_owned(), 0);
+        rpc_server
+            .enable_file_transfer("127.0.0.1:8980", &server_dir)
+            .unwrap();
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut c = Client::new("127.0.0.1:8979");
+        c.start().unwrap();
+
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let local = client_dir.join("data.bin");
+        fs::write(&local, &data).unwrap();
+        let meta = HashMap::new();
+
+        // resume an interrupted upload
+        fs::write(server_dir.join("data.bin.part"), &data[..1000]).unwrap();
+        c.upload_file(&local, "data.bin", &meta).unwrap();
+        assert_eq!(data, fs::read(server_dir.join("data.bin")).unwrap());
+
+        // resume an interrupted download
+        let downloaded = client_dir.join("downloaded.bin");
+        fs::write(&downloaded, &data[..2000]).unwrap();
+        c.download_file("data.bin", &downloaded, &meta).unwrap();
+        assert_eq!(data, fs::read(&downloaded).unwrap());
+
+        assert!(c.upload_file(&local, "../data.bin", &meta).is_err());
+        assert!(c
+            .download_file("missing.bin", client_dir.join("missing.bin"), &meta)
+            .is_err());
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_concurrent_uploads() {
+        let dir = env::temp_dir().join("rpcx_test_concurrent_uploads");
+        let _ = fs::remove_dir_all(&dir);
+        let server_dir = dir.join("server");
+        let client_dir = dir.join("client");
+        fs::create_dir_all(&client_dir).unwrap();
+
+        let server_dir_cloned = server_dir.clone();
+        let cluster = TestCluster::start(1, move |rpc_server| {
+            rpc_server
+                .enable_file_transfer("127.0.0.1:0", &server_dir_cloned)
+                .unwrap();
+        })
+        .unwrap();
+        let addr = cluster.servers()[0].addr.clone();
+
+        // uploads of the same name don't mix their bytes, the last one wins
+        let uploads: Vec<_> = (0..4u8)
+            .map(|i| {
+                let local = client_dir.join(format!("data{}.bin", i));
+                fs::write(&local, vec![i; 1024 * 1024]).unwrap();
+                let addr = addr.clone();
+                thread::spawn(move || {
+                    let mut c = Client::new(&addr);
+                    c.start().unwrap();
+                    c.upload_file(&local, "data.bin", &HashMap::new())
+                })
+            })
+            .collect();
+        for upload in uploads {
+            upload.join().unwrap().unwrap();
+        }
+
+        let uploaded = fs::read(server_dir.join("data.bin")).unwrap();
+        assert_eq!(1024 * 1024, uploaded.len());
+        assert!(uploaded.iter().all(|&b| b == uploaded[0]));
+        assert_eq!(1, fs::read_dir(&server_dir).unwrap().count());
+
+        drop(cluster);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    struct DenyPlugin;
+
+    impl MessagePlugin for DenyPlugin {
+        fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+            Err(Error::new(ErrorKind::Server, "denied"))
+        }
+    }
+
+    #[test]
+    fn test_file_transfer_plugins() {
+        let dir = env::temp_dir().join("rpcx_test_file_transfer_plugins");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let local = dir.join("data.bin");
+        fs::write(&local, b"data").unwrap();
+
+        let server_dir = dir.join("server");
+        let cluster = TestCluster::start(1, move |rpc_server| {
+            rpc_server
+                .enable_file_transfer("127.0.0.1:0", &server_dir)
+                .unwrap();
+            rpc_server.add_message_plugin(Box::new(DenyPlugin));
+        })
+        .unwrap();
+
+        // transfers pass the plugins like calls
+        let mut c = Client::new(&cluster.servers()[0].addr);
+        c.start().unwrap();
+        let err = c
+            .upload_file(&local, "data.bin", &HashMap::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("denied"));
+        assert!(!dir.join("server").join("data.bin").exists());
+
+        drop(cluster);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::Read,
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::common::register_mul;
+
+    fn add(args: ArithAddArgs) -> ArithAddReply {
+        ArithAddReply { c: args.a + args.b }
+    }
+
+    // multiplies by the factor in the metadata of the request
+    fn scale(args: ArithAddArgs) -> ArithAddReply {
+        let factor = Context::current()
+            .metadata()
+            .get("factor")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+        ArithAddReply { c: args.a * factor }
+    }
+
+    #[test]
+    fn test_forward() {
+        let backend = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Scale",
+                scale,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let backend_addr = backend.servers()[0].addr.clone();
+        let proxy = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Add",
+                add,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            rpc_server.forward_to(&[&backend_addr]).unwrap();
+        })
+        .unwrap();
+
+        let mut xc = proxy.xclient("Arith", FailMode::Failfast);
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let mut metadata = HashMap::new();
+
+        // the services of the proxy are handled by it, the others by the backend
+        let reply: Option<Result<ArithAddReply>> = xc.call("Add", false, &metadata, &args);
+        assert_eq!(12, reply.unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        // the metadata is forwarded
+        metadata.insert("factor".to_owned(), "3".to_owned());
+        let reply: Option<Result<ArithAddReply>> = xc.call("Scale", false, &metadata, &args);
+        assert_eq!(6, reply.unwrap().unwrap().c);
+
+        // so are the errors of the backend
+        let reply: Option<Result<ArithAddReply>> = xc.call("Div", false, &metadata, &args);
+        let err = reply.unwrap().unwrap_err();
+        assert!(err.to_string().contains("Arith.Div not found"), "{}", err);
+    }
+
+    // an upstream which reads a request and closes the connection without replying.
+    fn broken_upstream(requests: Arc<AtomicUsize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for conn in listener.incoming() {
+                let mut conn = conn.unwrap();
+                let mut buf = [0u8; 1024];
+                if let Ok(n) = conn.read(&mut buf) {
+                    if n > 0 {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_forward_not_retried() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let upstreams = [
+            broken_upstream(requests.clone()),
+            broken_upstream(requests.clone()),
+        ];
+        let proxy = TestCluster::start(1, |rpc_server| {
+            rpc_server
+                .forward_to(&[&upstreams[0], &upstreams[1]])
+                .unwrap();
+        })
+        .unwrap();
+
+        // the request is written to the first upstream, which may have handled it
+        let mut xc = proxy.xclient("Arith", FailMode::Failfast);
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        let err = reply.unwrap().unwrap_err();
+        assert_eq!(ErrorKind::ConnectionClosed, err.kind());
+        assert_eq!(1, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_forward_config() {
+        let config = ServerConfig {
+            addr: "127.0.0.1:0".to_owned(),
+            upstreams: vec!["not an address".to_owned()],
+            ..Default::default()
+        };
+        let err = Server::with_config(&config).err().unwrap();
+        assert_eq!(ErrorKind::Config, err.kind());
+    }
+}
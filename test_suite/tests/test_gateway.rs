@@ -0,0 +1,234 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        os::unix::io::AsRawFd,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::common::register_mul;
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(500));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    #[test]
+    fn test_http_invoke() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8973".to_owned(), 0);
+        register_mul(&mut rpc_server);
+
+        let addr = rpc_server
+            .addr
+            .parse::<SocketAddr>()
+            .map_err(|err| Error::new(ErrorKind::Other, err))
+            .unwrap();
+
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // invoke by plain http
+        let body = r#"{"A":3,"B":7}"#;
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nX-RPCX-ServicePath: Arith\r\n\
+             X-RPCX-ServiceMethod: Mul\r\nX-RPCX-SerializeType: 1\r\n\
+             Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.contains("X-RPCX-MessageStatusType: 0"));
+        assert!(resp.ends_with(r#"{"C":21}"#));
+
+        // clean
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+
+    #[test]
+    fn test_gateway_client() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            register_func!(
+                rpc_server,
+                "Arith",
+                "SlowMul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let mut client = GatewayClient::new(&cluster.servers()[0].addr).unwrap();
+        client.opt.connect_timeout = Duration::from_secs(1);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 3, b: 7 };
+        let reply: Option<Result<ArithAddReply>> =
+            client.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(21, reply.unwrap().unwrap().c);
+
+        // the errors of the server are replied in the headers
+        let reply: Option<Result<ArithAddReply>> =
+            client.call("Arith", "Div", false, &metadata, &args);
+        assert_eq!(ErrorKind::Server, reply.unwrap().unwrap_err().kind());
+
+        // the call fails after the read timeout
+        client.opt.read_timeout = Duration::from_millis(100);
+        let start = Instant::now();
+        let reply: Option<Result<ArithAddReply>> =
+            client.call("Arith", "SlowMul", false, &metadata, &args);
+        assert_eq!(ErrorKind::Timeout, reply.unwrap().unwrap_err().kind());
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_jsonrpc() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8974".to_owned(), 0);
+        register_mul(&mut rpc_server);
+        rpc_server.start_jsonrpc("127.0.0.1:8975").unwrap();
+
+        let body = r#"[{"jsonrpc":"2.0","method":"Arith.Mul","params":{"A":3,"B":7},"id":1},{"jsonrpc":"2.0","method":"Arith.Div","params":{},"id":2}]"#;
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\n\
+             Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect("127.0.0.1:8975").unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.contains(r#"{"jsonrpc":"2.0","result":{"C":21},"id":1}"#));
+        assert!(resp.contains(r#""error":{"code":-32601"#));
+    }
+
+    // sends the request and returns the status line of the response.
+    fn http_status(addr: &str, req: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(req).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        resp.lines().next().unwrap_or_default().to_owned()
+    }
+
+    #[test]
+    fn test_http_limits() {
+        let cluster = TestCluster::start(1, |_| {}).unwrap();
+        let addr = cluster.servers()[0].addr.clone();
+
+        // the body is refused by its length before it is read
+        let req = "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: 1000000000000\r\n\r\n";
+        assert_eq!(
+            "HTTP/1.1 413 Payload Too Large",
+            http_status(&addr, req.as_bytes())
+        );
+
+        // just over the limit, so the server reads the whole request before it replies
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nX-Padding: {}\r\n\r\n",
+            "a".repeat(9 * 1024)
+        );
+        assert_eq!(
+            "HTTP/1.1 431 Request Header Fields Too Large",
+            http_status(&addr, req.as_bytes())
+        );
+
+        // the bodies are only read by their length
+        let req = "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   d\r\n{\"A\":3,\"B\":7}\r\n0\r\n\r\n";
+        assert_eq!(
+            "HTTP/1.1 501 Not Implemented",
+            http_status(&addr, req.as_bytes())
+        );
+    }
+
+    // rejects every request, like an authenticator would reject a client without a token.
+    struct DenyPlugin;
+
+    impl MessagePlugin for DenyPlugin {
+        fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+            Err(Error::new(ErrorKind::Server, "denied"))
+        }
+    }
+
+    #[test]
+    fn test_http_plugins() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            rpc_server.add_message_plugin(Box::new(DenyPlugin));
+        })
+        .unwrap();
+        let addr = cluster.servers()[0].addr.clone();
+
+        // http requests pass the plugins like the ones of rpcx connections
+        let body = r#"{"A":3,"B":7}"#;
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nX-RPCX-ServicePath: Arith\r\n\
+             X-RPCX-ServiceMethod: Mul\r\nX-RPCX-SerializeType: 1\r\n\
+             Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        assert!(resp.contains("X-RPCX-MessageStatusType: 1"));
+        assert!(resp.contains("X-RPCX-ErrorMessage: denied"));
+        assert!(!resp.ends_with(r#"{"C":21}"#));
+    }
+
+    #[test]
+    fn test_jsonrpc_plugins() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            rpc_server.add_message_plugin(Box::new(DenyPlugin));
+        })
+        .unwrap();
+        cluster.servers()[0]
+            .start_jsonrpc("127.0.0.1:8992")
+            .unwrap();
+
+        let body = r#"{"jsonrpc":"2.0","method":"Arith.Mul","params":{"A":3,"B":7},"id":1}"#;
+        let req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\n\
+             Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect("127.0.0.1:8992").unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        assert!(resp.contains(r#""message":"denied""#));
+        assert!(!resp.contains(r#""result""#));
+    }
+}
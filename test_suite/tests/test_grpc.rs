@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use hyper::{body::Payload, header::HeaderMap, Body, Client, Request};
+    use rpcx::{testing::TestCluster, *};
+    use tokio::runtime::current_thread::Runtime;
+
+    // calls the method with a length-prefixed message and returns the body and the trailers of
+    // the response.
+    fn grpc_call(rt: &mut Runtime, addr: &str, path: &str, msg: &[u8]) -> (Vec<u8>, HeaderMap) {
+        let client = Client::builder().http2_only(true).build_http::<Body>();
+        let mut body = vec![0u8];
+        body.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        body.extend_from_slice(msg);
+        let req = Request::post(format!("http://{}{}", addr, path))
+            .header("content-type", "application/grpc")
+            .body(Body::from(body))
+            .unwrap();
+
+        let mut resp_body = rt.block_on(client.request(req)).unwrap().into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = rt
+            .block_on(future::poll_fn(|| resp_body.poll_data()))
+            .unwrap()
+        {
+            data.extend_from_slice(&chunk);
+        }
+        let trailers = rt
+            .block_on(future::poll_fn(|| resp_body.poll_trailers()))
+            .unwrap()
+            .unwrap_or_default();
+        (data, trailers)
+    }
+
+    // replies the calls of the Proxy service, which are not registered.
+    struct ProxyPlugin;
+
+    impl MessagePlugin for ProxyPlugin {
+        fn intercept_request(&self, req: &Message) -> Option<Vec<u8>> {
+            if req.service_path == "Proxy" {
+                return Some(b"proxied".to_vec());
+            }
+            None
+        }
+    }
+
+    #[test]
+    fn test_grpc_unary() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            let echo: RpcxFn = |x, _| Ok(x.to_vec());
+            rpc_server.register_fn("Echo", "Echo", "".to_owned(), echo);
+            let fail: RpcxFn = |_, _| Err(Error::new(ErrorKind::Service, "100% wrong\n"));
+            rpc_server.register_fn("Echo", "Fail", "".to_owned(), fail);
+            rpc_server.add_message_plugin(Box::new(ProxyPlugin));
+        })
+        .unwrap();
+        cluster.servers()[0].start_grpc("127.0.0.1:8993").unwrap();
+        let mut rt = Runtime::new().unwrap();
+
+        let (data, trailers) = grpc_call(&mut rt, "127.0.0.1:8993", "/pb.Echo/Echo", b"hello");
+        assert_eq!(b"\0\0\0\0\x05hello", &data[..]);
+        assert_eq!("0", trailers["grpc-status"]);
+
+        let (data, trailers) = grpc_call(&mut rt, "127.0.0.1:8993", "/pb.Echo/Fail", b"hello");
+        assert!(data.is_empty());
+        assert_eq!("2", trailers["grpc-status"]);
+        assert_eq!("100%25 wrong%0A", trailers["grpc-message"]);
+
+        let (_, trailers) = grpc_call(&mut rt, "127.0.0.1:8993", "/pb.Echo/Missing", b"");
+        assert_eq!("12", trailers["grpc-status"]);
+
+        // the plugins reply the methods which are not registered
+        let (data, trailers) = grpc_call(&mut rt, "127.0.0.1:8993", "/pb.Proxy/Echo", b"");
+        assert_eq!(b"\0\0\0\0\x07proxied", &data[..]);
+        assert_eq!("0", trailers["grpc-status"]);
+
+        // the body is bounded like the one of the http gateway
+        let large = vec![0u8; 16 * 1024 * 1024];
+        let (data, trailers) = grpc_call(&mut rt, "127.0.0.1:8993", "/pb.Echo/Echo", &large);
+        assert!(data.is_empty());
+        assert_eq!("8", trailers["grpc-status"]);
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::{HashMap, HashSet};
+
+    // replies the port of the server.
+    fn server_port(_: ArithAddArgs) -> ArithAddReply {
+        let ctx = Context::current();
+        let port = ctx.metadata().server_address().map(|addr| addr.port());
+        ArithAddReply {
+            c: u64::from(port.unwrap_or_default()),
+        }
+    }
+
+    // returns the servers the calls with `a` and many `b` are sent to.
+    fn servers_of<S: ClientSelector>(xc: &mut XClient<S>, a: u64) -> HashSet<u64> {
+        (0..30)
+            .map(|b| {
+                let args = ArithAddArgs { a, b };
+                let reply: Option<Result<ArithAddReply>> =
+                    xc.call("ServerPort", false, &HashMap::new(), &args);
+                reply.unwrap().unwrap().c
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_key() {
+        let cluster = TestCluster::start(3, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "ServerPort",
+                server_port,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let servers: HashMap<String, String> = cluster
+            .addrs()
+            .into_iter()
+            .map(|addr| (addr, String::new()))
+            .collect();
+        let selector = ConsistentHashSelector::new();
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+
+        // the whole args are hashed
+        assert!(servers_of(&mut xc, 7).len() > 1);
+
+        xc.set_hash_key("ServerPort", |args: &ArithAddArgs| args.a.to_string());
+        assert_eq!(1, servers_of(&mut xc, 7).len());
+        let all: HashSet<u64> = (0..30).flat_map(|a| servers_of(&mut xc, a)).collect();
+        assert!(all.len() > 1);
+
+        // the key can't be extracted from args of another type
+        let reply: Option<Result<ArithAddReply>> =
+            xc.call("ServerPort", false, &HashMap::new(), &Bytes::new());
+        assert_eq!(ErrorKind::Client, reply.unwrap().unwrap_err().kind());
+    }
+}
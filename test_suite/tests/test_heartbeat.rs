@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{io::Write, net::TcpStream, time::Instant};
+
+    #[test]
+    fn test_heartbeat() {
+        let cluster = TestCluster::start(1, |_| {}).unwrap();
+        let server = cluster.servers()[0].clone();
+
+        // sends a heartbeat like the keepalive of Go clients
+        let mut stream = TcpStream::connect(&server.addr).unwrap();
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_heartbeat(true);
+        req.set_serialize_type(SerializeType::SerializeNone);
+        req.set_compress_type(CompressType::CompressNone);
+        req.set_seq(42);
+        req.payload = Bytes::from(&b"ping"[..]);
+        let before = Instant::now();
+        stream.write_all(&req.encode()).unwrap();
+
+        let mut reply = Message::new();
+        reply.decode(&mut stream).unwrap();
+        assert_eq!(Some(MessageType::Response), reply.get_message_type());
+        assert!(reply.is_heartbeat());
+        assert_eq!(42, reply.get_seq());
+        assert_eq!(&b"ping"[..], &reply.payload[..]);
+        assert!(Error::from_reply(&reply).is_none());
+
+        let conn = stream.local_addr().unwrap();
+        let last = server.last_heartbeat(&conn).unwrap();
+        assert!(last >= before);
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::common::start_cluster;
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_secs(1));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    #[test]
+    fn test_hedge() {
+        let slow = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let fast = start_cluster(1);
+
+        // round robin selects the slow server first
+        let selector = RoundbinSelector::new();
+        selector
+            .servers
+            .write()
+            .unwrap()
+            .extend(vec![fast.addrs()[0].clone(), slow.addrs()[0].clone()]);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+        let policy = HedgePolicy {
+            delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        xc.set_hedge_policy("Mul", policy);
+        assert_eq!(Some(policy), xc.hedge_policy("Mul"));
+        assert_eq!(None, xc.hedge_policy("Add"));
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let start = Instant::now();
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        // the hedge to the fast server replied first
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_hedge_config() {
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config
+            .servers
+            .insert("tcp@127.0.0.1:8972".to_owned(), String::new());
+        config.methods.insert(
+            "Mul".to_owned(),
+            MethodConfig {
+                hedge: Some(HedgeConfig {
+                    delay_ms: 20,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+
+        let xc = XClient::with_config(&config).unwrap();
+        // hedging doesn't change the fail mode
+        assert_eq!(FailMode::Failfast, xc.call_policy("Mul").fail_mode);
+        assert_eq!(
+            Some(HedgePolicy {
+                percentile: 0.95,
+                delay: Duration::from_millis(20),
+                max_hedges: 1,
+            }),
+            xc.hedge_policy("Mul")
+        );
+    }
+}
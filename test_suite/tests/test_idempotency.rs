@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    // adds to the number of calls, so a duplicate would reply another number
+    fn add_calls(args: ArithAddArgs) -> ArithAddReply {
+        let calls = CALLS.fetch_add(1, Ordering::SeqCst) as u64 + 1;
+        if args.b == 0 {
+            thread::sleep(Duration::from_millis(300));
+        }
+        ArithAddReply { c: args.a + calls }
+    }
+
+    #[test]
+    fn test_idempotency_plugin() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Add",
+                add_calls,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            let plugin = IdempotencyPlugin::new(Duration::from_millis(300), 16);
+            rpc_server.add_message_plugin(Box::new(plugin));
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let call = |xc: &mut XClient<RoundbinSelector>, key: &str, b: u64| {
+            let mut metadata = HashMap::new();
+            if !key.is_empty() {
+                metadata.insert(IDEMPOTENCY_KEY.to_owned(), key.to_owned());
+            }
+            let args = ArithAddArgs { a: 100, b };
+            let reply: Option<Result<ArithAddReply>> = xc.call("Add", false, &metadata, &args);
+            reply.unwrap().map(|reply| reply.c)
+        };
+
+        // the duplicate is replied without invoking the handler
+        assert_eq!(101, call(&mut xc, "a", 1).unwrap());
+        assert_eq!(101, call(&mut xc, "a", 1).unwrap());
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        // other keys and the requests without a key are handled
+        assert_eq!(102, call(&mut xc, "b", 1).unwrap());
+        assert_eq!(103, call(&mut xc, "", 1).unwrap());
+        assert_eq!(104, call(&mut xc, "", 1).unwrap());
+
+        // the cached reply expires
+        thread::sleep(Duration::from_millis(350));
+        assert_eq!(105, call(&mut xc, "a", 1).unwrap());
+
+        // a duplicate of a request in progress is rejected
+        let mut slow_xc = cluster.xclient("Arith", FailMode::Failfast);
+        let slow = thread::spawn(move || call(&mut slow_xc, "c", 0));
+        thread::sleep(Duration::from_millis(100));
+        let err = call(&mut xc, "c", 0).unwrap_err();
+        assert_eq!(ErrorKind::ServerBusy, err.kind());
+        assert_eq!(106, slow.join().unwrap().unwrap());
+        assert_eq!(106, call(&mut xc, "c", 0).unwrap());
+        assert_eq!(6, CALLS.load(Ordering::SeqCst));
+
+        // the replies of other callers are not shared
+        let mut metadata = HashMap::new();
+        metadata.insert(IDEMPOTENCY_KEY.to_owned(), "c".to_owned());
+        metadata.set_auth_token("other");
+        let args = ArithAddArgs { a: 100, b: 1 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Add", false, &metadata, &args);
+        assert_eq!(107, reply.unwrap().unwrap().c);
+    }
+}
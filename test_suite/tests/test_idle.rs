@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+        time::Duration,
+    };
+
+    fn heartbeat(stream: &mut TcpStream, seq: u64) {
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_heartbeat(true);
+        req.set_serialize_type(SerializeType::SerializeNone);
+        req.set_compress_type(CompressType::CompressNone);
+        req.set_seq(seq);
+        req.payload = Bytes::from(&b"ping"[..]);
+        stream.write_all(&req.encode()).unwrap();
+
+        let mut reply = Message::new();
+        reply.decode(stream).unwrap();
+        assert!(reply.is_heartbeat());
+        assert_eq!(seq, reply.get_seq());
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let cluster = TestCluster::start(1, |server| {
+            server.set_idle_timeout(Duration::from_millis(300));
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+
+        // heartbeats keep a connection
+        let mut alive = TcpStream::connect(&server.addr).unwrap();
+        let mut silent = TcpStream::connect(&server.addr).unwrap();
+        heartbeat(&mut silent, 0);
+        for seq in 0..6 {
+            heartbeat(&mut alive, seq);
+            thread::sleep(Duration::from_millis(100));
+        }
+        let conn = alive.local_addr().unwrap();
+        assert!(server.last_activity(&conn).unwrap().elapsed() < Duration::from_millis(300));
+
+        // the silent one is closed
+        silent
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(0, silent.read(&mut buf).unwrap());
+        thread::sleep(Duration::from_millis(100));
+        let conn = silent.local_addr().unwrap();
+        assert!(server.last_activity(&conn).is_none());
+        assert!(!server.active_conns().contains(&conn));
+    }
+}
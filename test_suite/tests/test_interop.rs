@@ -0,0 +1,247 @@
+// Wire-format interop tests against rpcx-go.
+//
+// Run them by `cargo test -p test_suite --features interop`. The Go programs in `interop/` are
+// started by `go run`, so a Go toolchain is required.
+#![cfg(feature = "interop")]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        io::Write,
+        net::{SocketAddr, TcpListener, TcpStream},
+        os::unix::io::AsRawFd,
+        path::PathBuf,
+        process::{Child, Command},
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    const GO_SERVER_ADDR: &str = "127.0.0.1:8990";
+    const RUST_SERVER_ADDR: &str = "127.0.0.1:8991";
+
+    fn go_run(program: &str) -> Command {
+        let mut cmd = Command::new("go");
+        cmd.current_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("interop"))
+            .arg("run")
+            .arg(format!("./{}", program));
+        cmd
+    }
+
+    // kills the go server when the test is finished.
+    struct GoServer(Child);
+
+    impl Drop for GoServer {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+        }
+    }
+
+    fn start_go_server() -> GoServer {
+        let child = go_run("server")
+            .arg("-addr")
+            .arg(GO_SERVER_ADDR)
+            .spawn()
+            .expect("failed to run go");
+        let server = GoServer(child);
+
+        // `go run` compiles the server first
+        for _ in 0..600 {
+            if TcpStream::connect(GO_SERVER_ADDR).is_ok() {
+                return server;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("go server is not started");
+    }
+
+    struct Case {
+        seq: u64,
+        st: SerializeType,
+        ct: CompressType,
+        oneway: bool,
+        status: MessageStatusType,
+        response: bool,
+        meta: Option<(&'static str, &'static str)>,
+        payload: &'static str,
+    }
+
+    fn capture(c: &Case) -> Vec<u8> {
+        let mut cmd = go_run("capture");
+        cmd.arg(format!("-seq={}", c.seq))
+            .arg(format!("-st={}", c.st as u8))
+            .arg(format!("-ct={}", c.ct as u8))
+            .arg(format!("-oneway={}", c.oneway))
+            .arg(format!("-status={}", c.status as u8))
+            .arg(format!("-response={}", c.response))
+            .arg(format!("-payload={}", c.payload));
+        if let Some((k, v)) = c.meta {
+            cmd.arg(format!("-meta={}={}", k, v));
+        }
+        let output = cmd.output().expect("failed to run go");
+        assert!(output.status.success(), "{:?}", output);
+        output.stdout
+    }
+
+    fn build(c: &Case) -> Message {
+        let mut msg = Message::new();
+        if c.response {
+            msg.set_message_type(MessageType::Response);
+        }
+        msg.set_seq(c.seq);
+        msg.set_serialize_type(c.st);
+        msg.set_compress_type(c.ct);
+        msg.set_oneway(c.oneway);
+        msg.set_message_status_type(c.status);
+        msg.service_path = "Arith".to_owned();
+        msg.service_method = "Mul".to_owned();
+        if let Some((k, v)) = c.meta {
+            msg.metadata.borrow_mut().insert(k.to_owned(), v.to_owned());
+        }
+        msg.payload = c.payload.as_bytes().into();
+        msg
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut cases = Vec::new();
+        for &st in &[
+            SerializeType::SerializeNone,
+            SerializeType::JSON,
+            SerializeType::Protobuf,
+            SerializeType::MsgPack,
+        ] {
+            for &ct in &[CompressType::CompressNone, CompressType::Gzip] {
+                cases.push(Case {
+                    seq: 1_234_567_890,
+                    st,
+                    ct,
+                    oneway: false,
+                    status: MessageStatusType::Normal,
+                    response: false,
+                    meta: Some(("__ID", "6ba7b810-9dad-11d1-80b4-00c04fd430c9")),
+                    payload: r#"{"A":1,"B":2}"#,
+                });
+            }
+        }
+        cases.push(Case {
+            seq: 7,
+            st: SerializeType::JSON,
+            ct: CompressType::CompressNone,
+            oneway: true,
+            status: MessageStatusType::Normal,
+            response: false,
+            meta: None,
+            payload: "",
+        });
+        cases.push(Case {
+            seq: u64::max_value(),
+            st: SerializeType::JSON,
+            ct: CompressType::CompressNone,
+            oneway: false,
+            status: MessageStatusType::Error,
+            response: true,
+            meta: Some((SERVICE_ERROR, "rpcx: can't find method Div")),
+            payload: "",
+        });
+
+        for c in &cases {
+            let go_bytes = capture(c);
+            let expected = build(c);
+
+            let mut msg = Message::new();
+            msg.decode(&mut &go_bytes[..]).unwrap();
+            assert_eq!(expected.header, msg.header);
+            assert_eq!(expected.service_path, msg.service_path);
+            assert_eq!(expected.service_method, msg.service_method);
+            assert_eq!(*expected.metadata.borrow(), *msg.metadata.borrow());
+            assert_eq!(expected.payload, msg.payload);
+
+            // compressed payloads differ between gzip implementations
+            if c.ct == CompressType::CompressNone {
+                assert_eq!(go_bytes, expected.encode());
+            }
+        }
+    }
+
+    #[test]
+    fn test_rust_client_and_go_server() {
+        let _server = start_go_server();
+
+        for &ct in &[CompressType::CompressNone, CompressType::Gzip] {
+            let mut c = Client::new(GO_SERVER_ADDR);
+            c.opt.serialize_type = SerializeType::JSON;
+            c.opt.compress_type = ct;
+            c.start().unwrap();
+
+            let args = ArithAddArgs { a: 10, b: 20 };
+            let metadata = HashMap::new();
+            let reply: Option<Result<ArithAddReply>> =
+                c.call("Arith", "Mul", false, &metadata, &args);
+            assert_eq!(200, reply.unwrap().unwrap().c);
+
+            let reply: Option<Result<ArithAddReply>> =
+                c.call("Arith", "Div", false, &metadata, &args);
+            assert!(reply.unwrap().is_err());
+        }
+
+        // error status of the raw reply
+        let mut req = build(&Case {
+            seq: 1,
+            st: SerializeType::JSON,
+            ct: CompressType::CompressNone,
+            oneway: false,
+            status: MessageStatusType::Normal,
+            response: false,
+            meta: None,
+            payload: r#"{"A":1,"B":2}"#,
+        });
+        req.service_method = "Div".to_owned();
+        let mut stream = TcpStream::connect(GO_SERVER_ADDR).unwrap();
+        stream.write_all(&req.encode()).unwrap();
+        let mut reply = Message::new();
+        reply.decode(&mut stream).unwrap();
+        assert_eq!(MessageType::Response, reply.get_message_type().unwrap());
+        assert_eq!(
+            MessageStatusType::Error,
+            reply.get_message_status_type().unwrap()
+        );
+        assert_eq!(1, reply.get_seq());
+        assert!(reply.get_error().is_some());
+    }
+
+    #[test]
+    fn test_go_client_and_rust_server() {
+        let mut rpc_server = Server::new(RUST_SERVER_ADDR.to_owned(), 0);
+        register_mul(&mut rpc_server);
+
+        let addr = RUST_SERVER_ADDR.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        let status = go_run("client")
+            .arg("-addr")
+            .arg(RUST_SERVER_ADDR)
+            .status()
+            .expect("failed to run go");
+        assert!(status.success());
+
+        // clean
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(200));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    // records the calls reported by XClient.
+    struct Recorder {
+        inner: LeastConnSelector,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ClientSelector for Recorder {
+        fn select(
+            &mut self,
+            service_path: &str,
+            service_method: &str,
+            args: &dyn RpcxParam,
+        ) -> String {
+            self.inner.select(service_path, service_method, args)
+        }
+        fn update_server(&self, servers: &HashMap<String, String>) {
+            self.inner.update_server(servers)
+        }
+        fn on_call_start(&self, server: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("start {}", server));
+            self.inner.on_call_start(server)
+        }
+        fn on_call_end(&self, server: &str) {
+            self.events.lock().unwrap().push(format!("end {}", server));
+            self.inner.on_call_end(server)
+        }
+    }
+
+    #[test]
+    fn test_least_conn_selector() {
+        let mut servers = HashMap::new();
+        for addr in &[
+            "tcp@127.0.0.1:8972",
+            "tcp@127.0.0.1:8973",
+            "tcp@127.0.0.1:8974",
+        ] {
+            servers.insert(addr.to_string(), String::new());
+        }
+        let mut selector = LeastConnSelector::new();
+        selector.update_server(&servers);
+
+        let args = ArithAddArgs { a: 1, b: 2 };
+        let first = selector.select("Arith", "Mul", &args);
+        selector.on_call_start(&first);
+        let second = selector.select("Arith", "Mul", &args);
+        assert_ne!(first, second);
+        selector.on_call_start(&second);
+        let third = selector.select("Arith", "Mul", &args);
+        assert!(third != first && third != second);
+        selector.on_call_start(&third);
+        selector.on_call_start(&third);
+
+        selector.on_call_end(&second);
+        assert_eq!(second, selector.select("Arith", "Mul", &args));
+        assert_eq!(2, selector.active_calls(&third));
+
+        // the counts are kept across updates
+        selector.update_server(&servers);
+        assert_eq!(2, selector.active_calls(&third));
+    }
+
+    #[test]
+    fn test_least_conn_xclient() {
+        let cluster = TestCluster::start(2, |rpc_server| {
+            register_mul(rpc_server);
+            register_func!(
+                rpc_server,
+                "Arith",
+                "SlowMul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+
+        let servers: HashMap<String, String> = cluster
+            .addrs()
+            .into_iter()
+            .map(|addr| (addr, String::new()))
+            .collect();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let selector = Recorder {
+            inner: LeastConnSelector::new(),
+            events: events.clone(),
+        };
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let f = xc.acall::<ArithAddReply>("SlowMul", &metadata, &args);
+        for _ in 0..3 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        }
+        assert_eq!(20, f.wait().unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        let events = events.lock().unwrap();
+        let slow = events[0].trim_start_matches("start ").to_owned();
+        let fast = servers.keys().find(|k| **k != slow).unwrap();
+        let mut expected = vec![format!("start {}", slow)];
+        for _ in 0..3 {
+            expected.push(format!("start {}", fast));
+            expected.push(format!("end {}", fast));
+        }
+        // the end of the asynchronous call is reported before the next selection
+        expected.push(format!("end {}", slow));
+        assert_eq!(expected, events[..expected.len()].to_vec());
+    }
+}
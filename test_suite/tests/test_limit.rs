@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(200));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn start_cluster(policy: LimitPolicy) -> TestCluster {
+        TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            rpc_server.set_concurrency_limit("Arith", "Mul", 1, policy);
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_limit_reject() {
+        let cluster = start_cluster(LimitPolicy::Reject);
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        thread::sleep(Duration::from_millis(50));
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+
+    #[test]
+    fn test_limit_queue() {
+        let cluster = start_cluster(LimitPolicy::Queue(Duration::from_secs(2)));
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let start = Instant::now();
+        let first = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        let second = xc.acall::<ArithAddReply>("Mul", &metadata, &args);
+        assert_eq!(20, first.wait().unwrap().unwrap().c);
+        assert_eq!(20, second.wait().unwrap().unwrap().c);
+        // the calls run one after another
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+}
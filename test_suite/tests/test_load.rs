@@ -0,0 +1,128 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::common::register_mul;
+
+    fn start_cluster(n: usize) -> TestCluster {
+        TestCluster::start(n, |rpc_server| {
+            register_mul(rpc_server);
+            rpc_server.enable_load_report();
+        })
+        .unwrap()
+    }
+
+    // records the loads reported by XClient.
+    struct Recorder {
+        inner: LoadAwareSelector,
+        loads: Arc<Mutex<Vec<(String, Load)>>>,
+    }
+
+    impl ClientSelector for Recorder {
+        fn select(
+            &mut self,
+            service_path: &str,
+            service_method: &str,
+            args: &dyn RpcxParam,
+        ) -> String {
+            self.inner.select(service_path, service_method, args)
+        }
+        fn update_server(&self, servers: &HashMap<String, String>) {
+            self.inner.update_server(servers)
+        }
+        fn on_load(&self, server: &str, load: &Load) {
+            self.loads.lock().unwrap().push((server.to_owned(), *load));
+            self.inner.on_load(server, load)
+        }
+    }
+
+    #[test]
+    fn test_load_report() {
+        let cluster = start_cluster(1);
+        let addr = cluster.servers()[0].addr.clone();
+        let mut c = Client::new(&addr);
+        c.start().unwrap();
+        assert_eq!(None, c.load());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        let load = c.load().unwrap();
+        assert_eq!(0, load.queue);
+        assert!(load.cpu <= 100);
+    }
+
+    #[test]
+    fn test_load_aware_selector() {
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), String::new());
+        servers.insert("tcp@127.0.0.1:8973".to_owned(), String::new());
+        let mut selector = LoadAwareSelector::new();
+        selector.update_server(&servers);
+        assert!(selector.weight("tcp@127.0.0.1:8972") > 0.99);
+
+        // the loaded server gets a small share of the selections
+        let load = Load { cpu: 90, queue: 9 };
+        selector.on_load("tcp@127.0.0.1:8972", &load);
+        assert!(selector.weight("tcp@127.0.0.1:8972") < 0.02);
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let loaded = (0..1000)
+            .filter(|_| selector.select("Arith", "Mul", &args) == "tcp@127.0.0.1:8972")
+            .count();
+        assert!(loaded < 100);
+
+        // the loads are kept when the servers are updated
+        selector.update_server(&servers);
+        assert!(selector.weight("tcp@127.0.0.1:8972") < 0.02);
+
+        // any queue reported leaves a share
+        let load = Load {
+            cpu: 100,
+            queue: usize::MAX,
+        };
+        selector.on_load("tcp@127.0.0.1:8972", &load);
+        assert!(selector.weight("tcp@127.0.0.1:8972") > 0.0);
+    }
+
+    #[test]
+    fn test_load_aware_xclient() {
+        let cluster = start_cluster(2);
+        let servers: HashMap<String, String> = cluster
+            .addrs()
+            .into_iter()
+            .map(|addr| (addr, String::new()))
+            .collect();
+        let loads = Arc::new(Mutex::new(Vec::new()));
+        let selector = Recorder {
+            inner: LoadAwareSelector::new(),
+            loads: loads.clone(),
+        };
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        for _ in 0..2 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        }
+        // the load of the first reply is reported before the second selection
+        let loads = loads.lock().unwrap();
+        assert!(!loads.is_empty());
+        assert!(loads.iter().all(|(server, _)| servers.contains_key(server)));
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::register_mul;
+
+    // returns the name of the thread the call is handled on.
+    fn whoami<S: ClientSelector>(xc: &mut XClient<S>) -> String {
+        let name: Option<Result<Bytes>> = xc.call("Whoami", false, &HashMap::new(), &Bytes::new());
+        String::from_utf8(name.unwrap().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_local_dispatch() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            let whoami: RpcxFn = |_, _| {
+                let name = thread::current().name().unwrap_or_default().to_owned();
+                Ok(name.into_bytes())
+            };
+            rpc_server.register_fn(
+                "Arith".to_owned(),
+                "Whoami".to_owned(),
+                "".to_owned(),
+                whoami,
+            );
+        })
+        .unwrap();
+        let addr = cluster.addrs()[0].clone();
+        // the server is local once it serves
+        while local_server(&addr).is_none() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let metadata = HashMap::new();
+        let caller = thread::current().name().unwrap().to_owned();
+        assert_ne!(caller, whoami(&mut xc));
+
+        xc.enable_local_dispatch();
+        assert_eq!(caller, whoami(&mut xc));
+
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", true, &metadata, &args);
+        assert!(reply.is_none());
+        let reply: Option<Result<ArithAddReply>> = xc.call("Div", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+
+        drop(cluster);
+        assert!(local_server(&addr).is_none());
+    }
+}
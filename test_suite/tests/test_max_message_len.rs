@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, time::Duration};
+
+    const MAX_LEN: usize = 2 * MAX_MESSAGE_LEN;
+
+    fn start_client(addr: &str, max_message_len: usize) -> Client {
+        let mut c = Client::new(addr);
+        c.opt.serialize_type = SerializeType::SerializeNone;
+        c.opt.read_timeout = Duration::from_secs(10);
+        c.opt.max_message_len = max_message_len;
+        c.start().unwrap();
+        c
+    }
+
+    #[test]
+    fn test_max_message_len() {
+        let cluster = TestCluster::start(1, |server| {
+            server.set_max_message_len(MAX_LEN);
+            let echo: RpcxFn = |x, _| Ok(x.to_vec());
+            server.register_fn("Echo".to_owned(), "Echo".to_owned(), "".to_owned(), echo);
+        })
+        .unwrap();
+        let addr = cluster.servers()[0].addr.clone();
+
+        let metadata = HashMap::new();
+        let args = Bytes::from(vec![7u8; MAX_MESSAGE_LEN + 1]);
+        let c = start_client(&addr, MAX_LEN);
+        let reply: Option<Result<Bytes>> = c.call("Echo", "Echo", false, &metadata, &args);
+        assert_eq!(args, reply.unwrap().unwrap());
+
+        // the reply exceeds the default limit of the client
+        let c = start_client(&addr, MAX_MESSAGE_LEN);
+        let reply: Option<Result<Bytes>> = c.call("Echo", "Echo", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::HashMap;
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_metadata_limits() {
+        let cluster = TestCluster::start(1, |server| {
+            server.set_metadata_limits(MetadataLimits {
+                max_entries: 4,
+                ..MetadataLimits::default()
+            });
+            register_mul(server);
+        })
+        .unwrap();
+
+        let args = ArithAddArgs { a: 10, b: 20 };
+
+        // the request id is an entry too
+        let mut metadata = HashMap::new();
+        for i in 0..3 {
+            metadata.insert(i.to_string(), String::new());
+        }
+        let mut client = cluster.xclient("Arith", FailMode::Failfast);
+        let reply: ArithAddReply = client
+            .call("Mul", false, &metadata, &args)
+            .unwrap()
+            .unwrap();
+        assert_eq!(200, reply.c);
+
+        // the server closes the connection of a request with too many entries
+        metadata.insert("3".to_owned(), String::new());
+        let mut client = cluster.xclient("Arith", FailMode::Failfast);
+        let rt: Result<ArithAddReply> = client.call("Mul", false, &metadata, &args).unwrap();
+        assert!(rt.is_err());
+    }
+}
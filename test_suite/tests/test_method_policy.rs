@@ -0,0 +1,133 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, net::TcpListener};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_method_selector() {
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), String::new());
+        servers.insert("tcp@127.0.0.1:8973".to_owned(), String::new());
+
+        let mut selector = MethodSelector::new(Box::new(RoundbinSelector::new()));
+        selector.update_server(&servers);
+        // starts with the current servers
+        selector.set_method_selector("Mul", Box::new(ConsistentHashSelector::new()));
+
+        let args = ArithAddArgs { a: 1, b: 2 };
+        let mul = selector.select("Arith", "Mul", &args);
+        let add = selector.select("Arith", "Add", &args);
+        for _ in 0..4 {
+            assert_eq!(mul, selector.select("Arith", "Mul", &args));
+        }
+        assert_ne!(add, selector.select("Arith", "Add", &args));
+    }
+
+    #[test]
+    fn test_method_config() {
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config.fail_mode = FailMode::Failover;
+        config.retry = 3;
+        config
+            .servers
+            .insert("tcp@127.0.0.1:8972".to_owned(), String::new());
+        config.methods.insert(
+            "Add".to_owned(),
+            MethodConfig {
+                fail_mode: Some(FailMode::Failfast),
+                select_mode: None,
+                retry: Some(0),
+                hedge: None,
+            },
+        );
+        config.methods.insert(
+            "Mul".to_owned(),
+            MethodConfig {
+                select_mode: Some(SelectMode::ConsistentHash),
+                ..Default::default()
+            },
+        );
+
+        let mut xc = XClient::with_config(&config).unwrap();
+        assert_eq!(
+            CallPolicy {
+                fail_mode: FailMode::Failfast,
+                retry: 0
+            },
+            xc.call_policy("Add")
+        );
+        assert_eq!(
+            CallPolicy {
+                fail_mode: FailMode::Failover,
+                retry: 3
+            },
+            xc.call_policy("Mul")
+        );
+
+        config.methods.remove("Add");
+        xc.reload(&config).unwrap();
+        assert_eq!(FailMode::Failover, xc.call_policy("Add").fail_mode);
+
+        xc.set_call_policy(
+            "Add",
+            CallPolicy {
+                fail_mode: FailMode::Failtry,
+                retry: 1,
+            },
+        );
+        assert_eq!(FailMode::Failtry, xc.call_policy("Add").fail_mode);
+    }
+
+    #[test]
+    fn test_failover_reselects() {
+        let cluster = start_cluster(1);
+        // a server which is gone
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut servers = HashMap::new();
+        servers.insert(cluster.addrs()[0].clone(), String::new());
+        servers.insert(format!("tcp@{}", dead), String::new());
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failover,
+            Box::new(selector),
+            Opt::default(),
+        );
+        xc.set_call_policy(
+            "Mul",
+            CallPolicy {
+                fail_mode: FailMode::Failover,
+                retry: 1,
+            },
+        );
+        // the policies of the methods of other services are their own
+        xc.set_service_call_policy(
+            "Calc",
+            "Mul",
+            CallPolicy {
+                fail_mode: FailMode::Failfast,
+                retry: 0,
+            },
+        );
+        assert_eq!(FailMode::Failover, xc.call_policy("Mul").fail_mode);
+
+        // the retry of a call to the dead server goes to the other one
+        let metadata = HashMap::new();
+        for a in 0..4 {
+            let args = ArithAddArgs { a, b: 10 };
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(a * 10, reply.unwrap().unwrap().c);
+        }
+    }
+}
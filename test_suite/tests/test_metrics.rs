@@ -0,0 +1,160 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::common::register_mul;
+
+    // records the metrics as `name{label=value,...}` and their values.
+    #[derive(Default)]
+    struct Recorder {
+        metrics: Mutex<Vec<(String, f64)>>,
+    }
+
+    impl Recorder {
+        fn record(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+            let labels: Vec<String> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            self.metrics
+                .lock()
+                .unwrap()
+                .push((format!("{}{{{}}}", name, labels.join(",")), value));
+        }
+
+        fn find(&self, name: &str) -> Vec<(String, f64)> {
+            let metrics = self.metrics.lock().unwrap();
+            metrics
+                .iter()
+                .filter(|(k, _)| k.starts_with(name))
+                .cloned()
+                .collect()
+        }
+    }
+
+    impl MetricsSink for Recorder {
+        fn counter(&self, name: &str, value: u64, labels: &[(&str, &str)]) {
+            self.record(name, value as f64, labels);
+        }
+        fn gauge(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+            self.record(name, value, labels);
+        }
+        fn histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+            self.record(name, value, labels);
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let server_metrics = Arc::new(Recorder::default());
+        let sink = server_metrics.clone();
+        let cluster = TestCluster::start(1, move |rpc_server| {
+            register_mul(rpc_server);
+            rpc_server.set_metrics_sink(sink.clone());
+        })
+        .unwrap();
+        let addr = cluster.addrs()[0].clone();
+        let client_metrics = Arc::new(Recorder::default());
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        xc.set_metrics_sink(client_metrics.clone());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Div", false, &metadata, &args);
+        assert!(reply.unwrap().is_err());
+
+        let calls = client_metrics.find(CLIENT_CALLS);
+        assert_eq!(
+            vec![
+                (
+                    format!(
+                        "{}{{service=Arith,method=Mul,server={},status=ok}}",
+                        CLIENT_CALLS, addr
+                    ),
+                    1.0
+                ),
+                (
+                    format!(
+                        "{}{{service=Arith,method=Div,server={},status=error}}",
+                        CLIENT_CALLS, addr
+                    ),
+                    1.0
+                ),
+            ],
+            calls
+        );
+        assert_eq!(2, client_metrics.find(CLIENT_CALL_DURATION).len());
+        assert_eq!(
+            vec![(
+                format!(
+                    "{}{{service=Arith,method=Div,server={},class=server}}",
+                    CLIENT_ERRORS, addr
+                ),
+                1.0
+            )],
+            client_metrics.find(CLIENT_ERRORS)
+        );
+        assert_eq!("timeout", error_class(ErrorKind::Timeout));
+        assert_eq!("connect", error_class(ErrorKind::ConnectionClosed));
+        assert_eq!("rate_limited", error_class(ErrorKind::ServerBusy));
+
+        let requests = server_metrics.find(SERVER_REQUESTS);
+        assert_eq!(
+            vec![
+                (
+                    format!("{}{{service=Arith,method=Mul,status=ok}}", SERVER_REQUESTS),
+                    1.0
+                ),
+                (
+                    format!(
+                        "{}{{service=Arith,method=Div,status=error}}",
+                        SERVER_REQUESTS
+                    ),
+                    1.0
+                ),
+            ],
+            requests
+        );
+        assert_eq!(2, server_metrics.find(SERVER_REQUEST_DURATION).len());
+        let conns = server_metrics.find(SERVER_CONNECTIONS);
+        assert_eq!((format!("{}{{}}", SERVER_CONNECTIONS), 1.0), conns[0]);
+
+        // the requests are counted by method
+        let stats = cluster.servers()[0].method_stats();
+        assert_eq!(2, stats.len());
+        assert_eq!(
+            ("Arith", "Div", 1, 1),
+            (
+                stats[0].service_path.as_str(),
+                stats[0].service_method.as_str(),
+                stats[0].requests,
+                stats[0].errors
+            )
+        );
+        assert_eq!(0.0, stats[0].success_ratio());
+        assert_eq!(0.0, stats[0].apdex());
+        assert_eq!("Mul", stats[1].service_method);
+        assert_eq!(1.0, stats[1].success_ratio());
+        assert_eq!(1.0, stats[1].apdex());
+        assert_eq!(LATENCY_BUCKETS.len() + 1, stats[1].buckets.len());
+        assert_eq!(1, stats[1].buckets.iter().sum::<u64>());
+        assert_eq!(
+            (
+                format!(
+                    "{}{{service=Arith,method=Div}}",
+                    SERVER_METHOD_SUCCESS_RATIO
+                ),
+                0.0
+            ),
+            server_metrics.find(SERVER_METHOD_SUCCESS_RATIO)[1]
+        );
+        assert_eq!(2, server_metrics.find(SERVER_METHOD_APDEX).len());
+    }
+}
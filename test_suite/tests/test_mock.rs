@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    // a consumer of the Arith service, generic over the client.
+    fn double<C: RpcxClient>(client: &mut C, a: u64) -> Result<u64> {
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a, b: 2 };
+        let reply: Option<Result<ArithAddReply>> = client.call("Mul", false, &metadata, &args);
+        reply.unwrap().map(|reply| reply.c)
+    }
+
+    #[test]
+    fn test_mock_client() {
+        let mut client = MockClient::new("Arith");
+        client
+            .reply("Mul", &ArithAddReply { c: 42 })
+            .unwrap()
+            .latency("Mul", Duration::from_millis(20));
+        let start = Instant::now();
+        assert_eq!(42, double(&mut client, 21).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        client.handle("Mul", |data| {
+            let mut args = ArithAddArgs::default();
+            args.from_slice(SerializeType::JSON, data)?;
+            ArithAddReply { c: args.a * args.b }.into_bytes(SerializeType::JSON)
+        });
+        assert_eq!(14, double(&mut client, 7).unwrap());
+        assert_eq!(2, client.calls("Mul"));
+
+        client.error("Mul", ErrorKind::RateLimited, "limit exceeded");
+        assert_eq!(
+            ErrorKind::RateLimited,
+            double(&mut client, 1).unwrap_err().kind()
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 1, b: 2 };
+        let reply = client
+            .acall::<ArithAddReply>("Add", &metadata, &args)
+            .wait()
+            .unwrap();
+        assert_eq!(ErrorKind::Server, reply.unwrap_err().kind());
+    }
+}
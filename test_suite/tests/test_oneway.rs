@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::Write,
+        net::{TcpListener, TcpStream},
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn mul(args: ArithAddArgs) -> ArithAddReply {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    #[test]
+    fn test_oneway_request() {
+        // a fake server reads the request of the client
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = Message::new();
+            req.decode(&mut stream).unwrap();
+            req
+        });
+
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config
+            .servers
+            .insert(format!("tcp@{}", addr), String::new());
+        let mut client = XClient::with_config(&config).unwrap();
+
+        let args = ArithAddArgs { a: 10, b: 20 };
+        let rt = client.call::<ArithAddReply>("Mul", true, &HashMap::new(), &args);
+        assert!(rt.is_none());
+
+        let req = server.join().unwrap();
+        assert!(req.is_oneway());
+        assert_eq!("Mul", req.service_method);
+    }
+
+    #[test]
+    fn test_oneway_no_reply() {
+        let cluster = TestCluster::start(1, |server| {
+            register_func!(
+                server,
+                "Arith",
+                "Mul",
+                mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+
+        let mut stream = TcpStream::connect(&server.addr).unwrap();
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_oneway(true);
+        req.set_serialize_type(SerializeType::JSON);
+        req.set_compress_type(CompressType::CompressNone);
+        req.set_seq(1);
+        req.service_path = "Arith".to_owned();
+        req.service_method = "Mul".to_owned();
+        req.payload = Bytes::from(&br#"{"A":10,"B":20}"#[..]);
+        stream.write_all(&req.encode()).unwrap();
+
+        while CALLS.load(Ordering::SeqCst) == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        // the first message read is the reply of the heartbeat sent after the oneway request
+        let mut heartbeat = Message::new();
+        heartbeat.set_message_type(MessageType::Request);
+        heartbeat.set_heartbeat(true);
+        heartbeat.set_seq(2);
+        stream.write_all(&heartbeat.encode()).unwrap();
+
+        let mut reply = Message::new();
+        reply.decode(&mut stream).unwrap();
+        assert!(reply.is_heartbeat());
+        assert_eq!(2, reply.get_seq());
+    }
+}
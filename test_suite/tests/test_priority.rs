@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    // replies the order the request is handled in
+    fn order(_: ArithAddArgs) -> ArithAddReply {
+        ArithAddReply {
+            c: HANDLED.fetch_add(1, Ordering::SeqCst) as u64,
+        }
+    }
+
+    #[test]
+    fn test_priority() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // a single worker, so the other requests wait for it
+        let mut rpc_server = Server::new(listener.local_addr().unwrap().to_string(), 1);
+        rpc_server.set_max_queued_requests(2);
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Mul",
+            slow_mul,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Order",
+            order,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        let addr = rpc_server.addr.clone();
+        thread::spawn(move || {
+            let _ = rpc_server.start_with_listener(listener);
+        });
+
+        let mut c = Client::new(&addr);
+        c.start().unwrap();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = c.acall::<ArithAddReply>("Arith", "Mul", &HashMap::new(), &args);
+        thread::sleep(Duration::from_millis(50));
+
+        let mut calls = Vec::new();
+        for priority in &[Priority::Low, Priority::Normal, Priority::High] {
+            let mut metadata = HashMap::new();
+            set_priority(&mut metadata, *priority);
+            calls.push(c.acall::<ArithAddReply>("Arith", "Order", &metadata, &args));
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+
+        // the high priority request takes a slot reserved for it though the queue is full, and
+        // all of them are handled by priority
+        let order: Vec<u64> = calls
+            .into_iter()
+            .map(|call| call.wait().unwrap().unwrap().c)
+            .collect();
+        assert_eq!(vec![2, 1, 0], order);
+    }
+
+    #[test]
+    fn test_high_priority_slots() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // a single worker, so the other requests wait for it
+        let mut rpc_server = Server::new(listener.local_addr().unwrap().to_string(), 1);
+        rpc_server.set_max_queued_requests(1);
+        rpc_server.set_high_priority_slots(1);
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Mul",
+            slow_mul,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        let addr = rpc_server.addr.clone();
+        thread::spawn(move || {
+            let _ = rpc_server.start_with_listener(listener);
+        });
+
+        let mut c = Client::new(&addr);
+        c.start().unwrap();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = c.acall::<ArithAddReply>("Arith", "Mul", &HashMap::new(), &args);
+        thread::sleep(Duration::from_millis(50));
+
+        let mut metadata = HashMap::new();
+        set_priority(&mut metadata, Priority::High);
+        let calls: Vec<_> = (0..3)
+            .map(|_| {
+                let call = c.acall::<ArithAddReply>("Arith", "Mul", &metadata, &args);
+                thread::sleep(Duration::from_millis(20));
+                call
+            })
+            .collect();
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+
+        // high priority requests are shed beyond the slots reserved for them
+        let replies: Vec<_> = calls
+            .into_iter()
+            .map(|call| call.wait().unwrap())
+            .collect();
+        assert_eq!(20, replies[0].as_ref().unwrap().c);
+        assert_eq!(20, replies[1].as_ref().unwrap().c);
+        assert_eq!(
+            ErrorKind::ServerBusy,
+            replies[2].as_ref().unwrap_err().kind()
+        );
+    }
+}
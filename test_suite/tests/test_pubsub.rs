@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn test_pubsub() {
+        // setup server
+        let rpc_server = Server::new("127.0.0.1:8978".to_owned(), 0);
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let rpc_server = Arc::new(rpc_server);
+        let server_cloned = rpc_server.clone();
+        let handler = thread::spawn(move || match server_cloned.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut c = Client::new("127.0.0.1:8978");
+        c.start().unwrap();
+        let messages = c.subscribe();
+        c.subscribe_topic("orders.*.created").unwrap();
+        assert!(c.subscribe_topic("orders.>.created").is_err());
+
+        let metadata = HashMap::new();
+        rpc_server
+            .publish("orders.eu.deleted", &metadata, b"1".to_vec())
+            .unwrap();
+        rpc_server
+            .publish("orders.eu.created", &metadata, b"2".to_vec())
+            .unwrap();
+
+        let msg = messages.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(PUBSUB_SERVICE, msg.service_path);
+        assert_eq!("orders.eu.created", msg.service_method);
+        assert_eq!(b"2".to_vec(), msg.payload);
+
+        c.unsubscribe_topic("orders.*.created").unwrap();
+        rpc_server
+            .publish("orders.us.created", &metadata, b"3".to_vec())
+            .unwrap();
+        assert!(messages.recv_timeout(Duration::from_millis(500)).is_err());
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+
+    struct DenyPlugin;
+
+    impl MessagePlugin for DenyPlugin {
+        fn post_read_request(&self, _req: &mut Message) -> Result<()> {
+            Err(Error::new(ErrorKind::Server, "denied"))
+        }
+    }
+
+    #[test]
+    fn test_subscribe_plugins() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            rpc_server.add_message_plugin(Box::new(DenyPlugin));
+        })
+        .unwrap();
+
+        // subscriptions pass the plugins like calls
+        let server = cluster.servers()[0].clone();
+        let mut c = Client::new(&server.addr);
+        c.start().unwrap();
+        let messages = c.subscribe();
+        let err = c.subscribe_topic("orders.*.created").unwrap_err();
+        assert!(err.to_string().contains("denied"));
+
+        server
+            .publish("orders.eu.created", &HashMap::new(), b"1".to_vec())
+            .unwrap();
+        assert!(messages.recv_timeout(Duration::from_millis(500)).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_server_push() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8976".to_owned(), 0);
+        register_mul(&mut rpc_server);
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let rpc_server = Arc::new(rpc_server);
+        let server_cloned = rpc_server.clone();
+        let handler = thread::spawn(move || match server_cloned.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut c = Client::new("127.0.0.1:8976");
+        c.start().unwrap();
+        let messages = c.subscribe();
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 3 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(6, reply.unwrap().unwrap().c);
+
+        // push a message to the client
+        let conns = rpc_server.active_conns();
+        assert_eq!(1, conns.len());
+        let mut metadata = HashMap::new();
+        metadata.insert("topic".to_owned(), "news".to_owned());
+        rpc_server
+            .send_message(&conns[0], "Notify", "Push", &metadata, b"hello".to_vec())
+            .unwrap();
+
+        let msg = messages.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("Notify", msg.service_path);
+        assert_eq!("Push", msg.service_method);
+        assert_eq!("news", msg.metadata.borrow().get("topic").unwrap());
+        assert_eq!(b"hello".to_vec(), msg.payload);
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, net::TcpListener, thread, time::Duration};
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    #[test]
+    fn test_max_queued_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // a single worker, so the other requests wait in the queue
+        let mut rpc_server = Server::new(listener.local_addr().unwrap().to_string(), 1);
+        rpc_server.set_max_queued_requests(1);
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Mul",
+            slow_mul,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        let addr = rpc_server.addr.clone();
+        thread::spawn(move || {
+            let _ = rpc_server.start_with_listener(listener);
+        });
+
+        let mut c = Client::new(&addr);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let running = c.acall::<ArithAddReply>("Arith", "Mul", &metadata, &args);
+        thread::sleep(Duration::from_millis(50));
+        let queued = c.acall::<ArithAddReply>("Arith", "Mul", &metadata, &args);
+        thread::sleep(Duration::from_millis(50));
+
+        // the queue is full
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(ErrorKind::ServerBusy, reply.unwrap().unwrap_err().kind());
+
+        assert_eq!(20, running.wait().unwrap().unwrap().c);
+        assert_eq!(20, queued.wait().unwrap().unwrap().c);
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+    }
+}
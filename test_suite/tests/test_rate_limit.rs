@@ -0,0 +1,82 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::register_mul;
+
+    fn call<S: ClientSelector>(xc: &mut XClient<S>, token: Option<&str>) -> Result<u64> {
+        let mut metadata = HashMap::new();
+        if let Some(token) = token {
+            metadata.set_auth_token(token);
+        }
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        reply.unwrap().map(|reply| reply.c)
+    }
+
+    #[test]
+    fn test_rate_limit_by_identity() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            // tokens are `<tenant>.<secret>`, only the tenant is limited
+            let mut limiter =
+                RateLimitPlugin::with_identity(Quota { qps: 5.0, burst: 2 }, |metadata| {
+                    let token = metadata.auth_token()?;
+                    let mut parts = token.splitn(2, '.');
+                    match (parts.next(), parts.next()) {
+                        (Some(tenant), Some("secret")) => Some(tenant.to_owned()),
+                        _ => None,
+                    }
+                });
+            limiter.set_quota("vip", Quota { qps: 5.0, burst: 5 });
+            rpc_server.add_message_plugin(Box::new(limiter));
+            register_mul(rpc_server);
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        // each tenant has its own quota, whatever its tokens
+        assert_eq!(20, call(&mut xc, Some("a.secret")).unwrap());
+        assert_eq!(20, call(&mut xc, Some("a.secret")).unwrap());
+        let err = call(&mut xc, Some("a.secret")).unwrap_err();
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+        assert_eq!(20, call(&mut xc, Some("b.secret")).unwrap());
+        for _ in 0..5 {
+            assert_eq!(20, call(&mut xc, Some("vip.secret")).unwrap());
+        }
+        assert!(call(&mut xc, Some("vip.secret")).is_err());
+
+        // the requests without a valid token share the quota of the peer IP
+        assert_eq!(20, call(&mut xc, None).unwrap());
+        assert_eq!(20, call(&mut xc, Some("a.forged")).unwrap());
+        assert!(call(&mut xc, None).is_err());
+
+        // the bucket refills at the rate of the quota
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(20, call(&mut xc, Some("a.secret")).unwrap());
+    }
+
+    #[test]
+    fn test_rate_limit_by_peer() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            let limiter = RateLimitPlugin::new(Quota {
+                qps: 0.01,
+                burst: 2,
+            });
+            rpc_server.add_message_plugin(Box::new(limiter));
+            register_mul(rpc_server);
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        // the tokens are not verified, so they don't get their own quota
+        assert_eq!(20, call(&mut xc, Some("a")).unwrap());
+        assert_eq!(20, call(&mut xc, Some("b")).unwrap());
+        let err = call(&mut xc, Some("c")).unwrap_err();
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+    }
+}
@@ -0,0 +1,49 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_refresh_removed_servers() {
+        let cluster = start_cluster(2);
+        let addrs = cluster.addrs();
+        let removed = cluster.servers()[1].clone();
+
+        let selector = SharedSelector::new(Box::new(RoundbinSelector::new()));
+        let mut servers: HashMap<String, String> = addrs
+            .iter()
+            .map(|addr| (addr.clone(), String::new()))
+            .collect();
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector.clone()),
+            Opt::default(),
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        for _ in 0..2 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        }
+        assert_eq!(1, removed.active_conns().len());
+
+        // the discovery removes the server, its connection is closed at the next call
+        servers.remove(&addrs[1]);
+        selector.update_server(&servers);
+        thread::sleep(REFRESH_INTERVAL);
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        thread::sleep(Duration::from_millis(200));
+        assert!(removed.active_conns().is_empty());
+        assert_eq!(1, cluster.servers()[0].active_conns().len());
+    }
+}
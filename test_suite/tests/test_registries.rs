@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{
+        env, fs, process,
+        sync::{Arc, Mutex},
+    };
+
+    struct MetaRecorder {
+        metas: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl RegisterPlugin for MetaRecorder {
+        fn register_fn(
+            &mut self,
+            service_path: &str,
+            service_method: &str,
+            meta: String,
+            _: RpcxFn,
+        ) -> Result<()> {
+            self.metas
+                .lock()
+                .unwrap()
+                .push((format!("{}.{}", service_path, service_method), meta));
+            Ok(())
+        }
+
+        fn update_meta(&mut self, service_path: &str, meta: String) -> Result<()> {
+            self.metas
+                .lock()
+                .unwrap()
+                .push((service_path.to_owned(), meta));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_multiple_registries() {
+        let old = Arc::new(Mutex::new(Vec::new()));
+        let new = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new("127.0.0.1:0".to_owned(), 0);
+        rpc_server.add_register_plugin(Box::new(MetaRecorder { metas: old.clone() }));
+
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        rpc_server.register_fn(
+            "Echo".to_owned(),
+            "Echo".to_owned(),
+            "group=a".to_owned(),
+            echo,
+        );
+        // the methods of a service are registered with the metadata of the service
+        rpc_server.register_fn(
+            "Echo".to_owned(),
+            "Ping".to_owned(),
+            "group=b".to_owned(),
+            echo,
+        );
+
+        // the services registered before are registered to the new registry
+        rpc_server.add_register_plugin(Box::new(MetaRecorder { metas: new.clone() }));
+        let mut registered = new.lock().unwrap().clone();
+        registered.sort();
+        assert_eq!(
+            vec![
+                ("Echo.Echo".to_owned(), "group=a".to_owned()),
+                ("Echo.Ping".to_owned(), "group=a".to_owned()),
+            ],
+            registered
+        );
+
+        // both registries are updated
+        rpc_server.set_weight(5).unwrap();
+        let old: Vec<(String, String)> = old.lock().unwrap().clone();
+        let new: Vec<(String, String)> = new.lock().unwrap().clone();
+        assert_eq!(
+            vec![
+                ("Echo.Echo".to_owned(), "group=a".to_owned()),
+                ("Echo.Ping".to_owned(), "group=a".to_owned()),
+                ("Echo".to_owned(), "group=a&weight=5".to_owned()),
+            ],
+            old
+        );
+        assert_eq!(old[2], new[2]);
+    }
+
+    #[test]
+    fn test_registries_config() {
+        let path = env::temp_dir().join(format!("rpcx_test_registries_{}.toml", process::id()));
+        fs::write(
+            &path,
+            r#"
+[registry]
+addrs = ["http://10.0.0.1:2379"]
+
+[[registries]]
+addrs = ["http://10.0.1.1:2379"]
+base_path = "/rpcx_v2"
+"#,
+        )
+        .unwrap();
+        let config: ServerConfig = load_config(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!("/rpcx", config.registry.unwrap().base_path);
+        assert_eq!(1, config.registries.len());
+        assert_eq!(vec!["http://10.0.1.1:2379"], config.registries[0].addrs);
+        assert_eq!("/rpcx_v2", config.registries[0].base_path);
+        assert_eq!(10, config.registries[0].update_interval_secs);
+    }
+}
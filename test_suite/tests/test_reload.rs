@@ -0,0 +1,75 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, env, fs, process, thread, time::Duration};
+
+    use super::common::register_mul;
+
+    fn call<S: ClientSelector>(xc: &mut XClient<S>) -> Result<u64> {
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        reply.unwrap().map(|reply| reply.c)
+    }
+
+    #[test]
+    fn test_reload() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            let limiter = RateLimitPlugin::new(Quota {
+                qps: 0.01,
+                burst: 1,
+            });
+            rpc_server.add_message_plugin(Box::new(limiter));
+            register_mul(rpc_server);
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        assert_eq!(20, call(&mut xc).unwrap());
+        assert_eq!(ErrorKind::RateLimited, call(&mut xc).unwrap_err().kind());
+
+        // the connection is kept and the new quota applies at once
+        let mut config = ServerConfig::default();
+        config.rate_limit = Some(RateLimitConfig {
+            qps: 1000.0,
+            burst: 10,
+            quotas: HashMap::new(),
+        });
+        server.reload(&config).unwrap();
+        // the bucket is kept empty, it refills at the new rate
+        thread::sleep(Duration::from_millis(20));
+        for _ in 0..5 {
+            assert_eq!(20, call(&mut xc).unwrap());
+        }
+
+        config.tls = Some(TlsConfig::default());
+        assert_eq!(
+            ErrorKind::Config,
+            server.reload(&config).unwrap_err().kind()
+        );
+
+        // reloads the file on SIGHUP
+        let path = env::temp_dir().join(format!("rpcx_test_reload_{}.toml", process::id()));
+        fs::write(&path, "[rate_limit]\nqps = 0.01\nburst = 1\n").unwrap();
+        Server::reload_on_sighup(server.clone(), &path).unwrap();
+        assert!(Server::reload_on_sighup(server.clone(), &path).is_err());
+        unsafe {
+            libc::raise(libc::SIGHUP);
+        }
+        let mut limited = false;
+        for _ in 0..50 {
+            if let Err(err) = call(&mut xc) {
+                assert_eq!(ErrorKind::RateLimited, err.kind());
+                limited = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(limited);
+        fs::remove_file(&path).unwrap();
+    }
+}
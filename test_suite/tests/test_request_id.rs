@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, io::Write, net::TcpStream};
+
+    // replies the id of the request the handler serves.
+    fn request_id(_: &[u8], _: SerializeType) -> Result<Vec<u8>> {
+        let ctx = Context::current();
+        Ok(ctx.metadata()[REQUEST_ID].clone().into_bytes())
+    }
+
+    fn request(seq: u64, service_method: &str) -> Message {
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(SerializeType::SerializeNone);
+        req.set_compress_type(CompressType::CompressNone);
+        req.set_seq(seq);
+        req.service_path = "Trace".to_owned();
+        req.service_method = service_method.to_owned();
+        req.metadata
+            .borrow_mut()
+            .insert(REQUEST_ID.to_owned(), "6ba7b810".to_owned());
+        req
+    }
+
+    #[test]
+    fn test_request_id() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            rpc_server.enable_error_log();
+            rpc_server.register_fn(
+                "Trace".to_owned(),
+                "Id".to_owned(),
+                "".to_owned(),
+                request_id,
+            );
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+
+        let mut c = Client::new(&server.addr);
+        c.opt.serialize_type = SerializeType::SerializeNone;
+        c.start().unwrap();
+        let args = Bytes::new();
+
+        // the id passed by the caller reaches the handler
+        let mut metadata = HashMap::new();
+        metadata.insert(REQUEST_ID.to_owned(), "6ba7b810".to_owned());
+        let reply: Option<Result<Bytes>> = c.call("Trace", "Id", false, &metadata, &args);
+        assert_eq!(&b"6ba7b810"[..], &reply.unwrap().unwrap()[..]);
+
+        // the client generates one otherwise
+        let reply: Option<Result<Bytes>> = c.call("Trace", "Id", false, &HashMap::new(), &args);
+        let id = reply.unwrap().unwrap();
+        assert_eq!(32, id.len());
+        assert!(id.iter().all(u8::is_ascii_hexdigit));
+
+        // the id comes back in the replies, the failed ones too
+        let mut stream = TcpStream::connect(&server.addr).unwrap();
+        for (seq, service_method) in [(1, "Id"), (2, "Unknown")].iter() {
+            stream
+                .write_all(&request(*seq, service_method).encode())
+                .unwrap();
+            let mut reply = Message::new();
+            reply.decode(&mut stream).unwrap();
+            assert_eq!(*seq, reply.get_seq());
+            assert_eq!(Some("6ba7b810".to_owned()), reply.get_request_id());
+        }
+    }
+}
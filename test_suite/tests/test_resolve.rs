@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_hostname() {
+        let cluster = start_cluster(1);
+        let port = cluster.servers()[0]
+            .addr
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .to_owned();
+
+        // the hostname is resolved, with or without a connect timeout
+        let mut servers = HashMap::new();
+        servers.insert(format!("tcp@localhost:{}", port), String::new());
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+        let mut opt = Opt::default();
+        opt.connect_timeout = Duration::from_secs(1);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            opt,
+        );
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        let mut c = Client::new("unknown.invalid:8972");
+        assert!(c.start().is_err());
+    }
+
+    #[test]
+    fn test_dns_cache() {
+        let cluster = start_cluster(1);
+        let server = cluster.servers()[0].clone();
+        let port = server.addr.rsplit(':').next().unwrap().to_owned();
+        let endpoint = format!("tcp@localhost:{}", port);
+
+        let mut servers = HashMap::new();
+        servers.insert(endpoint.clone(), String::new());
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector),
+            Opt::default(),
+        );
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let call = |xc: &mut XClient<RoundbinSelector>| {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        };
+
+        call(&mut xc);
+        assert!(!xc.resolved_addrs(&endpoint).unwrap().is_empty());
+        let conn = server.active_conns();
+        assert_eq!(1, conn.len());
+
+        // the next call resolves the hostname again on a new connection
+        xc.invalidate(&endpoint);
+        assert!(xc.resolved_addrs(&endpoint).is_none());
+        call(&mut xc);
+        assert!(xc.resolved_addrs(&endpoint).is_some());
+        for _ in 0..100 {
+            if !server.active_conns().contains(&conn[0]) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let conns = server.active_conns();
+        assert_eq!(1, conns.len());
+        assert_ne!(conn[0], conns[0]);
+
+        xc.set_dns_ttl(Duration::from_secs(0));
+        xc.invalidate(&endpoint);
+        call(&mut xc);
+        assert!(xc.resolved_addrs(&endpoint).is_none());
+    }
+}
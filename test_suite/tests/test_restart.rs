@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        env,
+        net::TcpListener,
+        os::unix::io::AsRawFd,
+        sync::{mpsc, Arc},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn start_slow() -> TestCluster {
+        TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_inherited_listeners() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let fd = unsafe { libc::dup(listener.as_raw_fd()) };
+
+        env::set_var(INHERITED_FDS, fd.to_string());
+        let inherited = inherited_listeners().unwrap();
+        assert_eq!(1, inherited.len());
+        assert_eq!(addr, inherited[0].local_addr().unwrap());
+        // the variable is consumed
+        assert!(env::var(INHERITED_FDS).is_err());
+        assert!(inherited_listeners().unwrap().is_empty());
+        assert!(Server::from_parent().is_err());
+
+        let fd = unsafe { libc::dup(listener.as_raw_fd()) };
+        env::set_var(INHERITED_FDS, fd.to_string());
+        let server = Server::from_parent().unwrap();
+        assert_eq!(addr.to_string(), server.addr);
+
+        env::set_var(INHERITED_FDS, "x");
+        assert_eq!(ErrorKind::Config, inherited_listeners().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_shutdown_drains() {
+        let cluster = start_slow();
+        let server = cluster.servers()[0].clone();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let call = thread::spawn(move || {
+            let args = ArithAddArgs { a: 2, b: 10 };
+            let reply: Option<Result<ArithAddReply>> =
+                xc.call("Mul", false, &HashMap::new(), &args);
+            reply.unwrap()
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        server.shutdown(Duration::from_secs(5)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(20, call.join().unwrap().unwrap().c);
+        assert!(server.in_flight_calls().is_empty());
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        let cluster = start_slow();
+        let server = cluster.servers()[0].clone();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let call = thread::spawn(move || {
+            let args = ArithAddArgs { a: 2, b: 10 };
+            let _: Option<Result<ArithAddReply>> = xc.call("Mul", false, &HashMap::new(), &args);
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let err = server.shutdown(Duration::from_millis(50)).unwrap_err();
+        assert_eq!(ErrorKind::Timeout, err.kind());
+        call.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_stops_accepting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = Arc::new(Server::new(listener.local_addr().unwrap().to_string(), 1));
+        let server_cloned = server.clone();
+        let (done, done_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let rt = server_cloned.start_with_listener(listener);
+            done.send(rt).unwrap();
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        // the accept loop returns without a connection to wake it up
+        server.shutdown(Duration::from_secs(1)).unwrap();
+        let rt = done_receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(rt.is_ok());
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(20, 1, Duration::from_secs(10));
+        // the minimum is allowed without calls
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+        assert_eq!(1, budget.shed_retries());
+
+        for _ in 0..10 {
+            budget.record_request();
+        }
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+        assert_eq!(2, budget.shed_retries());
+    }
+
+    #[test]
+    fn test_xclient_retry_budget() {
+        let cluster = start_cluster(1);
+
+        let mut servers = HashMap::new();
+        servers.insert(cluster.addrs()[0].clone(), String::new());
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failtry,
+            Box::new(selector),
+            Opt::default(),
+        );
+        let budget = Arc::new(RetryBudget::new(50, 0, Duration::from_secs(10)));
+        xc.set_retry_budget(budget.clone());
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        for _ in 0..4 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        }
+
+        // the calls are counted, half of them can be retried
+        assert!(budget.try_retry());
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+        assert_eq!(1, budget.shed_retries());
+    }
+}
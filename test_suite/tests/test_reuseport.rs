@@ -0,0 +1,125 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use futures::Future;
+    use std::{
+        collections::HashMap,
+        mem, thread,
+        time::{Duration, Instant},
+    };
+
+    use super::common::register_mul;
+
+    // replies the core the handler runs on.
+    fn slow_core(_: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(300));
+        ArithAddReply {
+            c: unsafe { libc::sched_getcpu() } as u64,
+        }
+    }
+
+    // the cores the calling thread may run on.
+    fn allowed_cores() -> Vec<usize> {
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            assert_eq!(
+                0,
+                libc::sched_getaffinity(0, mem::size_of_val(&set), &mut set)
+            );
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|&core| libc::CPU_ISSET(core, &set))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_reuseport() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8983".to_owned(), 0);
+        register_mul(&mut rpc_server);
+        thread::spawn(move || {
+            if let Err(err) = rpc_server.start_reuseport(2) {
+                println!("{}", err);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        // connections are spread over the acceptors
+        let metadata = HashMap::new();
+        for i in 0..8 {
+            let mut c = Client::new("127.0.0.1:8983");
+            c.start().unwrap();
+            let args = ArithAddArgs { a: i, b: 10 };
+            let reply: Option<Result<ArithAddReply>> =
+                c.call("Arith", "Mul", false, &metadata, &args);
+            assert_eq!(i * 10, reply.unwrap().unwrap().c);
+        }
+    }
+
+    #[test]
+    fn test_reuseport_pinned() {
+        let mut rpc_server = Server::new("127.0.0.1:8986".to_owned(), 0);
+        register_mul(&mut rpc_server);
+        thread::spawn(move || {
+            if let Err(err) = rpc_server.start_reuseport_pinned(0) {
+                println!("{}", err);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let metadata = HashMap::new();
+        for i in 0..8 {
+            let mut c = Client::new("127.0.0.1:8986");
+            c.start().unwrap();
+            let args = ArithAddArgs { a: i, b: 10 };
+            let reply: Option<Result<ArithAddReply>> =
+                c.call("Arith", "Mul", false, &metadata, &args);
+            assert_eq!(i * 10, reply.unwrap().unwrap().c);
+        }
+    }
+
+    #[test]
+    fn test_reuseport_stealing() {
+        let mut rpc_server = Server::new("127.0.0.1:8994".to_owned(), 2);
+        register_func!(
+            rpc_server,
+            "Arith",
+            "Mul",
+            slow_core,
+            "".to_owned(),
+            ArithAddArgs,
+            ArithAddReply
+        );
+        // the server may only run on the last core, like in a cpuset
+        let core = *allowed_cores().last().unwrap();
+        thread::spawn(move || {
+            unsafe {
+                let mut set: libc::cpu_set_t = mem::zeroed();
+                libc::CPU_SET(core, &mut set);
+                libc::sched_setaffinity(0, mem::size_of_val(&set), &set);
+            }
+            if let Err(err) = rpc_server.start_reuseport_pinned(2) {
+                println!("{}", err);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        // the requests of one connection are handled by the workers of both reactors, on
+        // the allowed core
+        let mut c = Client::new("127.0.0.1:8994");
+        c.start().unwrap();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let started = Instant::now();
+        let calls: Vec<_> = (0..4)
+            .map(|_| c.acall::<ArithAddReply>("Arith", "Mul", &HashMap::new(), &args))
+            .collect();
+        for call in calls {
+            assert_eq!(core as u64, call.wait().unwrap().unwrap().c);
+        }
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{
+        env, fs,
+        net::TcpListener,
+        os::unix::net::UnixDatagram,
+        process, thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_sd_notify() {
+        let path = env::temp_dir().join(format!("rpcx_test_notify_{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+        env::set_var("WATCHDOG_USEC", "200000");
+        assert_eq!(Some(Duration::from_millis(200)), watchdog_interval());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut rpc_server = Server::new(listener.local_addr().unwrap().to_string(), 0);
+        rpc_server.enable_sd_notify();
+        thread::spawn(move || {
+            let _ = rpc_server.start_with_listener(listener);
+        });
+
+        let mut buf = [0u8; 64];
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(b"READY=1", &buf[..n]);
+
+        // the watchdog is notified twice per interval
+        let start = Instant::now();
+        for _ in 0..2 {
+            let n = socket.recv(&mut buf).unwrap();
+            assert_eq!(b"WATCHDOG=1", &buf[..n]);
+        }
+        assert!(start.elapsed() < Duration::from_millis(400));
+
+        env::remove_var("NOTIFY_SOCKET");
+        env::remove_var("WATCHDOG_USEC");
+        fs::remove_file(&path).unwrap();
+        // it does nothing out of systemd
+        sd_notify("READY=1").unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn counted_mul(args: ArithAddArgs) -> ArithAddReply {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    fn call(xc: &mut XClient<RoundbinSelector>, metadata: &HashMap<String, String>, a: u64) -> u64 {
+        let args = ArithAddArgs { a, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, metadata, &args);
+        reply.unwrap().unwrap().c
+    }
+
+    #[test]
+    fn test_response_cache_plugin() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                counted_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+            let mut cache = ResponseCachePlugin::new(2);
+            cache.cache_method("Arith", "Mul", Duration::from_millis(300));
+            rpc_server.add_message_plugin(Box::new(cache));
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        assert_eq!(20, call(&mut xc, &metadata, 2));
+        assert_eq!(20, call(&mut xc, &metadata, 2));
+        assert_eq!(1, CALLS.load(Ordering::SeqCst));
+
+        // other args are not cached yet
+        assert_eq!(30, call(&mut xc, &metadata, 3));
+        assert_eq!(2, CALLS.load(Ordering::SeqCst));
+
+        // the cached reply expires
+        thread::sleep(Duration::from_millis(350));
+        assert_eq!(20, call(&mut xc, &metadata, 2));
+        assert_eq!(3, CALLS.load(Ordering::SeqCst));
+
+        // the oldest reply is evicted beyond the capacity
+        assert_eq!(40, call(&mut xc, &metadata, 4));
+        assert_eq!(4, CALLS.load(Ordering::SeqCst));
+        assert_eq!(20, call(&mut xc, &metadata, 2));
+        assert_eq!(4, CALLS.load(Ordering::SeqCst));
+        assert_eq!(30, call(&mut xc, &metadata, 3));
+        assert_eq!(5, CALLS.load(Ordering::SeqCst));
+
+        // the replies are cached by the metadata, such as the auth token
+        let mut auth = HashMap::new();
+        auth.insert(AUTH_KEY.to_owned(), "alice".to_owned());
+        assert_eq!(30, call(&mut xc, &auth, 3));
+        assert_eq!(6, CALLS.load(Ordering::SeqCst));
+        assert_eq!(30, call(&mut xc, &auth, 3));
+        assert_eq!(6, CALLS.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::register_mul;
+
+    #[test]
+    fn test_service_opt() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            let serialize_type: RpcxFn = |_, st| Ok(vec![st as u8]);
+            rpc_server.register_fn(
+                "Echo".to_owned(),
+                "SerializeType".to_owned(),
+                "".to_owned(),
+                serialize_type,
+            );
+            let sleep: RpcxFn = |_, _| {
+                thread::sleep(Duration::from_millis(300));
+                Ok(Vec::new())
+            };
+            rpc_server.register_fn("Slow".to_owned(), "Sleep".to_owned(), "".to_owned(), sleep);
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        let metadata = HashMap::new();
+
+        let echo = |xc: &mut XClient<RoundbinSelector>| -> u8 {
+            let reply: Option<Result<Bytes>> =
+                xc.call_service("Echo", "SerializeType", false, &metadata, &Bytes::new());
+            reply.unwrap().unwrap()[0]
+        };
+        assert_eq!(SerializeType::JSON as u8, echo(&mut xc));
+
+        let mut opt = Opt::default();
+        opt.serialize_type = SerializeType::MsgPack;
+        xc.set_service_opt("Echo", opt);
+        assert_eq!(
+            SerializeType::MsgPack,
+            xc.service_opt("Echo").serialize_type
+        );
+        assert_eq!(SerializeType::MsgPack as u8, echo(&mut xc));
+
+        // the timeout of a slow service doesn't apply to the others
+        let mut opt = Opt::default();
+        opt.read_timeout = Duration::from_millis(100);
+        opt.retry = 0;
+        xc.set_service_opt("Slow", opt);
+        let reply: Option<Result<Bytes>> =
+            xc.call_service("Slow", "Sleep", false, &metadata, &Bytes::new());
+        assert_eq!(ErrorKind::Timeout, reply.unwrap().unwrap_err().kind());
+
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        xc.remove_service_opt("Echo");
+        assert_eq!(SerializeType::JSON as u8, echo(&mut xc));
+    }
+
+    #[test]
+    fn test_service_config() {
+        let mut config = XClientConfig::default();
+        config.service_path = "Arith".to_owned();
+        config
+            .servers
+            .insert("tcp@127.0.0.1:1".to_owned(), String::new());
+        config.read_timeout_ms = 1000;
+        let mut sc = ServiceConfig::default();
+        sc.compress_type = Some(CompressType::Gzip);
+        sc.read_timeout_ms = Some(5000);
+        config.services.insert("Report".to_owned(), sc);
+
+        let mut xc = XClient::with_config(&config).unwrap();
+        let opt = xc.service_opt("Report");
+        assert_eq!(CompressType::Gzip, opt.compress_type);
+        assert_eq!(Duration::from_millis(5000), opt.read_timeout);
+        assert_eq!(xc.opt.serialize_type, opt.serialize_type);
+        assert_eq!(
+            Duration::from_millis(1000),
+            xc.service_opt("Arith").read_timeout
+        );
+
+        config.services.clear();
+        xc.reload(&config).unwrap();
+        assert_eq!(
+            CompressType::CompressNone,
+            xc.service_opt("Report").compress_type
+        );
+    }
+}
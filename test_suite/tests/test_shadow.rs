@@ -0,0 +1,61 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    static SHADOW_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn shadow_mul(_: ArithAddArgs) -> ArithAddReply {
+        SHADOW_CALLS.fetch_add(1, Ordering::SeqCst);
+        // the shadow server replies differently, which must not reach the callers
+        ArithAddReply { c: 0 }
+    }
+
+    #[test]
+    fn test_shadow_plugin() {
+        let shadow = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "Mul",
+                shadow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let shadow_addr = shadow.servers()[0].addr.clone();
+
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            let plugin = ShadowPlugin::new(&shadow_addr, 100.0).unwrap();
+            rpc_server.add_message_plugin(Box::new(plugin));
+        })
+        .unwrap();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        for _ in 0..3 {
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(20, reply.unwrap().unwrap().c);
+        }
+
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(3, SHADOW_CALLS.load(Ordering::SeqCst));
+
+        assert!(ShadowPlugin::new("not an address", 10.0).is_err());
+    }
+}
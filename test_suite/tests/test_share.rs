@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::HashMap;
+
+    // replies the port of the client connection if the other conventions are set, 0 if not.
+    fn remote_port(args: ArithAddArgs) -> ArithAddReply {
+        let ctx = Context::current();
+        let metadata = ctx.metadata();
+        let server_port = metadata.server_address().map(|addr| u64::from(addr.port()));
+        if metadata.auth_token() != Some("secret")
+            || metadata.group() != Some("blue")
+            || server_port != Some(args.a)
+        {
+            return ArithAddReply { c: 0 };
+        }
+        ArithAddReply {
+            c: metadata
+                .remote_conn_addr()
+                .map_or(0, |addr| u64::from(addr.port())),
+        }
+    }
+
+    #[test]
+    fn test_conn_metadata() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_func!(
+                rpc_server,
+                "Arith",
+                "RemotePort",
+                remote_port,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+        let port = server.addr.parse::<std::net::SocketAddr>().unwrap().port();
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+
+        let mut metadata = HashMap::new();
+        metadata.set_auth_token("secret");
+        metadata.set_group("blue");
+        let args = ArithAddArgs {
+            a: u64::from(port),
+            b: 0,
+        };
+        let reply: Option<Result<ArithAddReply>> = xc.call("RemotePort", false, &metadata, &args);
+        let remote_port = reply.unwrap().unwrap().c;
+        let conns = server.active_conns();
+        assert_eq!(1, conns.len());
+        assert_eq!(u64::from(conns[0].port()), remote_port);
+    }
+}
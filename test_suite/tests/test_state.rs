@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use mul_model::ArithAddArgs;
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn test_inactive_servers() {
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), "weight=10".to_owned());
+        servers.insert(
+            "tcp@127.0.0.1:8973".to_owned(),
+            "weight=10&state=inactive".to_owned(),
+        );
+        servers.insert("tcp@127.0.0.1:8974".to_owned(), "state=paused".to_owned());
+
+        let selectors: Vec<Box<dyn ClientSelector>> = vec![
+            Box::new(RandomSelector::new()),
+            Box::new(RoundbinSelector::new()),
+            Box::new(WeightedSelector::new()),
+            Box::new(ConsistentHashSelector::new()),
+        ];
+        for mut selector in selectors {
+            selector.update_server(&servers);
+            for a in 0..10 {
+                let args = ArithAddArgs { a, b: 10 };
+                assert_eq!("tcp@127.0.0.1:8972", selector.select("Arith", "Mul", &args));
+            }
+        }
+
+        // the server is active again
+        let selector = RoundbinSelector::new();
+        servers.insert("tcp@127.0.0.1:8973".to_owned(), "state=active".to_owned());
+        selector.update_server(&servers);
+        assert_eq!(2, selector.servers.read().unwrap().len());
+    }
+
+    #[test]
+    fn test_paused_servers_keep_connections() {
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), "".to_owned());
+        servers.insert("tcp@127.0.0.1:8973".to_owned(), "state=paused".to_owned());
+
+        let selector = RoundbinSelector::new();
+        selector.update_server(&servers);
+        assert_eq!(1, selector.servers.read().unwrap().len());
+        let mut candidates = selector.candidates().unwrap();
+        candidates.sort();
+        assert_eq!(vec!["tcp@127.0.0.1:8972", "tcp@127.0.0.1:8973"], candidates);
+    }
+
+    struct MetaRecorder {
+        metas: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RegisterPlugin for MetaRecorder {
+        fn register_fn(&mut self, _: &str, _: &str, meta: String, _: RpcxFn) -> Result<()> {
+            self.metas.lock().unwrap().push(meta);
+            Ok(())
+        }
+
+        fn update_meta(&mut self, _: &str, meta: String) -> Result<()> {
+            self.metas.lock().unwrap().push(meta);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pause_server() {
+        let metas = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new("127.0.0.1:0".to_owned(), 0);
+        rpc_server.add_register_plugin(Box::new(MetaRecorder {
+            metas: metas.clone(),
+        }));
+
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        rpc_server.register_fn(
+            "Echo".to_owned(),
+            "Echo".to_owned(),
+            "group=a".to_owned(),
+            echo,
+        );
+        rpc_server.pause().unwrap();
+        assert!(rpc_server.is_paused());
+        rpc_server.register_fn("Arith".to_owned(), "Mul".to_owned(), "".to_owned(), echo);
+        rpc_server.resume().unwrap();
+        assert!(!rpc_server.is_paused());
+
+        let mut metas: Vec<String> = metas.lock().unwrap().drain(..).collect();
+        // the services are resumed in any order
+        metas[3..].sort();
+        assert_eq!(
+            vec![
+                "group=a",
+                "group=a&state=paused",
+                "state=paused",
+                "group=a&state=active",
+                "state=active",
+            ],
+            metas
+        );
+    }
+}
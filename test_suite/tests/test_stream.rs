@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        io::{self, Cursor, Read, Write},
+        net::{SocketAddr, TcpListener},
+        os::unix::io::AsRawFd,
+        thread,
+    };
+
+    fn echo(_: &[u8], _: SerializeType, body: &mut dyn Read, out: &mut dyn Write) -> Result<()> {
+        io::copy(body, out)?;
+        Ok(())
+    }
+
+    fn reject(_: &[u8], _: SerializeType, _: &mut dyn Read, _: &mut dyn Write) -> Result<()> {
+        Err(Error::from("rejected"))
+    }
+
+    #[test]
+    fn test_stream() {
+        // setup server
+        let mut rpc_server = Server::new("127.0.0.1:8977".to_owned(), 0);
+        rpc_server.register_stream_fn("File".to_owned(), "Echo".to_owned(), echo);
+        rpc_server.register_stream_fn("File".to_owned(), "Reject".to_owned(), reject);
+
+        let addr = rpc_server.addr.parse::<SocketAddr>().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let raw_fd = listener.as_raw_fd();
+        let handler = thread::spawn(move || match rpc_server.start_with_listener(listener) {
+            Ok(()) => {}
+            Err(err) => println!("{}", err),
+        });
+
+        // setup client
+        let mut c = Client::new("127.0.0.1:8977");
+        c.start().unwrap();
+
+        let data: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let metadata = HashMap::new();
+        let args = bytes::BytesMut::new();
+        let mut out = Vec::new();
+        c.call_stream(
+            "File",
+            "Echo",
+            &metadata,
+            &args,
+            Box::new(Cursor::new(data.clone())),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(data, out);
+
+        let mut out = Vec::new();
+        let rt = c.call_stream(
+            "File",
+            "Reject",
+            &metadata,
+            &args,
+            Box::new(Cursor::new(data)),
+            &mut out,
+        );
+        assert_eq!("rejected", rt.unwrap_err().to_string());
+
+        // clean
+        drop(c);
+        unsafe {
+            libc::close(raw_fd);
+        }
+
+        let _ = handler.join();
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::collections::HashMap;
+
+    use super::common::register_mul;
+
+    fn dictionary() -> StreamCompression {
+        StreamCompression::default().with_dictionary("arith-v1", br#"{"A":1,"B":2,"C":3}"#.to_vec())
+    }
+
+    fn start_cluster(compression: Option<StreamCompression>) -> TestCluster {
+        TestCluster::start(1, |rpc_server| {
+            if let Some(compression) = &compression {
+                rpc_server.enable_stream_compression(compression.clone());
+            }
+            register_mul(rpc_server);
+        })
+        .unwrap()
+    }
+
+    fn call_mul(c: &Client, a: u64) -> u64 {
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        reply.unwrap().unwrap().c
+    }
+
+    #[test]
+    fn test_stream_compression() {
+        let cluster = start_cluster(Some(dictionary()));
+        let server = cluster.servers()[0].clone();
+
+        let mut c = Client::new(&server.addr);
+        c.set_stream_compression(dictionary());
+        c.start().unwrap();
+        for i in 0..100 {
+            assert_eq!(i * 10, call_mul(&c, i));
+        }
+        // the built-in services are served over the compressed stream as well
+        assert!(c.services().unwrap().contains(&"Arith.Mul".to_owned()));
+
+        // the stream is not compressed without the same dictionary
+        let mut c = Client::new(&server.addr);
+        c.set_stream_compression(StreamCompression::default());
+        c.start().unwrap();
+        assert_eq!(20, call_mul(&c, 2));
+
+        let mut c = Client::new(&server.addr);
+        c.start().unwrap();
+        assert_eq!(20, call_mul(&c, 2));
+    }
+
+    #[test]
+    fn test_stream_compression_disabled() {
+        let cluster = start_cluster(None);
+        let server = cluster.servers()[0].clone();
+
+        let mut c = Client::new(&server.addr);
+        c.set_stream_compression(dictionary());
+        c.start().unwrap();
+        assert_eq!(20, call_mul(&c, 2));
+    }
+}
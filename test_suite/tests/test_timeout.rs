@@ -0,0 +1,71 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::common::register_mul;
+
+    fn slow_mul(args: ArithAddArgs) -> ArithAddReply {
+        thread::sleep(Duration::from_millis(500));
+        ArithAddReply { c: args.a * args.b }
+    }
+
+    #[test]
+    fn test_call_timeout() {
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            register_func!(
+                rpc_server,
+                "Arith",
+                "SlowMul",
+                slow_mul,
+                "".to_owned(),
+                ArithAddArgs,
+                ArithAddReply
+            );
+        })
+        .unwrap();
+
+        let mut c = Client::new(&cluster.servers()[0].addr);
+        c.opt.read_timeout = Duration::from_millis(200);
+        c.start().unwrap();
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+
+        // the slow calls time out together
+        let start = Instant::now();
+        let calls: Vec<_> = (0..2)
+            .map(|_| c.acall::<ArithAddReply>("Arith", "SlowMul", &metadata, &args))
+            .collect();
+        for call in calls {
+            let err = call.wait().unwrap().unwrap_err();
+            assert_eq!(ErrorKind::Timeout, err.kind());
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(450), "{:?}", elapsed);
+
+        // the connection is kept once the server is done with the slow calls
+        thread::sleep(Duration::from_millis(400));
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        // a propagated deadline shortens the timeout
+        let mut metadata = HashMap::new();
+        set_deadline(&mut metadata, Duration::from_millis(50));
+        let start = Instant::now();
+        let reply: Option<Result<ArithAddReply>> =
+            c.call("Arith", "SlowMul", false, &metadata, &args);
+        assert_eq!(ErrorKind::Timeout, reply.unwrap().unwrap_err().kind());
+        assert!(start.elapsed() < Duration::from_millis(150));
+    }
+}
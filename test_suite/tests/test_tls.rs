@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        env, fs, process, thread,
+        time::{Duration, Instant},
+    };
+
+    fn echo(args: &[u8], _: SerializeType) -> Result<Vec<u8>> {
+        Ok(args.to_vec())
+    }
+
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    // connects the server whose certificate is issued to `server_name`.
+    fn client(addr: &str, server_name: &str) -> Result<Client> {
+        let mut tls = TlsConnector::new(&testdata("tls_ca.pem"))?;
+        tls.set_server_name(server_name);
+        tls.set_alpn_protocols(&["rpcx"]);
+        let mut c = Client::new(addr);
+        c.opt.serialize_type = SerializeType::SerializeNone;
+        c.opt.read_timeout = Duration::from_secs(5);
+        c.set_tls(tls);
+        c.start()?;
+        Ok(c)
+    }
+
+    fn call(c: &mut Client) -> Result<Bytes> {
+        let args = Bytes::from(&b"hello"[..]);
+        let reply: Option<Result<Bytes>> = c.call("Echo", "Say", false, &HashMap::new(), &args);
+        reply.unwrap()
+    }
+
+    #[test]
+    fn test_tls() {
+        // the served files are replaced to rotate the certificate
+        let dir = env::temp_dir().join(format!("rpcx_test_tls_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cert_file = dir.join("cert.pem");
+        let key_file = dir.join("key.pem");
+        fs::copy(testdata("tls_a.pem"), &cert_file).unwrap();
+        fs::copy(testdata("tls_a.key"), &key_file).unwrap();
+        let config = TlsConfig {
+            cert_file: cert_file.to_str().unwrap().to_owned(),
+            key_file: key_file.to_str().unwrap().to_owned(),
+            alpn_protocols: vec!["rpcx".to_owned()],
+            ..Default::default()
+        };
+
+        let cluster = TestCluster::start(1, |rpc_server| {
+            rpc_server.enable_tls(&config).unwrap();
+            rpc_server.register_fn("Echo".to_owned(), "Say".to_owned(), "".to_owned(), echo);
+        })
+        .unwrap();
+        let server = cluster.servers()[0].clone();
+        let addr = server.addr.clone();
+
+        let mut c = client(&addr, "a.rpcx.test").unwrap();
+        assert_eq!(&b"hello"[..], &call(&mut c).unwrap()[..]);
+
+        // the certificate is verified by the name, which an IP address is not
+        let err = client(&addr, "b.rpcx.test").unwrap_err();
+        assert_eq!(ErrorKind::Network, err.kind());
+        let mut unnamed = Client::new(&addr);
+        unnamed.set_tls(TlsConnector::new(&testdata("tls_ca.pem")).unwrap());
+        assert_eq!(ErrorKind::Config, unnamed.start().unwrap_err().kind());
+
+        // the connections without TLS are closed
+        let mut plain = Client::new(&addr);
+        plain.opt.serialize_type = SerializeType::SerializeNone;
+        plain.opt.read_timeout = Duration::from_secs(1);
+        plain.start().unwrap();
+        assert!(call(&mut plain).is_err());
+
+        // the rotated certificate serves the new connections while the others go on
+        fs::copy(testdata("tls_b.pem"), &cert_file).unwrap();
+        fs::copy(testdata("tls_b.key"), &key_file).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut rotated = loop {
+            match client(&addr, "b.rpcx.test") {
+                Ok(rotated) => break rotated,
+                Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(100)),
+                Err(err) => panic!("the certificate is not reloaded: {}", err),
+            }
+        };
+        assert_eq!(&b"hello"[..], &call(&mut rotated).unwrap()[..]);
+        assert_eq!(&b"hello"[..], &call(&mut c).unwrap()[..]);
+
+        // TLS can't be changed by reloading
+        let mut server_config = ServerConfig::default();
+        assert_eq!(
+            ErrorKind::Config,
+            server.reload(&server_config).unwrap_err().kind()
+        );
+        server_config.tls = Some(config.clone());
+        server.reload(&server_config).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
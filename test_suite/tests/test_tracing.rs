@@ -0,0 +1,86 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::{testing::TestCluster, *};
+
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc::{self, Receiver},
+        thread,
+        time::Duration,
+    };
+
+    use super::common::register_mul;
+
+    // starts a collector which sends the bodies of the requests it receives.
+    fn start_collector() -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/api/v2/spans", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if line.starts_with("content-length:") {
+                        len = line["content-length:".len()..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; len];
+                reader.read_exact(&mut body).unwrap();
+                let _ = sender.send(String::from_utf8(body).unwrap());
+                stream
+                    .write_all(b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn test_tracing() {
+        let (endpoint, spans) = start_collector();
+        let cluster = TestCluster::start(1, |rpc_server| {
+            register_mul(rpc_server);
+            rpc_server.enable_tracing(&endpoint, "arith").unwrap();
+        })
+        .unwrap();
+        let mut c = Client::new(&cluster.servers()[0].addr);
+        c.start().unwrap();
+
+        // the trace of the caller is continued
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            TRACE_ID.to_owned(),
+            "463ac35c9f6413ad48485a3953bb6124".to_owned(),
+        );
+        metadata.insert(SPAN_ID.to_owned(), "a2fb4a1d1a96d312".to_owned());
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = c.call("Arith", "Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+
+        let body = spans.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(body.contains(r#""traceId":"463ac35c9f6413ad48485a3953bb6124""#));
+        assert!(body.contains(r#""parentId":"a2fb4a1d1a96d312""#));
+        assert!(body.contains(r#""name":"Arith.Mul""#));
+        assert!(body.contains(r#""serviceName":"arith""#));
+        assert!(body.contains(r#""kind":"SERVER""#));
+    }
+
+    #[test]
+    fn test_tracing_endpoint() {
+        let err = TracingPlugin::new("not a url", "arith").unwrap_err();
+        assert_eq!(ErrorKind::Config, err.kind());
+    }
+}
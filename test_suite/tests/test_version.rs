@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    struct MetaRecorder {
+        metas: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RegisterPlugin for MetaRecorder {
+        fn register_fn(&mut self, _: &str, _: &str, meta: String, _: RpcxFn) -> Result<()> {
+            self.metas.lock().unwrap().push(meta);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_version_selector() {
+        let mut servers = HashMap::new();
+        servers.insert("tcp@127.0.0.1:8972".to_owned(), "version=1.1".to_owned());
+        servers.insert(
+            "tcp@127.0.0.1:8973".to_owned(),
+            "weight=10&version=1.2.3".to_owned(),
+        );
+        servers.insert("tcp@127.0.0.1:8974".to_owned(), "version=2.0.0".to_owned());
+        servers.insert("tcp@127.0.0.1:8975".to_owned(), "weight=10".to_owned());
+
+        let mut selector = VersionSelector::new(RoundbinSelector::new(), ">=1.2, <2").unwrap();
+        selector.update_server(&servers);
+        let args = ArithArgs;
+        for _ in 0..4 {
+            assert_eq!("tcp@127.0.0.1:8973", selector.select("Arith", "Mul", &args));
+        }
+
+        let err = VersionSelector::new(RoundbinSelector::new(), "one.two")
+            .err()
+            .unwrap();
+        assert_eq!(ErrorKind::Config, err.kind());
+    }
+
+    #[derive(Debug, Default)]
+    struct ArithArgs;
+
+    impl RpcxParam for ArithArgs {
+        fn into_bytes(&self, _: SerializeType) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+        fn from_slice(&mut self, _: SerializeType, _: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_server_version() {
+        let metas = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new("127.0.0.1:0".to_owned(), 0);
+        rpc_server.add_register_plugin(Box::new(MetaRecorder {
+            metas: metas.clone(),
+        }));
+        rpc_server.set_version("1.2.0");
+
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        rpc_server.register_fn("Echo".to_owned(), "Echo".to_owned(), "".to_owned(), echo);
+        // the methods of a service share its metadata, so the weight is of another one
+        rpc_server.register_fn(
+            "Echo2".to_owned(),
+            "Echo".to_owned(),
+            "weight=10".to_owned(),
+            echo,
+        );
+        assert_eq!(
+            vec!["version=1.2.0", "weight=10&version=1.2.0"],
+            *metas.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,67 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use mul_model::{ArithAddArgs, ArithAddReply};
+    use rpcx::*;
+
+    use std::{collections::HashMap, thread, time::Duration};
+
+    use super::common::start_cluster;
+
+    #[test]
+    fn test_warm_up() {
+        let cluster = start_cluster(3);
+        let mut xc = cluster.xclient("Arith", FailMode::Failfast);
+        xc.enable_warm_up();
+        thread::sleep(Duration::from_millis(500));
+
+        // every server is connected before any call
+        for server in cluster.servers() {
+            assert_eq!(1, server.active_conns().len());
+        }
+
+        // the calls use the connections of the warm-up
+        let metadata = HashMap::new();
+        for a in 0..6 {
+            let args = ArithAddArgs { a, b: 10 };
+            let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+            assert_eq!(a * 10, reply.unwrap().unwrap().c);
+        }
+        for server in cluster.servers() {
+            assert_eq!(1, server.active_conns().len());
+        }
+    }
+
+    #[test]
+    fn test_warm_up_new_servers() {
+        let cluster = start_cluster(1);
+        let other = start_cluster(1);
+
+        let selector = SharedSelector::new(Box::new(RoundbinSelector::new()));
+        let mut servers = HashMap::new();
+        servers.insert(cluster.addrs()[0].clone(), String::new());
+        selector.update_server(&servers);
+        let mut xc = XClient::new(
+            "Arith".to_owned(),
+            FailMode::Failfast,
+            Box::new(selector.clone()),
+            Opt::default(),
+        );
+        xc.enable_warm_up();
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(1, cluster.servers()[0].active_conns().len());
+        assert!(other.servers()[0].active_conns().is_empty());
+
+        // the new server is connected at the first call after the interval
+        servers.insert(other.addrs()[0].clone(), String::new());
+        selector.update_server(&servers);
+        thread::sleep(WARM_UP_INTERVAL);
+        let metadata = HashMap::new();
+        let args = ArithAddArgs { a: 2, b: 10 };
+        let reply: Option<Result<ArithAddReply>> = xc.call("Mul", false, &metadata, &args);
+        assert_eq!(20, reply.unwrap().unwrap().c);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(1, other.servers()[0].active_conns().len());
+    }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpStream,
+    };
+
+    use super::common::start_cluster;
+
+    // a frame of the client, which browsers mask.
+    fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        frame
+    }
+
+    // reads a frame of the server and returns its opcode and its payload.
+    fn read_frame<R: Read>(r: &mut R) -> (u8, Vec<u8>) {
+        let mut head = [0u8; 2];
+        r.read_exact(&mut head).unwrap();
+        assert_eq!(0, head[1] & 0x80, "the frames of servers are not masked");
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                r.read_exact(&mut len).unwrap();
+                u16::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut payload = vec![0u8; len];
+        r.read_exact(&mut payload).unwrap();
+        (head[0] & 0x0f, payload)
+    }
+
+    #[test]
+    fn test_websocket() {
+        let cluster = start_cluster(1);
+
+        let mut req = Message::new();
+        req.set_message_type(MessageType::Request);
+        req.set_serialize_type(SerializeType::JSON);
+        req.set_seq(7);
+        req.service_path = "Arith".to_owned();
+        req.service_method = "Mul".to_owned();
+        req.payload = br#"{"A":3,"B":7}"#.to_vec().into();
+        let req = req.encode();
+
+        // the first frame of the message is sent with the handshake
+        let mut stream = TcpStream::connect(&cluster.servers()[0].addr).unwrap();
+        let mut out = format!(
+            "GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: rpcx\r\n\r\n",
+            WEBSOCKET_PATH
+        )
+        .into_bytes();
+        out.extend(client_frame(0x2, &req[..10]));
+        stream.write_all(&out).unwrap();
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+        }
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols"));
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(head.contains("Sec-WebSocket-Protocol: rpcx\r\n"));
+
+        // the rest of the message spans a frame, pings are answered
+        stream.write_all(&client_frame(0x2, &req[10..])).unwrap();
+        stream.write_all(&client_frame(0x9, b"ping")).unwrap();
+        let mut data = Vec::new();
+        let mut ponged = false;
+        while !ponged || Frame::frame_len(&data) != Ok(data.len()) {
+            match read_frame(&mut reader) {
+                (0xa, payload) => {
+                    assert_eq!(b"ping".to_vec(), payload);
+                    ponged = true;
+                }
+                (0x2, payload) => data.extend(payload),
+                (opcode, _) => panic!("unexpected opcode {}", opcode),
+            }
+        }
+        let mut reply = Message::new();
+        reply.decode(&mut &data[..]).unwrap();
+        assert_eq!(7, reply.get_seq());
+        assert_eq!(None, reply.get_error());
+        assert_eq!(&br#"{"C":21}"#[..], &reply.payload[..]);
+
+        // the close is echoed
+        stream.write_all(&client_frame(0x8, &[0x03, 0xe8])).unwrap();
+        assert_eq!((0x8, vec![0x03, 0xe8]), read_frame(&mut reader));
+    }
+}
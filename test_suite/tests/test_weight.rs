@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use rpcx::*;
+
+    use std::sync::{Arc, Mutex};
+
+    struct MetaRecorder {
+        metas: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl RegisterPlugin for MetaRecorder {
+        fn register_fn(
+            &mut self,
+            service_path: &str,
+            _: &str,
+            meta: String,
+            _: RpcxFn,
+        ) -> Result<()> {
+            self.metas
+                .lock()
+                .unwrap()
+                .push((service_path.to_owned(), meta));
+            Ok(())
+        }
+
+        fn update_meta(&mut self, service_path: &str, meta: String) -> Result<()> {
+            self.metas
+                .lock()
+                .unwrap()
+                .push((service_path.to_owned(), meta));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_set_weight() {
+        let metas = Arc::new(Mutex::new(Vec::new()));
+        let mut rpc_server = Server::new("127.0.0.1:0".to_owned(), 0);
+        rpc_server.add_register_plugin(Box::new(MetaRecorder {
+            metas: metas.clone(),
+        }));
+
+        let echo: RpcxFn = |x, _| Ok(x.to_vec());
+        rpc_server.register_fn(
+            "Echo".to_owned(),
+            "Echo".to_owned(),
+            "group=a&weight=10".to_owned(),
+            echo,
+        );
+
+        rpc_server.set_weight(0).unwrap();
+        rpc_server.set_weight(5).unwrap();
+        rpc_server.register_fn("Arith".to_owned(), "Mul".to_owned(), "".to_owned(), echo);
+
+        let metas: Vec<(String, String)> = metas.lock().unwrap().drain(..).collect();
+        assert_eq!(
+            vec![
+                ("Echo".to_owned(), "group=a&weight=10".to_owned()),
+                ("Echo".to_owned(), "group=a&weight=0".to_owned()),
+                ("Echo".to_owned(), "group=a&weight=5".to_owned()),
+                ("Arith".to_owned(), "weight=5".to_owned()),
+            ],
+            metas
+        );
+    }
+}
@@ -1,3 +1,5 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     use mul_model::{ArithAddArgs, ArithAddReply};
@@ -10,14 +12,12 @@ mod tests {
         thread,
     };
 
+    use super::common::mul;
+
     fn add(args: ArithAddArgs) -> ArithAddReply {
         ArithAddReply { c: args.a + args.b }
     }
 
-    fn mul(args: ArithAddArgs) -> ArithAddReply {
-        ArithAddReply { c: args.a * args.b }
-    }
-
     #[test]
     fn test_xclient_and_server() {
         // setup server